@@ -0,0 +1,118 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// The format of decoded messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Format {
+    /// One JSON object per line
+    #[default]
+    Json,
+    /// Comma separated values, with a header line
+    Csv,
+    /// One JSON object per line, with the field names and bit numbering of
+    /// ICAO Doc 9871 (for validation against the standard)
+    Doc9871,
+    /// A CZML document with the trajectories, for 3D replay in CesiumJS
+    /// (with an input file)
+    Czml,
+    /// SBS-1 (BaseStation) lines, as on port 30003 of dump1090
+    Sbs,
+    /// The last state vector of each aircraft, one JSON object per line
+    /// (with an input file)
+    States,
+}
+
+/// The columns of the CSV format, unless specified
+pub const DEFAULT_COLUMNS: &str = "timestamp,frame,df,icao24,bds,callsign,\
+    latitude,longitude,altitude,groundspeed,track,vertical_rate,squawk";
+
+/// The column collecting all the fields not selected, as a JSON object
+pub const EXTRA: &str = "extra";
+
+/// The header line of the CSV format
+pub fn header(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|column| quote(column))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/**
+ * A line of the CSV format, with the fields of the (flattened) JSON
+ * serialization of a message.
+ *
+ * Missing fields are left empty; lists and objects (e.g. the metadata) are
+ * written as JSON strings.
+ */
+pub fn row<T: Serialize>(
+    msg: &T,
+    columns: &[String],
+) -> serde_json::Result<String> {
+    let Value::Object(mut fields) = serde_json::to_value(msg)? else {
+        return Ok(String::new());
+    };
+    let mut values: Vec<String> = Vec::with_capacity(columns.len());
+    let mut extra = None;
+    for (i, column) in columns.iter().enumerate() {
+        match fields.remove(column) {
+            Some(value) => values.push(quote(&text(value)?).into_owned()),
+            None if column == EXTRA => extra = Some(i),
+            None => values.push(String::new()),
+        }
+    }
+    // Other fields are only known once the selected ones are removed
+    if let Some(i) = extra {
+        let others = match fields.is_empty() {
+            true => String::new(),
+            false => serde_json::to_string(&Value::Object(fields))?,
+        };
+        values.insert(i, quote(&others).into_owned());
+    }
+    Ok(values.join(","))
+}
+
+fn text(value: Value) -> serde_json::Result<String> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::String(s) => Ok(s),
+        Value::Array(_) | Value::Object(_) => serde_json::to_string(&value),
+        other => Ok(other.to_string()),
+    }
+}
+
+/// Quote a field if it contains separators, quotes or line breaks
+fn quote(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rs1090::prelude::*;
+
+    #[test]
+    fn test_csv() {
+        let columns: Vec<String> = ["icao24", "callsign", "extra", "altitude"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(header(&columns), "icao24,callsign,extra,altitude");
+
+        let bytes = hex::decode("8d4840d6202cc371c32ce0576098").unwrap();
+        let msg = Message::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            row(&msg, &columns).unwrap(),
+            r#"4840d6,KLM1023,"{""bds"":""08"",""df"":""17"",""id"":4,""wake_vortex"":""n/a""}","#
+        );
+
+        assert_eq!(quote("a,b"), "\"a,b\"");
+        assert_eq!(quote("a\"b"), "\"a\"\"b\"");
+        assert_eq!(quote("ab"), "ab");
+    }
+}
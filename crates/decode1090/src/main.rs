@@ -1,30 +1,49 @@
 #![doc = include_str!("../readme.md")]
 
-use clap::Parser;
-use rs1090::decode::cpr::{decode_position, AircraftState, Position, UpdateIf};
-use rs1090::decode::SensorMetadata;
+mod csv;
+
+use clap::{Parser, Subcommand};
+use rayon::prelude::*;
+use rs1090::archive;
+use rs1090::czml::Czml;
+use rs1090::decode::cpr::{
+    decode_position, decode_positions, decode_tisb_position, AircraftState,
+    Position, UpdateIf,
+};
+use rs1090::decode::doc9871::{self, Audit};
+use rs1090::decode::units::{Precision, Units};
+use rs1090::decode::{serialize_config, SensorMetadata};
+use rs1090::integrity::{HashChain, IntegrityMode, SignedRecord};
 use rs1090::prelude::*;
-use serde::{Deserialize, Serialize};
+use rs1090::tracker::StateVectorTracker;
+use serde::Serialize;
 use std::cmp::Reverse;
 use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::time::Duration;
 use tokio::fs::{self, File};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 
 #[derive(Debug, Parser)]
 #[command(
     name = "decode1090",
     version,
     author = "xoolive",
-    about = "Decode Mode S demodulated raw messages to JSON format"
+    about = "Decode Mode S demodulated raw messages to JSON format",
+    args_conflicts_with_subcommands = true
 )]
 struct Options {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Input file instead of individual messages (jsonl format)
     #[arg(long, short, default_value= None)]
     input: Option<String>,
 
     /// Reference coordinates for the decoding
     ///  (e.g. --reference LFPG for major airports,
-    ///   --reference 43.3,1.35 or --reference ' -34,18.6' if negative)
+    ///   --reference 43.3,1.35 or --reference ' -34,18.6' if negative,
+    ///   --reference 31UDQ4825111932 (MGRS)
+    ///   or --reference '31U 448251 5411932' (UTM))
     #[arg(long, short, default_value=None)]
     reference: Option<Position>,
 
@@ -32,32 +51,86 @@ struct Options {
     #[arg(long, short, default_value=None)]
     output: Option<String>,
 
+    /// Output format: json (one object per line), csv, doc9871, czml, sbs
+    /// or states
+    #[arg(long, value_enum, default_value = "json")]
+    format: csv::Format,
+
+    /// Columns of the csv format (comma separated), `extra` collects all the
+    /// other fields as a JSON object
+    #[arg(long, value_delimiter = ',', default_value = csv::DEFAULT_COLUMNS)]
+    columns: Vec<String>,
+
     /// Deduplication threshold (in ms)
     #[arg(long, short, default_value = "400")]
-    deduplication: u128,
+    deduplication: u64,
+
+    /// Decode the positions of messages received before the even/odd pair
+    /// resolves (requires to keep all the messages in memory). Positions are
+    /// then decoded in parallel for each aircraft if a reference is set.
+    #[arg(long, default_value = "false")]
+    backfill: bool,
+
+    /// Remove physically impossible values (e.g. altitudes above 60,000 ft
+    /// in ADS-B positions) from decoded messages, with a warning instead
+    #[arg(long, default_value = "false")]
+    strict: bool,
+
+    /// Decode military extended squitters (DF19) with AF=0 as ADS-B
+    /// messages, tagged with `military: true`
+    #[arg(long, default_value = "false")]
+    military: bool,
+
+    /// Units of decoded values: implicit, explicit (with the unit in the
+    /// field names, e.g. altitude_ft) or si (converted to SI units, e.g.
+    /// altitude_m)
+    #[arg(long, default_value = "implicit")]
+    units: Units,
+
+    /// Decimal places of positions, altitudes and speeds, e.g.
+    /// position=5,altitude=0,speed=1 (default: all)
+    #[arg(long, value_name = "PRECISION")]
+    precision: Option<Precision>,
+
+    /// Only decode the messages of the input file received after this
+    /// timestamp (in s)
+    #[arg(long, value_name = "TIMESTAMP")]
+    since: Option<f64>,
+
+    /// Only decode the messages of the input file received before this
+    /// timestamp (in s)
+    #[arg(long, value_name = "TIMESTAMP")]
+    until: Option<f64>,
 
     /// Individual messages to decode
     msgs: Vec<String>,
 }
 
-// We create this struct because it is too troublesome to have Deserialize for
-// Message at this point.
-#[derive(Serialize, Deserialize)]
-struct JSONEntry {
-    timestamp: f64,
-    rssi: Option<f32>, // from older format
-    #[serde(
-        serialize_with = "rs1090::decode::as_hex",
-        deserialize_with = "rs1090::decode::from_hex"
-    )]
-    frame: Vec<u8>,
-    #[serde(skip_serializing_if = "Vec::is_empty", default)]
-    metadata: Vec<SensorMetadata>,
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Check the integrity hashes of a file written with jet1090 --integrity
+    Verify {
+        /// The file to check (jsonl format)
+        file: String,
+
+        /// How the hashes were computed (hash or chain)
+        #[arg(long, default_value = "chain")]
+        mode: IntegrityMode,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let options = Options::parse();
+    serialize_config(
+        false,
+        options.units,
+        options.precision.unwrap_or_default(),
+    );
+
+    if let Some(Command::Verify { file, mode }) = options.command {
+        return verify(&file, mode).await;
+    }
 
     let input_file = if let Some(input_path) = options.input {
         let file = fs::File::open(input_path).await?;
@@ -66,10 +139,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None
     };
 
-    let mut output_file = if let Some(output_path) = options.output {
+    let czml = options.format == csv::Format::Czml;
+    if czml && !options.msgs.is_empty() {
+        return Err("the czml format requires an input file".into());
+    }
+    if options.format == csv::Format::States && !options.msgs.is_empty() {
+        return Err("the states format requires an input file".into());
+    }
+
+    let output_file = if let Some(output_path) = options.output {
+        // A CZML document cannot be appended to another one
         Some(
             fs::OpenOptions::new()
-                .append(true)
+                .append(!czml)
+                .write(true)
+                .truncate(czml)
                 .create(true)
                 .open(output_path)
                 .await?,
@@ -77,6 +161,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     } else {
         None
     };
+    let mut output_file =
+        Output::new(output_file, options.format, options.columns).await?;
 
     let mut reference = options.reference;
     let mut aircraft: BTreeMap<ICAO, AircraftState> = BTreeMap::new();
@@ -88,36 +174,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         let raw_messages: Vec<&str> = content_str.split('\n').collect();
 
-        // Parse each segment as a JSON object
-        let json_objects: Vec<Result<JSONEntry, _>> = raw_messages
+        // Archives of all versions are converted to the current format
+        let json_objects: Vec<TimedMessage> = raw_messages
             .iter()
-            .map(|msg| serde_json::from_str(msg))
+            .filter_map(|msg| archive::parse_line(msg).ok().flatten())
+            .filter(|msg| {
+                let timestamp = msg.timestamp.as_secs_f64();
+                options.since.is_none_or(|since| timestamp >= since)
+                    && options.until.is_none_or(|until| timestamp <= until)
+            })
             .collect();
 
-        let mut cache: HashMap<Vec<u8>, Vec<JSONEntry>> = HashMap::new();
-        // Need to do timestamps in u128 because f64 is not comparable (Ord)
-        let mut expiration_heap: BinaryHeap<Reverse<(u128, Vec<u8>)>> =
+        let mut cache: HashMap<Vec<u8>, Vec<TimedMessage>> = HashMap::new();
+        let mut expiration_heap: BinaryHeap<Reverse<(Timestamp, Vec<u8>)>> =
             BinaryHeap::new();
+        let deduplication = Duration::from_millis(options.deduplication);
 
-        let update_reference = Some(Box::new(|pos: &AirbornePosition| {
-            pos.alt.is_some_and(|alt| alt < 1000)
-        })
-            as Box<dyn Fn(&AirbornePosition) -> bool>);
-
-        // Print the JSON objects
-        for mut json in json_objects.into_iter().flatten() {
-            // In case there is a rssi field (older version), create a source
-            if json.rssi.is_some() {
-                json.metadata.push(SensorMetadata {
-                    system_timestamp: json.timestamp,
-                    gnss_timestamp: None,
-                    nanoseconds: None,
-                    rssi: json.rssi,
-                    serial: 0,
-                    name: None,
-                })
-            }
-            let timestamp_ms = (json.timestamp * 1e3) as u128;
+        // Copies of the same message, in the order of their deduplication
+        let mut entries: Vec<Vec<TimedMessage>> = Vec::new();
+
+        for json in json_objects {
+            let timestamp = json.timestamp;
             let frame = json.frame.clone();
 
             // Push the JSON to the list of similar messages received
@@ -125,132 +202,356 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             // Push the expiration timestamp into the heap
             if cache[&frame].len() == 1 {
-                expiration_heap.push(Reverse((
-                    timestamp_ms + options.deduplication,
-                    frame.clone(),
-                )));
+                expiration_heap
+                    .push(Reverse((timestamp + deduplication, frame.clone())));
             }
 
             // Check and handle expired entries
             while let Some(Reverse((curtime, frame))) = expiration_heap.pop() {
-                if curtime > timestamp_ms {
+                if curtime > timestamp {
                     // If not expired, push it back and stop processing
                     expiration_heap.push(Reverse((curtime, frame)));
                     break;
                 }
 
-                // Otherwise clear the cache and process the deduplicated message
-                if let Some(entries) = cache.remove(&frame) {
-                    let _ = process_entries(
-                        entries,
-                        &mut aircraft,
-                        &mut reference,
-                        &update_reference,
-                        &mut output_file,
-                    )
-                    .await;
+                // Otherwise clear the cache and keep the deduplicated message
+                if let Some(copies) = cache.remove(&frame) {
+                    entries.push(copies);
                 }
             }
         }
         // Flush remaining entries after processing all lines
         while let Some(Reverse((_curtime, frame))) = expiration_heap.pop() {
-            if let Some(entries) = cache.remove(&frame) {
-                let _ = process_entries(
-                    entries,
+            if let Some(copies) = cache.remove(&frame) {
+                entries.push(copies);
+            }
+        }
+
+        let update_reference: UpdateIf =
+            Some(Box::new(|pos: &AirbornePosition| {
+                pos.alt.is_some_and(|alt| alt < 1000)
+            }));
+
+        if options.backfill {
+            // All the messages are decoded before their positions, in parallel
+            let mut decoded: Vec<TimedMessage> = entries
+                .into_par_iter()
+                .filter_map(|copies| {
+                    merge_entries(copies, options.strict, options.military)
+                })
+                .collect();
+            // Without a reference, it is learnt from aircraft at low altitude
+            let update_reference = match reference {
+                Some(_) => None,
+                None => update_reference,
+            };
+            decode_positions(&mut decoded, reference, &update_reference, true);
+            for msg in decoded {
+                output_file.write_timed(&msg).await?;
+            }
+        } else {
+            for copies in entries {
+                if let Some(msg) = process_entries(
+                    copies,
                     &mut aircraft,
                     &mut reference,
                     &update_reference,
-                    &mut output_file,
-                )
-                .await;
+                    options.strict,
+                    options.military,
+                ) {
+                    output_file.write_timed(&msg).await?;
+                }
             }
         }
+        output_file.finish().await?;
     }
 
     if !options.msgs.is_empty() {
         for msg in options.msgs {
             let bytes = hex::decode(&msg).unwrap();
-            let msg = Message::try_from(bytes.as_slice()).unwrap();
-            let json = serde_json::to_string(&msg).unwrap();
-            if let Some(file) = &mut output_file {
-                file.write_all(json.as_bytes()).await?;
-                file.write_all("\n".as_bytes()).await?;
-            } else {
-                println!("{}", json);
+            let mut msg = Message::try_from(bytes.as_slice()).unwrap();
+            if options.military {
+                msg.military();
+            }
+            if options.strict {
+                msg.strict();
             }
+            if options.format == csv::Format::Sbs {
+                // SBS lines are timestamped: messages are received now
+                let msg = TimedMessage {
+                    timestamp: Timestamp::now(),
+                    frame: bytes,
+                    message: Some(msg),
+                    metadata: vec![],
+                    decode_time: None,
+                };
+                output_file.write_timed(&msg).await?;
+                continue;
+            }
+            let audited = || Audited::new(None, &bytes, Some(&msg));
+            output_file.write(&msg, audited).await?;
         }
     }
 
     Ok(())
 }
 
-// Helper function to merge entries into a single output
-async fn process_entries(
-    mut entries: Vec<JSONEntry>,
-    aircraft: &mut BTreeMap<ICAO, AircraftState>,
-    reference: &mut Option<Position>,
-    update_reference: &UpdateIf,
-    mut output_file: &mut Option<File>,
-) -> Result<(), Box<dyn std::error::Error>> {
+/// Merge the copies of a message, and decode it (except for positions)
+fn merge_entries(
+    mut entries: Vec<TimedMessage>,
+    strict: bool,
+    military: bool,
+) -> Option<TimedMessage> {
     let merged_metadata: Vec<SensorMetadata> = entries
         .iter()
         .flat_map(|entry| entry.metadata.clone())
         .collect();
     let json = entries.first_mut().unwrap();
 
-    let message = if let Ok((_, msg)) = Message::from_bytes((&json.frame, 0)) {
-        Some(msg)
-    } else {
-        None
-    };
+    let (_, mut message) = Message::from_bytes((&json.frame, 0)).ok()?;
+    if military {
+        message.military();
+    }
+    match &mut message.df {
+        CommBAltitudeReply { bds, .. } => {
+            if let (Some(_), Some(_)) = (&bds.bds50, &bds.bds60) {
+                bds.bds50 = None;
+                bds.bds60 = None
+            }
+        }
+        CommBIdentityReply { bds, .. } => {
+            if let (Some(_), Some(_)) = (&bds.bds50, &bds.bds60) {
+                bds.bds50 = None;
+                bds.bds60 = None
+            }
+        }
+        _ => {}
+    }
+    if strict {
+        message.strict();
+    }
 
-    // If old fashioned file, include the data in a metadata entry
-    let mut msg = TimedMessage {
+    Some(TimedMessage {
         timestamp: json.timestamp,
-        frame: json.frame.clone(),
-        message,
+        frame: std::mem::take(&mut json.frame),
+        message: Some(message),
         metadata: merged_metadata,
         decode_time: None,
-    };
-    if let Some(message) = &mut msg.message {
-        match &mut message.df {
-            ExtendedSquitterADSB(adsb) => decode_position(
-                &mut adsb.message,
-                msg.timestamp,
-                &adsb.icao24,
-                aircraft,
-                reference,
-                update_reference,
-            ),
-            ExtendedSquitterTisB { cf, .. } => decode_position(
-                &mut cf.me,
-                msg.timestamp,
-                &cf.aa,
-                aircraft,
-                reference,
-                update_reference,
-            ),
-            CommBAltitudeReply { bds, .. } => {
-                if let (Some(_), Some(_)) = (&bds.bds50, &bds.bds60) {
-                    bds.bds50 = None;
-                    bds.bds60 = None
-                }
+    })
+}
+
+/// Merge the copies of a message, and decode it
+fn process_entries(
+    entries: Vec<TimedMessage>,
+    aircraft: &mut BTreeMap<ICAO, AircraftState>,
+    reference: &mut Option<Position>,
+    update_reference: &UpdateIf,
+    strict: bool,
+    military: bool,
+) -> Option<TimedMessage> {
+    let mut msg = merge_entries(entries, strict, military)?;
+    let timestamp = msg.timestamp.as_secs_f64();
+    match &mut msg.message.as_mut()?.df {
+        ExtendedSquitterADSB(adsb) => decode_position(
+            &mut adsb.message,
+            timestamp,
+            &adsb.icao24,
+            aircraft,
+            reference,
+            update_reference,
+        ),
+        ExtendedSquitterTisB { cf, .. } => decode_tisb_position(
+            cf,
+            timestamp,
+            aircraft,
+            reference,
+            update_reference,
+        ),
+        _ => {}
+    }
+    Some(msg)
+}
+
+/// A message described with the names of ICAO Doc 9871
+#[derive(Serialize)]
+struct Audited {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<Timestamp>,
+    frame: String,
+    #[serde(flatten)]
+    audit: Option<Audit>,
+}
+
+impl Audited {
+    fn new(
+        timestamp: Option<Timestamp>,
+        frame: &[u8],
+        message: Option<&Message>,
+    ) -> Self {
+        Audited {
+            timestamp,
+            frame: hex::encode(frame),
+            audit: message.map(|msg| doc9871::audit(frame, msg)),
+        }
+    }
+
+    fn timed(msg: &TimedMessage) -> Self {
+        Self::new(Some(msg.timestamp), &msg.frame, msg.message.as_ref())
+    }
+}
+
+/// Decoded messages written to a file (or stdout) in a given format
+struct Output {
+    file: Option<File>,
+    format: csv::Format,
+    columns: Vec<String>,
+    /// The CZML document, written when all the messages are decoded
+    czml: Option<Czml>,
+    /// The state vectors, written when all the messages are decoded
+    tracker: Option<StateVectorTracker>,
+}
+
+impl Output {
+    /// The CSV header is only written if the file is empty
+    async fn new(
+        file: Option<File>,
+        format: csv::Format,
+        columns: Vec<String>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let czml =
+            (format == csv::Format::Czml).then(|| Czml::new("decode1090"));
+        let tracker = (format == csv::Format::States)
+            .then(|| StateVectorTracker::new(None));
+        let mut output = Output {
+            file,
+            format,
+            columns,
+            czml,
+            tracker,
+        };
+        let empty = match &output.file {
+            Some(file) => file.metadata().await?.len() == 0,
+            None => true,
+        };
+        if format == csv::Format::Csv && empty {
+            let header = csv::header(&output.columns);
+            output.write_line(&header).await?;
+        }
+        Ok(output)
+    }
+
+    /// The audit view is only computed in the Doc 9871 format
+    async fn write<T: Serialize>(
+        &mut self,
+        msg: &T,
+        audited: impl FnOnce() -> Audited,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let line = match self.format {
+            csv::Format::Json => serde_json::to_string(msg)?,
+            csv::Format::Csv => csv::row(msg, &self.columns)?,
+            csv::Format::Doc9871 => serde_json::to_string(&audited())?,
+            // Only timed messages have an equivalent in these formats
+            csv::Format::Czml | csv::Format::Sbs | csv::Format::States => {
+                return Ok(())
             }
-            CommBIdentityReply { bds, .. } => {
-                if let (Some(_), Some(_)) = (&bds.bds50, &bds.bds60) {
-                    bds.bds50 = None;
-                    bds.bds60 = None
-                }
+        };
+        self.write_line(&line).await
+    }
+
+    /// Write a decoded message, or add it to the CZML document or the state
+    /// vectors
+    async fn write_timed(
+        &mut self,
+        msg: &TimedMessage,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(tracker) = &mut self.tracker {
+            tracker.update(msg);
+            return Ok(());
+        }
+        match &mut self.czml {
+            Some(czml) => {
+                czml.push(msg);
+                Ok(())
+            }
+            None if self.format == csv::Format::Sbs => match msg.to_sbs() {
+                Some(line) => self.write_line(&line).await,
+                None => Ok(()),
+            },
+            None => self.write(msg, || Audited::timed(msg)).await,
+        }
+    }
+
+    /// Write the CZML document or the state vectors, if any
+    async fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(tracker) = self.tracker.take() {
+            for state in tracker.iter() {
+                let line = serde_json::to_string(state)?;
+                self.write_line(&line).await?;
             }
-            _ => {}
         }
-        let json = serde_json::to_string(&msg).unwrap();
-        if let Some(file) = &mut output_file {
-            file.write_all(json.as_bytes()).await?;
+        match self.czml.take() {
+            Some(czml) => {
+                let document = serde_json::to_string(&czml.document())?;
+                self.write_line(&document).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn write_line(
+        &mut self,
+        line: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(file) = &mut self.file {
+            file.write_all(line.as_bytes()).await?;
             file.write_all("\n".as_bytes()).await?;
         } else {
-            println!("{}", json);
+            println!("{}", line);
         }
+        Ok(())
+    }
+}
+
+/// Records written without a hash carry a `record` field with their type
+fn is_unsigned(line: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(line)
+        .is_ok_and(|value| value.get("record").is_some())
+}
+
+/// Check the integrity hashes of all the records of a file
+async fn verify(
+    path: &str,
+    mode: IntegrityMode,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file = File::open(path).await?;
+    let mut lines = BufReader::new(file).lines();
+    let mut chain = HashChain::new(mode);
+    let (mut valid, mut invalid) = (0, 0);
+    let mut line_number = 0;
+
+    while let Some(line) = lines.next_line().await? {
+        line_number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<SignedRecord>(&line) {
+            Ok(record) if chain.verify(&record) => valid += 1,
+            Ok(_) => {
+                invalid += 1;
+                println!("line {}: hash mismatch", line_number);
+            }
+            // Other records (e.g. flight summaries) are not part of the chain
+            Err(_) if is_unsigned(&line) => {}
+            Err(e) => {
+                invalid += 1;
+                println!("line {}: {}", line_number, e);
+            }
+        }
+    }
+
+    println!("{} valid records, {} invalid records", valid, invalid);
+    if invalid > 0 {
+        return Err(format!("integrity check failed for {}", path).into());
     }
     Ok(())
 }
@@ -1,9 +1,17 @@
 use rusqlite::Connection;
+use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
 use std::fs::{self, File};
-use std::io::{copy, BufReader, Cursor};
+use std::io::{copy, BufReader};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
 use zip::read::ZipArchive;
 
+/// The default location of the aircraft database (BaseStation format)
+pub const DEFAULT_URL: &str =
+    "https://jetvision.de/resources/sqb_databases/basestation.zip";
+const ARCHIVE: &str = "basestation.zip";
+
 #[derive(Debug)]
 pub struct Aircraft {
     /// The ICAO 24-bit transponder address
@@ -17,67 +25,165 @@ pub struct Aircraft {
 type Result<T> =
     std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
-async fn download_file(url: &str, destination: &str) -> Result<()> {
-    let response = reqwest::get(url).await?.bytes().await?;
-    let mut file = File::create(destination)?;
-    let mut content = Cursor::new(response);
-    copy(&mut content, &mut file)?;
-    Ok(())
+/// Where to get the aircraft database from, and where to keep it
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// The cache directory, default to the user cache directory
+    pub cache_dir: Option<PathBuf>,
+    /// The URL (or local path) of the zipped database, default to
+    /// [`DEFAULT_URL`]
+    pub url: Option<String>,
+    /// The expected SHA-256 checksum of the zipped database
+    pub sha256: Option<String>,
+    /// Never download the database, only use the cached copy
+    pub offline: bool,
+}
+
+impl Settings {
+    pub fn cache_dir(&self) -> PathBuf {
+        self.cache_dir
+            .clone()
+            .unwrap_or_else(|| crate::config::cache_dir().unwrap_or_default())
+    }
+
+    fn archive(&self) -> PathBuf {
+        self.cache_dir().join(ARCHIVE)
+    }
+}
+
+/// The checksum recorded next to a downloaded file
+fn checksum_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
 }
 
-pub async fn aircraft() -> BTreeMap<String, Aircraft> {
-    let mut cache_path = dirs::cache_dir().unwrap_or_default();
-    cache_path.push("jet1090");
-    if !cache_path.exists() {
-        let msg =
-            format!("failed to create {:?}", cache_path.to_str().unwrap());
-        fs::create_dir_all(&cache_path).expect(&msg);
+fn sha256(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+pub async fn fetch(url: &str) -> Result<Vec<u8>> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        let response = reqwest::get(url).await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    } else {
+        // A local mirror, e.g. on air-gapped systems
+        let path = url.strip_prefix("file://").unwrap_or(url);
+        Ok(fs::read(path).map_err(|e| format!("{}: {}", path, e))?)
     }
+}
 
-    let zip_url =
-        "https://jetvision.de/resources/sqb_databases/basestation.zip";
-    let zip_file_path = "basestation.zip";
-    cache_path.push(zip_file_path);
-
-    // Check if the zip file exists
-    if !cache_path.exists() {
-        println!("Downloading basestation.zip...");
-        download_file(zip_url, cache_path.to_str().unwrap())
-            .await
-            .expect("Failed to download basestation.zip");
+/**
+ * Download the aircraft database and replace the cached copy.
+ *
+ * The cached copy is only replaced if the downloaded file matches the
+ * expected checksum (if any) and can be read. The checksum of the file is
+ * recorded next to it, so that a corrupted cache is detected.
+ */
+pub async fn refresh(settings: &Settings) -> Result<PathBuf> {
+    let url = settings.url.as_deref().unwrap_or(DEFAULT_URL);
+    let cache_dir = settings.cache_dir();
+    fs::create_dir_all(&cache_dir).map_err(|e| {
+        format!("failed to create {}: {}", cache_dir.display(), e)
+    })?;
+
+    info!("Downloading the aircraft database from {}", url);
+    let content = fetch(url).await?;
+    let checksum = sha256(&content);
+    if let Some(expected) = &settings.sha256 {
+        if !checksum.eq_ignore_ascii_case(expected) {
+            return Err(format!(
+                "checksum mismatch for {}: expected {}, got {}",
+                url, expected, checksum
+            )
+            .into());
+        }
     }
 
-    // Open the zip file
-    let file = File::open(&cache_path).unwrap();
+    let archive = settings.archive();
+    let partial = cache_dir.join(format!("{ARCHIVE}.part"));
+    fs::write(&partial, &content)?;
+    if let Err(e) = load(&partial) {
+        fs::remove_file(&partial)?;
+        return Err(format!("invalid aircraft database: {}", e).into());
+    }
+    fs::rename(&partial, &archive)?;
+    fs::write(checksum_path(&archive), &checksum)?;
+    info!("Aircraft database saved to {}", archive.display());
+    Ok(archive)
+}
+
+/// Check the cached database against its recorded checksum
+fn verify(archive: &Path) -> Result<()> {
+    // Databases cached by older versions come without any checksum
+    let Ok(expected) = fs::read_to_string(checksum_path(archive)) else {
+        return Ok(());
+    };
+    let checksum = sha256(&fs::read(archive)?);
+    if checksum != expected.trim() {
+        return Err(format!(
+            "{} is corrupted (checksum mismatch)",
+            archive.display()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/**
+ * Load the aircraft database, from the cache if possible.
+ *
+ * The database is downloaded if it is not in the cache yet, or if the
+ * cached copy is corrupted, unless in offline mode.
+ */
+pub async fn aircraft(
+    settings: &Settings,
+) -> Result<BTreeMap<String, Aircraft>> {
+    let archive = settings.archive();
+    let cached = match archive.exists() {
+        true => verify(&archive),
+        false => Err(format!("{} not found", archive.display()).into()),
+    };
+    match cached {
+        Ok(()) => {}
+        Err(e) if settings.offline => return Err(e),
+        Err(e) => {
+            if archive.exists() {
+                warn!("{}", e);
+            }
+            refresh(settings).await?;
+        }
+    }
+    load(&archive)
+}
+
+fn load(archive: &Path) -> Result<BTreeMap<String, Aircraft>> {
+    let file = File::open(archive)?;
     let reader = BufReader::new(file);
-    let mut archive = ZipArchive::new(reader).unwrap();
-    let mut sqlite_in_archive = archive.by_index(0).unwrap();
+    let mut archive_zip = ZipArchive::new(reader)?;
+    let mut sqlite_in_archive = archive_zip.by_index(0)?;
 
-    // Unzip the sqb file
-    let cache_dir = cache_path.parent().unwrap();
-    let mut sqlite_path = cache_dir.to_path_buf();
-    sqlite_path.push(sqlite_in_archive.mangled_name());
-    let mut sqlite_file = File::create(&sqlite_path).unwrap();
-    copy(&mut sqlite_in_archive, &mut sqlite_file).unwrap();
+    // Unzip the sqb file (the CRC is checked at the end of the copy)
+    let cache_dir = archive.parent().unwrap_or(Path::new("."));
+    let sqlite_path = cache_dir.join(sqlite_in_archive.mangled_name());
+    let mut sqlite_file = File::create(&sqlite_path)?;
+    copy(&mut sqlite_in_archive, &mut sqlite_file)?;
 
     // Read the SQLite file and establish a connection
-    let sqlite_connection = Connection::open(sqlite_path).unwrap();
+    let sqlite_connection = Connection::open(sqlite_path)?;
 
     let mut stmt = sqlite_connection
-        .prepare("SELECT ModeS, Registration, ICAOTypeCode FROM Aircraft")
-        .unwrap();
+        .prepare("SELECT ModeS, Registration, ICAOTypeCode FROM Aircraft")?;
 
     let mut aircraftdb = BTreeMap::new();
 
-    let rows = stmt
-        .query_map([], |row| {
-            Ok(Aircraft {
-                icao24: row.get(0).unwrap(),
-                registration: row.get(1).unwrap_or_default(),
-                typecode: row.get(2).unwrap_or_default(),
-            })
+    let rows = stmt.query_map([], |row| {
+        Ok(Aircraft {
+            icao24: row.get(0)?,
+            registration: row.get(1).unwrap_or_default(),
+            typecode: row.get(2).unwrap_or_default(),
         })
-        .unwrap();
+    })?;
 
     for entry in rows.flatten() {
         aircraftdb.insert(
@@ -90,5 +196,69 @@ pub async fn aircraft() -> BTreeMap<String, Aircraft> {
         );
     }
 
-    aircraftdb
+    Ok(aircraftdb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    /// A zipped database with a single aircraft
+    fn database(directory: &Path) -> PathBuf {
+        let sqb = directory.join("basestation.sqb");
+        let connection = Connection::open(&sqb).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE Aircraft \
+                (ModeS TEXT, Registration TEXT, ICAOTypeCode TEXT); \
+                INSERT INTO Aircraft VALUES ('39B415', 'F-HNAV', 'A320');",
+            )
+            .unwrap();
+        drop(connection);
+
+        let path = directory.join("mirror.zip");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file("basestation.sqb", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(&fs::read(&sqb).unwrap()).unwrap();
+        zip.finish().unwrap();
+        fs::remove_file(sqb).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_aircraftdb() {
+        let directory = std::env::temp_dir().join("jet1090_test_aircraftdb");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let mirror = database(&directory);
+        let mut settings = Settings {
+            cache_dir: Some(directory.join("cache")),
+            url: Some(mirror.to_str().unwrap().to_string()),
+            sha256: None,
+            offline: true,
+        };
+
+        // Nothing in the cache yet
+        assert!(aircraft(&settings).await.is_err());
+
+        settings.sha256 = Some("0".repeat(64));
+        assert!(refresh(&settings).await.is_err());
+        settings.sha256 = Some(sha256(&fs::read(&mirror).unwrap()));
+        refresh(&settings).await.unwrap();
+
+        let db = aircraft(&settings).await.unwrap();
+        assert_eq!(db["39b415"].registration.as_deref(), Some("F-HNAV"));
+        assert_eq!(db["39b415"].typecode.as_deref(), Some("A320"));
+
+        // A corrupted cache is detected, and downloaded again if possible
+        fs::write(settings.archive(), b"corrupted").unwrap();
+        assert!(aircraft(&settings).await.is_err());
+        settings.offline = false;
+        assert_eq!(aircraft(&settings).await.unwrap().len(), 1);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
 }
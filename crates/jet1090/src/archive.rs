@@ -0,0 +1,448 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use zstd::stream::write::Encoder;
+
+use crate::tasks;
+
+/// Interval between two flushes of the compressed data and of the index
+pub const FLUSH_PERIOD: Duration = Duration::from_secs(10);
+
+/// A message to archive, with the address of its aircraft if announced
+#[derive(Debug)]
+pub struct Entry {
+    pub line: String,
+    pub icao24: Option<String>,
+}
+
+/// The content of the index file of a day
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DayIndex {
+    /// Number of messages archived that day
+    pub messages: u64,
+    /// Number of messages archived by hour, e.g. "13"
+    pub hours: BTreeMap<String, u64>,
+    /// Addresses of all the aircraft seen that day
+    pub aircraft: BTreeSet<String>,
+}
+
+/// The path of the archive file of an hour
+pub fn hour_path(root: &Path, time: DateTime<Utc>) -> PathBuf {
+    root.join(time.format("%Y/%m/%d/%H.jsonl.zst").to_string())
+}
+
+/// The path of the index file of a day
+pub fn index_path(root: &Path, day: NaiveDate) -> PathBuf {
+    root.join(day.format("%Y/%m/%d/index.json").to_string())
+}
+
+/**
+ * Archive messages in a directory tree partitioned by time, one compressed
+ * file per hour, e.g. `2025/01/31/13.jsonl.zst`.
+ *
+ * Files follow UTC hours of the system clock, like the rotation of output
+ * files. Each day comes with an `index.json` file with the number of
+ * messages by hour and the addresses of the aircraft seen, so that the
+ * archive can be browsed without decompressing anything.
+ *
+ * Data is compressed with zstd and flushed every [`FLUSH_PERIOD`]: if
+ * jet1090 stops abruptly, the file of the current hour ends with a
+ * truncated frame, but everything flushed before is still readable. Files
+ * are appended with a new frame if the process restarts in the same hour.
+ */
+pub struct ArchiveWriter {
+    root: PathBuf,
+    /// The hour of the current file, in hours since the epoch
+    hour: i64,
+    path: PathBuf,
+    file: Option<File>,
+    encoder: Option<Encoder<'static, Vec<u8>>>,
+    day: Option<NaiveDate>,
+    index: DayIndex,
+}
+
+impl ArchiveWriter {
+    pub fn new(root: PathBuf) -> Self {
+        ArchiveWriter {
+            root,
+            hour: 0,
+            path: PathBuf::new(),
+            file: None,
+            encoder: None,
+            day: None,
+            index: DayIndex::default(),
+        }
+    }
+
+    async fn open(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        let day = now.date_naive();
+        if self.day != Some(day) {
+            // Counts are resumed if the process restarts in the same day
+            let path = index_path(&self.root, day);
+            self.index = match path.exists() {
+                true => load(&path).unwrap_or_else(|e| {
+                    warn!("Failed to read {}: {}", path.display(), e);
+                    DayIndex::default()
+                }),
+                false => DayIndex::default(),
+            };
+            self.day = Some(day);
+        }
+        self.hour = now.timestamp().div_euclid(3600);
+        self.path = hour_path(&self.root, now);
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let file = fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .await?;
+        self.file = Some(file);
+        self.encoder = Some(Encoder::new(Vec::new(), 0)?);
+        Ok(())
+    }
+
+    /// Write a message to the file of the current hour
+    pub async fn write(
+        &mut self,
+        entry: &Entry,
+        now: DateTime<Utc>,
+    ) -> io::Result<()> {
+        if self.file.is_some() && now.timestamp().div_euclid(3600) != self.hour
+        {
+            self.close().await?;
+        }
+        if self.file.is_none() {
+            self.open(now).await?;
+        }
+        if let Some(encoder) = &mut self.encoder {
+            encoder.write_all(entry.line.as_bytes())?;
+            encoder.write_all(b"\n")?;
+        }
+        let hour = now.format("%H").to_string();
+        self.index.messages += 1;
+        *self.index.hours.entry(hour).or_default() += 1;
+        if let Some(icao24) = &entry.icao24 {
+            self.index.aircraft.insert(icao24.clone());
+        }
+        Ok(())
+    }
+
+    /// Write the data compressed so far, and the index of the day
+    pub async fn flush(&mut self) -> io::Result<()> {
+        let (Some(file), Some(encoder)) = (&mut self.file, &mut self.encoder)
+        else {
+            return Ok(());
+        };
+        encoder.flush()?;
+        file.write_all(encoder.get_ref()).await?;
+        file.flush().await?;
+        encoder.get_mut().clear();
+        self.save().await
+    }
+
+    /// Complete the compressed frame, and close the file of the current hour
+    pub async fn close(&mut self) -> io::Result<()> {
+        let (Some(mut file), Some(encoder)) =
+            (self.file.take(), self.encoder.take())
+        else {
+            return Ok(());
+        };
+        file.write_all(&encoder.finish()?).await?;
+        file.flush().await?;
+        info!("Closing archive file {}", self.path.display());
+        self.save().await
+    }
+
+    /// Write the index in a temporary file first, so that it is never
+    /// truncated
+    async fn save(&self) -> io::Result<()> {
+        let Some(day) = self.day else {
+            return Ok(());
+        };
+        let path = index_path(&self.root, day);
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        fs::write(&partial, serde_json::to_string(&self.index)?).await?;
+        fs::rename(&partial, path).await
+    }
+
+    /// Start the task archiving the messages received on the returned channel
+    pub fn spawn(mut self) -> mpsc::Sender<Entry> {
+        let (tx, mut rx) = mpsc::channel::<Entry>(1024);
+        tasks::spawn("archive", async move {
+            let mut tick = tokio::time::interval(FLUSH_PERIOD);
+            loop {
+                let result = tokio::select! {
+                    entry = rx.recv() => match entry {
+                        Some(entry) => self.write(&entry, Utc::now()).await,
+                        None => break,
+                    },
+                    _ = tick.tick() => self.flush().await,
+                };
+                if let Err(e) = result {
+                    error!("Failed to write to {}: {}", self.path.display(), e);
+                }
+            }
+            if let Err(e) = self.close().await {
+                error!("Failed to close {}: {}", self.path.display(), e);
+            }
+        });
+        tx
+    }
+}
+
+fn load(path: &Path) -> io::Result<DayIndex> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// A range of hours, from a day (e.g. `2025-01-31`) or an hour (e.g.
+/// `2025-01-31T13`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Period {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (day, hour) = match s.split_once('T') {
+            Some((day, hour)) => (day, Some(hour)),
+            None => (s, None),
+        };
+        let day = NaiveDate::from_str(day)
+            .map_err(|_| format!("invalid day: {s} (e.g. 2025-01-31)"))?;
+        let at = |hour: u32| {
+            NaiveTime::from_hms_opt(hour, 0, 0).map(|time| day.and_time(time))
+        };
+        let period = match hour {
+            None => at(0).zip(at(23)),
+            Some(h) => h.parse().ok().and_then(at).map(|time| (time, time)),
+        };
+        match period {
+            Some((start, end)) => Ok(Period { start, end }),
+            None => Err(format!("invalid hour: {s} (e.g. 2025-01-31T13)")),
+        }
+    }
+}
+
+/// Browse the archive directory
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// List the archived days, or the hours of a day
+    Ls {
+        /// The archive directory (default: the one of the configuration file)
+        #[arg(long, value_name = "DIR")]
+        dir: Option<String>,
+
+        /// A day, e.g. 2025-01-31
+        day: Option<NaiveDate>,
+    },
+    /// Print the archived messages of a day or an hour, or of a range of them
+    Cat {
+        /// The archive directory (default: the one of the configuration file)
+        #[arg(long, value_name = "DIR")]
+        dir: Option<String>,
+
+        /// A day or an hour, e.g. 2025-01-31 or 2025-01-31T13
+        from: Period,
+
+        /// The last day or hour (included) of a range
+        to: Option<Period>,
+    },
+}
+
+/// The archived days, in chronological order
+pub fn days(root: &Path) -> io::Result<Vec<NaiveDate>> {
+    let mut days = Vec::new();
+    for year in subdirs(root)? {
+        for month in subdirs(&root.join(&year))? {
+            for day in subdirs(&root.join(&year).join(&month))? {
+                let date = format!("{year}-{month}-{day}");
+                if let Ok(date) = NaiveDate::from_str(&date) {
+                    days.push(date);
+                }
+            }
+        }
+    }
+    days.sort();
+    Ok(days)
+}
+
+/// The archive files of a day, by hour
+pub fn hours(root: &Path, day: NaiveDate) -> io::Result<Vec<(u32, PathBuf)>> {
+    let dir = root.join(day.format("%Y/%m/%d").to_string());
+    let mut hours = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(hour) = name.strip_suffix(".jsonl.zst") {
+            if let Ok(hour) = hour.parse() {
+                hours.push((hour, path));
+            }
+        }
+    }
+    hours.sort();
+    Ok(hours)
+}
+
+fn subdirs(path: &Path) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir()
+            && name.bytes().all(|b| b.is_ascii_digit())
+        {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/**
+ * Decompress an archive file.
+ *
+ * The file of an hour still being written, or of an interrupted run, ends
+ * with a truncated frame: the lines before are written, then a warning.
+ */
+pub fn read(path: &Path, out: &mut impl Write) -> io::Result<()> {
+    let file = std::fs::File::open(path)?;
+    let reader = BufReader::new(zstd::stream::read::Decoder::new(file)?);
+    for line in reader.lines() {
+        match line {
+            Ok(line) => writeln!(out, "{line}")?,
+            Err(e) => {
+                eprintln!("Truncated file {}: {}", path.display(), e);
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run a command of the `archive` subcommand
+pub fn run(command: Command, default: Option<String>) -> io::Result<()> {
+    let root = |dir: Option<String>| {
+        match dir.or(default) {
+        Some(dir) => Ok(crate::config::expanduser(PathBuf::from(dir))),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no archive directory, use --dir or set archive in the configuration file",
+        )),
+    }
+    };
+    let mut stdout = io::stdout().lock();
+    match command {
+        Command::Ls { dir, day: None } => {
+            let root = root(dir)?;
+            for day in days(&root)? {
+                let index = load(&index_path(&root, day)).unwrap_or_default();
+                writeln!(
+                    stdout,
+                    "{}  {:>10} messages  {:>6} aircraft",
+                    day,
+                    index.messages,
+                    index.aircraft.len()
+                )?;
+            }
+        }
+        Command::Ls {
+            dir,
+            day: Some(day),
+        } => {
+            let root = root(dir)?;
+            let index = load(&index_path(&root, day)).unwrap_or_default();
+            for (hour, path) in hours(&root, day)? {
+                let messages = index.hours.get(&format!("{hour:02}"));
+                writeln!(
+                    stdout,
+                    "{} {:02}:00  {:>10} messages  {:>12} bytes",
+                    day,
+                    hour,
+                    messages.copied().unwrap_or_default(),
+                    path.metadata()?.len()
+                )?;
+            }
+        }
+        Command::Cat { dir, from, to } => {
+            let root = root(dir)?;
+            let (start, end) = (from.start, to.unwrap_or(from).end);
+            for day in days(&root)? {
+                for (hour, path) in hours(&root, day)? {
+                    let time = NaiveTime::from_hms_opt(hour, 0, 0)
+                        .map(|time| day.and_time(time));
+                    if time.is_some_and(|time| start <= time && time <= end) {
+                        read(&path, &mut stdout)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_archive() {
+        let root = std::env::temp_dir().join("jet1090_test_archive");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let entry = |line: &str, icao24: Option<&str>| Entry {
+            line: line.to_string(),
+            icao24: icao24.map(str::to_string),
+        };
+        let t0 = Utc.with_ymd_and_hms(2025, 1, 31, 12, 59, 0).unwrap();
+        let t1 = Utc.with_ymd_and_hms(2025, 1, 31, 13, 0, 0).unwrap();
+
+        let mut writer = ArchiveWriter::new(root.clone());
+        writer.write(&entry("a", Some("40621d")), t0).await.unwrap();
+        writer.flush().await.unwrap();
+        writer.write(&entry("b", None), t0).await.unwrap();
+        writer.write(&entry("c", Some("40621d")), t1).await.unwrap();
+        writer.close().await.unwrap();
+
+        // A restart in the same hour appends a new frame to the file
+        let mut writer = ArchiveWriter::new(root.clone());
+        writer.write(&entry("d", Some("4ca84d")), t1).await.unwrap();
+        writer.close().await.unwrap();
+
+        let day = t0.date_naive();
+        assert_eq!(days(&root).unwrap(), vec![day]);
+        let index = load(&index_path(&root, day)).unwrap();
+        assert_eq!(index.messages, 4);
+        assert_eq!(index.hours["12"], 2);
+        assert_eq!(index.aircraft.len(), 2);
+
+        let hours = hours(&root, day).unwrap();
+        assert_eq!(hours.len(), 2);
+        assert_eq!(hours[1].1, hour_path(&root, t1));
+        let mut out = Vec::new();
+        read(&hours[1].1, &mut out).unwrap();
+        assert_eq!(out, b"c\nd\n");
+
+        let period: Period = "2025-01-31T13".parse().unwrap();
+        assert_eq!(period.start, t1.naive_utc());
+        assert_eq!(period.start, period.end);
+        assert!("2025-01-31T24".parse::<Period>().is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+
+use rs1090::decode::cpr::haversine;
+
+use crate::snapshot::{Snapshot, StateVectors};
+
+/// Maximum silence (in s) between the end of a segment and the next one
+const MAX_GAP: u64 = 60;
+/// Maximum distance (in km) between the predicted and the first position
+const MAX_DISTANCE: f64 = 5.;
+/// Maximum difference of barometric altitude (in ft)
+const MAX_ALTITUDE: i32 = 1000;
+/// Mean Earth radius, in km
+const RADIUS: f64 = 6371.;
+
+/**
+ * True if a new aircraft plausibly continues the trajectory of another one,
+ * which stopped transmitting before the new one appeared.
+ *
+ * The last position of the previous segment is extrapolated with its last
+ * ground speed and track (if known) to the time of the first position of
+ * the new one. Altitudes must match, and so must callsigns when both are
+ * known.
+ */
+pub fn continues(old: &Snapshot, new: &Snapshot) -> bool {
+    if old.lastseen > new.firstseen || new.lastseen > old.lastseen + MAX_GAP {
+        return false;
+    }
+    let (Some(lat0), Some(lon0), Some(lat1), Some(lon1)) = (
+        old.state.latitude,
+        old.state.longitude,
+        new.state.latitude,
+        new.state.longitude,
+    ) else {
+        return false;
+    };
+    if let (Some(a0), Some(a1)) = (old.state.altitude, new.state.altitude) {
+        if (a0 as i32 - a1 as i32).abs() > MAX_ALTITUDE {
+            return false;
+        }
+    }
+    if let (Some(c0), Some(c1)) = (&old.state.callsign, &new.state.callsign) {
+        let (c0, c1) = (c0.trim(), c1.trim());
+        if !c0.is_empty() && !c1.is_empty() && c0 != c1 {
+            return false;
+        }
+    }
+    let (lat0, lon0) = match (old.state.groundspeed, old.state.track) {
+        (Some(groundspeed), Some(track)) => {
+            let elapsed = (new.lastseen - old.lastseen) as f64;
+            let distance = groundspeed * 1.852 * elapsed / 3600.;
+            let (sin, cos) = track.to_radians().sin_cos();
+            let dlat = (distance * cos / RADIUS).to_degrees();
+            let dlon = (distance * sin / RADIUS / lat0.to_radians().cos())
+                .to_degrees();
+            (lat0 + dlat, lon0 + dlon)
+        }
+        _ => (lat0, lon0),
+    };
+    haversine(lat0, lon0, lat1, lon1) < MAX_DISTANCE
+}
+
+/**
+ * Stitch an aircraft, at its first decoded position, to the segment of
+ * another aircraft it continues (see [`continues`]), in order to follow
+ * airframes changing their ICAO 24-bit address in flight.
+ *
+ * This is a heuristic: the aircraft inherits the track identifier of the
+ * previous segment only if exactly one segment matches, and each segment
+ * is continued at most once. Otherwise, the track identifier is its own
+ * address.
+ */
+pub fn associate(states: &mut BTreeMap<String, StateVectors>, key: &str) {
+    let Some(new) = states.get(key) else {
+        return;
+    };
+    let candidates: Vec<_> = states
+        .iter()
+        .filter(|(other, sv)| *other != key && !sv.continued)
+        .filter(|(_, sv)| continues(&sv.cur, &new.cur))
+        .map(|(other, _)| other.clone())
+        .collect();
+    let previous = match candidates.as_slice() {
+        [previous] => states.get_mut(previous),
+        _ => None,
+    };
+    let track_id = match previous {
+        Some(previous) => {
+            previous.continued = true;
+            let track_id = previous.cur.track_id.clone();
+            Some((track_id, previous.cur.icao24.clone()))
+        }
+        None => None,
+    };
+    if let Some(new) = states.get_mut(key) {
+        match track_id {
+            Some((track_id, previous)) => {
+                new.cur.track_id = track_id.or(Some(previous.clone()));
+                new.cur.stitched_from = Some(previous);
+            }
+            None => new.cur.track_id = Some(new.cur.icao24.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enrich::Enricher;
+
+    fn aircraft(icao24: &str, time: u64, position: (f64, f64)) -> StateVectors {
+        let mut sv = StateVectors::new(
+            time,
+            icao24.to_string(),
+            &BTreeMap::new(),
+            &Enricher::default(),
+        );
+        sv.cur.state.latitude = Some(position.0);
+        sv.cur.state.longitude = Some(position.1);
+        sv.cur.state.altitude = Some(24000);
+        sv
+    }
+
+    #[test]
+    fn test_association() {
+        let mut states = BTreeMap::new();
+        // Heading north at 450 kts, i.e. about 7.5 km per minute
+        let mut old = aircraft("3a0001", 1_700_000_000, (43.6, 1.4));
+        old.cur.track_id = Some("3a0001".to_string());
+        old.cur.state.groundspeed = Some(450.);
+        old.cur.state.track = Some(0.);
+        old.cur.state.callsign = Some("CTM1234".to_string());
+        states.insert("3a0001".to_string(), old);
+        // Another aircraft far away
+        let other = aircraft("4ca84d", 1_700_000_000, (45.0, 1.4));
+        states.insert("4ca84d".to_string(), other);
+
+        // A new address 40 s later, 5 km further north
+        let new = aircraft("3b7777", 1_700_000_040, (43.645, 1.4));
+        assert!(continues(&states["3a0001"].cur, &new.cur));
+        states.insert("3b7777".to_string(), new);
+        associate(&mut states, "3b7777");
+        assert_eq!(states["3b7777"].cur.track_id.as_deref(), Some("3a0001"));
+        assert_eq!(
+            states["3b7777"].cur.stitched_from.as_deref(),
+            Some("3a0001")
+        );
+
+        // The first segment was already continued, the track goes on
+        let sv = states.get_mut("3b7777").unwrap();
+        sv.cur.state.groundspeed = Some(450.);
+        sv.cur.state.track = Some(0.);
+        let new = aircraft("3c8888", 1_700_000_060, (43.705, 1.4));
+        assert!(continues(&states["3a0001"].cur, &new.cur));
+        states.insert("3c8888".to_string(), new);
+        associate(&mut states, "3c8888");
+        assert_eq!(states["3c8888"].cur.track_id.as_deref(), Some("3a0001"));
+        assert_eq!(
+            states["3c8888"].cur.stitched_from.as_deref(),
+            Some("3b7777")
+        );
+
+        // A callsign is not needed on both sides, but altitudes must match
+        let old = &states["4ca84d"].cur;
+        let mut new = aircraft("3d9999", 1_700_000_030, (45.01, 1.4)).cur;
+        assert!(continues(old, &new));
+        new.state.callsign = Some("AFR1234".to_string());
+        assert!(continues(old, &new));
+        new.state.altitude = Some(30000);
+        assert!(!continues(old, &new));
+    }
+}
@@ -0,0 +1,105 @@
+use rs1090::decode::bds::bds08::WakeVortex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Surface positions needed to classify a target without category as vehicle
+const SURFACE_ONLY: u32 = 20;
+
+/**
+ * The class of a target: aircraft, or surface vehicle (including ground
+ * obstructions, in the emitter category set C)
+ */
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetClass {
+    #[default]
+    Aircraft,
+    Vehicle,
+}
+
+impl fmt::Display for TargetClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            TargetClass::Aircraft => "aircraft",
+            TargetClass::Vehicle => "vehicle",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/**
+ * Tell surface vehicles from aircraft.
+ *
+ * The emitter category (BDS 0,8) is used when available. Otherwise, targets
+ * unknown to the aircraft database which only ever send surface positions
+ * (BDS 0,6) are classified as vehicles.
+ */
+#[derive(Debug, Default)]
+pub struct Classifier {
+    /// Number of surface positions received
+    surface: u32,
+    /// Whether the target was ever seen airborne
+    airborne: bool,
+}
+
+impl Classifier {
+    /// Record whether a message was sent on the ground, or airborne
+    pub fn record(&mut self, on_ground: bool) {
+        match on_ground {
+            true => self.surface = self.surface.saturating_add(1),
+            false => self.airborne = true,
+        }
+    }
+
+    /// The class of the target, given its category and its typecode
+    pub fn class(
+        &self,
+        category: Option<WakeVortex>,
+        typecode: Option<&str>,
+    ) -> TargetClass {
+        use WakeVortex::*;
+        match category {
+            Some(EmergencyVehicle | ServiceVehicle | Obstruction) => {
+                TargetClass::Vehicle
+            }
+            Some(NoInformation | Reserved) | None
+                if typecode.is_none()
+                    && !self.airborne
+                    && self.surface >= SURFACE_ONLY =>
+            {
+                TargetClass::Vehicle
+            }
+            _ => TargetClass::Aircraft,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_class() {
+        let mut classifier = Classifier::default();
+        assert_eq!(classifier.class(None, None), TargetClass::Aircraft);
+        let vehicle = Some(WakeVortex::ServiceVehicle);
+        assert_eq!(classifier.class(vehicle, None), TargetClass::Vehicle);
+
+        for _ in 0..SURFACE_ONLY {
+            classifier.record(true);
+        }
+        assert_eq!(classifier.class(None, None), TargetClass::Vehicle);
+        // Aircraft parked on the ground
+        let medium = Some(WakeVortex::Medium2);
+        assert_eq!(classifier.class(medium, None), TargetClass::Aircraft);
+        assert_eq!(classifier.class(None, Some("A320")), TargetClass::Aircraft);
+
+        classifier.record(false);
+        assert_eq!(classifier.class(None, None), TargetClass::Aircraft);
+
+        let class: TargetClass = serde_json::from_str("\"vehicle\"").unwrap();
+        assert_eq!(class, TargetClass::Vehicle);
+    }
+}
@@ -0,0 +1,251 @@
+use rs1090::decode::time::set_clock_error;
+use rs1090::prelude::*;
+use serde::Deserialize;
+use std::io;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, info, warn};
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_TO_UNIX_OFFSET: i64 = 2_208_988_800;
+/// Duration between two checks of the system clock
+const INTERVAL: Duration = Duration::from_secs(600);
+/// Estimated error above which a warning is issued
+const TOLERANCE: Duration = Duration::from_millis(50);
+
+/**
+ * How to check the synchronisation of the system clock.
+ *
+ * With `kernel`, the state maintained by the kernel (Linux only) is read:
+ * it is disciplined by chrony, ntpd or systemd-timesyncd. Otherwise, the
+ * offset of the system clock is measured against a NTP server (e.g.
+ * `pool.ntp.org`, port 123 by default) with a SNTP query.
+ */
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ClockSync {
+    Kernel,
+    Ntp(String),
+}
+
+impl FromStr for ClockSync {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("empty NTP server".to_string()),
+            "kernel" => Ok(ClockSync::Kernel),
+            server if server.contains(':') => {
+                Ok(ClockSync::Ntp(server.to_string()))
+            }
+            server => Ok(ClockSync::Ntp(format!("{server}:123"))),
+        }
+    }
+}
+
+impl TryFrom<String> for ClockSync {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// The synchronisation state of the system clock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockState {
+    pub synchronized: bool,
+    /// The estimated error of the system clock
+    pub error: Duration,
+}
+
+impl ClockSync {
+    pub async fn check(&self) -> io::Result<ClockState> {
+        match self {
+            ClockSync::Kernel => kernel(),
+            ClockSync::Ntp(server) => sntp(server).await,
+        }
+    }
+}
+
+/**
+ * Check the system clock at startup and periodically, and record its
+ * estimated error in the metadata of messages timestamped by the host.
+ *
+ * A warning is issued when the clock is (or becomes) unsynchronized.
+ */
+pub async fn monitor(sync: ClockSync) {
+    let mut previous: Option<bool> = None;
+    loop {
+        match sync.check().await {
+            Ok(state) => {
+                set_clock_error(Some(state.error));
+                let healthy = state.synchronized && state.error <= TOLERANCE;
+                match (previous, healthy) {
+                    (Some(true), true) | (Some(false), false) => {
+                        debug!("System clock error: {:?}", state.error)
+                    }
+                    (_, true) => {
+                        info!("System clock error: {:?}", state.error)
+                    }
+                    (_, false) if !state.synchronized => warn!(
+                        "System clock not synchronized (error up to {:?})",
+                        state.error
+                    ),
+                    (_, false) => {
+                        warn!("System clock error: {:?}", state.error)
+                    }
+                }
+                previous = Some(healthy);
+            }
+            Err(e) => {
+                set_clock_error(None);
+                warn!("Failed to check the system clock: {}", e);
+            }
+        }
+        sleep(INTERVAL).await;
+    }
+}
+
+/// Read the synchronisation state maintained by the kernel
+#[cfg(target_os = "linux")]
+fn kernel() -> io::Result<ClockState> {
+    // SAFETY: with modes set to 0, the structure is only read
+    let mut timex: libc::timex = unsafe { std::mem::zeroed() };
+    let state = unsafe { libc::adjtimex(&mut timex) };
+    if state == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let synchronized =
+        state != libc::TIME_ERROR && timex.status & libc::STA_UNSYNC == 0;
+    // Errors are in µs, the maximum error grows while unsynchronized
+    let error = match synchronized {
+        true => timex.esterror,
+        false => timex.maxerror,
+    };
+    Ok(ClockState {
+        synchronized,
+        error: Duration::from_micros(error.max(0) as u64),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kernel() -> io::Result<ClockState> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the kernel clock state is only available on Linux, \
+        consider setting a NTP server instead",
+    ))
+}
+
+/**
+ * Measure the offset of the system clock with a SNTP query (RFC 4330).
+ *
+ * The estimated error is the measured offset, plus half the round trip
+ * delay and the root distance of the server.
+ */
+async fn sntp(server: &str) -> io::Result<ClockState> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(server).await?;
+
+    let mut request = [0u8; 48];
+    request[0] = 0x23; // no leap warning, version 4, client mode
+    let t1 = Timestamp::now();
+    request[40..48].copy_from_slice(&to_ntp(t1).to_be_bytes());
+    socket.send(&request).await?;
+
+    let mut response = [0u8; 48];
+    let len = timeout(Duration::from_secs(5), socket.recv(&mut response))
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+    let t4 = Timestamp::now();
+
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg);
+    if len < 48 || response[24..32] != request[40..48] {
+        return Err(invalid("unexpected response from the NTP server"));
+    }
+    // Leap indicator set to 3 (unsynchronized server) or kiss-o'-death
+    if response[0] >> 6 == 3 || response[1] == 0 {
+        return Err(invalid("the NTP server is not synchronized"));
+    }
+
+    let field =
+        |i: usize| u64::from_be_bytes(response[i..i + 8].try_into().unwrap());
+    let t2 = from_ntp(field(32));
+    let t3 = from_ntp(field(40));
+    // Root delay and dispersion are in 16.16 fixed point seconds
+    let short = |i: usize| {
+        u32::from_be_bytes(response[i..i + 4].try_into().unwrap()) as f64
+            / 65536.
+    };
+    let root_distance = short(4) / 2. + short(8);
+
+    let (t1, t2, t3, t4) =
+        (t1.as_nanos(), t2.as_nanos(), t3.as_nanos(), t4.as_nanos());
+    let offset = ((t2 - t1) + (t3 - t4)) as f64 / 2. * 1e-9;
+    let delay = ((t4 - t1) - (t3 - t2)).max(0) as f64 * 1e-9;
+    let error = offset.abs() + delay / 2. + root_distance;
+    Ok(ClockState {
+        synchronized: true,
+        error: Duration::from_secs_f64(error),
+    })
+}
+
+/// Convert a timestamp to the 64-bit NTP format (32.32 fixed point)
+fn to_ntp(timestamp: Timestamp) -> u64 {
+    let secs = (timestamp.as_secs() + NTP_TO_UNIX_OFFSET) as u64;
+    let nanos = timestamp.as_nanos().rem_euclid(1_000_000_000) as u64;
+    (secs << 32) | ((nanos << 32) / 1_000_000_000)
+}
+
+fn from_ntp(ntp: u64) -> Timestamp {
+    let secs = (ntp >> 32) as i64 - NTP_TO_UNIX_OFFSET;
+    let nanos = ((ntp & 0xffff_ffff) * 1_000_000_000) >> 32;
+    Timestamp::from_nanos(secs * 1_000_000_000 + nanos as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_sync() {
+        assert_eq!(ClockSync::from_str("kernel"), Ok(ClockSync::Kernel));
+        assert_eq!(
+            ClockSync::from_str("pool.ntp.org"),
+            Ok(ClockSync::Ntp("pool.ntp.org:123".to_string()))
+        );
+        assert_eq!(
+            ClockSync::from_str("127.0.0.1:1123"),
+            Ok(ClockSync::Ntp("127.0.0.1:1123".to_string()))
+        );
+        let ts = Timestamp::from_nanos(1_712_345_678_123_456_789);
+        // The NTP format has a resolution of about 0.2 ns
+        assert!((from_ntp(to_ntp(ts)).as_nanos() - ts.as_nanos()).abs() <= 1);
+    }
+
+    #[tokio::test]
+    async fn test_sntp() {
+        // A server 2 seconds ahead of the system clock
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = server.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 48];
+            let (_, client) = server.recv_from(&mut buf).await.unwrap();
+            let now = to_ntp(Timestamp::now() + Duration::from_secs(2));
+            let mut response = [0u8; 48];
+            response[0] = 0x24; // version 4, server mode
+            response[1] = 2; // stratum
+            response[24..32].copy_from_slice(&buf[40..48]);
+            response[32..40].copy_from_slice(&now.to_be_bytes());
+            response[40..48].copy_from_slice(&now.to_be_bytes());
+            server.send_to(&response, client).await.unwrap();
+        });
+        let state = ClockSync::Ntp(address).check().await.unwrap();
+        assert!(state.synchronized);
+        let error = state.error.as_secs_f64();
+        assert!((2. ..2.1).contains(&error), "{error}");
+    }
+}
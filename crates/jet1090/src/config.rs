@@ -0,0 +1,119 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use clap::Subcommand;
+
+/// A commented configuration file, written by `jet1090 config init`
+pub const TEMPLATE: &str = include_str!("config.toml");
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Print the path of the configuration file
+    Path,
+    /// Write a commented configuration file
+    Init {
+        /// Overwrite an existing configuration file
+        #[arg(long, default_value = "false")]
+        force: bool,
+    },
+}
+
+/// Expand the `~` (tilde) character at the beginning of a path
+pub fn expanduser(path: PathBuf) -> PathBuf {
+    // Check if the path starts with "~"
+    if let Some(stripped) = path.to_str().and_then(|p| p.strip_prefix("~")) {
+        if let Some(home_dir) = dirs::home_dir() {
+            // Join the home directory with the rest of the path
+            return home_dir.join(stripped.trim_start_matches('/'));
+        }
+    }
+    path
+}
+
+/**
+ * A base directory: the XDG variable if set (on all systems), otherwise the
+ * default of the system, e.g. `~/.config` on Linux, `~/Library/Application
+ * Support` on macOS or `AppData\Roaming` on Windows for configuration files.
+ */
+fn base_dir(
+    variable: &str,
+    default: fn() -> Option<PathBuf>,
+) -> Option<PathBuf> {
+    match std::env::var(variable) {
+        Ok(dir) if !dir.is_empty() => Some(expanduser(PathBuf::from(dir))),
+        _ => default(),
+    }
+}
+
+/// The jet1090 configuration directory
+pub fn config_dir() -> Option<PathBuf> {
+    base_dir("XDG_CONFIG_HOME", dirs::config_dir).map(|dir| dir.join("jet1090"))
+}
+
+/// The jet1090 cache directory (aircraft database, sensors, statistics)
+pub fn cache_dir() -> Option<PathBuf> {
+    base_dir("XDG_CACHE_HOME", dirs::cache_dir).map(|dir| dir.join("jet1090"))
+}
+
+/**
+ * The configuration file: `JET1090_CONFIG` if set, otherwise `config.toml`
+ * in the jet1090 configuration directory.
+ */
+pub fn path() -> Option<PathBuf> {
+    if let Ok(config_file) = std::env::var("JET1090_CONFIG") {
+        return Some(expanduser(PathBuf::from(config_file)));
+    }
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// The configuration file, if it exists
+pub fn find() -> Option<PathBuf> {
+    path().filter(|path| path.exists())
+}
+
+/// Run a command of the `config` subcommand
+pub fn run(command: Command) -> io::Result<()> {
+    let path = path().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "no configuration directory, set JET1090_CONFIG",
+        )
+    })?;
+    match command {
+        Command::Path => {
+            println!("{}", path.display());
+            if !path.exists() {
+                eprintln!(
+                    "(not found, use `jet1090 config init` to create it)"
+                );
+            }
+        }
+        Command::Init { force } => {
+            if path.exists() && !force {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} already exists, use --force", path.display()),
+                ));
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, TEMPLATE)?;
+            println!("{}", path.display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expanduser() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expanduser(PathBuf::from("~/a.toml")), home.join("a.toml"));
+        assert_eq!(expanduser(PathBuf::from("/a/b")), PathBuf::from("/a/b"));
+    }
+}
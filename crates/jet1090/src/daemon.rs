@@ -0,0 +1,210 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing_appender::rolling::Rotation;
+
+/**
+ * Detach the process from the terminal (unix double-fork).
+ *
+ * Standard input and outputs are redirected to /dev/null, so logs should be
+ * written to a file. The working directory is left untouched so that relative
+ * paths (output, log files) remain valid.
+ *
+ * This must be called before the tokio runtime is started: only the calling
+ * thread survives a fork.
+ */
+#[cfg(unix)]
+pub fn daemonize() -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: the process is still single-threaded at this point
+    unsafe {
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+        // Become the leader of a new session, without a controlling terminal
+        if libc::setsid() == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // Fork again so that the daemon can never acquire a terminal again
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+        libc::umask(0o022);
+    }
+
+    let devnull = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+    for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        // SAFETY: both file descriptors are valid
+        if unsafe { libc::dup2(devnull.as_raw_fd(), fd) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn daemonize() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "daemon mode is only available on unix systems, \
+        consider registering jet1090 as a Windows service (e.g. with nssm)",
+    ))
+}
+
+/**
+ * A PID file, removed when the structure is dropped.
+ *
+ * The file is created exclusively, so that two instances started at the
+ * same time cannot both succeed. Creating a PID file fails if the file
+ * already exists and refers to a running process; stale files (e.g. after a
+ * crash) are replaced.
+ */
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = match Self::create_new(path) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                if let Some(pid) = fs::read_to_string(path)
+                    .ok()
+                    .and_then(|content| content.trim().parse::<u32>().ok())
+                {
+                    if is_running(pid) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::AlreadyExists,
+                            format!("jet1090 already running with PID {}", pid),
+                        ));
+                    }
+                }
+                // A stale file: if another instance replaces it first, the
+                // second exclusive creation fails
+                fs::remove_file(path)?;
+                Self::create_new(path)?
+            }
+            result => result?,
+        };
+        writeln!(file, "{}", std::process::id())?;
+        Ok(PidFile {
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn create_new(path: &Path) -> io::Result<fs::File> {
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/**
+ * Wait for a request to stop the process: Ctrl-C (SIGINT), or SIGTERM as
+ * sent by service managers and `kill`, so that jet1090 shuts down cleanly
+ * (and removes its PID file) in both cases.
+ */
+#[cfg(unix)]
+pub async fn terminated() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Failed to listen to SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn terminated() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    // Signal 0 only checks whether the process exists
+    // SAFETY: no signal is actually sent
+    pid != std::process::id() && unsafe { libc::kill(pid as i32, 0) } == 0
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    false
+}
+
+/// How often to start a new log file
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(value: LogRotation) -> Self {
+        match value {
+            LogRotation::Minutely => Rotation::MINUTELY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file() {
+        let path = std::env::temp_dir().join("jet1090_test.pid");
+        {
+            let _pid = PidFile::create(&path).unwrap();
+            let content = fs::read_to_string(&path).unwrap();
+            assert_eq!(content.trim(), format!("{}", std::process::id()));
+        }
+        assert!(!path.exists());
+
+        // Stale PID files are replaced
+        fs::write(&path, "4194304\n").unwrap();
+        let pid = PidFile::create(&path);
+        assert!(pid.is_ok());
+        drop(pid);
+
+        // The file of a running process (here, the parent) is kept
+        #[cfg(unix)]
+        {
+            let parent = std::os::unix::process::parent_id();
+            fs::write(&path, format!("{}\n", parent)).unwrap();
+            let pid = PidFile::create(&path);
+            assert_eq!(pid.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+            fs::remove_file(&path).unwrap();
+        }
+    }
+}
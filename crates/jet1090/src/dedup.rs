@@ -1,82 +1,477 @@
+use crate::errors::{self, ErrorReporter};
+use crate::latency;
+use clap::ValueEnum;
 use rs1090::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap};
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::sync::mpsc;
 use tracing::info;
 
+/// Which copy of a message is sent when several sensors receive it
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Send the first copy right away, with its own metadata
+    FirstWins,
+    /// Send the copy with the strongest signal, with its own metadata
+    BestRssiWins,
+    /// Send the first copy, with the metadata of all the sensors
+    #[default]
+    MergeAll,
+}
+
 /**
- * A basic message deduplication algorithm.
+ * The protocol of the frames received by a sensor.
  *
- * Reads messages from a MPSC and sends deduplicated messages to another one.
+ * Frames of different protocols are deduplicated separately: identical
+ * bytes in two protocols are different messages. The protocol is set for
+ * each source (Mode S by default), with its own window if configured.
+ */
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    /// Mode S, including ADS-B (and Mode A/C frames in the Beast format)
+    #[default]
+    ModeS,
+    /// FLARM, e.g. for a source relaying frames of a FLARM receiver
+    Flarm,
+}
+
+/// Messages are grouped by protocol and frame
+type Key = (Protocol, Vec<u8>);
+
+/**
+ * The deduplication settings: a default window, possibly overridden for
+ * the sensors of some sources (identified by their serial number) or for
+ * the frames of some protocols.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// Default duration (in ms) during which identical messages are grouped
+    pub window: u32,
+    /// Per sensor overrides of the window, e.g. for slow satellite links
+    pub windows: HashMap<u64, u32>,
+    pub strategy: Strategy,
+    /// The protocol of sensors not receiving Mode S frames
+    pub protocols: HashMap<u64, Protocol>,
+    /// Per protocol overrides of the window (sensor overrides come first)
+    pub protocol_windows: HashMap<Protocol, u32>,
+}
+
+impl Settings {
+    fn protocol(&self, msg: &TimedMessage) -> Protocol {
+        msg.metadata
+            .first()
+            .and_then(|meta| self.protocols.get(&meta.serial))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn window(&self, msg: &TimedMessage, protocol: Protocol) -> u32 {
+        msg.metadata
+            .first()
+            .and_then(|meta| self.windows.get(&meta.serial))
+            .or_else(|| self.protocol_windows.get(&protocol))
+            .copied()
+            .unwrap_or(self.window)
+    }
+
+    fn max_window(&self) -> u32 {
+        self.windows
+            .values()
+            .chain(self.protocol_windows.values())
+            .copied()
+            .fold(self.window, u32::max)
+    }
+}
+
+/// Counters of the deduplication, shared with the site report
+#[derive(Debug, Default)]
+pub struct Statistics {
+    received: AtomicU64,
+    sent: AtomicU64,
+}
+
+impl Statistics {
+    /// Number of messages received by all the sensors
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages after deduplication
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+}
+
+/**
+ * A basic message deduplication algorithm.
  *
- * Identical messages are grouped for a duration of `dedup_threshold`.
+ * Identical messages are grouped for the window of the source which received
+ * the first copy. Copies coming later, but within the window of their own
+ * source, are dropped: a slow source with a long window does not delay the
+ * messages received by faster sources.
  *
  * Future versions should check for average gap between sensors for a better
  * synchronisation.
  */
-pub async fn deduplicate_messages(
-    mut rx: mpsc::Receiver<TimedMessage>,
-    tx: mpsc::Sender<TimedMessage>,
-    dedup_threshold: u32,
-) {
-    let mut cache: HashMap<Vec<u8>, Vec<TimedMessage>> = HashMap::new();
-    let mut expiration_heap: BinaryHeap<Reverse<(u128, Vec<u8>)>> =
-        BinaryHeap::new();
+pub struct Deduplicator {
+    settings: Settings,
+    cache: HashMap<Key, Vec<TimedMessage>>,
+    expiration_heap: BinaryHeap<Reverse<(Timestamp, Key)>>,
+    /// Messages already sent, with the timestamp of their first copy
+    sent: HashMap<Key, Timestamp>,
+    sent_heap: BinaryHeap<Reverse<(Timestamp, Key)>>,
+}
 
-    while let Some(msg) = rx.recv().await {
-        let timestamp_ms = (msg.timestamp * 1e3) as u128;
-        let frame = msg.frame.clone();
-
-        // Add message to cache
-        cache.entry(frame.clone()).or_default().push(msg);
-
-        // Push the expiration timestamp into the heap
-        if cache[&frame].len() == 1 {
-            expiration_heap.push(Reverse((
-                timestamp_ms + dedup_threshold as u128,
-                frame.clone(),
-            )));
+impl Deduplicator {
+    pub fn new(settings: Settings) -> Self {
+        Deduplicator {
+            settings,
+            cache: HashMap::new(),
+            expiration_heap: BinaryHeap::new(),
+            sent: HashMap::new(),
+            sent_heap: BinaryHeap::new(),
+        }
+    }
+
+    /**
+     * Add a message, and return the (undecoded) messages ready to be sent.
+     *
+     * Messages are only released when more recent messages come in.
+     */
+    pub fn push(&mut self, msg: TimedMessage) -> Vec<TimedMessage> {
+        let timestamp = msg.timestamp;
+        let protocol = self.settings.protocol(&msg);
+        let window = self.settings.window(&msg, protocol);
+        let window = Duration::from_millis(window as u64);
+        let key = (protocol, msg.frame.clone());
+
+        let mut ready = Vec::new();
+        let late = self
+            .sent
+            .get(&key)
+            .is_some_and(|first| timestamp <= *first + window);
+
+        if let Some(entries) = self.cache.get_mut(&key) {
+            entries.push(msg);
+        } else if !late {
+            if self.settings.strategy == Strategy::FirstWins {
+                self.mark_sent(key, timestamp);
+                ready.push(msg);
+            } else {
+                // Push the expiration timestamp into the heap
+                self.expiration_heap
+                    .push(Reverse((timestamp + window, key.clone())));
+                self.cache.insert(key, vec![msg]);
+            }
         }
 
         // Check and handle expired entries
-        while let Some(Reverse((curtime, frame))) = expiration_heap.pop() {
-            if curtime > timestamp_ms {
-                // If not expired, push it back and stop processing
-                expiration_heap.push(Reverse((curtime, frame)));
+        while let Some(Reverse((curtime, _))) = self.expiration_heap.peek() {
+            if *curtime > timestamp {
                 break;
             }
-
+            let Reverse((_, key)) = self.expiration_heap.pop().unwrap();
             // Otherwise clear the cache and process the deduplicated message
-            if let Some(mut entries) = cache.remove(&frame) {
+            if let Some(entries) = self.cache.remove(&key) {
+                self.mark_sent(key, entries[0].timestamp);
+                ready.push(self.merge(entries));
+            }
+        }
+
+        // Forget about messages sent long ago
+        let max_window = self.max_window();
+        while let Some(Reverse((curtime, _))) = self.sent_heap.peek() {
+            if *curtime > timestamp {
+                break;
+            }
+            let Reverse((_, key)) = self.sent_heap.pop().unwrap();
+            // The same message may have been sent again in the meantime
+            if self
+                .sent
+                .get(&key)
+                .is_some_and(|first| *first + max_window <= timestamp)
+            {
+                self.sent.remove(&key);
+            }
+        }
+
+        ready
+    }
+
+    fn max_window(&self) -> Duration {
+        Duration::from_millis(self.settings.max_window() as u64)
+    }
+
+    fn mark_sent(&mut self, key: Key, first: Timestamp) {
+        let max_window = self.max_window();
+        self.sent_heap
+            .push(Reverse((first + max_window, key.clone())));
+        self.sent.insert(key, first);
+    }
+
+    fn merge(&self, mut entries: Vec<TimedMessage>) -> TimedMessage {
+        match self.settings.strategy {
+            Strategy::FirstWins => entries.remove(0),
+            Strategy::BestRssiWins => {
+                let rssi = |msg: &TimedMessage| {
+                    msg.metadata
+                        .iter()
+                        .filter_map(|meta| meta.rssi)
+                        .fold(f32::MIN, f32::max)
+                };
+                // The first copy wins in case of a tie
+                let (best, _) = entries
+                    .iter()
+                    .enumerate()
+                    .max_by(|(i, a), (j, b)| {
+                        rssi(a).total_cmp(&rssi(b)).then(j.cmp(i))
+                    })
+                    .unwrap();
+                entries.swap_remove(best)
+            }
+            Strategy::MergeAll => {
                 let merged_metadata: Vec<SensorMetadata> = entries
                     .iter()
                     .flat_map(|entry| entry.metadata.clone())
                     .collect();
-
                 let mut tmsg = entries.remove(0);
                 tmsg.metadata = merged_metadata;
+                tmsg
+            }
+        }
+    }
+}
+
+/**
+ * Read messages from a MPSC and send deduplicated (and decoded) messages to
+ * another one, see [`Deduplicator`].
+ *
+ * With `military`, military extended squitters (DF19) with AF=0 are decoded
+ * as ADS-B messages, see [`Message::military`].
+ *
+ * Messages which are already decoded (from other instances of jet1090) are
+ * not decoded again. Messages failing to decode are dropped, and reported
+ * to `errors`.
+ */
+pub async fn deduplicate_messages(
+    mut rx: mpsc::Receiver<TimedMessage>,
+    tx: mpsc::Sender<TimedMessage>,
+    settings: Settings,
+    statistics: Arc<Statistics>,
+    military: bool,
+    mut errors: ErrorReporter,
+) {
+    let mut dedup = Deduplicator::new(settings);
 
-                let start = SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .expect("SystemTime before unix epoch")
-                    .as_secs_f64();
-
-                if let Ok((_, msg)) = Message::from_bytes((&tmsg.frame, 0)) {
-                    tmsg.decode_time = Some(
-                        SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .expect("SystemTime before unix epoch")
-                            .as_secs_f64()
-                            - start,
-                    );
-                    tmsg.message = Some(msg);
-
-                    if let Err(e) = tx.send(tmsg).await {
-                        info!("Failed to send deduplicated entries: {}", e);
+    while let Some(msg) = rx.recv().await {
+        statistics.received.fetch_add(1, Ordering::Relaxed);
+        let ready = dedup.push(msg);
+        statistics
+            .sent
+            .fetch_add(ready.len() as u64, Ordering::Relaxed);
+        for mut tmsg in ready {
+            latency::checkpoint(latency::DEDUP, &tmsg);
+            let start = SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .expect("SystemTime before unix epoch")
+                .as_secs_f64();
+
+            // Messages from other instances of jet1090 are already decoded
+            let forwarded = tmsg.message.is_some();
+            let mut msg = match tmsg.message.take() {
+                Some(msg) => msg,
+                None => match Message::from_bytes((&tmsg.frame, 0)) {
+                    Ok((_, msg)) => msg,
+                    Err(e) => {
+                        errors.record(errors::kind(&e), &tmsg);
+                        continue;
                     }
-                }
+                },
+            };
+            if military {
+                msg.military();
+            }
+            if !forwarded {
+                tmsg.decode_time = Some(
+                    SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .expect("SystemTime before unix epoch")
+                        .as_secs_f64()
+                        - start,
+                );
+            }
+            tmsg.message = Some(msg);
+
+            if let Err(e) = tx.send(tmsg).await {
+                info!("Failed to send deduplicated entries: {}", e);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::timed;
+
+    fn rssi(mut msg: TimedMessage, rssi: f32) -> TimedMessage {
+        msg.metadata[0].rssi = Some(rssi);
+        msg
+    }
+
+    fn serials(messages: &[TimedMessage]) -> Vec<Vec<u64>> {
+        messages
+            .iter()
+            .map(|msg| msg.metadata.iter().map(|meta| meta.serial).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_strategies() {
+        let run = |strategy| {
+            let mut dedup = Deduplicator::new(Settings {
+                window: 450,
+                windows: HashMap::new(),
+                strategy,
+                ..Default::default()
+            });
+            let mut sent = dedup.push(rssi(timed("0a", 0.0, 1), -20.));
+            sent.extend(dedup.push(rssi(timed("0a", 0.1, 2), -10.)));
+            sent.extend(dedup.push(rssi(timed("0a", 0.2, 3), -15.)));
+            sent.extend(dedup.push(rssi(timed("0b", 1.0, 1), -20.)));
+            serials(&sent)
+        };
+        assert_eq!(run(Strategy::MergeAll), vec![vec![1, 2, 3]]);
+        assert_eq!(run(Strategy::BestRssiWins), vec![vec![2]]);
+        // The first copy of each message is sent right away
+        assert_eq!(run(Strategy::FirstWins), vec![vec![1], vec![1]]);
+    }
+
+    #[test]
+    fn test_interleaved() {
+        // A second sensor with a clock 20 ms behind: copies interleave
+        let mut dedup = Deduplicator::new(Settings {
+            window: 450,
+            strategy: Strategy::MergeAll,
+            ..Default::default()
+        });
+        let mut sent = vec![];
+        for (frame, timestamp, serial) in [
+            ("a0", 10.0, 1),
+            ("a1", 10.3, 1),
+            ("a0", 9.98, 2),
+            ("a1", 10.28, 2),
+            ("a2", 11.0, 1),
+        ] {
+            sent.extend(dedup.push(timed(frame, timestamp, serial)));
+        }
+        // Copies are merged, and released in timestamp order
+        assert_eq!(serials(&sent), vec![vec![1, 2], vec![1, 2]]);
+        assert_eq!(sent[0].frame, [0xa0]);
+        assert!(sent[0].timestamp < sent[1].timestamp);
+    }
+
+    #[test]
+    fn test_windows() {
+        // A slow source (serial 9) with a longer window
+        let mut dedup = Deduplicator::new(Settings {
+            window: 450,
+            windows: HashMap::from([(9, 3000)]),
+            strategy: Strategy::MergeAll,
+            ..Default::default()
+        });
+        assert!(dedup.push(timed("0a", 0.0, 1)).is_empty());
+        assert!(dedup.push(timed("0a", 0.1, 2)).is_empty());
+        // Messages received first by fast sources are not delayed
+        let sent = dedup.push(timed("0b", 0.5, 1));
+        assert_eq!(serials(&sent), vec![vec![1, 2]]);
+        // Late copies from the slow source are dropped...
+        let sent = dedup.push(timed("0a", 2.0, 9));
+        assert_eq!(serials(&sent), vec![vec![1]]);
+        assert!(dedup.push(timed("0b", 2.5, 9)).is_empty());
+        // ... but not copies from fast sources received much later
+        assert!(dedup.push(timed("0a", 2.6, 1)).is_empty());
+        let sent = dedup.push(timed("0c", 3.1, 1));
+        assert_eq!(serials(&sent), vec![vec![1]]);
+        assert_eq!(sent[0].timestamp, Timestamp::from_millis(2600));
+    }
+
+    #[test]
+    fn test_protocols() {
+        // A FLARM receiver (serial 7) next to a Mode S one
+        let mut dedup = Deduplicator::new(Settings {
+            window: 450,
+            strategy: Strategy::MergeAll,
+            protocols: HashMap::from([(7, Protocol::Flarm)]),
+            protocol_windows: HashMap::from([(Protocol::Flarm, 1000)]),
+            ..Default::default()
+        });
+        // The same bytes in both protocols are not merged
+        assert!(dedup.push(timed("0a", 0.0, 1)).is_empty());
+        assert!(dedup.push(timed("0a", 0.1, 7)).is_empty());
+        assert!(dedup.push(timed("0a", 0.2, 2)).is_empty());
+        let sent = dedup.push(timed("0b", 0.5, 1));
+        assert_eq!(serials(&sent), vec![vec![1, 2]]);
+        // FLARM frames are grouped over their own window
+        let sent = dedup.push(timed("0c", 1.0, 7));
+        assert_eq!(serials(&sent), vec![vec![1]]);
+        let sent = dedup.push(timed("0d", 1.1, 1));
+        assert_eq!(serials(&sent), vec![vec![7]]);
+    }
+
+    #[tokio::test]
+    async fn test_decoded() {
+        let (tx, rx) = mpsc::channel(10);
+        let (out, mut decoded) = mpsc::channel(10);
+        tokio::spawn(deduplicate_messages(
+            rx,
+            out,
+            Settings {
+                window: 450,
+                strategy: Strategy::MergeAll,
+                ..Default::default()
+            },
+            Arc::default(),
+            false,
+            ErrorReporter::new(errors::ErrorStats::default()),
+        ));
+
+        // A message decoded by another instance of jet1090 is forwarded as is
+        let mut forwarded = timed("0a", 0.0, 2);
+        forwarded.message = Some(Message {
+            crc: 0,
+            df: ExtendedSquitterMilitary {
+                af: 1,
+                content: vec![0; 10],
+            },
+            warnings: vec![],
+        });
+        let expected = forwarded.message.clone();
+        tx.send(forwarded).await.unwrap();
+        tx.send(timed("0c", 1.0, 1)).await.unwrap();
+
+        let msg = decoded.recv().await.unwrap();
+        assert_eq!(msg.message, expected);
+        assert!(msg.decode_time.is_none());
+    }
+}
@@ -0,0 +1,218 @@
+use clap::ValueEnum;
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use tracing::info;
+
+use crate::aircraftdb;
+
+/// The default location of the tar1090 database
+pub const DEFAULT_URL: &str =
+    "https://raw.githubusercontent.com/wiedehopf/tar1090-db/csv/aircraft.csv.gz";
+const DATABASE: &str = "tar1090-db.csv.gz";
+
+type Result<T> =
+    std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// The information about an aircraft added to its state vector
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Enrichment {
+    /// The owner or operator of the aircraft
+    pub operator: Option<String>,
+    /// A link to photos of the aircraft
+    pub photo: Option<String>,
+}
+
+impl Enrichment {
+    /// Complete the missing fields with the ones of another enrichment
+    fn or(self, other: Enrichment) -> Enrichment {
+        Enrichment {
+            operator: self.operator.or(other.operator),
+            photo: self.photo.or(other.photo),
+        }
+    }
+}
+
+/**
+ * A source of information about aircraft, looked up once when an aircraft
+ * is first seen.
+ *
+ * Lookups are done while decoding: providers must not block, e.g. remote
+ * databases are downloaded and cached locally beforehand.
+ */
+pub trait Provider: Send + Sync {
+    fn lookup(&self, icao24: &str, registration: Option<&str>) -> Enrichment;
+}
+
+/// The available providers
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// Operators from a tar1090 database (downloaded and cached)
+    Tar1090,
+    /// Links to photos on planespotters.net, from the registration
+    Planespotters,
+}
+
+/**
+ * A tar1090 style database, with one aircraft per line and `;` separated
+ * fields: ICAO 24-bit address, registration, typecode, flags, description,
+ * year and owner or operator. The file may be compressed with gzip.
+ */
+#[derive(Debug, Default)]
+pub struct Tar1090 {
+    operators: HashMap<String, String>,
+}
+
+impl Tar1090 {
+    pub fn parse(content: &[u8]) -> Result<Self> {
+        let reader: Box<dyn Read> = match content {
+            [0x1f, 0x8b, ..] => Box::new(GzDecoder::new(content)),
+            _ => Box::new(content),
+        };
+        let mut operators = HashMap::new();
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            let fields: Vec<&str> = line.split(';').collect();
+            let (Some(icao24), Some(operator)) =
+                (fields.first(), fields.get(6))
+            else {
+                continue;
+            };
+            if !operator.trim().is_empty() {
+                operators
+                    .insert(icao24.to_lowercase(), operator.trim().to_string());
+            }
+        }
+        Ok(Tar1090 { operators })
+    }
+
+    /**
+     * Load the database, from the cache directory of the aircraft database
+     * if possible.
+     *
+     * The database is downloaded if it is not in the cache yet, unless in
+     * offline mode. Delete the cached file to download it again.
+     */
+    pub async fn load(
+        url: Option<&str>,
+        settings: &aircraftdb::Settings,
+    ) -> Result<Self> {
+        let path = settings.cache_dir().join(DATABASE);
+        if !path.exists() {
+            if settings.offline {
+                return Err(format!("{} not found", path.display()).into());
+            }
+            let url = url.unwrap_or(DEFAULT_URL);
+            info!("Downloading the tar1090 database from {}", url);
+            let content = aircraftdb::fetch(url).await?;
+            // Check the content before caching it
+            Tar1090::parse(&content)?;
+            fs::create_dir_all(settings.cache_dir())?;
+            let partial = PathBuf::from(format!("{}.part", path.display()));
+            fs::write(&partial, &content)?;
+            fs::rename(&partial, &path)?;
+        }
+        let content = fs::read(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Tar1090::parse(&content)
+    }
+}
+
+impl Provider for Tar1090 {
+    fn lookup(&self, icao24: &str, _registration: Option<&str>) -> Enrichment {
+        Enrichment {
+            operator: self.operators.get(icao24).cloned(),
+            photo: None,
+        }
+    }
+}
+
+/// Links to the photos of an aircraft on planespotters.net
+#[derive(Debug, Default)]
+pub struct Planespotters;
+
+impl Provider for Planespotters {
+    fn lookup(&self, _icao24: &str, registration: Option<&str>) -> Enrichment {
+        let photo = registration
+            .map(str::trim)
+            .filter(|reg| !reg.is_empty())
+            .map(|reg| {
+                format!("https://www.planespotters.net/photos/reg/{reg}")
+            });
+        Enrichment {
+            operator: None,
+            photo,
+        }
+    }
+}
+
+/// All the configured providers, the first ones taking precedence
+#[derive(Default)]
+pub struct Enricher {
+    providers: Vec<Box<dyn Provider>>,
+}
+
+impl Enricher {
+    pub fn new(providers: Vec<Box<dyn Provider>>) -> Self {
+        Enricher { providers }
+    }
+
+    pub fn lookup(
+        &self,
+        icao24: &str,
+        registration: Option<&str>,
+    ) -> Enrichment {
+        self.providers
+            .iter()
+            .map(|provider| provider.lookup(icao24, registration))
+            .fold(Enrichment::default(), Enrichment::or)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const CONTENT: &str =
+        "39B415;F-HNAV;A320;00;AIRBUS A-320;2014;Air France\n\
+        3C6444;D-AIBD;A319;00;AIRBUS A-319;;\n\
+        invalid line\n";
+
+    #[test]
+    fn test_enricher() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(CONTENT.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let tar1090 = Tar1090::parse(&compressed).unwrap();
+        assert_eq!(tar1090.operators.len(), 1);
+        assert_eq!(
+            Tar1090::parse(CONTENT.as_bytes()).unwrap().operators,
+            tar1090.operators
+        );
+
+        let enricher =
+            Enricher::new(vec![Box::new(tar1090), Box::new(Planespotters)]);
+        let enrichment = enricher.lookup("39b415", Some("F-HNAV"));
+        assert_eq!(enrichment.operator.as_deref(), Some("Air France"));
+        assert_eq!(
+            enrichment.photo.as_deref(),
+            Some("https://www.planespotters.net/photos/reg/F-HNAV")
+        );
+        assert_eq!(enricher.lookup("3c6444", None), Enrichment::default());
+        assert_eq!(
+            Enricher::default()
+                .lookup("39b415", Some("F-HNAV"))
+                .operator,
+            None
+        );
+    }
+}
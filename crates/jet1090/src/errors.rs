@@ -0,0 +1,168 @@
+use deku::DekuError;
+use rs1090::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Interval between two reports of recurring errors in the logs
+pub const PERIOD: Duration = Duration::from_secs(60);
+
+/// The kind of a decoding error, as reported in the logs and the REST API
+pub fn kind(error: &DekuError) -> &'static str {
+    match error {
+        // Raised for ADS-B messages with a non zero CRC
+        DekuError::Assertion(_) | DekuError::AssertionNoStr => "CRC failures",
+        DekuError::Incomplete(_) => "truncated frames",
+        _ => "parse errors",
+    }
+}
+
+/// The number of errors of a kind from a sensor
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorCount {
+    pub kind: &'static str,
+    pub serial: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub count: u64,
+}
+
+/**
+ * The number of errors since startup, by kind and sensor, as served on the
+ * REST API.
+ *
+ * The counters are shared between clones, so that they can be served while
+ * errors are recorded in another task.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ErrorStats(Arc<Mutex<BTreeMap<(&'static str, u64), ErrorCount>>>);
+
+impl ErrorStats {
+    fn add(&self, kind: &'static str, serial: u64, name: &Option<String>) {
+        let Ok(mut totals) = self.0.lock() else {
+            return;
+        };
+        totals
+            .entry((kind, serial))
+            .and_modify(|total| total.count += 1)
+            .or_insert_with(|| ErrorCount {
+                kind,
+                serial,
+                name: name.clone(),
+                count: 1,
+            });
+    }
+
+    pub fn counts(&self) -> Vec<ErrorCount> {
+        match self.0.lock() {
+            Ok(totals) => totals.values().cloned().collect(),
+            Err(_) => vec![],
+        }
+    }
+}
+
+/**
+ * Aggregate recurring errors, and log them once per [`PERIOD`].
+ *
+ * At high message rates, a misbehaving sensor may produce thousands of
+ * malformed frames per second: a warning per frame would flood the logs.
+ * Errors are counted by kind and sensor instead, and each count is logged
+ * at the end of the period, e.g. "1234 CRC failures from sensor X in last
+ * 60 s". Nothing is logged for periods without any error.
+ */
+#[derive(Debug)]
+pub struct ErrorReporter {
+    stats: ErrorStats,
+    start: Instant,
+    /// Errors in the current period, not logged yet
+    counts: BTreeMap<(&'static str, u64), ErrorCount>,
+}
+
+impl ErrorReporter {
+    pub fn new(stats: ErrorStats) -> Self {
+        ErrorReporter {
+            stats,
+            start: Instant::now(),
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// Record an error of a kind for the sensor which received a message
+    pub fn record(&mut self, kind: &'static str, msg: &TimedMessage) {
+        let (serial, name) = match msg.metadata.first() {
+            Some(meta) => (meta.serial, meta.name.clone()),
+            None => (0, None),
+        };
+        self.stats.add(kind, serial, &name);
+        self.counts
+            .entry((kind, serial))
+            .and_modify(|count| count.count += 1)
+            .or_insert(ErrorCount {
+                kind,
+                serial,
+                name,
+                count: 1,
+            });
+        self.tick(Instant::now());
+    }
+
+    /// Log the errors of the current period if it is over
+    pub fn tick(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.start);
+        if elapsed < PERIOD {
+            return;
+        }
+        self.start = now;
+        for count in self.counts.values() {
+            let sensor = match &count.name {
+                Some(name) => name.clone(),
+                None => count.serial.to_string(),
+            };
+            warn!(
+                "{} {} from sensor {} in last {} s",
+                count.count,
+                count.kind,
+                sensor,
+                elapsed.as_secs()
+            );
+        }
+        self.counts.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::timed;
+
+    #[test]
+    fn test_errors() {
+        let stats = ErrorStats::default();
+        let mut reporter = ErrorReporter::new(stats.clone());
+
+        // The last byte of an extended squitter is corrupted
+        let msg = timed("8d40621d58c382d690c8ac2863a8", 1_700_000_000., 1);
+        for _ in 0..3 {
+            let error = Message::try_from(msg.frame.as_slice()).unwrap_err();
+            reporter.record(kind(&error), &msg);
+        }
+        let msg = timed("8d40621d58c382", 1_700_000_000., 2);
+        let error = Message::try_from(msg.frame.as_slice()).unwrap_err();
+        reporter.record(kind(&error), &msg);
+
+        let counts = stats.counts();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[0].kind, "CRC failures");
+        assert_eq!((counts[0].serial, counts[0].count), (1, 3));
+        assert_eq!((counts[1].serial, counts[1].count), (2, 1));
+
+        // Counts are only logged (and reset) at the end of the period
+        reporter.tick(Instant::now());
+        assert_eq!(reporter.counts.len(), 2);
+        reporter.tick(Instant::now() + PERIOD);
+        assert!(reporter.counts.is_empty());
+        assert_eq!(stats.counts().len(), 2);
+    }
+}
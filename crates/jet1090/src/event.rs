@@ -0,0 +1,300 @@
+use std::sync::Arc;
+
+use rs1090::decode::IdentityCode;
+use rs1090::prelude::*;
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::filters::Filters;
+use crate::flight::FlightSummary;
+use crate::script::Outcome;
+use crate::snapshot::Snapshot;
+
+/// Number of events kept for sinks lagging behind, before they miss some
+/// (or before sending waits for them, see [`Publisher`])
+pub const CAPACITY: usize = 4096;
+/// Duration (in s) without any message before a sensor is considered down
+pub const SENSOR_TIMEOUT: u64 = 60;
+
+/**
+ * Everything happening in the decoding pipeline, as published to sinks.
+ *
+ * Events are sent by a [`Publisher`], so that each sink (stdout, output
+ * file, Redis) subscribes to it and handles all events the same way. Adding
+ * a new kind of event does not require any wiring in the decoding loop.
+ */
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A decoded message, after deduplication and position decoding, with
+    /// the state of the aircraft after the message, only if a sink writes
+    /// the enriched layer, and what the script decided about it, if any
+    Message(
+        Arc<TimedMessage>,
+        Option<Arc<Snapshot>>,
+        Option<Arc<Outcome>>,
+    ),
+    /// A sensor started, or resumed, sending messages
+    SensorUp(SensorStatus),
+    /// A sensor sent no message for [`SENSOR_TIMEOUT`] seconds
+    SensorDown(SensorStatus),
+    /// Something worth the attention of an operator
+    Alert(Alert),
+    /// An aircraft expired, with the summary of its flight
+    FlightClosed(Arc<FlightSummary>),
+    /// The periodic activity of a sensor, even without any traffic
+    Heartbeat(Heartbeat),
+}
+
+/**
+ * The sender of events to the sinks.
+ *
+ * Most sinks subscribe to a broadcast channel: a sink lagging too far behind
+ * misses events, but does not slow down decoding. Sinks which must not miss
+ * any event (e.g. the output file) have a bounded channel of their own
+ * instead: when they lag behind, sending waits for them.
+ */
+#[derive(Debug, Clone)]
+pub struct Publisher {
+    broadcast: broadcast::Sender<Event>,
+    lossless: Vec<mpsc::Sender<Event>>,
+}
+
+impl Default for Publisher {
+    fn default() -> Self {
+        let (broadcast, _) = broadcast::channel(CAPACITY);
+        Publisher {
+            broadcast,
+            lossless: Vec::new(),
+        }
+    }
+}
+
+impl Publisher {
+    /// Subscribe to the broadcast channel, missing events when lagging
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.broadcast.subscribe()
+    }
+
+    /// The broadcast channel, for consumers subscribing later (e.g. clients)
+    pub fn broadcast(&self) -> broadcast::Sender<Event> {
+        self.broadcast.clone()
+    }
+
+    /// A channel of its own, for a sink which must not miss any event
+    ///
+    /// Only clones of the publisher made afterwards send to this channel.
+    pub fn subscribe_lossless(&mut self) -> mpsc::Receiver<Event> {
+        let (tx, rx) = mpsc::channel(CAPACITY);
+        self.lossless.push(tx);
+        rx
+    }
+
+    /// Send an event to all sinks, waiting for lossless ones to have room
+    pub async fn send(&self, event: Event) {
+        for tx in &self.lossless {
+            // The sink stopped: there is nothing to wait for
+            let _ = tx.send(event.clone()).await;
+        }
+        // Having no subscriber is not an error
+        let _ = self.broadcast.send(event);
+    }
+
+    /// True if all sinks received the events already sent
+    pub fn is_empty(&self) -> bool {
+        self.broadcast.is_empty()
+            && self
+                .lossless
+                .iter()
+                .all(|tx| tx.is_closed() || tx.capacity() == tx.max_capacity())
+    }
+}
+
+/// The sensor concerned by a [`Event::SensorUp`] or [`Event::SensorDown`]
+#[derive(Debug, Clone, Serialize)]
+pub struct SensorStatus {
+    pub serial: u64,
+    pub name: Option<String>,
+    /// The timestamp of the change of status
+    pub timestamp: u64,
+}
+
+/**
+ * The activity of a sensor over the last heartbeat interval, so that
+ * consumers tell a quiet sky from a dead feed.
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct Heartbeat {
+    pub serial: u64,
+    pub name: Option<String>,
+    pub timestamp: u64,
+    /// Seconds since the last message, if any message was ever received
+    pub last_message_age: Option<u64>,
+    /// Number of messages received since the previous heartbeat
+    pub messages_in_interval: u64,
+}
+
+/**
+ * Alerts raised while decoding messages.
+ */
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "alert", rename_all = "snake_case")]
+pub enum Alert {
+    /// An emergency squawk code: 7500 (hijacking), 7600 (radio failure) or
+    /// 7700 (general emergency)
+    Squawk {
+        icao24: ICAO,
+        squawk: IdentityCode,
+        timestamp: u64,
+    },
+    /// A velocity (BDS 0,9) inconsistent with the velocity derived from
+    /// successive positions, e.g. a corrupted message or a spoofed track
+    VelocityInconsistent {
+        icao24: ICAO,
+        /// The ground speed (in kts) and track (in degrees) in the message
+        groundspeed: f64,
+        track: f64,
+        /// The ground speed and track derived from successive positions
+        derived_groundspeed: f64,
+        derived_track: f64,
+        timestamp: u64,
+    },
+}
+
+impl Alert {
+    /// An alert if the squawk code is an emergency code
+    pub fn squawk(
+        icao24: ICAO,
+        squawk: IdentityCode,
+        timestamp: u64,
+    ) -> Option<Alert> {
+        // Squawk codes are stored as their octal digits, i.e. 7700 is 0x7700
+        [0x7500, 0x7600, 0x7700]
+            .contains(&squawk.0)
+            .then_some(Alert::Squawk {
+                icao24,
+                squawk,
+                timestamp,
+            })
+    }
+
+    pub fn icao24(&self) -> &ICAO {
+        match self {
+            Alert::Squawk { icao24, .. }
+            | Alert::VelocityInconsistent { icao24, .. } => icao24,
+        }
+    }
+}
+
+/// The JSON format of events other than messages and flight summaries
+#[derive(Serialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum Record<'a> {
+    Sensor {
+        status: &'static str,
+        #[serde(flatten)]
+        sensor: &'a SensorStatus,
+    },
+    Alert(&'a Alert),
+    Heartbeat(&'a Heartbeat),
+}
+
+impl Event {
+    /**
+     * Serialize the event as a JSON line.
+     *
+     * Messages and flight summaries keep their own format (with the fields
+     * added by the script on messages); other events come with a `record`
+     * field, like flight summaries.
+     */
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        match self {
+            Event::Message(msg, _, None) => serde_json::to_string(msg.as_ref()),
+            Event::Message(msg, _, Some(outcome)) => {
+                outcome.extend(serde_json::to_string(msg.as_ref())?)
+            }
+            Event::SensorUp(sensor) => serde_json::to_string(&Record::Sensor {
+                status: "up",
+                sensor,
+            }),
+            Event::SensorDown(sensor) => {
+                serde_json::to_string(&Record::Sensor {
+                    status: "down",
+                    sensor,
+                })
+            }
+            Event::Alert(alert) => serde_json::to_string(&Record::Alert(alert)),
+            Event::FlightClosed(summary) => {
+                serde_json::to_string(summary.as_ref())
+            }
+            Event::Heartbeat(heartbeat) => {
+                serde_json::to_string(&Record::Heartbeat(heartbeat))
+            }
+        }
+    }
+
+    /// True if the script kept the message from the sink (by its name)
+    pub fn is_vetoed(&self, sink: &str) -> bool {
+        match self {
+            Event::Message(_, _, Some(outcome)) => outcome.vetoes(sink),
+            _ => false,
+        }
+    }
+
+    /// True if the event passes the downlink format and aircraft filters
+    pub fn is_in(&self, filters: &Filters) -> bool {
+        match self {
+            Event::Message(msg, ..) => Filters::is_in(filters, msg),
+            Event::Alert(alert) => {
+                Filters::aircraft_in(filters, alert.icao24())
+            }
+            Event::FlightClosed(summary) => summary
+                .icao24
+                .parse()
+                .is_ok_and(|icao24| Filters::aircraft_in(filters, &icao24)),
+            Event::SensorUp(_) | Event::SensorDown(_) | Event::Heartbeat(_) => {
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let sensor = SensorStatus {
+            serial: 42,
+            name: Some("home".to_string()),
+            timestamp: 1700000000,
+        };
+        assert_eq!(
+            Event::SensorDown(sensor).to_json().unwrap(),
+            r#"{"record":"sensor","status":"down","serial":42,"name":"home","timestamp":1700000000}"#
+        );
+
+        assert!(
+            Alert::squawk(ICAO(0x406b90), IdentityCode(0x1000), 0).is_none()
+        );
+        let alert =
+            Alert::squawk(ICAO(0x406b90), IdentityCode(0x7700), 1700000000)
+                .unwrap();
+        assert_eq!(
+            Event::Alert(alert).to_json().unwrap(),
+            r#"{"record":"alert","alert":"squawk","icao24":"406b90","squawk":"7700","timestamp":1700000000}"#
+        );
+
+        let heartbeat = Heartbeat {
+            serial: 42,
+            name: None,
+            timestamp: 1700000000,
+            last_message_age: Some(3),
+            messages_in_interval: 0,
+        };
+        assert_eq!(
+            Event::Heartbeat(heartbeat).to_json().unwrap(),
+            r#"{"record":"heartbeat","serial":42,"name":null,"timestamp":1700000000,"last_message_age":3,"messages_in_interval":0}"#
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use rs1090::decode::{TimedMessage, ICAO};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Filters {
     pub df_filter: Option<Vec<String>>,
     pub aircraft_filter: Option<Vec<ICAO>>,
@@ -9,12 +9,20 @@ pub struct Filters {
 }
 
 impl Filters {
-    fn aircraft_in<T>(filter: &Filters, icao24: &T) -> bool
-    where
-        T: Copy + Into<ICAO>,
-    {
+    pub fn new(
+        df_filter: Option<Vec<u16>>,
+        aircraft_filter: Option<Vec<ICAO>>,
+    ) -> Self {
+        Filters {
+            df_filter: df_filter
+                .map(|df| df.into_iter().map(|v| format!("{}", v)).collect()),
+            aircraft_filter,
+        }
+    }
+
+    pub fn aircraft_in(filter: &Filters, icao24: &ICAO) -> bool {
         if let Some(filter) = &filter.aircraft_filter {
-            if filter.contains(&(*icao24).into()) {
+            if filter.contains(icao24) {
                 return true;
             }
             return filter.is_empty();
@@ -33,68 +41,19 @@ impl Filters {
     }
 
     pub fn is_in(filter: &Filters, msg: &TimedMessage) -> bool {
-        if let Some(msg) = &msg.message {
-            match &msg.df {
-                rs1090::decode::DF::ShortAirAirSurveillance { ap, .. } => {
-                    if Self::aircraft_in(filter, ap) {
-                        return Self::df_in(filter, "0");
-                    }
-                }
-                rs1090::decode::DF::SurveillanceAltitudeReply {
-                    ap, ..
-                } => {
-                    if Self::aircraft_in(filter, ap) {
-                        return Self::df_in(filter, "4");
-                    }
-                }
-                rs1090::decode::DF::SurveillanceIdentityReply {
-                    ap, ..
-                } => {
-                    if Self::aircraft_in(filter, ap) {
-                        return Self::df_in(filter, "5");
-                    }
-                }
-                rs1090::decode::DF::AllCallReply { icao, .. } => {
-                    if Self::aircraft_in(filter, icao) {
-                        return Self::df_in(filter, "11");
-                    }
-                }
-                rs1090::decode::DF::LongAirAirSurveillance { ap, .. } => {
-                    if Self::aircraft_in(filter, ap) {
-                        return Self::df_in(filter, "16");
-                    }
-                }
-                rs1090::decode::DF::ExtendedSquitterADSB(adsb) => {
-                    if Self::aircraft_in(filter, &adsb.icao24) {
-                        return Self::df_in(filter, "17");
-                    }
-                }
-                rs1090::decode::DF::ExtendedSquitterTisB { pi, .. } => {
-                    if Self::aircraft_in(filter, pi) {
-                        return Self::df_in(filter, "18");
-                    }
-                }
-                rs1090::decode::DF::ExtendedSquitterMilitary { .. } => {
-                    return Self::df_in(filter, "19");
-                }
-                rs1090::decode::DF::CommBAltitudeReply { ap, .. } => {
-                    if Self::aircraft_in(filter, ap) {
-                        return Self::df_in(filter, "20");
-                    }
-                }
-                rs1090::decode::DF::CommBIdentityReply { ap, .. } => {
-                    if Self::aircraft_in(filter, ap) {
-                        return Self::df_in(filter, "21");
-                    }
-                }
-                rs1090::decode::DF::CommDExtended { parity, .. } => {
-                    if Self::aircraft_in(filter, parity) {
-                        return Self::df_in(filter, "24");
-                    }
-                }
+        let (Some(message), Some(first)) = (&msg.message, msg.frame.first())
+        else {
+            return false;
+        };
+        // Messages without an address (DF19) are only filtered on their DF
+        if let Some(icao24) = message.icao24() {
+            if !Self::aircraft_in(filter, &icao24) {
+                return false;
             }
         }
-        false
+        // All downlink formats starting with 11 are DF24 (Comm-D)
+        let df = (first >> 3).min(24);
+        Self::df_in(filter, &df.to_string())
     }
 }
 
@@ -102,12 +61,13 @@ impl Filters {
 mod tests {
 
     use super::*;
+    use rs1090::decode::time::Timestamp;
     use rs1090::decode::Message;
 
     #[test]
     fn test_filter() {
         let mut tmsg = TimedMessage {
-            timestamp: 0.,
+            timestamp: Timestamp::default(),
             frame: hex::decode("8c4841753a9a153237aef0f275be").unwrap(),
             message: None,
             metadata: vec![],
@@ -161,7 +121,7 @@ mod tests {
         assert!(!Filters::is_in(&filter, &tmsg));
 
         let mut tmsg = TimedMessage {
-            timestamp: 1735943148.353877,
+            timestamp: Timestamp::from_nanos(1_735_943_148_353_877_000),
             frame: hex::decode("02c18c3b323e4f").unwrap(),
             message: None,
             metadata: vec![],
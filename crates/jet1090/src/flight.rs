@@ -0,0 +1,193 @@
+use std::collections::BTreeSet;
+
+use rs1090::decode::cpr::haversine;
+use serde::Serialize;
+
+use crate::snapshot::Snapshot;
+
+/**
+ * A summary of a flight, emitted when the aircraft expires.
+ *
+ * Summaries make a lightweight flight log for long-running deployments,
+ * without storing the full history of the aircraft.
+ */
+#[derive(Debug, Serialize)]
+#[serde(tag = "record", rename = "flight")]
+pub struct FlightSummary {
+    /// The ICAO 24-bit address of the aircraft transponder
+    pub icao24: String,
+    /// The tail number of the aircraft, if known
+    pub registration: Option<String>,
+    /// The ICAO code to the type of aircraft, if known
+    pub typecode: Option<String>,
+    /// The timestamp of the first seen message
+    pub firstseen: u64,
+    /// The timestamp of the last seen message
+    pub lastseen: u64,
+    /// All the callsigns used during the flight, in order of appearance
+    pub callsigns: Vec<String>,
+    /// Maximum barometric altitude in feet
+    pub max_altitude: Option<u16>,
+    /// Distance flown between the decoded positions, in kilometers
+    pub distance: f64,
+    /// The identifiers of the sensors which received the aircraft
+    pub sensors: Vec<u64>,
+    /// Number of messages received for the aircraft
+    pub count: usize,
+    /// The logical track of the aircraft, shared with the previous segments
+    /// of the trajectory under other addresses (heuristic)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<String>,
+}
+
+/**
+ * Accumulate the information needed for a flight summary.
+ *
+ * The log is updated with every message, so that the summary does not
+ * depend on the history stored for the aircraft (which expires earlier,
+ * when it is stored at all).
+ */
+#[derive(Debug, Default)]
+pub struct FlightLog {
+    callsigns: Vec<String>,
+    max_altitude: Option<u16>,
+    distance: f64,
+    sensors: BTreeSet<u64>,
+    last_position: Option<(f64, f64)>,
+}
+
+impl FlightLog {
+    /// Update the log with the latest state of the aircraft
+    pub fn update(&mut self, cur: &Snapshot) {
+        if let Some(callsign) = &cur.state.callsign {
+            if !self.callsigns.contains(callsign) {
+                self.callsigns.push(callsign.to_string());
+            }
+        }
+        if let Some(altitude) = cur.state.altitude {
+            self.max_altitude = self.max_altitude.max(Some(altitude));
+        }
+        if let (Some(lat), Some(lon)) =
+            (cur.state.latitude, cur.state.longitude)
+        {
+            if let Some((last_lat, last_lon)) = self.last_position {
+                self.distance += haversine(last_lat, last_lon, lat, lon);
+            }
+            self.last_position = Some((lat, lon));
+        }
+        for meta in &cur.metadata {
+            self.sensors.insert(meta.serial);
+        }
+    }
+
+    pub fn summary(self, cur: Snapshot) -> FlightSummary {
+        FlightSummary {
+            icao24: cur.icao24,
+            registration: cur.registration,
+            typecode: cur.typecode,
+            firstseen: cur.firstseen,
+            lastseen: cur.lastseen,
+            callsigns: self.callsigns,
+            max_altitude: self.max_altitude,
+            distance: self.distance,
+            sensors: self.sensors.into_iter().collect(),
+            count: cur.count,
+            track_id: cur.track_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::TargetClass;
+    use rs1090::data::patterns::Allocation;
+    use rs1090::decode::time::Timestamp;
+    use rs1090::decode::SensorMetadata;
+    use rs1090::tracker::State;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            icao24: "39b415".to_string(),
+            firstseen: 1_700_000_000,
+            lastseen: 1_700_000_000,
+            registration: Some("F-HNAV".to_string()),
+            typecode: None,
+            operator: None,
+            photo: None,
+            acas_capability: None,
+            wtc: None,
+            engine: None,
+            class: TargetClass::Aircraft,
+            allocation: Allocation::Civil,
+            mode_s_only: false,
+            state: State::default(),
+            altitude_qnh_corrected: None,
+            qnh: None,
+            qnh_source: None,
+            position_failure: None,
+            derived: false,
+            velocity_inconsistent: false,
+            phase: None,
+            height: None,
+            ecef: None,
+            enu: None,
+            display_label: "F-HNAV".to_string(),
+            label_offset: 0,
+            count: 0,
+            metadata: vec![],
+            num_receivers: 0,
+            track_id: None,
+            stitched_from: None,
+            smoothed: None,
+            version: 0,
+        }
+    }
+
+    #[test]
+    fn test_flight_log() {
+        let mut log = FlightLog::default();
+        let mut cur = snapshot();
+        let states = [
+            (Some("AFR123"), Some(2000), Some((43.6, 1.4)), 1),
+            (Some("AFR123"), Some(3000), None, 2),
+            (Some("AFR123"), Some(3000), Some((43.7, 1.4)), 1),
+            (Some("AFR456"), Some(2500), Some((43.8, 1.4)), 1),
+        ];
+        for (callsign, altitude, position, serial) in states {
+            cur.state.callsign = callsign.map(|c| c.to_string());
+            cur.state.altitude = altitude;
+            // Positions are kept in the snapshot until the next update
+            if let Some((lat, lon)) = position {
+                cur.state.latitude = Some(lat);
+                cur.state.longitude = Some(lon);
+            }
+            cur.metadata = vec![SensorMetadata {
+                system_timestamp: Timestamp::default(),
+                gnss_timestamp: None,
+                nanoseconds: None,
+                rssi: None,
+                serial,
+                name: None,
+                time_source: None,
+                clock_error: None,
+                timestamp_error: None,
+            }];
+            cur.lastseen += 10;
+            cur.count += 1;
+            log.update(&cur);
+        }
+
+        let summary = log.summary(cur);
+        assert_eq!(summary.callsigns, vec!["AFR123", "AFR456"]);
+        assert_eq!(summary.max_altitude, Some(3000));
+        assert_eq!(summary.sensors, vec![1, 2]);
+        assert_eq!(summary.lastseen, 1_700_000_040);
+        // 0.2° of latitude, no distance added without a new position
+        assert!((summary.distance - 22.24).abs() < 0.01);
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["record"], "flight");
+        assert_eq!(json["icao24"], "39b415");
+    }
+}
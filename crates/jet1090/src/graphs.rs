@@ -0,0 +1,285 @@
+use rs1090::decode::cpr::haversine;
+use rs1090::prelude::*;
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Duration of each bucket (in seconds)
+pub const BUCKET: u64 = 300;
+/// Number of buckets kept in the history (two weeks)
+pub const CAPACITY: usize = 14 * 24 * 3600 / BUCKET as usize;
+
+/// The header of the history file, with the version of the format
+const MAGIC: &[u8; 4] = b"J1G1";
+/// Size of each bucket in the history file
+const RECORD: usize = 28;
+
+/**
+ * Reception statistics over a bucket of [`BUCKET`] seconds.
+ *
+ * Buckets are stored in a compact binary file: after a `J1G1` header, each
+ * bucket is written on 28 bytes (little endian): timestamp (u64), messages
+ * (u64), maximum range (f32), aircraft (u32) and CPU usage (f32, NaN if
+ * unknown).
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Bucket {
+    /// The start of the bucket, in seconds since the epoch
+    pub timestamp: u64,
+    /// Number of messages received by all the sensors
+    pub messages: u64,
+    /// Maximum distance (in km) between a sensor and the airborne positions
+    /// it received, 0 if no position was decoded
+    pub max_range: f32,
+    /// Number of different aircraft seen
+    pub aircraft: u32,
+    /// Average CPU usage of the process (in percent of a core), if known
+    pub cpu: Option<f32>,
+}
+
+impl Bucket {
+    fn to_bytes(self) -> [u8; RECORD] {
+        let mut bytes = [0; RECORD];
+        bytes[0..8].copy_from_slice(&self.timestamp.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.messages.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.max_range.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.aircraft.to_le_bytes());
+        let cpu = self.cpu.unwrap_or(f32::NAN);
+        bytes[24..28].copy_from_slice(&cpu.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let u64_at =
+            |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        let u32_at =
+            |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let cpu = f32::from_bits(u32_at(24));
+        Bucket {
+            timestamp: u64_at(0),
+            messages: u64_at(8),
+            max_range: f32::from_bits(u32_at(16)),
+            aircraft: u32_at(20),
+            cpu: (!cpu.is_nan()).then_some(cpu),
+        }
+    }
+}
+
+/// The CPU time (user and system, in seconds) consumed by the process
+#[cfg(unix)]
+fn cpu_time() -> Option<f64> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    let seconds =
+        |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1e6;
+    Some(seconds(usage.ru_utime) + seconds(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+fn cpu_time() -> Option<f64> {
+    None
+}
+
+/**
+ * The history of reception statistics, in buckets of [`BUCKET`] seconds, so
+ * that long-term performance graphs of the receivers can be drawn without
+ * any external time-series database.
+ *
+ * The history is saved to a file each time a bucket is complete, and read
+ * again at startup.
+ */
+#[derive(Debug)]
+pub struct Graphs {
+    path: Option<PathBuf>,
+    buckets: VecDeque<Bucket>,
+    current: Bucket,
+    aircraft: HashSet<ICAO>,
+    /// The CPU time at the start of the current bucket
+    cpu_start: Option<f64>,
+}
+
+impl Graphs {
+    /// Start a history, with the buckets saved in a previous run if any
+    pub fn new(path: Option<PathBuf>, now: u64) -> Self {
+        let buckets = match &path {
+            Some(path) if path.exists() => load(path).unwrap_or_else(|e| {
+                warn!("Failed to read {}: {}", path.display(), e);
+                VecDeque::new()
+            }),
+            _ => VecDeque::new(),
+        };
+        Graphs {
+            path,
+            buckets,
+            current: Bucket {
+                timestamp: now - now % BUCKET,
+                ..Bucket::default()
+            },
+            aircraft: HashSet::new(),
+            cpu_start: cpu_time(),
+        }
+    }
+
+    /**
+     * Record a message, with the references of the sensors which received
+     * it (for the range).
+     */
+    pub fn record<'a>(
+        &mut self,
+        msg: &TimedMessage,
+        now: u64,
+        references: impl Iterator<Item = &'a Position>,
+    ) {
+        self.tick(now);
+        self.current.messages += 1;
+        let Some(message) = &msg.message else {
+            return;
+        };
+        if let Some(icao24) = message.icao24() {
+            self.aircraft.insert(icao24);
+        }
+        if let ExtendedSquitterADSB(ADSB {
+            message:
+                ME::BDS05(AirbornePosition {
+                    latitude: Some(latitude),
+                    longitude: Some(longitude),
+                    ..
+                }),
+            ..
+        }) = message.df
+        {
+            for reference in references {
+                let range = haversine(
+                    reference.latitude,
+                    reference.longitude,
+                    latitude,
+                    longitude,
+                ) as f32;
+                self.current.max_range = self.current.max_range.max(range);
+            }
+        }
+    }
+
+    /// Close the current bucket if it is complete, and save the history
+    pub fn tick(&mut self, now: u64) {
+        if now < self.current.timestamp + BUCKET {
+            return;
+        }
+        let cpu_end = cpu_time();
+        let mut bucket = self.current;
+        bucket.aircraft = self.aircraft.len() as u32;
+        if let (Some(start), Some(end)) = (self.cpu_start, cpu_end) {
+            let elapsed = (now - bucket.timestamp) as f64;
+            bucket.cpu = Some((100. * (end - start) / elapsed) as f32);
+        }
+        if self.buckets.len() == CAPACITY {
+            self.buckets.pop_front();
+        }
+        self.buckets.push_back(bucket);
+
+        self.current = Bucket {
+            timestamp: now - now % BUCKET,
+            ..Bucket::default()
+        };
+        self.aircraft.clear();
+        self.cpu_start = cpu_end;
+
+        if let Some(path) = &self.path {
+            if let Err(e) = save(path, &self.buckets) {
+                warn!("Failed to write {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// The complete buckets, oldest first
+    pub fn buckets(&self) -> &VecDeque<Bucket> {
+        &self.buckets
+    }
+}
+
+fn load(path: &Path) -> io::Result<VecDeque<Bucket>> {
+    let content = fs::read(path)?;
+    let Some(records) = content.strip_prefix(MAGIC) else {
+        let message = "not a history file (or unknown version)";
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    };
+    let mut buckets: VecDeque<Bucket> = records
+        .chunks_exact(RECORD)
+        .map(Bucket::from_bytes)
+        .collect();
+    // The file may come from a version keeping a longer history
+    while buckets.len() > CAPACITY {
+        buckets.pop_front();
+    }
+    Ok(buckets)
+}
+
+/// Write the history in a temporary file first, so that it is never truncated
+fn save(path: &Path, buckets: &VecDeque<Bucket>) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut content = Vec::with_capacity(MAGIC.len() + buckets.len() * RECORD);
+    content.extend_from_slice(MAGIC);
+    for bucket in buckets {
+        content.extend_from_slice(&bucket.to_bytes());
+    }
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    fs::write(&partial, content)?;
+    fs::rename(&partial, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graphs() {
+        let path = std::env::temp_dir().join("jet1090_test_graphs.bin");
+        let _ = fs::remove_file(&path);
+        let reference = Position {
+            latitude: 43.6,
+            longitude: 1.4,
+        };
+
+        let mut graphs = Graphs::new(Some(path.clone()), 1_700_000_100);
+        let bytes = hex::decode("8d40621d58c382d690c8ac2863a7").unwrap();
+        let mut message = Message::try_from(bytes.as_slice()).unwrap();
+        if let ExtendedSquitterADSB(adsb) = &mut message.df {
+            if let ME::BDS05(position) = &mut adsb.message {
+                position.latitude = Some(44.6);
+                position.longitude = Some(1.4);
+            }
+        }
+        let msg = TimedMessage {
+            timestamp: Timestamp::from_secs_f64(1_700_000_100.),
+            frame: bytes,
+            message: Some(message),
+            metadata: vec![],
+            decode_time: None,
+        };
+        graphs.record(&msg, 1_700_000_100, [&reference].into_iter());
+        graphs.record(&msg, 1_700_000_200, std::iter::empty());
+        assert!(graphs.buckets().is_empty());
+
+        // The bucket is complete, and saved
+        graphs.tick(1_700_000_400);
+        let bucket = graphs.buckets()[0];
+        assert_eq!(bucket.timestamp, 1_700_000_100 - 1_700_000_100 % BUCKET);
+        assert_eq!((bucket.messages, bucket.aircraft), (2, 1));
+        assert_eq!(bucket.max_range.round(), 111.);
+
+        let graphs = Graphs::new(Some(path.clone()), 1_700_000_400);
+        assert_eq!(graphs.buckets().len(), 1);
+        assert_eq!(graphs.buckets()[0], bucket);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 4 + 28);
+
+        fs::remove_file(path).unwrap();
+    }
+}
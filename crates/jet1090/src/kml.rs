@@ -0,0 +1,208 @@
+use std::fmt::Write;
+
+use rs1090::geom::FT;
+use rs1090::prelude::Position;
+
+use crate::report::escape;
+use crate::snapshot::Snapshot;
+
+/// Mean Earth radius, in km
+const RADIUS: f64 = 6371.;
+/// Radius of the rings around sensors, in km
+const RINGS: [f64; 4] = [50., 100., 200., 400.];
+/// Number of segments to draw a ring
+const SEGMENTS: usize = 72;
+
+/// Styles of aircraft by barometric altitude (in ft): name, upper bound and
+/// color (in KML notation, i.e. aabbggrr)
+const ALTITUDES: [(&str, u16, &str); 4] = [
+    ("low", 10_000, "ff00c8ff"),
+    ("medium", 20_000, "ff00ff7f"),
+    ("high", 30_000, "ffffbf00"),
+    ("cruise", u16::MAX, "ffff3fbf"),
+];
+
+const AIRCRAFT_ICON: &str =
+    "https://maps.google.com/mapfiles/kml/shapes/airports.png";
+const SENSOR_ICON: &str =
+    "https://maps.google.com/mapfiles/kml/shapes/target.png";
+
+/**
+ * A KML document loading the live traffic from `href`, to add once in
+ * Google Earth: the traffic is then refreshed every `refresh` seconds.
+ */
+pub fn network_link(href: &str, refresh: u64) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <kml xmlns=\"http://www.opengis.net/kml/2.2\"><NetworkLink>\
+        <name>jet1090</name><open>1</open>\
+        <Link><href>{}</href><refreshMode>onInterval</refreshMode>\
+        <refreshInterval>{refresh}</refreshInterval></Link>\
+        </NetworkLink></kml>\n",
+        escape(href)
+    )
+}
+
+/**
+ * A KML document with the aircraft with a known position, styled by
+ * altitude, and the sensors with a known position, with range rings.
+ *
+ * Aircraft are placed at their barometric altitude (on the ground when
+ * unknown) and oriented along their track.
+ */
+pub fn document<'a>(
+    aircraft: impl IntoIterator<Item = &'a Snapshot>,
+    sensors: impl IntoIterator<Item = (String, Position)>,
+) -> String {
+    let mut kml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <kml xmlns=\"http://www.opengis.net/kml/2.2\"><Document>\
+        <name>jet1090</name>",
+    );
+    for (name, _, color) in ALTITUDES.iter().chain([&("ground", 0, "ff7f7f7f")])
+    {
+        let _ = write!(
+            kml,
+            "<Style id=\"{name}\"><IconStyle><color>{color}</color>\
+            <Icon><href>{AIRCRAFT_ICON}</href></Icon></IconStyle>\
+            <LabelStyle><scale>0.7</scale></LabelStyle></Style>"
+        );
+    }
+    let _ = write!(
+        kml,
+        "<Style id=\"sensor\"><IconStyle><Icon><href>{SENSOR_ICON}</href>\
+        </Icon></IconStyle></Style><Style id=\"ring\"><LineStyle>\
+        <color>7fffffff</color><width>1</width></LineStyle></Style>"
+    );
+
+    kml.push_str("<Folder><name>Sensors</name>");
+    for (name, position) in sensors {
+        let _ = write!(
+            kml,
+            "<Placemark><name>{}</name><styleUrl>#sensor</styleUrl>\
+            <Point><coordinates>{},{}</coordinates></Point></Placemark>",
+            escape(&name),
+            position.longitude,
+            position.latitude
+        );
+        for radius in RINGS {
+            let coordinates: Vec<String> = (0..=SEGMENTS)
+                .map(|i| {
+                    let bearing = (i * 360 / SEGMENTS) as f64;
+                    let (lat, lon) = destination(&position, bearing, radius);
+                    format!("{lon:.5},{lat:.5}")
+                })
+                .collect();
+            let _ = write!(
+                kml,
+                "<Placemark><name>{radius} km</name>\
+                <styleUrl>#ring</styleUrl><LineString><tessellate>1\
+                </tessellate><coordinates>{}</coordinates></LineString>\
+                </Placemark>",
+                coordinates.join(" ")
+            );
+        }
+    }
+    kml.push_str("</Folder><Folder><name>Aircraft</name>");
+    for sv in aircraft {
+        let (Some(latitude), Some(longitude)) =
+            (sv.state.latitude, sv.state.longitude)
+        else {
+            continue;
+        };
+        let name = sv.state.callsign.as_deref().unwrap_or(&sv.icao24).trim();
+        let (style, point) = match sv.state.altitude {
+            Some(altitude) => (
+                ALTITUDES
+                    .iter()
+                    .find(|(_, max, _)| altitude < *max)
+                    .map_or("cruise", |(name, _, _)| name),
+                format!(
+                    "<altitudeMode>absolute</altitudeMode>\
+                    <coordinates>{longitude},{latitude},{:.0}</coordinates>",
+                    altitude as f64 * FT
+                ),
+            ),
+            None => (
+                "ground",
+                format!("<coordinates>{longitude},{latitude}</coordinates>"),
+            ),
+        };
+        let _ = write!(
+            kml,
+            "<Placemark><name>{}</name><description>{}</description>\
+            <styleUrl>#{style}</styleUrl>",
+            escape(name),
+            sv.icao24
+        );
+        if let Some(track) = sv.state.track {
+            let _ = write!(
+                kml,
+                "<Style><IconStyle><heading>{track:.0}</heading>\
+                </IconStyle></Style>"
+            );
+        }
+        let _ = write!(kml, "<Point>{point}</Point></Placemark>");
+    }
+    kml.push_str("</Folder></Document></kml>\n");
+    kml
+}
+
+/// The position at a distance (in km) and bearing (in degrees) of another
+fn destination(origin: &Position, bearing: f64, distance: f64) -> (f64, f64) {
+    let (lat0, lon0) = (origin.latitude.to_radians(), origin.longitude);
+    let delta = distance / RADIUS;
+    let (sin, cos) = bearing.to_radians().sin_cos();
+    let lat =
+        (lat0.sin() * delta.cos() + lat0.cos() * delta.sin() * cos).asin();
+    let dlon = (sin * delta.sin() * lat0.cos())
+        .atan2(delta.cos() - lat0.sin() * lat.sin());
+    (lat.to_degrees(), lon0 + dlon.to_degrees())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enrich::Enricher;
+    use crate::snapshot::StateVectors;
+    use rs1090::decode::cpr::haversine;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_kml() {
+        let toulouse = Position {
+            latitude: 43.63,
+            longitude: 1.37,
+        };
+        let (lat, lon) = destination(&toulouse, 90., 100.);
+        assert!((haversine(43.63, 1.37, lat, lon) - 100.).abs() < 0.1);
+        assert!((lat - 43.63).abs() < 0.1 && lon > 1.37);
+
+        let mut sv = StateVectors::new(
+            1_700_000_000,
+            "39b415".to_string(),
+            &BTreeMap::new(),
+            &Enricher::default(),
+        );
+        let no_position = sv.cur.clone();
+        sv.cur.state.latitude = Some(43.7);
+        sv.cur.state.longitude = Some(1.5);
+        sv.cur.state.altitude = Some(24000);
+        sv.cur.state.track = Some(271.3);
+        sv.cur.state.callsign = Some("AFR12<3".to_string());
+
+        let kml = document(
+            [&sv.cur, &no_position],
+            [("rooftop & co".to_string(), toulouse)],
+        );
+        assert_eq!(kml.matches("<Placemark>").count(), 1 + RINGS.len() + 1);
+        assert!(kml.contains("<name>rooftop &amp; co</name>"));
+        assert!(kml.contains("<name>AFR12&lt;3</name>"));
+        assert!(kml.contains("<styleUrl>#high</styleUrl>"));
+        assert!(kml.contains("<coordinates>1.5,43.7,7315</coordinates>"));
+        assert!(kml.contains("<heading>271</heading>"));
+
+        let link = network_link("http://localhost:8080/kml/live", 5);
+        assert!(link.contains("<refreshInterval>5</refreshInterval>"));
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+
+use rs1090::prelude::*;
+use serde::Serialize;
+
+use crate::profile::Histogram;
+
+/// Checkpoint after the deduplication of the copies of a message
+pub const DEDUP: &str = "dedup";
+/// Checkpoint after the decoding of a message (with its position)
+pub const DECODE: &str = "decode";
+
+/// The latency histograms, only set when measuring latency
+static LATENCY: OnceLock<Latency> = OnceLock::new();
+
+/**
+ * The end-to-end latency of messages, from the time they are read from a
+ * source (the earliest copy, with the clock of the host computer) to the
+ * checkpoints of the pipeline and to each sink.
+ *
+ * Latencies are cumulative: the time spent in the deduplication buffer is
+ * included in all the checkpoints after it. Replayed messages (from files)
+ * are stamped with their original reception time and should not be
+ * measured.
+ */
+#[derive(Debug, Default)]
+pub struct Latency {
+    pipeline: Mutex<BTreeMap<&'static str, Histogram>>,
+    sinks: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+/// Percentiles of latency (upper bounds), in milliseconds
+#[derive(Debug, Serialize)]
+pub struct Percentiles {
+    pub count: u64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencyStats {
+    /// By checkpoint of the pipeline
+    pub pipeline: BTreeMap<&'static str, Percentiles>,
+    /// When messages are handled by each sink
+    pub sinks: BTreeMap<&'static str, Percentiles>,
+}
+
+impl From<&Histogram> for Percentiles {
+    fn from(h: &Histogram) -> Self {
+        let ms = |nanos: u64| nanos as f64 / 1e6;
+        Percentiles {
+            count: h.count(),
+            mean: ms(h.mean()),
+            p50: ms(h.quantile(0.5)),
+            p90: ms(h.quantile(0.9)),
+            p99: ms(h.quantile(0.99)),
+            max: ms(h.max()),
+        }
+    }
+}
+
+impl Latency {
+    /// The latency of a message at a given time, in nanoseconds
+    fn elapsed(msg: &TimedMessage, now: Timestamp) -> Option<u64> {
+        let read = msg.metadata.iter().map(|m| m.system_timestamp).min()?;
+        Some((now - read).as_nanos() as u64)
+    }
+
+    fn record(
+        histograms: &Mutex<BTreeMap<&'static str, Histogram>>,
+        name: &'static str,
+        msg: &TimedMessage,
+        now: Timestamp,
+    ) {
+        if let Some(nanos) = Self::elapsed(msg, now) {
+            histograms
+                .lock()
+                .unwrap()
+                .entry(name)
+                .or_default()
+                .record(nanos);
+        }
+    }
+
+    pub fn stats(&self) -> LatencyStats {
+        let percentiles = |h: &Mutex<BTreeMap<&'static str, Histogram>>| {
+            h.lock()
+                .unwrap()
+                .iter()
+                .map(|(name, h)| (*name, Percentiles::from(h)))
+                .collect()
+        };
+        LatencyStats {
+            pipeline: percentiles(&self.pipeline),
+            sinks: percentiles(&self.sinks),
+        }
+    }
+}
+
+/// Start measuring latency
+pub fn enable() {
+    let _ = LATENCY.set(Latency::default());
+}
+
+/// The latency statistics, if measured
+pub fn stats() -> Option<LatencyStats> {
+    LATENCY.get().map(Latency::stats)
+}
+
+/// Record the latency of a message at a checkpoint of the pipeline
+pub fn checkpoint(name: &'static str, msg: &TimedMessage) {
+    if let Some(latency) = LATENCY.get() {
+        Latency::record(&latency.pipeline, name, msg, Timestamp::now());
+    }
+}
+
+/// Record the latency of a message once handled by a sink
+pub fn sink(name: &'static str, msg: &TimedMessage) {
+    if let Some(latency) = LATENCY.get() {
+        Latency::record(&latency.sinks, name, msg, Timestamp::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency() {
+        let read = |secs: f64| SensorMetadata {
+            system_timestamp: Timestamp::from_secs_f64(secs),
+            gnss_timestamp: None,
+            nanoseconds: None,
+            rssi: None,
+            serial: 0,
+            name: None,
+            time_source: None,
+            clock_error: None,
+            timestamp_error: None,
+        };
+        let mut msg = TimedMessage {
+            timestamp: Timestamp::from_secs(1_700_000_000),
+            frame: vec![],
+            message: None,
+            metadata: vec![],
+            decode_time: None,
+        };
+        let now = Timestamp::from_secs(1_700_000_001);
+        assert_eq!(Latency::elapsed(&msg, now), None);
+
+        // From the earliest copy
+        msg.metadata = vec![read(1_700_000_000.8), read(1_700_000_000.5)];
+        assert_eq!(Latency::elapsed(&msg, now), Some(500_000_000));
+
+        let latency = Latency::default();
+        Latency::record(&latency.pipeline, DEDUP, &msg, now);
+        Latency::record(&latency.sinks, "file", &msg, now);
+        // Clocks may step back
+        Latency::record(&latency.sinks, "file", &msg, Timestamp::default());
+        let stats = latency.stats();
+        assert_eq!(stats.pipeline[DEDUP].count, 1);
+        assert_eq!(stats.pipeline[DEDUP].max, 500.);
+        assert_eq!(stats.sinks["file"].count, 2);
+        assert!(stats.sinks["file"].p50 < 1e-3);
+    }
+}
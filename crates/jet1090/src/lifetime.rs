@@ -0,0 +1,181 @@
+use rs1090::decode::AddressProvenance;
+use rs1090::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Interval (in seconds) between two saves of the counters
+pub const SAVE_PERIOD: u64 = 60;
+
+/// Counters over the whole life of an installation, as served on the REST API
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LifetimeStats {
+    /// The first start of jet1090 with this file, in seconds since the epoch
+    pub since: u64,
+    /// Number of times jet1090 was started
+    pub starts: u64,
+    /// Number of messages received by all the sensors
+    pub messages: u64,
+    /// Number of unique airframes seen
+    pub aircraft: u64,
+}
+
+/// The content of the file, with the addresses of all the airframes seen
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Content {
+    since: u64,
+    starts: u64,
+    messages: u64,
+    aircraft: BTreeSet<String>,
+}
+
+/**
+ * Counters of messages and unique airframes, surviving restarts.
+ *
+ * Only addresses announced in clear (all-call replies, extended squitters)
+ * are counted as airframes: addresses recovered from the parity of other
+ * messages may come from corrupted messages, which would add up over time.
+ *
+ * The counters are saved to a JSON file every [`SAVE_PERIOD`] seconds (the
+ * messages of the last period are lost if jet1090 stops meanwhile), and
+ * read again at startup.
+ */
+#[derive(Debug)]
+pub struct Lifetime {
+    path: Option<PathBuf>,
+    since: u64,
+    starts: u64,
+    messages: u64,
+    aircraft: HashSet<ICAO>,
+    /// The time of the last save
+    saved: u64,
+}
+
+impl Lifetime {
+    /// Resume the counters saved in a previous run if any
+    pub fn new(path: Option<PathBuf>, now: u64) -> Self {
+        let content = match &path {
+            Some(path) if path.exists() => load(path).unwrap_or_else(|e| {
+                warn!("Failed to read {}: {}", path.display(), e);
+                Content::default()
+            }),
+            _ => Content::default(),
+        };
+        let aircraft = content
+            .aircraft
+            .iter()
+            .filter_map(|icao24| u32::from_str_radix(icao24, 16).ok())
+            .map(ICAO)
+            .collect();
+        Lifetime {
+            path,
+            since: match content.since {
+                0 => now,
+                since => since,
+            },
+            starts: content.starts + 1,
+            messages: content.messages,
+            aircraft,
+            saved: now,
+        }
+    }
+
+    /// Count a message, and its airframe
+    pub fn record(&mut self, msg: &TimedMessage) {
+        self.messages += 1;
+        let address = msg.message.as_ref().and_then(Message::address);
+        if let Some((icao24, AddressProvenance::Announced)) = address {
+            self.aircraft.insert(icao24);
+        }
+    }
+
+    /// Save the counters if the last save is old enough
+    pub fn tick(&mut self, now: u64) {
+        if now < self.saved + SAVE_PERIOD {
+            return;
+        }
+        self.saved = now;
+        if let Some(path) = &self.path {
+            if let Err(e) = self.save(path) {
+                warn!("Failed to write {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> LifetimeStats {
+        LifetimeStats {
+            since: self.since,
+            starts: self.starts,
+            messages: self.messages,
+            aircraft: self.aircraft.len() as u64,
+        }
+    }
+
+    /// Write the counters in a temporary file first, so that it is never
+    /// truncated
+    fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = Content {
+            since: self.since,
+            starts: self.starts,
+            messages: self.messages,
+            aircraft: self.aircraft.iter().map(ICAO::to_string).collect(),
+        };
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        fs::write(&partial, serde_json::to_string(&content)?)?;
+        fs::rename(&partial, path)
+    }
+}
+
+fn load(path: &Path) -> io::Result<Content> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::timed;
+
+    #[test]
+    fn test_lifetime() {
+        let path = std::env::temp_dir().join("jet1090_test_lifetime.json");
+        let _ = fs::remove_file(&path);
+
+        let mut lifetime = Lifetime::new(Some(path.clone()), 1_700_000_000);
+        // Two extended squitters of the same aircraft
+        lifetime.record(&timed(
+            "8d40621d58c382d690c8ac2863a7",
+            1_700_000_000.,
+            1,
+        ));
+        lifetime.record(&timed(
+            "8d40621d58c386435cc412692ad6",
+            1_700_000_000.,
+            1,
+        ));
+        // The address of a surveillance reply comes from its parity
+        lifetime.record(&timed("20001838ca3804", 1_700_000_000., 1));
+        lifetime.tick(1_700_000_010);
+        assert!(!path.exists());
+        lifetime.tick(1_700_000_060);
+
+        let lifetime = Lifetime::new(Some(path.clone()), 1_700_000_100);
+        assert_eq!(
+            lifetime.stats(),
+            LifetimeStats {
+                since: 1_700_000_000,
+                starts: 2,
+                messages: 3,
+                aircraft: 1,
+            }
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::{HashMap, VecDeque};
+
+use rs1090::decode::cpr::haversine;
+use rs1090::geom::FT;
+use rs1090::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Number of samples kept for each sensor
+const CAPACITY: usize = 4096;
+/// Minimum number of samples before estimating a position
+const MIN_SAMPLES: usize = 100;
+/// Minimum duration (in s) between two samples of the same aircraft
+const SAMPLE_INTERVAL: u64 = 30;
+/// Distance (in km) above which a reference is considered misconfigured,
+/// on top of the uncertainty of the estimate
+pub const MISMATCH: f64 = 50.;
+/// Mean Earth radius, in meters
+const RADIUS: f64 = 6_371_000.;
+
+/// An airborne position received by a sensor
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// The altitude of the aircraft, in meters
+    pub altitude: f64,
+    /// The signal level, in dBFS
+    pub rssi: f32,
+}
+
+/// The estimated position of a sensor
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Estimate {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// The uncertainty (one standard deviation) of the position, in km
+    pub uncertainty: f64,
+    /// The number of samples used for the estimate
+    pub samples: usize,
+}
+
+impl Estimate {
+    /// The distance (in km) between the estimate and a given position
+    pub fn distance(&self, position: &Position) -> f64 {
+        haversine(
+            self.latitude,
+            self.longitude,
+            position.latitude,
+            position.longitude,
+        )
+    }
+
+    /// True if the position is too far from the estimate to be a reference
+    pub fn disagrees(&self, position: &Position) -> bool {
+        self.distance(position) > MISMATCH + 3. * self.uncertainty
+    }
+}
+
+/**
+ * Collect the airborne positions received by a sensor, in order to estimate
+ * the position of the sensor.
+ *
+ * Samples are taken at most every [`SAMPLE_INTERVAL`] seconds for each
+ * aircraft, so that a few aircraft close to the sensor do not weigh more
+ * than the rest of the traffic.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Locator {
+    samples: VecDeque<Sample>,
+    /// The timestamp of the last sample of each aircraft
+    last: HashMap<ICAO, u64>,
+}
+
+impl Locator {
+    /// Record the ADS-B airborne position of a message, if any
+    pub fn record_message(&mut self, msg: &TimedMessage, now: u64, rssi: f32) {
+        let Some(Message {
+            df: ExtendedSquitterADSB(adsb),
+            ..
+        }) = &msg.message
+        else {
+            return;
+        };
+        if let ME::BDS05(AirbornePosition {
+            latitude: Some(latitude),
+            longitude: Some(longitude),
+            alt: Some(altitude),
+            ..
+        }) = adsb.message
+        {
+            let sample = Sample {
+                latitude,
+                longitude,
+                altitude: altitude as f64 * FT,
+                rssi,
+            };
+            self.record(adsb.icao24, now, sample);
+        }
+    }
+
+    pub fn record(&mut self, icao24: ICAO, timestamp: u64, sample: Sample) {
+        if self
+            .last
+            .get(&icao24)
+            .is_some_and(|last| timestamp < last + SAMPLE_INTERVAL)
+        {
+            return;
+        }
+        self.last.insert(icao24, timestamp);
+        if self.samples.len() == CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn estimate(&mut self, now: u64) -> Option<Estimate> {
+        self.last.retain(|_, last| now < *last + SAMPLE_INTERVAL);
+        estimate(self.samples.make_contiguous())
+    }
+}
+
+/**
+ * Estimate the position of a sensor from the airborne positions it receives.
+ *
+ * The received power decreases with the square of the distance, so the
+ * signal level (in dBFS) is `c - 20 log10(d)` with `d` the slant range to
+ * the aircraft, and `c` an unknown constant depending on the transmitting
+ * power and on the gain of the receiving chain. The position of the sensor
+ * and this constant are solved with the Levenberg-Marquardt algorithm, in a
+ * local plane around the centroid of the samples.
+ *
+ * Transmitting powers differ between aircraft, so estimates are coarse (a
+ * few kilometers with enough samples all around the sensor); they are meant
+ * to suggest a reference position, or to detect a wrong one.
+ */
+pub fn estimate(samples: &[Sample]) -> Option<Estimate> {
+    if samples.len() < MIN_SAMPLES {
+        return None;
+    }
+    let n = samples.len() as f64;
+    let lat0 = samples.iter().map(|s| s.latitude).sum::<f64>() / n;
+    let lon0 = samples.iter().map(|s| s.longitude).sum::<f64>() / n;
+    let cos0 = lat0.to_radians().cos();
+
+    // Equirectangular projection around the centroid, in meters
+    let points: Vec<(f64, f64, f64, f64)> = samples
+        .iter()
+        .map(|s| {
+            let x = (s.longitude - lon0).to_radians() * cos0 * RADIUS;
+            let y = (s.latitude - lat0).to_radians() * RADIUS;
+            (x, y, s.altitude, s.rssi as f64)
+        })
+        .collect();
+
+    // Start from the centroid weighted by the received power
+    let weights: Vec<f64> =
+        points.iter().map(|p| 10f64.powf(p.3 / 10.)).collect();
+    let total: f64 = weights.iter().sum();
+    let mut x = points
+        .iter()
+        .zip(&weights)
+        .map(|(p, w)| p.0 * w)
+        .sum::<f64>()
+        / total;
+    let mut y = points
+        .iter()
+        .zip(&weights)
+        .map(|(p, w)| p.1 * w)
+        .sum::<f64>()
+        / total;
+    let mut c = points.iter().map(|p| level(p, x, y)).sum::<f64>() / n;
+
+    // Sum of the squared residuals, in dB
+    let cost = |x: f64, y: f64, c: f64| {
+        points
+            .iter()
+            .map(|p| (level(p, x, y) - c).powi(2))
+            .sum::<f64>()
+    };
+    let mut current = cost(x, y, c);
+    let mut lambda = 1e-3;
+    let mut normal = [[0.; 3]; 3];
+
+    for _ in 0..100 {
+        // Normal equations of the linearized problem
+        normal = [[0.; 3]; 3];
+        let mut gradient = [0.; 3];
+        for p in &points {
+            let d2 = distance(p, x, y).powi(2).max(1.);
+            let factor = 20. / std::f64::consts::LN_10 / d2;
+            let jacobian = [factor * (x - p.0), factor * (y - p.1), -1.];
+            let residual = level(p, x, y) - c;
+            for i in 0..3 {
+                gradient[i] -= jacobian[i] * residual;
+                for j in 0..3 {
+                    normal[i][j] += jacobian[i] * jacobian[j];
+                }
+            }
+        }
+        let mut damped = normal;
+        for (i, row) in damped.iter_mut().enumerate() {
+            row[i] *= 1. + lambda;
+        }
+        let step = solve(damped, gradient)?;
+        let next = cost(x + step[0], y + step[1], c + step[2]);
+        if next < current {
+            (x, y, c) = (x + step[0], y + step[1], c + step[2]);
+            let converged = (current - next) < 1e-9 * current;
+            current = next;
+            lambda /= 10.;
+            if converged {
+                break;
+            }
+        } else {
+            lambda *= 10.;
+        }
+    }
+
+    // Covariance of the position from the residual variance
+    let variance = current / (n - 3.);
+    let inverse = invert(normal)?;
+    let uncertainty = (variance * (inverse[0][0] + inverse[1][1])).sqrt();
+    if !(x.is_finite() && y.is_finite() && uncertainty.is_finite()) {
+        return None;
+    }
+    Some(Estimate {
+        latitude: lat0 + (y / RADIUS).to_degrees(),
+        longitude: lon0 + (x / RADIUS / cos0).to_degrees(),
+        uncertainty: uncertainty / 1e3,
+        samples: samples.len(),
+    })
+}
+
+/// The slant range between a sample and a position in the local plane
+fn distance(p: &(f64, f64, f64, f64), x: f64, y: f64) -> f64 {
+    ((p.0 - x).powi(2) + (p.1 - y).powi(2) + p.2.powi(2)).sqrt()
+}
+
+/// The signal level corrected for the path loss, constant in the model
+fn level(p: &(f64, f64, f64, f64), x: f64, y: f64) -> f64 {
+    p.3 + 20. * distance(p, x, y).max(1.).log10()
+}
+
+fn determinant(m: &[[f64; 3]; 3]) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+/// Solve a 3x3 linear system with the Cramer rule
+fn solve(m: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let det = determinant(&m);
+    if det.abs() < f64::EPSILON {
+        return None;
+    }
+    let mut x = [0.; 3];
+    for (i, xi) in x.iter_mut().enumerate() {
+        let mut mi = m;
+        for j in 0..3 {
+            mi[j][i] = b[j];
+        }
+        *xi = determinant(&mi) / det;
+    }
+    Some(x)
+}
+
+fn invert(m: [[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let mut inverse = [[0.; 3]; 3];
+    for i in 0..3 {
+        let mut e = [0.; 3];
+        e[i] = 1.;
+        let column = solve(m, e)?;
+        for j in 0..3 {
+            inverse[j][i] = column[j];
+        }
+    }
+    Some(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simulated traffic around a receiver, with various transmitting powers
+    fn traffic(latitude: f64, longitude: f64, count: usize) -> Vec<Sample> {
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        (0..count)
+            .map(|_| {
+                let range = 10e3 + 300e3 * random().sqrt();
+                let bearing = 360. * random();
+                let altitude = 3000. + 9000. * random();
+                let (sin, cos) = bearing.to_radians().sin_cos();
+                let slant = (range * range + altitude * altitude).sqrt();
+                // Up to 6 dB between transmitters, and measurement noise
+                let power = 6. * random() + 2. * random();
+                Sample {
+                    latitude: latitude + (range * cos / RADIUS).to_degrees(),
+                    longitude: longitude
+                        + (range * sin / RADIUS / latitude.to_radians().cos())
+                            .to_degrees(),
+                    altitude,
+                    rssi: (40. - 20. * slant.log10() + power) as f32,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_estimate() {
+        let samples = traffic(43.6, 1.4, 50);
+        assert!(estimate(&samples).is_none());
+
+        let samples = traffic(43.6, 1.4, 2000);
+        let estimate = estimate(&samples).unwrap();
+        let receiver = Position {
+            latitude: 43.6,
+            longitude: 1.4,
+        };
+        assert!(estimate.distance(&receiver) < 10.);
+        assert!(estimate.uncertainty < 10.);
+        assert!(!estimate.disagrees(&receiver));
+        // A reference at another airport
+        assert!(estimate.disagrees(&Position {
+            latitude: 48.72,
+            longitude: 2.38,
+        }));
+    }
+
+    #[test]
+    fn test_locator() {
+        let mut locator = Locator::default();
+        let samples = traffic(43.6, 1.4, 200);
+        for (i, sample) in samples.iter().enumerate() {
+            // Only one sample per aircraft every 30 seconds
+            locator.record(ICAO(i as u32 % 50), i as u64 / 10, *sample);
+        }
+        assert_eq!(locator.samples.len(), 50);
+        assert!(locator.estimate(1000).is_none());
+        assert!(locator.last.is_empty());
+    }
+}
@@ -1,37 +1,88 @@
 #![doc = include_str!("../readme.md")]
 
 mod aircraftdb;
+mod archive;
+mod association;
+mod class;
+mod clock;
+mod config;
+mod daemon;
 mod dedup;
+mod enrich;
+mod errors;
+mod event;
 mod filters;
+mod flight;
+mod graphs;
+mod kml;
+mod latency;
+mod lifetime;
+mod locate;
+mod multicast;
+mod output;
+mod phase;
+mod priority;
+mod privacy;
+mod profile;
+mod projection;
+mod qnh;
+mod range;
+mod rebroadcast;
+mod reference;
+mod reload;
+mod reorder;
+mod report;
+mod retention;
+mod script;
 mod sensor;
 mod shell;
+mod sink;
 mod snapshot;
 mod source;
 mod table;
+mod tasks;
+#[cfg(test)]
+mod testing;
+mod timing;
 mod tui;
+mod velocity;
 mod web;
 
+use crate::config::expanduser;
+use crate::range::RangeAction;
 use crate::tui::Event;
-use crate::web::TrackQuery;
-use clap::{Command, CommandFactory, Parser, ValueHint};
+use crate::web::{AllQuery, CzmlQuery, KmlQuery, TrackQuery};
+use clap::{Command, CommandFactory, Parser, Subcommand, ValueHint};
 use clap_complete::{generate, Generator};
 use crossterm::event::KeyCode;
 use ratatui::widgets::*;
-use redis::AsyncCommands;
-use rs1090::decode::cpr::{decode_position, AircraftState};
+use rs1090::data::patterns::Allocation;
+use rs1090::decode::commb::{self, BdsPolicy};
+use rs1090::decode::cpr::{
+    decode_position, decode_tisb_position, AircraftState,
+};
 use rs1090::decode::serialize_config;
+use rs1090::decode::tracking::Tracker;
+use rs1090::decode::units::{Precision, Units};
+use rs1090::integrity::{HashChain, IntegrityMode};
 use rs1090::prelude::*;
+use rs1090::source::beast::{FramingStats, ReceiverReport};
+use rs1090::source::jet1090;
+use rs1090::source::processing::SampleStats;
+use rs1090::source::sensor::SensorStore;
+use rs1090::source::timestamps::TimestampStats;
 use sensor::Sensor;
 use serde::Deserialize;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use std::time::SystemTime;
-use tokio::fs;
-use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 use tokio::time::{sleep, Duration};
+use tracing::{info, trace_span, warn};
+use tracing_appender::rolling::RollingFileAppender;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 use warp::Filter;
 
@@ -51,6 +102,33 @@ struct Options {
     #[arg(short, long, default_value=None, value_hint=ValueHint::FilePath)]
     output: Option<String>,
 
+    /// Format of the messages on the standard output and in the output file: json (default) or sbs (SBS-1 BaseStation lines, as on port 30003 of dump1090)
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    format: Option<sink::Format>,
+
+    /// Start a new output file periodically or above a given size: hourly, daily or a size (e.g. 100MB). The file name may be a template, e.g. dump-%Y%m%d-%H.jsonl
+    #[arg(long, value_name = "ROTATION")]
+    output_rotate: Option<output::Rotation>,
+
+    /// Compress rotated output files with gzip
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    output_compress: bool,
+
+    /// Archive the received messages in a directory, one YYYY/MM/DD/HH.jsonl.zst file per hour with an index file per day
+    #[arg(long, value_name = "DIR", value_hint=ValueHint::DirPath)]
+    archive: Option<String>,
+
+    /// Write messages to the output file and Redis in timestamp order, holding them at most the given time (in ms, 2000 if no value is given)
+    #[arg(
+        long,
+        value_name = "MS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "2000"
+    )]
+    ordered: Option<u32>,
+
     /// Display a table in interactive mode (not compatible with verbose)
     #[arg(short, long, default_value = "false")]
     interactive: bool,
@@ -59,10 +137,23 @@ struct Options {
     #[arg(long, default_value=None)]
     serve_port: Option<u16>,
 
+    /// Port to re-broadcast the deduplicated frames in the Beast binary format (on 0.0.0.0)
+    #[arg(long, value_name = "PORT")]
+    serve_beast: Option<u16>,
+
+    /// Port to re-broadcast the deduplicated frames in the AVR text format (on 0.0.0.0)
+    #[arg(long, value_name = "PORT")]
+    serve_avr: Option<u16>,
+
     /// How much history to expire (in minutes), 0 for no history
     #[arg(long, short = 'x')]
     history_expire: Option<u64>,
 
+    /// How much history to expire (in minutes) by activity class, in the configuration file only
+    #[arg(skip)]
+    #[serde(default)]
+    retention: retention::Retention,
+
     /// Downlink formats to select for stdout, file output and history in REST API (keep empty to select all)
     #[arg(long, value_name = "DF")]
     df_filter: Option<Vec<u16>>,
@@ -71,6 +162,18 @@ struct Options {
     #[arg(long, value_name = "ICAO24")]
     aircraft_filter: Option<Vec<ICAO>>,
 
+    /// Aircraft addresses to drop before any output, as addresses, ranges (3c0000-3c0fff) or prefixes (3c66*)
+    #[arg(long, value_name = "ICAO24")]
+    privacy_drop: Option<Vec<privacy::AddressRange>>,
+
+    /// Aircraft addresses to anonymize before any output (pseudonymous address, no callsign nor registration)
+    #[arg(long, value_name = "ICAO24")]
+    privacy_anonymize: Option<Vec<privacy::AddressRange>>,
+
+    /// Salt for the pseudonyms of anonymized aircraft (random at each start if not set)
+    #[arg(long, value_name = "SALT")]
+    privacy_salt: Option<String>,
+
     /// Prevent the computer sleeping when decoding is in progress
     #[arg(long, default_value=None)]
     prevent_sleep: bool,
@@ -79,23 +182,113 @@ struct Options {
     #[arg(short, long, default_value=None)]
     update_position: bool,
 
+    /// Directory where the aircraft database is cached (default to the user cache directory)
+    #[arg(long, value_name = "DIR", value_hint=ValueHint::DirPath)]
+    aircraftdb_cache: Option<String>,
+
+    /// URL (or local path) of the aircraft database, a zipped BaseStation file
+    #[arg(long, value_name = "URL")]
+    aircraftdb_url: Option<String>,
+
+    /// Expected SHA-256 checksum of the downloaded aircraft database
+    #[arg(long, value_name = "SHA256")]
+    aircraftdb_sha256: Option<String>,
+
+    /// Never download the aircraft database, only use the cached copy
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    offline: bool,
+
+    /// Add information to state vectors: tar1090 (operators, from a tar1090 database), planespotters (links to photos, from the registration)
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "PROVIDER")]
+    enrich: Option<Vec<enrich::Source>>,
+
+    /// URL (or local path) of the tar1090 database, cached next to the aircraft database
+    #[arg(long, value_name = "URL")]
+    enrich_url: Option<String>,
+
+    /// Download the aircraft database again, then exit
+    #[arg(long, default_value = "false")]
+    #[serde(skip)]
+    update_aircraftdb: bool,
+
+    /// Check the synchronisation of the system clock at startup and every 10 minutes: `kernel` (Linux, as disciplined by chrony or ntpd) or a NTP server (e.g. pool.ntp.org)
+    #[arg(long, value_name = "SOURCE")]
+    clock_sync: Option<clock::ClockSync>,
+
+    /// Reference position for sources without any: an airport code, `43.3,1.35`, a MGRS or UTM grid reference or `auto` (from the receiver, its airport, then IP geolocation: the public IP address is sent to ipwho.is)
+    #[arg(long, value_name = "REFERENCE")]
+    reference: Option<reference::Reference>,
+
+    /// The local QNH in hPa (e.g. 1020.5) or `auto` (agreed on by aircraft at low altitude), to add an altitude corrected with the QNH to state vectors
+    #[arg(long, value_name = "QNH")]
+    qnh: Option<qnh::Qnh>,
+
     /// When performing deduplication, after how long to dump deduplicated messages (time in ms)
     #[arg(long, default_value = "450")]
     deduplication: Option<u32>,
 
+    /// Which copy of a message to keep when several sensors receive it: first-wins, best-rssi-wins or merge-all (default)
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    deduplication_strategy: Option<dedup::Strategy>,
+
+    /// The deduplication window (in ms) for the frames of some protocols, e.g. `flarm = 1000`, in the configuration file only
+    #[arg(skip)]
+    #[serde(default)]
+    deduplication_protocols: HashMap<dedup::Protocol, u32>,
+
+    /// Drop decoded positions farther than this range (in km) from the reference of the receiver, most likely CPR mis-decodes
+    #[arg(long, value_name = "KM")]
+    max_range_filter: Option<f64>,
+
+    /// What to do with positions beyond the maximum range: drop (default) or flag (with out_of_range: true)
+    #[arg(long, value_enum, value_name = "ACTION")]
+    max_range_action: Option<range::RangeAction>,
+
+    /// Decode positions and velocities first, and shed the oldest other messages (then the oldest positions) beyond a number of pending messages, for underpowered hardware
+    #[arg(long, value_name = "SIZE")]
+    priority_queue: Option<usize>,
+
     #[arg(long)]
     stats: Option<bool>,
 
+    /// Units of decoded values: implicit (default), explicit (e.g. altitude_ft) or si (e.g. altitude_m)
+    #[arg(long, value_name = "UNITS")]
+    units: Option<Units>,
+
+    /// Decimal places of serialized positions, altitudes and speeds, e.g. position=5,altitude=0,speed=1 (default: all)
+    #[arg(long, value_name = "PRECISION")]
+    precision: Option<Precision>,
+
+    /// BDS registers attempted when decoding Comm-B replies, e.g. 20,40,50,60, els, ehs, meteo (default: all)
+    #[arg(long, value_name = "REGISTERS")]
+    bds_registers: Option<BdsPolicy>,
+
+    /// Measure the time spent in each stage of the decoding pipeline and print a summary at exit
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    profile: bool,
+
+    /// Measure the end-to-end latency of messages, from the source to each sink, and serve percentiles on /stats/latency
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    latency: bool,
+
     /// Shell completion generation
     #[arg(long = "completion", value_enum)]
     #[serde(skip)]
     completion: Option<shell::Shell>,
 
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<Commands>,
+
     /// List the sources of data following the format \[host:\]port\[\@reference\]
     //
     // - `host` can be a DNS name, an IP address or `rtlsdr` (for RTL-SDR dongles)
     // - `port` must be a number
-    // - `reference` can be LFPG for major airports, `43.3,1.35` otherwise
+    // - `reference` can be LFPG for major airports, `43.3,1.35` otherwise, or
+    //   a MGRS grid reference (`31UDQ4825111932`)
     sources: Vec<source::Source>,
 
     #[cfg(feature = "rtlsdr")]
@@ -107,6 +300,19 @@ struct Options {
     #[arg(short, long, value_name = "FILE")]
     log_file: Option<String>,
 
+    /// Start a new log file periodically (the date is appended to the log file name)
+    #[arg(long, value_enum, value_name = "PERIOD")]
+    log_rotation: Option<daemon::LogRotation>,
+
+    /// Run in the background, detached from the terminal (unix only, not compatible with interactive)
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    daemon: bool,
+
+    /// Write the process identifier to a file (removed on exit)
+    #[arg(long, value_name = "FILE", value_hint=ValueHint::FilePath)]
+    pid_file: Option<String>,
+
     /// Publish messages to a Redis pubsub
     /// Setup Redis stack by:
     ///   `docker run -d --rm --name redis -p 6379:6379 -p 8001:8001 redis/redis-stack:latest`
@@ -117,46 +323,114 @@ struct Options {
     /// Redis topic for the messages, default to "jet1090"
     #[arg(long, value_name = "REDIS TOPIC")]
     redis_topic: Option<String>,
+
+    /// Maximum number of messages buffered while the Redis server is unreachable (default: 10000)
+    #[arg(long, value_name = "SIZE")]
+    redis_buffer: Option<usize>,
+
+    /// Only publish these fields of messages to Redis, as JSON pointers (e.g. /icao24,/latitude,/longitude)
+    #[arg(long, value_delimiter = ',', value_name = "POINTER")]
+    redis_fields: Option<Vec<projection::Pointer>>,
+
+    /// Send decoded messages (as JSON) to a multicast group, e.g. 239.255.10.90:30090
+    #[arg(long, value_name = "GROUP:PORT")]
+    multicast: Option<SocketAddr>,
+
+    /// Time to live (or hop limit) of multicast datagrams (default: 1, i.e. the local network)
+    #[arg(long, value_name = "TTL")]
+    multicast_ttl: Option<u32>,
+
+    /// Interface for multicast datagrams: a local IPv4 address, or an interface index for IPv6 groups
+    #[arg(long, value_name = "INTERFACE")]
+    multicast_interface: Option<String>,
+
+    /// Add an integrity hash to messages in the output file and Redis (hash or chain)
+    #[arg(long, value_name = "MODE")]
+    integrity: Option<IntegrityMode>,
+
+    /// Write messages as {"decoded": ..., "enriched": ...} to these sinks (stdout, file, archive, redis, multicast), with the state vector of the aircraft as the enriched layer
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "SINK")]
+    enriched_sinks: Option<Vec<sink::Kind>>,
+
+    /// Write a summary of each flight to the output file, Redis and stdout (with verbose) when the aircraft expires
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    flight_summary: bool,
+
+    /// Send a heartbeat record for each sensor every given number of seconds, to the output file, Redis, stdout (with verbose) and the websocket stream
+    #[arg(long, value_name = "SECONDS")]
+    heartbeat_interval: Option<u64>,
+
+    /// Run a Lua script on each decoded message, to add fields to it or keep it from some sinks (requires the scripting feature)
+    #[arg(long, value_name = "FILE", value_hint=ValueHint::FilePath)]
+    script: Option<String>,
+
+    /// Maximum number of Lua instructions run by the script for each message (default: 1000000)
+    #[arg(long, value_name = "INSTRUCTIONS")]
+    script_budget: Option<u64>,
+
+    /// Compute the WGS84 height, ECEF coordinates and ENU coordinates (relative to the receiver) of aircraft in state vectors
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    geometry: bool,
+
+    /// Stitch the trajectories of aircraft changing their ICAO 24-bit address in flight into one track_id (heuristic, based on callsigns and the continuity of positions)
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    track_association: bool,
+
+    /// Smooth the trajectories of aircraft with a Kalman filter, fusing positions, velocities and Comm-B speeds (in the `smoothed` field of state vectors)
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    smoothing: bool,
+
+    /// Decode military extended squitters (DF19) with AF=0 as ADS-B messages, tagged with `military: true`
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    military: bool,
+
+    /// File where the reception statistics served on /graphs are kept between runs (default to graphs.bin in the jet1090 user cache directory)
+    #[arg(long, value_name = "FILE", value_hint=ValueHint::FilePath)]
+    graphs_file: Option<String>,
+
+    /// File where the lifetime counters served on /stats/lifetime are kept between runs (default to lifetime.json in the jet1090 user cache directory)
+    #[arg(long, value_name = "FILE", value_hint=ValueHint::FilePath)]
+    lifetime_file: Option<String>,
+
+    /// File where the settings of sensors (alias, reference, altitude, RSSI offset) set on the REST API are kept between runs (default to sensors.json in the jet1090 user cache directory)
+    #[arg(long, value_name = "FILE", value_hint=ValueHint::FilePath)]
+    sensors_file: Option<String>,
+
+    /// Write a site report (coverage, message rates, sensors, deduplication, top aircraft) every 5 minutes, as HTML if the extension is .html, as JSON otherwise
+    #[arg(long, value_name = "FILE", value_hint=ValueHint::FilePath)]
+    site_report: Option<String>,
 }
 
-fn expanduser(path: PathBuf) -> PathBuf {
-    // Check if the path starts with "~"
-    if let Some(stripped) = path.to_str().and_then(|p| p.strip_prefix("~")) {
-        if let Some(home_dir) = dirs::home_dir() {
-            // Join the home directory with the rest of the path
-            return home_dir.join(stripped.trim_start_matches('/'));
+impl Options {
+    fn aircraftdb(&self) -> aircraftdb::Settings {
+        aircraftdb::Settings {
+            cache_dir: self
+                .aircraftdb_cache
+                .as_ref()
+                .map(|path| expanduser(PathBuf::from(path))),
+            url: self.aircraftdb_url.clone(),
+            sha256: self.aircraftdb_sha256.clone(),
+            offline: self.offline,
         }
     }
-    path
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Read and validate a configuration file
+fn read_config(path: &Path) -> Result<Options, String> {
+    let string = std::fs::read_to_string(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    toml::from_str(&string).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load environment variables from a .env file
     dotenv::dotenv().ok();
 
-    let mut options = Options::default();
-
-    let mut cfg_path = match std::env::var("XDG_CONFIG_HOME") {
-        Ok(xdg_config) => expanduser(PathBuf::from(xdg_config)),
-        Err(_) => dirs::config_dir().unwrap_or_default(),
-    };
-    cfg_path.push("jet1090");
-    cfg_path.push("config.toml");
-
-    if cfg_path.exists() {
-        let string = fs::read_to_string(cfg_path).await.ok().unwrap();
-        options = toml::from_str(&string).unwrap();
-    }
-
-    if let Ok(config_file) = std::env::var("JET1090_CONFIG") {
-        let path = expanduser(PathBuf::from(config_file));
-        let string = fs::read_to_string(path)
-            .await
-            .expect("Configuration file not found");
-        options = toml::from_str(&string).unwrap();
-    }
-
     let mut cli_options = Options::parse();
 
     // Generate completion instructions
@@ -166,116 +440,519 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    // Also with an invalid configuration file
+    if let Some(Commands::Config { command }) = cli_options.command {
+        config::run(command)?;
+        return Ok(());
+    }
+
+    let config = config::find();
+    let mut options = match &config {
+        Some(path) => read_config(path)?,
+        None => Options::default(),
+    };
+
+    if let Some(Commands::Archive { command }) = cli_options.command {
+        archive::run(command, options.archive)?;
+        return Ok(());
+    }
+
     if cli_options.verbose {
         options.verbose = true;
     }
     if cli_options.output.is_some() {
         options.output = cli_options.output;
     }
+    if cli_options.format.is_some() {
+        options.format = cli_options.format;
+    }
+    if cli_options.output_rotate.is_some() {
+        options.output_rotate = cli_options.output_rotate;
+    }
+    if cli_options.output_compress {
+        options.output_compress = cli_options.output_compress;
+    }
+    if cli_options.archive.is_some() {
+        options.archive = cli_options.archive;
+    }
+    if cli_options.ordered.is_some() {
+        options.ordered = cli_options.ordered;
+    }
     if cli_options.interactive {
         options.interactive = true;
     }
     if cli_options.serve_port.is_some() {
         options.serve_port = cli_options.serve_port;
     }
+    if cli_options.serve_beast.is_some() {
+        options.serve_beast = cli_options.serve_beast;
+    }
+    if cli_options.serve_avr.is_some() {
+        options.serve_avr = cli_options.serve_avr;
+    }
     if cli_options.history_expire.is_some() {
         options.history_expire = cli_options.history_expire;
     }
+    // Filters may be reloaded from the configuration file at runtime
+    let reloader = reload::Reloader::new(
+        config,
+        cli_options.df_filter.clone(),
+        cli_options.aircraft_filter.clone(),
+    );
     if cli_options.df_filter.is_some() {
         options.df_filter = cli_options.df_filter;
     }
     if cli_options.aircraft_filter.is_some() {
         options.aircraft_filter = cli_options.aircraft_filter;
     }
+    if cli_options.privacy_drop.is_some() {
+        options.privacy_drop = cli_options.privacy_drop;
+    }
+    if cli_options.privacy_anonymize.is_some() {
+        options.privacy_anonymize = cli_options.privacy_anonymize;
+    }
+    if cli_options.privacy_salt.is_some() {
+        options.privacy_salt = cli_options.privacy_salt;
+    }
     if cli_options.prevent_sleep {
         options.prevent_sleep = cli_options.prevent_sleep;
     }
     if cli_options.update_position {
         options.update_position = cli_options.update_position;
     }
+    if cli_options.aircraftdb_cache.is_some() {
+        options.aircraftdb_cache = cli_options.aircraftdb_cache;
+    }
+    if cli_options.aircraftdb_url.is_some() {
+        options.aircraftdb_url = cli_options.aircraftdb_url;
+    }
+    if cli_options.aircraftdb_sha256.is_some() {
+        options.aircraftdb_sha256 = cli_options.aircraftdb_sha256;
+    }
+    if cli_options.enrich.is_some() {
+        options.enrich = cli_options.enrich;
+    }
+    if cli_options.enrich_url.is_some() {
+        options.enrich_url = cli_options.enrich_url;
+    }
+    if cli_options.offline {
+        options.offline = cli_options.offline;
+    }
+    if cli_options.clock_sync.is_some() {
+        options.clock_sync = cli_options.clock_sync;
+    }
+    if cli_options.reference.is_some() {
+        options.reference = cli_options.reference;
+    }
+    if cli_options.qnh.is_some() {
+        options.qnh = cli_options.qnh;
+    }
     if cli_options.log_file.is_some() {
         options.log_file = cli_options.log_file;
     }
+    if cli_options.log_rotation.is_some() {
+        options.log_rotation = cli_options.log_rotation;
+    }
+    if cli_options.daemon {
+        options.daemon = cli_options.daemon;
+    }
+    if cli_options.pid_file.is_some() {
+        options.pid_file = cli_options.pid_file;
+    }
     if cli_options.redis_url.is_some() {
         options.redis_url = cli_options.redis_url;
     }
     if cli_options.redis_topic.is_some() {
         options.redis_topic = cli_options.redis_topic;
     }
+    if cli_options.multicast.is_some() {
+        options.multicast = cli_options.multicast;
+    }
+    if cli_options.multicast_ttl.is_some() {
+        options.multicast_ttl = cli_options.multicast_ttl;
+    }
+    if cli_options.multicast_interface.is_some() {
+        options.multicast_interface = cli_options.multicast_interface;
+    }
+    if cli_options.redis_buffer.is_some() {
+        options.redis_buffer = cli_options.redis_buffer;
+    }
+    if cli_options.redis_fields.is_some() {
+        options.redis_fields = cli_options.redis_fields;
+    }
+    if cli_options.integrity.is_some() {
+        options.integrity = cli_options.integrity;
+    }
+    if cli_options.enriched_sinks.is_some() {
+        options.enriched_sinks = cli_options.enriched_sinks;
+    }
+    if cli_options.flight_summary {
+        options.flight_summary = cli_options.flight_summary;
+    }
+
+    if cli_options.heartbeat_interval.is_some() {
+        options.heartbeat_interval = cli_options.heartbeat_interval;
+    }
+    if cli_options.script.is_some() {
+        options.script = cli_options.script;
+    }
+    if cli_options.script_budget.is_some() {
+        options.script_budget = cli_options.script_budget;
+    }
+    if cli_options.geometry {
+        options.geometry = cli_options.geometry;
+    }
+    if cli_options.track_association {
+        options.track_association = true;
+    }
+    if cli_options.smoothing {
+        options.smoothing = true;
+    }
+    if cli_options.military {
+        options.military = cli_options.military;
+    }
     if cli_options.stats.is_some() {
         options.stats = cli_options.stats;
     }
+    if cli_options.profile {
+        options.profile = cli_options.profile;
+    }
+    if cli_options.latency {
+        options.latency = cli_options.latency;
+    }
     if cli_options.deduplication.is_some() {
         options.deduplication = cli_options.deduplication;
     }
-    if options.stats.unwrap_or(false) {
-        serialize_config(true);
+    if cli_options.deduplication_strategy.is_some() {
+        options.deduplication_strategy = cli_options.deduplication_strategy;
+    }
+    if cli_options.max_range_filter.is_some() {
+        options.max_range_filter = cli_options.max_range_filter;
+    }
+    if cli_options.max_range_action.is_some() {
+        options.max_range_action = cli_options.max_range_action;
+    }
+    if cli_options.site_report.is_some() {
+        options.site_report = cli_options.site_report;
+    }
+    if cli_options.graphs_file.is_some() {
+        options.graphs_file = cli_options.graphs_file;
+    }
+    if cli_options.lifetime_file.is_some() {
+        options.lifetime_file = cli_options.lifetime_file;
+    }
+    if cli_options.sensors_file.is_some() {
+        options.sensors_file = cli_options.sensors_file;
+    }
+    if cli_options.priority_queue.is_some() {
+        options.priority_queue = cli_options.priority_queue;
+    }
+    if cli_options.units.is_some() {
+        options.units = cli_options.units;
+    }
+    if cli_options.precision.is_some() {
+        options.precision = cli_options.precision;
+    }
+    if cli_options.bds_registers.is_some() {
+        options.bds_registers = cli_options.bds_registers;
+    }
+    if let Some(policy) = options.bds_registers {
+        commb::set_policy(policy);
+    }
+    if options.latency {
+        latency::enable();
+    }
+    let units = options.units.unwrap_or_default();
+    let precision = options.precision.unwrap_or_default();
+    if options.stats.unwrap_or(false)
+        || units != Units::Implicit
+        || !precision.is_empty()
+    {
+        serialize_config(options.stats.unwrap_or(false), units, precision);
     }
 
     options.sources.append(&mut cli_options.sources);
 
+    #[cfg(feature = "rtlsdr")]
+    if let Some(args) = cli_options.discover {
+        rtlsdr::enumerate(&args.to_string());
+        return Ok(());
+    }
+
+    if cli_options.update_aircraftdb {
+        // An explicit refresh, even in offline mode
+        let runtime = tokio::runtime::Runtime::new()?;
+        let archive = runtime
+            .block_on(aircraftdb::refresh(&options.aircraftdb()))
+            .map_err(|e| e.to_string())?;
+        println!("Aircraft database saved to {}", archive.display());
+        return Ok(());
+    }
+
+    if options.daemon {
+        if options.interactive {
+            return Err("daemon mode is not compatible with interactive".into());
+        }
+        // Forking must happen before any thread is started
+        daemon::daemonize()?;
+    }
+
+    let _pid_file = match &options.pid_file {
+        Some(path) => {
+            Some(daemon::PidFile::create(&expanduser(PathBuf::from(path)))?)
+        }
+        None => None,
+    };
+
     // example: RUST_LOG=rs1090=DEBUG
     let env_filter = EnvFilter::from_default_env();
 
-    let subscriber = tracing_subscriber::registry().with(env_filter);
+    let profiler = options.profile.then(profile::Profiler::default);
+    let subscriber = tracing_subscriber::registry().with(
+        profiler
+            .clone()
+            .map(|p| p.with_filter(profile::Profiler::filter())),
+    );
+    // Serve the tasks to tokio-console (on 127.0.0.1:6669 by default)
+    #[cfg(feature = "console")]
+    let subscriber = subscriber.with(console_subscriber::spawn());
     match options.log_file.as_deref() {
         Some("-") if !cli_options.interactive => {
             // when it's interactive, logs will disrupt the display
-            subscriber.with(fmt::layer().pretty()).init();
+            subscriber
+                .with(fmt::layer().pretty().with_filter(env_filter))
+                .init();
         }
         Some(log_file) if log_file != "-" => {
-            let file = std::fs::File::create(log_file).unwrap_or_else(|_| {
-                panic!("fail to create log file: {}", log_file)
-            });
-            let file_layer = fmt::layer().with_writer(file).with_ansi(false);
-            subscriber.with(file_layer).init();
+            let log_path = expanduser(PathBuf::from(log_file));
+            match options.log_rotation {
+                Some(rotation) => {
+                    let directory = log_path
+                        .parent()
+                        .map(|p| p.to_path_buf())
+                        .unwrap_or_default();
+                    let file_name = log_path.file_name().unwrap_or_else(|| {
+                        panic!("invalid log file name: {}", log_file)
+                    });
+                    let appender = RollingFileAppender::new(
+                        rotation.into(),
+                        directory,
+                        file_name,
+                    );
+                    let file_layer =
+                        fmt::layer().with_writer(appender).with_ansi(false);
+                    subscriber.with(file_layer.with_filter(env_filter)).init();
+                }
+                None => {
+                    let file =
+                        std::fs::File::create(&log_path).unwrap_or_else(|_| {
+                            panic!("fail to create log file: {}", log_file)
+                        });
+                    let file_layer =
+                        fmt::layer().with_writer(file).with_ansi(false);
+                    subscriber.with(file_layer.with_filter(env_filter)).init();
+                }
+            }
         }
         _ => {
             subscriber.init(); // no logging
         }
     }
 
-    #[cfg(feature = "rtlsdr")]
-    if let Some(args) = cli_options.discover {
-        rtlsdr::enumerate(&args.to_string());
-        return Ok(());
-    }
-
-    let mut redis_connect = match options
-        .redis_url
-        .map(|url| redis::Client::open(url).unwrap())
-    {
-        // map is not possible because of the .await (the async context thing)
-        Some(c) => Some(
-            c.get_multiplexed_async_connection()
-                .await
-                .expect("Unable to connect to the Redis server"),
-        ),
-        None => None,
+    let runtime = tokio::runtime::Runtime::new()?;
+    let result = match profiler {
+        Some(profiler) => {
+            // Also stops on Ctrl-C, so that the summary is printed
+            let result = runtime.block_on(run(options, reloader));
+            eprintln!("{}", profiler.summary());
+            result
+        }
+        None => runtime.block_on(run(options, reloader)),
     };
+    // Blocking reads (e.g. of a source) must not delay the removal of the
+    // PID file
+    runtime.shutdown_timeout(Duration::from_secs(1));
+    result
+}
+
+async fn run(
+    options: Options,
+    reloader: reload::Reloader,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let aircraftdb_settings = options.aircraftdb();
+    let redis_client =
+        options.redis_url.map(redis::Client::open).transpose()?;
     let redis_topic = options.redis_topic.unwrap_or("jet1090".to_string());
+    let enriched_sinks = options.enriched_sinks.unwrap_or_default();
+    let enriched = |kind| enriched_sinks.contains(&kind);
+    let mut redis_fields = options.redis_fields.unwrap_or_default();
+    if !redis_fields.is_empty() && options.integrity.is_some() {
+        // Keep the fields needed to check the hash
+        let prefix = match enriched(sink::Kind::Redis) {
+            true => "/decoded",
+            false => "",
+        };
+        for field in ["/timestamp", "/frame", "/hash"] {
+            redis_fields.push(format!("{prefix}{field}").parse()?);
+        }
+    }
 
-    let filters = filters::Filters {
-        df_filter: options
-            .df_filter
-            .map(|df| df.into_iter().map(|v| format!("{}", v)).collect()),
-        aircraft_filter: options.aircraft_filter,
-    };
+    let filters = reloader.subscribe();
+    reloader.set(filters::Filters::new(
+        options.df_filter,
+        options.aircraft_filter,
+    ));
+    let reloader = Arc::new(reloader);
+    // Closing the terminal of the table view should still stop the program
+    #[cfg(unix)]
+    if !options.interactive {
+        tasks::spawn("reload", reload::on_sighup(reloader.clone()));
+    }
+    let privacy = privacy::Privacy::new(
+        options.privacy_drop.unwrap_or_default(),
+        options.privacy_anonymize.unwrap_or_default(),
+        options.privacy_salt,
+    );
+
+    // Integrity hashes are chained separately for each sink
+    let redis_chain = options.integrity.map(HashChain::new);
+    let mut file_chain = None;
+
+    let flight_summary = options.flight_summary;
+    // Sinks subscribe to the events before the first one is sent
+    let mut event_tx = event::Publisher::default();
+    // Only the file, archive and Redis sinks, meant for later processing, are
+    // ordered, and never miss events
+    let ordered = options.ordered.map(|ms| Duration::from_millis(ms.into()));
+    if options.verbose {
+        let filters = filters.clone();
+        let format = options.format.unwrap_or_default();
+        let sink = sink::Stdout {
+            format,
+            enriched: enriched(sink::Kind::Stdout),
+            filters,
+        };
+        sink::spawn(sink, event_tx.subscribe());
+    }
+    let mut redis_status = None;
+    if let Some(client) = redis_client {
+        let mut sink = sink::Redis::new(
+            client,
+            redis_topic,
+            redis_chain,
+            redis_fields,
+            enriched(sink::Kind::Redis),
+            options.redis_buffer.unwrap_or(sink::REDIS_BUFFER),
+        );
+        // Messages are buffered until the server is reachable
+        sink.connect().await;
+        redis_status = Some(sink.status());
+        match ordered {
+            Some(delay) => {
+                sink::spawn_ordered(sink, event_tx.subscribe_lossless(), delay)
+            }
+            None => sink::spawn(sink, event_tx.subscribe_lossless()),
+        }
+    }
 
-    let mut file = if let Some(output_path) = options.output {
+    let file = if let Some(output_path) = options.output {
         let output_path = expanduser(PathBuf::from(output_path));
-        Some(
-            fs::OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(output_path)
-                .await?,
-        )
+        let template = output::template(&output_path, options.output_rotate)?;
+        let writer = output::OutputWriter::new(
+            template,
+            options.output_rotate,
+            options.output_compress,
+        );
+        file_chain = options
+            .integrity
+            .map(|mode| HashChain::resume(mode, writer.path()))
+            .transpose()?;
+        Some(writer.spawn())
     } else {
         None
     };
+    if let Some(writer) = file {
+        let sink = sink::File {
+            writer,
+            format: options.format.unwrap_or_default(),
+            enriched: enriched(sink::Kind::File),
+            chain: file_chain,
+            filters: filters.clone(),
+        };
+        match ordered {
+            Some(delay) => {
+                sink::spawn_ordered(sink, event_tx.subscribe_lossless(), delay)
+            }
+            None => sink::spawn(sink, event_tx.subscribe_lossless()),
+        }
+    }
 
-    let aircraftdb = aircraftdb::aircraft().await;
+    if let Some(port) = options.serve_beast {
+        let protocol = rebroadcast::Protocol::Beast;
+        rebroadcast::serve(port, protocol, event_tx.broadcast()).await?;
+    }
+    if let Some(port) = options.serve_avr {
+        let protocol = rebroadcast::Protocol::Avr;
+        rebroadcast::serve(port, protocol, event_tx.broadcast()).await?;
+    }
+
+    if let Some(root) = options.archive {
+        let root = expanduser(PathBuf::from(root));
+        let sink = sink::Archive {
+            writer: archive::ArchiveWriter::new(root).spawn(),
+            enriched: enriched(sink::Kind::Archive),
+            filters: filters.clone(),
+        };
+        match ordered {
+            Some(delay) => {
+                sink::spawn_ordered(sink, event_tx.subscribe_lossless(), delay)
+            }
+            None => sink::spawn(sink, event_tx.subscribe_lossless()),
+        }
+    }
+
+    if let Some(group) = options.multicast {
+        let socket = multicast::socket(
+            group,
+            options.multicast_ttl.unwrap_or(multicast::TTL),
+            options.multicast_interface.as_deref(),
+        )?;
+        let filters = filters.clone();
+        let sink = sink::Multicast {
+            socket,
+            group,
+            enriched: enriched(sink::Kind::Multicast),
+            filters,
+        };
+        sink::spawn(sink, event_tx.subscribe());
+    }
+
+    let aircraftdb = match aircraftdb::aircraft(&aircraftdb_settings).await {
+        Ok(aircraftdb) => aircraftdb,
+        Err(e) => {
+            // Registrations and typecodes are then only missing
+            warn!("Aircraft database not available: {}", e);
+            BTreeMap::new()
+        }
+    };
+    let aircraftdb = Arc::new(aircraftdb);
+
+    let mut providers: Vec<Box<dyn enrich::Provider>> = Vec::new();
+    for source in options.enrich.iter().flatten() {
+        match source {
+            enrich::Source::Tar1090 => {
+                let url = options.enrich_url.as_deref();
+                match enrich::Tar1090::load(url, &aircraftdb_settings).await {
+                    Ok(tar1090) => providers.push(Box::new(tar1090)),
+                    Err(e) => warn!("tar1090 database not available: {}", e),
+                }
+            }
+            enrich::Source::Planespotters => {
+                providers.push(Box::new(enrich::Planespotters))
+            }
+        }
+    }
+    let enricher = Arc::new(enrich::Enricher::new(providers));
 
     let _awake = match options.prevent_sleep {
         true => Some(
@@ -292,6 +969,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     let mut aircraft: BTreeMap<ICAO, AircraftState> = BTreeMap::new();
+    let mut tracker = options.smoothing.then(Tracker::default);
 
     let terminal = if options.interactive {
         Some(tui::init()?)
@@ -306,14 +984,107 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut events = tui::EventHandler::new(width);
 
+    if let Some(sync) = options.clock_sync.clone() {
+        tasks::spawn("clock sync", clock::monitor(sync));
+    }
+
+    let sensors_file = match &options.sensors_file {
+        Some(path) => Some(expanduser(PathBuf::from(path))),
+        None => config::cache_dir().map(|dir| dir.join("sensors.json")),
+    };
+    // Settings saved on the REST API override the configuration file, the
+    // aliases and RSSI offsets apply right away to the decoded messages
+    let sensor_store = Arc::new(RwLock::new(SensorStore::open(sensors_file)));
+
     let mut references = BTreeMap::<u64, Option<Position>>::new();
+    let mut unlocated = Vec::<u64>::new();
     let mut sensors = BTreeMap::<u64, Sensor>::new();
+    // Deduplication windows and protocols for all the sensors behind a source
+    let mut windows = HashMap::<u64, u32>::new();
+    let mut protocols = HashMap::<u64, dedup::Protocol>::new();
+    // Maximum ranges for all the sensors behind a source
+    let mut ranges = HashMap::<u64, f64>::new();
     for source in options.sources.iter() {
-        for sensor in sensor::sensors(source).await {
+        for mut sensor in sensor::sensors(source).await {
+            // Sensors without coordinates may take the position of their
+            // receiver, before the airport and IP fallbacks
+            let mut located = source.reference.is_some();
+            if let Some(settings) =
+                sensor_store.read().unwrap().get(sensor.serial)
+            {
+                located |= settings.reference.is_some();
+                sensor.configure(settings);
+            }
             references.insert(sensor.serial, sensor.reference);
+            if !located {
+                unlocated.push(sensor.serial);
+            }
+            if let Some(window) = source.dedup_ms {
+                windows.insert(sensor.serial, window);
+            }
+            if let Some(protocol) = source.protocol {
+                protocols.insert(sensor.serial, protocol);
+            }
+            if let Some(range) = source.max_range_filter {
+                ranges.insert(sensor.serial, range);
+            }
             sensors.insert(sensor.serial, sensor);
         }
     }
+    // Framing statistics are updated by the sources, and served with sensors
+    let framing: HashMap<u64, FramingStats> = sensors
+        .iter()
+        .map(|(serial, sensor)| (*serial, sensor.framing.clone()))
+        .collect();
+    let samples: HashMap<u64, SampleStats> = sensors
+        .iter()
+        .map(|(serial, sensor)| (*serial, sensor.samples.clone()))
+        .collect();
+    let timestamps: HashMap<u64, TimestampStats> = sensors
+        .iter()
+        .map(|(serial, sensor)| (*serial, sensor.timestamps.clone()))
+        .collect();
+    let reports: HashMap<u64, ReceiverReport> = sensors
+        .iter()
+        .map(|(serial, sensor)| (*serial, sensor.receiver.clone()))
+        .collect();
+    // With `--reference auto`, the positions reported by the receivers
+    // replace the fallbacks as soon as they are received
+    let mut reported: HashMap<u64, ReceiverReport> = match &options.reference {
+        Some(reference) if reference.uses_receivers() => unlocated
+            .iter()
+            .filter_map(|serial| {
+                let sensor = sensors.get(serial)?;
+                Some((*serial, sensor.receiver.clone()))
+            })
+            .collect(),
+        _ => HashMap::new(),
+    };
+    if let Some(fallback) = &options.reference {
+        if sensors.values().any(|sensor| sensor.reference.is_none()) {
+            let position = fallback.resolve().await;
+            for sensor in sensors.values_mut() {
+                if sensor.reference.is_none() {
+                    sensor.reference = position;
+                    references.insert(sensor.serial, position);
+                }
+            }
+        }
+    }
+    let dedup_statistics = Arc::new(dedup::Statistics::default());
+    let error_stats = errors::ErrorStats::default();
+    let graphs_file = match &options.graphs_file {
+        Some(path) => Some(expanduser(PathBuf::from(path))),
+        None => config::cache_dir().map(|dir| dir.join("graphs.bin")),
+    };
+    let lifetime_file = match &options.lifetime_file {
+        Some(path) => Some(expanduser(PathBuf::from(path))),
+        None => config::cache_dir().map(|dir| dir.join("lifetime.json")),
+    };
+    let now = SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("SystemTime before unix epoch")
+        .as_secs();
     let app_tui = Arc::new(Mutex::new(Jet1090 {
         sensors,
         items: Vec::new(),
@@ -322,18 +1093,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         should_quit: false,
         should_clear: false,
         state_vectors: BTreeMap::new(),
+        version: 0,
+        removals: snapshot::Removals::default(),
         sort_key: SortKey::default(),
         sort_asc: false,
         width,
         is_search_mode: false,
         search_query: "".to_string(),
+        class_filter: None,
+        allocation_filter: None,
+        show_sensors: false,
+        events: event_tx.clone(),
+        graphs: graphs::Graphs::new(graphs_file, now),
+        lifetime: lifetime::Lifetime::new(lifetime_file, now),
+        sensor_store: sensor_store.clone(),
+        dedup: dedup_statistics.clone(),
+        errors: error_stats.clone(),
+        timing: timing::Timing::default(),
+        altimeter: qnh::Altimeter::new(options.qnh),
     }));
     let app_dec = app_tui.clone();
     let app_web = app_tui.clone();
     let app_exp = app_tui.clone();
+    let app_sen = app_tui.clone();
+    let app_hb = app_tui.clone();
+    let app_report = app_tui.clone();
+    let app_exit = app_tui.clone();
 
     if let Some(mut terminal) = terminal {
-        tokio::spawn(async move {
+        tasks::spawn("interactive", async move {
             loop {
                 if let Ok(event) = events.next().await {
                     update(&mut app_tui.lock().await, event)?;
@@ -346,56 +1134,154 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     terminal.clear()?;
                     app.should_clear = false;
                 }
-                terminal.draw(|frame| table::build_table(frame, &mut app))?;
+                terminal.draw(|frame| match app.show_sensors {
+                    true => table::build_sensor_table(frame, &mut app),
+                    false => table::build_table(frame, &mut app),
+                })?;
             }
             tui::restore()
         });
     }
 
-    if let Some(minutes) = options.history_expire {
-        // No need to start this task if we don't store history
-        if minutes > 0 {
-            tokio::spawn(async move {
-                let app_expire = app_exp.clone();
-                loop {
-                    sleep(Duration::from_secs(60)).await;
-                    {
-                        let mut app = app_expire.lock().await;
-                        let now = SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .expect("SystemTime before unix epoch")
-                            .as_secs();
-
-                        let remove_keys = app
-                            .state_vectors
-                            .iter()
-                            .filter(|(_key, value)| {
+    let retention = options.retention;
+    let history_expire = options.history_expire;
+    // No need to start this task if we don't store history
+    if history_expire.is_some_and(|minutes| minutes > 0)
+        || (history_expire.is_none() && !retention.is_empty())
+    {
+        tasks::spawn("expiration", async move {
+            let app_expire = app_exp.clone();
+            loop {
+                sleep(Duration::from_secs(60)).await;
+                {
+                    let mut app = app_expire.lock().await;
+                    let now = SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .expect("SystemTime before unix epoch")
+                        .as_secs();
+
+                    // How long each aircraft is kept, after its activity
+                    let expire = |sv: &snapshot::StateVectors| {
+                        let class = retention::ActivityClass::of(sv);
+                        retention.minutes(class, history_expire)
+                    };
+
+                    let remove_keys = app
+                        .state_vectors
+                        .iter()
+                        .filter(|(_key, value)| {
+                            expire(value).is_some_and(|minutes| {
                                 now > value.cur.lastseen + minutes * 60
                             })
-                            .map(|(key, _)| key.to_string())
-                            .collect::<Vec<String>>();
-
-                        for key in remove_keys {
-                            app.state_vectors.remove(&key);
+                        })
+                        .map(|(key, _)| key.to_string())
+                        .collect::<Vec<String>>();
+
+                    let version = app.version;
+                    app.removals.new_round(version);
+                    for key in remove_keys {
+                        let removed = app.state_vectors.remove(&key);
+                        if let Some(sv) = removed.filter(|_| flight_summary) {
+                            let summary = sv.flight.summary(sv.cur);
+                            app.events
+                                .send(event::Event::FlightClosed(Arc::new(
+                                    summary,
+                                )))
+                                .await;
                         }
+                        app.version += 1;
+                        let version = app.version;
+                        app.removals.push(version, key);
+                    }
 
-                        let _ = app
-                            .state_vectors
-                            .iter_mut()
-                            .map(|(_key, value)| {
-                                value.hist.retain(|elt| {
-                                    now < (elt.timestamp as u64) + minutes * 60
-                                })
+                    for value in app.state_vectors.values_mut() {
+                        if let Some(minutes) = expire(value) {
+                            value.hist.retain(|elt| {
+                                now < (elt.timestamp.as_secs() as u64)
+                                    + minutes * 60
                             })
-                            .collect::<Vec<()>>();
+                        }
                     }
                 }
-            });
+            }
+        });
+    }
+
+    // Sensors without any recent message are reported down and labels of
+    // close aircraft are deconflicted; the position of static sensors is
+    // estimated every minute
+    let locate = !options.update_position;
+    tasks::spawn("sensors", async move {
+        for tick in 1.. {
+            sleep(Duration::from_secs(5)).await;
+            let mut app = app_sen.lock().await;
+            app.check_sensors().await;
+            let now = SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .expect("SystemTime before unix epoch")
+                .as_secs();
+            app.graphs.tick(now);
+            app.lifetime.tick(now);
+            let app = &mut *app;
+            snapshot::deconflict_labels(
+                &mut app.state_vectors,
+                &mut app.version,
+            );
+            if locate && tick % 12 == 0 {
+                app.locate_sensors();
+            }
         }
+    });
+
+    if let Some(seconds) = options.heartbeat_interval.filter(|&s| s > 0) {
+        tasks::spawn("heartbeat", async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(seconds));
+            // The first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                app_hb.lock().await.heartbeat().await;
+            }
+        });
+    }
+
+    if let Some(path) = &options.site_report {
+        let path = expanduser(PathBuf::from(path));
+        tasks::spawn("site report", async move {
+            let period = Duration::from_secs(graphs::BUCKET);
+            let mut interval = tokio::time::interval(period);
+            // The first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let now = SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .expect("SystemTime before unix epoch")
+                    .as_secs();
+                let report =
+                    report::Report::new(&*app_report.lock().await, now);
+                if let Err(e) = report.save(&path) {
+                    warn!("Failed to write {}: {}", path.display(), e);
+                }
+            }
+        });
+    }
+
+    // Created before the REST API, which serves its metrics
+    let priority_queue = options
+        .priority_queue
+        .map(|capacity| Arc::new(priority::PriorityQueue::new(capacity)));
+    if let Some(queue) = &priority_queue {
+        tasks::spawn("queue monitor", priority::monitor(queue.clone()));
     }
 
     if let Some(port) = options.serve_port {
-        tokio::spawn(async move {
+        let events = event_tx.clone();
+        let aircraftdb = aircraftdb.clone();
+        let reloader = reloader.clone();
+        let queue = priority_queue.clone();
+        tasks::spawn("web server", async move {
             let app_home = app_web.clone();
             let home = warp::path::end()
                 .and(warp::any().map(move || app_home.clone()))
@@ -406,9 +1292,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let app_all = app_web.clone();
             let all = warp::path("all")
                 .and(warp::any().map(move || app_all.clone()))
-                .and_then(|app: Arc<Mutex<Jet1090>>| async move {
-                    web::all(&app).await
-                });
+                .and(warp::query::<AllQuery>())
+                .and(warp::header::optional::<String>("if-none-match"))
+                .and_then(
+                    |app: Arc<Mutex<Jet1090>>,
+                     q: AllQuery,
+                     etag: Option<String>| async move {
+                        web::all(&app, q, etag).await
+                    },
+                );
 
             let app_track = app_web.clone();
             let track = warp::get()
@@ -421,20 +1313,204 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     },
                 );
 
+            let app_czml = app_web.clone();
+            let czml = warp::path("czml")
+                .and(warp::any().map(move || app_czml.clone()))
+                .and(warp::query::<CzmlQuery>())
+                .and_then(
+                    |app: Arc<Mutex<Jet1090>>, q: CzmlQuery| async move {
+                        web::czml(&app, q).await
+                    },
+                );
+
+            let app_kml = app_web.clone();
+            let kml = warp::path("kml")
+                .and(warp::path::end())
+                .and(warp::header::optional::<String>("host"))
+                .and(warp::query::<KmlQuery>())
+                .and_then(|host: Option<String>, q: KmlQuery| async move {
+                    web::kml(host, q).await
+                });
+            let kml_live = warp::path!("kml" / "live")
+                .and(warp::any().map(move || app_kml.clone()))
+                .and_then(|app: Arc<Mutex<Jet1090>>| async move {
+                    web::kml_live(&app).await
+                });
+
             let app_sensors = app_web.clone();
+            let app_settings = app_web.clone();
+            let sensor_settings = warp::path!("sensors" / "settings")
+                .and(warp::any().map(move || app_settings.clone()))
+                .and_then(|app: Arc<Mutex<Jet1090>>| async move {
+                    web::sensor_settings(&app).await
+                });
+
             let sensors = warp::path("sensors")
                 .and(warp::any().map(move || app_sensors.clone()))
                 .and_then(|app: Arc<Mutex<Jet1090>>| async move {
                     web::sensors(&app).await
                 });
 
+            let schema = warp::path("schema").and_then(web::schema);
+            let tasks = warp::path("tasks").and_then(web::tasks);
+
+            let app_report = app_web.clone();
+            let report = warp::path("report")
+                .and(warp::path::end())
+                .and(warp::any().map(move || app_report.clone()))
+                .and_then(|app: Arc<Mutex<Jet1090>>| async move {
+                    web::report(&app).await
+                });
+            let app_report = app_web.clone();
+            let report_html = warp::path("report.html")
+                .and(warp::any().map(move || app_report.clone()))
+                .and_then(|app: Arc<Mutex<Jet1090>>| async move {
+                    web::report_html(&app).await
+                });
+
+            let app_graphs = app_web.clone();
+            let graphs = warp::path("graphs")
+                .and(warp::any().map(move || app_graphs.clone()))
+                .and_then(|app: Arc<Mutex<Jet1090>>| async move {
+                    web::graphs(&app).await
+                });
+
+            let app_lifetime = app_web.clone();
+            let lifetime = warp::path!("stats" / "lifetime")
+                .and(warp::any().map(move || app_lifetime.clone()))
+                .and_then(|app: Arc<Mutex<Jet1090>>| async move {
+                    web::lifetime(&app).await
+                });
+
+            let app_errors = app_web.clone();
+            let errors = warp::path!("stats" / "errors")
+                .and(warp::any().map(move || app_errors.clone()))
+                .and_then(|app: Arc<Mutex<Jet1090>>| async move {
+                    web::errors(&app).await
+                });
+
+            let app_timing = app_web.clone();
+            let timing = warp::path!("stats" / "timing")
+                .and(warp::any().map(move || app_timing.clone()))
+                .and_then(|app: Arc<Mutex<Jet1090>>| async move {
+                    web::timing(&app).await
+                });
+
+            let latency =
+                warp::path!("stats" / "latency").and_then(web::latency);
+
+            let redis = warp::path!("stats" / "redis")
+                .and(warp::any().map(move || redis_status.clone()))
+                .and_then(
+                    |status: Option<Arc<sink::RedisStatus>>| async move {
+                        web::redis(status.as_deref()).await
+                    },
+                );
+
+            let queue = warp::path("queue")
+                .and(warp::any().map(move || queue.clone()))
+                .and_then(
+                    |queue: Option<Arc<priority::PriorityQueue>>| async move {
+                        web::queue(queue.as_deref()).await
+                    },
+                );
+
+            let stream = warp::path(jet1090::ENDPOINT).and(warp::ws()).map(
+                move |ws: warp::ws::Ws| {
+                    let rx = events.subscribe();
+                    ws.on_upgrade(move |socket| web::stream(socket, rx))
+                },
+            );
+
+            let map = warp::path("map")
+                .and(warp::path::end())
+                .and(warp::path::full())
+                .and_then(web::map)
+                .or(warp::path!("map" / String).and_then(web::map_asset));
+
+            let aircraft = warp::path("aircraft")
+                .and(warp::path::end())
+                .and(warp::post())
+                .and(warp::body::content_length_limit(
+                    16 * web::MAX_AIRCRAFT as u64,
+                ))
+                .and(warp::body::bytes())
+                .and(warp::any().map(move || aircraftdb.clone()))
+                .and_then(
+                    |body: warp::hyper::body::Bytes,
+                     aircraftdb: Arc<
+                        BTreeMap<String, aircraftdb::Aircraft>,
+                    >| async move {
+                        web::aircraft(&aircraftdb, &body).await
+                    },
+                );
+
+            let reload = warp::path("reload")
+                .and(warp::path::end())
+                .and(warp::post())
+                .and(warp::any().map(move || reloader.clone()))
+                .and_then(|reloader: Arc<reload::Reloader>| async move {
+                    web::reload(&reloader).await
+                });
+
+            let app_set_settings = app_web.clone();
+            let set_sensor_settings = warp::path!("sensors" / u64 / "settings")
+                .and(warp::put())
+                .and(warp::body::content_length_limit(4096))
+                .and(warp::body::bytes())
+                .and(warp::any().map(move || app_set_settings.clone()))
+                .and_then(
+                    |serial: u64,
+                     body: warp::hyper::body::Bytes,
+                     app: Arc<Mutex<Jet1090>>| async move {
+                        web::set_sensor_settings(&app, serial, &body).await
+                    },
+                );
+
+            let app_remove_settings = app_web.clone();
+            let remove_sensor_settings =
+                warp::path!("sensors" / u64 / "settings")
+                    .and(warp::delete())
+                    .and(warp::any().map(move || app_remove_settings.clone()))
+                    .and_then(
+                        |serial: u64, app: Arc<Mutex<Jet1090>>| async move {
+                            web::remove_sensor_settings(&app, serial).await
+                        },
+                    );
+
             let cors = warp::cors()
                 .allow_any_origin()
                 .allow_headers(vec!["*"])
-                .allow_methods(vec!["GET"]);
+                .allow_methods(vec!["GET", "POST", "PUT", "DELETE"])
+                .expose_headers(vec!["etag", web::POSITION_FAILURE]);
 
             let routes = warp::get()
-                .and(home.or(all).or(track).or(sensors))
+                .and(
+                    home.or(all)
+                        .or(track)
+                        .or(czml)
+                        .or(kml)
+                        .or(kml_live)
+                        .or(sensor_settings)
+                        .or(sensors)
+                        .or(schema)
+                        .or(queue)
+                        .or(tasks)
+                        .or(graphs)
+                        .or(lifetime)
+                        .or(errors)
+                        .or(timing)
+                        .or(redis)
+                        .or(latency)
+                        .or(report)
+                        .or(report_html)
+                        .or(map)
+                        .or(stream),
+                )
+                .or(aircraft)
+                .or(reload)
+                .or(set_sensor_settings)
+                .or(remove_sensor_settings)
                 .recover(web::handle_rejection)
                 .with(cors);
 
@@ -449,23 +1525,85 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let (tx_dedup, mut rx_dedup) =
         tokio::sync::mpsc::channel(100 * multiplier + 1);
 
+    let military = options.military;
+    let dedup_settings = dedup::Settings {
+        window: options.deduplication.unwrap_or(450),
+        windows,
+        strategy: options.deduplication_strategy.unwrap_or_default(),
+        protocols,
+        protocol_windows: options.deduplication_protocols,
+    };
+
+    // Positions and velocities overtake other messages before deduplication
+    let rx = match priority_queue {
+        Some(queue) => {
+            let (tx_queue, rx_queue) = tokio::sync::mpsc::channel(1);
+            tasks::spawn("priority", priority::prioritize(rx, tx_queue, queue));
+            rx_queue
+        }
+        None => rx,
+    };
+
     for source in options.sources.into_iter() {
         let serial = source.serial();
         let tx_copy = tx.clone();
-        tokio::spawn(async move {
-            source.receiver(tx_copy, serial, source.name.clone()).await;
+        let framing = framing.get(&serial).cloned().unwrap_or_default();
+        let samples = samples.get(&serial).cloned().unwrap_or_default();
+        let timestamps = timestamps.get(&serial).cloned().unwrap_or_default();
+        let report = reports.get(&serial).cloned().unwrap_or_default();
+        let alias = sensor_store
+            .read()
+            .unwrap()
+            .get(serial)
+            .and_then(|settings| settings.name.clone());
+        let task = match alias.as_ref().or(source.name.as_ref()) {
+            Some(name) => format!("{} source", name),
+            None => format!("{} source", serial),
+        };
+        let name = source.name.clone();
+        tasks::spawn(&task, async move {
+            source
+                .receiver(
+                    tx_copy, serial, name, framing, samples, timestamps, report,
+                )
+                .await;
         });
     }
 
-    tokio::spawn(async move {
+    #[cfg(feature = "scripting")]
+    let mut script = match &options.script {
+        Some(path) => Some(script::Script::load(
+            &expanduser(PathBuf::from(path)),
+            options.script_budget.unwrap_or(script::BUDGET),
+            errors::ErrorReporter::new(error_stats.clone()),
+        )?),
+        None => None,
+    };
+    #[cfg(not(feature = "scripting"))]
+    if options.script.is_some() {
+        return Err("scripts require jet1090 compiled with the scripting \
+            feature"
+            .into());
+    }
+
+    tasks::spawn("dedup", async move {
         dedup::deduplicate_messages(
             rx,
             tx_dedup,
-            options.deduplication.unwrap_or(450),
+            dedup_settings,
+            dedup_statistics,
+            military,
+            errors::ErrorReporter::new(error_stats),
         )
         .await;
     });
 
+    let range_settings = range::Settings {
+        range: options.max_range_filter,
+        ranges,
+        action: options.max_range_action.unwrap_or_default(),
+    };
+
     // If we choose to update the reference (only useful for surface positions)
     // then we define the callback (for now, if the altitude is below 1000ft)
     let update_reference = match options.update_position {
@@ -475,113 +1613,226 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         false => None,
     };
 
-    let mut first_msg = true;
-    while let Some(mut msg) = rx_dedup.recv().await {
-        if first_msg {
-            // This workaround results from soapysdr writing directly on stdout.
-            // The best thing would be to not write to stdout in the first
-            // place. A better workaround would be to condition that clear to
-            // the first message received from rtlsdr.
-
-            app_dec.lock().await.should_clear = true;
-            first_msg = false;
-        }
+    let decoder = async {
+        let mut first_msg = true;
+        while let Some(mut msg) = rx_dedup.recv().await {
+            if first_msg {
+                // This workaround results from soapysdr writing directly on stdout.
+                // The best thing would be to not write to stdout in the first
+                // place. A better workaround would be to condition that clear to
+                // the first message received from rtlsdr.
+
+                app_dec.lock().await.should_clear = true;
+                first_msg = false;
+            }
 
-        if let Some(message) = &mut msg.message {
-            match &mut message.df {
-                ExtendedSquitterADSB(adsb) => match adsb.message {
-                    ME::BDS05(_) | ME::BDS06(_) => {
-                        let serial = msg
-                            .metadata
-                            .first()
-                            .map(|meta| meta.serial)
-                            .unwrap();
-                        let mut reference = references[&serial];
-
-                        decode_position(
-                            &mut adsb.message,
-                            msg.timestamp,
-                            &adsb.icao24,
-                            &mut aircraft,
-                            &mut reference,
-                            &update_reference,
-                        );
-
-                        // References may have been modified.
-                        // With static receivers, we don't care; for dynamic ones, we may
-                        // want to update the reference position.
-                        if options.update_position {
-                            for meta in &msg.metadata {
-                                let _ =
-                                    references.insert(meta.serial, reference);
-                            }
+            {
+                let store = sensor_store.read().unwrap();
+                for meta in msg.metadata.iter_mut() {
+                    if let Some(settings) = store.get(meta.serial) {
+                        meta.rssi = settings.calibrate(meta.rssi);
+                        if let Some(name) = &settings.name {
+                            meta.name = Some(name.clone());
                         }
                     }
-                    _ => {}
-                },
-                ExtendedSquitterTisB { cf, .. } => match cf.me {
-                    ME::BDS05(_) | ME::BDS06(_) => {
-                        let serial = msg
-                            .metadata
-                            .first()
-                            .map(|meta| meta.serial)
-                            .unwrap();
-
-                        let mut reference = references[&serial];
-
-                        decode_position(
-                            &mut cf.me,
-                            msg.timestamp,
-                            &cf.aa,
-                            &mut aircraft,
-                            &mut reference,
-                            &update_reference,
-                        )
-                    }
-                    _ => {}
-                },
-                _ => {}
-            }
-        };
-
-        snapshot::update_snapshot(&app_dec, &mut msg, &aircraftdb).await;
-
-        let is_in = filters::Filters::is_in(&filters, &msg);
-
-        if let Ok(json) = serde_json::to_string(&msg) {
-            if options.verbose & is_in {
-                println!("{}", json);
+                }
             }
 
-            if is_in {
-                if let Some(file) = &mut file {
-                    file.write_all(json.as_bytes()).await?;
-                    file.write_all("\n".as_bytes()).await?;
+            if let Some(meta) = msg.metadata.first() {
+                let position = reported
+                    .get(&meta.serial)
+                    .and_then(ReceiverReport::position);
+                if let Some(position) = position {
+                    info!(
+                        "Reference position of {} from its receiver: {:.4},{:.4}",
+                        meta.serial, position.latitude, position.longitude
+                    );
+                    references.insert(meta.serial, Some(position));
+                    reported.remove(&meta.serial);
+                    let mut app = app_dec.lock().await;
+                    if let Some(sensor) = app.sensors.get_mut(&meta.serial) {
+                        sensor.reference = Some(position);
+                    }
                 }
             }
 
-            if let Some(c) = &mut redis_connect {
-                let _: () = c.publish(redis_topic.clone(), json).await?;
+            // Dropped messages still count in the activity of sensors
+            if !privacy.apply(&mut msg) {
+                app_dec.lock().await.record_activity(&msg).await;
+                continue;
             }
-        }
 
-        match options.history_expire {
-            Some(0) => (),
-            _ => {
-                if is_in {
-                    snapshot::store_history(&app_dec, msg, &aircraftdb).await
+            if let Some(message) = &mut msg.message {
+                match &mut message.df {
+                    ExtendedSquitterADSB(adsb) => match adsb.message {
+                        ME::BDS05(_) | ME::BDS06(_) => {
+                            let serial = msg
+                                .metadata
+                                .first()
+                                .map(|meta| meta.serial)
+                                .unwrap();
+                            // Sensors of remote sources may be unknown
+                            let mut reference =
+                                references.get(&serial).copied().flatten();
+
+                            let out_of_range = {
+                                let _cpr =
+                                    trace_span!(target: profile::TARGET, "cpr")
+                                        .entered();
+                                decode_position(
+                                    &mut adsb.message,
+                                    msg.timestamp.as_secs_f64(),
+                                    &adsb.icao24,
+                                    &mut aircraft,
+                                    &mut reference,
+                                    &update_reference,
+                                );
+                                range_settings.apply(adsb, serial, reference)
+                            };
+                            if out_of_range {
+                                // Decode the next positions from scratch
+                                if range_settings.action == RangeAction::Drop {
+                                    aircraft.remove(&adsb.icao24);
+                                }
+                                let mut app = app_dec.lock().await;
+                                if let Some(sensor) =
+                                    app.sensors.get_mut(&serial)
+                                {
+                                    sensor.out_of_range += 1;
+                                }
+                            }
+
+                            // References may have been modified.
+                            // With static receivers, we don't care; for dynamic ones, we may
+                            // want to update the reference position.
+                            if options.update_position {
+                                for meta in &msg.metadata {
+                                    let _ = references
+                                        .insert(meta.serial, reference);
+                                }
+                            }
+                        }
+                        _ => {}
+                    },
+                    ExtendedSquitterTisB { cf, .. } => match cf.me {
+                        TisBMessage::ExtendedSquitter(
+                            ME::BDS05(_) | ME::BDS06(_),
+                        )
+                        | TisBMessage::Coarse(_) => {
+                            let serial = msg
+                                .metadata
+                                .first()
+                                .map(|meta| meta.serial)
+                                .unwrap();
+
+                            let mut reference =
+                                references.get(&serial).copied().flatten();
+
+                            let _cpr =
+                                trace_span!(target: profile::TARGET, "cpr")
+                                    .entered();
+                            decode_tisb_position(
+                                cf,
+                                msg.timestamp.as_secs_f64(),
+                                &mut aircraft,
+                                &mut reference,
+                                &update_reference,
+                            )
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            };
+
+            let failure = msg
+                .message
+                .as_ref()
+                .and_then(|message| aircraft.get(&message.icao24()?))
+                .and_then(AircraftState::failure);
+            // Set before the update, so that it is published with it
+            if let Some(tracker) = &mut tracker {
+                let icao24 = msg.message.as_ref().and_then(Message::icao24);
+                if let (Some(smoothed), Some(icao24)) =
+                    (tracker.observe(&msg), icao24)
+                {
+                    let mut app = app_dec.lock().await;
+                    if let Some(sv) =
+                        app.state_vectors.get_mut(&icao24.to_string())
+                    {
+                        sv.cur.smoothed = Some(smoothed);
+                    }
+                }
+            }
+            snapshot::update_snapshot(
+                &app_dec,
+                &mut msg,
+                &aircraftdb,
+                &enricher,
+                failure,
+                options.geometry,
+                options.track_association,
+            )
+            .await;
+            // The state vector after the message, for the enriched layer
+            let snapshot = {
+                let mut app = app_dec.lock().await;
+                app.record_activity(&msg).await;
+                let icao24 = msg.message.as_ref().and_then(Message::icao24);
+                match icao24 {
+                    Some(icao24) if !enriched_sinks.is_empty() => app
+                        .state_vectors
+                        .get(&icao24.to_string())
+                        .map(|sv| sv.published.clone()),
+                    _ => None,
+                }
+            };
+
+            let is_in = filters::Filters::is_in(&filters.borrow(), &msg);
+            let msg = Arc::new(msg);
+            latency::checkpoint(latency::DECODE, &msg);
+            #[cfg(feature = "scripting")]
+            let outcome =
+                script.as_mut().and_then(|script| script.process(&msg));
+            #[cfg(not(feature = "scripting"))]
+            let outcome = None;
+            let event = event::Event::Message(msg.clone(), snapshot, outcome);
+            event_tx.send(event).await;
+
+            match options.history_expire {
+                Some(0) => (),
+                _ => {
+                    if is_in {
+                        snapshot::store_history(
+                            &app_dec,
+                            &msg,
+                            &aircraftdb,
+                            &enricher,
+                        )
+                        .await
+                    }
                 }
             }
-        }
 
-        if app_dec.lock().await.should_quit {
-            break;
+            if app_dec.lock().await.should_quit {
+                break;
+            }
         }
+    };
+    // Stop on Ctrl-C or SIGTERM as well, so that the last flights are
+    // summarized
+    tokio::select! {
+        _ = tasks::watch("decoder", decoder) => {}
+        _ = daemon::terminated() => {}
     }
+    if flight_summary {
+        app_exit.lock().await.close_flights().await;
+    }
+    sink::drain(&event_tx).await;
     Ok(())
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Jet1090 {
     sensors: BTreeMap<u64, Sensor>,
     state: TableState,
@@ -590,11 +1841,29 @@ pub struct Jet1090 {
     should_quit: bool,
     should_clear: bool,
     state_vectors: BTreeMap<String, snapshot::StateVectors>,
+    version: u64,
+    removals: snapshot::Removals,
     sort_key: SortKey,
     sort_asc: bool,
     width: u16,
     is_search_mode: bool,
     search_query: String,
+    /// Only show aircraft, or surface vehicles, in the table
+    class_filter: Option<class::TargetClass>,
+    /// Only show aircraft with an address in a kind of block
+    allocation_filter: Option<Allocation>,
+    show_sensors: bool,
+    events: event::Publisher,
+    graphs: graphs::Graphs,
+    lifetime: lifetime::Lifetime,
+    /// Settings of the sensors, shared with the decoding loop
+    sensor_store: Arc<RwLock<SensorStore>>,
+    dedup: Arc<dedup::Statistics>,
+    /// Decoding errors, by kind and sensor
+    errors: errors::ErrorStats,
+    /// Timing residuals between pairs of sensors
+    timing: timing::Timing,
+    altimeter: qnh::Altimeter,
 }
 
 #[derive(Debug, Default, PartialEq)]
@@ -638,6 +1907,27 @@ fn update(
                 (false, Char('l')) => jet1090.sort_key = SortKey::LAST,
                 (false, Char('-')) => jet1090.sort_asc = !jet1090.sort_asc,
                 (false, Char('/')) => jet1090.is_search_mode = true,
+                (false, Char('t')) => {
+                    use class::TargetClass::*;
+                    jet1090.class_filter = match jet1090.class_filter {
+                        None => Some(Aircraft),
+                        Some(Aircraft) => Some(Vehicle),
+                        Some(Vehicle) => None,
+                    }
+                }
+                (false, Char('m')) => {
+                    use Allocation::*;
+                    jet1090.allocation_filter = match jet1090.allocation_filter
+                    {
+                        None => Some(Military),
+                        Some(Military) => Some(Special),
+                        Some(Special) => Some(Unallocated),
+                        Some(Unallocated) | Some(Civil) => None,
+                    }
+                }
+                (false, Char('s')) => {
+                    jet1090.show_sensors = !jet1090.show_sensors
+                }
                 _ => {}
             }
         }
@@ -648,6 +1938,130 @@ fn update(
 }
 
 impl Jet1090 {
+    /// Send the summaries of the flights still in progress, e.g. at exit
+    pub async fn close_flights(&mut self) {
+        let state_vectors = std::mem::take(&mut self.state_vectors);
+        for sv in state_vectors.into_values() {
+            let summary = sv.flight.summary(sv.cur);
+            self.events
+                .send(event::Event::FlightClosed(Arc::new(summary)))
+                .await;
+        }
+    }
+
+    /// Count the message in the activity of all the sensors receiving it
+    pub async fn record_activity(&mut self, msg: &TimedMessage) {
+        let now = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("SystemTime before unix epoch")
+            .as_secs();
+        for meta in &msg.metadata {
+            if let Some(sensor) = self.sensors.get_mut(&meta.serial) {
+                sensor.activity.record(now, meta.rssi);
+                sensor.interval_count += 1;
+                if let Some(rssi) = meta.rssi {
+                    sensor.locator.record_message(msg, now, rssi);
+                }
+                if let Some(reference) = &sensor.reference {
+                    sensor.coverage.record(msg, reference);
+                }
+                if meta.time_source.is_some() {
+                    sensor.time_source = meta.time_source;
+                }
+                if !sensor.online {
+                    sensor.online = true;
+                    let status = sensor.status(now);
+                    self.events.send(event::Event::SensorUp(status)).await;
+                }
+            }
+        }
+        let references = msg.metadata.iter().filter_map(|meta| {
+            self.sensors.get(&meta.serial)?.reference.as_ref()
+        });
+        self.graphs.record(msg, now, references);
+        self.lifetime.record(msg);
+        self.timing.record(msg, |serial| {
+            let sensor = self.sensors.get(&serial)?;
+            Some(timing::site(sensor.reference.as_ref()?, sensor.altitude))
+        });
+    }
+
+    /// Report the sensors without any message for a while
+    pub async fn check_sensors(&mut self) {
+        let now = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("SystemTime before unix epoch")
+            .as_secs();
+        for sensor in self.sensors.values_mut() {
+            let last = sensor.activity.last().unwrap_or_default();
+            if sensor.online && now > last + event::SENSOR_TIMEOUT {
+                sensor.online = false;
+                let status = sensor.status(now);
+                self.events.send(event::Event::SensorDown(status)).await;
+            }
+        }
+    }
+
+    /// Send the heartbeat of all the sensors
+    pub async fn heartbeat(&mut self) {
+        let now = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("SystemTime before unix epoch")
+            .as_secs();
+        for sensor in self.sensors.values_mut() {
+            let heartbeat = sensor.heartbeat(now);
+            self.events.send(event::Event::Heartbeat(heartbeat)).await;
+        }
+    }
+
+    /**
+     * Estimate the position of sensors from the received traffic.
+     *
+     * The estimate is suggested for sensors without any reference position;
+     * references too far from the estimate are reported as misconfigured.
+     */
+    pub fn locate_sensors(&mut self) {
+        let now = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("SystemTime before unix epoch")
+            .as_secs();
+        for sensor in self.sensors.values_mut() {
+            let previous = sensor.estimate;
+            sensor.estimate = sensor.locator.estimate(now);
+            let Some(estimate) = sensor.estimate else {
+                continue;
+            };
+            let label =
+                sensor.name.clone().unwrap_or(sensor.serial.to_string());
+            match sensor.reference {
+                None if previous.is_none() => info!(
+                    "Estimated position of sensor {}: {:.4},{:.4} (±{:.1} km), \
+                    consider using it as a reference",
+                    label,
+                    estimate.latitude,
+                    estimate.longitude,
+                    estimate.uncertainty
+                ),
+                Some(reference)
+                    if estimate.disagrees(&reference)
+                        && !previous
+                            .is_some_and(|p| p.disagrees(&reference)) =>
+                {
+                    warn!(
+                        "The reference of sensor {} is {:.0} km away from its \
+                        estimated position {:.4},{:.4} (±{:.1} km)",
+                        label,
+                        estimate.distance(&reference),
+                        estimate.latitude,
+                        estimate.longitude,
+                        estimate.uncertainty
+                    )
+                }
+                _ => {}
+            }
+        }
+    }
+
     pub fn receivers(&mut self) {
         for sensor in self.sensors.values_mut() {
             sensor.aircraft_count = 0;
@@ -704,6 +2118,20 @@ impl Jet1090 {
     }
 }
 
+#[derive(Debug, Clone, Subcommand)]
+enum Commands {
+    /// Browse the messages archived with --archive
+    Archive {
+        #[command(subcommand)]
+        command: archive::Command,
+    },
+    /// Locate or create the configuration file
+    Config {
+        #[command(subcommand)]
+        command: config::Command,
+    },
+}
+
 fn print_completions<G: Generator>(gen: G, cmd: &mut Command) {
     generate(gen, cmd, cmd.get_name().to_string(), &mut io::stdout());
 }
@@ -711,7 +2139,15 @@ fn print_completions<G: Generator>(gen: G, cmd: &mut Command) {
 #[cfg(test)]
 mod tests {
 
-    use crate::Options;
+    use crate::{config, dedup, Options};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_template() {
+        let options: Options = toml::from_str(config::TEMPLATE).unwrap();
+        assert!(options.sources.is_empty());
+        assert!(!options.interactive);
+    }
 
     #[test]
     fn test_config() {
@@ -732,11 +2168,21 @@ mod tests {
             udp = "0.0.0.0:3456"
             latitude = 48.723
             longitude = 2.379
+            protocol = "flarm"
+
+            [deduplication_protocols]
+            flarm = 1000
             "#,
         )
         .unwrap();
 
         assert!(options.interactive);
         assert_eq!(options.sources.len(), 2);
+        assert_eq!(options.sources[0].protocol, None);
+        assert_eq!(options.sources[1].protocol, Some(dedup::Protocol::Flarm));
+        assert_eq!(
+            options.deduplication_protocols,
+            HashMap::from([(dedup::Protocol::Flarm, 1000)])
+        );
     }
 }
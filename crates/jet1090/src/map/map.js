@@ -0,0 +1,356 @@
+// The REST API, also behind a reverse proxy and with a trailing slash
+const base = location.pathname.replace(/map\/?$/, "");
+
+// State vectors, as returned by the /all endpoint
+const aircraft = new Map();
+let version = null;
+let selected = null;
+let track = [];
+let sensors = [];
+
+// A Web Mercator map with raster tiles, and an overlay for the traffic
+const TILE = 256;
+const TILES = "https://tile.openstreetmap.org/{z}/{x}/{y}.png";
+const MIN_ZOOM = 2;
+const MAX_ZOOM = 14;
+
+const container = document.getElementById("map");
+const tiles = document.getElementById("tiles");
+const overlay = document.getElementById("overlay");
+const view = { longitude: 0, latitude: 30, zoom: 2 };
+
+// World coordinates (in pixels) at the current zoom level
+function project(longitude, latitude) {
+  const scale = TILE * 2 ** view.zoom;
+  const phi = (Math.max(-85, Math.min(85, latitude)) * Math.PI) / 180;
+  return [
+    ((longitude + 180) / 360) * scale,
+    ((1 - Math.log(Math.tan(phi) + 1 / Math.cos(phi)) / Math.PI) / 2) * scale,
+  ];
+}
+
+function unproject(x, y) {
+  const scale = TILE * 2 ** view.zoom;
+  const n = Math.PI - (2 * Math.PI * y) / scale;
+  return [
+    (x / scale) * 360 - 180,
+    (Math.atan(Math.sinh(n)) * 180) / Math.PI,
+  ];
+}
+
+// Screen coordinates of a position
+function screen(longitude, latitude) {
+  const [cx, cy] = project(view.longitude, view.latitude);
+  const [x, y] = project(longitude, latitude);
+  return [
+    x - cx + container.clientWidth / 2,
+    y - cy + container.clientHeight / 2,
+  ];
+}
+
+function drawTiles() {
+  const [cx, cy] = project(view.longitude, view.latitude);
+  const left = cx - container.clientWidth / 2;
+  const top = cy - container.clientHeight / 2;
+  const count = 2 ** view.zoom;
+  const wanted = new Map();
+  for (
+    let tx = Math.floor(left / TILE);
+    tx * TILE < left + container.clientWidth;
+    tx++
+  ) {
+    for (
+      let ty = Math.max(0, Math.floor(top / TILE));
+      ty * TILE < top + container.clientHeight && ty < count;
+      ty++
+    ) {
+      const x = ((tx % count) + count) % count;
+      const key = `${view.zoom}/${tx}/${ty}`;
+      const src = TILES.replace("{z}", view.zoom)
+        .replace("{x}", x)
+        .replace("{y}", ty);
+      wanted.set(key, [src, tx * TILE - left, ty * TILE - top]);
+    }
+  }
+  for (const img of [...tiles.children]) {
+    if (!wanted.has(img.dataset.key)) img.remove();
+  }
+  const existing = new Map(
+    [...tiles.children].map((img) => [img.dataset.key, img]),
+  );
+  for (const [key, [src, x, y]] of wanted) {
+    let img = existing.get(key);
+    if (!img) {
+      img = document.createElement("img");
+      img.dataset.key = key;
+      img.alt = "";
+      img.src = src;
+      tiles.append(img);
+    }
+    img.style.left = `${Math.round(x)}px`;
+    img.style.top = `${Math.round(y)}px`;
+  }
+}
+
+const emergency = (sv) => ["7500", "7600", "7700"].includes(sv.squawk);
+const located = (sv) => sv.latitude != null && sv.longitude != null;
+
+function drawArrow(ctx, x, y, track, size, color) {
+  ctx.save();
+  ctx.translate(x, y);
+  ctx.rotate(((track ?? 0) * Math.PI) / 180);
+  ctx.beginPath();
+  ctx.moveTo(0, -size / 2);
+  ctx.lineTo(size / 2 - 3, size / 2 - 2);
+  ctx.lineTo(0, size / 2 - 7);
+  ctx.lineTo(-size / 2 + 3, size / 2 - 2);
+  ctx.closePath();
+  ctx.fillStyle = color;
+  ctx.strokeStyle = "#fff";
+  ctx.lineWidth = 1;
+  ctx.fill();
+  ctx.stroke();
+  ctx.restore();
+}
+
+function drawOverlay() {
+  const ratio = window.devicePixelRatio || 1;
+  overlay.width = container.clientWidth * ratio;
+  overlay.height = container.clientHeight * ratio;
+  const ctx = overlay.getContext("2d");
+  ctx.scale(ratio, ratio);
+
+  for (const sensor of sensors) {
+    const [x, y] = screen(sensor.longitude, sensor.latitude);
+    ctx.fillStyle = "#333";
+    ctx.fillRect(x - 4, y - 4, 8, 8);
+  }
+
+  if (track.length > 1) {
+    ctx.beginPath();
+    track.forEach(([longitude, latitude], i) => {
+      const [x, y] = screen(longitude, latitude);
+      if (i === 0) ctx.moveTo(x, y);
+      else ctx.lineTo(x, y);
+    });
+    ctx.strokeStyle = "#e07000";
+    ctx.lineWidth = 2;
+    ctx.stroke();
+  }
+
+  for (const sv of aircraft.values()) {
+    if (!located(sv)) continue;
+    const [x, y] = screen(sv.longitude, sv.latitude);
+    const size = sv.icao24 === selected ? 30 : 24;
+    const color = emergency(sv) ? "#c00" : "#1f5fa0";
+    drawArrow(ctx, x, y, sv.track, size, color);
+  }
+}
+
+function draw() {
+  drawTiles();
+  drawOverlay();
+}
+
+function zoomTo(zoom, x, y) {
+  zoom = Math.max(MIN_ZOOM, Math.min(MAX_ZOOM, zoom));
+  if (zoom === view.zoom) return;
+  // Keep the position under the cursor in place
+  const [px, py] = project(view.longitude, view.latitude);
+  const dx = x - container.clientWidth / 2;
+  const dy = y - container.clientHeight / 2;
+  const [longitude, latitude] = unproject(px + dx, py + dy);
+  view.zoom = zoom;
+  const [qx, qy] = project(longitude, latitude);
+  [view.longitude, view.latitude] = unproject(qx - dx, qy - dy);
+  draw();
+}
+
+let drag = null;
+
+container.addEventListener("pointerdown", (e) => {
+  if (e.target.closest("#zoom")) return;
+  drag = { x: e.clientX, y: e.clientY, moved: false };
+  container.setPointerCapture(e.pointerId);
+});
+
+container.addEventListener("pointermove", (e) => {
+  if (!drag) return;
+  const dx = e.clientX - drag.x;
+  const dy = e.clientY - drag.y;
+  if (!drag.moved && Math.hypot(dx, dy) < 3) return;
+  drag.moved = true;
+  container.classList.add("dragging");
+  const [px, py] = project(view.longitude, view.latitude);
+  [view.longitude, view.latitude] = unproject(px - dx, py - dy);
+  drag.x = e.clientX;
+  drag.y = e.clientY;
+  draw();
+});
+
+container.addEventListener("pointerup", (e) => {
+  const click = drag && !drag.moved;
+  drag = null;
+  container.classList.remove("dragging");
+  if (!click) return;
+  // Select the closest aircraft under the cursor
+  const bounds = container.getBoundingClientRect();
+  const cx = e.clientX - bounds.left;
+  const cy = e.clientY - bounds.top;
+  let closest = null;
+  let distance = 15;
+  for (const sv of aircraft.values()) {
+    if (!located(sv)) continue;
+    const [x, y] = screen(sv.longitude, sv.latitude);
+    const d = Math.hypot(x - cx, y - cy);
+    if (d < distance) {
+      closest = sv.icao24;
+      distance = d;
+    }
+  }
+  if (closest) select(closest);
+});
+
+container.addEventListener(
+  "wheel",
+  (e) => {
+    e.preventDefault();
+    const bounds = container.getBoundingClientRect();
+    const zoom = view.zoom + (e.deltaY < 0 ? 1 : -1);
+    zoomTo(zoom, e.clientX - bounds.left, e.clientY - bounds.top);
+  },
+  { passive: false },
+);
+
+for (const [id, step] of [
+  ["zoom-in", 1],
+  ["zoom-out", -1],
+]) {
+  document.getElementById(id).addEventListener("click", () => {
+    const x = container.clientWidth / 2;
+    const y = container.clientHeight / 2;
+    zoomTo(view.zoom + step, x, y);
+  });
+}
+
+window.addEventListener("resize", draw);
+
+const value = (x, unit = "") => (x == null ? "" : `${x}${unit}`);
+
+function showDetails() {
+  const details = document.getElementById("details");
+  const sv = aircraft.get(selected);
+  if (!sv) {
+    details.innerHTML = "";
+    return;
+  }
+  const rows = [
+    ["icao24", sv.icao24],
+    ["callsign", sv.callsign],
+    ["registration", sv.registration],
+    ["typecode", sv.typecode],
+    ["squawk", sv.squawk],
+    ["altitude", value(sv.altitude, " ft")],
+    ["groundspeed", value(sv.groundspeed, " kts")],
+    ["track", value(sv.track, "°")],
+    ["vertical rate", value(sv.vertical_rate, " ft/mn")],
+    ["phase", sv.phase],
+    ["messages", sv.count],
+  ];
+  details.innerHTML =
+    "<table>" +
+    rows
+      .filter(([, v]) => v != null && v !== "")
+      .map(([k, v]) => `<tr><th>${k}</th><td>${v}</td></tr>`)
+      .join("") +
+    "</table>";
+}
+
+function showList() {
+  const tbody = document.getElementById("aircraft");
+  const sorted = [...aircraft.values()].sort(
+    (a, b) => b.lastseen - a.lastseen,
+  );
+  tbody.innerHTML = sorted
+    .map(
+      (sv) =>
+        `<tr data-icao24="${sv.icao24}"` +
+        ` class="${sv.icao24 === selected ? "selected" : ""}` +
+        `${emergency(sv) ? " emergency" : ""}">` +
+        `<td>${sv.icao24}</td><td>${value(sv.callsign)}</td>` +
+        `<td>${value(sv.altitude)}</td>` +
+        `<td>${value(sv.groundspeed && Math.round(sv.groundspeed))}</td>` +
+        "</tr>",
+    )
+    .join("");
+}
+
+function refresh() {
+  drawOverlay();
+  showDetails();
+  showList();
+  document.getElementById("status").textContent = `${aircraft.size} aircraft`;
+}
+
+async function select(icao24) {
+  selected = icao24 === selected ? null : icao24;
+  track = [];
+  refresh();
+  if (!selected) return;
+  const response = await fetch(`${base}track?icao24=${selected}`);
+  const history = (await response.json()) ?? [];
+  track = history
+    .filter(located)
+    .map((msg) => [msg.longitude, msg.latitude]);
+  drawOverlay();
+}
+
+async function poll() {
+  try {
+    const query = version == null ? "" : `?changed_since=${version}`;
+    const response = await fetch(`${base}all${query}`);
+    const changes = await response.json();
+    if (version == null) {
+      // The first query returns the list of all state vectors
+      aircraft.clear();
+      changes.forEach((sv) => aircraft.set(sv.icao24, sv));
+      version = Number(response.headers.get("etag").replace(/"/g, ""));
+    } else {
+      if (changes.full) aircraft.clear();
+      changes.aircraft.forEach((sv) => aircraft.set(sv.icao24, sv));
+      changes.removed.forEach((icao24) => aircraft.delete(icao24));
+      version = changes.version;
+    }
+    refresh();
+  } catch (e) {
+    version = null;
+    document.getElementById("status").textContent =
+      `Connection lost (${e.message})`;
+  }
+  setTimeout(poll, 1000);
+}
+
+async function center() {
+  // Center the map on the receivers
+  try {
+    const response = await (await fetch(`${base}sensors`)).json();
+    sensors = Object.values(response)
+      .map((sensor) => sensor.reference)
+      .filter((reference) => reference != null);
+    if (sensors.length > 0) {
+      view.longitude = sensors[0].longitude;
+      view.latitude = sensors[0].latitude;
+      view.zoom = 7;
+    }
+  } catch (e) {}
+  draw();
+}
+
+document.getElementById("aircraft").addEventListener("click", (e) => {
+  const row = e.target.closest("tr");
+  if (row) select(row.dataset.icao24);
+});
+
+draw();
+center();
+poll();
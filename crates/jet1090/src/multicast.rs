@@ -0,0 +1,79 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::net::UdpSocket;
+
+/// Default time to live of multicast datagrams: do not leave the LAN
+pub const TTL: u32 = 1;
+
+/**
+ * A socket sending datagrams to a multicast group.
+ *
+ * The TTL (hop limit in IPv6) bounds the number of routers the datagrams
+ * may cross. The interface is the address of a local interface for IPv4
+ * groups, or the index of an interface for IPv6 groups; the system picks
+ * one (from the routing table) if not set. Datagrams are also looped back
+ * to listeners on the same host.
+ */
+pub fn socket(
+    group: SocketAddr,
+    ttl: u32,
+    interface: Option<&str>,
+) -> io::Result<UdpSocket> {
+    if !group.ip().is_multicast() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a multicast address", group.ip()),
+        ));
+    }
+    let invalid = |interface: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid multicast interface: {interface}"),
+        )
+    };
+    let socket = Socket::new(
+        Domain::for_address(group),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    match group {
+        SocketAddr::V4(_) => {
+            socket.set_multicast_ttl_v4(ttl)?;
+            socket.set_multicast_loop_v4(true)?;
+            if let Some(interface) = interface {
+                let address: Ipv4Addr =
+                    interface.parse().map_err(|_| invalid(interface))?;
+                socket.set_multicast_if_v4(&address)?;
+            }
+            socket.bind(&SocketAddr::from(([0, 0, 0, 0], 0)).into())?;
+        }
+        SocketAddr::V6(_) => {
+            socket.set_multicast_hops_v6(ttl)?;
+            socket.set_multicast_loop_v6(true)?;
+            if let Some(interface) = interface {
+                let index: u32 =
+                    interface.parse().map_err(|_| invalid(interface))?;
+                socket.set_multicast_if_v6(index)?;
+            }
+            socket.bind(&SocketAddr::from(([0u16; 8], 0)).into())?;
+        }
+    }
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_socket() {
+        let group: SocketAddr = "239.255.10.90:30090".parse().unwrap();
+        assert!(socket(group, 4, None).is_ok());
+        assert!(socket(group, 1, Some("127.0.0.1")).is_ok());
+        assert!(socket(group, 1, Some("eth0")).is_err());
+        let unicast: SocketAddr = "127.0.0.1:30090".parse().unwrap();
+        assert!(socket(unicast, 1, None).is_err());
+    }
+}
@@ -0,0 +1,329 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use crate::tasks;
+
+/**
+ * When to start a new output file.
+ *
+ * Time based rotations follow UTC hours and days; size based rotations
+ * accept a number of bytes with an optional unit, e.g. `100MB`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Rotation {
+    Hourly,
+    Daily,
+    Size(u64),
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hourly" => return Ok(Rotation::Hourly),
+            "daily" => return Ok(Rotation::Daily),
+            _ => {}
+        }
+        let idx = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (value, unit) = s.split_at(idx);
+        let factor = match unit.to_ascii_uppercase().as_str() {
+            "" | "B" => 1,
+            "K" | "KB" => 1_000,
+            "M" | "MB" => 1_000_000,
+            "G" | "GB" => 1_000_000_000,
+            _ => return Err(format!("unknown rotation: {s}")),
+        };
+        match value.parse::<u64>() {
+            Ok(value) if value > 0 => Ok(Rotation::Size(value * factor)),
+            _ => {
+                Err(format!("unknown rotation: {s} (hourly, daily or a size)"))
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Rotation {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Rotation {
+    /// The default timestamp appended to file names without any template
+    fn suffix(&self) -> &'static str {
+        match self {
+            Rotation::Hourly => "-%Y%m%d-%H",
+            Rotation::Daily => "-%Y%m%d",
+            Rotation::Size(_) => "-%Y%m%d-%H%M%S",
+        }
+    }
+
+    /// True if a new file must be started before writing `len` more bytes
+    fn is_due(
+        &self,
+        opened: DateTime<Utc>,
+        now: DateTime<Utc>,
+        size: u64,
+        len: u64,
+    ) -> bool {
+        let changed = |fmt: &str| {
+            opened.format(fmt).to_string() != now.format(fmt).to_string()
+        };
+        match self {
+            Rotation::Hourly => changed("%Y%m%d%H"),
+            Rotation::Daily => changed("%Y%m%d"),
+            Rotation::Size(max) => size > 0 && size + len > *max,
+        }
+    }
+}
+
+/**
+ * Check the template of the output file name, and add a timestamp to it if
+ * files are rotated and the template does not contain any.
+ *
+ * Templates follow the strftime format, e.g. `dump-%Y%m%d-%H.jsonl`.
+ */
+pub fn template(
+    path: &Path,
+    rotation: Option<Rotation>,
+) -> Result<String, String> {
+    let template = path.to_string_lossy().to_string();
+    if StrftimeItems::new(&template).any(|item| item == Item::Error) {
+        return Err(format!("invalid output file template: {template}"));
+    }
+    let Some(rotation) = rotation.filter(|_| !template.contains('%')) else {
+        return Ok(template);
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{}{}", stem, rotation.suffix());
+    if let Some(extension) = path.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+    }
+    Ok(path.with_file_name(name).to_string_lossy().to_string())
+}
+
+/**
+ * Write lines to an output file, starting new files as configured.
+ *
+ * Rotated files are optionally compressed with gzip: the compressed file is
+ * written under a temporary name, then atomically renamed, so that a `.gz`
+ * file is always complete.
+ */
+pub struct OutputWriter {
+    template: String,
+    rotation: Option<Rotation>,
+    compress: bool,
+    file: Option<File>,
+    path: PathBuf,
+    opened: DateTime<Utc>,
+    size: u64,
+}
+
+impl OutputWriter {
+    pub fn new(
+        template: String,
+        rotation: Option<Rotation>,
+        compress: bool,
+    ) -> Self {
+        let opened = Utc::now();
+        OutputWriter {
+            path: render(&template, opened),
+            template,
+            rotation,
+            compress,
+            file: None,
+            opened,
+            size: 0,
+        }
+    }
+
+    /// The path of the current output file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn open(&mut self) -> io::Result<()> {
+        let file = fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .await?;
+        // Files are appended if the process restarts in the same period
+        self.size = file.metadata().await?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    async fn rotate(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush().await?;
+            info!("Closing output file {}", self.path.display());
+            if self.compress {
+                let path = self.path.clone();
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = compress(&path) {
+                        error!("Failed to compress {}: {}", path.display(), e);
+                    }
+                });
+            }
+        }
+        self.opened = now;
+        self.path = available(render(&self.template, now));
+        Ok(())
+    }
+
+    /// Write a line to the output file, after a rotation if needed
+    pub async fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if let Some(rotation) = self.rotation {
+            let now = Utc::now();
+            if rotation.is_due(self.opened, now, self.size, len) {
+                self.rotate(now).await?;
+            }
+        }
+        if self.file.is_none() {
+            self.open().await?;
+        }
+        if let Some(file) = &mut self.file {
+            file.write_all(line.as_bytes()).await?;
+            file.write_all("\n".as_bytes()).await?;
+            self.size += len;
+        }
+        Ok(())
+    }
+
+    /// Start the task writing the lines received on the returned channel
+    pub fn spawn(mut self) -> mpsc::Sender<String> {
+        let (tx, mut rx) = mpsc::channel::<String>(1024);
+        tasks::spawn("output file", async move {
+            while let Some(line) = rx.recv().await {
+                if let Err(e) = self.write_line(&line).await {
+                    error!("Failed to write to {}: {}", self.path.display(), e);
+                }
+            }
+            if let Some(mut file) = self.file.take() {
+                let _ = file.flush().await;
+            }
+        });
+        tx
+    }
+}
+
+fn render(template: &str, time: DateTime<Utc>) -> PathBuf {
+    PathBuf::from(time.format(template).to_string())
+}
+
+/// A path not used by any previous file, with a counter if needed
+fn available(path: PathBuf) -> PathBuf {
+    let exists = |path: &Path| {
+        let mut gz = path.as_os_str().to_owned();
+        gz.push(".gz");
+        path.exists() || Path::new(&gz).exists()
+    };
+    if !exists(&path) {
+        return path;
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|i| path.with_file_name(format!("{stem}-{i}{extension}")))
+        .find(|path| !exists(path))
+        .unwrap()
+}
+
+/// Compress a file with gzip, then remove it
+fn compress(path: &Path) -> io::Result<()> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    let target = PathBuf::from(name);
+    let mut tmp = target.as_os_str().to_owned();
+    tmp.push(".part");
+    let tmp = PathBuf::from(tmp);
+
+    let mut input = std::fs::File::open(path)?;
+    let output = std::fs::File::create(&tmp)?;
+    let mut encoder =
+        flate2::write::GzEncoder::new(output, flate2::Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    std::fs::rename(&tmp, &target)?;
+    std::fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::io::Read;
+
+    #[test]
+    fn test_rotation() {
+        assert_eq!("hourly".parse(), Ok(Rotation::Hourly));
+        assert_eq!("100MB".parse(), Ok(Rotation::Size(100_000_000)));
+        assert_eq!("512k".parse(), Ok(Rotation::Size(512_000)));
+        assert!("weekly".parse::<Rotation>().is_err());
+        assert!("0MB".parse::<Rotation>().is_err());
+
+        let t0 = Utc.with_ymd_and_hms(2024, 1, 1, 10, 59, 0).unwrap();
+        let t1 = Utc.with_ymd_and_hms(2024, 1, 1, 11, 0, 0).unwrap();
+        assert!(Rotation::Hourly.is_due(t0, t1, 10, 10));
+        assert!(!Rotation::Daily.is_due(t0, t1, 10, 10));
+        assert!(Rotation::Size(100).is_due(t0, t0, 95, 10));
+        // A line is always written to an empty file
+        assert!(!Rotation::Size(100).is_due(t0, t0, 0, 200));
+    }
+
+    #[test]
+    fn test_template() {
+        let path = Path::new("/data/dump.jsonl");
+        assert_eq!(template(path, None).unwrap(), "/data/dump.jsonl");
+        assert_eq!(
+            template(path, Some(Rotation::Hourly)).unwrap(),
+            "/data/dump-%Y%m%d-%H.jsonl"
+        );
+        let path = Path::new("/data/dump-%Y%m%d.jsonl");
+        assert_eq!(
+            template(path, Some(Rotation::Hourly)).unwrap(),
+            "/data/dump-%Y%m%d.jsonl"
+        );
+        assert!(template(Path::new("dump-%Q.jsonl"), None).is_err());
+
+        let t0 = Utc.with_ymd_and_hms(2024, 1, 1, 10, 59, 0).unwrap();
+        assert_eq!(
+            render("/data/dump-%Y%m%d-%H.jsonl", t0),
+            PathBuf::from("/data/dump-20240101-10.jsonl")
+        );
+    }
+
+    #[test]
+    fn test_compress() {
+        let path = std::env::temp_dir().join("jet1090_test_output.jsonl");
+        std::fs::write(&path, "{}\n").unwrap();
+        compress(&path).unwrap();
+        assert!(!path.exists());
+
+        let gz = std::env::temp_dir().join("jet1090_test_output.jsonl.gz");
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(std::fs::File::open(&gz).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        std::fs::remove_file(&gz).unwrap();
+        assert_eq!(content, "{}\n");
+    }
+}
@@ -0,0 +1,172 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Vertical rate (in ft/min) above which an aircraft starts climbing
+const CLIMB_ENTER: i16 = 500;
+/// Vertical rate (in ft/min) below which a climbing aircraft levels off
+const CLIMB_EXIT: i16 = 250;
+/// Ground speed (in kts) above which an aircraft on the ground is taxiing
+const TAXI_ENTER: f64 = 5.;
+/// Ground speed (in kts) below which a taxiing aircraft is stopped
+const TAXI_EXIT: f64 = 2.;
+/// Duration (in seconds) a new phase must last before being confirmed
+const CONFIRM_DURATION: f64 = 10.;
+
+/**
+ * The phase of flight, or motion state, of an aircraft
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlightPhase {
+    /// On the ground, stopped (e.g. at the gate)
+    Ground,
+    /// On the ground, moving
+    Taxi,
+    Climb,
+    Cruise,
+    Descent,
+}
+
+impl fmt::Display for FlightPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            FlightPhase::Ground => "ground",
+            FlightPhase::Taxi => "taxi",
+            FlightPhase::Climb => "climb",
+            FlightPhase::Cruise => "cruise",
+            FlightPhase::Descent => "descent",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/**
+ * Derive the flight phase from successive observations of an aircraft.
+ *
+ * Hysteresis prevents the phase from flickering: thresholds to enter a
+ * phase are stricter than thresholds to stay in it, and a new phase is only
+ * confirmed after it has been observed for a few seconds.
+ */
+#[derive(Debug, Default)]
+pub struct PhaseClassifier {
+    /// The confirmed phase
+    phase: Option<FlightPhase>,
+    /// A new phase waiting for confirmation, with the time it was first seen
+    candidate: Option<(FlightPhase, f64)>,
+    /// Whether the aircraft was last seen on the ground
+    on_ground: Option<bool>,
+}
+
+impl PhaseClassifier {
+    /**
+     * Update the classifier with the latest state of the aircraft.
+     *
+     * `on_ground` is known from the type of position messages (surface or
+     * airborne), None if the current message does not carry the information.
+     */
+    pub fn update(
+        &mut self,
+        timestamp: f64,
+        on_ground: Option<bool>,
+        vertical_rate: Option<i16>,
+        groundspeed: Option<f64>,
+    ) -> Option<FlightPhase> {
+        if on_ground.is_some() {
+            self.on_ground = on_ground;
+        }
+        let Some(observed) = self.classify(vertical_rate, groundspeed) else {
+            return self.phase;
+        };
+
+        match (self.phase, self.candidate) {
+            (None, _) => {
+                self.phase = Some(observed);
+                self.candidate = None;
+            }
+            (Some(phase), _) if phase == observed => self.candidate = None,
+            (_, Some((candidate, since))) if candidate == observed => {
+                if timestamp - since >= CONFIRM_DURATION {
+                    self.phase = Some(observed);
+                    self.candidate = None;
+                }
+            }
+            _ => self.candidate = Some((observed, timestamp)),
+        }
+        self.phase
+    }
+
+    /// The phase suggested by the current observation
+    fn classify(
+        &self,
+        vertical_rate: Option<i16>,
+        groundspeed: Option<f64>,
+    ) -> Option<FlightPhase> {
+        use FlightPhase::*;
+        match self.on_ground? {
+            true => {
+                let threshold = match self.phase {
+                    Some(Taxi) => TAXI_EXIT,
+                    _ => TAXI_ENTER,
+                };
+                match groundspeed {
+                    Some(gs) if gs >= threshold => Some(Taxi),
+                    Some(_) => Some(Ground),
+                    None => self.phase.filter(|p| matches!(p, Ground | Taxi)),
+                }
+            }
+            false => {
+                let vr = vertical_rate?;
+                let (climb, descent) = match self.phase {
+                    Some(Climb) => (CLIMB_EXIT, -CLIMB_ENTER),
+                    Some(Descent) => (CLIMB_ENTER, -CLIMB_EXIT),
+                    _ => (CLIMB_ENTER, -CLIMB_ENTER),
+                };
+                Some(match vr {
+                    vr if vr >= climb => Climb,
+                    vr if vr <= descent => Descent,
+                    _ => Cruise,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use FlightPhase::*;
+
+    #[test]
+    fn test_phase_hysteresis() {
+        let mut classifier = PhaseClassifier::default();
+        // No position yet: we don't know whether the aircraft is airborne
+        assert_eq!(classifier.update(0., None, Some(1000), None), None);
+
+        assert_eq!(
+            classifier.update(1., Some(false), Some(1000), None),
+            Some(Climb)
+        );
+        // Levelling off is only confirmed after a while
+        assert_eq!(classifier.update(2., None, Some(0), None), Some(Climb));
+        assert_eq!(classifier.update(5., None, Some(0), None), Some(Climb));
+        assert_eq!(classifier.update(12., None, Some(0), None), Some(Cruise));
+        // Small vertical rates do not count as climbing
+        assert_eq!(classifier.update(13., None, Some(300), None), Some(Cruise));
+        assert_eq!(classifier.update(30., None, Some(300), None), Some(Cruise));
+
+        // Touchdown
+        let ground = Some(true);
+        assert_eq!(
+            classifier.update(40., ground, None, Some(60.)),
+            Some(Cruise)
+        );
+        assert_eq!(classifier.update(50., ground, None, Some(20.)), Some(Taxi));
+        // Slow taxi remains taxi
+        assert_eq!(classifier.update(60., ground, None, Some(3.)), Some(Taxi));
+        assert_eq!(classifier.update(61., ground, None, Some(0.)), Some(Taxi));
+        assert_eq!(
+            classifier.update(75., ground, None, Some(0.)),
+            Some(Ground)
+        );
+    }
+}
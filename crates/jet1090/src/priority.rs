@@ -0,0 +1,231 @@
+use rs1090::prelude::*;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+use tracing::warn;
+
+use crate::tasks;
+
+/// How often shed messages are reported in the logs (in seconds)
+const REPORT_INTERVAL: u64 = 10;
+
+/// The priority of a message in the decoding queue
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    /// ADS-B positions and velocities, which keep tracks up to date
+    High,
+    /// All the other messages, e.g. DF11 all-call replies
+    Low,
+}
+
+impl Priority {
+    /// The priority of a raw frame, based on the downlink format and typecode
+    pub fn of(frame: &[u8]) -> Self {
+        let df = frame.first().map_or(0, |byte| byte >> 3);
+        let tc = frame.get(4).map_or(0, |byte| byte >> 3);
+        match (df, tc) {
+            // Surface and airborne positions (5 to 18, 20 to 22), velocity (19)
+            (17 | 18, 5..=22) => Priority::High,
+            _ => Priority::Low,
+        }
+    }
+}
+
+/// Metrics about the priority queue, as returned by the REST API
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Stats {
+    /// The maximum number of pending messages
+    pub capacity: usize,
+    /// Number of pending high priority messages
+    pub high: usize,
+    /// Number of pending low priority messages
+    pub low: usize,
+    /// Number of high priority messages shed since the start
+    pub shed_high: u64,
+    /// Number of low priority messages shed since the start
+    pub shed_low: u64,
+}
+
+#[derive(Default)]
+struct Queues {
+    high: VecDeque<TimedMessage>,
+    low: VecDeque<TimedMessage>,
+    shed_high: u64,
+    shed_low: u64,
+    closed: bool,
+}
+
+/**
+ * A bounded queue with two priorities, between the sources and the decoder.
+ *
+ * High priority messages are always decoded first. When more than
+ * `capacity` messages are pending, the oldest low priority messages are
+ * shed first, then the oldest high priority ones: under overload, the
+ * decoding latency remains bounded and tracks remain responsive.
+ */
+pub struct PriorityQueue {
+    capacity: usize,
+    queues: Mutex<Queues>,
+    notify: Notify,
+}
+
+impl PriorityQueue {
+    pub fn new(capacity: usize) -> Self {
+        PriorityQueue {
+            capacity: capacity.max(1),
+            queues: Mutex::new(Queues::default()),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Queue a message, shedding older messages if the queue is full
+    pub fn push(&self, msg: TimedMessage) {
+        let mut queues = self.queues.lock().unwrap();
+        match Priority::of(&msg.frame) {
+            Priority::High => queues.high.push_back(msg),
+            Priority::Low => queues.low.push_back(msg),
+        }
+        while queues.high.len() + queues.low.len() > self.capacity {
+            if queues.low.pop_front().is_some() {
+                queues.shed_low += 1;
+            } else if queues.high.pop_front().is_some() {
+                queues.shed_high += 1;
+            }
+        }
+        drop(queues);
+        self.notify.notify_one();
+    }
+
+    /// Take the next message, high priority first
+    pub fn pop(&self) -> Option<TimedMessage> {
+        let mut queues = self.queues.lock().unwrap();
+        queues.high.pop_front().or_else(|| queues.low.pop_front())
+    }
+
+    /// No more messages will be queued
+    pub fn close(&self) {
+        self.queues.lock().unwrap().closed = true;
+        self.notify.notify_one();
+    }
+
+    /// Wait for the next message, None if the queue is closed and empty
+    pub async fn recv(&self) -> Option<TimedMessage> {
+        loop {
+            if let Some(msg) = self.pop() {
+                return Some(msg);
+            }
+            if self.queues.lock().unwrap().closed {
+                return None;
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        let queues = self.queues.lock().unwrap();
+        Stats {
+            capacity: self.capacity,
+            high: queues.high.len(),
+            low: queues.low.len(),
+            shed_high: queues.shed_high,
+            shed_low: queues.shed_low,
+        }
+    }
+}
+
+/**
+ * Insert the messages received from the sources in the priority queue, and
+ * forward them (by priority) to the next stage, i.e. the deduplication.
+ */
+pub async fn prioritize(
+    mut rx: mpsc::Receiver<TimedMessage>,
+    tx: mpsc::Sender<TimedMessage>,
+    queue: Arc<PriorityQueue>,
+) {
+    let queue_in = queue.clone();
+    tasks::spawn("priority queue", async move {
+        while let Some(msg) = rx.recv().await {
+            queue_in.push(msg);
+        }
+        queue_in.close();
+    });
+    while let Some(msg) = queue.recv().await {
+        if tx.send(msg).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Log a warning when messages were shed since the previous report
+pub async fn monitor(queue: Arc<PriorityQueue>) {
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(REPORT_INTERVAL));
+    let mut previous = Stats::default();
+    loop {
+        interval.tick().await;
+        let stats = queue.stats();
+        let (high, low) = (
+            stats.shed_high - previous.shed_high,
+            stats.shed_low - previous.shed_low,
+        );
+        if high + low > 0 {
+            warn!(
+                "Decoder overloaded: {} high and {} low priority messages \
+                shed in the last {} seconds",
+                high, low, REPORT_INTERVAL
+            );
+        }
+        previous = stats;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::timed;
+
+    #[test]
+    fn test_priority() {
+        // Airborne position, velocity, identification and all-call reply
+        let position = "8d40621d58c382d690c8ac2863a7";
+        let velocity = "8d485020994409940838175b284f";
+        let identification = "8d4840d6202cc371c32ce0576098";
+        let allcall = "5d4840d6d6b9e5";
+        assert_eq!(
+            Priority::of(&hex::decode(position).unwrap()),
+            Priority::High
+        );
+        assert_eq!(
+            Priority::of(&hex::decode(velocity).unwrap()),
+            Priority::High
+        );
+        assert_eq!(
+            Priority::of(&hex::decode(identification).unwrap()),
+            Priority::Low
+        );
+        assert_eq!(Priority::of(&hex::decode(allcall).unwrap()), Priority::Low);
+
+        let queue = PriorityQueue::new(3);
+        queue.push(timed(allcall, 0., 1));
+        queue.push(timed(identification, 0., 1));
+        queue.push(timed(position, 0., 1));
+        queue.push(timed(velocity, 0., 1));
+        // The oldest low priority message is shed first
+        let stats = queue.stats();
+        assert_eq!((stats.high, stats.low, stats.shed_low), (2, 1, 1));
+
+        // High priority messages come first
+        assert_eq!(queue.pop().unwrap().frame, hex::decode(position).unwrap());
+        assert_eq!(queue.pop().unwrap().frame, hex::decode(velocity).unwrap());
+        let low = queue.pop().unwrap();
+        assert_eq!(low.frame, hex::decode(identification).unwrap());
+        assert!(queue.pop().is_none());
+
+        for _ in 0..5 {
+            queue.push(timed(position, 0., 1));
+        }
+        assert_eq!(queue.stats().shed_high, 2);
+    }
+}
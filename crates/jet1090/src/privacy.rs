@@ -0,0 +1,294 @@
+use rs1090::decode::crc::modes_checksum;
+use rs1090::prelude::*;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::str::FromStr;
+
+/// Pseudonymous addresses are taken in a block never allocated to any State
+const PSEUDONYM_BLOCK: u32 = 0xf80000;
+const PSEUDONYM_MASK: u32 = 0x07ffff;
+
+/**
+ * A range of aircraft addresses, written as a single address (`3c6444`), an
+ * inclusive range (`3c0000-3c0fff`) or a prefix (`3c66*`).
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct AddressRange {
+    start: u32,
+    end: u32,
+}
+
+impl FromStr for AddressRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            u32::from_str_radix(value, 16)
+                .ok()
+                .filter(|_| value.len() == 6)
+                .ok_or_else(|| format!("invalid aircraft address: {s}"))
+        };
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => match s.strip_suffix('*') {
+                Some(prefix) if prefix.len() < 6 => (
+                    parse(&format!("{prefix:0<6}"))?,
+                    parse(&format!("{prefix:f<6}"))?,
+                ),
+                Some(_) => return Err(format!("invalid address prefix: {s}")),
+                None => (parse(s)?, parse(s)?),
+            },
+        };
+        if start > end {
+            return Err(format!("empty address range: {s}"));
+        }
+        Ok(AddressRange { start, end })
+    }
+}
+
+impl TryFrom<String> for AddressRange {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl AddressRange {
+    fn contains(&self, icao24: &ICAO) -> bool {
+        (self.start..=self.end).contains(&icao24.0)
+    }
+}
+
+/**
+ * Drop or anonymize messages from a list of aircraft addresses, before any
+ * sink (stdout, output file, Redis, state vectors) receives them.
+ *
+ * Anonymized aircraft are given a pseudonymous address, derived from a
+ * salted hash of their address: the same aircraft keeps the same pseudonym
+ * as long as the salt does not change. Identification messages (callsign,
+ * registration) of anonymized aircraft are dropped.
+ */
+#[derive(Debug, Default)]
+pub struct Privacy {
+    drop: Vec<AddressRange>,
+    anonymize: Vec<AddressRange>,
+    salt: Vec<u8>,
+}
+
+impl Privacy {
+    /// Without any salt, pseudonyms are only stable for the current process
+    pub fn new(
+        drop: Vec<AddressRange>,
+        anonymize: Vec<AddressRange>,
+        salt: Option<String>,
+    ) -> Self {
+        let salt = salt.map(String::into_bytes).unwrap_or_else(|| {
+            RandomState::new().hash_one(0u8).to_be_bytes().to_vec()
+        });
+        Privacy {
+            drop,
+            anonymize,
+            salt,
+        }
+    }
+
+    /// The pseudonymous address of an anonymized aircraft
+    pub fn pseudonym(&self, icao24: &ICAO) -> ICAO {
+        let digest = Sha256::new()
+            .chain_update(&self.salt)
+            .chain_update(icao24.0.to_be_bytes())
+            .finalize();
+        let value = u32::from_be_bytes([0, digest[0], digest[1], digest[2]]);
+        ICAO(PSEUDONYM_BLOCK | (value & PSEUDONYM_MASK))
+    }
+
+    /**
+     * Apply the privacy settings to a message.
+     *
+     * Returns false if the message must be dropped; otherwise, the address
+     * of anonymized aircraft is replaced in both the decoded message and the
+     * raw frame (with a consistent parity field).
+     */
+    pub fn apply(&self, msg: &mut TimedMessage) -> bool {
+        let Some(icao24) = msg.message.as_ref().and_then(|m| m.icao24()) else {
+            return true;
+        };
+        if self.drop.iter().any(|range| range.contains(&icao24)) {
+            return false;
+        }
+        if !self.anonymize.iter().any(|range| range.contains(&icao24)) {
+            return true;
+        }
+        let pseudonym = self.pseudonym(&icao24);
+        let delta = rewrite_frame(&mut msg.frame, icao24, pseudonym);
+        match &mut msg.message {
+            Some(message) => rewrite_message(message, pseudonym, delta),
+            None => true,
+        }
+    }
+}
+
+/**
+ * Replace the address in a raw frame, and return the change applied to the
+ * parity field.
+ *
+ * The address is explicit in DF11, DF17 and DF18 frames: the parity is
+ * computed again, so that the interrogator identifier (if any) is kept.
+ * Other downlink formats overlay the address on the parity.
+ */
+fn rewrite_frame(frame: &mut [u8], old: ICAO, new: ICAO) -> u32 {
+    let bits = frame.len() * 8;
+    match frame.first().map(|first| first >> 3) {
+        Some(11 | 17 | 18) if frame.len() >= 7 => {
+            let before = modes_checksum(frame, bits).unwrap_or_default();
+            frame[1..4].copy_from_slice(&new.0.to_be_bytes()[1..]);
+            let after = modes_checksum(frame, bits).unwrap_or_default();
+            xor_parity(frame, before ^ after);
+            before ^ after
+        }
+        _ => {
+            xor_parity(frame, old.0 ^ new.0);
+            old.0 ^ new.0
+        }
+    }
+}
+
+fn xor_parity(frame: &mut [u8], delta: u32) {
+    let Some(start) = frame.len().checked_sub(3) else {
+        return;
+    };
+    for (byte, d) in frame[start..].iter_mut().zip(&delta.to_be_bytes()[1..]) {
+        *byte ^= d;
+    }
+}
+
+/// Replace the address in a decoded message, false for identification messages
+fn rewrite_message(message: &mut Message, new: ICAO, delta: u32) -> bool {
+    match &mut message.df {
+        ShortAirAirSurveillance { ap, .. }
+        | SurveillanceAltitudeReply { ap, .. }
+        | SurveillanceIdentityReply { ap, .. }
+        | LongAirAirSurveillance { ap, .. } => {
+            *ap = new;
+            message.crc = new.0;
+        }
+        CommBAltitudeReply { bds, .. } if bds.bds20.is_some() => return false,
+        CommBAltitudeReply { bds, .. } if bds.bds21.is_some() => return false,
+        CommBIdentityReply { bds, .. } if bds.bds20.is_some() => return false,
+        CommBIdentityReply { bds, .. } if bds.bds21.is_some() => return false,
+        CommBAltitudeReply { ap, .. } | CommBIdentityReply { ap, .. } => {
+            *ap = new;
+            message.crc = new.0;
+        }
+        AllCallReply { icao, p_icao, .. } => {
+            *icao = new;
+            p_icao.0 ^= delta;
+        }
+        ExtendedSquitterADSB(adsb) => {
+            if let ME::BDS08(_) = adsb.message {
+                return false;
+            }
+            adsb.icao24 = new;
+            adsb.parity.0 ^= delta;
+        }
+        ExtendedSquitterTisB { cf, pi } => {
+            if let TisBMessage::ExtendedSquitter(ME::BDS08(_)) = cf.me {
+                return false;
+            }
+            cf.aa = new;
+            pi.0 ^= delta;
+        }
+        ExtendedSquitterMilitary { .. } => {}
+        CommDExtended { parity, .. } => {
+            parity.0 ^= delta;
+            message.crc = new.0;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::timed;
+
+    #[test]
+    fn test_range() {
+        let range: AddressRange = "3c0000-3c0fff".parse().unwrap();
+        assert!(range.contains(&ICAO(0x3c0abc)));
+        assert!(!range.contains(&ICAO(0x3c1000)));
+        let range: AddressRange = "3c66*".parse().unwrap();
+        assert_eq!(range, "3c6600-3c66ff".parse().unwrap());
+        let range: AddressRange = "3C6444".parse().unwrap();
+        assert!(range.contains(&ICAO(0x3c6444)));
+        let range: AddressRange = "*".parse().unwrap();
+        assert_eq!(range, "000000-ffffff".parse().unwrap());
+
+        assert!("3c64".parse::<AddressRange>().is_err());
+        assert!("3c0fff-3c0000".parse::<AddressRange>().is_err());
+        assert!("3c6444*".parse::<AddressRange>().is_err());
+    }
+
+    #[test]
+    fn test_pseudonym() {
+        let privacy = Privacy::new(vec![], vec![], Some("salt".into()));
+        let pseudonym = privacy.pseudonym(&ICAO(0x406b90));
+        assert_eq!(pseudonym, privacy.pseudonym(&ICAO(0x406b90)));
+        assert_ne!(pseudonym, privacy.pseudonym(&ICAO(0x406b91)));
+        assert_eq!(pseudonym.0 & !PSEUDONYM_MASK, PSEUDONYM_BLOCK);
+        // No registration can be inferred from a pseudonym
+        assert_eq!(rs1090::data::tail::tail(pseudonym.0), None);
+
+        let other = Privacy::new(vec![], vec![], Some("pepper".into()));
+        assert_ne!(pseudonym, other.pseudonym(&ICAO(0x406b90)));
+    }
+
+    #[test]
+    fn test_apply() {
+        let privacy = Privacy::new(
+            vec!["4840d6".parse().unwrap()],
+            vec!["40*".parse().unwrap()],
+            Some("salt".into()),
+        );
+
+        // Dropped aircraft
+        let mut msg = timed("8d4840d6202cc371c32ce0576098", 0., 1);
+        assert!(!privacy.apply(&mut msg));
+
+        // Identification messages of anonymized aircraft
+        let mut msg = timed("8d406b902015a678d4d220aa4bda", 0., 1);
+        assert!(!privacy.apply(&mut msg));
+
+        // Other aircraft are untouched
+        let mut msg = timed("a0001910cc300030aa0000eae004", 0., 1);
+        let frame = msg.frame.clone();
+        assert!(privacy.apply(&mut msg));
+        assert_eq!(msg.frame, frame);
+
+        // Decoding the new frame must give the same message, with the
+        // pseudonym: extended squitters, all-call replies and Comm-B replies
+        let privacy = Privacy::new(
+            vec![],
+            vec!["*".parse().unwrap()],
+            Some("salt".into()),
+        );
+        for (frame, icao24) in [
+            ("8d40621d58c382d690c8ac2863a7", 0x40621d),
+            ("5d4d20237a55a6", 0x4d2023),
+            ("a0001910cc300030aa0000eae004", 0x020176),
+        ] {
+            let mut msg = timed(frame, 0., 1);
+            let frame = msg.frame.clone();
+            assert!(privacy.apply(&mut msg));
+            assert_ne!(msg.frame, frame);
+            let pseudonym = privacy.pseudonym(&ICAO(icao24));
+            let decoded = Message::try_from(msg.frame.as_slice()).unwrap();
+            assert_eq!(decoded.icao24(), Some(pseudonym));
+            assert_eq!(Some(decoded), msg.message);
+        }
+    }
+}
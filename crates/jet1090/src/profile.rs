@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, Filter, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The target of the spans delimiting the stages of the decoding pipeline
+pub const TARGET: &str = "profile";
+
+/// The stages of the decoding pipeline, in the order they are performed
+const STAGES: [&str; 6] =
+    ["framing", "crc", "parse", "cpr", "serialization", "sink"];
+
+/// Number of buckets, each bucket covering a power of two nanoseconds
+const BUCKETS: usize = 40;
+
+/**
+ * A histogram of durations, with logarithmic buckets.
+ *
+ * Bucket `i` counts durations between 2^(i-1) and 2^i nanoseconds, which
+ * is precise enough to compare stages spanning several orders of magnitude.
+ */
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    total: u64,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            buckets: [0; BUCKETS],
+            count: 0,
+            total: 0,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Record a duration, in nanoseconds
+    pub fn record(&mut self, nanos: u64) {
+        let idx = (u64::BITS - nanos.leading_zeros()) as usize;
+        self.buckets[idx.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.total += nanos;
+        self.max = self.max.max(nanos);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> u64 {
+        self.total.checked_div(self.count).unwrap_or(0)
+    }
+
+    /// An upper bound of the given quantile (between 0 and 1), in nanoseconds
+    pub fn quantile(&self, q: f64) -> u64 {
+        let rank = (q * self.count as f64).ceil() as u64;
+        let mut cumulated = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            cumulated += count;
+            if cumulated >= rank.max(1) {
+                return (1u64 << i).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+/// Start time of a span, stored in the span extensions
+struct Start(Instant);
+
+/**
+ * A tracing layer accumulating the duration of the pipeline stages.
+ *
+ * The duration of a stage is the time between the creation and the closing
+ * of a span with the [`TARGET`] target, aggregated by span name. Spans of
+ * asynchronous stages (e.g. writing to a sink) include the time spent
+ * waiting.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    stages: Arc<Mutex<HashMap<&'static str, Histogram>>>,
+}
+
+impl Profiler {
+    /// Only the spans delimiting the pipeline stages are of interest
+    pub fn filter<S>() -> impl Filter<S> {
+        Targets::new().with_target(TARGET, tracing::Level::TRACE)
+    }
+
+    pub fn histograms(&self) -> HashMap<&'static str, Histogram> {
+        self.stages.lock().unwrap().clone()
+    }
+
+    /// A summary of the time spent in each stage, with histograms
+    pub fn summary(&self) -> String {
+        let stages = self.histograms();
+        let mut names: Vec<_> = STAGES
+            .iter()
+            .filter(|name| stages.contains_key(*name))
+            .copied()
+            .collect();
+        let mut others: Vec<_> = stages
+            .keys()
+            .filter(|name| !STAGES.contains(name))
+            .copied()
+            .collect();
+        others.sort();
+        names.extend(others);
+
+        let mut s = String::new();
+        let _ = writeln!(
+            s,
+            "{:<14}{:>10}{:>11}{:>11}{:>11}{:>11}{:>11}",
+            "stage", "count", "mean", "p50", "p99", "max", "total"
+        );
+        for name in &names {
+            let h = &stages[name];
+            let _ = writeln!(
+                s,
+                "{:<14}{:>10}{:>11}{:>11}{:>11}{:>11}{:>11}",
+                name,
+                h.count,
+                format_nanos(h.mean()),
+                format_nanos(h.quantile(0.5)),
+                format_nanos(h.quantile(0.99)),
+                format_nanos(h.max),
+                format_nanos(h.total),
+            );
+        }
+        for name in &names {
+            let h = &stages[name];
+            let _ = writeln!(s, "\n{}", name);
+            let peak = h.buckets.iter().max().copied().unwrap_or(0).max(1);
+            let first = h.buckets.iter().position(|&c| c > 0).unwrap_or(0);
+            let last = h.buckets.iter().rposition(|&c| c > 0).unwrap_or(0);
+            for (i, &count) in
+                h.buckets.iter().enumerate().take(last + 1).skip(first)
+            {
+                let width = (40 * count).div_ceil(peak) as usize;
+                let _ = writeln!(
+                    s,
+                    "  < {:>9} {:<40} {}",
+                    format_nanos(1 << i),
+                    "█".repeat(width),
+                    count
+                );
+            }
+        }
+        s
+    }
+}
+
+impl<S> Layer<S> for Profiler
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        _attrs: &Attributes<'_>,
+        id: &Id,
+        ctx: Context<'_, S>,
+    ) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Start(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(start) = span.extensions().get::<Start>().map(|s| s.0) else {
+            return;
+        };
+        let nanos = start.elapsed().as_nanos() as u64;
+        self.stages
+            .lock()
+            .unwrap()
+            .entry(span.name())
+            .or_default()
+            .record(nanos);
+    }
+}
+
+fn format_nanos(nanos: u64) -> String {
+    match nanos {
+        0..1_000 => format!("{} ns", nanos),
+        1_000..1_000_000 => format!("{:.1} µs", nanos as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.1} ms", nanos as f64 / 1e6),
+        _ => format!("{:.1} s", nanos as f64 / 1e9),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::trace_span;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut h = Histogram::default();
+        for nanos in [0, 100, 150, 200, 3_000, 1_000_000] {
+            h.record(nanos);
+        }
+        assert_eq!(h.count, 6);
+        assert_eq!(h.max, 1_000_000);
+        assert_eq!(h.mean(), 1_003_450 / 6);
+        // 150 and 200 fall in the same bucket, up to 256 ns
+        assert_eq!(h.quantile(0.5), 256);
+        assert_eq!(h.quantile(1.), 1_000_000);
+    }
+
+    #[test]
+    fn test_profiler() {
+        let profiler = Profiler::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(profiler.clone().with_filter(Profiler::filter()));
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..3 {
+                trace_span!(target: TARGET, "crc").in_scope(|| ());
+            }
+            trace_span!(target: TARGET, "custom").in_scope(|| ());
+            // Other spans are ignored
+            trace_span!("crc").in_scope(|| ());
+        });
+        let histograms = profiler.histograms();
+        assert_eq!(histograms["crc"].count, 3);
+        assert_eq!(histograms["custom"].count, 1);
+
+        let summary = profiler.summary();
+        let crc = summary.find("\ncrc").unwrap();
+        let custom = summary.find("\ncustom").unwrap();
+        assert!(crc < custom);
+    }
+}
@@ -0,0 +1,146 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/**
+ * A path to a field of a JSON message, following RFC 6901: e.g. `/icao24`,
+ * or `/metadata/0/rssi` for a field in a list.
+ */
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Pointer(Vec<String>);
+
+impl FromStr for Pointer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(path) = s.strip_prefix('/') else {
+            return Err(format!("Invalid JSON pointer: {s} (missing /)"));
+        };
+        let tokens = path
+            .split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect();
+        Ok(Pointer(tokens))
+    }
+}
+
+impl TryFrom<String> for Pointer {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/**
+ * Only keep some fields of a JSON value, with the same structure.
+ *
+ * Missing fields are ignored; elements of lists keep their index, with
+ * `null` in place of the elements not selected.
+ */
+pub fn project(value: &Value, fields: &[Pointer]) -> Value {
+    let mut projected = Value::Object(Map::new());
+    for Pointer(tokens) in fields {
+        if resolve(value, tokens).is_some() {
+            copy(value, &mut projected, tokens);
+        }
+    }
+    projected
+}
+
+fn resolve<'a>(value: &'a Value, tokens: &[String]) -> Option<&'a Value> {
+    tokens.iter().try_fold(value, |value, token| match value {
+        Value::Object(map) => map.get(token),
+        Value::Array(items) => items.get(token.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn copy(source: &Value, target: &mut Value, tokens: &[String]) {
+    let Some((token, rest)) = tokens.split_first() else {
+        *target = source.clone();
+        return;
+    };
+    match source {
+        Value::Object(map) => {
+            let Some(child) = map.get(token) else {
+                return;
+            };
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            if let Value::Object(projected) = target {
+                let slot = projected.entry(token).or_insert(Value::Null);
+                copy(child, slot, rest);
+            }
+        }
+        Value::Array(items) => {
+            let Some((idx, child)) = token
+                .parse::<usize>()
+                .ok()
+                .and_then(|idx| Some((idx, items.get(idx)?)))
+            else {
+                return;
+            };
+            if !target.is_array() {
+                *target = Value::Array(vec![]);
+            }
+            if let Value::Array(projected) = target {
+                if projected.len() <= idx {
+                    projected.resize(idx + 1, Value::Null);
+                }
+                copy(child, &mut projected[idx], rest);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pointers(fields: &[&str]) -> Vec<Pointer> {
+        fields.iter().map(|field| field.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_project() {
+        let message = json!({
+            "timestamp": 1712345678.5,
+            "icao24": "39b415",
+            "df": "17",
+            "latitude": 43.6,
+            "longitude": 1.37,
+            "metadata": [
+                {"serial": 1, "rssi": -20.5},
+                {"serial": 2, "rssi": -30.1}
+            ],
+            "a/b": 1
+        });
+        let fields = pointers(&[
+            "/icao24",
+            "/latitude",
+            "/altitude",
+            "/metadata/1/rssi",
+            "/a~1b",
+        ]);
+        assert_eq!(
+            project(&message, &fields),
+            json!({
+                "icao24": "39b415",
+                "latitude": 43.6,
+                "metadata": [null, {"rssi": -30.1}],
+                "a/b": 1
+            })
+        );
+        let fields = pointers(&["/metadata/0", "/metadata/x", "/df/0"]);
+        assert_eq!(
+            project(&message, &fields),
+            json!({"metadata": [{"serial": 1, "rssi": -20.5}]})
+        );
+        assert!("icao24".parse::<Pointer>().is_err());
+    }
+}
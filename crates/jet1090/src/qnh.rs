@@ -0,0 +1,194 @@
+use rs1090::geom::qnh_altitude;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Only aircraft below this altitude (in ft) are expected to set the QNH
+const MAX_ALTITUDE: u16 = 4000;
+/// Barometric settings older than this (in s) are ignored
+const MAX_AGE: u64 = 1800;
+/// The number of aircraft needed to agree on a QNH
+const MIN_AIRCRAFT: usize = 2;
+/// Plausible values of the QNH, in hPa
+const PLAUSIBLE: RangeInclusive<f64> = 900.0..=1070.0;
+
+/**
+ * The local QNH used to correct barometric altitudes.
+ *
+ * With `auto`, the QNH is the median of the barometric settings (BDS 4,0)
+ * selected by aircraft flying at low altitude around the receivers, i.e.
+ * below the transition altitude.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "QnhValue")]
+pub enum Qnh {
+    Auto,
+    Fixed(f64),
+}
+
+/// The QNH may be configured as a number or as a string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QnhValue {
+    Number(f64),
+    Text(String),
+}
+
+impl FromStr for Qnh {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Qnh::Auto),
+            _ => s
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid QNH: {s}"))
+                .and_then(Qnh::fixed),
+        }
+    }
+}
+
+impl TryFrom<QnhValue> for Qnh {
+    type Error = String;
+
+    fn try_from(value: QnhValue) -> Result<Self, Self::Error> {
+        match value {
+            QnhValue::Number(qnh) => Qnh::fixed(qnh),
+            QnhValue::Text(s) => s.parse(),
+        }
+    }
+}
+
+impl Qnh {
+    fn fixed(qnh: f64) -> Result<Self, String> {
+        if PLAUSIBLE.contains(&qnh) {
+            Ok(Qnh::Fixed(qnh))
+        } else {
+            Err(format!("Implausible QNH: {qnh} hPa"))
+        }
+    }
+}
+
+/// Where the QNH used for a corrected altitude comes from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QnhSource {
+    /// Set in the configuration
+    Fixed,
+    /// Agreed on by aircraft selecting it (BDS 4,0)
+    Bds40,
+}
+
+/// The current QNH, and the barometric settings it is derived from
+#[derive(Debug, Default)]
+pub struct Altimeter {
+    qnh: Option<Qnh>,
+    /// The last barometric setting of each aircraft, with its timestamp
+    settings: HashMap<String, (u64, f64)>,
+}
+
+impl Altimeter {
+    pub fn new(qnh: Option<Qnh>) -> Self {
+        Altimeter {
+            qnh,
+            settings: HashMap::new(),
+        }
+    }
+
+    /// Record the barometric setting selected by an aircraft
+    pub fn record(
+        &mut self,
+        icao24: &str,
+        timestamp: u64,
+        altitude: Option<u16>,
+        setting: f64,
+    ) {
+        if self.qnh != Some(Qnh::Auto)
+            || altitude.is_none_or(|alt| alt >= MAX_ALTITUDE)
+            || !PLAUSIBLE.contains(&setting)
+        {
+            return;
+        }
+        self.settings
+            .retain(|_, (ts, _)| *ts + MAX_AGE >= timestamp);
+        self.settings
+            .insert(icao24.to_string(), (timestamp, setting));
+    }
+
+    /// The QNH (in hPa) at a given time, if any
+    pub fn current(&self, timestamp: u64) -> Option<(f64, QnhSource)> {
+        match self.qnh? {
+            Qnh::Fixed(qnh) => Some((qnh, QnhSource::Fixed)),
+            Qnh::Auto => {
+                let mut recent: Vec<f64> = self
+                    .settings
+                    .values()
+                    .filter(|(ts, _)| *ts + MAX_AGE >= timestamp)
+                    .map(|(_, setting)| *setting)
+                    .collect();
+                if recent.len() < MIN_AIRCRAFT {
+                    return None;
+                }
+                recent.sort_by(f64::total_cmp);
+                let mid = recent.len() / 2;
+                let median = match recent.len() % 2 {
+                    0 => (recent[mid - 1] + recent[mid]) / 2.,
+                    _ => recent[mid],
+                };
+                Some((median, QnhSource::Bds40))
+            }
+        }
+    }
+
+    /// The corrected altitude (in ft) and the QNH used, if any
+    pub fn correct(
+        &self,
+        timestamp: u64,
+        altitude: Option<u16>,
+    ) -> Option<(i32, f64, QnhSource)> {
+        let altitude = altitude?;
+        let (qnh, source) = self.current(timestamp)?;
+        let corrected = qnh_altitude(altitude as f64, qnh).round() as i32;
+        Some((corrected, qnh, source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Config {
+        qnh: Qnh,
+    }
+
+    #[test]
+    fn test_qnh() {
+        let config: Config = toml::from_str("qnh = 1020.5").unwrap();
+        assert_eq!(config.qnh, Qnh::Fixed(1020.5));
+        let config: Config = toml::from_str("qnh = \"auto\"").unwrap();
+        assert_eq!(config.qnh, Qnh::Auto);
+        assert!(toml::from_str::<Config>("qnh = 20").is_err());
+        assert_eq!("1003".parse(), Ok(Qnh::Fixed(1003.)));
+
+        let altimeter = Altimeter::new(Some(Qnh::Fixed(1023.25)));
+        assert_eq!(
+            altimeter.correct(0, Some(1000)),
+            Some((1270, 1023.25, QnhSource::Fixed))
+        );
+        assert_eq!(Altimeter::new(None).correct(0, Some(1000)), None);
+
+        let mut altimeter = Altimeter::new(Some(Qnh::Auto));
+        altimeter.record("39b415", 0, Some(2500), 1018.);
+        // Aircraft above the transition altitude use the standard setting
+        altimeter.record("3c6444", 10, Some(35000), 1013.2);
+        assert_eq!(altimeter.current(10), None);
+        altimeter.record("4ca7b4", 20, Some(1500), 1019.);
+        altimeter.record("406b90", 30, Some(3000), 1020.);
+        assert_eq!(altimeter.current(30), Some((1019., QnhSource::Bds40)));
+        // Outdated settings are ignored
+        altimeter.record("39b415", 2000, Some(2500), 1017.);
+        assert_eq!(altimeter.current(2000), None);
+    }
+}
@@ -0,0 +1,141 @@
+use clap::ValueEnum;
+use rs1090::decode::cpr::haversine;
+use rs1090::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// What to do with positions beyond the maximum range of a receiver
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum RangeAction {
+    /// Remove the position from the message
+    #[default]
+    Drop,
+    /// Keep the position, with `out_of_range: true` in the message
+    Flag,
+}
+
+/**
+ * The maximum range filter: a default range, possibly overridden for the
+ * sensors of some sources (identified by their serial number).
+ *
+ * Positions decoded farther than the range from the reference of the
+ * receiver are most likely CPR mis-decodes, polluting maps.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// Default maximum range (in km), if any
+    pub range: Option<f64>,
+    /// Per sensor overrides of the range
+    pub ranges: HashMap<u64, f64>,
+    pub action: RangeAction,
+}
+
+impl Settings {
+    fn range(&self, serial: u64) -> Option<f64> {
+        self.ranges.get(&serial).copied().or(self.range)
+    }
+
+    /**
+     * Check the position decoded in an ADS-B message, received by a sensor
+     * at a reference position, and drop or flag it if it is out of range.
+     *
+     * Returns true if the position is out of range.
+     */
+    pub fn apply(
+        &self,
+        adsb: &mut ADSB,
+        serial: u64,
+        reference: Option<Position>,
+    ) -> bool {
+        let (Some(range), Some(reference)) = (self.range(serial), reference)
+        else {
+            return false;
+        };
+        let (latitude, longitude) = match &mut adsb.message {
+            ME::BDS05(AirbornePosition {
+                latitude,
+                longitude,
+                ..
+            })
+            | ME::BDS06(SurfacePosition {
+                latitude,
+                longitude,
+                ..
+            }) => (latitude, longitude),
+            _ => return false,
+        };
+        let (Some(lat), Some(lon)) = (*latitude, *longitude) else {
+            return false;
+        };
+        let distance =
+            haversine(reference.latitude, reference.longitude, lat, lon);
+        if distance <= range {
+            return false;
+        }
+        match self.action {
+            RangeAction::Drop => {
+                *latitude = None;
+                *longitude = None;
+            }
+            RangeAction::Flag => adsb.out_of_range = true,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adsb(frame: &str, latitude: f64, longitude: f64) -> ADSB {
+        let bytes = hex::decode(frame).unwrap();
+        let message = Message::try_from(bytes.as_slice()).unwrap();
+        let ExtendedSquitterADSB(mut adsb) = message.df else {
+            panic!("not an ADS-B message");
+        };
+        if let ME::BDS05(position) = &mut adsb.message {
+            position.latitude = Some(latitude);
+            position.longitude = Some(longitude);
+        }
+        adsb
+    }
+
+    #[test]
+    fn test_range() {
+        let frame = "8d40621d58c382d690c8ac2863a7";
+        let reference = Some(Position {
+            latitude: 43.6,
+            longitude: 1.4,
+        });
+        let mut settings = Settings {
+            range: Some(400.),
+            ranges: HashMap::from([(2, 100.)]),
+            action: RangeAction::Drop,
+        };
+
+        // About 222 km away from the reference
+        let mut msg = adsb(frame, 45.6, 1.4);
+        assert!(!settings.apply(&mut msg, 1, reference));
+        assert!(!settings.apply(&mut msg, 1, None));
+        assert!(settings.apply(&mut msg, 2, reference));
+        let ME::BDS05(position) = &msg.message else {
+            unreachable!()
+        };
+        assert_eq!(position.latitude, None);
+        assert!(!msg.out_of_range);
+
+        settings.action = RangeAction::Flag;
+        let mut msg = adsb(frame, 52.3, 4.8);
+        assert!(settings.apply(&mut msg, 1, reference));
+        let ME::BDS05(position) = &msg.message else {
+            unreachable!()
+        };
+        assert_eq!(position.latitude, Some(52.3));
+        assert!(msg.out_of_range);
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["out_of_range"], true);
+    }
+}
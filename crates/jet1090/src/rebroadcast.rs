@@ -0,0 +1,142 @@
+use std::io;
+use std::net::SocketAddr;
+
+use rs1090::source::{avr, beast};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+use crate::event::Event;
+use crate::tasks;
+
+/// The format of the frames sent to the clients
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    /// Binary Beast frames, with timestamps and signal levels
+    Beast,
+    /// AVR text lines, `*<frame>;`
+    Avr,
+}
+
+impl Protocol {
+    fn name(&self) -> &'static str {
+        match self {
+            Protocol::Beast => "beast",
+            Protocol::Avr => "avr",
+        }
+    }
+
+    /// The bytes sent for an event, if any
+    fn encode(&self, event: &Event) -> Option<Vec<u8>> {
+        let Event::Message(msg, ..) = event else {
+            return None;
+        };
+        match self {
+            Protocol::Beast => beast::encode(msg),
+            Protocol::Avr => {
+                Some(format!("{}\n", avr::encode(&msg.frame)).into_bytes())
+            }
+        }
+    }
+}
+
+/**
+ * Re-broadcast the deduplicated frames to the TCP clients connected to a
+ * port (on 0.0.0.0), e.g. to feed other decoders or feeder clients.
+ *
+ * Each client receives the frames from the time it connects; slow clients
+ * miss frames rather than slowing down the others.
+ */
+pub async fn serve(
+    port: u16,
+    protocol: Protocol,
+    events: broadcast::Sender<Event>,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    info!("Serving {} frames on port {}", protocol.name(), port);
+    let task = format!("{} server", protocol.name());
+    tasks::spawn(&task, async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let rx = events.subscribe();
+                    tokio::spawn(client(stream, peer, protocol, rx));
+                }
+                Err(e) => warn!("Failed to accept a client: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn client(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    protocol: Protocol,
+    mut rx: broadcast::Receiver<Event>,
+) {
+    info!("{} client connected: {}", protocol.name(), peer);
+    loop {
+        match rx.recv().await {
+            Ok(event) => {
+                let Some(data) = protocol.encode(&event) else {
+                    continue;
+                };
+                if stream.write_all(&data).await.is_err() {
+                    break;
+                }
+            }
+            Err(RecvError::Lagged(count)) => {
+                warn!(
+                    "{} client {} missed {} frames",
+                    protocol.name(),
+                    peer,
+                    count
+                );
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+    info!("{} client disconnected: {}", protocol.name(), peer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rs1090::prelude::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_rebroadcast() {
+        let (tx, _) = broadcast::channel(16);
+        // Find a free port
+        let port = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        serve(port, Protocol::Avr, tx.clone()).await.unwrap();
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        // Wait for the client to subscribe
+        while tx.receiver_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let msg = TimedMessage::from_frame(
+            Timestamp::from_secs(1_700_000_000),
+            &[0x5d, 0x4d, 0x20, 0x23, 0x7a, 0x55, 0xa6],
+        );
+        tx.send(Event::Message(Arc::new(msg), None, None)).unwrap();
+
+        let mut line = [0u8; 17];
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            stream.read_exact(&mut line),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(&line, b"*5D4D20237A55A6;\n");
+    }
+}
@@ -0,0 +1,161 @@
+use rs1090::prelude::*;
+use serde::Deserialize;
+use std::str::FromStr;
+#[cfg(feature = "geoip")]
+use tracing::info;
+use tracing::warn;
+
+/// The service used to locate the public IP address of the computer (the
+/// address is sent to this third party, over HTTPS)
+#[cfg(feature = "geoip")]
+const GEOIP_URL: &str = "https://ipwho.is/?fields=success,latitude,longitude";
+
+/**
+ * The reference position of sources configured without any.
+ *
+ * With `auto`, the position reported by the receiver comes first: the GNSS
+ * position of SeRo Systems sensors, or the position frames of Radarcape
+ * receivers, which replace the fallbacks as soon as they are received. The
+ * airport configured for the source comes next, then a coarse geolocation
+ * of the public IP address by a third-party service, which receives this
+ * address (requires the `geoip` feature). This is enough
+ * to decode surface positions for a quick start, but precise coordinates
+ * should be set for any serious use.
+ */
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Reference {
+    Auto,
+    Position(Position),
+}
+
+impl FromStr for Reference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Reference::Auto),
+            _ => Position::from_str(s).map(Reference::Position),
+        }
+    }
+}
+
+impl TryFrom<String> for Reference {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Reference {
+    /// Whether positions reported by receivers replace the fallback
+    pub fn uses_receivers(&self) -> bool {
+        matches!(self, Reference::Auto)
+    }
+
+    /// The fallback position, only resolved when a source needs it
+    pub async fn resolve(&self) -> Option<Position> {
+        match self {
+            Reference::Position(position) => Some(*position),
+            Reference::Auto => geolocate().await,
+        }
+    }
+}
+
+#[cfg(feature = "geoip")]
+async fn geolocate() -> Option<Position> {
+    let response = reqwest::get(GEOIP_URL)
+        .await
+        .and_then(|response| response.error_for_status());
+    let geolocation = match response {
+        Ok(response) => response.json::<Geolocation>().await,
+        Err(e) => Err(e),
+    };
+    let position = match geolocation {
+        Ok(geolocation) => geolocation.position(),
+        Err(e) => {
+            warn!("IP geolocation failed: {}", e);
+            return None;
+        }
+    };
+    match position {
+        Some(pos) => info!(
+            "Reference position from IP geolocation: {:.2},{:.2}",
+            pos.latitude, pos.longitude
+        ),
+        None => warn!("IP geolocation failed: no position returned"),
+    }
+    position
+}
+
+#[cfg(not(feature = "geoip"))]
+async fn geolocate() -> Option<Position> {
+    warn!("Compile jet1090 with the geoip feature to locate the receiver from its IP address");
+    None
+}
+
+/// The response of the geolocation service
+#[cfg(feature = "geoip")]
+#[derive(Deserialize)]
+struct Geolocation {
+    success: bool,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+#[cfg(feature = "geoip")]
+impl Geolocation {
+    fn position(self) -> Option<Position> {
+        match self {
+            Geolocation {
+                success: true,
+                latitude: Some(latitude),
+                longitude: Some(longitude),
+            } => Some(Position {
+                latitude,
+                longitude,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference() {
+        assert_eq!(Reference::from_str("auto"), Ok(Reference::Auto));
+        let Ok(Reference::Position(pos)) = Reference::from_str("LFBO") else {
+            unreachable!()
+        };
+        assert_eq!(pos.latitude, 43.628101);
+        let Ok(Reference::Position(pos)) = Reference::from_str("43.6,1.4")
+        else {
+            unreachable!()
+        };
+        assert_eq!(pos.longitude, 1.4);
+
+        #[derive(Deserialize)]
+        struct Config {
+            reference: Reference,
+        }
+        let config: Config = toml::from_str(r#"reference = "auto""#).unwrap();
+        assert_eq!(config.reference, Reference::Auto);
+        assert!(config.reference.uses_receivers());
+    }
+
+    #[cfg(feature = "geoip")]
+    #[test]
+    fn test_geolocation() {
+        let parse = |body: &str| {
+            serde_json::from_str::<Geolocation>(body).ok()?.position()
+        };
+        let body = r#"{"success":true,"latitude":43.6046,"longitude":1.4451}"#;
+        let pos = parse(body).unwrap();
+        assert_eq!(pos.latitude, 43.6046);
+        assert!(parse(r#"{"success":false}"#).is_none());
+    }
+}
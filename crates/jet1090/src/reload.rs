@@ -0,0 +1,127 @@
+use rs1090::decode::ICAO;
+use std::path::PathBuf;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::filters::Filters;
+
+/**
+ * Read the filters again from the configuration file, without restarting
+ * the decoding.
+ *
+ * The whole configuration file is validated before the filters are swapped:
+ * the current filters are kept if it cannot be read. Filters set on the
+ * command line take precedence over the configuration file and are never
+ * reloaded.
+ */
+pub struct Reloader {
+    /// The configuration file, if any
+    pub config: Option<PathBuf>,
+    /// Downlink formats set on the command line
+    pub df_filter: Option<Vec<u16>>,
+    /// Aircraft addresses set on the command line
+    pub aircraft_filter: Option<Vec<ICAO>>,
+    filters: watch::Sender<Filters>,
+}
+
+impl Reloader {
+    pub fn new(
+        config: Option<PathBuf>,
+        df_filter: Option<Vec<u16>>,
+        aircraft_filter: Option<Vec<ICAO>>,
+    ) -> Self {
+        let (filters, _) = watch::channel(Filters::default());
+        Reloader {
+            config,
+            df_filter,
+            aircraft_filter,
+            filters,
+        }
+    }
+
+    /// The current filters, updated at each reload
+    pub fn subscribe(&self) -> watch::Receiver<Filters> {
+        self.filters.subscribe()
+    }
+
+    /// Replace the current filters
+    pub fn set(&self, filters: Filters) {
+        self.filters.send_replace(filters);
+    }
+
+    /// Read the configuration file again, and swap the filters if valid
+    pub fn reload(&self) -> Result<Filters, String> {
+        let options = match &self.config {
+            Some(path) => crate::read_config(path)?,
+            None => crate::Options::default(),
+        };
+        let filters = Filters::new(
+            self.df_filter.clone().or(options.df_filter),
+            self.aircraft_filter.clone().or(options.aircraft_filter),
+        );
+        self.set(filters.clone());
+        Ok(filters)
+    }
+
+    /// Reload the filters, and log the outcome
+    pub fn reload_and_log(&self) -> Result<Filters, String> {
+        let result = self.reload();
+        match &result {
+            Ok(filters) => info!("Filters reloaded: {:?}", filters),
+            Err(e) => warn!("Failed to reload the filters: {}", e),
+        }
+        result
+    }
+}
+
+/// Reload the filters each time the process receives a SIGHUP signal
+#[cfg(unix)]
+pub async fn on_sighup(reloader: std::sync::Arc<Reloader>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            warn!("Failed to listen to SIGHUP: {}", e);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        let _ = reloader.reload_and_log();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_reload() {
+        let path = std::env::temp_dir().join("jet1090_test_reload.toml");
+        let config = "interactive = false\nverbose = false\n\
+            prevent_sleep = false\nupdate_position = false\nsources = []\n";
+        fs::write(&path, format!("{config}df_filter = [17]\n")).unwrap();
+        let aircraft: Vec<ICAO> = vec!["39b415".parse().unwrap()];
+        let reloader =
+            Reloader::new(Some(path.clone()), None, Some(aircraft.clone()));
+        let filters = reloader.subscribe();
+
+        // Filters set on the command line take precedence
+        reloader.reload().unwrap();
+        assert_eq!(filters.borrow().df_filter, Some(vec!["17".to_string()]));
+        assert_eq!(filters.borrow().aircraft_filter, Some(aircraft.clone()));
+
+        // Invalid files are rejected, the current filters are kept
+        let invalid = format!("{config}history_expire = \"1h\"\n");
+        fs::write(&path, invalid).unwrap();
+        assert!(reloader.reload().is_err());
+        assert_eq!(filters.borrow().df_filter, Some(vec!["17".to_string()]));
+
+        fs::write(&path, format!("{config}df_filter = [17, 20]\n")).unwrap();
+        reloader.reload().unwrap();
+        assert_eq!(filters.borrow().df_filter.as_ref().unwrap().len(), 2);
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use rs1090::decode::time::Timestamp;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/**
+ * A reordering buffer, releasing items in timestamp order.
+ *
+ * Messages from several sources (or several sensors behind one source) do
+ * not arrive in timestamp order. Items are kept until the most recent
+ * timestamp is ahead of theirs by the maximum delay, so that late items from
+ * slower sources are released in order. When nothing arrives for that delay,
+ * the buffer is flushed. Items arriving later than the delay are released
+ * as soon as possible, and counted.
+ */
+#[derive(Debug)]
+pub struct Reorder<T> {
+    delay: Duration,
+    /// Items sorted by timestamp, then by order of arrival
+    buffer: BTreeMap<(Timestamp, u64), T>,
+    count: u64,
+    /// The most recent timestamp pushed
+    newest: Option<Timestamp>,
+    /// The most recent timestamp released
+    released: Option<Timestamp>,
+    last_push: Instant,
+    /// The number of items released out of order
+    pub late: u64,
+}
+
+impl<T> Reorder<T> {
+    pub fn new(delay: Duration) -> Self {
+        Reorder {
+            delay,
+            buffer: BTreeMap::new(),
+            count: 0,
+            newest: None,
+            released: None,
+            last_push: Instant::now(),
+            late: 0,
+        }
+    }
+
+    pub fn push(&mut self, timestamp: Timestamp, item: T) {
+        if self.released.is_some_and(|released| timestamp < released) {
+            self.late += 1;
+        }
+        self.newest = self.newest.max(Some(timestamp));
+        self.buffer.insert((timestamp, self.count), item);
+        self.count += 1;
+        self.last_push = Instant::now();
+    }
+
+    /// Release the oldest item if the buffer held it long enough
+    pub fn pop(&mut self) -> Option<T> {
+        let limit = self.newest?.0 - self.delay.as_nanos() as i64;
+        let entry = self.buffer.first_entry()?;
+        let (timestamp, _) = *entry.key();
+        if timestamp.0 > limit && self.last_push.elapsed() < self.delay {
+            return None;
+        }
+        self.released = self.released.max(Some(timestamp));
+        Some(entry.remove())
+    }
+
+    /// Release all the items in timestamp order, e.g. before stopping
+    pub fn drain(&mut self) -> impl Iterator<Item = T> {
+        std::mem::take(&mut self.buffer).into_values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reorder() {
+        let mut reorder = Reorder::new(Duration::from_secs(2));
+        for (secs, item) in [(10, "a"), (12, "c"), (11, "b"), (11, "b'")] {
+            reorder.push(Timestamp::from_secs(secs), item);
+        }
+        // Nothing is older than 2 s before the most recent timestamp
+        assert_eq!(reorder.pop(), Some("a"));
+        assert_eq!(reorder.pop(), None);
+
+        reorder.push(Timestamp::from_secs(14), "d");
+        assert_eq!(reorder.pop(), Some("b"));
+        assert_eq!(reorder.pop(), Some("b'"));
+        assert_eq!(reorder.pop(), Some("c"));
+        assert_eq!(reorder.pop(), None);
+
+        // Too late to be released in order
+        reorder.push(Timestamp::from_secs(9), "late");
+        assert_eq!(reorder.pop(), Some("late"));
+        assert_eq!(reorder.late, 1);
+        assert_eq!(reorder.drain().collect::<Vec<_>>(), vec!["d"]);
+
+        // Flushed when nothing arrives for the maximum delay
+        let mut reorder = Reorder::new(Duration::from_millis(10));
+        reorder.push(Timestamp::from_secs(10), "a");
+        assert_eq!(reorder.pop(), None);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(reorder.pop(), Some("a"));
+        assert_eq!(reorder.pop(), None);
+    }
+}
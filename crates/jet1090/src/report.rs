@@ -0,0 +1,423 @@
+use rs1090::decode::cpr::haversine;
+use rs1090::prelude::*;
+use serde::Serialize;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::graphs::{Bucket, BUCKET};
+use crate::locate::Estimate;
+use crate::Jet1090;
+
+/// Number of sectors (of 10 degrees) in the coverage of a sensor
+pub const SECTORS: usize = 36;
+/// Number of aircraft listed in the report
+const TOP_AIRCRAFT: usize = 20;
+/// Duration (in seconds) of the message rate history in the report
+const HISTORY: u64 = 24 * 3600;
+
+/**
+ * The coverage of a sensor: the maximum range (in km) of the airborne
+ * positions it received, in sectors of 10 degrees clockwise from the north.
+ */
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    ranges: [f64; SECTORS],
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Coverage {
+            ranges: [0.; SECTORS],
+        }
+    }
+}
+
+impl Coverage {
+    /// Record the airborne position of a message, if any
+    pub fn record(&mut self, msg: &TimedMessage, reference: &Position) {
+        let Some(Message {
+            df:
+                ExtendedSquitterADSB(ADSB {
+                    message:
+                        ME::BDS05(AirbornePosition {
+                            latitude: Some(latitude),
+                            longitude: Some(longitude),
+                            ..
+                        }),
+                    ..
+                }),
+            ..
+        }) = msg.message
+        else {
+            return;
+        };
+        let range = haversine(
+            reference.latitude,
+            reference.longitude,
+            latitude,
+            longitude,
+        );
+        let bearing = bearing(reference, latitude, longitude);
+        let sector = (bearing / (360. / SECTORS as f64)) as usize % SECTORS;
+        self.ranges[sector] = self.ranges[sector].max(range);
+    }
+
+    pub fn ranges(&self) -> &[f64] {
+        &self.ranges
+    }
+}
+
+/// The initial bearing (in degrees) from a reference to a position
+fn bearing(reference: &Position, latitude: f64, longitude: f64) -> f64 {
+    let (lat1, lat2) = (reference.latitude.to_radians(), latitude.to_radians());
+    let d_lon = (longitude - reference.longitude).to_radians();
+    let y = d_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.)
+}
+
+/// Information about a sensor, in the site report
+#[derive(Debug, Serialize)]
+pub struct SensorReport {
+    pub serial: u64,
+    pub name: Option<String>,
+    pub reference: Option<Position>,
+    pub altitude: Option<f64>,
+    pub estimate: Option<Estimate>,
+    pub online: bool,
+    pub aircraft_count: u64,
+    /// The maximum range (in km) in each sector of 10 degrees, clockwise
+    /// from the north
+    pub coverage: Vec<f64>,
+}
+
+/// Statistics of the deduplication of messages received by several sensors
+#[derive(Debug, Serialize)]
+pub struct Deduplication {
+    /// Number of messages received by all the sensors
+    pub received: u64,
+    /// Number of messages after deduplication
+    pub sent: u64,
+}
+
+/// The aircraft with the most messages, in the site report
+#[derive(Debug, Serialize)]
+pub struct TopAircraft {
+    pub icao24: String,
+    pub callsign: Option<String>,
+    pub registration: Option<String>,
+    pub typecode: Option<String>,
+    pub count: usize,
+}
+
+/**
+ * A self-contained report of the performance of the receivers, to share
+ * with the community: coverage, message rates over the last 24 hours,
+ * sensors, deduplication statistics and top aircraft.
+ */
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// The time of the report, in seconds since the epoch
+    pub generated: u64,
+    pub version: &'static str,
+    pub sensors: Vec<SensorReport>,
+    /// The reception statistics in buckets of 5 minutes
+    pub history: Vec<Bucket>,
+    pub deduplication: Deduplication,
+    pub top_aircraft: Vec<TopAircraft>,
+}
+
+impl Report {
+    pub fn new(app: &Jet1090, now: u64) -> Self {
+        let sensors = app
+            .sensors
+            .values()
+            .map(|sensor| SensorReport {
+                serial: sensor.serial,
+                name: sensor.name.clone(),
+                reference: sensor.reference,
+                altitude: sensor.altitude,
+                estimate: sensor.estimate,
+                online: sensor.online,
+                aircraft_count: sensor.aircraft_count,
+                coverage: sensor.coverage.ranges().to_vec(),
+            })
+            .collect();
+        let history = app
+            .graphs
+            .buckets()
+            .iter()
+            .filter(|bucket| bucket.timestamp + HISTORY > now)
+            .copied()
+            .collect();
+        let mut top_aircraft: Vec<TopAircraft> = app
+            .state_vectors
+            .values()
+            .map(|sv| TopAircraft {
+                icao24: sv.cur.icao24.clone(),
+                callsign: sv.cur.state.callsign.clone(),
+                registration: sv.cur.registration.clone(),
+                typecode: sv.cur.typecode.clone(),
+                count: sv.cur.count,
+            })
+            .collect();
+        top_aircraft.sort_by_key(|ac| std::cmp::Reverse(ac.count));
+        top_aircraft.truncate(TOP_AIRCRAFT);
+        Report {
+            generated: now,
+            version: env!("CARGO_PKG_VERSION"),
+            sensors,
+            history,
+            deduplication: Deduplication {
+                received: app.dedup.received(),
+                sent: app.dedup.sent(),
+            },
+            top_aircraft,
+        }
+    }
+
+    /**
+     * Write the report to a file, as a HTML page if the extension is
+     * `.html` or `.htm`, as JSON otherwise.
+     */
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("html" | "htm") => self.html(),
+            _ => serde_json::to_string(self)?,
+        };
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        fs::write(&partial, content)?;
+        fs::rename(&partial, path)
+    }
+
+    /// A self-contained HTML page, with the report embedded as JSON
+    pub fn html(&self) -> String {
+        let json = serde_json::to_string(self)
+            .unwrap_or_default()
+            .replace("</", "<\\/");
+        let generated =
+            chrono::DateTime::from_timestamp(self.generated as i64, 0)
+                .map(|dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_default();
+
+        let mut body = String::new();
+        let _ = write!(
+            body,
+            "<h1>jet1090 site report</h1><p>Generated on {generated} \
+            (jet1090 {})</p>",
+            self.version
+        );
+
+        body.push_str("<h2>Sensors</h2><div class=\"sensors\">");
+        for sensor in &self.sensors {
+            let name = sensor.name.as_deref().unwrap_or("(unnamed)");
+            let reference = sensor
+                .reference
+                .map(|p| format!("{:.3}, {:.3}", p.latitude, p.longitude))
+                .unwrap_or_else(|| "unknown".to_string());
+            let max_range = sensor.coverage.iter().copied().fold(0., f64::max);
+            let _ = write!(
+                body,
+                "<div class=\"sensor\"><h3>{}</h3>{}<table>\
+                <tr><th>Serial</th><td>{}</td></tr>\
+                <tr><th>Reference</th><td>{}</td></tr>\
+                <tr><th>Status</th><td>{}</td></tr>\
+                <tr><th>Aircraft</th><td>{}</td></tr>\
+                <tr><th>Maximum range</th><td>{:.0} km</td></tr>\
+                </table></div>",
+                escape(name),
+                polar(&sensor.coverage),
+                sensor.serial,
+                reference,
+                if sensor.online { "online" } else { "offline" },
+                sensor.aircraft_count,
+                max_range,
+            );
+        }
+        body.push_str("</div>");
+
+        let _ = write!(
+            body,
+            "<h2>Message rate (last 24 hours)</h2>{}",
+            rates(&self.history)
+        );
+
+        let dedup = &self.deduplication;
+        let ratio = match dedup.sent {
+            0 => 0.,
+            sent => dedup.received as f64 / sent as f64,
+        };
+        let _ = write!(
+            body,
+            "<h2>Deduplication</h2><table>\
+            <tr><th>Messages received</th><td>{}</td></tr>\
+            <tr><th>Messages after deduplication</th><td>{}</td></tr>\
+            <tr><th>Copies per message</th><td>{:.2}</td></tr></table>",
+            dedup.received, dedup.sent, ratio
+        );
+
+        body.push_str(
+            "<h2>Top aircraft</h2><table><tr><th>icao24</th>\
+            <th>Callsign</th><th>Registration</th><th>Typecode</th>\
+            <th>Messages</th></tr>",
+        );
+        for ac in &self.top_aircraft {
+            let field = |f: &Option<String>| escape(f.as_deref().unwrap_or(""));
+            let _ = write!(
+                body,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                <td>{}</td></tr>",
+                escape(&ac.icao24),
+                field(&ac.callsign),
+                field(&ac.registration),
+                field(&ac.typecode),
+                ac.count
+            );
+        }
+        body.push_str("</table>");
+
+        format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+            <title>jet1090 site report</title><style>{STYLE}</style></head>\
+            <body>{body}<script type=\"application/json\" id=\"report\">\
+            {json}</script></body></html>"
+        )
+    }
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+    table{border-collapse:collapse;margin:.5em 0}\
+    th,td{padding:.2em .8em;border-bottom:1px solid #ddd;text-align:left}\
+    .sensors{display:flex;flex-wrap:wrap;gap:2em}\
+    svg text{font-size:10px;fill:#666}";
+
+/// The polar plot of the coverage of a sensor, as SVG
+fn polar(ranges: &[f64]) -> String {
+    const SIZE: f64 = 300.;
+    const RADIUS: f64 = 130.;
+    let center = SIZE / 2.;
+    let max = ranges.iter().copied().fold(0., f64::max);
+    // Rings every 50, 100, 200 km, etc.
+    let step = [50., 100., 200., 500.]
+        .into_iter()
+        .find(|step| max / step <= 5.)
+        .unwrap_or(1000.);
+    let scale = RADIUS / (max / step).ceil().max(1.) / step;
+
+    let mut svg = format!(
+        "<svg width=\"{SIZE}\" height=\"{SIZE}\" \
+        xmlns=\"http://www.w3.org/2000/svg\">"
+    );
+    let mut ring = step;
+    while ring * scale <= RADIUS + 1e-6 {
+        let _ = write!(
+            svg,
+            "<circle cx=\"{center}\" cy=\"{center}\" r=\"{:.1}\" \
+            fill=\"none\" stroke=\"#ccc\"/>\
+            <text x=\"{center}\" y=\"{:.1}\">{ring} km</text>",
+            ring * scale,
+            center - ring * scale - 2.,
+        );
+        ring += step;
+    }
+    let points: Vec<String> = ranges
+        .iter()
+        .enumerate()
+        .map(|(i, range)| {
+            let angle = ((i as f64 + 0.5) * 360. / SECTORS as f64).to_radians();
+            let r = range * scale;
+            format!(
+                "{:.1},{:.1}",
+                center + r * angle.sin(),
+                center - r * angle.cos()
+            )
+        })
+        .collect();
+    let _ = write!(
+        svg,
+        "<polygon points=\"{}\" fill=\"#4a90d9\" fill-opacity=\"0.4\" \
+        stroke=\"#4a90d9\"/></svg>",
+        points.join(" ")
+    );
+    svg
+}
+
+/// The number of messages per second in each bucket, as SVG
+fn rates(history: &[Bucket]) -> String {
+    const WIDTH: f64 = 720.;
+    const HEIGHT: f64 = 150.;
+    let rate = |bucket: &Bucket| bucket.messages as f64 / BUCKET as f64;
+    let max = history.iter().map(rate).fold(0., f64::max);
+    let Some(first) = history.first() else {
+        return "<p>No statistics yet</p>".to_string();
+    };
+    let points: Vec<String> = history
+        .iter()
+        .map(|bucket| {
+            let x = (bucket.timestamp - first.timestamp) as f64
+                / HISTORY as f64
+                * WIDTH;
+            let y = HEIGHT - rate(bucket) / max.max(1.) * (HEIGHT - 15.);
+            format!("{x:.1},{y:.1}")
+        })
+        .collect();
+    format!(
+        "<svg width=\"{WIDTH}\" height=\"{HEIGHT}\" \
+        xmlns=\"http://www.w3.org/2000/svg\">\
+        <line x1=\"0\" y1=\"{HEIGHT}\" x2=\"{WIDTH}\" y2=\"{HEIGHT}\" \
+        stroke=\"#ccc\"/><text x=\"0\" y=\"10\">{max:.0} msg/s</text>\
+        <polyline points=\"{}\" fill=\"none\" stroke=\"#4a90d9\"/></svg>",
+        points.join(" ")
+    )
+}
+
+/// Escape special characters in HTML (or XML) text and attributes
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage() {
+        let reference = Position {
+            latitude: 43.6,
+            longitude: 1.4,
+        };
+        assert_eq!(bearing(&reference, 44.6, 1.4).round(), 0.);
+        assert_eq!(bearing(&reference, 43.6, 2.4).round(), 90.);
+        assert_eq!(bearing(&reference, 42.6, 1.4).round(), 180.);
+
+        let bytes = hex::decode("8d40621d58c382d690c8ac2863a7").unwrap();
+        let mut message = Message::try_from(bytes.as_slice()).unwrap();
+        if let ExtendedSquitterADSB(adsb) = &mut message.df {
+            if let ME::BDS05(position) = &mut adsb.message {
+                position.latitude = Some(43.6);
+                position.longitude = Some(2.4);
+            }
+        }
+        let msg = TimedMessage {
+            timestamp: Timestamp::from_secs_f64(0.),
+            frame: bytes,
+            message: Some(message),
+            metadata: vec![],
+            decode_time: None,
+        };
+        let mut coverage = Coverage::default();
+        coverage.record(&msg, &reference);
+        assert!((coverage.ranges()[8] - 80.5).abs() < 1.);
+        assert_eq!(coverage.ranges().iter().filter(|r| **r > 0.).count(), 1);
+
+        let svg = polar(coverage.ranges());
+        assert!(svg.contains("50 km") && svg.contains("<polygon"));
+        assert_eq!(escape("<a&b>"), "&lt;a&amp;b&gt;");
+    }
+}
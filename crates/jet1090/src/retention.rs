@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+use crate::phase::FlightPhase;
+use crate::snapshot::StateVectors;
+
+/// The activity class of an aircraft, to decide how long it is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityClass {
+    /// Aircraft in flight, the most interesting ones
+    Airborne,
+    /// Aircraft and vehicles on the ground
+    Ground,
+    /// Targets broadcast by ground stations (TIS-B)
+    TisB,
+    /// Targets only seen in all-call replies (DF11), with no other
+    /// information than their address
+    AllCall,
+}
+
+impl ActivityClass {
+    pub fn of(sv: &StateVectors) -> Self {
+        let cur = &sv.cur;
+        if sv.tisb {
+            ActivityClass::TisB
+        } else if matches!(
+            cur.phase,
+            Some(FlightPhase::Ground | FlightPhase::Taxi)
+        ) {
+            ActivityClass::Ground
+        } else if cur.state.altitude.is_none()
+            && cur.state.squawk.is_none()
+            && cur.state.callsign.is_none()
+            && cur.state.latitude.is_none()
+        {
+            ActivityClass::AllCall
+        } else {
+            ActivityClass::Airborne
+        }
+    }
+}
+
+/**
+ * How long aircraft and their history are kept (in minutes), by activity
+ * class.
+ *
+ * Classes with no retention fall back to the `history_expire` option. On
+ * busy airports, short retentions for ground traffic and all-call only
+ * targets keep the memory bounded while flights are kept longer.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Retention {
+    pub airborne: Option<u64>,
+    pub ground: Option<u64>,
+    pub tisb: Option<u64>,
+    pub all_call: Option<u64>,
+}
+
+impl Retention {
+    /// The retention (in minutes) of a class, if any
+    pub fn minutes(
+        &self,
+        class: ActivityClass,
+        default: Option<u64>,
+    ) -> Option<u64> {
+        let minutes = match class {
+            ActivityClass::Airborne => self.airborne,
+            ActivityClass::Ground => self.ground,
+            ActivityClass::TisB => self.tisb,
+            ActivityClass::AllCall => self.all_call,
+        };
+        minutes.or(default)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Retention::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention() {
+        let retention: Retention = toml::from_str(
+            r#"
+            airborne = 1440
+            ground = 60
+            all_call = 5
+            "#,
+        )
+        .unwrap();
+        assert!(!retention.is_empty());
+        assert_eq!(
+            retention.minutes(ActivityClass::Airborne, None),
+            Some(1440)
+        );
+        assert_eq!(
+            retention.minutes(ActivityClass::AllCall, Some(30)),
+            Some(5)
+        );
+        assert_eq!(retention.minutes(ActivityClass::TisB, Some(30)), Some(30));
+        assert_eq!(retention.minutes(ActivityClass::TisB, None), None);
+        assert!(Retention::default().is_empty());
+    }
+}
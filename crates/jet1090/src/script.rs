@@ -0,0 +1,291 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::sink::Kind;
+
+#[cfg(feature = "scripting")]
+pub use lua::{Script, BUDGET};
+
+/**
+ * What a script decided about a message.
+ *
+ * The fields are added to the JSON line of the message, in every sink
+ * writing it (also with integrity hashes, which only cover the timestamp
+ * and the frame); vetoed sinks do not receive the message at all.
+ */
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Outcome {
+    #[serde(default)]
+    pub fields: Map<String, Value>,
+    #[serde(default)]
+    pub veto: Vec<Kind>,
+}
+
+impl Outcome {
+    /// True if the sink (by its name) must not receive the message
+    pub fn vetoes(&self, sink: &str) -> bool {
+        self.veto.iter().any(|kind| {
+            kind.to_possible_value()
+                .is_some_and(|value| value.get_name() == sink)
+        })
+    }
+
+    /// Add the fields to the JSON line of a message
+    pub fn extend(&self, mut line: String) -> serde_json::Result<String> {
+        if self.fields.is_empty() || !line.ends_with('}') {
+            return Ok(line);
+        }
+        line.pop();
+        for (name, value) in &self.fields {
+            if !line.ends_with('{') {
+                line.push(',');
+            }
+            line.push_str(&serde_json::to_string(name)?);
+            line.push(':');
+            line.push_str(&serde_json::to_string(value)?);
+        }
+        line.push('}');
+        Ok(line)
+    }
+}
+
+#[cfg(feature = "scripting")]
+mod lua {
+    use std::path::Path;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use clap::ValueEnum;
+    use mlua::{Function, HookTriggers, Lua, LuaSerdeExt, Value, VmState};
+    use rs1090::prelude::*;
+    use tracing::warn;
+
+    use super::Outcome;
+    use crate::errors::ErrorReporter;
+    use crate::sink::Kind;
+
+    /// Default number of instructions a script may run for each message
+    pub const BUDGET: u64 = 1_000_000;
+    /// Number of instructions between two checks of the budget
+    const STEP: u32 = 1000;
+    /// Memory available to a script, beyond which calls fail
+    const MEMORY: usize = 64 * 1024 * 1024;
+
+    /**
+     * A Lua script run on each decoded message, before it is sent to sinks.
+     *
+     * The script defines a global `on_message(msg)` function, called with
+     * the message as it is serialized (same field names and units) and
+     * returning:
+     *
+     * - `nil` (or `true`) to leave the message unchanged;
+     * - `false` to keep the message from all sinks;
+     * - a table `{fields = {...}, veto = {"redis", ...}}` to add fields to
+     *   the message, and keep it from some sinks.
+     *
+     * Globals persist between calls, e.g. for counters. A call running more
+     * than its budget of instructions (or using too much memory) fails: the
+     * message is then sent unchanged, and the failure is counted as a script
+     * error.
+     */
+    pub struct Script {
+        lua: Lua,
+        on_message: Function,
+        /// Instructions run by the current call (by steps of [`STEP`])
+        used: Arc<AtomicU64>,
+        errors: ErrorReporter,
+    }
+
+    impl Script {
+        pub fn load(
+            path: &Path,
+            budget: u64,
+            errors: ErrorReporter,
+        ) -> Result<Self, String> {
+            let source = std::fs::read_to_string(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let lua = Lua::new();
+            lua.set_memory_limit(MEMORY).map_err(|e| e.to_string())?;
+            lua.load(source)
+                .set_name(path.display().to_string())
+                .exec()
+                .map_err(|e| e.to_string())?;
+            let on_message = lua.globals().get("on_message").map_err(|_| {
+                format!("{}: no on_message function", path.display())
+            })?;
+            let used = Arc::new(AtomicU64::new(0));
+            let counter = used.clone();
+            // Scripts catching errors with pcall may go on for a few more
+            // steps, but not much further
+            lua.set_hook(
+                HookTriggers::new().every_nth_instruction(STEP),
+                move |_, _| {
+                    let used =
+                        counter.fetch_add(STEP.into(), Ordering::Relaxed);
+                    match used < budget {
+                        true => Ok(VmState::Continue),
+                        false => Err(mlua::Error::runtime(
+                            "instruction budget exceeded",
+                        )),
+                    }
+                },
+            );
+            Ok(Script {
+                lua,
+                on_message,
+                used,
+                errors,
+            })
+        }
+
+        /// Run the script on a message, errors are logged periodically
+        pub fn process(&mut self, msg: &TimedMessage) -> Option<Arc<Outcome>> {
+            match self.call(msg) {
+                Ok(outcome) => outcome.map(Arc::new),
+                Err(e) => {
+                    warn_once(&e);
+                    self.errors.record("script errors", msg);
+                    None
+                }
+            }
+        }
+
+        fn call(&self, msg: &TimedMessage) -> mlua::Result<Option<Outcome>> {
+            let json = serde_json::to_value(msg)
+                .map_err(|e| mlua::Error::SerializeError(e.to_string()))?;
+            let arg = self.lua.to_value(&json)?;
+            self.used.store(0, Ordering::Relaxed);
+            let outcome = match self.on_message.call::<Value>(arg)? {
+                Value::Nil | Value::Boolean(true) => return Ok(None),
+                // No sink gets the message
+                Value::Boolean(false) => Outcome {
+                    veto: Kind::value_variants().to_vec(),
+                    ..Outcome::default()
+                },
+                value => self.lua.from_value::<Outcome>(value)?,
+            };
+            // Decoded fields are not replaced
+            let Outcome { mut fields, veto } = outcome;
+            fields.retain(|name, _| json.get(name).is_none());
+            Ok(Some(Outcome { fields, veto }))
+        }
+    }
+
+    /// Log the first error in full: the others are only counted
+    fn warn_once(error: &mlua::Error) {
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        WARNED.call_once(|| warn!("Error in the script: {}", error));
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::errors::ErrorStats;
+
+        fn script(
+            name: &str,
+            source: &str,
+            budget: u64,
+        ) -> (Script, ErrorStats) {
+            let path = std::env::temp_dir().join(name);
+            std::fs::write(&path, source).unwrap();
+            let stats = ErrorStats::default();
+            let errors = ErrorReporter::new(stats.clone());
+            let script = Script::load(&path, budget, errors).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            (script, stats)
+        }
+
+        #[test]
+        fn test_script() {
+            let msg = TimedMessage::from_frame(
+                Timestamp::from_secs(1_700_000_000),
+                &hex::decode("8d406b902015a678d4d220aa4bda").unwrap(),
+            );
+            let (mut script, stats) = script(
+                "jet1090_test_script.lua",
+                r#"
+                count = 0
+                function on_message(msg)
+                    count = count + 1
+                    if msg.callsign == "EZY85MH" then
+                        return {
+                            fields = {route = "north", count = count, df = 0},
+                            veto = {"redis"},
+                        }
+                    end
+                end
+                "#,
+                BUDGET,
+            );
+            let outcome = script.process(&msg).unwrap();
+            assert_eq!(outcome.fields["route"], "north");
+            assert_eq!(outcome.fields["count"], 1);
+            // Decoded fields are not replaced
+            assert!(!outcome.fields.contains_key("df"));
+            assert_eq!(outcome.veto, vec![Kind::Redis]);
+            assert_eq!(script.process(&msg).unwrap().fields["count"], 2);
+
+            let msg = TimedMessage::from_frame(
+                Timestamp::from_secs(1_700_000_000),
+                &hex::decode("5d4d20237a55a6").unwrap(),
+            );
+            assert!(script.process(&msg).is_none());
+            assert!(stats.counts().is_empty());
+        }
+
+        #[test]
+        fn test_budget() {
+            let msg = TimedMessage::from_frame(
+                Timestamp::from_secs(1_700_000_000),
+                &hex::decode("5d4d20237a55a6").unwrap(),
+            );
+            let (mut script, stats) = script(
+                "jet1090_test_budget.lua",
+                r#"
+                function on_message(msg)
+                    if msg.df == "11" then
+                        while true do end
+                    end
+                    return false
+                end
+                "#,
+                10_000,
+            );
+            // The message is sent unchanged
+            assert!(script.process(&msg).is_none());
+            assert_eq!(stats.counts()[0].kind, "script errors");
+            // The budget is reset for each call
+            let msg = TimedMessage::from_frame(
+                Timestamp::from_secs(1_700_000_000),
+                &hex::decode("8d406b902015a678d4d220aa4bda").unwrap(),
+            );
+            let outcome = script.process(&msg).unwrap();
+            assert!(outcome.vetoes("stdout") && outcome.vetoes("redis"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome() {
+        let outcome: Outcome = serde_json::from_str(
+            r#"{"fields": {"route": "north", "rank": 2}, "veto": ["redis"]}"#,
+        )
+        .unwrap();
+        assert!(outcome.vetoes("redis"));
+        assert!(!outcome.vetoes("file"));
+        assert_eq!(
+            outcome.extend(r#"{"df":"17"}"#.to_string()).unwrap(),
+            r#"{"df":"17","rank":2,"route":"north"}"#
+        );
+        assert!(
+            serde_json::from_str::<Outcome>(r#"{"veto": ["tui"]}"#).is_err()
+        );
+    }
+}
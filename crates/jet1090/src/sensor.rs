@@ -1,12 +1,28 @@
 use rs1090::prelude::*;
+use rs1090::source::beast::{FramingStats, ReceiverReport};
+use rs1090::source::processing::SampleStats;
+use rs1090::source::sensor::SensorSettings;
+use rs1090::source::timestamps::TimestampStats;
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
 
 #[cfg(feature = "sero")]
 use rs1090::source::sero;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, warn};
 
+use crate::event::{Heartbeat, SensorStatus};
+use crate::locate::{Estimate, Locator};
+use crate::report::Coverage;
 use crate::source::{Address, Source};
 
+/// Number of seconds of activity kept for each sensor
+pub const HISTORY: usize = 60;
+/// Number of bins in the RSSI distribution, from -50 dBFS to 0 dBFS
+pub const RSSI_BINS: usize = 10;
+/// Width (in dBFS) of each bin in the RSSI distribution
+const RSSI_BIN_WIDTH: f32 = 5.;
+
 /**
  * A structure to describe information to label data produced by a sensor.
  */
@@ -24,6 +40,190 @@ pub struct Sensor {
     pub aircraft_count: u64,
     /// The timestamp for the last seen message
     pub last_timestamp: u64,
+    /// The clock timestamping the messages, as last reported by the sensor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_source: Option<TimeSource>,
+    /// Messages received over the last seconds (only for display)
+    #[serde(skip)]
+    pub activity: Activity,
+    /// Whether the sensor is currently sending messages
+    #[serde(skip)]
+    pub online: bool,
+    /// Messages received since the last heartbeat
+    #[serde(skip)]
+    pub interval_count: u64,
+    /// The position of the sensor, estimated from the received traffic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<Estimate>,
+    /// Airborne positions received by the sensor, for the estimate
+    #[serde(skip)]
+    pub locator: Locator,
+    /// The maximum range of airborne positions, for the site report
+    #[serde(skip)]
+    pub coverage: Coverage,
+    /// Resynchronizations and discarded bytes in the Beast stream
+    #[serde(default, skip_deserializing)]
+    pub framing: FramingStats,
+    /// Buffers of IQ samples read from SDR devices, and the dropped ones
+    #[serde(
+        default,
+        skip_deserializing,
+        skip_serializing_if = "SampleStats::is_empty"
+    )]
+    pub samples: SampleStats,
+    /// Positions farther than the maximum range, dropped or flagged
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub out_of_range: u64,
+    /// Timestamps of Beast frames going backwards or jumping
+    #[serde(
+        default,
+        skip_deserializing,
+        skip_serializing_if = "TimestampStats::is_empty"
+    )]
+    pub timestamps: TimestampStats,
+    /// The status and position reported by the receiver (Radarcape)
+    #[serde(
+        default,
+        skip_deserializing,
+        skip_serializing_if = "ReceiverReport::is_empty"
+    )]
+    pub receiver: ReceiverReport,
+}
+
+impl Sensor {
+    /// The status of the sensor, as sent in events
+    pub fn status(&self, now: u64) -> SensorStatus {
+        SensorStatus {
+            serial: self.serial,
+            name: self.name.clone(),
+            timestamp: now,
+        }
+    }
+
+    /// Override the alias and the position of the sensor with its settings
+    pub fn configure(&mut self, settings: &SensorSettings) {
+        if settings.name.is_some() {
+            self.name = settings.name.clone();
+        }
+        if settings.reference.is_some() {
+            self.reference = settings.reference;
+        }
+        if settings.altitude.is_some() {
+            self.altitude = settings.altitude;
+        }
+    }
+
+    /// The heartbeat of the sensor, then start a new interval
+    pub fn heartbeat(&mut self, now: u64) -> Heartbeat {
+        let heartbeat = Heartbeat {
+            serial: self.serial,
+            name: self.name.clone(),
+            timestamp: now,
+            last_message_age: self
+                .activity
+                .last()
+                .map(|last| now.saturating_sub(last)),
+            messages_in_interval: self.interval_count,
+        };
+        self.interval_count = 0;
+        heartbeat
+    }
+}
+
+/// Messages received by a sensor during one second
+#[derive(Debug, Clone, Default)]
+struct Second {
+    count: u64,
+    rssi: [u64; RSSI_BINS],
+}
+
+/**
+ * Live activity of a sensor: number of messages and RSSI distribution over
+ * the last [`HISTORY`] seconds.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Activity {
+    /// One entry per second, the last one being in progress
+    seconds: VecDeque<Second>,
+    /// The timestamp (in s) of the last entry
+    current: u64,
+}
+
+impl Activity {
+    /// Record a message received at `now` (in s)
+    pub fn record(&mut self, now: u64, rssi: Option<f32>) {
+        self.advance(now);
+        if let Some(second) = self.seconds.back_mut() {
+            second.count += 1;
+            if let Some(rssi) = rssi {
+                let bin = ((rssi + RSSI_BINS as f32 * RSSI_BIN_WIDTH)
+                    / RSSI_BIN_WIDTH)
+                    .clamp(0., (RSSI_BINS - 1) as f32);
+                second.rssi[bin as usize] += 1;
+            }
+        }
+    }
+
+    fn advance(&mut self, now: u64) {
+        if self.seconds.is_empty() || now > self.current {
+            let gap = match self.seconds.is_empty() {
+                true => 1,
+                false => (now - self.current).min(HISTORY as u64),
+            };
+            for _ in 0..gap {
+                self.seconds.push_back(Second::default());
+            }
+            while self.seconds.len() > HISTORY {
+                self.seconds.pop_front();
+            }
+            self.current = now;
+        }
+    }
+
+    /// The timestamp (in s) of the last recorded message
+    pub fn last(&self) -> Option<u64> {
+        (!self.seconds.is_empty()).then_some(self.current)
+    }
+
+    /// The entries of the last [`HISTORY`] seconds before `now` (included)
+    fn window(&self, now: u64) -> impl Iterator<Item = Option<&Second>> {
+        (0..HISTORY as u64).rev().map(move |ago| {
+            let timestamp = now.checked_sub(ago)?;
+            let offset = self.current.checked_sub(timestamp)? as usize;
+            let len = self.seconds.len();
+            self.seconds.get(len.checked_sub(offset + 1)?)
+        })
+    }
+
+    /// Number of messages received in each of the last seconds, oldest first
+    pub fn counts(&self, now: u64) -> Vec<u64> {
+        self.window(now)
+            .map(|second| second.map_or(0, |s| s.count))
+            .collect()
+    }
+
+    /// Average number of messages per second over the last complete seconds
+    pub fn rate(&self, now: u64, seconds: usize) -> f64 {
+        let counts = self.counts(now);
+        let complete = &counts[..HISTORY - 1];
+        let last = &complete[complete.len() - seconds.min(complete.len())..];
+        last.iter().sum::<u64>() as f64 / last.len().max(1) as f64
+    }
+
+    /// Distribution of the RSSI of the messages over the last seconds
+    pub fn rssi(&self, now: u64) -> [u64; RSSI_BINS] {
+        let mut total = [0; RSSI_BINS];
+        for second in self.window(now).flatten() {
+            for (t, r) in total.iter_mut().zip(second.rssi) {
+                *t += r;
+            }
+        }
+        total
+    }
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
 }
 
 /**
@@ -38,10 +238,22 @@ pub async fn sensors(value: &Source) -> Vec<Sensor> {
             vec![Sensor {
                 serial: value.serial(),
                 name: value.name.clone(),
-                reference: value.reference,
+                reference: value.position(),
                 altitude: value.altitude,
                 aircraft_count: 0,
+                out_of_range: 0,
                 last_timestamp: 0,
+                time_source: None,
+                activity: Activity::default(),
+                online: false,
+                interval_count: 0,
+                estimate: None,
+                locator: Locator::default(),
+                coverage: Coverage::default(),
+                framing: FramingStats::default(),
+                samples: SampleStats::default(),
+                timestamps: TimestampStats::default(),
+                receiver: ReceiverReport::default(),
             }]
         }
         Address::Sero(params) => {
@@ -68,7 +280,19 @@ pub async fn sensors(value: &Source) -> Vec<Sensor> {
                             .map(|pos| pos.height),
                         name: Some(elt.alias.to_string()),
                         aircraft_count: 0,
+                        out_of_range: 0,
                         last_timestamp: 0,
+                        time_source: Some(TimeSource::Gnss),
+                        activity: Activity::default(),
+                        online: false,
+                        interval_count: 0,
+                        estimate: None,
+                        locator: Locator::default(),
+                        coverage: Coverage::default(),
+                        framing: FramingStats::default(),
+                        samples: SampleStats::default(),
+                        timestamps: TimestampStats::default(),
+                        receiver: ReceiverReport::default(),
                     })
                     .collect()
             }
@@ -78,5 +302,64 @@ pub async fn sensors(value: &Source) -> Vec<Sensor> {
                 vec![]
             }
         }
+        Address::Jet1090(address) => match remote(address).await {
+            Ok(sensors) => sensors,
+            Err(e) => {
+                warn!(
+                    "Failed to get the sensors of jet1090 {}: {}",
+                    address, e
+                );
+                vec![]
+            }
+        },
+    }
+}
+
+/// The sensors of another instance of jet1090, from its REST API
+async fn remote(address: &str) -> Result<Vec<Sensor>, Box<dyn Error>> {
+    let url = format!("http://{}/sensors", address.trim_end_matches('/'));
+    let text = reqwest::get(url).await?.error_for_status()?.text().await?;
+    let sensors: BTreeMap<String, Sensor> = serde_json::from_str(&text)?;
+    Ok(sensors
+        .into_values()
+        .map(|sensor| Sensor {
+            aircraft_count: 0,
+            last_timestamp: 0,
+            ..sensor
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activity() {
+        let mut activity = Activity::default();
+        for _ in 0..3 {
+            activity.record(100, Some(-3.));
+        }
+        activity.record(101, Some(-42.));
+        activity.record(101, None);
+        // nothing received for a while
+        activity.record(110, Some(-80.));
+
+        let counts = activity.counts(111);
+        assert_eq!(counts.len(), HISTORY);
+        assert_eq!(
+            counts[HISTORY - 12..],
+            [3, 2, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]
+        );
+        assert_eq!(activity.rate(111, 10), 0.3);
+
+        let rssi = activity.rssi(111);
+        assert_eq!(rssi, [1, 1, 0, 0, 0, 0, 0, 0, 0, 3]);
+
+        // Old messages are forgotten
+        assert_eq!(activity.counts(200), vec![0; HISTORY]);
+        activity.record(200, None);
+        assert_eq!(activity.counts(200)[HISTORY - 1], 1);
+        assert_eq!(activity.rssi(200), [0; RSSI_BINS]);
     }
 }
@@ -0,0 +1,759 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use rs1090::decode::AddressProvenance;
+use rs1090::integrity::HashChain;
+use rs1090::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{mpsc, watch};
+use tokio::time::{timeout, Instant};
+use tracing::{debug, error, info, trace_span, warn, Instrument};
+
+use crate::archive;
+use crate::event::{Event, Publisher};
+use crate::filters::Filters;
+use crate::latency;
+use crate::profile;
+use crate::projection::{self, Pointer};
+use crate::reorder::Reorder;
+use crate::script::Outcome;
+use crate::tasks;
+
+pub type SinkError = Box<dyn Error + Send + Sync>;
+
+/**
+ * A consumer of the events of the decoding pipeline.
+ *
+ * Each sink runs in its own task, see [`spawn`]: a slow sink subscribed to
+ * the broadcast channel does not slow down the decoding, but may miss events
+ * if it lags too far behind; a slow lossless sink slows down the decoding
+ * instead, see [`Publisher`].
+ */
+pub trait Sink: Send + 'static {
+    /// The name of the sink, for logging purposes
+    fn name(&self) -> &'static str;
+
+    /// Handle one event; errors are logged and the sink keeps running
+    fn handle(
+        &mut self,
+        event: &Event,
+    ) -> impl Future<Output = Result<(), SinkError>> + Send;
+}
+
+/// The events received by a sink, see [`Publisher`]
+pub enum Receiver {
+    /// From the broadcast channel, missing events when lagging behind
+    Broadcast(broadcast::Receiver<Event>),
+    /// From a channel of its own, without missing any event
+    Lossless(mpsc::Receiver<Event>),
+}
+
+impl From<broadcast::Receiver<Event>> for Receiver {
+    fn from(rx: broadcast::Receiver<Event>) -> Self {
+        Receiver::Broadcast(rx)
+    }
+}
+
+impl From<mpsc::Receiver<Event>> for Receiver {
+    fn from(rx: mpsc::Receiver<Event>) -> Self {
+        Receiver::Lossless(rx)
+    }
+}
+
+impl Receiver {
+    /// The next event for the sink, or None when the channel is closed
+    async fn recv(&mut self, sink: &str) -> Option<Event> {
+        match self {
+            Receiver::Broadcast(rx) => loop {
+                match rx.recv().await {
+                    Ok(event) => return Some(event),
+                    Err(RecvError::Lagged(count)) => {
+                        warn!("{} sink missed {} events", sink, count);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            },
+            Receiver::Lossless(rx) => rx.recv().await,
+        }
+    }
+}
+
+/// Start a task feeding the sink with events from the channel
+pub fn spawn(mut sink: impl Sink, rx: impl Into<Receiver>) {
+    let mut rx = rx.into();
+    let task = format!("{} sink", sink.name());
+    tasks::spawn(&task, async move {
+        while let Some(event) = rx.recv(sink.name()).await {
+            if event.is_vetoed(sink.name()) {
+                continue;
+            }
+            let result = sink
+                .handle(&event)
+                .instrument(trace_span!(target: profile::TARGET, "sink"))
+                .await;
+            match result {
+                Ok(()) => handled(sink.name(), &event),
+                Err(e) => error!("Error in {} sink: {}", sink.name(), e),
+            }
+        }
+    });
+}
+
+/// How long the sinks may take to handle the last events, at exit
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/**
+ * Wait for the sinks to receive the events already sent, e.g. at exit.
+ *
+ * A short grace period follows, for the handling of the last event to
+ * complete (and output files to be written).
+ */
+pub async fn drain(events: &Publisher) {
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+    while !events.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    tokio::time::sleep(REORDER_TICK).await;
+}
+
+/// How often the reordering buffer of ordered sinks is checked
+const REORDER_TICK: Duration = Duration::from_millis(100);
+
+/**
+ * Start a task feeding the sink with messages in timestamp order.
+ *
+ * Messages are held in a [`Reorder`] buffer for at most the given delay;
+ * other events are handled right away.
+ */
+pub fn spawn_ordered(
+    mut sink: impl Sink,
+    rx: impl Into<Receiver>,
+    delay: Duration,
+) {
+    let mut rx = rx.into();
+    let task = format!("{} sink", sink.name());
+    tasks::spawn(&task, async move {
+        let mut reorder = Reorder::new(delay);
+        let mut tick = tokio::time::interval(REORDER_TICK);
+        let mut warned = false;
+        loop {
+            let event = tokio::select! {
+                event = rx.recv(sink.name()) => match event {
+                    Some(event) if event.is_vetoed(sink.name()) => None,
+                    Some(Event::Message(msg, snapshot, outcome)) => {
+                        let timestamp = msg.timestamp;
+                        let event = Event::Message(msg, snapshot, outcome);
+                        reorder.push(timestamp, event);
+                        None
+                    }
+                    Some(event) => Some(event),
+                    None => break,
+                },
+                _ = tick.tick() => None,
+            };
+            let ready = std::iter::from_fn(|| reorder.pop());
+            for event in ready.chain(event).collect::<Vec<_>>() {
+                let result = sink
+                    .handle(&event)
+                    .instrument(trace_span!(target: profile::TARGET, "sink"))
+                    .await;
+                match result {
+                    Ok(()) => handled(sink.name(), &event),
+                    Err(e) => error!("Error in {} sink: {}", sink.name(), e),
+                }
+            }
+            if !warned && reorder.late > 0 {
+                warn!(
+                    "{} sink received messages later than the maximum delay, \
+                    consider increasing it",
+                    sink.name(),
+                );
+                warned = true;
+            }
+        }
+        // Flush the buffer when the channel is closed
+        for event in reorder.drain() {
+            if let Err(e) = sink.handle(&event).await {
+                error!("Error in {} sink: {}", sink.name(), e);
+            }
+        }
+    });
+}
+
+/// Record the latency of messages handled by a sink, if measured
+fn handled(name: &'static str, event: &Event) {
+    if let Event::Message(msg, ..) = event {
+        latency::sink(name, msg);
+    }
+}
+
+fn to_json(event: &Event) -> serde_json::Result<String> {
+    trace_span!(target: profile::TARGET, "serialization")
+        .in_scope(|| event.to_json())
+}
+
+/// The JSON line of a message with its integrity hash, and the fields
+/// added by the script
+fn signed(
+    chain: &mut HashChain,
+    msg: &TimedMessage,
+    outcome: &Option<Arc<Outcome>>,
+) -> serde_json::Result<String> {
+    let line = chain.sign(msg)?;
+    match outcome {
+        Some(outcome) => outcome.extend(line),
+        None => Ok(line),
+    }
+}
+
+/// The sinks, as named in the configuration
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// The standard output (verbose mode)
+    Stdout,
+    /// The output file
+    File,
+    /// The hourly archive
+    Archive,
+    /// The Redis topic
+    Redis,
+    /// The multicast group
+    Multicast,
+}
+
+/**
+ * Add the enriched layer to the JSON line of a message:
+ * `{"decoded": <line>, "enriched": <state vector>}`.
+ *
+ * The decoded layer is the output of the decoder, as written by default;
+ * the enriched layer is the state vector of the aircraft after the message
+ * (as on the REST API), with everything jet1090 adds: information from the
+ * aircraft database and enrichment providers, and derived values. The
+ * enriched layer is `null` for messages without an aircraft. Other events
+ * are left unchanged.
+ */
+fn layered(line: String, event: &Event) -> serde_json::Result<String> {
+    let Event::Message(_, snapshot, _) = event else {
+        return Ok(line);
+    };
+    let enriched = trace_span!(target: profile::TARGET, "serialization")
+        .in_scope(|| serde_json::to_string(&snapshot.as_deref()))?;
+    Ok(format!(r#"{{"decoded":{line},"enriched":{enriched}}}"#))
+}
+
+/// The format of the lines printed on the standard output and in the file
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// One JSON object per line, for all events
+    #[default]
+    Json,
+    /// SBS-1 (BaseStation) lines, as on port 30003 of dump1090: only
+    /// messages with an equivalent in the format are written
+    Sbs,
+}
+
+impl Format {
+    /// The line of an event, if it has one in this format
+    fn line(
+        &self,
+        event: &Event,
+        enriched: bool,
+    ) -> serde_json::Result<Option<String>> {
+        match (self, event) {
+            (Format::Json, _) if enriched => {
+                layered(to_json(event)?, event).map(Some)
+            }
+            (Format::Json, _) => to_json(event).map(Some),
+            (Format::Sbs, Event::Message(msg, ..)) => Ok(msg.to_sbs()),
+            (Format::Sbs, _) => Ok(None),
+        }
+    }
+}
+
+/// Print events on the standard output (verbose mode)
+pub struct Stdout {
+    pub format: Format,
+    /// Add the enriched layer to JSON messages
+    pub enriched: bool,
+    pub filters: watch::Receiver<Filters>,
+}
+
+impl Sink for Stdout {
+    fn name(&self) -> &'static str {
+        "stdout"
+    }
+
+    async fn handle(&mut self, event: &Event) -> Result<(), SinkError> {
+        if !event.is_in(&self.filters.borrow()) {
+            return Ok(());
+        }
+        if let Some(line) = self.format.line(event, self.enriched)? {
+            println!("{line}");
+        }
+        Ok(())
+    }
+}
+
+/// Write events to the output file, with integrity hashes on messages
+pub struct File {
+    pub writer: mpsc::Sender<String>,
+    pub format: Format,
+    /// Add the enriched layer to JSON messages
+    pub enriched: bool,
+    pub chain: Option<HashChain>,
+    pub filters: watch::Receiver<Filters>,
+}
+
+impl Sink for File {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn handle(&mut self, event: &Event) -> Result<(), SinkError> {
+        if !event.is_in(&self.filters.borrow()) {
+            return Ok(());
+        }
+        let line = match (event, &mut self.chain) {
+            // Integrity hashes are only added to JSON lines
+            (Event::Message(msg, _, outcome), Some(chain))
+                if self.format == Format::Json =>
+            {
+                let line = signed(chain, msg, outcome)?;
+                match self.enriched {
+                    true => Some(layered(line, event)?),
+                    false => Some(line),
+                }
+            }
+            _ => self.format.line(event, self.enriched)?,
+        };
+        if let Some(line) = line {
+            self.writer.send(line).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Archive messages in a directory tree, one compressed file per hour
+pub struct Archive {
+    pub writer: mpsc::Sender<archive::Entry>,
+    /// Add the enriched layer to messages
+    pub enriched: bool,
+    pub filters: watch::Receiver<Filters>,
+}
+
+impl Sink for Archive {
+    fn name(&self) -> &'static str {
+        "archive"
+    }
+
+    async fn handle(&mut self, event: &Event) -> Result<(), SinkError> {
+        let Event::Message(msg, ..) = event else {
+            return Ok(());
+        };
+        if !event.is_in(&self.filters.borrow()) {
+            return Ok(());
+        }
+        // Only addresses announced in clear are listed in the index
+        let icao24 = match msg.message.as_ref().and_then(Message::address) {
+            Some((icao24, AddressProvenance::Announced)) => {
+                Some(icao24.to_string())
+            }
+            _ => None,
+        };
+        let mut line = to_json(event)?;
+        if self.enriched {
+            line = layered(line, event)?;
+        }
+        self.writer.send(archive::Entry { line, icao24 }).await?;
+        Ok(())
+    }
+}
+
+/**
+ * Send messages to a multicast group, one JSON message per datagram, so
+ * that several consumers on the LAN receive them without a broker.
+ */
+pub struct Multicast {
+    pub socket: UdpSocket,
+    pub group: SocketAddr,
+    /// Add the enriched layer to messages
+    pub enriched: bool,
+    pub filters: watch::Receiver<Filters>,
+}
+
+impl Sink for Multicast {
+    fn name(&self) -> &'static str {
+        "multicast"
+    }
+
+    async fn handle(&mut self, event: &Event) -> Result<(), SinkError> {
+        if !matches!(event, Event::Message(..))
+            || !event.is_in(&self.filters.borrow())
+        {
+            return Ok(());
+        }
+        let mut payload = to_json(event)?;
+        if self.enriched {
+            payload = layered(payload, event)?;
+        }
+        self.socket.send_to(payload.as_bytes(), self.group).await?;
+        Ok(())
+    }
+}
+
+/// Default number of messages buffered while Redis is unreachable
+pub const REDIS_BUFFER: usize = 10_000;
+/// Delay before the first attempt to reconnect to Redis, doubled each time
+const REDIS_MIN_BACKOFF: Duration = Duration::from_millis(500);
+/// Maximum delay between two attempts to reconnect to Redis
+const REDIS_MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Maximum duration of a connection attempt, or of a publication
+const REDIS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state of the connection to Redis, shared with the REST API
+#[derive(Debug, Default)]
+pub struct RedisStatus {
+    capacity: usize,
+    connected: AtomicBool,
+    buffered: AtomicUsize,
+    dropped: AtomicU64,
+    reconnections: AtomicU64,
+}
+
+/// The state of the connection to Redis, as served on the REST API
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RedisStats {
+    /// Maximum number of messages buffered while disconnected
+    pub capacity: usize,
+    pub connected: bool,
+    /// Messages waiting for the connection to be restored
+    pub buffered: usize,
+    /// Messages dropped (the oldest first) when the buffer was full
+    pub dropped: u64,
+    /// Number of times the connection was restored after an outage
+    pub reconnections: u64,
+}
+
+impl RedisStatus {
+    pub fn stats(&self) -> RedisStats {
+        RedisStats {
+            capacity: self.capacity,
+            connected: self.connected.load(Ordering::Relaxed),
+            buffered: self.buffered.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            reconnections: self.reconnections.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/**
+ * Publish events to a Redis topic, with integrity hashes on messages.
+ *
+ * Messages may be restricted to some fields (all fields if empty), with
+ * the ones needed to check the integrity hash if any. With the enriched
+ * layer, fields are selected after the layers are added.
+ *
+ * When the Redis server is unreachable (e.g. while it restarts), events are
+ * buffered up to a capacity, then the oldest ones are dropped. The sink
+ * reconnects with an exponential backoff, when the next event comes, and
+ * publishes the buffered events first, in order.
+ */
+pub struct Redis {
+    client: redis::Client,
+    connection: Option<MultiplexedConnection>,
+    topic: String,
+    chain: Option<HashChain>,
+    fields: Vec<Pointer>,
+    enriched: bool,
+    buffer: VecDeque<String>,
+    backoff: Duration,
+    retry: Instant,
+    /// True after a failure, until the connection is restored
+    outage: bool,
+    status: Arc<RedisStatus>,
+}
+
+impl Redis {
+    pub fn new(
+        client: redis::Client,
+        topic: String,
+        chain: Option<HashChain>,
+        fields: Vec<Pointer>,
+        enriched: bool,
+        capacity: usize,
+    ) -> Self {
+        Redis {
+            client,
+            connection: None,
+            topic,
+            chain,
+            fields,
+            enriched,
+            buffer: VecDeque::new(),
+            backoff: REDIS_MIN_BACKOFF,
+            retry: Instant::now(),
+            outage: false,
+            status: Arc::new(RedisStatus {
+                capacity,
+                ..RedisStatus::default()
+            }),
+        }
+    }
+
+    /// The state of the connection, updated by the sink
+    pub fn status(&self) -> Arc<RedisStatus> {
+        self.status.clone()
+    }
+
+    /// Try to connect to the server, true on success
+    pub async fn connect(&mut self) -> bool {
+        let connection = self.client.get_multiplexed_async_connection();
+        let reason = match timeout(REDIS_TIMEOUT, connection).await {
+            Ok(Ok(connection)) => {
+                if self.outage {
+                    info!(
+                        "Reconnected to the Redis server, publishing {} \
+                        buffered messages",
+                        self.buffer.len()
+                    );
+                    self.status.reconnections.fetch_add(1, Ordering::Relaxed);
+                }
+                self.connection = Some(connection);
+                self.outage = false;
+                self.backoff = REDIS_MIN_BACKOFF;
+                self.status.connected.store(true, Ordering::Relaxed);
+                return true;
+            }
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => "timeout".to_string(),
+        };
+        self.disconnect(&reason);
+        false
+    }
+
+    /// Drop the connection after a failure, and schedule the next attempt
+    fn disconnect(&mut self, reason: &str) {
+        self.connection = None;
+        self.status.connected.store(false, Ordering::Relaxed);
+        if !self.outage {
+            warn!(
+                "Unable to publish to the Redis server ({}), buffering up to \
+                {} messages",
+                reason, self.status.capacity
+            );
+        } else {
+            debug!("Failed to reconnect to the Redis server: {}", reason);
+        }
+        self.outage = true;
+        self.retry = Instant::now() + self.backoff;
+        self.backoff = (self.backoff * 2).min(REDIS_MAX_BACKOFF);
+    }
+
+    /// Publish the buffered events, reconnecting first if needed
+    async fn flush(&mut self) {
+        if self.connection.is_none() && Instant::now() >= self.retry {
+            self.connect().await;
+        }
+        while let (Some(connection), Some(payload)) =
+            (&mut self.connection, self.buffer.front())
+        {
+            let publish = connection.publish::<_, _, ()>(&self.topic, payload);
+            match timeout(REDIS_TIMEOUT, publish).await {
+                Ok(Ok(())) => {
+                    self.buffer.pop_front();
+                }
+                Ok(Err(e)) => self.disconnect(&e.to_string()),
+                Err(_) => self.disconnect("timeout"),
+            }
+        }
+        self.status
+            .buffered
+            .store(self.buffer.len(), Ordering::Relaxed);
+    }
+}
+
+impl Sink for Redis {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn handle(&mut self, event: &Event) -> Result<(), SinkError> {
+        let payload = match (event, &mut self.chain) {
+            (Event::Message(msg, _, outcome), Some(chain)) => {
+                signed(chain, msg, outcome)?
+            }
+            _ => to_json(event)?,
+        };
+        let payload = match self.enriched {
+            true => layered(payload, event)?,
+            false => payload,
+        };
+        let payload = match event {
+            Event::Message(..) if !self.fields.is_empty() => {
+                let value = serde_json::from_str(&payload)?;
+                serde_json::to_string(&projection::project(
+                    &value,
+                    &self.fields,
+                ))?
+            }
+            _ => payload,
+        };
+        if self.buffer.len() >= self.status.capacity.max(1) {
+            self.buffer.pop_front();
+            self.status.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.buffer.push_back(payload);
+        self.flush().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enrich::Enricher;
+    use crate::event::SensorStatus;
+    use crate::snapshot::StateVectors;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_layered() {
+        let msg = TimedMessage::from_frame(
+            Timestamp::from_secs(1_700_000_000),
+            &[0x5d, 0x4d, 0x20, 0x23, 0x7a, 0x55, 0xa6],
+        );
+        let sv = StateVectors::new(
+            1_700_000_000,
+            "4d2023".to_string(),
+            &BTreeMap::new(),
+            &Enricher::default(),
+        );
+        let event = Event::Message(Arc::new(msg), Some(sv.published), None);
+        let decoded = Format::Json.line(&event, false).unwrap().unwrap();
+        let line = Format::Json.line(&event, true).unwrap().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            value["decoded"],
+            serde_json::from_str::<serde_json::Value>(&decoded).unwrap()
+        );
+        assert_eq!(value["enriched"]["icao24"], "4d2023");
+        // Only the enriched layer has the additions of jet1090
+        assert!(value["decoded"].get("allocation").is_none());
+        assert!(value["enriched"].get("allocation").is_some());
+
+        // Without an aircraft, the enriched layer is null
+        let Event::Message(msg, ..) = event else {
+            unreachable!()
+        };
+        let event = Event::Message(msg, None, None);
+        let line = Format::Json.line(&event, true).unwrap().unwrap();
+        assert!(line.ends_with(r#","enriched":null}"#));
+
+        // Other events are not layered
+        let event = Event::SensorUp(SensorStatus {
+            serial: 0,
+            name: None,
+            timestamp: 1_700_000_000,
+        });
+        assert_eq!(
+            Format::Json.line(&event, true).unwrap(),
+            Format::Json.line(&event, false).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redis_buffer() {
+        // Nothing listens on this port: the connection is refused
+        let client = redis::Client::open("redis://127.0.0.1:1").unwrap();
+        let mut sink =
+            Redis::new(client, "jet1090".into(), None, vec![], false, 2);
+        let status = sink.status();
+        assert!(!sink.connect().await);
+        for serial in 0..3 {
+            let event = Event::SensorUp(SensorStatus {
+                serial,
+                name: None,
+                timestamp: 1_700_000_000,
+            });
+            sink.handle(&event).await.unwrap();
+        }
+        let stats = status.stats();
+        assert!(!stats.connected);
+        assert_eq!((stats.buffered, stats.dropped), (2, 1));
+        assert!(sink.buffer[0].contains("\"serial\":1"));
+    }
+
+    /// A sink recording serials, which waits before handling any event
+    struct Slow {
+        gate: Arc<tokio::sync::Notify>,
+        serials: Arc<std::sync::Mutex<Vec<u64>>>,
+    }
+
+    impl Sink for Slow {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        async fn handle(&mut self, event: &Event) -> Result<(), SinkError> {
+            if let Event::SensorUp(sensor) = event {
+                if sensor.serial == 0 {
+                    self.gate.notified().await;
+                }
+                self.serials.lock().unwrap().push(sensor.serial);
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lossless() {
+        let gate = Arc::new(tokio::sync::Notify::new());
+        let serials = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut events = Publisher::default();
+        let sink = Slow {
+            gate: gate.clone(),
+            serials: serials.clone(),
+        };
+        spawn(sink, events.subscribe_lossless());
+
+        // More events than the channel holds, while the sink is stuck
+        let count = 2 * crate::event::CAPACITY as u64;
+        let publisher = events.clone();
+        let sender = tokio::spawn(async move {
+            for serial in 0..count {
+                let event = Event::SensorUp(SensorStatus {
+                    serial,
+                    name: None,
+                    timestamp: 1_700_000_000,
+                });
+                publisher.send(event).await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // Sending waits for the sink, rather than dropping events
+        assert!(!sender.is_finished());
+        assert!(serials.lock().unwrap().is_empty());
+
+        gate.notify_one();
+        sender.await.unwrap();
+        drain(&events).await;
+        let serials = serials.lock().unwrap();
+        assert_eq!(*serials, (0..count).collect::<Vec<_>>());
+    }
+}
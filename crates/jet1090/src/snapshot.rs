@@ -1,20 +1,37 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
 
-use rs1090::decode::bds::bds09::AirborneVelocitySubType::{
-    AirspeedSubsonic, GroundSpeedDecoding,
-};
-use rs1090::decode::bds::bds09::AirspeedType::{IAS, TAS};
-use rs1090::decode::{IdentityCode, SensorMetadata};
+use rs1090::data::patterns::{allocation, Allocation};
+use rs1090::data::performance::{performance, Engine, WakeTurbulence};
+use rs1090::decode::bds::bds09::AirborneVelocitySubType::GroundSpeedDecoding;
+use rs1090::decode::cpr::{haversine, CprFailure};
+use rs1090::decode::tracking::Smoothed;
+use rs1090::decode::{ReplyInformation, SensorMetadata};
+use rs1090::geom;
 use rs1090::prelude::*;
+use rs1090::tracker::State;
 use serde::Serialize;
 use tokio::sync::Mutex;
 
+use crate::association;
+use crate::class::{Classifier, TargetClass};
+use crate::enrich::Enricher;
+use crate::event::{Alert, Event};
+use crate::flight::FlightLog;
+use crate::phase::{FlightPhase, PhaseClassifier};
+use crate::qnh::QnhSource;
+use crate::velocity::VelocityEstimator;
 use crate::{aircraftdb, Jet1090};
 
+/// Distance (in km) under which the labels of two aircraft may overlap
+pub const LABEL_DISTANCE: f64 = 5.;
+/// The period (in s) over which the number of receivers is the maximum
+pub const RECEIVERS_WINDOW: u64 = 60;
+
 /**
  * A state vector with the most up-to-date information about an aircraft
  */
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Snapshot {
     /// The ICAO 24-bit address of the aircraft transponder
     pub icao24: String,
@@ -22,44 +39,112 @@ pub struct Snapshot {
     pub firstseen: u64,
     /// The timestamp of the last seen message
     pub lastseen: u64,
-    /// The callsign of the aircraft, ICAO flight number for commercial aircraft, often matches registration in General Aviation.
-    pub callsign: Option<String>,
     /// The tail number of the aircraft. If the aircraft is not known in the local database, some heuristics may reconstruct the tail number in some countries.
     pub registration: Option<String>,
     /// The ICAO code to the type of aircraft, e.g. A32O or B789
     pub typecode: Option<String>,
-    /// The squawk code, a 4-digit number set on the transponder, 7700 for general emergencies
-    pub squawk: Option<IdentityCode>,
-    /// WGS84 latitude angle in degrees
-    pub latitude: Option<f64>,
-    /// WGS84 longitude angle in degrees
-    pub longitude: Option<f64>,
-    /// Barometric altitude in feet, expressed in ISA
-    pub altitude: Option<u16>,
-    /// Altitude selected in the FMS
-    pub selected_altitude: Option<u16>,
-    /// Ground speed, in knots
-    pub groundspeed: Option<f64>,
-    /// Vertical rate of the aircraft, in feet/min
-    pub vertical_rate: Option<i16>,
-    /// The true track angle of the aircraft in degrees with respect to the geographic North
-    pub track: Option<f64>,
-    /// Indicated air speed, in knots
-    pub ias: Option<u16>,
-    /// True air speed, in knots
-    pub tas: Option<u16>,
-    /// The Mach number
-    pub mach: Option<f64>,
-    /// The roll angle of the aircraft in degrees (positive angle for banking to the right-hand side)
-    pub roll: Option<f64>,
-    /// The magnetic heading of the aircraft in degrees with respect to the magnetic North
-    pub heading: Option<f64>,
-    /// The NAC position indicator, for uncertainty
-    pub nacp: Option<u8>,
+    /// The owner or operator of the aircraft (with the enrich option)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    /// A link to photos of the aircraft (with the enrich option)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub photo: Option<String>,
+    /// The ACAS capability, from the reply information of the last air-air
+    /// surveillance reply (DF0 or DF16)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acas_capability: Option<ReplyInformation>,
+    /// The wake turbulence category of the type of aircraft, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wtc: Option<WakeTurbulence>,
+    /// The type of engines of the type of aircraft, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<Engine>,
+    /// Whether the target is an aircraft or a surface vehicle
+    pub class: TargetClass,
+    /// The kind of block the address is allocated from, e.g. military
+    pub allocation: Allocation,
+    /// True if the aircraft was only seen in replies to interrogations
+    /// (e.g. DF4, DF5, DF20, DF21), without any extended squitter
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub mode_s_only: bool,
+    /// The callsign, squawk code, position, altitude, speeds and last BDS
+    /// registers, as merged from the messages by [`State`]
+    #[serde(flatten)]
+    pub state: State,
+    /// Barometric altitude in feet, corrected with the local QNH (with the
+    /// qnh option)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude_qnh_corrected: Option<i32>,
+    /// The QNH in hPa used to correct the barometric altitude
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qnh: Option<f64>,
+    /// Where the QNH comes from: `fixed` if configured, `bds40` if agreed on
+    /// by the barometric settings of aircraft around
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qnh_source: Option<QnhSource>,
+    /// Why the last position message could not be decoded, if it failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position_failure: Option<CprFailure>,
+    /// True if the ground speed and track are derived from successive
+    /// positions, for aircraft not sending any velocity
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub derived: bool,
+    /// True if the last velocity message is inconsistent with successive
+    /// positions
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub velocity_inconsistent: bool,
+    /// The phase of flight, derived from the history of the aircraft
+    pub phase: Option<FlightPhase>,
+    /// WGS84 geometric height in meters (with the geometry option)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<f64>,
+    /// ECEF coordinates in meters (with the geometry option)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ecef: Option<[f64; 3]>,
+    /// East North Up coordinates in meters, relative to the receiver (with
+    /// the geometry option)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enu: Option<[f64; 3]>,
+    /// The label to display: the callsign, else the registration, else the
+    /// ICAO 24-bit address
+    pub display_label: String,
+    /// An index to offset the label on a map, different for aircraft closer
+    /// than [`LABEL_DISTANCE`] to each other (0 for isolated aircraft)
+    pub label_offset: u8,
     /// Number of messages received for the aircraft
     pub count: usize,
     /// Metadata information from the sensors seeing the aircraft
     pub metadata: Vec<SensorMetadata>,
+    /// The largest number of sensors receiving a same message over the last
+    /// minute, e.g. to assess the feasibility of multilateration
+    pub num_receivers: usize,
+    /// A logical track identifier, shared with the previous segments of the
+    /// trajectory under other addresses (heuristic, with the
+    /// track_association option)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<String>,
+    /// The address of the previous segment of the trajectory, if stitched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stitched_from: Option<String>,
+    /// The trajectory smoothed with a Kalman filter (with the smoothing
+    /// option)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoothed: Option<Smoothed>,
+    /// The snapshot version of the last update of the aircraft, to compare
+    /// with the `changed_since` parameter of diff-based clients
+    pub version: u64,
+}
+
+impl Snapshot {
+    fn display_label(&self) -> String {
+        [&self.state.callsign, &self.registration]
+            .into_iter()
+            .flatten()
+            .map(|label| label.trim())
+            .find(|label| !label.is_empty())
+            .unwrap_or(&self.icao24)
+            .to_string()
+    }
 }
 
 /**
@@ -67,17 +152,85 @@ pub struct Snapshot {
  */
 #[derive(Debug)]
 pub struct StateVectors {
-    /// The latest state of the aircraft
+    /// The latest state of the aircraft, updated field by field
     pub cur: Snapshot,
+    /// The latest consistent state of the aircraft, replaced at once after
+    /// each update: readers never see a state mixing old and new values
+    pub published: Arc<Snapshot>,
     /// The history of received messages
     pub hist: Vec<TimedMessage>,
+    /// The snapshot version when the aircraft state was last updated
+    pub version: u64,
+    /// The classifier deriving the phase of flight
+    pub phase: PhaseClassifier,
+    /// The information accumulated for the flight summary
+    pub flight: FlightLog,
+    /// The estimator deriving velocities from positions
+    pub velocity: VelocityEstimator,
+    /// The difference between the GNSS height and the barometric altitude
+    pub geo_minus_baro: Option<i16>,
+    /// True if the aircraft is a target broadcast by TIS-B
+    pub tisb: bool,
+    /// The classifier telling surface vehicles from aircraft
+    pub class: Classifier,
+    /// True if another address continues the trajectory of the aircraft
+    pub continued: bool,
+    /// True if the aircraft ever sent an extended squitter (DF17 or DF18)
+    pub squitter: bool,
+    /// The number of receivers of recent messages (timestamp in s),
+    /// decreasing so that the first one is the maximum over the window
+    receivers: VecDeque<(u64, usize)>,
+}
+
+/**
+ * Aircraft removed from the state vectors, for incremental updates.
+ *
+ * Removals are kept over two rounds of expiration, so that clients polling
+ * more often than the expiration period never miss any of them.
+ */
+#[derive(Debug, Default)]
+pub struct Removals {
+    /// The removed aircraft, with the snapshot version of their removal
+    pub entries: Vec<(u64, String)>,
+    /// All removals after this snapshot version are known
+    pub since: u64,
+    /// The snapshot version at the beginning of the current round
+    round: u64,
+}
+
+impl Removals {
+    /// Start a new round of expiration, forget about the previous round
+    pub fn new_round(&mut self, version: u64) {
+        self.since = std::mem::replace(&mut self.round, version);
+        let since = self.since;
+        self.entries.retain(|(v, _)| *v > since);
+    }
+
+    pub fn push(&mut self, version: u64, icao24: String) {
+        self.entries.push((version, icao24));
+    }
+
+    /// The aircraft removed after a given snapshot version, if known
+    pub fn after(&self, version: u64) -> Option<Vec<&String>> {
+        if version < self.since {
+            return None;
+        }
+        Some(
+            self.entries
+                .iter()
+                .filter(|(v, _)| *v > version)
+                .map(|(_, icao24)| icao24)
+                .collect(),
+        )
+    }
 }
 
 impl StateVectors {
-    fn new(
+    pub fn new(
         ts: u64,
         icao24: String,
         aircraftdb: &BTreeMap<String, aircraftdb::Aircraft>,
+        enricher: &Enricher,
     ) -> StateVectors {
         let hexid = u32::from_str_radix(&icao24, 16).unwrap_or(0);
         let ac = aircraftdb.get(&icao24);
@@ -85,6 +238,7 @@ impl StateVectors {
             Some(ac) => ac.typecode.to_owned(),
             None => None,
         };
+        let performance = typecode.as_deref().and_then(performance);
         let mut registration = match ac {
             Some(ac) => ac.registration.to_owned(),
             None => None,
@@ -93,57 +247,192 @@ impl StateVectors {
             // Heuristics to decode the tail number
             registration = rs1090::data::tail::tail(hexid);
         }
+        let enrichment = enricher.lookup(&icao24, registration.as_deref());
 
-        let cur = Snapshot {
+        let mut cur = Snapshot {
             icao24,
             firstseen: ts,
             lastseen: ts,
-            callsign: None,
             registration,
             typecode,
-            squawk: None,
-            latitude: None,
-            longitude: None,
-            altitude: None,
-            selected_altitude: None,
-            groundspeed: None,
-            vertical_rate: None,
-            track: None,
-            ias: None,
-            tas: None,
-            mach: None,
-            roll: None,
-            heading: None,
-            nacp: None,
+            operator: enrichment.operator,
+            photo: enrichment.photo,
+            acas_capability: None,
+            wtc: performance.map(|p| p.wtc),
+            engine: performance.map(|p| p.engine),
+            class: TargetClass::Aircraft,
+            allocation: allocation(hexid),
+            mode_s_only: false,
+            state: State::default(),
+            altitude_qnh_corrected: None,
+            qnh: None,
+            qnh_source: None,
+            position_failure: None,
+            derived: false,
+            velocity_inconsistent: false,
+            phase: None,
+            height: None,
+            ecef: None,
+            enu: None,
+            display_label: String::new(),
+            label_offset: 0,
             count: 0,
             metadata: vec![],
+            num_receivers: 0,
+            track_id: None,
+            stitched_from: None,
+            smoothed: None,
+            version: 0,
         };
+        cur.display_label = cur.display_label();
         StateVectors {
+            published: Arc::new(cur.clone()),
             cur,
             hist: Vec::<TimedMessage>::new(),
+            version: 0,
+            phase: PhaseClassifier::default(),
+            flight: FlightLog::default(),
+            velocity: VelocityEstimator::default(),
+            geo_minus_baro: None,
+            tisb: false,
+            class: Classifier::default(),
+            continued: false,
+            squitter: false,
+            receivers: VecDeque::new(),
+        }
+    }
+
+    /**
+     * Count the receivers of a message at a given time (in s), and update
+     * the maximum number of receivers over the last [`RECEIVERS_WINDOW`].
+     */
+    pub fn count_receivers(&mut self, now: u64, count: usize) {
+        let receivers = &mut self.receivers;
+        while receivers.back().is_some_and(|&(_, n)| n <= count) {
+            receivers.pop_back();
+        }
+        receivers.push_back((now, count));
+        while receivers
+            .front()
+            .is_some_and(|&(t, _)| t + RECEIVERS_WINDOW < now)
+        {
+            receivers.pop_front();
+        }
+        self.cur.num_receivers = receivers.front().map_or(0, |&(_, n)| n);
+    }
+
+    /// Publish the current state, updated with a new snapshot version
+    pub fn publish(&mut self, version: u64) {
+        self.version = version;
+        self.cur.version = version;
+        self.published = Arc::new(self.cur.clone());
+    }
+
+    /**
+     * Compute the WGS84 height, ECEF and ENU coordinates of the aircraft.
+     *
+     * The receiver is given with its position and its height (in meters).
+     */
+    fn update_geometry(&mut self, receiver: Option<(Position, f64)>) {
+        let cur = &mut self.cur;
+        cur.height = match (cur.state.altitude, self.geo_minus_baro) {
+            (Some(altitude), Some(geo_minus_baro)) => Some(
+                geom::geometric_height(altitude as i32, geo_minus_baro, None),
+            ),
+            _ => None,
+        };
+        cur.ecef = match (cur.state.latitude, cur.state.longitude, cur.height) {
+            (Some(lat), Some(lon), Some(height)) => {
+                Some(geom::ecef(lat, lon, height))
+            }
+            _ => None,
+        };
+        cur.enu = match (cur.ecef, receiver) {
+            (Some(ecef), Some((pos, height))) => {
+                Some(geom::enu(ecef, pos.latitude, pos.longitude, height))
+            }
+            _ => None,
+        };
+    }
+}
+
+/**
+ * Assign label offsets to aircraft, so that display clients can move apart
+ * the labels of aircraft closer than [`LABEL_DISTANCE`] to each other.
+ *
+ * Aircraft keep their offset unless a neighbour already has it, so that
+ * labels do not jump around at each update. Aircraft with a new offset are
+ * marked as updated with a new snapshot version.
+ */
+pub fn deconflict_labels(
+    state_vectors: &mut BTreeMap<String, StateVectors>,
+    version: &mut u64,
+) {
+    let (keys, positions): (Vec<&String>, Vec<(f64, f64, u8)>) = state_vectors
+        .iter()
+        .filter_map(|(key, sv)| {
+            let (lat, lon) = (sv.cur.state.latitude?, sv.cur.state.longitude?);
+            Some((key, (lat, lon, sv.cur.label_offset)))
+        })
+        .unzip();
+    let offsets: BTreeMap<String, u8> = keys
+        .into_iter()
+        .cloned()
+        .zip(label_offsets(&positions))
+        .collect();
+
+    for (key, sv) in state_vectors.iter_mut() {
+        let offset = offsets.get(key).copied().unwrap_or(0);
+        if sv.cur.label_offset != offset {
+            sv.cur.label_offset = offset;
+            *version += 1;
+            sv.publish(*version);
         }
     }
 }
 
-fn icao24(msg: &Message) -> Option<String> {
-    match &msg.df {
-        ShortAirAirSurveillance { ap, .. } => Some(ap.to_string()),
-        SurveillanceAltitudeReply { ap, .. } => Some(ap.to_string()),
-        SurveillanceIdentityReply { ap, .. } => Some(ap.to_string()),
-        AllCallReply { icao, .. } => Some(icao.to_string()),
-        LongAirAirSurveillance { ap, .. } => Some(ap.to_string()),
-        ExtendedSquitterADSB(ADSB { icao24, .. }) => Some(icao24.to_string()),
-        ExtendedSquitterTisB { cf, .. } => Some(cf.aa.to_string()),
-        CommBAltitudeReply { ap, .. } => Some(ap.to_string()),
-        CommBIdentityReply { ap, .. } => Some(ap.to_string()),
-        _ => None,
+/// The label offset of each (latitude, longitude, current offset) position
+fn label_offsets(positions: &[(f64, f64, u8)]) -> Vec<u8> {
+    // Sort by latitude to only compare aircraft in the same band
+    let mut order: Vec<usize> = (0..positions.len()).collect();
+    order.sort_by(|&i, &j| positions[i].0.total_cmp(&positions[j].0));
+    let band = LABEL_DISTANCE / 111.;
+
+    let mut offsets = vec![0; positions.len()];
+    for (k, &i) in order.iter().enumerate() {
+        let (lat, lon, current) = positions[i];
+        let taken: Vec<u8> = order[..k]
+            .iter()
+            .rev()
+            .take_while(|&&j| lat - positions[j].0 <= band)
+            .filter(|&&j| {
+                let (lat2, lon2, _) = positions[j];
+                haversine(lat, lon, lat2, lon2) <= LABEL_DISTANCE
+            })
+            .map(|&j| offsets[j])
+            .collect();
+        offsets[i] = match taken.contains(&current) {
+            false => current,
+            true => (0..=u8::MAX)
+                .find(|offset| !taken.contains(offset))
+                .unwrap_or(0),
+        };
     }
+    offsets
+}
+
+fn icao24(msg: &Message) -> Option<String> {
+    msg.icao24().map(|icao| icao.to_string())
 }
 
 pub async fn update_snapshot(
     states: &Mutex<Jet1090>,
     msg: &mut TimedMessage,
     aircraftdb: &BTreeMap<String, aircraftdb::Aircraft>,
+    enricher: &Enricher,
+    failure: Option<CprFailure>,
+    geometry: bool,
+    associate: bool,
 ) {
     if let TimedMessage {
         timestamp,
@@ -153,214 +442,273 @@ pub async fn update_snapshot(
     } = msg
     {
         if let Some(icao24) = icao24(message) {
-            let states = &mut states.lock().await.state_vectors;
+            let mut guard = states.lock().await;
+            let app = &mut *guard;
+            // The position of the first sensor receiving the message
+            let receiver = metadata.iter().find_map(|meta| {
+                let sensor = app.sensors.get(&meta.serial)?;
+                let pos = sensor.reference?;
+                Some((pos, sensor.altitude.unwrap_or(0.)))
+            });
+            let events = app.events.clone();
+            app.version += 1;
+            let version = app.version;
+            let states = &mut app.state_vectors;
             let aircraft =
                 states
                     .entry(icao24.to_string())
                     .or_insert(StateVectors::new(
-                        *timestamp as u64,
+                        timestamp.as_secs() as u64,
                         icao24,
                         aircraftdb,
+                        enricher,
                     ));
-            aircraft.cur.lastseen = *timestamp as u64;
+            aircraft.cur.lastseen = timestamp.as_secs() as u64;
             aircraft.cur.metadata = metadata.to_vec();
+            aircraft
+                .count_receivers(timestamp.as_secs() as u64, metadata.len());
             aircraft.cur.count += 1;
+            let squawk = aircraft.cur.state.squawk;
 
+            // Surface position messages are only sent by aircraft on ground
+            let mut on_ground = None;
+            // Whether the message carries a position, or a velocity
+            let mut position = false;
+            let mut velocity = false;
+            // The derived velocity if the measured one is inconsistent
+            let mut inconsistent = None;
+
+            if let ExtendedSquitterADSB(_) | ExtendedSquitterTisB { .. } =
+                message.df
+            {
+                aircraft.squitter = true;
+            }
+            aircraft.cur.mode_s_only = !aircraft.squitter;
+
+            // Invalidate data if marked as both BDS50 and BDS60
             match &mut message.df {
-                SurveillanceIdentityReply { id, .. } => {
-                    aircraft.cur.squawk = Some(*id)
+                CommBAltitudeReply { bds, .. }
+                    if bds.bds50.is_some() && bds.bds60.is_some() =>
+                {
+                    bds.bds50 = None;
+                    bds.bds60 = None;
+                }
+                CommBIdentityReply { bds, .. }
+                    if bds.bds50.is_some() && bds.bds60.is_some() =>
+                {
+                    bds.bds50 = None;
+                    bds.bds60 = None;
                 }
-                SurveillanceAltitudeReply { ac, .. } => {
-                    aircraft.cur.altitude = Some(ac.0);
+                _ => {}
+            }
+            aircraft.cur.state.update(message);
+
+            match &message.df {
+                ShortAirAirSurveillance { ri, .. }
+                | LongAirAirSurveillance { ri, .. }
+                    if ri.is_acas() =>
+                {
+                    aircraft.cur.acas_capability = Some(*ri);
                 }
                 ExtendedSquitterADSB(adsb) => match &adsb.message {
                     ME::BDS05(bds05) => {
-                        aircraft.cur.latitude = bds05.latitude;
-                        aircraft.cur.longitude = bds05.longitude;
-                        aircraft.cur.altitude = bds05.alt;
+                        on_ground = Some(false);
+                        position = bds05.latitude.is_some();
                     }
                     ME::BDS06(bds06) => {
-                        aircraft.cur.latitude = bds06.latitude;
-                        aircraft.cur.longitude = bds06.longitude;
-                        aircraft.cur.track = bds06.track;
-                        aircraft.cur.groundspeed = bds06.groundspeed;
-                        aircraft.cur.altitude = None;
-                    }
-                    ME::BDS08(bds08) => {
-                        if !bds08.callsign.contains("#") {
-                            aircraft.cur.callsign =
-                                Some(bds08.callsign.to_string())
-                        }
+                        on_ground = Some(true);
+                        position = bds06.latitude.is_some();
+                        velocity = bds06.groundspeed.is_some();
                     }
                     ME::BDS09(bds09) => {
-                        aircraft.cur.vertical_rate = bds09.vertical_rate;
-                        match &bds09.velocity {
-                            GroundSpeedDecoding(spd) => {
-                                aircraft.cur.groundspeed =
-                                    Some(spd.groundspeed);
-                                aircraft.cur.track = Some(spd.track)
-                            }
-                            AirspeedSubsonic(spd) => {
-                                match spd.airspeed_type {
-                                    IAS => aircraft.cur.ias = spd.airspeed,
-                                    TAS => aircraft.cur.tas = spd.airspeed,
-                                }
-                                aircraft.cur.heading = spd.heading;
-                            }
-                            _ => {}
-                        }
-                    }
-                    ME::BDS61(bds61) => {
-                        aircraft.cur.squawk = Some(bds61.squawk);
-                    }
-                    ME::BDS62(bds62) => {
-                        aircraft.cur.selected_altitude =
-                            bds62.selected_altitude;
-                        aircraft.cur.nacp = Some(bds62.nac_p);
-                    }
-                    ME::BDS65(bds65) => match bds65 {
-                        AircraftOperationStatus::Airborne(st) => {
-                            match st.version {
-                                rs1090::decode::bds::bds65::ADSBVersionAirborne::DOC9871AppendixB(v) => {
-                                    aircraft.cur.nacp = Some(v.nac_p)
-                                }
-                                rs1090::decode::bds::bds65::ADSBVersionAirborne::DOC9871AppendixC(v) => {
-                                    aircraft.cur.nacp = Some(v.nac_p)
-                                }
-                                _ => {}
-                            }
+                        on_ground = Some(false);
+                        if bds09.geo_minus_baro.is_some() {
+                            aircraft.geo_minus_baro = bds09.geo_minus_baro;
                         }
-                        AircraftOperationStatus::Surface(st) => {
-                            match st.version {
-                                rs1090::decode::bds::bds65::ADSBVersionSurface::DOC9871AppendixB(v) => {
-                                    aircraft.cur.nacp = Some(v.nac_p)
-                                }
-                                rs1090::decode::bds::bds65::ADSBVersionSurface::DOC9871AppendixC(v) => {
-                                    aircraft.cur.nacp = Some(v.nac_p)
-                                }
-                                _ => {}
-                            }
+                        if let GroundSpeedDecoding(spd) = &bds09.velocity {
+                            velocity = true;
+                            inconsistent =
+                                Some(aircraft.velocity.inconsistent(
+                                    timestamp.as_secs_f64(),
+                                    spd.groundspeed,
+                                    spd.track,
+                                ));
                         }
-                        _ => {}
-                    },
+                    }
                     _ => {}
                 },
                 ExtendedSquitterTisB { cf, .. } => {
+                    aircraft.tisb = true;
                     aircraft.cur.typecode = Some("GRND".to_string());
                     match &cf.me {
-                        ME::BDS05(bds05) => {
-                            aircraft.cur.latitude = bds05.latitude;
-                            aircraft.cur.longitude = bds05.longitude;
-                            aircraft.cur.altitude = bds05.alt;
+                        TisBMessage::ExtendedSquitter(ME::BDS05(bds05)) => {
+                            on_ground = Some(false);
+                            position = bds05.latitude.is_some();
                         }
-                        ME::BDS06(bds06) => {
-                            aircraft.cur.latitude = bds06.latitude;
-                            aircraft.cur.longitude = bds06.longitude;
-                            aircraft.cur.track = bds06.track;
-                            aircraft.cur.groundspeed = bds06.groundspeed;
-                            aircraft.cur.altitude = None;
+                        TisBMessage::ExtendedSquitter(ME::BDS06(bds06)) => {
+                            on_ground = Some(true);
+                            position = bds06.latitude.is_some();
+                            velocity = bds06.groundspeed.is_some();
                         }
-                        ME::BDS08(bds08) => {
-                            aircraft.cur.callsign =
-                                Some(bds08.callsign.to_string())
+                        TisBMessage::Coarse(coarse) => {
+                            on_ground = Some(false);
+                            position = coarse.latitude.is_some();
+                            velocity = coarse.groundspeed.is_some();
                         }
                         _ => {}
                     }
                 }
                 CommBAltitudeReply { bds, .. } => {
-                    // Invalidate data if marked as both BDS50 and BDS60
-                    if let (Some(_), Some(_)) = (&bds.bds50, &bds.bds60) {
-                        bds.bds50 = None;
-                        bds.bds60 = None
-                    }
-                    if let Some(bds20) = &bds.bds20 {
-                        if !bds20.callsign.contains("#") {
-                            aircraft.cur.callsign =
-                                Some(bds20.callsign.to_string());
-                        }
-                    }
-                    if let Some(bds40) = &bds.bds40 {
-                        aircraft.cur.selected_altitude =
-                            bds40.selected_altitude_mcp;
-                    }
-                    if let Some(bds50) = &bds.bds50 {
-                        aircraft.cur.roll = bds50.roll_angle;
-                        aircraft.cur.track = bds50.track_angle;
-                        aircraft.cur.groundspeed =
-                            bds50.groundspeed.map(|x| x as f64);
-                        aircraft.cur.tas = bds50.true_airspeed;
-                    }
-                    if let Some(bds60) = &bds.bds60 {
-                        aircraft.cur.ias = bds60.indicated_airspeed;
-                        aircraft.cur.mach = bds60.mach_number;
-                        aircraft.cur.heading = bds60.magnetic_heading;
-                        if bds60.inertial_vertical_velocity.is_some() {
-                            aircraft.cur.vertical_rate =
-                                bds60.inertial_vertical_velocity;
-                        }
+                    let setting = bds
+                        .bds40
+                        .as_ref()
+                        .and_then(|bds40| bds40.barometric_setting);
+                    if let Some(setting) = setting {
+                        app.altimeter.record(
+                            &aircraft.cur.icao24,
+                            aircraft.cur.lastseen,
+                            aircraft.cur.state.altitude,
+                            setting,
+                        );
                     }
+                    velocity = bds
+                        .bds50
+                        .as_ref()
+                        .is_some_and(|bds50| bds50.groundspeed.is_some());
                 }
                 CommBIdentityReply { bds, .. } => {
-                    // Invalidate data if marked as both BDS50 and BDS60
-                    if let (Some(_), Some(_)) = (&bds.bds50, &bds.bds60) {
-                        bds.bds50 = None;
-                        bds.bds60 = None
-                    }
-                    if let Some(bds20) = &bds.bds20 {
-                        if !bds20.callsign.contains("#") {
-                            aircraft.cur.callsign =
-                                Some(bds20.callsign.to_string());
-                        }
-                    }
-                    if let Some(bds40) = &bds.bds40 {
-                        aircraft.cur.selected_altitude =
-                            bds40.selected_altitude_mcp;
-                    }
-                    if let Some(bds50) = &bds.bds50 {
-                        aircraft.cur.roll = bds50.roll_angle;
-                        aircraft.cur.track = bds50.track_angle;
-                        aircraft.cur.groundspeed =
-                            bds50.groundspeed.map(|x| x as f64);
-                        aircraft.cur.tas = bds50.true_airspeed;
-                    }
-                    if let Some(bds60) = &bds.bds60 {
-                        aircraft.cur.ias = bds60.indicated_airspeed;
-                        aircraft.cur.mach = bds60.mach_number;
-                        aircraft.cur.heading = bds60.magnetic_heading;
-                        if bds60.inertial_vertical_velocity.is_some() {
-                            aircraft.cur.vertical_rate =
-                                bds60.inertial_vertical_velocity;
-                        }
+                    let setting = bds
+                        .bds40
+                        .as_ref()
+                        .and_then(|bds40| bds40.barometric_setting);
+                    if let Some(setting) = setting {
+                        app.altimeter.record(
+                            &aircraft.cur.icao24,
+                            aircraft.cur.lastseen,
+                            aircraft.cur.state.altitude,
+                            setting,
+                        );
                     }
+                    velocity = bds
+                        .bds50
+                        .as_ref()
+                        .is_some_and(|bds50| bds50.groundspeed.is_some());
                 }
                 _ => {}
             };
+
+            if let Some(on_ground) = on_ground {
+                aircraft.class.record(on_ground);
+            }
+            aircraft.cur.class = aircraft.class.class(
+                aircraft.cur.state.category,
+                aircraft.cur.typecode.as_deref(),
+            );
+
+            // Cleared by the CPR decoding as soon as a position is decoded
+            if position || failure.is_some() {
+                aircraft.cur.position_failure = failure;
+            }
+            if velocity {
+                aircraft.velocity.measured(timestamp.as_secs_f64());
+                aircraft.cur.derived = false;
+            }
+            if let (true, Some(lat), Some(lon)) = (
+                position,
+                aircraft.cur.state.latitude,
+                aircraft.cur.state.longitude,
+            ) {
+                let derived =
+                    aircraft.velocity.update(timestamp.as_secs_f64(), lat, lon);
+                if let Some((groundspeed, track)) = derived {
+                    aircraft.cur.state.groundspeed = Some(groundspeed);
+                    aircraft.cur.state.track = Some(track);
+                    aircraft.cur.derived = true;
+                }
+            }
+
+            if geometry {
+                aircraft.update_geometry(receiver);
+            }
+
+            let corrected = app
+                .altimeter
+                .correct(aircraft.cur.lastseen, aircraft.cur.state.altitude);
+            aircraft.cur.altitude_qnh_corrected = corrected.map(|c| c.0);
+            aircraft.cur.qnh = corrected.map(|c| c.1);
+            aircraft.cur.qnh_source = corrected.map(|c| c.2);
+
+            aircraft.cur.phase = aircraft.phase.update(
+                timestamp.as_secs_f64(),
+                on_ground,
+                aircraft.cur.state.vertical_rate,
+                aircraft.cur.state.groundspeed,
+            );
+            aircraft.cur.display_label = aircraft.cur.display_label();
+            aircraft.flight.update(&aircraft.cur);
+
+            if let Some(derived) = inconsistent {
+                let previous = aircraft.cur.velocity_inconsistent;
+                aircraft.cur.velocity_inconsistent = derived.is_some();
+                if let (false, Some((gs, track)), Some(address)) =
+                    (previous, derived, message.icao24())
+                {
+                    let alert = Alert::VelocityInconsistent {
+                        icao24: address,
+                        groundspeed: aircraft
+                            .cur
+                            .state
+                            .groundspeed
+                            .unwrap_or(0.),
+                        track: aircraft.cur.state.track.unwrap_or(0.),
+                        derived_groundspeed: (gs * 10.).round() / 10.,
+                        derived_track: (track * 10.).round() / 10.,
+                        timestamp: timestamp.as_secs() as u64,
+                    };
+                    events.send(Event::Alert(alert)).await;
+                }
+            }
+
+            if let (Some(code), Some(address)) =
+                (aircraft.cur.state.squawk, message.icao24())
+            {
+                let alert =
+                    Alert::squawk(address, code, timestamp.as_secs() as u64);
+                if let Some(alert) = alert.filter(|_| squawk != Some(code)) {
+                    events.send(Event::Alert(alert)).await;
+                }
+            }
+
+            // Aircraft are stitched to previous segments at their first
+            // decoded position
+            let key = aircraft.cur.icao24.clone();
+            if associate && position && aircraft.cur.track_id.is_none() {
+                association::associate(states, &key);
+            }
+            if let Some(aircraft) = states.get_mut(&key) {
+                aircraft.publish(version);
+            }
         }
     }
 }
 
 pub async fn store_history(
     states: &Mutex<Jet1090>,
-    msg: TimedMessage,
+    msg: &TimedMessage,
     aircraftdb: &BTreeMap<String, aircraftdb::Aircraft>,
+    enricher: &Enricher,
 ) {
-    if let TimedMessage {
-        timestamp,
-        message: Some(message),
-        metadata,
-        decode_time,
-        ..
-    } = msg
-    {
-        if let Some(icao24) = icao24(&message) {
+    if let Some(message) = &msg.message {
+        if let Some(icao24) = icao24(message) {
             let states = &mut states.lock().await.state_vectors;
             let aircraft =
                 states
                     .entry(icao24.to_string())
                     .or_insert(StateVectors::new(
-                        timestamp as u64,
+                        msg.timestamp.as_secs() as u64,
                         icao24,
                         aircraftdb,
+                        enricher,
                     ));
 
             match message.df {
@@ -369,11 +717,11 @@ pub async fn store_history(
                 | CommBAltitudeReply { .. }
                 | CommBIdentityReply { .. } => {
                     aircraft.hist.push(TimedMessage {
-                        timestamp,
+                        timestamp: msg.timestamp,
                         frame: vec![],
-                        message: Some(message),
-                        metadata,
-                        decode_time,
+                        message: Some(message.clone()),
+                        metadata: msg.metadata.clone(),
+                        decode_time: msg.decode_time,
                     })
                 }
                 _ => {}
@@ -381,3 +729,90 @@ pub async fn store_history(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removals() {
+        let mut removals = Removals::default();
+        removals.new_round(10);
+        removals.push(11, "abc123".to_string());
+        removals.new_round(20);
+        removals.push(21, "def456".to_string());
+
+        assert_eq!(removals.after(10).unwrap().len(), 2);
+        assert_eq!(removals.after(11).unwrap().len(), 1);
+
+        // Removals from the first round are forgotten
+        removals.new_round(30);
+        assert!(removals.after(10).is_none());
+        assert_eq!(removals.after(20).unwrap(), vec!["def456"]);
+        assert!(removals.after(21).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_publish() {
+        let mut sv = StateVectors::new(
+            0,
+            "39b415".to_string(),
+            &BTreeMap::new(),
+            &Enricher::default(),
+        );
+        sv.cur.state.latitude = Some(43.6);
+        // Readers only see the state once published
+        assert_eq!(sv.published.state.latitude, None);
+        sv.publish(12);
+        assert_eq!(sv.published.state.latitude, Some(43.6));
+        assert_eq!(sv.published.version, 12);
+        sv.cur.state.longitude = Some(1.37);
+        let published = serde_json::to_value(sv.published.as_ref()).unwrap();
+        assert_eq!(published["version"], 12);
+        assert_eq!(published["longitude"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_count_receivers() {
+        let mut sv = StateVectors::new(
+            0,
+            "39b415".to_string(),
+            &BTreeMap::new(),
+            &Enricher::default(),
+        );
+        sv.count_receivers(0, 1);
+        assert_eq!(sv.cur.num_receivers, 1);
+        sv.count_receivers(10, 4);
+        sv.count_receivers(20, 2);
+        assert_eq!(sv.cur.num_receivers, 4);
+        sv.count_receivers(65, 1);
+        assert_eq!(sv.cur.num_receivers, 4);
+        // Four receivers more than a minute ago
+        sv.count_receivers(71, 1);
+        assert_eq!(sv.cur.num_receivers, 2);
+        sv.count_receivers(90, 1);
+        assert_eq!(sv.cur.num_receivers, 1);
+    }
+
+    #[test]
+    fn test_label_offsets() {
+        // Three aircraft within a few km, and an isolated one
+        let positions = [
+            (43.60, 1.40, 0),
+            (43.62, 1.40, 0),
+            (43.61, 1.41, 0),
+            (45.00, 1.40, 0),
+        ];
+        let offsets = label_offsets(&positions);
+        assert_eq!(offsets, vec![0, 2, 1, 0]);
+
+        // Offsets are kept when aircraft move
+        let positions = [
+            (43.60, 1.40, 0),
+            (43.63, 1.40, 2),
+            (43.59, 1.41, 1),
+            (45.00, 1.40, 0),
+        ];
+        assert_eq!(label_offsets(&positions), offsets);
+    }
+}
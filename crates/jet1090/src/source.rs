@@ -1,8 +1,16 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
 use std::str::FromStr;
+use std::time::Duration;
 
 use rs1090::prelude::*;
+use rs1090::source::filter::FrameFilter;
+use rs1090::source::format::Format;
+use rs1090::source::iqcapture::CaptureSettings;
+use rs1090::source::jet1090;
+use rs1090::source::processing::{SampleStats, ThreadSettings};
+use rs1090::source::sensor::serial as build_serial;
+use rs1090::source::timestamps::{
+    TimestampCheck, TimestampSettings, TimestampStats,
+};
 
 #[cfg(feature = "rtlsdr")]
 use rs1090::source::rtlsdr;
@@ -14,6 +22,8 @@ use tokio::sync::mpsc::Sender;
 use tracing::error;
 use url::Url;
 
+use crate::dedup::Protocol;
+
 /**
 * A structure to describe the endpoint to access data.
 *
@@ -54,6 +64,8 @@ pub enum Address {
     Rtlsdr(Option<String>),
     /// A token-based access to Sero Systems (require feature `sero`).
     Sero(SeroParams),
+    /// Another instance of jet1090, serving its REST API, e.g. `remote:8080`
+    Jet1090(String),
 }
 
 /**
@@ -74,15 +86,43 @@ pub struct Source {
     pub reference: Option<Position>,
     /// Localize the source of data, altitude (in m, WGS84 height)
     pub altitude: Option<f64>,
-}
-
-fn build_serial(input: &str) -> u64 {
-    // Create a hasher
-    let mut hasher = DefaultHasher::new();
-    // Hash the string
-    input.hash(&mut hasher);
-    // Get the hash as a u64
-    hasher.finish()
+    /// Localize the source of data with an airport code, if no latitude and
+    /// longitude are given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub airport: Option<String>,
+    /// The format of the feed (beast, avr or sbs), detected if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<Format>,
+    /// Filters on downlink formats and typecodes, applied before decoding
+    #[serde(flatten)]
+    pub filter: FrameFilter,
+    /// The deduplication window for messages from this source (in ms), if
+    /// different from the default one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedup_ms: Option<u32>,
+    /// The protocol of the frames of this source (mode_s by default), which
+    /// are only deduplicated with frames of the same protocol
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<Protocol>,
+    /// The maximum range (in km) of decoded positions for this source, if
+    /// different from the default one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_range_filter: Option<f64>,
+    /// Capture the IQ samples around weak frames (only for RTL-SDR dongles)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iq_capture: Option<CaptureSettings>,
+    /// Process the IQ samples on a dedicated thread (only for RTL-SDR
+    /// dongles), optionally pinned to a CPU core with real-time priority
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread: Option<ThreadSettings>,
+    /// Flag the source when the timestamps of its frames go backwards or
+    /// jump, and optionally fall back to the arrival time (only for Beast)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamps: Option<TimestampSettings>,
+    /// Reconnect when no data is received for this duration (in s), or when
+    /// the connection is closed (only for TCP)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout: Option<u64>,
 }
 
 impl FromStr for Source {
@@ -116,6 +156,11 @@ impl FromStr for Source {
                 url.port_or_known_default().unwrap()
             )),
             "rtlsdr" => Address::Rtlsdr(url.host_str().map(|s| s.to_string())),
+            "jet1090" => Address::Jet1090(format!(
+                "{}:{}",
+                url.host_str().unwrap_or("localhost"),
+                url.port().ok_or("A port number was expected")?
+            )),
             "ws" => Address::Websocket(format!(
                 "ws://{}:{}/{}",
                 url.host_str().unwrap_or("0.0.0.0"),
@@ -130,22 +175,56 @@ impl FromStr for Source {
             name: None,
             reference: None,
             altitude: None,
+            airport: None,
+            format: None,
+            filter: FrameFilter::default(),
+            dedup_ms: None,
+            protocol: None,
+            max_range_filter: None,
+            iq_capture: None,
+            thread: None,
+            timestamps: None,
+            idle_timeout: None,
         };
 
-        if let Some(query) = url.query() {
-            source.reference = Position::from_str(query).ok()
-        };
+        // A reference position (e.g. an airport code) and key=value options
+        let params = url.query().into_iter().flat_map(|q| q.split('&'));
+        for param in params.filter(|param| !param.is_empty()) {
+            match param.split_once('=') {
+                Some(("format", format)) => {
+                    source.format = Some(format.parse()?)
+                }
+                Some((key, _)) => {
+                    return Err(format!("unknown parameter: {key}"));
+                }
+                None => match Position::from_str(param) {
+                    Ok(position) => source.reference = Some(position),
+                    Err(_) => {
+                        return Err(format!("invalid reference: {param}"));
+                    }
+                },
+            }
+        }
 
         Ok(source)
     }
 }
 
 impl Source {
+    /// The reference position of the source, from its coordinates or airport
+    pub fn position(&self) -> Option<Position> {
+        self.reference.or_else(|| {
+            let airport = self.airport.as_deref()?;
+            Position::from_str(airport).ok()
+        })
+    }
+
     pub fn serial(&self) -> u64 {
         match &self.address {
             Address::Tcp(name) => build_serial(name),
             Address::Udp(name) => build_serial(name),
             Address::Websocket(name) => build_serial(name),
+            Address::Jet1090(name) => build_serial(name),
             Address::Rtlsdr(reference) => {
                 let name = reference.clone().unwrap_or("rtlsdr".to_string());
                 build_serial(&name)
@@ -160,11 +239,17 @@ impl Source {
      *
      * The next step will be deduplication.
      */
+    #[allow(clippy::too_many_arguments)]
     pub async fn receiver(
         &self,
         tx: Sender<TimedMessage>,
         serial: u64,
         name: Option<String>,
+        framing: beast::FramingStats,
+        #[cfg_attr(not(feature = "rtlsdr"), allow(unused_variables))]
+        samples: SampleStats,
+        timestamps: TimestampStats,
+        report: beast::ReceiverReport,
     ) {
         match &self.address {
             Address::Rtlsdr(args) => {
@@ -175,8 +260,17 @@ impl Source {
                 }
                 #[cfg(feature = "rtlsdr")]
                 {
-                    rtlsdr::receiver::<&str>(tx, args.as_deref(), serial, name)
-                        .await
+                    rtlsdr::receiver::<&str>(
+                        tx,
+                        args.as_deref(),
+                        serial,
+                        name,
+                        self.iq_capture.clone(),
+                        self.thread.clone(),
+                        self.filter.clone(),
+                        samples,
+                    )
+                    .await
                 }
             }
             Address::Sero(sero) => {
@@ -186,7 +280,14 @@ impl Source {
                 }
                 #[cfg(feature = "sero")]
                 {
-                    sero::receiver(sero::SeroClient::from(sero), tx).await
+                    let client = sero::SeroClient::from(sero);
+                    sero::receiver(client, tx, self.filter.clone()).await
+                }
+            }
+            Address::Jet1090(address) => {
+                let filter = self.filter.clone();
+                if let Err(e) = jet1090::receiver(address, tx, filter).await {
+                    error!("{}", e.to_string());
                 }
             }
             _ => {
@@ -198,8 +299,26 @@ impl Source {
                     }
                     _ => unreachable!(),
                 };
-                if let Err(e) =
-                    beast::receiver(server_address, tx, serial, name).await
+                if let Err(e) = beast::receiver(
+                    server_address,
+                    tx,
+                    serial,
+                    name,
+                    beast::StreamSettings {
+                        format: self.format,
+                        idle_timeout: self
+                            .idle_timeout
+                            .map(Duration::from_secs),
+                        filter: self.filter.clone(),
+                    },
+                    framing,
+                    TimestampCheck::new(
+                        self.timestamps.clone().unwrap_or_default(),
+                        timestamps,
+                    ),
+                    report,
+                )
+                .await
                 {
                     error!("{}", e.to_string());
                 }
@@ -218,6 +337,8 @@ pub struct SeroParams {
     pub df_filter: Option<Vec<u32>>,
     /// Filter on messages coming from a set of aircraft (default:all)
     pub aircraft_filter: Option<Vec<u32>>,
+    /// Filter on messages received by a set of sensors (default: all)
+    pub sensor_filter: Option<Vec<u64>>,
 }
 
 #[cfg(feature = "sero")]
@@ -229,6 +350,7 @@ impl From<&SeroParams> for sero::SeroClient {
             token: value.token.clone(),
             df_filter: value.df_filter.clone().unwrap_or_default(),
             aircraft_filter: value.aircraft_filter.clone().unwrap_or_default(),
+            sensor_filter: value.sensor_filter.clone().unwrap_or_default(),
         }
     }
 }
@@ -317,5 +439,71 @@ mod test {
             assert_eq!(pos.latitude, 43.628101);
             assert_eq!(pos.longitude, 1.367263);
         }
+
+        let source = Source::from_str("tcp://1.2.3.4:30002?LFBO&format=avr");
+        assert!(source.is_ok());
+        if let Ok(Source {
+            format, reference, ..
+        }) = source
+        {
+            assert_eq!(format, Some(Format::Avr));
+            assert!(reference.is_some());
+        }
+        assert!(Source::from_str("tcp://1.2.3.4:30002?format=raw").is_err());
+        // Other parameters must not reset the reference
+        let source = Source::from_str("tcp://1.2.3.4:30002?format=avr&LFBO");
+        assert!(source.unwrap().reference.is_some());
+        assert!(Source::from_str("tcp://1.2.3.4:30002?LFBO&foo=1").is_err());
+        assert!(Source::from_str("tcp://1.2.3.4:30002?nowhere").is_err());
+
+        let source = Source::from_str("jet1090://remote:8080").unwrap();
+        assert_eq!(source.address, Address::Jet1090("remote:8080".to_string()));
+        assert!(Source::from_str("jet1090://remote").is_err());
+    }
+
+    #[test]
+    fn test_source_filter() {
+        let source: Source = toml::from_str(
+            r#"
+            tcp = "localhost:10003"
+            latitude = 43.6
+            longitude = 1.4
+            df_filter = [17, 18]
+            tc_exclude = [28, 29]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(source.address, Address::Tcp("localhost:10003".to_string()));
+        assert!(source.reference.is_some());
+        assert_eq!(source.filter.df_filter, Some(vec![17, 18]));
+        assert_eq!(source.filter.tc_exclude, Some(vec![28, 29]));
+        assert!(source.filter.tc_filter.is_none());
+    }
+
+    #[test]
+    fn test_source_airport() {
+        let source: Source = toml::from_str(
+            r#"
+            udp = "0.0.0.0:1234"
+            airport = "LFBO"
+            "#,
+        )
+        .unwrap();
+        assert!(source.reference.is_none());
+        let pos = source.position().unwrap();
+        assert_eq!(pos.latitude, 43.628101);
+        assert_eq!(pos.longitude, 1.367263);
+
+        // Coordinates take precedence over the airport
+        let source: Source = toml::from_str(
+            r#"
+            udp = "0.0.0.0:1234"
+            airport = "LFBO"
+            latitude = 48.723
+            longitude = 2.379
+            "#,
+        )
+        .unwrap();
+        assert_eq!(source.position().unwrap().latitude, 48.723);
     }
 }
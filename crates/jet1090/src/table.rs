@@ -2,14 +2,24 @@ use chrono::prelude::*;
 use ratatui::prelude::*;
 use ratatui::widgets::*;
 use regex::Regex;
+use rs1090::decode::cpr::CprFailure;
+use rs1090::prelude::TimeSource;
 use std::time::{SystemTime, UNIX_EPOCH};
 use style::palette::tailwind;
 
+use crate::class::TargetClass;
+use crate::sensor::{self, HISTORY};
 use crate::snapshot::Snapshot;
 use crate::{Jet1090, SortKey};
+use rs1090::data::patterns::Allocation;
 
-const INFO_TEXT: &str =
-    "(Esc/Q) quit | (↑/K) up | (↓/J) down | (⤒/G) top | (/) search";
+const INFO_TEXT: &str = "(Esc/Q) quit | (↑/K) up | (↓/J) down | (⤒/G) top \
+    | (/) search | (T) aircraft/vehicles | (M) military/special/unallocated \
+    | (S) sensors";
+const SENSOR_INFO_TEXT: &str = "(Esc/Q) quit | (S) aircraft";
+
+/// Characters used to draw sparklines, from the lowest to the highest value
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
 
 /**
  * Rendering of the table in interactive mode
@@ -29,30 +39,36 @@ pub fn build_table(frame: &mut Frame, app: &mut Jet1090) {
     let search_query = app.search_query.to_lowercase().replace("-", "");
     let search_regex =
         Regex::new(&search_query).unwrap_or_else(|_| Regex::new("").unwrap());
-    let filtered_states =
-        states
-            .values()
-            .filter(|sv| {
-                (sv.cur.count > 1)
-                    && (now as i64 - sv.cur.lastseen as i64) < 30
-                    && (sv.cur.callsign.as_ref().is_some_and(|s| {
+    let filtered_states = states
+        .values()
+        .filter(|sv| {
+            (sv.cur.count > 1)
+                && (now as i64 - sv.cur.lastseen as i64) < 30
+                && app.class_filter.is_none_or(|class| sv.cur.class == class)
+                && app
+                    .allocation_filter
+                    .is_none_or(|block| sv.cur.allocation == block)
+                && (sv
+                    .cur
+                    .state
+                    .callsign
+                    .as_ref()
+                    .is_some_and(|s| search_regex.is_match(&s.to_lowercase()))
+                    || search_regex.is_match(&sv.cur.icao24.to_lowercase())
+                    || sv.cur.typecode.as_ref().is_some_and(|s| {
                         search_regex.is_match(&s.to_lowercase())
-                    }) || search_regex
-                        .is_match(&sv.cur.icao24.to_lowercase())
-                        || sv.cur.typecode.as_ref().is_some_and(|s| {
-                            search_regex.is_match(&s.to_lowercase())
-                        })
-                        || sv.cur.registration.as_ref().is_some_and(|s| {
-                            search_regex
-                                .is_match(&s.replace("-", "").to_lowercase())
+                    })
+                    || sv.cur.registration.as_ref().is_some_and(|s| {
+                        search_regex
+                            .is_match(&s.replace("-", "").to_lowercase())
+                    })
+                    || sv.cur.metadata.iter().any(|m| {
+                        m.name.as_ref().is_some_and(|n| {
+                            search_regex.is_match(&n.to_lowercase())
                         })
-                        || sv.cur.metadata.iter().any(|m| {
-                            m.name.as_ref().is_some_and(|n| {
-                                search_regex.is_match(&n.to_lowercase())
-                            })
-                        }))
-            })
-            .collect::<Vec<&StateVectors>>();
+                    }))
+        })
+        .collect::<Vec<&StateVectors>>();
 
     app.items = filtered_states
         .iter()
@@ -70,13 +86,13 @@ pub fn build_table(frame: &mut Frame, app: &mut Jet1090) {
 
     let sort_by = match &app.sort_key {
         SortKey::ALTITUDE => |a: &&StateVectors, b: &&StateVectors| {
-            a.cur.altitude.cmp(&b.cur.altitude)
+            a.cur.state.altitude.cmp(&b.cur.state.altitude)
         },
         SortKey::CALLSIGN => |a: &&StateVectors, b: &&StateVectors| {
-            a.cur.callsign.cmp(&b.cur.callsign)
+            a.cur.state.callsign.cmp(&b.cur.state.callsign)
         },
         SortKey::VRATE => |a: &&StateVectors, b: &&StateVectors| {
-            a.cur.vertical_rate.cmp(&b.cur.vertical_rate)
+            a.cur.state.vertical_rate.cmp(&b.cur.state.vertical_rate)
         },
         SortKey::COUNT => {
             |a: &&StateVectors, b: &&StateVectors| a.cur.count.cmp(&b.cur.count)
@@ -189,10 +205,13 @@ pub fn build_table(frame: &mut Frame, app: &mut Jet1090) {
                     IAS,
                     MACH,
                     VRATE,
+                    PHASE,
                     TRACK,
                     HEADING,
                     ROLL,
                     NACP,
+                    CLASS,
+                    BLOCK,
                     COUNT,
                     REFERENCE,
                     LAST,
@@ -245,7 +264,18 @@ pub fn build_table(frame: &mut Frame, app: &mut Jet1090) {
         )
         .block(
             Block::default()
-                .title_bottom(format!("jet1090 ({} aircraft)", size,))
+                .title_bottom(match (app.class_filter, app.allocation_filter) {
+                    (_, Some(block)) => {
+                        format!("jet1090 ({} aircraft, {} block)", size, block)
+                    }
+                    (Some(TargetClass::Vehicle), None) => {
+                        format!("jet1090 ({} vehicles)", size)
+                    }
+                    (Some(TargetClass::Aircraft), None) => {
+                        format!("jet1090 ({} aircraft only)", size)
+                    }
+                    (None, None) => format!("jet1090 ({} aircraft)", size),
+                })
                 .title_alignment(Alignment::Right)
                 .title_style(Style::new().blue().bold())
                 .padding(Padding::symmetric(1, 0))
@@ -295,6 +325,133 @@ pub fn build_table(frame: &mut Frame, app: &mut Jet1090) {
     }
 }
 
+/**
+ * Rendering of the sensor panel in interactive mode
+ */
+pub fn build_sensor_table(frame: &mut Frame, app: &mut Jet1090) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("SystemTime before unix epoch")
+        .as_secs();
+
+    // Update aircraft counts
+    app.receivers();
+
+    let rects = Layout::vertical([Constraint::Min(5), Constraint::Length(1)])
+        .split(frame.area());
+    let colors = TableColors::new(&tailwind::CYAN);
+
+    let rows = app
+        .sensors
+        .values()
+        .enumerate()
+        .map(|(i, sensor)| {
+            let color = match i % 2 {
+                0 => colors.normal_row_color,
+                _ => colors.alt_row_color,
+            };
+            let last = match sensor.last_timestamp {
+                0 => "".to_string(),
+                ts if now > ts + 5 => format!("{}s ago", now - ts),
+                _ => "".to_string(),
+            };
+            Row::new(vec![
+                sensor
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("{:x}", sensor.serial)),
+                format!("{:.1}", sensor.activity.rate(now, 10)),
+                sensor.aircraft_count.to_string(),
+                last,
+                match sensor.time_source {
+                    Some(TimeSource::Gnss) => "gnss",
+                    Some(TimeSource::Counter) => "counter",
+                    Some(TimeSource::System) => "system",
+                    None => "",
+                }
+                .to_string(),
+                sparkline(&sensor.activity.rssi(now)),
+                sparkline(&sensor.activity.counts(now)),
+            ])
+            .style(Style::new().fg(colors.row_fg).bg(color))
+        })
+        .collect::<Vec<Row<'_>>>();
+
+    let header = [
+        "sensor", "msg/s", "aircraft", "last", "clock", "rssi", "activity",
+    ]
+    .into_iter()
+    .map(Cell::from)
+    .collect::<Row<'_>>()
+    .style(
+        Style::default()
+            .fg(colors.header_fg)
+            .bg(colors.header_bg)
+            .bold(),
+    );
+
+    let constraints = [
+        Constraint::Min(12),
+        Constraint::Length(7),
+        Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Length(7),
+        Constraint::Length(sensor::RSSI_BINS as u16),
+        Constraint::Length(HISTORY as u16),
+    ];
+
+    let table = Table::new(rows, constraints)
+        .column_spacing(2)
+        .header(header)
+        .block(
+            Block::default()
+                .title_bottom(format!(
+                    "jet1090 ({} sensors, rssi from -50 to 0 dBFS, \
+                    activity over {}s)",
+                    app.sensors.len(),
+                    HISTORY
+                ))
+                .title_alignment(Alignment::Right)
+                .title_style(Style::new().blue().bold())
+                .padding(Padding::symmetric(1, 0))
+                .borders(Borders::ALL),
+        )
+        .bg(colors.buffer_bg);
+
+    frame.render_widget(table, rects[0]);
+    frame.render_widget(
+        Paragraph::new(Line::from(SENSOR_INFO_TEXT))
+            .style(Style::new().fg(colors.row_fg).bg(colors.buffer_bg))
+            .centered(),
+        rects[1],
+    );
+}
+
+/// A short code for the reason why the position of an aircraft is missing
+fn failure_code(failure: CprFailure) -> &'static str {
+    match failure {
+        CprFailure::SingleParity => "?1par",
+        CprFailure::PairTooOld => "?old",
+        CprFailure::OutOfOrder => "?order",
+        CprFailure::IncompatiblePair => "?zone",
+        CprFailure::Jump => "?jump",
+        CprFailure::NoReference => "?noref",
+        CprFailure::ReferenceTooFar => "?far",
+    }
+}
+
+/// Render values as a line of block characters, scaled to the maximum value
+fn sparkline(values: &[u64]) -> String {
+    let max = values.iter().max().copied().unwrap_or(0).max(1);
+    values
+        .iter()
+        .map(|&v| match v {
+            0 => ' ',
+            v => BARS[((v * BARS.len() as u64).div_ceil(max) - 1) as usize],
+        })
+        .collect()
+}
+
 /**
  * Style-sheet of the table displayed in interactive mode
  */
@@ -326,7 +483,7 @@ impl TableColors {
 
 trait Render {
     fn cell(&self, snapshot: &Snapshot, now: u64) -> String;
-    fn header(&self, sort_key: &SortKey) -> Cell;
+    fn header(&self, sort_key: &SortKey) -> Cell<'_>;
     fn constraint(&self) -> Constraint;
 }
 
@@ -349,10 +506,13 @@ enum ColumnRender {
     IAS,
     MACH,
     VRATE,
+    PHASE,
     TRACK,
     HEADING,
     ROLL,
     NACP,
+    CLASS,
+    BLOCK,
     COUNT,
     REFERENCE,
     LAST,
@@ -364,24 +524,33 @@ impl Render for ColumnRender {
         match self {
             Self::ICAO24 => s.icao24.to_string(),
             Self::TAIL => s.registration.to_owned().unwrap_or("".to_string()),
-            Self::CALLSIGN => s.callsign.to_owned().unwrap_or("".to_string()),
-            Self::TYPECODE => s.typecode.to_owned().unwrap_or("".to_string()),
-            Self::SQUAWK => {
-                s.squawk.map(|s| s.to_string()).unwrap_or("".to_string())
+            Self::CALLSIGN => {
+                s.state.callsign.to_owned().unwrap_or("".to_string())
             }
-            Self::LATITUDE => s
-                .latitude
-                .map(|v| format!("{}", v))
+            Self::TYPECODE => s.typecode.to_owned().unwrap_or("".to_string()),
+            Self::SQUAWK => s
+                .state
+                .squawk
+                .map(|s| s.to_string())
                 .unwrap_or("".to_string()),
+            Self::LATITUDE => match (s.state.latitude, s.position_failure) {
+                (Some(v), _) => format!("{}", v),
+                // Why the position is missing, for troubleshooting
+                (None, Some(failure)) => failure_code(failure).to_string(),
+                (None, None) => "".to_string(),
+            },
             Self::LONGITUDE => s
+                .state
                 .longitude
                 .map(|v| format!("{}", v))
                 .unwrap_or("".to_string()),
             Self::ALTITUDE => s
+                .state
                 .altitude
                 .map(|v| format!("{}", v))
                 .unwrap_or("".to_string()),
-            Self::SELALT => match (s.selected_altitude, s.altitude) {
+            Self::SELALT => match (s.state.selected_altitude, s.state.altitude)
+            {
                 (Some(sel), Some(alt)) if u16::abs_diff(sel, alt) <= 50 => {
                     "=".to_string()
                 }
@@ -390,36 +559,67 @@ impl Render for ColumnRender {
                 }
                 _ => "".to_string(),
             },
-            Self::GROUNDSPEED => s
-                .groundspeed
+            Self::GROUNDSPEED => match (s.state.groundspeed, s.derived) {
+                // Derived values are marked with a tilde
+                (Some(v), true) => format!("~{}", v),
+                (Some(v), false) => format!("{}", v),
+                (None, _) => "".to_string(),
+            },
+            Self::TAS => s
+                .state
+                .tas
+                .map(|v| format!("{}", v))
+                .unwrap_or("".to_string()),
+            Self::IAS => s
+                .state
+                .ias
+                .map(|v| format!("{}", v))
+                .unwrap_or("".to_string()),
+            Self::MACH => s
+                .state
+                .mach
                 .map(|v| format!("{}", v))
                 .unwrap_or("".to_string()),
-            Self::TAS => {
-                s.tas.map(|v| format!("{}", v)).unwrap_or("".to_string())
-            }
-            Self::IAS => {
-                s.ias.map(|v| format!("{}", v)).unwrap_or("".to_string())
-            }
-            Self::MACH => {
-                s.mach.map(|v| format!("{}", v)).unwrap_or("".to_string())
-            }
             Self::VRATE => s
+                .state
                 .vertical_rate
                 .map(|v| format!("{}", v))
                 .unwrap_or("".to_string()),
-            Self::TRACK => {
-                s.track.map(|v| format!("{}", v)).unwrap_or("".to_string())
+            Self::PHASE => {
+                s.phase.map(|v| format!("{}", v)).unwrap_or("".to_string())
             }
+            Self::TRACK => match (s.state.track, s.derived) {
+                (Some(v), true) => format!("~{}", v),
+                (Some(v), false) => format!("{}", v),
+                (None, _) => "".to_string(),
+            },
             Self::HEADING => s
+                .state
                 .heading
                 .map(|v| format!("{}", v))
                 .unwrap_or("".to_string()),
-            Self::ROLL => {
-                s.roll.map(|v| format!("{}", v)).unwrap_or("".to_string())
-            }
-            Self::NACP => {
-                s.nacp.map(|v| format!("{}", v)).unwrap_or("".to_string())
-            }
+            Self::ROLL => s
+                .state
+                .roll
+                .map(|v| format!("{}", v))
+                .unwrap_or("".to_string()),
+            Self::NACP => s
+                .state
+                .nacp
+                .map(|v| format!("{}", v))
+                .unwrap_or("".to_string()),
+            Self::CLASS => match s.class {
+                // Aircraft without ADS-B, only seen in Mode S replies
+                TargetClass::Aircraft if s.mode_s_only => "mds".to_string(),
+                TargetClass::Aircraft => "".to_string(),
+                TargetClass::Vehicle => "veh".to_string(),
+            },
+            Self::BLOCK => match s.allocation {
+                Allocation::Civil => "".to_string(),
+                Allocation::Military => "mil".to_string(),
+                Allocation::Special => "spc".to_string(),
+                Allocation::Unallocated => "una".to_string(),
+            },
             Self::COUNT => s.count.to_string(),
             Self::REFERENCE => s
                 .metadata
@@ -443,7 +643,7 @@ impl Render for ColumnRender {
         }
     }
 
-    fn header(&self, sort_key: &SortKey) -> Cell {
+    fn header(&self, sort_key: &SortKey) -> Cell<'_> {
         match self {
             ColumnRender::ICAO24 => Cell::from("icao24".to_string()),
             ColumnRender::TAIL => Cell::from("tail".to_string()),
@@ -477,10 +677,13 @@ impl Render for ColumnRender {
                 }
                 c
             }
+            ColumnRender::PHASE => Cell::from("phase".to_string()),
             ColumnRender::TRACK => Cell::from("trk".to_string()),
             ColumnRender::HEADING => Cell::from("hdg".to_string()),
             ColumnRender::ROLL => Cell::from("roll".to_string()),
             ColumnRender::NACP => Cell::from("nac".to_string()),
+            ColumnRender::CLASS => Cell::from("cls".to_string()),
+            ColumnRender::BLOCK => Cell::from("blk".to_string()),
             ColumnRender::COUNT => Cell::from("count".to_string()),
             ColumnRender::REFERENCE => Cell::from("ref".to_string()),
             ColumnRender::LAST => {
@@ -515,10 +718,13 @@ impl Render for ColumnRender {
             ColumnRender::IAS => Constraint::Length(3),
             ColumnRender::MACH => Constraint::Length(4),
             ColumnRender::VRATE => Constraint::Length(5),
+            ColumnRender::PHASE => Constraint::Length(7),
             ColumnRender::TRACK => Constraint::Length(5),
             ColumnRender::HEADING => Constraint::Length(5),
             ColumnRender::ROLL => Constraint::Length(5),
             ColumnRender::NACP => Constraint::Length(3),
+            ColumnRender::CLASS => Constraint::Length(3),
+            ColumnRender::BLOCK => Constraint::Length(3),
             ColumnRender::COUNT => Constraint::Length(8),
             ColumnRender::REFERENCE => Constraint::Length(8),
             ColumnRender::LAST => Constraint::Length(7),
@@ -0,0 +1,201 @@
+use serde::Serialize;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{error, info, info_span, warn, Instrument};
+
+/// Polls longer than this block a worker thread of the async runtime
+const SLOW_POLL: Duration = Duration::from_millis(100);
+
+/// All the long-lived tasks, in the order they were started
+static TASKS: Mutex<Vec<Arc<Activity>>> = Mutex::new(Vec::new());
+
+/// The activity of a long-lived task, updated at each poll
+#[derive(Debug)]
+struct Activity {
+    name: String,
+    start: Instant,
+    polls: AtomicU64,
+    /// Time spent polling the task, in ns
+    busy: AtomicU64,
+    /// The longest poll, in ns
+    max_poll: AtomicU64,
+    /// The end of the last poll, in ns since the start
+    last_poll: AtomicU64,
+    finished: AtomicBool,
+}
+
+impl Activity {
+    fn new(name: &str) -> Self {
+        Activity {
+            name: name.to_string(),
+            start: Instant::now(),
+            polls: AtomicU64::new(0),
+            busy: AtomicU64::new(0),
+            max_poll: AtomicU64::new(0),
+            last_poll: AtomicU64::new(0),
+            finished: AtomicBool::new(false),
+        }
+    }
+
+    fn record(&self, elapsed: Duration) {
+        let nanos = elapsed.as_nanos() as u64;
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.busy.fetch_add(nanos, Ordering::Relaxed);
+        self.max_poll.fetch_max(nanos, Ordering::Relaxed);
+        let since_start = self.start.elapsed().as_nanos() as u64;
+        self.last_poll.store(since_start, Ordering::Relaxed);
+    }
+
+    fn status(&self) -> TaskStatus {
+        let last_poll = self.last_poll.load(Ordering::Relaxed);
+        let idle =
+            (self.start.elapsed().as_nanos() as u64).saturating_sub(last_poll);
+        TaskStatus {
+            name: self.name.clone(),
+            polls: self.polls.load(Ordering::Relaxed),
+            busy_ms: self.busy.load(Ordering::Relaxed) / 1_000_000,
+            max_poll_ms: self.max_poll.load(Ordering::Relaxed) / 1_000_000,
+            idle_s: (idle / 1_000_000) as f64 / 1000.,
+            finished: self.finished.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The state of a long-lived task
+#[derive(Debug, Serialize)]
+pub struct TaskStatus {
+    /// The name of the task, as in its span
+    pub name: String,
+    /// Number of times the task was polled
+    pub polls: u64,
+    /// Time spent polling the task, in ms
+    pub busy_ms: u64,
+    /// The longest poll of the task (in ms), blocking the runtime meanwhile
+    pub max_poll_ms: u64,
+    /// Time since the task was last polled (in s), e.g. a source not
+    /// receiving anything or a sink waiting for its server
+    pub idle_s: f64,
+    /// True if the task has stopped
+    pub finished: bool,
+}
+
+/// A future recording the activity of the task polling it
+struct Watched<F> {
+    future: Pin<Box<F>>,
+    activity: Arc<Activity>,
+}
+
+impl<F: Future> Future for Watched<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let start = Instant::now();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.future.as_mut().poll(cx)
+        }));
+        let elapsed = start.elapsed();
+        let activity = &self.activity;
+        activity.record(elapsed);
+        let result = match result {
+            Ok(result) => result,
+            Err(payload) => {
+                activity.finished.store(true, Ordering::Relaxed);
+                error!("{} task panicked", activity.name);
+                panic::resume_unwind(payload)
+            }
+        };
+        if elapsed > SLOW_POLL {
+            warn!(
+                "{} task blocked the runtime for {} ms",
+                activity.name,
+                elapsed.as_millis()
+            );
+        }
+        if result.is_ready() {
+            activity.finished.store(true, Ordering::Relaxed);
+            info!("{} task stopped", activity.name);
+        }
+        result
+    }
+}
+
+/**
+ * Follow the activity of a long-lived future, in a span named after it.
+ *
+ * Log messages of the future come with the name of the task, and its
+ * activity is served by the REST API (see [`status`]), so that a stalled
+ * source or a blocked sink can be spotted in production.
+ */
+pub fn watch<F: Future>(
+    name: &str,
+    future: F,
+) -> impl Future<Output = F::Output> {
+    let activity = Arc::new(Activity::new(name));
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push(activity.clone());
+    }
+    let span = info_span!("task", name = name);
+    Watched {
+        future: Box::pin(future),
+        activity,
+    }
+    .instrument(span)
+}
+
+/// Start a long-lived task, followed with [`watch`]
+pub fn spawn<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    // Named in tokio-console as well
+    #[cfg(all(feature = "console", tokio_unstable))]
+    return tokio::task::Builder::new()
+        .name(name)
+        .spawn(watch(name, future))
+        .expect("failed to spawn task");
+    #[cfg(not(all(feature = "console", tokio_unstable)))]
+    tokio::spawn(watch(name, future))
+}
+
+/// The state of all the long-lived tasks, in the order they were started
+pub fn status() -> Vec<TaskStatus> {
+    match TASKS.lock() {
+        Ok(tasks) => tasks.iter().map(|activity| activity.status()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tasks() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<u32>(1);
+        let consumer = spawn("test_consumer", async move {
+            let mut total = 0;
+            while let Some(value) = rx.recv().await {
+                total += value;
+            }
+            total
+        });
+        for value in 1..=3 {
+            tx.send(value).await.unwrap();
+        }
+        drop(tx);
+        assert_eq!(consumer.await.unwrap(), 6);
+
+        let status = status();
+        let task = status.iter().find(|t| t.name == "test_consumer").unwrap();
+        assert!(task.finished);
+        assert!(task.polls >= 2);
+        assert!(task.max_poll_ms < SLOW_POLL.as_millis() as u64);
+    }
+}
@@ -0,0 +1,22 @@
+//! Helpers shared by the tests of several modules
+
+use rs1090::prelude::*;
+
+/// A message received by a sensor at a time (in s), from its hex frame
+pub fn timed(frame: &str, secs: f64, serial: u64) -> TimedMessage {
+    let timestamp = Timestamp::from_secs_f64(secs);
+    let frame = hex::decode(frame).unwrap();
+    let mut msg = TimedMessage::from_frame(timestamp, &frame);
+    msg.metadata.push(SensorMetadata {
+        system_timestamp: timestamp,
+        gnss_timestamp: None,
+        nanoseconds: None,
+        rssi: None,
+        serial,
+        name: None,
+        time_source: None,
+        clock_error: None,
+        timestamp_error: None,
+    });
+    msg
+}
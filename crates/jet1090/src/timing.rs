@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+
+use rs1090::geom::{ecef, FT};
+use rs1090::prelude::*;
+use serde::Serialize;
+
+/// Speed of light, in m/s
+const LIGHT: f64 = 299_792_458.;
+
+/// The ECEF coordinates of a sensor, at the ground level if its altitude is
+/// unknown
+pub fn site(reference: &Position, altitude: Option<f64>) -> [f64; 3] {
+    ecef(
+        reference.latitude,
+        reference.longitude,
+        altitude.unwrap_or_default(),
+    )
+}
+
+/// The residuals of a pair of sensors (running mean and variance)
+#[derive(Debug, Clone, Default)]
+struct Residuals {
+    count: u64,
+    mean: f64,
+    /// Sum of squared differences from the mean (Welford's algorithm)
+    m2: f64,
+    last: f64,
+}
+
+impl Residuals {
+    fn add(&mut self, residual: f64) {
+        self.count += 1;
+        let delta = residual - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (residual - self.mean);
+        self.last = residual;
+    }
+
+    fn std(&self) -> f64 {
+        match self.count {
+            0 | 1 => 0.,
+            count => (self.m2 / (count - 1) as f64).sqrt(),
+        }
+    }
+}
+
+/// The timing residuals of a pair of sensors, as served on the REST API
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PairStats {
+    /// The serial numbers of the sensors, in increasing order
+    pub sensors: [u64; 2],
+    pub names: [Option<String>; 2],
+    /// Number of frames received by both sensors
+    pub count: u64,
+    /// Mean residual (in µs): a constant offset between the clocks, or a
+    /// wrong position of one of the sensors
+    pub mean: f64,
+    /// Standard deviation of the residuals (in µs): the timestamping jitter
+    pub std: f64,
+    /// The last residual (in µs)
+    pub last: f64,
+}
+
+/**
+ * Validate the positions and timestamps of sensors receiving the same
+ * frames.
+ *
+ * When two sensors with known positions receive the same airborne position
+ * (DF17, BDS 0,5), the difference between their arrival times should match
+ * the difference of the distances to the aircraft, divided by the speed of
+ * light. The residual (observed minus expected difference) is accumulated
+ * for each pair of sensors.
+ *
+ * A mean residual far from zero points to a clock offset, or to a wrong
+ * position of a sensor; the standard deviation measures the quality of the
+ * timestamps. Aircraft are located with their barometric altitude, which
+ * adds less than a microsecond of error. GNSS timestamps are used when
+ * available: residuals between system clocks are in the order of the
+ * millisecond. Only messages with the metadata of all the sensors (i.e.
+ * with the `merge-all` deduplication strategy) are compared.
+ */
+#[derive(Debug, Default)]
+pub struct Timing {
+    pairs: BTreeMap<(u64, u64), Residuals>,
+}
+
+impl Timing {
+    /// Record the arrival times of a message at the sensors with a position
+    pub fn record(
+        &mut self,
+        msg: &TimedMessage,
+        site: impl Fn(u64) -> Option<[f64; 3]>,
+    ) {
+        if msg.metadata.len() < 2 {
+            return;
+        }
+        let Some(Message {
+            df: ExtendedSquitterADSB(adsb),
+            ..
+        }) = &msg.message
+        else {
+            return;
+        };
+        let ME::BDS05(AirbornePosition {
+            latitude: Some(latitude),
+            longitude: Some(longitude),
+            alt: Some(altitude),
+            ..
+        }) = adsb.message
+        else {
+            return;
+        };
+        let aircraft = ecef(latitude, longitude, altitude as f64 * FT);
+        let arrivals: Vec<_> = msg
+            .metadata
+            .iter()
+            .filter_map(|meta| {
+                let time = meta.gnss_timestamp.unwrap_or(meta.system_timestamp);
+                Some((meta.serial, time, site(meta.serial)?))
+            })
+            .collect();
+        self.record_arrivals(&aircraft, &arrivals);
+    }
+
+    /// Record the arrival times of a frame sent from a position (ECEF)
+    fn record_arrivals(
+        &mut self,
+        aircraft: &[f64; 3],
+        arrivals: &[(u64, Timestamp, [f64; 3])],
+    ) {
+        let delay = |site: &[f64; 3]| {
+            let distance = (0..3)
+                .map(|i| (aircraft[i] - site[i]).powi(2))
+                .sum::<f64>()
+                .sqrt();
+            distance / LIGHT
+        };
+        for (i, (a, time_a, site_a)) in arrivals.iter().enumerate() {
+            for (b, time_b, site_b) in &arrivals[i + 1..] {
+                if a == b {
+                    continue;
+                }
+                // Pairs are ordered by serial number
+                let ((a, time_a, site_a), (b, time_b, site_b)) = match a < b {
+                    true => ((a, time_a, site_a), (b, time_b, site_b)),
+                    false => ((b, time_b, site_b), (a, time_a, site_a)),
+                };
+                let observed = (time_a.0 - time_b.0) as f64 * 1e-9;
+                let expected = delay(site_a) - delay(site_b);
+                self.pairs
+                    .entry((*a, *b))
+                    .or_default()
+                    .add((observed - expected) * 1e6);
+            }
+        }
+    }
+
+    /// The residuals of all the pairs of sensors, with their names
+    pub fn stats(
+        &self,
+        name: impl Fn(u64) -> Option<String>,
+    ) -> Vec<PairStats> {
+        self.pairs
+            .iter()
+            .map(|(&(a, b), residuals)| PairStats {
+                sensors: [a, b],
+                names: [name(a), name(b)],
+                count: residuals.count,
+                mean: residuals.mean,
+                std: residuals.std(),
+                last: residuals.last,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timing() {
+        let position = |latitude, longitude| Position {
+            latitude,
+            longitude,
+        };
+        let toulouse = site(&position(43.63, 1.37), Some(150.));
+        let blagnac = site(&position(43.66, 1.45), None);
+        let aircraft = ecef(43.8, 1.6, 10_000.);
+        let delay = |site: &[f64; 3]| {
+            let d = (0..3).map(|i| (aircraft[i] - site[i]).powi(2));
+            (d.sum::<f64>().sqrt() / LIGHT * 1e9) as i64
+        };
+
+        let mut timing = Timing::default();
+        let t0 = 1_700_000_000_000_000_000;
+        for (i, jitter) in [0, 100, -100].into_iter().enumerate() {
+            let t = t0 + i as i64 * 1_000_000_000;
+            // The clock of the second sensor is 2 µs late
+            let arrivals = [
+                (2, Timestamp(t + delay(&blagnac) + 2_000 + jitter), blagnac),
+                (1, Timestamp(t + delay(&toulouse)), toulouse),
+            ];
+            timing.record_arrivals(&aircraft, &arrivals);
+        }
+
+        let stats = timing.stats(|serial| Some(serial.to_string()));
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].sensors, [1, 2]);
+        assert_eq!(stats[0].count, 3);
+        assert!((stats[0].mean + 2.).abs() < 0.01);
+        assert!((stats[0].std - 0.1).abs() < 0.01);
+        assert!((stats[0].last + 1.9).abs() < 0.01);
+    }
+}
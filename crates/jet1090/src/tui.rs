@@ -6,6 +6,8 @@ use ratatui::prelude::*;
 use std::io::{self, stdout, Stdout};
 use tokio::sync::mpsc;
 
+use crate::tasks;
+
 /// A type alias for the terminal type used in this application
 pub type Tui = Terminal<CrosstermBackend<Stdout>>;
 
@@ -45,7 +47,7 @@ impl EventHandler {
         let _tx = tx.clone();
         let mut width = width;
 
-        let _task = tokio::spawn(async move {
+        let _task = tasks::spawn("terminal events", async move {
             let mut reader = crossterm::event::EventStream::new();
             let mut interval = tokio::time::interval(tick_rate);
             loop {
@@ -56,10 +58,8 @@ impl EventHandler {
                     match maybe_event {
                       Some(Ok(evt)) => {
                         match evt {
-                          crossterm::event::Event::Key(key) => {
-                            if key.kind == crossterm::event::KeyEventKind::Press {
-                              tx.send(Event::Key(key)).unwrap();
-                            }
+                          crossterm::event::Event::Key(key) if key.kind == crossterm::event::KeyEventKind::Press => {
+                            tx.send(Event::Key(key)).unwrap();
                           },
                           crossterm::event::Event::Resize(col,_) => {width = col},
                           crossterm::event::Event::Mouse(event) => {
@@ -94,8 +94,9 @@ impl EventHandler {
     }
 
     pub async fn next(&mut self) -> Result<Event, io::Error> {
-        self.rx.recv().await.ok_or_else(|| {
-            io::Error::new(io::ErrorKind::Other, "Unable to get event")
-        })
+        self.rx
+            .recv()
+            .await
+            .ok_or_else(|| io::Error::other("Unable to get event"))
     }
 }
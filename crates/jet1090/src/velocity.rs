@@ -0,0 +1,163 @@
+/// Minimum time (in seconds) between two positions to derive a velocity
+const MIN_INTERVAL: f64 = 2.;
+/// Maximum time (in seconds) between two positions to derive a velocity
+const MAX_INTERVAL: f64 = 60.;
+/// Weight of the latest estimate in the exponential smoothing
+const SMOOTHING: f64 = 0.5;
+/// Duration (in seconds) a measured velocity takes precedence
+const MEASURED_TIMEOUT: f64 = 30.;
+/// Maximum age (in seconds) of the derived velocity to check measured ones
+const CHECK_INTERVAL: f64 = 10.;
+/// Difference (in kts) between measured and derived velocities tolerated
+const TOLERANCE: f64 = 50.;
+/// Tolerated difference, relative to the measured ground speed (for fast
+/// aircraft, as derived velocities lag behind in turns)
+const RELATIVE_TOLERANCE: f64 = 0.3;
+
+/**
+ * Derive the ground speed and track angle from successive positions.
+ *
+ * Some targets (e.g. TIS-B or MLAT-fed aircraft) never send any velocity
+ * message. Velocities are estimated between positions at least
+ * [`MIN_INTERVAL`] seconds apart, then smoothed. Estimates are only
+ * returned if no velocity was measured during the last
+ * [`MEASURED_TIMEOUT`] seconds.
+ */
+#[derive(Debug, Default)]
+pub struct VelocityEstimator {
+    /// The position the next estimate is computed from (timestamp, lat, lon)
+    anchor: Option<(f64, f64, f64)>,
+    /// The smoothed north and east velocity components, in knots
+    velocity: Option<(f64, f64)>,
+    /// The timestamp of the last measured velocity
+    measured: Option<f64>,
+}
+
+impl VelocityEstimator {
+    /// Record that a velocity was measured (e.g. in a BDS 0,9 message)
+    pub fn measured(&mut self, timestamp: f64) {
+        self.measured = Some(timestamp);
+    }
+
+    /**
+     * Compare a measured velocity (e.g. in a BDS 0,9 message) with the
+     * velocity derived from recent positions.
+     *
+     * Returns the derived ground speed (in kts) and track angle (in degrees)
+     * if they differ from the measured ones beyond the tolerance, e.g. for
+     * corrupted (but CRC-valid) messages or spoofed tracks.
+     */
+    pub fn inconsistent(
+        &self,
+        timestamp: f64,
+        groundspeed: f64,
+        track: f64,
+    ) -> Option<(f64, f64)> {
+        let (ts, _, _) = self.anchor?;
+        if !(0. ..=CHECK_INTERVAL).contains(&(timestamp - ts)) {
+            return None;
+        }
+        let (vn, ve) = self.velocity?;
+        let track_rad = track.to_radians();
+        let (mn, me) =
+            (groundspeed * track_rad.cos(), groundspeed * track_rad.sin());
+        let difference = (vn - mn).hypot(ve - me);
+        let tolerance = TOLERANCE.max(RELATIVE_TOLERANCE * groundspeed);
+        (difference > tolerance).then(|| {
+            let derived = ve.atan2(vn).to_degrees().rem_euclid(360.);
+            (vn.hypot(ve), derived)
+        })
+    }
+
+    /**
+     * Update the estimator with a new position.
+     *
+     * Returns the derived ground speed (in kts) and track angle (in degrees),
+     * if available and if no velocity was measured recently.
+     */
+    pub fn update(
+        &mut self,
+        timestamp: f64,
+        latitude: f64,
+        longitude: f64,
+    ) -> Option<(f64, f64)> {
+        let Some((ts, lat, lon)) = self.anchor else {
+            self.anchor = Some((timestamp, latitude, longitude));
+            return None;
+        };
+        let dt = timestamp - ts;
+        if !(0. ..=MAX_INTERVAL).contains(&dt) {
+            self.anchor = Some((timestamp, latitude, longitude));
+            self.velocity = None;
+            return None;
+        }
+        if dt >= MIN_INTERVAL {
+            // Equirectangular approximation, in nautical miles
+            let dn = (latitude - lat) * 60.;
+            let de = (longitude - lon)
+                * 60.
+                * ((latitude + lat) / 2.).to_radians().cos();
+            let (vn, ve) = (dn / dt * 3600., de / dt * 3600.);
+            self.velocity = Some(match self.velocity {
+                Some((n, e)) => (
+                    SMOOTHING * vn + (1. - SMOOTHING) * n,
+                    SMOOTHING * ve + (1. - SMOOTHING) * e,
+                ),
+                None => (vn, ve),
+            });
+            self.anchor = Some((timestamp, latitude, longitude));
+        }
+        if self
+            .measured
+            .is_some_and(|m| timestamp - m < MEASURED_TIMEOUT)
+        {
+            return None;
+        }
+        let (vn, ve) = self.velocity?;
+        let groundspeed = vn.hypot(ve);
+        let track = ve.atan2(vn).to_degrees().rem_euclid(360.);
+        Some((
+            (groundspeed * 10.).round() / 10.,
+            (track * 10.).round() / 10.,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_velocity() {
+        let mut estimator = VelocityEstimator::default();
+        // Eastbound along the equator, 0.05° (3 nm) in 30 seconds
+        assert_eq!(estimator.update(0., 0., 0.), None);
+        assert_eq!(estimator.update(1., 0., 0.001), None);
+        let (groundspeed, track) = estimator.update(30., 0., 0.05).unwrap();
+        assert_eq!(groundspeed, 360.);
+        assert_eq!(track, 90.);
+
+        // Measured velocities take precedence
+        estimator.measured(40.);
+        assert_eq!(estimator.update(60., 0., 0.1), None);
+        assert!(estimator.update(90., 0., 0.15).is_some());
+
+        // Estimates are reset after a gap
+        assert_eq!(estimator.update(200., 0., 0.3), None);
+    }
+
+    #[test]
+    fn test_inconsistent() {
+        let mut estimator = VelocityEstimator::default();
+        // Eastbound at 360 kts
+        estimator.update(0., 0., 0.);
+        estimator.update(30., 0., 0.05);
+        assert_eq!(estimator.inconsistent(32., 380., 95.), None);
+        let (groundspeed, track) =
+            estimator.inconsistent(32., 360., 180.).unwrap();
+        assert_eq!((groundspeed.round(), track.round()), (360., 90.));
+        assert!(estimator.inconsistent(32., 120., 90.).is_some());
+        // Derived velocities too old are not compared
+        assert_eq!(estimator.inconsistent(60., 360., 180.), None);
+    }
+}
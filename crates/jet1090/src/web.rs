@@ -1,23 +1,107 @@
 /**
  * Information returned on a REST API
  */
+use futures_util::{SinkExt, StreamExt};
+use rs1090::czml::Czml;
+use rs1090::data::patterns::{aircraft_information, Allocation};
+use rs1090::prelude::Timestamp;
+use rs1090::source::jet1090;
+use rs1090::source::sensor::SensorSettings;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::convert::Infallible;
 use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::sync::Mutex;
+use tracing::warn;
+use warp::http::header::{CONTENT_TYPE, ETAG};
 use warp::http::StatusCode;
+use warp::path::FullPath;
 use warp::reject::Rejection;
-use warp::reply::Reply;
+use warp::reply::{with_header, Reply, Response};
+use warp::ws::{self, WebSocket};
 
+use crate::aircraftdb::Aircraft;
+use crate::class::TargetClass;
+use crate::event::Event;
+use crate::latency;
+use crate::priority::PriorityQueue;
+use crate::reload::Reloader;
+use crate::report::Report;
+use crate::sink::RedisStatus;
 use crate::snapshot::Snapshot;
 use crate::Jet1090;
 
 /// Information required to ask for a trajectory
 #[derive(Serialize, Deserialize)]
 pub struct TrackQuery {
-    icao24: String,
+    icao24: Option<String>,
+    /// All the segments of a track, with the track_association option
+    track_id: Option<String>,
 }
 
+/// Optional parameters for the CZML export
+#[derive(Serialize, Deserialize)]
+pub struct CzmlQuery {
+    /// Only export the trajectory of this aircraft
+    icao24: Option<String>,
+    /// Only export the positions received after this timestamp (in s)
+    since: Option<f64>,
+    /// Only export the positions received before this timestamp (in s)
+    until: Option<f64>,
+}
+
+/// Optional parameters for the KML network link
+#[derive(Serialize, Deserialize)]
+pub struct KmlQuery {
+    /// Refresh interval (in s) of the live traffic in Google Earth
+    refresh: Option<u64>,
+}
+
+/// Optional parameters for the state vectors
+#[derive(Serialize, Deserialize)]
+pub struct AllQuery {
+    /// Only return aircraft updated after this snapshot version
+    changed_since: Option<u64>,
+    /// Only return aircraft, or surface vehicles
+    class: Option<TargetClass>,
+    /// Only return aircraft with an address in a kind of block
+    allocation: Option<Allocation>,
+}
+
+impl AllQuery {
+    /// The snapshot version, followed by the parameters changing the reply
+    fn etag(&self, version: u64) -> String {
+        let mut tag = version.to_string();
+        if let Some(since) = self.changed_since {
+            tag.push_str(&format!(";changed_since={}", since));
+        }
+        if let Some(class) = self.class {
+            tag.push_str(&format!(";class={}", class));
+        }
+        if let Some(allocation) = self.allocation {
+            tag.push_str(&format!(";allocation={}", allocation));
+        }
+        format!("\"{}\"", tag)
+    }
+}
+
+/// Incremental update of the state vectors since a given snapshot version
+#[derive(Serialize)]
+struct Changes<'a> {
+    /// The current snapshot version, to pass in the next query
+    version: u64,
+    /// True if the version was too old to compute a diff: clear all aircraft
+    full: bool,
+    /// The aircraft updated since the requested version
+    aircraft: Vec<&'a Snapshot>,
+    /// The aircraft removed since the requested version
+    removed: Vec<String>,
+}
+
+/// Maximum number of aircraft in a batch query
+pub const MAX_AIRCRAFT: usize = 1000;
+
 /// An API error serializable to JSON
 #[derive(Serialize)]
 struct ErrorMessage {
@@ -33,28 +117,171 @@ pub async fn icao24(
     Ok::<_, Infallible>(warp::reply::json(&app.items))
 }
 
-/// Returns all state vectors without any history information
+/**
+ * Returns all state vectors without any history information
+ *
+ * The ETag header carries the snapshot version (followed by the query
+ * parameters, if any, so that a full list and a diff never share a tag):
+ * clients may send it back in an If-None-Match header and get a 304 Not
+ * Modified reply if nothing changed. With the `changed_since` parameter,
+ * only the aircraft updated (or removed) after that version are returned.
+ * With the `class` parameter, only aircraft or surface vehicles are
+ * returned; with the `allocation` parameter, only aircraft with an address
+ * in a kind of block (e.g. military) are returned.
+ */
 pub async fn all(
     app: &Arc<Mutex<Jet1090>>,
-) -> Result<warp::reply::Json, Infallible> {
+    q: AllQuery,
+    if_none_match: Option<String>,
+) -> Result<Response, Infallible> {
     let app = app.lock().await;
-    Ok::<_, Infallible>(warp::reply::json(
-        &app.state_vectors
-            .values()
-            .map(|sv| &sv.cur)
-            .collect::<Vec<&Snapshot>>(),
-    ))
+    let version = app.version;
+    let etag = q.etag(version);
+
+    if if_none_match.is_some_and(|tag| {
+        tag.split(',').any(|t| t.trim() == etag || t.trim() == "*")
+    }) {
+        return Ok(
+            with_header(StatusCode::NOT_MODIFIED, ETAG, etag).into_response()
+        );
+    }
+
+    // None if the version is too old to compute a diff
+    let removed = q.changed_since.map(|since| {
+        app.removals
+            .after(since)
+            .map(|removed| removed.into_iter().cloned().collect::<Vec<_>>())
+    });
+    let since = match removed {
+        Some(Some(_)) => q.changed_since,
+        _ => None,
+    };
+    // Published snapshots are consistent, and serialized out of the lock
+    let aircraft: Vec<Arc<Snapshot>> = app
+        .state_vectors
+        .values()
+        .filter(|sv| since.is_none_or(|since| sv.version > since))
+        .map(|sv| sv.published.clone())
+        .filter(|snapshot| q.class.is_none_or(|class| snapshot.class == class))
+        .filter(|snapshot| {
+            q.allocation
+                .is_none_or(|block| snapshot.allocation == block)
+        })
+        .collect();
+    drop(app);
+    let aircraft: Vec<&Snapshot> = aircraft.iter().map(Arc::as_ref).collect();
+
+    let reply = match removed {
+        None => warp::reply::json(&aircraft),
+        Some(removed) => warp::reply::json(&Changes {
+            version,
+            full: removed.is_none(),
+            aircraft,
+            removed: removed.unwrap_or_default(),
+        }),
+    };
+    Ok(with_header(reply, ETAG, etag).into_response())
 }
 
-/// Returns the trajectory of a given aircraft matching the REST query
+/// Header with the reason why the last position of an aircraft is missing
+pub const POSITION_FAILURE: &str = "x-position-failure";
+
+/**
+ * Returns the trajectory of a given aircraft matching the REST query
+ *
+ * If the last position message of the aircraft could not be decoded, the
+ * reason comes in the `x-position-failure` header (e.g. `single_parity`).
+ * With a `track_id`, the messages of all the segments of the track are
+ * returned in timestamp order.
+ */
 pub async fn track(
     app: &Arc<Mutex<Jet1090>>,
     q: TrackQuery,
+) -> Result<Response, Infallible> {
+    let app = app.lock().await;
+    if let Some(track_id) = &q.track_id {
+        let mut hist: Vec<_> = app
+            .state_vectors
+            .values()
+            .filter(|sv| sv.cur.track_id.as_ref() == Some(track_id))
+            .flat_map(|sv| &sv.hist)
+            .collect();
+        hist.sort_by_key(|msg| msg.timestamp);
+        return Ok(warp::reply::json(&hist).into_response());
+    }
+    let sv = q.icao24.and_then(|icao24| app.state_vectors.get(&icao24));
+    let reply = warp::reply::json(&sv.map(|sv| &sv.hist));
+    let failure = sv
+        .and_then(|sv| sv.published.position_failure)
+        .and_then(|failure| serde_json::to_value(failure).ok())
+        .and_then(|value| value.as_str().map(String::from));
+    match failure {
+        Some(failure) => {
+            Ok(with_header(reply, POSITION_FAILURE, failure).into_response())
+        }
+        None => Ok(reply.into_response()),
+    }
+}
+
+/**
+ * Returns a CZML document with the trajectories of aircraft, for 3D replay
+ * in CesiumJS
+ *
+ * Trajectories are built from the history of aircraft, so the
+ * `history_expire` option limits what can be exported.
+ */
+pub async fn czml(
+    app: &Arc<Mutex<Jet1090>>,
+    q: CzmlQuery,
 ) -> Result<warp::reply::Json, Infallible> {
     let app = app.lock().await;
-    Ok::<_, Infallible>(warp::reply::json(
-        &app.state_vectors.get(&q.icao24).map(|sv| &sv.hist),
-    ))
+    let since = q.since.map(Timestamp::from_secs_f64);
+    let until = q.until.map(Timestamp::from_secs_f64);
+    let mut czml = Czml::new("jet1090").with_window(since, until);
+    let aircraft = app
+        .state_vectors
+        .iter()
+        .filter(|(icao24, _)| q.icao24.as_ref().is_none_or(|q| q == *icao24));
+    for (_, sv) in aircraft {
+        sv.hist.iter().for_each(|msg| czml.push(msg));
+    }
+    Ok::<_, Infallible>(warp::reply::json(&czml.document()))
+}
+
+/// Default refresh interval (in s) of the KML network link
+const KML_REFRESH: u64 = 5;
+const KML_CONTENT_TYPE: &str = "application/vnd.google-earth.kml+xml";
+
+/**
+ * Returns a KML network link to open in Google Earth, which refreshes the
+ * live traffic (see [`kml_live`]) on an interval
+ */
+pub async fn kml(
+    host: Option<String>,
+    q: KmlQuery,
+) -> Result<Response, Infallible> {
+    let host = host.unwrap_or_else(|| "localhost".to_string());
+    let href = format!("http://{host}/kml/live");
+    let refresh = q.refresh.unwrap_or(KML_REFRESH).max(1);
+    let kml = crate::kml::network_link(&href, refresh);
+    Ok(with_header(kml, CONTENT_TYPE, KML_CONTENT_TYPE).into_response())
+}
+
+/// Returns a KML document with the aircraft and the sensors, with range rings
+pub async fn kml_live(
+    app: &Arc<Mutex<Jet1090>>,
+) -> Result<Response, Infallible> {
+    let app = app.lock().await;
+    let sensors = app.sensors.values().filter_map(|sensor| {
+        let name = match &sensor.name {
+            Some(name) => name.clone(),
+            None => sensor.serial.to_string(),
+        };
+        Some((name, sensor.reference?))
+    });
+    let aircraft = app.state_vectors.values().map(|sv| &sv.cur);
+    let kml = crate::kml::document(aircraft, sensors);
+    Ok(with_header(kml, CONTENT_TYPE, KML_CONTENT_TYPE).into_response())
 }
 
 /// Returns decoding information about all sensors
@@ -65,6 +292,305 @@ pub async fn sensors(
     Ok::<_, Infallible>(warp::reply::json(&app.sensors))
 }
 
+/// Returns the history of reception statistics, in buckets of 5 minutes
+pub async fn graphs(
+    app: &Arc<Mutex<Jet1090>>,
+) -> Result<warp::reply::Json, Infallible> {
+    let app = app.lock().await;
+    Ok::<_, Infallible>(warp::reply::json(app.graphs.buckets()))
+}
+
+/// Returns the counters of messages and airframes since the first start
+pub async fn lifetime(
+    app: &Arc<Mutex<Jet1090>>,
+) -> Result<warp::reply::Json, Infallible> {
+    let app = app.lock().await;
+    Ok::<_, Infallible>(warp::reply::json(&app.lifetime.stats()))
+}
+
+/// Returns the number of decoding errors since startup, by kind and sensor
+pub async fn errors(
+    app: &Arc<Mutex<Jet1090>>,
+) -> Result<warp::reply::Json, Infallible> {
+    let app = app.lock().await;
+    Ok::<_, Infallible>(warp::reply::json(&app.errors.counts()))
+}
+
+/// Returns the timing residuals between pairs of sensors
+pub async fn timing(
+    app: &Arc<Mutex<Jet1090>>,
+) -> Result<warp::reply::Json, Infallible> {
+    let app = app.lock().await;
+    let name = |serial| app.sensors.get(&serial)?.name.clone();
+    Ok::<_, Infallible>(warp::reply::json(&app.timing.stats(name)))
+}
+
+/// Returns the site report, as JSON
+pub async fn report(
+    app: &Arc<Mutex<Jet1090>>,
+) -> Result<warp::reply::Json, Infallible> {
+    let report = Report::new(&*app.lock().await, now());
+    Ok::<_, Infallible>(warp::reply::json(&report))
+}
+
+/// Returns the site report, as a self-contained HTML page
+pub async fn report_html(
+    app: &Arc<Mutex<Jet1090>>,
+) -> Result<warp::reply::Html<String>, Infallible> {
+    let report = Report::new(&*app.lock().await, now());
+    Ok::<_, Infallible>(warp::reply::html(report.html()))
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("SystemTime before unix epoch")
+        .as_secs()
+}
+
+/// Returns the metrics of the priority queue, null if not enabled
+pub async fn queue(
+    queue: Option<&PriorityQueue>,
+) -> Result<warp::reply::Json, Infallible> {
+    Ok::<_, Infallible>(warp::reply::json(&queue.map(|q| q.stats())))
+}
+
+/// Returns the state of the connection to Redis, null if not enabled
+pub async fn redis(
+    status: Option<&RedisStatus>,
+) -> Result<warp::reply::Json, Infallible> {
+    Ok::<_, Infallible>(warp::reply::json(&status.map(|s| s.stats())))
+}
+
+/// Returns the end-to-end latency of messages (null if not measured)
+pub async fn latency() -> Result<warp::reply::Json, Infallible> {
+    Ok::<_, Infallible>(warp::reply::json(&latency::stats()))
+}
+
+/**
+ * Returns information about a list of aircraft (a JSON list of icao24), in
+ * the same order
+ *
+ * The country, the registration pattern and category come from the address
+ * (see [`aircraft_information`]), the registration and typecode from the
+ * aircraft database, and the performance class from the typecode, so that a
+ * frontend gets all the aircraft of a table in one query.
+ */
+pub async fn aircraft(
+    aircraftdb: &BTreeMap<String, Aircraft>,
+    body: &[u8],
+) -> Result<Response, Infallible> {
+    let error = |message: String| {
+        let json = warp::reply::json(&ErrorMessage {
+            code: StatusCode::BAD_REQUEST.as_u16(),
+            message,
+        });
+        Ok(warp::reply::with_status(json, StatusCode::BAD_REQUEST)
+            .into_response())
+    };
+    let Ok(icao24) = serde_json::from_slice::<Vec<String>>(body) else {
+        return error("Invalid body, expecting a list of icao24".to_string());
+    };
+    if icao24.len() > MAX_AIRCRAFT {
+        return error(format!("At most {MAX_AIRCRAFT} aircraft per query"));
+    }
+    let mut result = Vec::with_capacity(icao24.len());
+    for address in icao24 {
+        let Ok(hexid) = u32::from_str_radix(&address, 16) else {
+            return error(format!("Invalid icao24: {address}"));
+        };
+        let entry = aircraftdb.get(&format!("{hexid:06x}"));
+        let registration = entry.and_then(|ac| ac.registration.as_deref());
+        let typecode = entry.and_then(|ac| ac.typecode.as_deref());
+        result.push(
+            aircraft_information(hexid, registration).with_typecode(typecode),
+        );
+    }
+    Ok(warp::reply::json(&result).into_response())
+}
+
+/// An error with its status code, as JSON
+fn error_reply(status: StatusCode, message: String) -> Response {
+    let json = warp::reply::json(&ErrorMessage {
+        code: status.as_u16(),
+        message,
+    });
+    warp::reply::with_status(json, status).into_response()
+}
+
+/// Returns the settings of all the sensors, by serial number
+pub async fn sensor_settings(
+    app: &Arc<Mutex<Jet1090>>,
+) -> Result<warp::reply::Json, Infallible> {
+    let app = app.lock().await;
+    let store = app.sensor_store.read().unwrap();
+    Ok::<_, Infallible>(warp::reply::json(store.all()))
+}
+
+/**
+ * Sets (or replaces) the settings of a sensor, and returns them.
+ *
+ * Settings are saved right away. The alias and the RSSI offset apply to
+ * the next decoded messages, the reference position and the altitude at the
+ * next start.
+ */
+pub async fn set_sensor_settings(
+    app: &Arc<Mutex<Jet1090>>,
+    serial: u64,
+    body: &[u8],
+) -> Result<Response, Infallible> {
+    let settings = match serde_json::from_slice::<SensorSettings>(body) {
+        Ok(settings) => settings,
+        Err(e) => {
+            let message = format!("Invalid sensor settings: {e}");
+            return Ok(error_reply(StatusCode::BAD_REQUEST, message));
+        }
+    };
+    let app = app.lock().await;
+    let mut store = app.sensor_store.write().unwrap();
+    if let Err(e) = store.set(serial, settings.clone()) {
+        let message = format!("Failed to save the sensor settings: {e}");
+        return Ok(error_reply(StatusCode::INTERNAL_SERVER_ERROR, message));
+    }
+    Ok(warp::reply::json(&settings).into_response())
+}
+
+/// Removes the settings of a sensor, and returns them
+pub async fn remove_sensor_settings(
+    app: &Arc<Mutex<Jet1090>>,
+    serial: u64,
+) -> Result<Response, Infallible> {
+    let app = app.lock().await;
+    let mut store = app.sensor_store.write().unwrap();
+    match store.remove(serial) {
+        Ok(Some(settings)) => Ok(warp::reply::json(&settings).into_response()),
+        Ok(None) => Ok(error_reply(
+            StatusCode::NOT_FOUND,
+            format!("No settings for sensor {serial}"),
+        )),
+        Err(e) => Ok(error_reply(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to save the sensor settings: {e}"),
+        )),
+    }
+}
+
+/// Reloads the filters from the configuration file, and returns them
+pub async fn reload(reloader: &Reloader) -> Result<Response, Infallible> {
+    match reloader.reload_and_log() {
+        Ok(filters) => Ok(warp::reply::json(&filters).into_response()),
+        Err(message) => {
+            let json = warp::reply::json(&ErrorMessage {
+                code: StatusCode::BAD_REQUEST.as_u16(),
+                message,
+            });
+            Ok(warp::reply::with_status(json, StatusCode::BAD_REQUEST)
+                .into_response())
+        }
+    }
+}
+
+/// Returns the activity of the long-lived tasks, to diagnose hangs
+pub async fn tasks() -> Result<warp::reply::Json, Infallible> {
+    Ok::<_, Infallible>(warp::reply::json(&crate::tasks::status()))
+}
+
+/// Returns the JSON schema of decoded messages
+pub async fn schema() -> Result<warp::reply::Json, Infallible> {
+    Ok::<_, Infallible>(warp::reply::json(&rs1090::decode::schema()))
+}
+
+/**
+ * Returns a web map of live traffic (only with the `map` feature)
+ *
+ * The page is served on `/map/`, so that its assets are found next to it
+ * with relative links: `/map` redirects there (with a relative location,
+ * which also works behind a reverse proxy).
+ */
+pub async fn map(path: FullPath) -> Result<Response, Rejection> {
+    #[cfg(feature = "map")]
+    return Ok(if path.as_str().ends_with('/') {
+        warp::reply::html(include_str!("map/map.html")).into_response()
+    } else {
+        let location = warp::http::header::LOCATION;
+        with_header(StatusCode::MOVED_PERMANENTLY, location, "map/")
+            .into_response()
+    });
+    #[cfg(not(feature = "map"))]
+    {
+        let _ = path;
+        Err(warp::reject::not_found())
+    }
+}
+
+/**
+ * Returns the script and the style sheet of the web map, bundled with the
+ * binary (only with the `map` feature): the browser only loads the
+ * background tiles from the Internet.
+ */
+pub async fn map_asset(name: String) -> Result<Response, Rejection> {
+    #[cfg(feature = "map")]
+    {
+        let (body, content_type): (&'static [u8], _) = match name.as_str() {
+            "map.js" => (include_bytes!("map/map.js"), "text/javascript"),
+            "map.css" => (include_bytes!("map/map.css"), "text/css"),
+            _ => return Err(warp::reject::not_found()),
+        };
+        Ok(with_header(body, CONTENT_TYPE, content_type).into_response())
+    }
+    #[cfg(not(feature = "map"))]
+    {
+        let _ = name;
+        Err(warp::reject::not_found())
+    }
+}
+
+/**
+ * Streams all decoded messages on a websocket, one JSON text message each
+ *
+ * Other instances of jet1090 subscribe to this stream with a `jet1090://`
+ * source. Filters on downlink formats and aircraft do not apply. Decoded
+ * fields are in implicit units and with full precision, whatever the
+ * configuration, so that they are not decoded again by the subscribers (see
+ * [`jet1090::to_json`]). Heartbeats of sensors are streamed as well, other
+ * events are not.
+ */
+pub async fn stream(socket: WebSocket, mut rx: broadcast::Receiver<Event>) {
+    let (mut tx, mut incoming) = socket.split();
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok(Event::Message(msg, ..)) => {
+                    let Ok(json) = jet1090::to_json(msg.as_ref()) else {
+                        continue;
+                    };
+                    if tx.send(ws::Message::text(json)).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(event @ Event::Heartbeat(_)) => {
+                    let Ok(json) = event.to_json() else {
+                        continue;
+                    };
+                    if tx.send(ws::Message::text(json)).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(count)) => {
+                    warn!("Websocket client missed {} messages", count);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            // Stop when the client leaves
+            msg = incoming.next() => match msg {
+                Some(Ok(msg)) if !msg.is_close() => {}
+                _ => break,
+            },
+        }
+    }
+}
+
 /// Returns proper error messages in JSON format
 pub async fn handle_rejection(
     err: Rejection,
@@ -77,10 +603,15 @@ pub async fn handle_rejection(
     if err.is_not_found() {
         code = StatusCode::NOT_FOUND;
         message =
-            "Route not found, try one of / /all and /track?icao24={icao24}";
+            "Route not found, try one of / /all /czml /graphs /kml /map /queue /report /report.html /sensors /schema /stream /track?icao24={icao24}, POST /aircraft and POST /reload";
+    } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+        // Before methods, as this route also rejects GET queries
+        code = StatusCode::PAYLOAD_TOO_LARGE;
+        message = "Payload too large";
     } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
         code = StatusCode::METHOD_NOT_ALLOWED;
-        message = "Only GET queries are supported";
+        message =
+            "Only GET queries are supported, and POST on /aircraft and /reload";
     } else if err.find::<warp::reject::InvalidQuery>().is_some() {
         code = StatusCode::BAD_REQUEST;
         message = "Invalid query";
@@ -12,14 +12,19 @@ use rs1090::decode::bds::bds20::AircraftIdentification;
 use rs1090::decode::bds::bds21::AircraftAndAirlineRegistrationMarkings;
 use rs1090::decode::bds::bds30::ACASResolutionAdvisory;
 use rs1090::decode::bds::bds40::SelectedVerticalIntention;
+use rs1090::decode::bds::bds41::NextWaypointIdentifier;
+use rs1090::decode::bds::bds42::NextWaypointPosition;
 use rs1090::decode::bds::bds44::MeteorologicalRoutineAirReport;
 use rs1090::decode::bds::bds45::MeteorologicalHazardReport;
 use rs1090::decode::bds::bds50::TrackAndTurnReport;
 use rs1090::decode::bds::bds60::HeadingAndSpeedReport;
 use rs1090::decode::cpr::{
-    airborne_position_with_reference, surface_position_with_reference,
+    airborne_position_with_reference, coarse_position_with_reference,
+    surface_position_with_reference,
 };
 use rs1090::prelude::*;
+use rs1090::source::parser::{BeastParser, FrameKind};
+use serde::Serialize;
 use utils::set_panic_hook;
 use wasm_bindgen::prelude::*;
 
@@ -64,6 +69,26 @@ fn decode_message_with_reference(me: &mut ME, reference: [f64; 2]) {
     }
 }
 
+fn decode_tisb_with_reference(me: &mut TisBMessage, reference: [f64; 2]) {
+    let [latitude_ref, longitude_ref] = reference;
+    match me {
+        TisBMessage::ExtendedSquitter(me) => {
+            decode_message_with_reference(me, reference)
+        }
+        TisBMessage::Coarse(coarse) => {
+            if let Some(pos) = coarse_position_with_reference(
+                coarse,
+                latitude_ref,
+                longitude_ref,
+            ) {
+                coarse.latitude = Some(pos.latitude);
+                coarse.longitude = Some(pos.longitude);
+            }
+        }
+        TisBMessage::Management(_) => (),
+    }
+}
+
 #[wasm_bindgen]
 pub fn decode(
     msg: &str,
@@ -75,7 +100,7 @@ pub fn decode(
             if let Some(reference) = reference.map(|v| [v[0], v[1]]) {
                 match &mut msg.df {
                     ExtendedSquitterTisB { cf, .. } => {
-                        decode_message_with_reference(&mut cf.me, reference)
+                        decode_tisb_with_reference(&mut cf.me, reference)
                     }
                     ExtendedSquitterADSB(adsb) => {
                         decode_message_with_reference(
@@ -93,6 +118,63 @@ pub fn decode(
     }
 }
 
+/// A message decoded from a Beast frame, with the timestamp of the receiver
+#[derive(Serialize)]
+struct BeastMessage {
+    /// The raw 48-bit timestamp of the receiver (0 if not available)
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signal: Option<u8>,
+    frame: String,
+    #[serde(flatten)]
+    message: Message,
+}
+
+/**
+ * An incremental decoder of Beast (or AVR) streams, e.g. received on a
+ * WebSocket: bytes are pushed as they arrive, and the Mode S frames they
+ * complete are decoded. Frames which cannot be decoded are skipped.
+ */
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct BeastDecoder(BeastParser);
+
+#[wasm_bindgen]
+impl BeastDecoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        BeastDecoder::default()
+    }
+
+    /// Push bytes, and return the messages completed by these bytes
+    pub fn push(&mut self, data: &[u8]) -> Result<Vec<JsValue>, JsError> {
+        let messages = self
+            .0
+            .push(data)
+            .filter(|frame| {
+                matches!(
+                    frame.kind(),
+                    FrameKind::ModeSShort | FrameKind::ModeSLong
+                )
+            })
+            .filter_map(|frame| {
+                let message = Message::try_from(frame.payload()).ok()?;
+                Some(BeastMessage {
+                    timestamp: frame.timestamp(),
+                    signal: frame.signal(),
+                    frame: hex::encode(frame.payload()),
+                    message,
+                })
+            });
+        let mut result = Vec::new();
+        for msg in messages {
+            let map_result = serde_wasm_bindgen::to_value(&msg)?;
+            result.push(Object::from_entries(&map_result).unwrap().into());
+        }
+        Ok(result)
+    }
+}
+
 #[wasm_bindgen]
 pub fn decode_bds05(msg: &str) -> Result<JsValue, JsError> {
     let bytes = hex::decode(msg)?;
@@ -210,6 +292,30 @@ pub fn decode_bds40(msg: &str) -> Result<JsValue, JsError> {
     }
 }
 
+#[wasm_bindgen]
+pub fn decode_bds41(msg: &str) -> Result<JsValue, JsError> {
+    let bytes = hex::decode(msg)?;
+    match NextWaypointIdentifier::from_bytes((&bytes[4..], 0)) {
+        Ok((_, msg)) => {
+            let map_result = serde_wasm_bindgen::to_value(&msg)?;
+            Ok(map_result)
+        }
+        Err(e) => Err(DecodeError(e).into()),
+    }
+}
+
+#[wasm_bindgen]
+pub fn decode_bds42(msg: &str) -> Result<JsValue, JsError> {
+    let bytes = hex::decode(msg)?;
+    match NextWaypointPosition::from_bytes((&bytes[4..], 0)) {
+        Ok((_, msg)) => {
+            let map_result = serde_wasm_bindgen::to_value(&msg)?;
+            Ok(map_result)
+        }
+        Err(e) => Err(DecodeError(e).into()),
+    }
+}
+
 #[wasm_bindgen]
 pub fn decode_bds44(msg: &str) -> Result<JsValue, JsError> {
     let bytes = hex::decode(msg)?;
@@ -282,3 +388,53 @@ pub fn decode_bds65(msg: &str) -> Result<JsValue, JsError> {
         }
     }
 }
+
+/**
+ * Returns the airports matching all the queries (case insensitive regular
+ * expressions) on their ICAO or IATA code, name, city or country code
+ */
+#[cfg(feature = "data-airports")]
+#[wasm_bindgen]
+pub fn airports(queries: Vec<String>) -> Result<JsValue, JsError> {
+    use regex::Regex;
+    use rs1090::data::airports::AIRPORTS;
+
+    let queries = queries
+        .iter()
+        .map(|query| Regex::new(&format!("(?i){}", query)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let airports: Vec<_> = AIRPORTS
+        .iter()
+        .filter(|airport| {
+            queries.iter().all(|query| {
+                [
+                    &airport.country,
+                    &airport.icao,
+                    &airport.iata,
+                    &airport.city,
+                    &airport.name,
+                ]
+                .iter()
+                .any(|field| query.is_match(field))
+            })
+        })
+        .collect();
+    Ok(serde_wasm_bindgen::to_value(&airports)?)
+}
+
+/**
+ * Returns the country, and the registration pattern and category of an
+ * aircraft, from its ICAO 24-bit address
+ */
+#[cfg(feature = "data-patterns")]
+#[wasm_bindgen]
+pub fn aircraft_information(
+    icao24: &str,
+    registration: Option<String>,
+) -> Result<JsValue, JsError> {
+    use rs1090::data::patterns;
+
+    let hexid = u32::from_str_radix(icao24, 16)?;
+    let info = patterns::aircraft_information(hexid, registration.as_deref());
+    Ok(serde_wasm_bindgen::to_value(&info)?)
+}
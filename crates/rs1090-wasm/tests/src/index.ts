@@ -1,5 +1,5 @@
 // src/index.ts
-import { run, decode } from "rs1090-wasm";
+import { run, decode, BeastDecoder } from "rs1090-wasm";
 
 async function init() {
   await run(); // Initialize the WebAssembly module
@@ -7,4 +7,4 @@ async function init() {
 
 init().catch(console.error);
 
-export { decode };
+export { decode, BeastDecoder };
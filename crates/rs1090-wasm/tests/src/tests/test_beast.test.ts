@@ -0,0 +1,19 @@
+import { BeastDecoder } from "../index";
+import { expect, describe, test } from "@jest/globals";
+
+describe("Beast Decoding Tests", () => {
+  test("incremental decoding", () => {
+    const frame = Buffer.from(
+      "1a33001a1a3f9a1c42c08d406b902015a678d4d220aa4bda",
+      "hex",
+    );
+    const decoder = new BeastDecoder();
+    // Frames may be split across several chunks
+    expect(decoder.push(frame.subarray(0, 10))).toHaveLength(0);
+    const msgs = decoder.push(frame.subarray(10));
+    expect(msgs).toHaveLength(1);
+    expect(msgs[0].timestamp).toBe(0x001a3f9a1c42);
+    expect(msgs[0].frame).toBe("8d406b902015a678d4d220aa4bda");
+    expect(msgs[0].callsign).toBe("EZY85MH");
+  });
+});
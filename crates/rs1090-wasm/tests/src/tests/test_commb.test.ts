@@ -29,6 +29,24 @@ describe("CommB Decoding Tests", () => {
     expect(bds40.barometric_setting).toBe(1020);
   });
 
+  test("bds41", () => {
+    const msg = decode("A00018389C0A4097041040000000");
+    expect(msg.df).toBe("20");
+    const bds41 = msg.bds41;
+    expect(bds41).not.toBeNull();
+    expect(bds41.waypoint).toBe("NARAK");
+  });
+
+  test("bds42", () => {
+    const msg = decode("A00018388F833807C88271000000");
+    expect(msg.df).toBe("20");
+    const bds42 = msg.bds42;
+    expect(bds42).not.toBeNull();
+    expect(bds42.latitude).toBeCloseTo(43.6289, 3);
+    expect(bds42.longitude).toBeCloseTo(1.3678, 3);
+    expect(bds42.altitude).toBe(5000);
+  });
+
   test("bds50", () => {
     const msg = decode("A000139381951536E024D4CCF6B5");
     expect(msg.df).toBe("20");
@@ -0,0 +1,44 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rs1090::decode::crc::{modes_checksum, modes_checksum_bytewise};
+
+/// Number of messages in each batch
+const MESSAGES: usize = 1024;
+
+fn messages(bytes: usize) -> Vec<Vec<u8>> {
+    let mut state = 0x2545_f491_u32;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    };
+    (0..MESSAGES)
+        .map(|_| (0..bytes).map(|_| next()).collect())
+        .collect()
+}
+
+fn checksum(c: &mut Criterion) {
+    for (name, bytes) in [("short", 7), ("long", 14)] {
+        let data = messages(bytes);
+        let mut group = c.benchmark_group(format!("crc_{name}"));
+        group.throughput(Throughput::Elements(MESSAGES as u64));
+        group.bench_function("bytewise", |b| {
+            b.iter(|| {
+                data.iter()
+                    .map(|msg| modes_checksum_bytewise(msg, bytes * 8).unwrap())
+                    .fold(0, |acc, crc| acc ^ crc)
+            })
+        });
+        group.bench_function("slice_by_8", |b| {
+            b.iter(|| {
+                data.iter()
+                    .map(|msg| modes_checksum(msg, bytes * 8).unwrap())
+                    .fold(0, |acc, crc| acc ^ crc)
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, checksum);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use num_complex::Complex;
+use rs1090::source::dsp;
+
+/// One buffer of samples, as read from the SDR
+const SAMPLES: usize = 131_072;
+
+fn samples() -> Vec<Complex<i16>> {
+    let mut state = 0x2545_f491_u32;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        // keep values in a realistic range for a RTL-SDR dongle
+        (state as i16) >> 4
+    };
+    (0..SAMPLES).map(|_| Complex::new(next(), next())).collect()
+}
+
+fn magnitude(c: &mut Criterion) {
+    let data = samples();
+    let mut out = vec![0_u16; SAMPLES];
+
+    let mut group = c.benchmark_group("magnitude");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    group.bench_function("scalar", |b| {
+        b.iter(|| dsp::magnitude_scalar(&data, &mut out))
+    });
+    group
+        .bench_function("simd", |b| b.iter(|| dsp::magnitude(&data, &mut out)));
+    group.finish();
+}
+
+fn preamble(c: &mut Criterion) {
+    let data = samples();
+    let mut mag = vec![0_u16; SAMPLES];
+    dsp::magnitude(&data, &mut mag);
+    let length = SAMPLES - 13;
+    let mut candidates = Vec::with_capacity(SAMPLES);
+
+    let mut group = c.benchmark_group("preamble");
+    group.throughput(Throughput::Elements(length as u64));
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            candidates.clear();
+            dsp::preamble_candidates_scalar(&mag, 0, length, &mut candidates)
+        })
+    });
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            candidates.clear();
+            dsp::preamble_candidates(&mag, length, &mut candidates)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, magnitude, preamble);
+criterion_main!(benches);
@@ -66,7 +66,7 @@ fn main() -> io::Result<()> {
                 let bytes = hex::decode(&hex).unwrap();
                 let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
                 res.push(TimedMessage {
-                    timestamp,
+                    timestamp: Timestamp::from_secs_f64(timestamp),
                     frame: bytes,
                     message: Some(msg),
                     metadata: vec![],
@@ -80,7 +80,7 @@ fn main() -> io::Result<()> {
 
     // println!("{} messages processed", res.len());
 
-    decode_positions(&mut res, Some(reference), &None);
+    decode_positions(&mut res, Some(reference), &None, true);
 
     println!("{}", serde_json::to_string(&res).unwrap());
     Ok(())
@@ -1,28 +1,46 @@
 #[cfg(feature = "sero")]
-use rs1090::source::sero::SeroClient;
+use futures_util::{pin_mut, StreamExt};
+#[cfg(feature = "sero")]
+use rs1090::prelude::*;
+#[cfg(feature = "sero")]
+use rs1090::source::sero::{self, SeroClient};
 
 #[tokio::main]
 #[cfg(feature = "sero")]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Load TOKEN from environment variables
     let client = SeroClient {
         token: std::env::var("SERO_TOKEN")?,
-        df_filter: vec![],
+        df_filter: vec![17, 18],
         aircraft_filter: vec![],
+        sensor_filter: vec![],
     };
 
-    // Access info about receivers and display it
-    let info = client.info().await.unwrap();
-    for sensor in info.sensor_info {
-        let gnss = sensor.gnss.unwrap();
-        println!(
-            "{} {} {} {} {}",
-            sensor.sensor.unwrap().serial,
-            gnss.position.unwrap().latitude,
-            gnss.position.unwrap().longitude,
-            gnss.position.unwrap().height,
-            sensor.alias,
-        );
+    // Access info about receivers and display it, by pages of 10 sensors
+    let info = client.sensor_info(10);
+    pin_mut!(info);
+    while let Some(page) = info.next().await {
+        for sensor in page? {
+            let gnss = sensor.gnss.unwrap();
+            println!(
+                "{} {} {} {} {}",
+                sensor.sensor.unwrap().serial,
+                gnss.position.unwrap().latitude,
+                gnss.position.unwrap().longitude,
+                gnss.position.unwrap().height,
+                sensor.alias,
+            );
+        }
+    }
+
+    // Decode a few messages from the live feed
+    let stream = sero::stream(client).await?.take(10);
+    pin_mut!(stream);
+    while let Some(mut msg) = stream.next().await {
+        if let Ok((_, message)) = Message::from_bytes((&msg.frame, 0)) {
+            msg.message = Some(message);
+            println!("{}", serde_json::to_string(&msg)?);
+        }
     }
 
     Ok(())
@@ -0,0 +1,185 @@
+//! Reading archived messages, as written in JSON lines by any version of
+//! `jet1090` or `decode1090`.
+//!
+//! The format of archives evolved across versions:
+//!
+//! | Version               | Content of each line                           |
+//! | --------------------- | ---------------------------------------------- |
+//! | [`Version::Legacy`]   | `timestamp`, `frame` and a top-level `rssi`    |
+//! | [`Version::Metadata`] | `timestamp`, `frame` and a list of `metadata`, |
+//! |                       | with one entry per sensor receiving the frame  |
+//!
+//! Lines of all versions are converted to the current [`TimedMessage`]. The
+//! decoded fields are not read, as they can always be decoded again from the
+//! frame (possibly with a more recent decoder). Lines which are not messages
+//! (e.g. flight summaries, with a `record` field) are skipped, and so are
+//! integrity hashes, see [`crate::integrity`] to check them.
+
+use crate::decode::time::Timestamp;
+use crate::decode::{from_hex, SensorMetadata, TimedMessage};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::io::{self, BufRead};
+
+/// The versions of the format of archived messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Version {
+    /// A top-level `rssi` field, without information about the sensor
+    Legacy,
+    /// A list of `metadata`, one entry per sensor
+    Metadata,
+}
+
+/// The version of the format written by this version of the library
+pub const CURRENT: Version = Version::Metadata;
+
+/// An archived message, with the fields of all the versions
+#[derive(Debug, Deserialize)]
+struct Entry {
+    timestamp: Timestamp,
+    #[serde(deserialize_with = "from_hex")]
+    frame: Vec<u8>,
+    /// Only in the legacy format
+    rssi: Option<f32>,
+    metadata: Option<Vec<SensorMetadata>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Line {
+    /// Other records are tagged with their type
+    #[allow(dead_code)]
+    Record {
+        record: IgnoredAny,
+    },
+    Message(Entry),
+}
+
+impl Entry {
+    fn version(&self) -> Version {
+        match self.metadata {
+            Some(_) => Version::Metadata,
+            None => Version::Legacy,
+        }
+    }
+}
+
+impl From<Entry> for TimedMessage {
+    fn from(entry: Entry) -> Self {
+        let mut metadata = entry.metadata.unwrap_or_default();
+        // The legacy format does not tell which sensor received the frame
+        if entry.rssi.is_some() && metadata.is_empty() {
+            metadata.push(SensorMetadata {
+                system_timestamp: entry.timestamp,
+                gnss_timestamp: None,
+                nanoseconds: None,
+                rssi: entry.rssi,
+                serial: 0,
+                name: None,
+                time_source: None,
+                clock_error: None,
+                timestamp_error: None,
+            })
+        }
+        TimedMessage {
+            timestamp: entry.timestamp,
+            frame: entry.frame,
+            message: None,
+            metadata,
+            decode_time: None,
+        }
+    }
+}
+
+fn parse(line: &str) -> serde_json::Result<Option<Entry>> {
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    match serde_json::from_str(line)? {
+        Line::Record { .. } => Ok(None),
+        Line::Message(entry) => Ok(Some(entry)),
+    }
+}
+
+/**
+ * The version of the format of an archived line.
+ *
+ * Returns None if the line is not a message.
+ */
+pub fn version(line: &str) -> Option<Version> {
+    parse(line).ok().flatten().map(|entry| entry.version())
+}
+
+/**
+ * Convert an archived line, in any version of the format, into a message.
+ *
+ * Returns None if the line is not a message. The message is not decoded:
+ * its `message` field is left empty.
+ */
+pub fn parse_line(line: &str) -> serde_json::Result<Option<TimedMessage>> {
+    Ok(parse(line)?.map(TimedMessage::from))
+}
+
+/**
+ * Read all the messages of an archive, in any version of the format.
+ *
+ * Lines which are not messages are skipped; invalid lines are returned as
+ * errors, so that the caller can decide whether to skip them.
+ */
+pub fn read<R: BufRead>(
+    reader: R,
+) -> impl Iterator<Item = io::Result<TimedMessage>> {
+    reader.lines().filter_map(|line| match line {
+        Ok(line) => parse_line(&line).map_err(io::Error::from).transpose(),
+        Err(e) => Some(Err(e)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions() {
+        let legacy = r#"{"timestamp":1700000000.5,"frame":"8d406b902015a678d4d220aa4bda","rssi":-12.5}"#;
+        assert_eq!(version(legacy), Some(Version::Legacy));
+        let msg = parse_line(legacy).unwrap().unwrap();
+        assert_eq!(msg.frame[0], 0x8d);
+        assert_eq!(msg.metadata.len(), 1);
+        assert_eq!(msg.metadata[0].rssi, Some(-12.5));
+        assert_eq!(
+            msg.metadata[0].system_timestamp,
+            Timestamp::from_millis(1_700_000_000_500)
+        );
+
+        let current = r#"{"timestamp":1700000000.5,"frame":"8d406b902015a678d4d220aa4bda","df":"17","icao24":"406b90","metadata":[{"system_timestamp":1700000000.5,"rssi":-12.5,"serial":42}],"hash":"00"}"#;
+        assert_eq!(version(current), Some(Version::Metadata));
+        let msg = parse_line(current).unwrap().unwrap();
+        assert_eq!(msg.metadata[0].serial, 42);
+        assert!(msg.message.is_none());
+
+        let summary = r#"{"record":"flight","icao24":"406b90","count":12}"#;
+        assert_eq!(version(summary), None);
+        assert!(parse_line(summary).unwrap().is_none());
+        assert!(parse_line("").unwrap().is_none());
+        assert!(parse_line(r#"{"timestamp":0}"#).is_err());
+    }
+
+    #[test]
+    fn test_read() {
+        let archive = "\
+            {\"timestamp\":1.0,\"frame\":\"5d4d20237a55a6\",\"rssi\":-3.0}\n\
+            {\"record\":\"sensor\",\"status\":\"up\",\"serial\":42}\n\
+            not json\n\
+            {\"timestamp\":2.0,\"frame\":\"5d4d20237a55a6\",\"metadata\":[]}\n";
+        let messages: Vec<_> = read(archive.as_bytes()).collect();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[1].is_err());
+        let timestamps: Vec<i64> = messages
+            .into_iter()
+            .flatten()
+            .map(|msg| msg.timestamp.as_secs())
+            .collect();
+        assert_eq!(timestamps, vec![1, 2]);
+    }
+}
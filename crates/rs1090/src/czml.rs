@@ -0,0 +1,262 @@
+/*!
+ * Export of trajectories to CZML, for 3D replay in CesiumJS.
+ *
+ * A CZML document is a JSON list of packets: a first packet describing the
+ * document (and its clock), then one packet per aircraft with a
+ * time-dynamic position, built from the decoded positions of ADS-B and
+ * TIS-B messages. Barometric altitudes are converted to meters; surface
+ * positions are on the ground.
+ *
+ * ```
+ * use rs1090::czml::Czml;
+ * use rs1090::prelude::*;
+ *
+ * let mut czml = Czml::new("replay");
+ * let mut messages: Vec<TimedMessage> = vec![]; // decoded messages
+ * for msg in &messages {
+ *     czml.push(msg);
+ * }
+ * let document = czml.document();
+ * assert_eq!(document[0]["id"], "document");
+ * ```
+ *
+ * See <https://github.com/AnalyticalGraphicsInc/czml-writer/wiki/CZML-Guide>
+ */
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::decode::adsb::ME;
+use crate::decode::tisb::TisBMessage;
+use crate::prelude::*;
+
+/// Conversion factor from feet to meters
+const FEET: f64 = 0.3048;
+/// Duration (in s) of the trail of aircraft behind their position
+const TRAIL_TIME: u32 = 300;
+
+/// The positions (time, longitude, latitude, height in m) of an aircraft
+#[derive(Debug, Default)]
+struct Track {
+    callsign: Option<String>,
+    positions: Vec<(Timestamp, f64, f64, f64)>,
+}
+
+/**
+ * A CZML document, built from decoded messages (with decoded positions).
+ *
+ * Messages may be restricted to a time window, e.g. to replay a given
+ * period of a long recording.
+ */
+#[derive(Debug, Default)]
+pub struct Czml {
+    name: String,
+    start: Option<Timestamp>,
+    stop: Option<Timestamp>,
+    tracks: BTreeMap<String, Track>,
+}
+
+impl Czml {
+    pub fn new(name: &str) -> Self {
+        Czml {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Only keep the messages received in a time window
+    pub fn with_window(
+        mut self,
+        start: Option<Timestamp>,
+        stop: Option<Timestamp>,
+    ) -> Self {
+        self.start = start;
+        self.stop = stop;
+        self
+    }
+
+    /// Add the position (or the callsign) in a message to the document
+    pub fn push(&mut self, msg: &TimedMessage) {
+        let Some(message) = &msg.message else {
+            return;
+        };
+        if self.start.is_some_and(|start| msg.timestamp < start)
+            || self.stop.is_some_and(|stop| msg.timestamp > stop)
+        {
+            return;
+        }
+        let Some(icao24) = message.icao24() else {
+            return;
+        };
+        let me = match &message.df {
+            ExtendedSquitterADSB(adsb) => &adsb.message,
+            ExtendedSquitterTisB { cf, .. } => match &cf.me {
+                TisBMessage::ExtendedSquitter(me) => me,
+                _ => return,
+            },
+            _ => return,
+        };
+        let position = match me {
+            ME::BDS05(pos) => match (pos.latitude, pos.longitude, pos.alt) {
+                (Some(lat), Some(lon), Some(alt)) => {
+                    Some((lat, lon, alt as f64 * FEET))
+                }
+                _ => None,
+            },
+            ME::BDS06(pos) => match (pos.latitude, pos.longitude) {
+                (Some(lat), Some(lon)) => Some((lat, lon, 0.)),
+                _ => None,
+            },
+            ME::BDS08(id) if !id.callsign.contains('#') => {
+                let track = self.tracks.entry(icao24.to_string()).or_default();
+                track.callsign = Some(id.callsign.trim().to_string());
+                None
+            }
+            _ => None,
+        };
+        if let Some((lat, lon, height)) = position {
+            let track = self.tracks.entry(icao24.to_string()).or_default();
+            track.positions.push((msg.timestamp, lon, lat, height));
+        }
+    }
+
+    /// The CZML document, a list of packets
+    pub fn document(&self) -> Value {
+        let tracks: Vec<(&String, &Track)> = self
+            .tracks
+            .iter()
+            .filter(|(_, track)| !track.positions.is_empty())
+            .collect();
+        let times = tracks
+            .iter()
+            .flat_map(|(_, track)| track.positions.iter().map(|p| p.0));
+        let (start, stop) = (times.clone().min(), times.max());
+
+        let mut document = json!({
+            "id": "document",
+            "name": self.name,
+            "version": "1.0",
+        });
+        if let (Some(start), Some(stop)) = (start, stop) {
+            document["clock"] = json!({
+                "interval": interval(start, stop),
+                "currentTime": iso8601(start),
+                "multiplier": 10,
+                "range": "LOOP_STOP",
+                "step": "SYSTEM_CLOCK_MULTIPLIER",
+            });
+        }
+
+        let mut packets = vec![document];
+        for (icao24, track) in tracks {
+            let mut positions = track.positions.clone();
+            positions.sort_by_key(|p| p.0);
+            let epoch = positions[0].0;
+            let last = positions[positions.len() - 1].0;
+            let cartographic: Vec<f64> = positions
+                .iter()
+                .flat_map(|&(t, lon, lat, height)| {
+                    let seconds = (t - epoch).as_secs_f64();
+                    [seconds, lon, lat, (height * 10.).round() / 10.]
+                })
+                .collect();
+            let name = track.callsign.as_deref().unwrap_or(icao24);
+            packets.push(json!({
+                "id": icao24,
+                "name": name,
+                "availability": interval(epoch, last),
+                "position": {
+                    "epoch": iso8601(epoch),
+                    "cartographicDegrees": cartographic,
+                },
+                "point": {
+                    "pixelSize": 6,
+                    "color": {"rgba": [255, 128, 0, 255]},
+                },
+                "path": {
+                    "width": 2,
+                    "leadTime": 0,
+                    "trailTime": TRAIL_TIME,
+                    "material": {
+                        "solidColor": {"color": {"rgba": [255, 128, 0, 160]}}
+                    },
+                },
+                "label": {
+                    "text": name,
+                    "font": "11pt sans-serif",
+                    "pixelOffset": {"cartesian2": [0, -16]},
+                },
+            }));
+        }
+        Value::Array(packets)
+    }
+}
+
+fn interval(start: Timestamp, stop: Timestamp) -> String {
+    format!("{}/{}", iso8601(start), iso8601(stop))
+}
+
+/// Format a timestamp as an ISO 8601 date in UTC, to the millisecond
+fn iso8601(timestamp: Timestamp) -> String {
+    let (year, month, day, ms) = timestamp.civil();
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::cpr::decode_positions;
+    use crate::testing::timed;
+    use hexlit::hex;
+
+    #[test]
+    fn test_czml() {
+        assert_eq!(
+            iso8601(Timestamp::from_millis(1_700_000_000_123)),
+            "2023-11-14T22:13:20.123Z"
+        );
+        assert_eq!(
+            iso8601(Timestamp::from_secs(0)),
+            "1970-01-01T00:00:00.000Z"
+        );
+
+        let mut messages = vec![
+            timed(&hex!("8d4840d6202cc371c32ce0576098"), 1_700_000_000.),
+            timed(&hex!("8d40621d58c382d690c8ac2863a7"), 1_700_000_001.),
+            timed(&hex!("8d40621d58c386435cc412692ad6"), 1_700_000_002.),
+        ];
+        decode_positions(&mut messages, None, &None, true);
+
+        let mut czml = Czml::new("test");
+        messages.iter().for_each(|msg| czml.push(msg));
+        let document = czml.document();
+        let packets = document.as_array().unwrap();
+        // The aircraft sending its callsign has no position
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0]["name"], "test");
+        assert_eq!(
+            packets[0]["clock"]["interval"],
+            "2023-11-14T22:13:21.000Z/2023-11-14T22:13:22.000Z"
+        );
+        assert_eq!(packets[1]["id"], "40621d");
+        let position = &packets[1]["position"];
+        assert_eq!(position["epoch"], "2023-11-14T22:13:21.000Z");
+        let cartographic = position["cartographicDegrees"].as_array().unwrap();
+        assert_eq!(cartographic.len(), 8);
+        assert_eq!(cartographic[4], json!(1.));
+        // 38000 ft
+        assert_eq!(cartographic[3], json!(11582.4));
+
+        // Outside of the time window
+        let start = Timestamp::from_secs(1_700_000_010);
+        let mut czml = Czml::new("test").with_window(Some(start), None);
+        messages.iter().for_each(|msg| czml.push(msg));
+        assert_eq!(czml.document().as_array().unwrap().len(), 1);
+    }
+}
@@ -1,3 +1,6 @@
+#[cfg(feature = "data-airports")]
 pub mod airports;
+#[cfg(feature = "data-patterns")]
 pub mod patterns;
+pub mod performance;
 pub mod tail;
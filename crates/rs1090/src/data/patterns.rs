@@ -1,5 +1,11 @@
 use once_cell::sync::Lazy;
-use serde::Deserialize;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use super::performance::{performance, Performance};
+use super::tail::tail;
 
 #[derive(Debug, Deserialize)]
 pub struct Patterns {
@@ -30,9 +36,227 @@ const PATTERNS_JSON: &str = include_str!("../../data/patterns.json");
 pub static PATTERNS: Lazy<Patterns> =
     Lazy::new(|| serde_json::from_str(PATTERNS_JSON).unwrap());
 
+/// The address range and compiled category patterns of a register
+struct Compiled {
+    range: Option<RangeInclusive<u32>>,
+    categories: Vec<Regex>,
+}
+
+static COMPILED: Lazy<Vec<Compiled>> = Lazy::new(|| {
+    let address = |s: &Option<String>| {
+        u32::from_str_radix(s.as_ref()?.trim_start_matches("0x"), 16).ok()
+    };
+    PATTERNS
+        .registers
+        .iter()
+        .map(|register| {
+            let range = address(&register.start)
+                .zip(address(&register.end))
+                .map(|(start, end)| start..=end);
+            let categories = register
+                .categories
+                .iter()
+                .flatten()
+                .map(|cat| Regex::new(&cat.pattern).unwrap())
+                .collect();
+            Compiled { range, categories }
+        })
+        .collect()
+});
+
+/**
+ * Blocks of addresses used by military aircraft, within the blocks of
+ * their countries (from the ranges used by tar1090 and readsb).
+ */
+const MILITARY: [RangeInclusive<u32>; 33] = [
+    0x010070..=0x01008f, // Egypt
+    0x0a4000..=0x0a4fff, // Algeria
+    0x33ff00..=0x33ffff, // Italy
+    0x350000..=0x37ffff, // Spain
+    0x3a8000..=0x3affff, // France
+    0x3b0000..=0x3bffff, // France
+    0x3ea000..=0x3ebfff, // Germany
+    0x3f4000..=0x3fbfff, // Germany
+    0x400000..=0x40003f, // United Kingdom
+    0x43c000..=0x43cfff, // United Kingdom
+    0x444000..=0x446fff, // Austria
+    0x44f000..=0x44ffff, // Belgium
+    0x457000..=0x457fff, // Bulgaria
+    0x45f400..=0x45f4ff, // Denmark
+    0x468000..=0x4683ff, // Greece
+    0x473c00..=0x473c0f, // Hungary
+    0x478100..=0x4781ff, // Norway
+    0x480000..=0x480fff, // Netherlands
+    0x48d800..=0x48d87f, // Poland
+    0x497c00..=0x497cff, // Portugal
+    0x498420..=0x49842f, // Czech Republic
+    0x4b7000..=0x4b7fff, // Switzerland
+    0x4b8200..=0x4b82ff, // Turkey
+    0x506f00..=0x506fff, // Slovenia
+    0x70c070..=0x70c07f, // Oman
+    0x710258..=0x71028f, // Saudi Arabia
+    0x710380..=0x71039f, // Saudi Arabia
+    0x738a00..=0x738aff, // Israel
+    0x7c822e..=0x7c84ff, // Australia
+    0x7c8800..=0x7c88ff, // Australia
+    0x7c9000..=0x7cbfff, // Australia
+    0xadf7c8..=0xafffff, // United States
+    0xc20000..=0xc3ffff, // Canada
+];
+
+/// The kind of block an ICAO 24-bit address is allocated from
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Allocation {
+    /// Allocated to a country, for civil aircraft
+    #[default]
+    Civil,
+    /// In a block used by military aircraft
+    Military,
+    /// Reserved by ICAO for special use, or temporary addresses
+    Special,
+    /// Not allocated to any country: often drones or test transponders
+    Unallocated,
+}
+
+impl fmt::Display for Allocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Allocation::Civil => "civil",
+            Allocation::Military => "military",
+            Allocation::Special => "special",
+            Allocation::Unallocated => "unallocated",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/**
+ * The kind of block an address is allocated from. The most specific block
+ * of the patterns data is considered, e.g. the blocks for special use
+ * within the blocks of countries.
+ */
+pub fn allocation(icao24: u32) -> Allocation {
+    if MILITARY.iter().any(|range| range.contains(&icao24)) {
+        return Allocation::Military;
+    }
+    let register = PATTERNS
+        .registers
+        .iter()
+        .zip(COMPILED.iter())
+        .filter_map(|(register, compiled)| {
+            let range = compiled.range.as_ref()?;
+            range
+                .contains(&icao24)
+                .then_some((register, range.end() - range.start()))
+        })
+        .min_by_key(|(_, size)| *size);
+    match register {
+        None => Allocation::Unallocated,
+        Some((register, _)) if register.country.starts_with("Unassigned") => {
+            Allocation::Unallocated
+        }
+        Some((register, _)) if register.country.starts_with("ICAO") => {
+            Allocation::Special
+        }
+        Some(_) => Allocation::Civil,
+    }
+}
+
+/// Information inferred from the ICAO 24-bit address of an aircraft
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AircraftInformation {
+    pub icao24: String,
+    /// The registration, if provided or inferred from the address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration: Option<String>,
+    /// The country the address is allocated to
+    pub country: String,
+    pub flag: String,
+    /// The kind of block the address is allocated from
+    pub allocation: Allocation,
+    /// The pattern of registrations in the country (or category)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// The category of aircraft matching the registration (e.g. gliders)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// The ICAO type designator, if provided, see [`Self::with_typecode`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typecode: Option<String>,
+    /// The performance class of the type of aircraft, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub performance: Option<Performance>,
+}
+
+impl AircraftInformation {
+    /// Complete the information with the type of aircraft, e.g. A320
+    pub fn with_typecode(mut self, typecode: Option<&str>) -> Self {
+        self.typecode = typecode.map(str::to_string);
+        self.performance = typecode.and_then(performance);
+        self
+    }
+}
+
+/**
+ * Infer the country and the registration pattern of an aircraft from its
+ * address. The registration is inferred from the address for the few
+ * countries where it is possible, unless it is provided.
+ */
+pub fn aircraft_information(
+    icao24: u32,
+    registration: Option<&str>,
+) -> AircraftInformation {
+    let mut info = AircraftInformation {
+        icao24: format!("{icao24:06x}"),
+        registration: registration.map(str::to_string).or(tail(icao24)),
+        country: "Unknown".to_string(),
+        flag: "🏳".to_string(),
+        allocation: allocation(icao24),
+        pattern: None,
+        category: None,
+        comment: None,
+        typecode: None,
+        performance: None,
+    };
+    let Some((register, Compiled { categories, .. })) =
+        PATTERNS.registers.iter().zip(COMPILED.iter()).find(
+            |(_, Compiled { range, .. })| {
+                range.as_ref().is_some_and(|r| r.contains(&icao24))
+            },
+        )
+    else {
+        return info;
+    };
+    info.country.clone_from(&register.country);
+    info.flag.clone_from(&register.flag);
+    info.pattern.clone_from(&register.pattern);
+    info.comment.clone_from(&register.comment);
+
+    let category = info.registration.as_ref().and_then(|tail| {
+        let index = categories.iter().position(|re| re.is_match(tail))?;
+        register.categories.as_ref()?.get(index)
+    });
+    if let Some(category) = category {
+        info.pattern = Some(category.pattern.clone());
+        info.category.clone_from(&category.category);
+        if let Some(country) = &category.country {
+            info.country.clone_from(country);
+        }
+        if let Some(flag) = &category.flag {
+            info.flag.clone_from(flag);
+        }
+    }
+    info
+}
+
 #[cfg(test)]
 mod tests {
-    use super::PATTERNS;
+    use super::{aircraft_information, allocation, Allocation, PATTERNS};
 
     #[test]
     fn test_find_country() {
@@ -44,4 +268,45 @@ mod tests {
         }
         unreachable!()
     }
+
+    #[test]
+    fn test_aircraft_information() {
+        let info = aircraft_information(0x39b415, Some("F-HNAV"));
+        assert_eq!(info.country, "France");
+        assert_eq!(info.registration.as_deref(), Some("F-HNAV"));
+        assert_eq!(info.pattern.as_deref(), Some("^F-"));
+        assert!(info.category.is_none());
+
+        let info = aircraft_information(0x39b415, Some("F-CAHE"));
+        assert_eq!(info.pattern.as_deref(), Some("^F-C"));
+        assert_eq!(info.category.as_deref(), Some("Gliders"));
+
+        // The registration is inferred for US aircraft
+        let info = aircraft_information(0xa43e7f, None);
+        assert_eq!(info.country, "United States");
+        assert_eq!(info.registration.as_deref(), Some("N37263"));
+
+        let info = aircraft_information(0x000001, None);
+        assert_eq!(info.country, "Unknown");
+
+        assert_eq!(info.allocation, Allocation::Unallocated);
+
+        let info =
+            aircraft_information(0x39b415, None).with_typecode(Some("A320"));
+        assert_eq!(info.typecode.as_deref(), Some("A320"));
+        assert_eq!(info.performance.unwrap().engines, 2);
+    }
+
+    #[test]
+    fn test_allocation() {
+        assert_eq!(allocation(0x39b415), Allocation::Civil);
+        assert_eq!(allocation(0x3b7a5d), Allocation::Military);
+        assert_eq!(allocation(0xae1234), Allocation::Military);
+        assert_eq!(allocation(0xa43e7f), Allocation::Civil);
+        // Special use blocks within the blocks of countries
+        assert_eq!(allocation(0xf09100), Allocation::Special);
+        assert_eq!(allocation(0xf00001), Allocation::Special);
+        assert_eq!(allocation(0xf80000), Allocation::Unallocated);
+        assert_eq!(allocation(0xb01234), Allocation::Unallocated);
+    }
 }
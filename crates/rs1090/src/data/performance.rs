@@ -0,0 +1,138 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Margin (in ft) above the service ceiling for plausible altitudes
+const CEILING_MARGIN: f64 = 2000.;
+/// Plausible ground speeds are below this factor of the cruise speed, plus
+/// [`WIND_MARGIN`] for strong tailwinds
+const SPEED_FACTOR: f64 = 1.3;
+/// Margin (in kts) for strong tailwinds
+const WIND_MARGIN: f64 = 120.;
+
+/// The ICAO wake turbulence category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum WakeTurbulence {
+    #[serde(rename = "L")]
+    Light,
+    #[serde(rename = "M")]
+    Medium,
+    #[serde(rename = "H")]
+    Heavy,
+    /// The Airbus A380
+    #[serde(rename = "J")]
+    Super,
+}
+
+/// The type of engines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    Jet,
+    /// Turboprop and turboshaft (helicopters)
+    Turboprop,
+    Piston,
+}
+
+/// The performance class of a type of aircraft
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Performance {
+    /// The wake turbulence category (WTC)
+    pub wtc: WakeTurbulence,
+    pub engine: Engine,
+    /// The number of engines
+    pub engines: u8,
+    /// The typical cruise speed (TAS in kts)
+    pub cruise_speed: u16,
+    /// The service ceiling (in ft)
+    pub ceiling: u32,
+}
+
+impl Performance {
+    /**
+     * True if an altitude (in ft) and a ground speed (in kts) are plausible
+     * for this performance class, allowing for strong tailwinds.
+     */
+    pub fn plausible(
+        &self,
+        altitude: Option<f64>,
+        groundspeed: Option<f64>,
+    ) -> bool {
+        let max_speed = self.cruise_speed as f64 * SPEED_FACTOR + WIND_MARGIN;
+        altitude.is_none_or(|alt| alt <= self.ceiling as f64 + CEILING_MARGIN)
+            && groundspeed.is_none_or(|gs| gs <= max_speed)
+    }
+}
+
+const PERFORMANCE_CSV: &str = include_str!("../../data/performance.csv");
+
+/// The performance classes of common ICAO type designators
+pub static PERFORMANCE: Lazy<HashMap<&'static str, Performance>> =
+    Lazy::new(|| {
+        PERFORMANCE_CSV
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(',').collect();
+                let [typecode, wtc, engine, engines, cruise_speed, ceiling] =
+                    fields[..]
+                else {
+                    return None;
+                };
+                let wtc = match wtc {
+                    "L" => WakeTurbulence::Light,
+                    "M" => WakeTurbulence::Medium,
+                    "H" => WakeTurbulence::Heavy,
+                    "J" => WakeTurbulence::Super,
+                    _ => return None,
+                };
+                let engine = match engine {
+                    "jet" => Engine::Jet,
+                    "turboprop" => Engine::Turboprop,
+                    "piston" => Engine::Piston,
+                    _ => return None,
+                };
+                let performance = Performance {
+                    wtc,
+                    engine,
+                    engines: engines.parse().ok()?,
+                    cruise_speed: cruise_speed.parse().ok()?,
+                    ceiling: ceiling.parse().ok()?,
+                };
+                Some((typecode, performance))
+            })
+            .collect()
+    });
+
+/// The performance class of an ICAO type designator (e.g. A320 or B77W)
+pub fn performance(typecode: &str) -> Option<Performance> {
+    PERFORMANCE.get(typecode.to_uppercase().as_str()).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_performance() {
+        // All the lines are parsed
+        let lines = PERFORMANCE_CSV.lines().count() - 1;
+        assert_eq!(PERFORMANCE.len(), lines);
+
+        let a320 = performance("A320").unwrap();
+        assert_eq!(a320.wtc, WakeTurbulence::Medium);
+        assert_eq!((a320.engine, a320.engines), (Engine::Jet, 2));
+        assert_eq!(performance("a388").unwrap().wtc, WakeTurbulence::Super);
+        assert_eq!(performance("C172").unwrap().engine, Engine::Piston);
+        assert_eq!(performance("ZZZZ"), None);
+
+        assert!(a320.plausible(Some(38000.), Some(650.)));
+        assert!(a320.plausible(None, None));
+        assert!(!a320.plausible(Some(51000.), None));
+        let c172 = performance("C172").unwrap();
+        assert!(!c172.plausible(Some(3000.), Some(450.)));
+    }
+}
@@ -1,7 +1,7 @@
 use super::bds::{bds05, bds06, bds08, bds09, bds61, bds62, bds65};
 use super::{Capability, ICAO};
 use deku::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /**
@@ -15,7 +15,8 @@ use std::fmt;
  *
  */
 
-#[derive(Debug, PartialEq, DekuRead, Clone, Serialize)]
+#[derive(Debug, PartialEq, DekuRead, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ADSB {
     /// The transponder capability
     #[serde(skip)]
@@ -31,11 +32,26 @@ pub struct ADSB {
     /// Parity/Interrogator ID
     #[serde(skip)]
     pub parity: ICAO,
+
+    /// True for the content of a military extended squitter (DF19 AF=0),
+    /// see [`Message::military`](super::Message::military)
+    #[deku(skip, default = "false")]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub military: bool,
+
+    /// True if the decoded position is farther from the receiver than a
+    /// maximum range, as flagged by the application (not set by decoding)
+    #[deku(skip, default = "false")]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub out_of_range: bool,
 }
 
 impl fmt::Display for ADSB {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, " DF17. Extended Squitter")?;
+        match self.military {
+            true => writeln!(f, " DF19. Military Extended Squitter")?,
+            false => writeln!(f, " DF17. Extended Squitter")?,
+        }
         writeln!(f, "  Address:       {}", &self.icao24)?;
         writeln!(f, "  Air/Ground:    {}", &self.capability)?;
         write!(f, "{}", &self.message)
@@ -64,14 +80,16 @@ impl fmt::Display for ADSB {
 * | 31       | [`bds65::AircraftOperationStatus`]                |
 */
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Unused {
     #[deku(skip, pad_bits_after = "48", default = "true")]
     #[serde(skip)]
     unused: bool,
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "5")]
 //#[serde(untagged)]
 #[serde(tag = "bds")]
@@ -1,8 +1,7 @@
 #![allow(clippy::suspicious_else_formatting)]
 
 use deku::prelude::*;
-use serde::ser::SerializeStruct;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /**
@@ -25,7 +24,8 @@ use std::fmt;
  * subtypes 2 and 4 at this moment.
  *
  */
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct AirborneVelocity {
     #[deku(bits = "3")]
     #[serde(skip)]
@@ -106,7 +106,8 @@ fn read_geobaro<R: deku::no_std_io::Read + deku::no_std_io::Seek>(
     Ok(value)
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(ctx = "subtype: u8", id = "subtype")]
 #[serde(untagged)]
 pub enum AirborneVelocitySubType {
@@ -125,9 +126,10 @@ pub enum AirborneVelocitySubType {
     Reserved1(#[deku(bits = "22")] u32),
 }
 
-#[derive(Debug, PartialEq, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, DekuRead, Copy, Clone, Default)]
 #[deku(id_type = "u8", bits = "1")]
 pub enum Sign {
+    #[default]
     Positive = 0,
     Negative = 1,
 }
@@ -155,7 +157,8 @@ impl fmt::Display for Sign {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GroundSpeedDecoding {
     #[serde(skip)]
     pub ew_sign: Sign,
@@ -195,6 +198,52 @@ pub struct GroundSpeedDecoding {
     pub track: f64,
 }
 
+/// The serialized form of airspeed decodings
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct Airspeed<T> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heading: Option<T>,
+    #[serde(rename = "IAS", skip_serializing_if = "Option::is_none")]
+    ias: Option<u16>,
+    #[serde(rename = "TAS", skip_serializing_if = "Option::is_none")]
+    tas: Option<u16>,
+}
+
+impl<T> Airspeed<T> {
+    fn new(
+        heading: Option<T>,
+        airspeed_type: AirspeedType,
+        airspeed: Option<u16>,
+    ) -> Self {
+        let (ias, tas) = match airspeed_type {
+            AirspeedType::IAS => (airspeed, None),
+            AirspeedType::TAS => (None, airspeed),
+        };
+        Airspeed { heading, ias, tas }
+    }
+
+    /// The type of airspeed and its value (IAS if unknown)
+    fn airspeed(&self) -> (AirspeedType, Option<u16>) {
+        match self.tas {
+            Some(tas) => (AirspeedType::TAS, Some(tas)),
+            None => (AirspeedType::IAS, self.ias),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for AirspeedSubsonicDecoding {
+    fn schema_name() -> String {
+        "AirspeedSubsonicDecoding".to_string()
+    }
+    fn json_schema(
+        gen: &mut schemars::gen::SchemaGenerator,
+    ) -> schemars::schema::Schema {
+        <Airspeed<f64> as schemars::JsonSchema>::json_schema(gen)
+    }
+}
+
 #[derive(Debug, PartialEq, DekuRead, Clone)]
 pub struct AirspeedSubsonicDecoding {
     #[deku(bits = "1")]
@@ -227,21 +276,36 @@ impl Serialize for AirspeedSubsonicDecoding {
     where
         S: serde::ser::Serializer,
     {
-        let mut state = serializer.serialize_struct("Message", 2)?;
-        if let Some(heading) = &self.heading {
-            state.serialize_field("heading", heading)?;
-        }
-        if let Some(airspeed) = &self.airspeed {
-            match &self.airspeed_type {
-                AirspeedType::IAS => {
-                    state.serialize_field("IAS", &airspeed)?;
-                }
-                AirspeedType::TAS => {
-                    state.serialize_field("TAS", &airspeed)?;
-                }
-            }
-        }
-        state.end()
+        Airspeed::new(self.heading, self.airspeed_type, self.airspeed)
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AirspeedSubsonicDecoding {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        let value = Airspeed::<f64>::deserialize(deserializer)?;
+        let (airspeed_type, airspeed) = value.airspeed();
+        Ok(AirspeedSubsonicDecoding {
+            status_heading: value.heading.is_some(),
+            heading: value.heading,
+            airspeed_type,
+            airspeed,
+        })
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for AirspeedSupersonicDecoding {
+    fn schema_name() -> String {
+        "AirspeedSupersonicDecoding".to_string()
+    }
+    fn json_schema(
+        gen: &mut schemars::gen::SchemaGenerator,
+    ) -> schemars::schema::Schema {
+        <Airspeed<f32> as schemars::JsonSchema>::json_schema(gen)
     }
 }
 
@@ -277,21 +341,24 @@ impl Serialize for AirspeedSupersonicDecoding {
     where
         S: serde::ser::Serializer,
     {
-        let mut state = serializer.serialize_struct("Message", 2)?;
-        if let Some(heading) = &self.heading {
-            state.serialize_field("heading", heading)?;
-        }
-        if let Some(airspeed) = &self.airspeed {
-            match &self.airspeed_type {
-                AirspeedType::IAS => {
-                    state.serialize_field("IAS", &airspeed)?;
-                }
-                AirspeedType::TAS => {
-                    state.serialize_field("TAS", &airspeed)?;
-                }
-            }
-        }
-        state.end()
+        Airspeed::new(self.heading, self.airspeed_type, self.airspeed)
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AirspeedSupersonicDecoding {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        let value = Airspeed::<f32>::deserialize(deserializer)?;
+        let (airspeed_type, airspeed) = value.airspeed();
+        Ok(AirspeedSupersonicDecoding {
+            status_heading: value.heading.is_some(),
+            heading: value.heading,
+            airspeed_type,
+            airspeed,
+        })
     }
 }
 
@@ -329,7 +396,8 @@ pub enum DirectionNS {
     NorthToSouth = 1,
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "1")]
 pub enum VerticalRateSource {
     #[serde(rename = "barometric")]
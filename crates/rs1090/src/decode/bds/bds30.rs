@@ -1,5 +1,5 @@
 use deku::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::decode::{AC13Field, ICAO};
 
@@ -10,7 +10,8 @@ use crate::decode::{AC13Field, ICAO};
  * ACAS equipment.
  */
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "bds", rename = "30")]
 pub struct ACASResolutionAdvisory {
     #[deku(bits = "8", map = "fail_if_not30")]
@@ -141,10 +142,18 @@ pub struct ACASResolutionAdvisory {
     pub threat_type: ThreatType,
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "2")]
 #[serde(untagged)]
 pub enum ThreatType {
+    // Variants with data come first, so that they are deserialized
+    #[deku(id = "1")]
+    ThreatAddress(ThreadAddress),
+
+    #[deku(id = "2")]
+    ThreatOrientation(ThreatOrientation),
+
     #[deku(id = "0")]
     NoIdentity {
         #[deku(bits = "26")]
@@ -152,12 +161,6 @@ pub enum ThreatType {
         unused: u32,
     },
 
-    #[deku(id = "1")]
-    ThreatAddress(ThreadAddress),
-
-    #[deku(id = "2")]
-    ThreatOrientation(ThreatOrientation),
-
     #[deku(id = "3")]
     NotAssigned {
         #[deku(bits = "26")]
@@ -166,7 +169,8 @@ pub enum ThreatType {
     },
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ThreadAddress {
     /// Threat identity data (icao24).
     pub threat_identity: ICAO,
@@ -176,11 +180,12 @@ pub struct ThreadAddress {
     pub zeros: u8,
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ThreatOrientation {
     /// Altitude code on 13 bits
     #[serde(rename = "threat_altitude")]
-    altitude: AC13Field,
+    pub altitude: AC13Field,
 
     #[deku(
         bits = "7",
@@ -190,7 +195,7 @@ pub struct ThreatOrientation {
     )]
     /// Most recent threat range from ACAS (max 12.55 nautical miles)
     #[serde(rename = "threat_range")]
-    range: Option<f32>,
+    pub range: Option<f32>,
 
     #[deku(
         bits = "6",
@@ -201,7 +206,7 @@ pub struct ThreatOrientation {
     /// Most recent estimated bearing of the threat aircraft,
     /// relative to their own heading (3 degree precision)
     #[serde(rename = "threat_bearing")]
-    bearing: Option<u16>,
+    pub bearing: Option<u16>,
 }
 
 fn fail_if_not30(value: u8) -> Result<u8, DekuError> {
@@ -1,7 +1,7 @@
 #![allow(clippy::suspicious_else_formatting)]
 
 use deku::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 /**
  * ## Selected vertical intention (BDS 4,0)
@@ -12,7 +12,8 @@ use serde::Serialize;
  * with an altitude command.
  */
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "bds", rename = "40")]
 pub struct SelectedVerticalIntention {
     #[deku(reader = "read_selected(deku::reader)")]
@@ -65,16 +66,21 @@ pub struct SelectedVerticalIntention {
     pub source_status: bool,
     #[serde(
         rename = "target_source",
+        default,
         skip_serializing_if = "TargetSource::is_unknown"
     )]
     /// Target altitude source
     pub target_altitude_source: TargetSource,
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(
+    Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone, Default,
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "2")]
 pub enum TargetSource {
     #[deku(id = "0")]
+    #[default]
     Unknown,
     #[deku(id = "1")]
     AircraftAltitude,
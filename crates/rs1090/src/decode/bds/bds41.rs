@@ -0,0 +1,114 @@
+use deku::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, trace};
+
+/**
+ * ## Next waypoint identifier (BDS 4,1)
+ *
+ * The name of the next waypoint in the flight plan of the aircraft, as
+ * entered in the flight management system, up to nine characters.
+ *
+ * | Bits  | Content                           |
+ * | ----- | --------------------------------- |
+ * | 1     | Status                            |
+ * | 2-55  | Characters 1 to 9 (6 bits each)   |
+ * | 56    | Reserved                          |
+ */
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "bds", rename = "41")]
+pub struct NextWaypointIdentifier {
+    #[deku(bits = "1", map = "fail_if_false")]
+    #[serde(skip)]
+    pub status: bool,
+
+    #[deku(reader = "waypoint_read(deku::reader)")]
+    /// The name of the next waypoint
+    pub waypoint: String,
+
+    #[deku(bits = "1", map = "fail_if_not_zero")]
+    #[serde(skip)]
+    pub reserved: u8,
+}
+
+const CHAR_LOOKUP: &[u8; 64] =
+    b"#ABCDEFGHIJKLMNOPQRSTUVWXYZ##### ###############0123456789######";
+
+fn waypoint_read<R: deku::no_std_io::Read + deku::no_std_io::Seek>(
+    reader: &mut Reader<R>,
+) -> Result<String, DekuError> {
+    let mut encoded = String::new();
+    for _ in 0..9 {
+        let c = u8::from_reader_with_ctx(reader, deku::ctx::BitSize(6))?;
+        trace!("Reading letter {}", CHAR_LOOKUP[c as usize] as char);
+        encoded.push(CHAR_LOOKUP[c as usize] as char);
+    }
+    debug!("Decoded waypoint: {}", encoded);
+
+    // Letters and digits, padded with trailing spaces
+    let waypoint = encoded.trim_end();
+    let valid = waypoint.len() >= 2
+        && waypoint.chars().all(|c| c.is_ascii_alphanumeric());
+    if valid {
+        Ok(waypoint.to_string())
+    } else {
+        Err(DekuError::Assertion(
+            format!("Invalid waypoint identifier {}", encoded).into(),
+        ))
+    }
+}
+
+fn fail_if_false(value: bool) -> Result<bool, DekuError> {
+    if value {
+        Ok(value)
+    } else {
+        Err(DekuError::Assertion(
+            "Status bit must be set in BDS 4,1".into(),
+        ))
+    }
+}
+
+fn fail_if_not_zero(value: u8) -> Result<u8, DekuError> {
+    if value == 0 {
+        Ok(value)
+    } else {
+        Err(DekuError::Assertion("Reserved bits must be zero".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use hexlit::hex;
+
+    #[test]
+    fn test_valid_bds41() {
+        let bytes = hex!("a00018389c0a4097041040000000");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        if let CommBAltitudeReply { bds, .. } = msg.df {
+            let NextWaypointIdentifier { waypoint, .. } = bds.bds41.unwrap();
+            assert_eq!(waypoint, "NARAK");
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn test_invalid_bds41() {
+        // BDS 2,0 (the status bit is not set)
+        let bytes = hex!("a0001838201584f23468207cdfa5");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        if let CommBAltitudeReply { bds, .. } = msg.df {
+            assert_eq!(bds.bds41, None);
+        } else {
+            unreachable!();
+        }
+        // Spaces in the middle of the name
+        assert!(NextWaypointIdentifier::try_from(
+            hex!("9c0c09025c1040").as_slice()
+        )
+        .is_err());
+    }
+}
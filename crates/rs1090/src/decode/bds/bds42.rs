@@ -0,0 +1,155 @@
+use deku::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+
+/**
+ * ## Next waypoint position (BDS 4,2)
+ *
+ * The position and crossing altitude of the next waypoint in the flight plan
+ * of the aircraft.
+ *
+ * | Bits  | Content                                                  |
+ * | ----- | -------------------------------------------------------- |
+ * | 1-20  | Status, sign and latitude (LSB = 90/131072°)             |
+ * | 21-40 | Status, sign and longitude (LSB = 90/131072°)            |
+ * | 41-56 | Status, sign and crossing altitude (LSB = 8 ft)          |
+ */
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "bds", rename = "42")]
+pub struct NextWaypointPosition {
+    #[deku(reader = "read_coordinate(deku::reader, 90.)")]
+    /// Latitude of the next waypoint (in degrees)
+    pub latitude: f64,
+
+    #[deku(reader = "read_coordinate(deku::reader, 180.)")]
+    /// Longitude of the next waypoint (in degrees)
+    pub longitude: f64,
+
+    #[deku(reader = "read_altitude(deku::reader)")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Crossing altitude at the next waypoint (in ft)
+    pub altitude: Option<i32>,
+}
+
+fn read_coordinate<R: deku::no_std_io::Read + deku::no_std_io::Seek>(
+    reader: &mut Reader<R>,
+    max: f64,
+) -> Result<f64, DekuError> {
+    let status = bool::from_reader_with_ctx(
+        reader,
+        (deku::ctx::Endian::Big, deku::ctx::BitSize(1)),
+    )?;
+    let sign = bool::from_reader_with_ctx(
+        reader,
+        (deku::ctx::Endian::Big, deku::ctx::BitSize(1)),
+    )?;
+    let value = u32::from_reader_with_ctx(
+        reader,
+        (deku::ctx::Endian::Big, deku::ctx::BitSize(18)),
+    )?;
+
+    let value = match sign {
+        true => value as i32 - (1 << 18),
+        false => value as i32,
+    };
+    let coordinate = value as f64 * 90. / 131_072.;
+
+    trace!("Reading coordinate status {} value {}", status, coordinate);
+
+    if !status {
+        return Err(DekuError::Assertion(
+            "Waypoint position must be available in BDS 4,2".into(),
+        ));
+    }
+    if coordinate.abs() > max {
+        let msg =
+            format!("Invalid coordinate {}° outside ±{}°", coordinate, max);
+        return Err(DekuError::Assertion(msg.into()));
+    }
+    Ok(coordinate)
+}
+
+fn read_altitude<R: deku::no_std_io::Read + deku::no_std_io::Seek>(
+    reader: &mut Reader<R>,
+) -> Result<Option<i32>, DekuError> {
+    let status = bool::from_reader_with_ctx(
+        reader,
+        (deku::ctx::Endian::Big, deku::ctx::BitSize(1)),
+    )?;
+    let sign = bool::from_reader_with_ctx(
+        reader,
+        (deku::ctx::Endian::Big, deku::ctx::BitSize(1)),
+    )?;
+    let value = u32::from_reader_with_ctx(
+        reader,
+        (deku::ctx::Endian::Big, deku::ctx::BitSize(14)),
+    )?;
+
+    let altitude = match sign {
+        true => (value as i32 - (1 << 14)) * 8,
+        false => value as i32 * 8,
+    };
+
+    trace!("Reading altitude status {} value {}", status, altitude);
+
+    match (status, value) {
+        (true, _) if (-1000..=50000).contains(&altitude) => Ok(Some(altitude)),
+        (true, _) => {
+            let msg = format!(
+                "Invalid altitude {} ft outside [-1000, 50000]",
+                altitude
+            );
+            Err(DekuError::Assertion(msg.into()))
+        }
+        (false, 0) if !sign => Ok(None),
+        (false, _) => {
+            Err(DekuError::Assertion("Invalid altitude value".into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use approx::assert_relative_eq;
+    use hexlit::hex;
+
+    #[test]
+    fn test_valid_bds42() {
+        let bytes = hex!("a00018388f833807c88271000000");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        if let CommBAltitudeReply { bds, .. } = msg.df {
+            let NextWaypointPosition {
+                latitude,
+                longitude,
+                altitude,
+            } = bds.bds42.unwrap();
+            assert_relative_eq!(latitude, 43.6289, max_relative = 1e-4);
+            assert_relative_eq!(longitude, 1.3678, max_relative = 1e-3);
+            assert_eq!(altitude, Some(5000));
+        } else {
+            unreachable!();
+        }
+
+        // Negative coordinates and altitude
+        let waypoint =
+            NextWaypointPosition::try_from(hex!("f3ee2b5c08ffe7").as_slice())
+                .unwrap();
+        assert_relative_eq!(waypoint.latitude, -33.9461, max_relative = 1e-4);
+        assert_relative_eq!(waypoint.longitude, 151.1772, max_relative = 1e-4);
+        assert_eq!(waypoint.altitude, Some(-200));
+    }
+
+    #[test]
+    fn test_invalid_bds42() {
+        // Latitude beyond 90°
+        let payload = hex!("bf833807c88271");
+        assert!(NextWaypointPosition::try_from(payload.as_slice()).is_err());
+        // Altitude without a status bit
+        let payload = hex!("8f833807c80271");
+        assert!(NextWaypointPosition::try_from(payload.as_slice()).is_err());
+    }
+}
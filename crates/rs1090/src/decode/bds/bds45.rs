@@ -1,12 +1,13 @@
 use deku::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tracing::trace;
 
 /**
  * ## Meteorological Hazard Report (BDS 4,5)
  */
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "bds", rename = "45")]
 pub struct MeteorologicalHazardReport {
     #[deku(reader = "read_level(deku::reader)")]
@@ -46,7 +47,8 @@ pub struct MeteorologicalHazardReport {
     pub reserved: u8,
 }
 
-#[derive(Debug, PartialEq, Serialize, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum Level {
     Nil,
     Light,
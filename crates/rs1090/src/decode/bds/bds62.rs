@@ -1,13 +1,14 @@
 #![allow(clippy::suspicious_else_formatting)]
 
 use deku::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /**
  * ## Target State and Status Information (BDS 6,2)
  */
-#[derive(Copy, Clone, Debug, Serialize, PartialEq, DekuRead)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, DekuRead)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TargetStateAndStatusInformation {
     #[deku(bits = "2")] // bits 5..=6
     #[serde(skip)]
@@ -206,7 +207,8 @@ impl fmt::Display for TargetStateAndStatusInformation {
     }
 }
 
-#[derive(Copy, Clone, Debug, Serialize, PartialEq, DekuRead)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, DekuRead)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "1")]
 /// Encode the source of information for selected altitude
 pub enum AltSource {
@@ -1,5 +1,5 @@
 use deku::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /**
@@ -13,7 +13,8 @@ use std::fmt;
  *
  */
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "3")]
 #[serde(untagged)]
 pub enum AircraftOperationStatus {
@@ -38,14 +39,15 @@ impl fmt::Display for AircraftOperationStatus {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct OperationStatusAirborne {
     /// The capacity class
-    #[serde(skip)]
+    #[serde(flatten)]
     pub capability_class: CapabilityClassAirborne,
 
     /// The operational mode
-    #[serde(skip)]
+    #[serde(flatten)]
     pub operational_mode: OperationalMode,
 
     #[deku(pad_bytes_before = "1")]
@@ -62,18 +64,21 @@ impl fmt::Display for OperationStatusAirborne {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CapabilityClassAirborne {
     #[deku(bits = "2", assert_eq = "0")]
     #[serde(skip)]
     pub reserved0: u8,
 
-    /// TCAS Resolution Advisory Active
+    /// TCAS operational (in version 1, the bit is set if TCAS is *not*
+    /// operational)
     #[deku(bits = "1")]
     #[serde(rename = "ACAS")]
     pub acas: bool,
 
-    /// Cockpit Display of Traffic Information
+    /// Cockpit Display of Traffic Information (version 1), or ADS-B 1090ES
+    /// Receive Capability (version 2)
     #[deku(bits = "1")]
     #[serde(rename = "CDTI")]
     pub cdti: bool,
@@ -93,7 +98,6 @@ pub struct CapabilityClassAirborne {
     pub ts: bool,
 
     #[deku(bits = "2")]
-    #[deku(pad_bits_after = "6")] //reserved
     #[serde(rename = "TC")]
     /// Target Trajectory Change Report Capability
     /// - 0: No capability for Trajectory Change Reports
@@ -101,6 +105,12 @@ pub struct CapabilityClassAirborne {
     /// - 2: Support for multiple TC reports
     /// - 3: Reserved
     pub tc: u8,
+
+    /// Aircraft has ADS-B UAT Receive Capability (version 2)
+    #[deku(bits = "1")]
+    #[deku(pad_bits_after = "5")] // reserved
+    #[serde(rename = "UATin")]
+    pub uat_in: bool,
 }
 
 impl fmt::Display for CapabilityClassAirborne {
@@ -120,29 +130,40 @@ impl fmt::Display for CapabilityClassAirborne {
         if self.tc == 1 {
             write!(f, " TC")?;
         }
+        if self.uat_in {
+            write!(f, " UATin")?;
+        }
         Ok(())
     }
 }
 
 /// Version 2 support only
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct OperationStatusSurface {
     /// The capacity class
-    #[serde(skip)]
+    #[serde(flatten)]
     pub capability_class: CapabilityClassSurface,
 
-    /// The capacity class L/W codes
+    /// The aircraft/vehicle length and width codes (2.2.3.2.7.2.11), from
+    /// 0 (no data) and 1 (less than 15 m long, 23 m wide) to 15 (more than
+    /// 85 m long, 90 m wide)
     #[deku(bits = "4")]
-    #[serde(skip)]
+    #[serde(rename = "LW")]
     pub lw_codes: u8,
 
     /// The operational mode
-    #[serde(skip)]
+    #[serde(flatten)]
     pub operational_mode: OperationalMode,
 
     /// The GPS antenna offset (2.2.3.2.7.2.4.7).
     /// Reference: <http://www.anteni.net/adsb/Doc/1090-WP30-18-DRAFT_DO-260B-V42.pdf>
-    #[serde(skip)]
+    ///
+    /// - bits 1-3: lateral offset, left (0) or right (1), then 0, 2, 4 or
+    ///   6 m from the longitudinal axis of the aircraft
+    /// - bits 4-8: longitudinal offset, 0 for no data, 1 if the offset is
+    ///   applied by the sensor, then 2 m steps aft of the nose
+    #[serde(rename = "GPSA")]
     pub gps_antenna_offset: u8,
 
     #[serde(flatten)]
@@ -158,7 +179,8 @@ impl fmt::Display for OperationStatusSurface {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CapabilityClassSurface {
     #[deku(bits = "2", assert_eq = "0")]
     #[serde(skip)]
@@ -166,6 +188,7 @@ pub struct CapabilityClassSurface {
 
     /// Position Offset Applied
     #[deku(bits = "1")]
+    #[serde(rename = "POA")]
     pub poe: bool,
 
     /// Aircraft has ADS-B 1090ES Receive Capability
@@ -197,33 +220,57 @@ pub struct CapabilityClassSurface {
 
 impl fmt::Display for CapabilityClassSurface {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "   Capability classes:")?;
+        if self.poe {
+            write!(f, " POA")?;
+        }
+        if self.es1090 {
+            write!(f, " 1090ES")?;
+        }
+        if self.b2_low {
+            write!(f, " GRND")?;
+        }
+        if self.uat_in {
+            write!(f, " UATin")?;
+        }
+        writeln!(f)?;
         writeln!(f, "   NICc:               {}", self.nic_c)?;
         writeln!(f, "   NACv:               {}", self.nac_v)?;
         Ok(())
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct OperationalMode {
     #[deku(bits = "2", assert_eq = "0")]
     #[serde(skip)]
-    reserved: u8,
+    pub reserved: u8,
 
     /// TCAS RA active
     #[deku(bits = "1")]
-    tcas_ra_active: bool,
+    #[serde(rename = "RA")]
+    pub tcas_ra_active: bool,
 
+    /// IDENT switch active
     #[deku(bits = "1")]
-    ident_switch_active: bool,
+    #[serde(rename = "IDENT")]
+    pub ident_switch_active: bool,
 
+    /// Receiving ATC services
     #[deku(bits = "1")]
-    reserved_recv_atc_service: bool,
+    #[serde(rename = "ATC")]
+    pub reserved_recv_atc_service: bool,
 
+    /// Single Antenna Flag: set if only one antenna is transmitting
     #[deku(bits = "1")]
-    single_antenna_flag: bool,
+    #[serde(rename = "SAF")]
+    pub single_antenna_flag: bool,
 
+    /// System Design Assurance (SDA), only for version 2
     #[deku(bits = "2")]
-    system_design_assurance: u8,
+    #[serde(rename = "SDA")]
+    pub system_design_assurance: u8,
 }
 
 impl fmt::Display for OperationalMode {
@@ -254,7 +301,8 @@ impl fmt::Display for OperationalMode {
 /// (specification defined in RTCA document DO-260). Version 1 was introduced
 /// around 2008 (DO-260A), and version 2 around 2012 (DO-260B). Version 3 is
 /// currently being developed.
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "3")]
 #[serde(tag = "version")]
 pub enum ADSBVersionAirborne {
@@ -275,7 +323,8 @@ pub enum ADSBVersionAirborne {
     Reserved { id: u8 },
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct AirborneV1 {
     #[deku(bits = "1")]
     #[serde(rename = "NICs")]
@@ -309,7 +358,8 @@ pub struct AirborneV1 {
     pub horizontal_reference_direction: u8,
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct AirborneV2 {
     #[deku(bits = "1")]
     #[serde(rename = "NICa")]
@@ -357,7 +407,8 @@ pub struct AirborneV2 {
 /// (specification defined in RTCA document DO-260). Version 1 was introduced
 /// around 2008 (DO-260A), and version 2 around 2012 (DO-260B). Version 3 is
 /// currently being developed.
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "3")]
 #[serde(tag = "version")]
 pub enum ADSBVersionSurface {
@@ -378,7 +429,8 @@ pub enum ADSBVersionSurface {
     Reserved { id: u8 },
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SurfaceV1 {
     #[deku(bits = "1")]
     #[serde(rename = "NICs")]
@@ -398,6 +450,7 @@ pub struct SurfaceV1 {
 
     #[deku(bits = "1")]
     #[serde(rename = "TAH")]
+    /// Track angle (0) or heading (1) reported in surface positions
     pub track_angle_or_heading: u8,
 
     #[deku(bits = "1")]
@@ -407,7 +460,8 @@ pub struct SurfaceV1 {
     pub horizontal_reference_direction: u8,
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SurfaceV2 {
     #[deku(bits = "1")]
     #[serde(rename = "NICa")]
@@ -427,6 +481,7 @@ pub struct SurfaceV2 {
 
     #[deku(bits = "1")]
     #[serde(rename = "TAH")]
+    /// Track angle (0) or heading (1) reported in surface positions
     pub track_angle_or_heading: u8,
 
     #[deku(bits = "1")]
@@ -443,11 +498,85 @@ pub struct SurfaceV2 {
     pub sil_supplement: u8,
 }
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Empty {}
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct EmptyU8 {
     pub id: u8,
     pub unused: u8,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use hexlit::hex;
+
+    #[test]
+    fn test_airborne_v2() {
+        let bytes = hex!("8d406b90f83160120049baedf9a0");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        let ExtendedSquitterADSB(adsb) = &msg.df else {
+            unreachable!()
+        };
+        let ME::BDS65(AircraftOperationStatus::Airborne(status)) = adsb.message
+        else {
+            unreachable!()
+        };
+        let cc = status.capability_class;
+        assert!(cc.acas && cc.cdti && cc.ts && cc.uat_in && !cc.arv);
+        assert_eq!(cc.tc, 1);
+        let om = status.operational_mode;
+        assert!(om.ident_switch_active && !om.tcas_ra_active);
+        assert_eq!(om.system_design_assurance, 2);
+
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["version"], "2");
+        assert_eq!(json["ACAS"], true);
+        assert_eq!(json["UATin"], true);
+        assert_eq!(json["TC"], 1);
+        assert_eq!(json["IDENT"], true);
+        assert_eq!(json["SDA"], 2);
+        assert_eq!(json["NACp"], 9);
+        assert_eq!(json["GVA"], 2);
+        assert_eq!(json["SIL"], 3);
+        assert_eq!(json["BAI"], 1);
+        assert_eq!(json["SILs"], 1);
+    }
+
+    #[test]
+    fn test_surface_v2() {
+        let bytes = hex!("8d406b90f9115506a35a3897cd23");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        let ExtendedSquitterADSB(adsb) = &msg.df else {
+            unreachable!()
+        };
+        let ME::BDS65(AircraftOperationStatus::Surface(status)) = adsb.message
+        else {
+            unreachable!()
+        };
+        let cc = status.capability_class;
+        assert!(cc.es1090 && cc.uat_in && !cc.poe && !cc.b2_low);
+        assert_eq!(status.lw_codes, 5);
+        assert!(status.operational_mode.single_antenna_flag);
+        assert_eq!(status.gps_antenna_offset, 0b1010_0011);
+
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["version"], "2");
+        assert_eq!(json["1090ES"], true);
+        assert_eq!(json["NACv"], 2);
+        assert_eq!(json["NICc"], 1);
+        assert_eq!(json["LW"], 5);
+        assert_eq!(json["SAF"], true);
+        assert_eq!(json["SDA"], 2);
+        assert_eq!(json["GPSA"], 163);
+        assert_eq!(json["NICa"], 1);
+        assert_eq!(json["NACp"], 10);
+        assert_eq!(json["SIL"], 3);
+        assert_eq!(json["TAH"], 1);
+        assert_eq!(json["HRD"], 0);
+        assert_eq!(json["SILs"], 0);
+    }
+}
@@ -10,6 +10,8 @@ pub mod bds20;
 pub mod bds21;
 pub mod bds30;
 pub mod bds40;
+pub mod bds41;
+pub mod bds42;
 pub mod bds44;
 pub mod bds45;
 pub mod bds50;
@@ -7,15 +7,19 @@ use super::bds::bds20::AircraftIdentification;
 use super::bds::bds21::AircraftAndAirlineRegistrationMarkings;
 use super::bds::bds30::ACASResolutionAdvisory;
 use super::bds::bds40::SelectedVerticalIntention;
+use super::bds::bds41::NextWaypointIdentifier;
+use super::bds::bds42::NextWaypointPosition;
 use super::bds::bds44::MeteorologicalRoutineAirReport;
 use super::bds::bds45::MeteorologicalHazardReport;
 use super::bds::bds50::TrackAndTurnReport;
 use super::bds::bds60::HeadingAndSpeedReport;
 use super::bds::bds65::AircraftOperationStatus;
-use super::AC13Field;
+use super::{AC13Field, DownlinkRequest, Message, DF};
 use deku::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU16, Ordering};
 use tracing::debug;
 
 /**
@@ -24,9 +28,18 @@ use tracing::debug;
  * The first four BDS codes (1,0, 1,7, 2,0, 3,0) belong to the ELS service,
  * the next three ones (4,0, 5,0, 6,0) belong to the EHS services,
  * and the last two codes (4,4, 4,5) report meteorological information.
+ * Registers 4,1 and 4,2 report the identifier and position of the next
+ * waypoint of the flight plan.
+ *
+ * The content of the register is not announced in the reply, and is inferred
+ * from the plausibility of the payload for each BDS code. When the downlink
+ * request (DR=4 or 5) announces a Comm-B broadcast message, only the
+ * broadcast registers (1,0, 2,0 and 3,0) are considered, unless none of them
+ * is plausible. Ambiguous payloads may be ranked with [`infer`].
  */
 
-#[derive(Debug, PartialEq, Serialize, Clone, Default)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DF20DataSelector {
     #[serde(skip)]
     /// Set to true if all zeros, then there is no need to parse
@@ -59,6 +72,12 @@ pub struct DF20DataSelector {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bds40: Option<SelectedVerticalIntention>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bds41: Option<NextWaypointIdentifier>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bds42: Option<NextWaypointPosition>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bds44: Option<MeteorologicalRoutineAirReport>,
 
@@ -75,7 +94,8 @@ pub struct DF20DataSelector {
     pub bds65: Option<AircraftOperationStatus>,
 }
 
-#[derive(Debug, PartialEq, Serialize, Clone, Default)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DF21DataSelector {
     #[serde(skip)]
     /// Set to true if all zeros, then there is no need to parse
@@ -108,6 +128,12 @@ pub struct DF21DataSelector {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bds40: Option<SelectedVerticalIntention>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bds41: Option<NextWaypointIdentifier>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bds42: Option<NextWaypointPosition>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bds44: Option<MeteorologicalRoutineAirReport>,
 
@@ -136,10 +162,128 @@ impl fmt::Display for DF20DataSelector {
     }
 }
 
-impl DekuReader<'_, AC13Field> for DF20DataSelector {
+/// The BDS registers which may be inferred, in the order of [`BdsPolicy`] bits
+const REGISTERS: [u8; 16] = [
+    5, 10, 17, 18, 19, 20, 21, 30, 40, 41, 42, 44, 45, 50, 60, 65,
+];
+
+/**
+ * The BDS registers attempted when inferring the content of Comm-B replies
+ * (DF20 and DF21).
+ *
+ * Each register is a hypothesis to validate, so that decoding all of them
+ * is wasteful on constrained hardware if only some are of interest. The
+ * string representation lists registers (e.g. `20,40,50,60`) or groups of
+ * registers: `els` (1,0, 1,7, 2,0 and 3,0), `ehs` (4,0, 5,0 and 6,0),
+ * `meteo` (4,4 and 4,5) or `all` (the default).
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct BdsPolicy(u16);
+
+impl BdsPolicy {
+    pub const ALL: BdsPolicy = BdsPolicy(u16::MAX >> (16 - REGISTERS.len()));
+
+    /// True if the register (e.g. 40 for BDS 4,0) is attempted
+    pub fn contains(&self, register: u8) -> bool {
+        REGISTERS
+            .iter()
+            .position(|&r| r == register)
+            .is_some_and(|i| self.0 & (1 << i) != 0)
+    }
+
+    fn with(self, registers: &[u8]) -> Self {
+        registers.iter().fold(self, |policy, register| {
+            match REGISTERS.iter().position(|r| r == register) {
+                Some(i) => BdsPolicy(policy.0 | 1 << i),
+                None => policy,
+            }
+        })
+    }
+}
+
+impl Default for BdsPolicy {
+    fn default() -> Self {
+        BdsPolicy::ALL
+    }
+}
+
+impl FromStr for BdsPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = BdsPolicy(0);
+        for item in s.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            policy = match item.to_ascii_lowercase().as_str() {
+                "all" => BdsPolicy::ALL,
+                "els" => policy.with(&[10, 17, 20, 30]),
+                "ehs" => policy.with(&[40, 50, 60]),
+                "meteo" => policy.with(&[44, 45]),
+                register => match register.trim_start_matches("bds").parse() {
+                    Ok(register) if REGISTERS.contains(&register) => {
+                        policy.with(&[register])
+                    }
+                    _ => {
+                        return Err(format!(
+                            "unknown BDS register: {item} (e.g. 20, els, ehs, meteo or all)"
+                        ))
+                    }
+                },
+            }
+        }
+        Ok(policy)
+    }
+}
+
+impl TryFrom<String> for BdsPolicy {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+static POLICY: AtomicU16 = AtomicU16::new(BdsPolicy::ALL.0);
+
+/// Set the BDS registers attempted when decoding Comm-B replies
+pub fn set_policy(policy: BdsPolicy) {
+    POLICY.store(policy.0, Ordering::Relaxed);
+}
+
+fn policy() -> BdsPolicy {
+    BdsPolicy(POLICY.load(Ordering::Relaxed))
+}
+
+/// The registers of Comm-B broadcast messages (BDS 1,0, 2,0 and 3,0)
+type Broadcast = (
+    Option<DataLinkCapability>,
+    Option<AircraftIdentification>,
+    Option<ACASResolutionAdvisory>,
+);
+
+/// Decode the payload of a Comm-B broadcast message, if plausible
+fn broadcast(buf: &[u8], policy: BdsPolicy) -> Option<Broadcast> {
+    let bds10 = DataLinkCapability::try_from(buf)
+        .ok()
+        .filter(|_| policy.contains(10));
+    let bds20 = AircraftIdentification::try_from(buf)
+        .ok()
+        .filter(|_| policy.contains(20));
+    let bds30 = ACASResolutionAdvisory::try_from(buf)
+        .ok()
+        .filter(|_| policy.contains(30));
+    if bds10.is_none() && bds20.is_none() && bds30.is_none() {
+        debug!("Comm-B broadcast: no plausible broadcast register");
+        return None;
+    }
+    Some((bds10, bds20, bds30))
+}
+
+impl DekuReader<'_, (AC13Field, DownlinkRequest)> for DF20DataSelector {
     fn from_reader_with_ctx<R: deku::no_std_io::Read + deku::no_std_io::Seek>(
         reader: &mut Reader<R>,
-        ac: AC13Field, // altitude helps a lot in the validation
+        // altitude helps a lot in the validation
+        (ac, dr): (AC13Field, DownlinkRequest),
     ) -> Result<Self, DekuError>
     where
         Self: Sized,
@@ -158,9 +302,21 @@ impl DekuReader<'_, AC13Field> for DF20DataSelector {
             return Ok(result);
         }
 
+        let policy = policy();
+        if dr.is_broadcast() {
+            if let Some((bds10, bds20, bds30)) = broadcast(&buf, policy) {
+                result.bds10 = bds10;
+                result.bds20 = bds20;
+                result.bds30 = bds30;
+                return Ok(result);
+            }
+        }
+
         // Read the first 5 bits as a u8 and get the typecode
         let tc = &buf[0] >> 3;
-        if (9..22).contains(&tc) && tc != 19 {
+        if !policy.contains(5) {
+            debug!("Hypothesis BDS05: not in the decoding policy")
+        } else if (9..22).contains(&tc) && tc != 19 {
             match AirbornePosition::try_from(buf.as_slice()) {
                 Ok(bds05) => match bds05.alt {
                     Some(alt) if alt == ac.0 => result.bds05 = Some(bds05),
@@ -174,58 +330,96 @@ impl DekuReader<'_, AC13Field> for DF20DataSelector {
                 tc
             )
         }
-        match DataLinkCapability::try_from(buf.as_slice()) {
-            Ok(bds10) => result.bds10 = Some(bds10),
-            Err(e) => debug!("Hypothesis BDS10: {}", e.to_string()),
+        if policy.contains(10) {
+            match DataLinkCapability::try_from(buf.as_slice()) {
+                Ok(bds10) => result.bds10 = Some(bds10),
+                Err(e) => debug!("Hypothesis BDS10: {}", e.to_string()),
+            }
+        }
+        if policy.contains(17) {
+            match CommonUsageGICBCapabilityReport::try_from(buf.as_slice()) {
+                Ok(bds17) => result.bds17 = Some(bds17),
+                Err(e) => debug!("Hypothesis BDS17: {}", e.to_string()),
+            }
         }
-        match CommonUsageGICBCapabilityReport::try_from(buf.as_slice()) {
-            Ok(bds17) => result.bds17 = Some(bds17),
-            Err(e) => debug!("Hypothesis BDS17: {}", e.to_string()),
+        if policy.contains(18) {
+            match GICBCapabilityReportPart1::try_from(buf.as_slice()) {
+                Ok(bds18) => result.bds18 = Some(bds18),
+                Err(e) => debug!("Hypothesis BDS18: {}", e.to_string()),
+            }
+        }
+        if policy.contains(19) {
+            match GICBCapabilityReportPart2::try_from(buf.as_slice()) {
+                Ok(bds19) => result.bds19 = Some(bds19),
+                Err(e) => debug!("Hypothesis BDS19: {}", e.to_string()),
+            }
         }
-        match GICBCapabilityReportPart1::try_from(buf.as_slice()) {
-            Ok(bds18) => result.bds18 = Some(bds18),
-            Err(e) => debug!("Hypothesis BDS18: {}", e.to_string()),
+        if policy.contains(20) {
+            match AircraftIdentification::try_from(buf.as_slice()) {
+                Ok(bds20) => result.bds20 = Some(bds20),
+                Err(e) => debug!("Hypothesis BDS20: {}", e.to_string()),
+            }
         }
-        match GICBCapabilityReportPart2::try_from(buf.as_slice()) {
-            Ok(bds19) => result.bds19 = Some(bds19),
-            Err(e) => debug!("Hypothesis BDS19: {}", e.to_string()),
+        if policy.contains(21) {
+            match AircraftAndAirlineRegistrationMarkings::try_from(
+                buf.as_slice(),
+            ) {
+                Ok(bds21) => result.bds21 = Some(bds21),
+                Err(e) => debug!("Hypothesis BDS21: {}", e.to_string()),
+            }
         }
-        match AircraftIdentification::try_from(buf.as_slice()) {
-            Ok(bds20) => result.bds20 = Some(bds20),
-            Err(e) => debug!("Hypothesis BDS20: {}", e.to_string()),
+        if policy.contains(30) {
+            match ACASResolutionAdvisory::try_from(buf.as_slice()) {
+                Ok(bds30) => result.bds30 = Some(bds30),
+                Err(e) => debug!("Hypothesis BDS30: {}", e.to_string()),
+            }
         }
-        match AircraftAndAirlineRegistrationMarkings::try_from(buf.as_slice()) {
-            Ok(bds21) => result.bds21 = Some(bds21),
-            Err(e) => debug!("Hypothesis BDS21: {}", e.to_string()),
+        if policy.contains(40) {
+            match SelectedVerticalIntention::try_from(buf.as_slice()) {
+                Ok(bds40) => result.bds40 = Some(bds40),
+                Err(e) => debug!("Hypothesis BDS40: {}", e.to_string()),
+            }
         }
-        match ACASResolutionAdvisory::try_from(buf.as_slice()) {
-            Ok(bds30) => result.bds30 = Some(bds30),
-            Err(e) => debug!("Hypothesis BDS30: {}", e.to_string()),
+        if policy.contains(41) {
+            match NextWaypointIdentifier::try_from(buf.as_slice()) {
+                Ok(bds41) => result.bds41 = Some(bds41),
+                Err(e) => debug!("Hypothesis BDS41: {}", e.to_string()),
+            }
         }
-        match SelectedVerticalIntention::try_from(buf.as_slice()) {
-            Ok(bds40) => result.bds40 = Some(bds40),
-            Err(e) => debug!("Hypothesis BDS40: {}", e.to_string()),
+        if policy.contains(42) {
+            match NextWaypointPosition::try_from(buf.as_slice()) {
+                Ok(bds42) => result.bds42 = Some(bds42),
+                Err(e) => debug!("Hypothesis BDS42: {}", e.to_string()),
+            }
         }
-        match MeteorologicalRoutineAirReport::try_from(buf.as_slice()) {
-            Ok(bds44) => result.bds44 = Some(bds44),
-            Err(e) => debug!("Hypothesis BDS44: {}", e.to_string()),
+        if policy.contains(44) {
+            match MeteorologicalRoutineAirReport::try_from(buf.as_slice()) {
+                Ok(bds44) => result.bds44 = Some(bds44),
+                Err(e) => debug!("Hypothesis BDS44: {}", e.to_string()),
+            }
         }
-        match MeteorologicalHazardReport::try_from(buf.as_slice()) {
-            Ok(bds45) => result.bds45 = Some(bds45),
-            Err(e) => debug!("Hypothesis BDS45: {}", e.to_string()),
+        if policy.contains(45) {
+            match MeteorologicalHazardReport::try_from(buf.as_slice()) {
+                Ok(bds45) => result.bds45 = Some(bds45),
+                Err(e) => debug!("Hypothesis BDS45: {}", e.to_string()),
+            }
         }
-        match TrackAndTurnReport::try_from(buf.as_slice()) {
-            Ok(bds50) => result.bds50 = Some(bds50),
-            Err(e) => debug!("Hypothesis BDS50: {}", e.to_string()),
+        if policy.contains(50) {
+            match TrackAndTurnReport::try_from(buf.as_slice()) {
+                Ok(bds50) => result.bds50 = Some(bds50),
+                Err(e) => debug!("Hypothesis BDS50: {}", e.to_string()),
+            }
         }
-        match HeadingAndSpeedReport::try_from(buf.as_slice()) {
-            Ok(bds60) => result.bds60 = Some(bds60),
-            Err(e) => debug!("Hypothesis BDS60: {}", e.to_string()),
+        if policy.contains(60) {
+            match HeadingAndSpeedReport::try_from(buf.as_slice()) {
+                Ok(bds60) => result.bds60 = Some(bds60),
+                Err(e) => debug!("Hypothesis BDS60: {}", e.to_string()),
+            }
         }
 
         let enum_id = &buf[0] & 0b111;
         match (tc, enum_id) {
-            (31, id) if id < 2 => {
+            (31, id) if id < 2 && policy.contains(65) => {
                 match  AircraftOperationStatus::try_from(buf.as_slice()) {
                     Ok(bds65) => {
                         result.bds65 = Some(bds65)
@@ -243,10 +437,10 @@ impl DekuReader<'_, AC13Field> for DF20DataSelector {
     }
 }
 
-impl DekuReader<'_> for DF21DataSelector {
+impl DekuReader<'_, DownlinkRequest> for DF21DataSelector {
     fn from_reader_with_ctx<R: deku::no_std_io::Read + deku::no_std_io::Seek>(
         reader: &mut Reader<R>,
-        _: (),
+        dr: DownlinkRequest,
     ) -> Result<Self, DekuError>
     where
         Self: Sized,
@@ -264,6 +458,16 @@ impl DekuReader<'_> for DF21DataSelector {
             return Ok(result);
         }
 
+        let policy = policy();
+        if dr.is_broadcast() {
+            if let Some((bds10, bds20, bds30)) = broadcast(&buf, policy) {
+                result.bds10 = bds10;
+                result.bds20 = bds20;
+                result.bds30 = bds30;
+                return Ok(result);
+            }
+        }
+
         let tc = &buf[0] >> 3;
 
         // On purpose: do not try bds05 here.
@@ -281,58 +485,96 @@ impl DekuReader<'_> for DF21DataSelector {
             )
         }*/
 
-        match DataLinkCapability::try_from(buf.as_slice()) {
-            Ok(bds10) => result.bds10 = Some(bds10),
-            Err(e) => debug!("Hypothesis BDS10: {}", e.to_string()),
+        if policy.contains(10) {
+            match DataLinkCapability::try_from(buf.as_slice()) {
+                Ok(bds10) => result.bds10 = Some(bds10),
+                Err(e) => debug!("Hypothesis BDS10: {}", e.to_string()),
+            }
+        }
+        if policy.contains(17) {
+            match CommonUsageGICBCapabilityReport::try_from(buf.as_slice()) {
+                Ok(bds17) => result.bds17 = Some(bds17),
+                Err(e) => debug!("Hypothesis BDS17: {}", e.to_string()),
+            }
         }
-        match CommonUsageGICBCapabilityReport::try_from(buf.as_slice()) {
-            Ok(bds17) => result.bds17 = Some(bds17),
-            Err(e) => debug!("Hypothesis BDS17: {}", e.to_string()),
+        if policy.contains(18) {
+            match GICBCapabilityReportPart1::try_from(buf.as_slice()) {
+                Ok(bds18) => result.bds18 = Some(bds18),
+                Err(e) => debug!("Hypothesis BDS18: {}", e.to_string()),
+            }
         }
-        match GICBCapabilityReportPart1::try_from(buf.as_slice()) {
-            Ok(bds18) => result.bds18 = Some(bds18),
-            Err(e) => debug!("Hypothesis BDS18: {}", e.to_string()),
+        if policy.contains(19) {
+            match GICBCapabilityReportPart2::try_from(buf.as_slice()) {
+                Ok(bds19) => result.bds19 = Some(bds19),
+                Err(e) => debug!("Hypothesis BDS19: {}", e.to_string()),
+            }
+        }
+        if policy.contains(20) {
+            match AircraftIdentification::try_from(buf.as_slice()) {
+                Ok(bds20) => result.bds20 = Some(bds20),
+                Err(e) => debug!("Hypothesis BDS20: {}", e.to_string()),
+            }
         }
-        match GICBCapabilityReportPart2::try_from(buf.as_slice()) {
-            Ok(bds19) => result.bds19 = Some(bds19),
-            Err(e) => debug!("Hypothesis BDS19: {}", e.to_string()),
+        if policy.contains(21) {
+            match AircraftAndAirlineRegistrationMarkings::try_from(
+                buf.as_slice(),
+            ) {
+                Ok(bds21) => result.bds21 = Some(bds21),
+                Err(e) => debug!("Hypothesis BDS21: {}", e.to_string()),
+            }
         }
-        match AircraftIdentification::try_from(buf.as_slice()) {
-            Ok(bds20) => result.bds20 = Some(bds20),
-            Err(e) => debug!("Hypothesis BDS20: {}", e.to_string()),
+        if policy.contains(30) {
+            match ACASResolutionAdvisory::try_from(buf.as_slice()) {
+                Ok(bds30) => result.bds30 = Some(bds30),
+                Err(e) => debug!("Hypothesis BDS30: {}", e.to_string()),
+            }
         }
-        match AircraftAndAirlineRegistrationMarkings::try_from(buf.as_slice()) {
-            Ok(bds21) => result.bds21 = Some(bds21),
-            Err(e) => debug!("Hypothesis BDS21: {}", e.to_string()),
+        if policy.contains(40) {
+            match SelectedVerticalIntention::try_from(buf.as_slice()) {
+                Ok(bds40) => result.bds40 = Some(bds40),
+                Err(e) => debug!("Hypothesis BDS40: {}", e.to_string()),
+            }
         }
-        match ACASResolutionAdvisory::try_from(buf.as_slice()) {
-            Ok(bds30) => result.bds30 = Some(bds30),
-            Err(e) => debug!("Hypothesis BDS30: {}", e.to_string()),
+        if policy.contains(41) {
+            match NextWaypointIdentifier::try_from(buf.as_slice()) {
+                Ok(bds41) => result.bds41 = Some(bds41),
+                Err(e) => debug!("Hypothesis BDS41: {}", e.to_string()),
+            }
         }
-        match SelectedVerticalIntention::try_from(buf.as_slice()) {
-            Ok(bds40) => result.bds40 = Some(bds40),
-            Err(e) => debug!("Hypothesis BDS40: {}", e.to_string()),
+        if policy.contains(42) {
+            match NextWaypointPosition::try_from(buf.as_slice()) {
+                Ok(bds42) => result.bds42 = Some(bds42),
+                Err(e) => debug!("Hypothesis BDS42: {}", e.to_string()),
+            }
         }
-        match MeteorologicalRoutineAirReport::try_from(buf.as_slice()) {
-            Ok(bds44) => result.bds44 = Some(bds44),
-            Err(e) => debug!("Hypothesis BDS44: {}", e.to_string()),
+        if policy.contains(44) {
+            match MeteorologicalRoutineAirReport::try_from(buf.as_slice()) {
+                Ok(bds44) => result.bds44 = Some(bds44),
+                Err(e) => debug!("Hypothesis BDS44: {}", e.to_string()),
+            }
         }
-        match MeteorologicalHazardReport::try_from(buf.as_slice()) {
-            Ok(bds45) => result.bds45 = Some(bds45),
-            Err(e) => debug!("Hypothesis BDS45: {}", e.to_string()),
+        if policy.contains(45) {
+            match MeteorologicalHazardReport::try_from(buf.as_slice()) {
+                Ok(bds45) => result.bds45 = Some(bds45),
+                Err(e) => debug!("Hypothesis BDS45: {}", e.to_string()),
+            }
         }
-        match TrackAndTurnReport::try_from(buf.as_slice()) {
-            Ok(bds50) => result.bds50 = Some(bds50),
-            Err(e) => debug!("Hypothesis BDS50: {}", e.to_string()),
+        if policy.contains(50) {
+            match TrackAndTurnReport::try_from(buf.as_slice()) {
+                Ok(bds50) => result.bds50 = Some(bds50),
+                Err(e) => debug!("Hypothesis BDS50: {}", e.to_string()),
+            }
         }
-        match HeadingAndSpeedReport::try_from(buf.as_slice()) {
-            Ok(bds60) => result.bds60 = Some(bds60),
-            Err(e) => debug!("Hypothesis BDS60: {}", e.to_string()),
+        if policy.contains(60) {
+            match HeadingAndSpeedReport::try_from(buf.as_slice()) {
+                Ok(bds60) => result.bds60 = Some(bds60),
+                Err(e) => debug!("Hypothesis BDS60: {}", e.to_string()),
+            }
         }
 
         let enum_id = &buf[0] & 0b111;
         match (tc, enum_id) {
-            (31, id) if id < 2 => {
+            (31, id) if id < 2 && policy.contains(65) => {
                 match  AircraftOperationStatus::try_from(buf.as_slice()) {
                     Ok(bds65) => {
                         result.bds65 = Some(bds65)
@@ -350,6 +592,243 @@ impl DekuReader<'_> for DF21DataSelector {
     }
 }
 
+impl From<DF21DataSelector> for DF20DataSelector {
+    fn from(bds: DF21DataSelector) -> Self {
+        DF20DataSelector {
+            is_empty: bds.is_empty,
+            bds05: bds.bds05,
+            bds10: bds.bds10,
+            bds17: bds.bds17,
+            bds18: bds.bds18,
+            bds19: bds.bds19,
+            bds20: bds.bds20,
+            bds21: bds.bds21,
+            bds30: bds.bds30,
+            bds40: bds.bds40,
+            bds41: bds.bds41,
+            bds42: bds.bds42,
+            bds44: bds.bds44,
+            bds45: bds.bds45,
+            bds50: bds.bds50,
+            bds60: bds.bds60,
+            bds65: bds.bds65,
+        }
+    }
+}
+
+/**
+ * A plausible register for the payload of a Comm-B reply, with a score
+ * measuring the consistency of its decoded fields.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Inference {
+    /// The register, e.g. 40 for BDS 4,0
+    pub bds: u8,
+    /// The consistency of the decoded fields, between 0 and 1
+    pub score: f64,
+}
+
+/**
+ * The state of an aircraft known from other messages (e.g. ADS-B), used to
+ * cross-check the registers inferred from Comm-B replies.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Reference {
+    /// Barometric altitude, in ft
+    pub altitude: Option<f64>,
+    /// Groundspeed, in kts
+    pub groundspeed: Option<f64>,
+    /// Track angle, in degrees
+    pub track: Option<f64>,
+}
+
+/**
+ * Infer the register of a Comm-B reply (DF20 or DF21), as a list of
+ * plausible registers ranked by decreasing score.
+ *
+ * Decoding keeps all the registers matching the structure of the payload,
+ * so that some payloads decode as several registers (often BDS 5,0 and 6,0).
+ * Each of them is scored here by cross-checking its fields with each other
+ * (e.g. the Mach number against the indicated airspeed at the altitude of
+ * the aircraft, the roll angle against the track rate) and with a reference
+ * state when available (e.g. from ADS-B). Scores close to 0 flag unlikely
+ * registers; registers with no field to cross-check keep a score of 1.
+ *
+ * Other messages (or invalid frames) return an empty list.
+ *
+ * ```
+ * use rs1090::decode::commb::{infer, Reference};
+ *
+ * let frame = hex::decode("A8001EBCFFFB23286004A73F6A5B").unwrap();
+ * let ranked = infer(&frame, &Reference::default());
+ * assert_eq!(ranked.len(), 2);
+ * ```
+ */
+pub fn infer(frame: &[u8], reference: &Reference) -> Vec<Inference> {
+    let Ok(msg) = Message::try_from(frame) else {
+        return vec![];
+    };
+    let (bds, altitude) = match msg.df {
+        DF::CommBAltitudeReply { bds, ac, .. } => (bds, Some(ac.0)),
+        DF::CommBIdentityReply { bds, .. } => (bds.into(), None),
+        _ => return vec![],
+    };
+    let altitude = altitude
+        .filter(|&altitude| altitude > 0)
+        .map(|altitude| altitude as f64)
+        .or(reference.altitude);
+    let mut ranked = scores(&bds, altitude, reference);
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked
+}
+
+/// The consistency of an error with a tolerance (one standard deviation)
+fn consistency(error: f64, tolerance: f64) -> f64 {
+    (-0.5 * (error / tolerance).powi(2)).exp()
+}
+
+/// The consistency of two values, or 1 if one of them is unknown
+fn check(a: Option<f64>, b: Option<f64>, tolerance: f64) -> f64 {
+    match (a, b) {
+        (Some(a), Some(b)) => consistency(a - b, tolerance),
+        _ => 1.,
+    }
+}
+
+/// The difference between two angles, in degrees
+fn angle(a: f64, b: f64) -> f64 {
+    (a - b + 540.).rem_euclid(360.) - 180.
+}
+
+/// The temperature in the standard atmosphere (ISA), in K, at an altitude
+fn isa_temperature(altitude: f64) -> f64 {
+    (288.15 - 1.9812e-3 * altitude).max(216.65)
+}
+
+/// The ratio to the pressure at the mean sea level in the standard atmosphere
+fn isa_pressure_ratio(altitude: f64) -> f64 {
+    if altitude < 36_089. {
+        (1. - 6.875_585_6e-6 * altitude).powf(5.255_879_7)
+    } else {
+        0.223_36 * (-(altitude - 36_089.) / 20_806.).exp()
+    }
+}
+
+/// The calibrated airspeed (in kts) for a Mach number at an altitude
+fn mach_to_cas(mach: f64, altitude: f64) -> f64 {
+    let impact = isa_pressure_ratio(altitude)
+        * ((1. + 0.2 * mach * mach).powf(3.5) - 1.);
+    661.4786 * (5. * ((impact + 1.).powf(2. / 7.) - 1.)).sqrt()
+}
+
+/// The true airspeed (in kts) for a Mach number at an altitude
+fn mach_to_tas(mach: f64, altitude: f64) -> f64 {
+    mach * 38.967_854 * isa_temperature(altitude).sqrt()
+}
+
+/// The score of each plausible register of a payload
+fn scores(
+    bds: &DF20DataSelector,
+    altitude: Option<f64>,
+    reference: &Reference,
+) -> Vec<Inference> {
+    let mut ranked = vec![];
+    let mut push = |bds, score| ranked.push(Inference { bds, score });
+
+    if let Some(bds05) = &bds.bds05 {
+        push(5, check(bds05.alt.map(|alt| alt as f64), altitude, 100.));
+    }
+    // No field to cross-check
+    let registers = [
+        (10, bds.bds10.is_some()),
+        (17, bds.bds17.is_some()),
+        (18, bds.bds18.is_some()),
+        (19, bds.bds19.is_some()),
+        (20, bds.bds20.is_some()),
+        (21, bds.bds21.is_some()),
+        (30, bds.bds30.is_some()),
+        (41, bds.bds41.is_some()),
+        (42, bds.bds42.is_some()),
+    ];
+    for (register, plausible) in registers {
+        if plausible {
+            push(register, 1.);
+        }
+    }
+    if let Some(bds40) = &bds.bds40 {
+        // Selected altitudes are set in hundreds of ft (16 ft resolution),
+        // usually not too far from the current altitude
+        let mut score = 1.;
+        for selected in
+            [bds40.selected_altitude_mcp, bds40.selected_altitude_fms]
+                .into_iter()
+                .flatten()
+                .map(|selected| selected as f64)
+        {
+            let rounding = selected.rem_euclid(100.);
+            score *= consistency(rounding.min(100. - rounding), 16.);
+            score *= check(Some(selected), altitude, 30_000.);
+        }
+        score *= check(bds40.barometric_setting, Some(1013.25), 30.);
+        push(40, score);
+    }
+    if let Some(bds44) = &bds.bds44 {
+        let isa = altitude.map(|alt| isa_temperature(alt) - 273.15);
+        let mut score = check(Some(bds44.temperature), isa, 20.);
+        score *= check(bds44.wind_speed.map(|ws| ws as f64), Some(0.), 150.);
+        push(44, score);
+    }
+    if bds.bds45.is_some() {
+        push(45, 1.);
+    }
+    if let Some(bds50) = &bds.bds50 {
+        let groundspeed = bds50.groundspeed.map(|gs| gs as f64);
+        let tas = bds50.true_airspeed.map(|tas| tas as f64);
+        // The difference is the wind speed
+        let mut score = check(groundspeed, tas, 100.);
+        // Rate of a coordinated turn
+        if let (Some(roll), Some(rate), Some(speed)) =
+            (bds50.roll_angle, bds50.track_rate, tas.or(groundspeed))
+        {
+            let expected = 1091. * roll.to_radians().tan() / speed.max(1.);
+            score *= consistency(rate - expected, 1.);
+        }
+        score *= check(groundspeed, reference.groundspeed, 20.);
+        if let (Some(track), Some(reference)) =
+            (bds50.track_angle, reference.track)
+        {
+            score *= consistency(angle(track, reference), 10.);
+        }
+        push(50, score);
+    }
+    if let Some(bds60) = &bds.bds60 {
+        let mach = bds60.mach_number;
+        let mut score = 1.;
+        if let (Some(mach), Some(altitude)) = (mach, altitude) {
+            let ias = bds60.indicated_airspeed.map(|ias| ias as f64);
+            score *= check(ias, Some(mach_to_cas(mach, altitude)), 20.);
+            let tas = mach_to_tas(mach, altitude);
+            score *= check(reference.groundspeed, Some(tas), 100.);
+        }
+        score *= check(
+            bds60.barometric_altitude_rate.map(|rate| rate as f64),
+            bds60.inertial_vertical_velocity.map(|rate| rate as f64),
+            500.,
+        );
+        if let (Some(heading), Some(track)) =
+            (bds60.magnetic_heading, reference.track)
+        {
+            // Including the drift angle and the magnetic declination
+            score *= consistency(angle(heading, track), 20.);
+        }
+        push(60, score);
+    }
+    if bds.bds65.is_some() {
+        push(65, 1.);
+    }
+    ranked
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -369,4 +848,104 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn test_policy() {
+        let policy: BdsPolicy = "ehs, bds20".parse().unwrap();
+        assert!(policy.contains(20) && policy.contains(60));
+        assert!(!policy.contains(10) && !policy.contains(44));
+        assert_eq!(
+            "els,ehs,meteo,05,18,19,21,41,42,65".parse(),
+            Ok(BdsPolicy::ALL)
+        );
+        assert_eq!("all".parse(), Ok(BdsPolicy::default()));
+        assert!("43".parse::<BdsPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_broadcast() {
+        // The same payload, plausible as BDS 3,0 and 4,4
+        let bytes = hex!("A0001838300000300000527CDFA5");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        match msg.df {
+            CommBAltitudeReply { dr, bds, .. } => {
+                assert!(!dr.is_broadcast());
+                assert!(bds.bds30.is_some());
+                assert!(bds.bds44.is_some());
+            }
+            _ => unreachable!(),
+        }
+        // DR=4 announces a Comm-B broadcast: only BDS 3,0 is considered
+        let bytes = hex!("A0201838300000300000527CDFA5");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        match msg.df {
+            CommBAltitudeReply { dr, bds, .. } => {
+                assert!(dr.is_broadcast());
+                assert!(bds.bds30.is_some());
+                assert!(bds.bds44.is_none());
+            }
+            _ => unreachable!(),
+        }
+        // Other payloads are decoded as usual
+        let bytes = hex!("A8201EBCFFFB23286004A73F6A5B");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        match msg.df {
+            CommBIdentityReply { bds, .. } => {
+                assert!(bds.bds50.is_some());
+                assert!(bds.bds60.is_some());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_infer() {
+        let infer = |frame: &[u8], reference| {
+            infer(frame, &reference)
+                .iter()
+                .map(|inference| (inference.bds, inference.score))
+                .collect::<Vec<_>>()
+        };
+        // Ambiguous BDS 5,0 and 6,0: vertical rates do not match for 6,0
+        let ranked =
+            infer(&hex!("A8001EBCFFFB23286004A73F6A5B"), Reference::default());
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].0, 50);
+        assert!(ranked[0].1 > 0.9 && ranked[1].1 < 1e-3);
+
+        // BDS 4,4 with 48°C at 38,000 ft
+        let ranked =
+            infer(&hex!("A0001838300000300000527CDFA5"), Reference::default());
+        assert_eq!(ranked[0], (30, 1.));
+        assert_eq!(ranked[1].0, 44);
+        assert!(ranked[1].1 < 1e-3);
+
+        // Selected altitude and barometric setting in BDS 4,0
+        let ranked =
+            infer(&hex!("A0001838CA3E51F0A8000047BC64"), Reference::default());
+        assert_eq!(ranked.len(), 1);
+        assert!(ranked[0].1 > 0.99);
+
+        // BDS 6,0 with a reference state
+        let frame = hex!("A80004AAA74A072BFDEFC1D5CB4F");
+        let reference = Reference {
+            altitude: Some(31_000.),
+            groundspeed: Some(410.),
+            track: Some(115.),
+        };
+        let ranked = infer(&frame, reference);
+        assert_eq!(ranked[0].0, 60);
+        assert!(ranked[0].1 > 0.5);
+        // The aircraft is flying in the opposite direction
+        let opposite = Reference {
+            track: Some(295.),
+            ..reference
+        };
+        assert!(infer(&frame, opposite)[0].1 < 1e-3);
+
+        // Other messages
+        assert!(
+            infer(&hex!("8d406b902015a678d4d220aa4bda"), reference).is_empty()
+        );
+    }
 }
@@ -16,17 +16,21 @@
 use super::adsb::ME;
 use super::bds::bds05::AirbornePosition;
 use super::bds::bds06::SurfacePosition;
-use super::{TimedMessage, DF, ICAO};
+use super::tisb::{TisBCoarseAirbornePosition, TisBMessage};
+use super::{ControlField, TimedMessage, DF, ICAO};
+#[cfg(feature = "data-airports")]
 use crate::data::airports::one_airport;
 use deku::prelude::*;
 use libm::fabs;
-use regex::Regex;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
 
-fn haversine(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+/// The great circle distance between two positions, in kilometers
+pub fn haversine(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
     let d_lat = (lat2 - lat1).to_radians();
     let d_lon = (lon2 - lon1).to_radians();
     let a = (d_lat / 2.0).sin() * (d_lat / 2.0).sin()
@@ -44,7 +48,10 @@ fn dist_haversine(pos1: &Position, pos2: &Position) -> f64 {
 }
 
 /// A flag to qualify a CPR position as odd or even
-#[derive(Debug, PartialEq, Eq, Serialize, DekuRead, Copy, Clone)]
+#[derive(
+    Debug, PartialEq, Eq, Serialize, Deserialize, DekuRead, Copy, Clone,
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "1")]
 #[serde(rename_all = "snake_case")]
 pub enum CPRFormat {
@@ -66,6 +73,7 @@ impl fmt::Display for CPRFormat {
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Position {
     pub latitude: f64,
     pub longitude: f64,
@@ -75,16 +83,30 @@ impl FromStr for Position {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let regex_list = [Regex::new(s).unwrap()];
-        if let Some(airport) = one_airport(&regex_list) {
+        // Grid references which are not valid may still be airport codes
+        let grid = crate::geom::grid_reference(s);
+        if let Some(Ok((latitude, longitude))) = grid {
             return Ok(Position {
-                latitude: airport.lat,
-                longitude: airport.lon,
+                latitude,
+                longitude,
             });
         }
+        #[cfg(feature = "data-airports")]
+        {
+            let regex_list = [regex::Regex::new(s).unwrap()];
+            if let Some(airport) = one_airport(&regex_list) {
+                return Ok(Position {
+                    latitude: airport.lat,
+                    longitude: airport.lon,
+                });
+            }
+        }
         let parts: Vec<&str> = s.split(',').map(|p| p.trim()).collect();
 
         if parts.len() != 2 {
+            if let Some(Err(e)) = grid {
+                return Err(e);
+            }
             return Err("Invalid number of coordinates".to_string());
         }
 
@@ -102,6 +124,9 @@ impl FromStr for Position {
     }
 }
 
+/// Maximum time (in s) between the even and odd messages of a pair
+const PAIR_MAX_AGE: f64 = 10.;
+
 #[derive(Default)]
 pub struct AircraftState {
     timestamp: f64,
@@ -110,8 +135,64 @@ pub struct AircraftState {
     odd_msg: Option<AirbornePosition>,
     even_ts: f64,
     even_msg: Option<AirbornePosition>,
+    /// The last coarse TIS-B messages of each parity, with their timestamp
+    coarse_odd: Option<(f64, TisBCoarseAirbornePosition)>,
+    coarse_even: Option<(f64, TisBCoarseAirbornePosition)>,
+    failure: Option<CprFailure>,
+}
+
+impl AircraftState {
+    /// Why the last position message could not be decoded, if it failed
+    pub fn failure(&self) -> Option<CprFailure> {
+        self.failure
+    }
+}
+
+/**
+ * The reason why a position message could not be decoded.
+ *
+ * Aircraft never getting any position are a common issue when setting up a
+ * receiver: the reason helps to tell a poor reception (only one parity of
+ * messages) from a wrong reference position.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CprFailure {
+    /// No message of the other parity (odd or even) was received yet
+    SingleParity,
+    /// The last message of the other parity is more than 10 s apart
+    PairTooOld,
+    /// The message is older than the last one of the aircraft (of either
+    /// parity): messages must be decoded in timestamp order
+    OutOfOrder,
+    /// The odd and even messages are not in the same latitude zone
+    IncompatiblePair,
+    /// The position is more than 50 km away from the previous one
+    Jump,
+    /// No previous position nor reference to decode a surface position
+    NoReference,
+    /// The position is too far from the reference (more than half a cell)
+    ReferenceTooFar,
 }
 
+impl fmt::Display for CprFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            CprFailure::SingleParity => "single parity",
+            CprFailure::PairTooOld => "pair too old",
+            CprFailure::OutOfOrder => "out of order",
+            CprFailure::IncompatiblePair => "incompatible pair",
+            CprFailure::Jump => "jump",
+            CprFailure::NoReference => "no reference",
+            CprFailure::ReferenceTooFar => "reference too far",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+/// Maximum time (in s) between a message and the position used to backfill it
+const BACKFILL_WINDOW: f64 = 10.;
+
 /// NZ represents the number of latitude zones between the equator and a pole.
 /// In Mode S, is defined to be 15.
 const NZ: f64 = 15.0;
@@ -119,13 +200,33 @@ const NZ: f64 = 15.0;
 /// CPR_MAX is 2^17 since CPR lat and lon values are encoded on 17 bits
 const CPR_MAX: f64 = 131_072.0;
 
-/// Given the latitude, this function yields the number of longitude zones
-/// between 1 and 59.
-/// The nl function uses the precomputed table from 1090-WP-9-14
+/// Coarse TIS-B positions are encoded on 12 bits
+const CPR_MAX_COARSE: f64 = 4_096.0;
+
+/**
+ * The number of longitude zones NL (between 1 and 59) at a given latitude,
+ * from the precomputed transition latitudes of 1090-WP-9-14.
+ *
+ * The function is symmetric around the equator. As in DO-260B (A.1.7.2):
+ *
+ *  - NL is 59 at the equator;
+ *  - NL is 2 at exactly ±87°, and 1 beyond, up to the poles;
+ *  - at a transition latitude, NL takes the value of the zone above it.
+ *
+ * Latitudes beyond the poles, and NaN, are not valid: NL is then 1.
+ *
+ * ```
+ * use rs1090::decode::cpr::nl;
+ * assert_eq!(nl(0.), 59);
+ * assert_eq!(nl(-43.6), 43);
+ * assert_eq!(nl(87.), 2);
+ * assert_eq!(nl(90.), 1);
+ * ```
+ */
 #[rustfmt::skip]
-fn nl(lat: f64) -> u64 {
-    let mut lat = lat;
-    if lat < 0.0 { lat = -lat; }
+pub fn nl(lat: f64) -> u64 {
+    let lat = lat.abs();
+    if lat.is_nan() || lat > 87. { return 1; }
     if lat < 29.911_356_86 {
         if lat < 10.470_471_30 { return 59; }
         if lat < 14.828_174_37 { return 58; }
@@ -192,12 +293,44 @@ fn nl(lat: f64) -> u64 {
     if lat < 84.891_661_91 { return 5; }
     if lat < 85.755_416_21 { return 4; }
     if lat < 86.535_369_98 { return 3; }
-    if lat < 87.000_000_00 { return 2; }
-    1
+    // up to 87 included
+    2
 }
 
-const D_LAT_EVEN: f64 = 360.0 / (4.0 * NZ);
-const D_LAT_ODD: f64 = 360.0 / (4.0 * NZ - 1.0);
+/**
+ * The number of longitude zones for a given latitude and CPR format: NL for
+ * even messages, NL - 1 for odd messages, with at least one zone.
+ */
+pub fn lon_zones(lat: f64, parity: CPRFormat) -> u64 {
+    match parity {
+        CPRFormat::Even => nl(lat),
+        CPRFormat::Odd => std::cmp::max(nl(lat) - 1, 1),
+    }
+}
+
+/**
+ * The size of latitude zones (in degrees) for a CPR format: 360 / 60 for
+ * even messages and 360 / 59 for odd messages, four times smaller for
+ * surface positions.
+ */
+pub fn d_lat(parity: CPRFormat, surface: bool) -> f64 {
+    let span = if surface { 90. } else { 360. };
+    match parity {
+        CPRFormat::Even => span / (4. * NZ),
+        CPRFormat::Odd => span / (4. * NZ - 1.),
+    }
+}
+
+/**
+ * The size of longitude zones (in degrees) at a given latitude for a CPR
+ * format, four times smaller for surface positions.
+ *
+ * Above ±87°, there is only one zone for both formats.
+ */
+pub fn d_lon(lat: f64, parity: CPRFormat, surface: bool) -> f64 {
+    let span = if surface { 90. } else { 360. };
+    span / lon_zones(lat, parity) as f64
+}
 
 // Module implementation according to 1090 MOPS, Vol.1 DO-260C, A.1.7.5
 fn modulo(a: f64, b: f64) -> f64 {
@@ -235,15 +368,52 @@ pub fn airborne_position(
         _ => return None,
     };
 
-    let cpr_lat_even = f64::from(even_frame.lat_cpr) / CPR_MAX;
-    let cpr_lon_even = f64::from(even_frame.lon_cpr) / CPR_MAX;
-    let cpr_lat_odd = f64::from(odd_frame.lat_cpr) / CPR_MAX;
-    let cpr_lon_odd = f64::from(odd_frame.lon_cpr) / CPR_MAX;
+    global_position(
+        f64::from(even_frame.lat_cpr) / CPR_MAX,
+        f64::from(even_frame.lon_cpr) / CPR_MAX,
+        f64::from(odd_frame.lat_cpr) / CPR_MAX,
+        f64::from(odd_frame.lon_cpr) / CPR_MAX,
+        latest.parity,
+    )
+}
 
+/**
+ * Decode a coarse TIS-B airborne position (CPR encoded on 12 bits) from a
+ * pair of even and odd messages.
+ */
+pub fn coarse_position(
+    oldest: &TisBCoarseAirbornePosition,
+    latest: &TisBCoarseAirbornePosition,
+) -> Option<Position> {
+    let (even_frame, odd_frame) = match (oldest.parity, latest.parity) {
+        (CPRFormat::Even, CPRFormat::Odd) => (oldest, latest),
+        (CPRFormat::Odd, CPRFormat::Even) => (latest, oldest),
+        _ => return None,
+    };
+    global_position(
+        f64::from(even_frame.lat_cpr) / CPR_MAX_COARSE,
+        f64::from(even_frame.lon_cpr) / CPR_MAX_COARSE,
+        f64::from(odd_frame.lat_cpr) / CPR_MAX_COARSE,
+        f64::from(odd_frame.lon_cpr) / CPR_MAX_COARSE,
+        latest.parity,
+    )
+}
+
+/// Globally decode airborne CPR coordinates, normalized between 0 and 1, at
+/// the position of the latest message of the pair
+fn global_position(
+    cpr_lat_even: f64,
+    cpr_lon_even: f64,
+    cpr_lat_odd: f64,
+    cpr_lon_odd: f64,
+    cpr_format: CPRFormat,
+) -> Option<Position> {
     let j = libm::floor(59.0 * cpr_lat_even - 60.0 * cpr_lat_odd + 0.5);
 
-    let mut lat_even = D_LAT_EVEN * (modulo(j, 60.) + cpr_lat_even);
-    let mut lat_odd = D_LAT_ODD * (modulo(j, 59.) + cpr_lat_odd);
+    let mut lat_even =
+        d_lat(CPRFormat::Even, false) * (modulo(j, 60.) + cpr_lat_even);
+    let mut lat_odd =
+        d_lat(CPRFormat::Odd, false) * (modulo(j, 59.) + cpr_lat_odd);
 
     if lat_even >= 270.0 {
         lat_even -= 360.0;
@@ -260,19 +430,17 @@ pub fn airborne_position(
         return None;
     }
 
-    let lat = if latest == even_frame {
-        lat_even
-    } else {
-        lat_odd
+    let lat = match cpr_format {
+        CPRFormat::Even => lat_even,
+        CPRFormat::Odd => lat_odd,
     };
-    let cpr_format = &latest.parity;
 
-    let (p, c) = if cpr_format == &CPRFormat::Even {
-        (0, cpr_lon_even)
+    let c = if cpr_format == CPRFormat::Even {
+        cpr_lon_even
     } else {
-        (1, cpr_lon_odd)
+        cpr_lon_odd
     };
-    let ni = std::cmp::max(nl(lat) - p, 1) as f64;
+    let ni = lon_zones(lat, cpr_format) as f64;
     let m = libm::floor(
         cpr_lon_even * (nl(lat) - 1) as f64 - cpr_lon_odd * nl(lat) as f64
             + 0.5,
@@ -302,14 +470,44 @@ pub fn airborne_position_with_reference(
     latitude_ref: f64,
     longitude_ref: f64,
 ) -> Option<Position> {
-    let cpr_lat = f64::from(msg.lat_cpr) / CPR_MAX;
-    let cpr_lon = f64::from(msg.lon_cpr) / CPR_MAX;
+    position_with_reference(
+        msg.parity,
+        f64::from(msg.lat_cpr) / CPR_MAX,
+        f64::from(msg.lon_cpr) / CPR_MAX,
+        latitude_ref,
+        longitude_ref,
+    )
+}
 
-    let d_lat = if msg.parity == CPRFormat::Even {
-        360. / 60.
-    } else {
-        360. / 59.
-    };
+/**
+ * Decode a coarse TIS-B airborne position (CPR encoded on 12 bits) knowing a
+ * reference nearby location, such as the previous position of the aircraft
+ * or the receiver location. The reference position shall be within 180NM of
+ * the true position.
+ */
+pub fn coarse_position_with_reference(
+    msg: &TisBCoarseAirbornePosition,
+    latitude_ref: f64,
+    longitude_ref: f64,
+) -> Option<Position> {
+    position_with_reference(
+        msg.parity,
+        f64::from(msg.lat_cpr) / CPR_MAX_COARSE,
+        f64::from(msg.lon_cpr) / CPR_MAX_COARSE,
+        latitude_ref,
+        longitude_ref,
+    )
+}
+
+/// Locally decode airborne CPR coordinates, normalized between 0 and 1
+fn position_with_reference(
+    parity: CPRFormat,
+    cpr_lat: f64,
+    cpr_lon: f64,
+    latitude_ref: f64,
+    longitude_ref: f64,
+) -> Option<Position> {
+    let d_lat = d_lat(parity, false);
 
     /* Older implementation:
       let j = libm::floor(latitude_ref / d_lat)
@@ -329,12 +527,7 @@ pub fn airborne_position_with_reference(
         return None;
     }
 
-    let ni = if msg.parity == CPRFormat::Even {
-        nl(lat)
-    } else {
-        nl(lat) - 1
-    };
-    let d_lon = if ni > 0 { 360. / ni as f64 } else { 360. };
+    let d_lon = d_lon(lat, parity, false);
 
     /* Older implementation:
       let m = libm::floor(longitude_ref / d_lon)
@@ -370,11 +563,7 @@ pub fn surface_position_with_reference(
     let cpr_lat = f64::from(msg.lat_cpr) / CPR_MAX;
     let cpr_lon = f64::from(msg.lon_cpr) / CPR_MAX;
 
-    let d_lat = if msg.parity == CPRFormat::Even {
-        90. / 60.
-    } else {
-        90. / 59.
-    };
+    let d_lat = d_lat(msg.parity, true);
 
     /* Older implementation:
       let j = libm::floor(latitude_ref / d_lat)
@@ -394,12 +583,7 @@ pub fn surface_position_with_reference(
         return None;
     }
 
-    let ni = if msg.parity == CPRFormat::Even {
-        nl(lat)
-    } else {
-        nl(lat) - 1
-    };
-    let d_lon = if ni > 0 { 90. / ni as f64 } else { 90. };
+    let d_lon = d_lon(lat, msg.parity, true);
 
     /* Older implementation:
       let m = libm::floor(longitude_ref / d_lon)
@@ -442,37 +626,38 @@ pub fn decode_position(
 ) {
     let latest = aircraft.entry(*icao24).or_insert(AircraftState {
         timestamp,
-        pos: None,
         odd_ts: timestamp,
-        odd_msg: None,
         even_ts: timestamp,
-        even_msg: None,
+        ..AircraftState::default()
     });
     match message {
         ME::BDS05(airborne) => {
             let mut pos: Option<Position> = None;
 
-            let latest_timestamp = match airborne.parity {
-                CPRFormat::Even => latest.odd_ts,
-                CPRFormat::Odd => latest.even_ts,
-            };
-            let latest_msg = match airborne.parity {
-                CPRFormat::Even => latest.odd_msg,
-                CPRFormat::Odd => latest.even_msg,
+            let (other_ts, other_msg) = match airborne.parity {
+                CPRFormat::Even => (latest.odd_ts, latest.odd_msg),
+                CPRFormat::Odd => (latest.even_ts, latest.even_msg),
             };
 
-            // This may happen with several sources of data coming on one mpsc
-            if (timestamp - latest_timestamp) < 0. {
+            // Messages are paired in timestamp order, i.e. after the
+            // deduplication of the copies received by several sensors: an
+            // older message would pair with (or overwrite) a fresher one.
+            if timestamp < latest.even_ts.max(latest.odd_ts) {
+                latest.failure = Some(CprFailure::OutOfOrder);
                 return;
             }
 
-            if (timestamp - latest_timestamp) < 10. {
+            let mut failure = match other_msg {
+                None => Some(CprFailure::SingleParity),
+                Some(_) => Some(CprFailure::PairTooOld),
+            };
+            if timestamp - other_ts < PAIR_MAX_AGE {
                 // First decoding based on odd/even (global)
                 // This is the most reasonable way to decode
-                pos = match latest_msg {
-                    Some(oldest) => airborne_position(&oldest, airborne),
-                    None => None,
-                };
+                if let Some(other) = other_msg {
+                    pos = airborne_position(&other, airborne);
+                    failure = Some(CprFailure::IncompatiblePair);
+                }
             }
 
             // If failed try to use previous reference
@@ -491,11 +676,17 @@ pub fn decode_position(
                 if let Some(latest_pos) = latest.pos {
                     // Invalidate if new position is not reasonable
                     if dist_haversine(&new_pos, &latest_pos) > 50. {
-                        pos = None
+                        pos = None;
+                        failure = Some(CprFailure::Jump);
                     }
                 }
             }
 
+            latest.failure = match pos {
+                Some(_) => None,
+                None => failure,
+            };
+
             if let Some(pos) = pos {
                 // First update the message
                 airborne.latitude = Some(pos.latitude);
@@ -550,6 +741,12 @@ pub fn decode_position(
                     )
                 }
             }
+            latest.failure = match (pos, reference) {
+                (Some(_), _) => None,
+                (None, Some(_)) => Some(CprFailure::ReferenceTooFar),
+                (None, None) if latest.pos.is_some() => Some(CprFailure::Jump),
+                (None, None) => Some(CprFailure::NoReference),
+            };
             if let Some(pos) = pos {
                 // First update the message
                 surface.latitude = Some(pos.latitude);
@@ -563,49 +760,319 @@ pub fn decode_position(
     }
 }
 
+/**
+ * Same as [`decode_position`], for TIS-B and ADS-R messages (DF=18).
+ *
+ * Coarse TIS-B positions are decoded from a pair of even and odd coarse
+ * messages, as airborne positions, or else based on the most recent
+ * position of the same aircraft, or on the reference position.
+ */
+pub fn decode_tisb_position(
+    cf: &mut ControlField,
+    timestamp: f64,
+    aircraft: &mut BTreeMap<ICAO, AircraftState>,
+    reference: &mut Option<Position>,
+    update_reference: &UpdateIf,
+) {
+    match &mut cf.me {
+        TisBMessage::ExtendedSquitter(me) => decode_position(
+            me,
+            timestamp,
+            &cf.aa,
+            aircraft,
+            reference,
+            update_reference,
+        ),
+        TisBMessage::Coarse(coarse) => {
+            let latest = aircraft.entry(cf.aa).or_insert(AircraftState {
+                timestamp,
+                odd_ts: timestamp,
+                even_ts: timestamp,
+                ..AircraftState::default()
+            });
+            let (other, same) = match coarse.parity {
+                CPRFormat::Even => (latest.coarse_odd, &mut latest.coarse_even),
+                CPRFormat::Odd => (latest.coarse_even, &mut latest.coarse_odd),
+            };
+            // Keep the most recent message of each parity to pair with
+            if same.is_none_or(|(ts, _)| ts <= timestamp) {
+                *same = Some((timestamp, *coarse));
+            }
+
+            // First decoding based on odd/even (global)
+            let mut pos = other
+                .filter(|(ts, _)| (timestamp - ts).abs() < PAIR_MAX_AGE)
+                .and_then(|(_, other)| coarse_position(&other, coarse));
+            if let (Some(new_pos), Some(latest_pos)) = (pos, latest.pos) {
+                // Invalidate if new position is not reasonable
+                if dist_haversine(&new_pos, &latest_pos) > 50. {
+                    pos = None;
+                }
+            }
+
+            // Otherwise, decode with the last position or the reference
+            let latest_pos =
+                latest.pos.filter(|_| (timestamp - latest.timestamp) < 180.);
+            let origin = latest_pos.or(*reference);
+            let pos = pos.or_else(|| {
+                origin.and_then(|pos| {
+                    coarse_position_with_reference(
+                        coarse,
+                        pos.latitude,
+                        pos.longitude,
+                    )
+                })
+            });
+            latest.failure = match (pos, origin, other) {
+                (Some(_), _, _) => None,
+                (None, Some(_), _) => Some(CprFailure::ReferenceTooFar),
+                (None, None, None) => Some(CprFailure::SingleParity),
+                (None, None, Some((ts, _))) => {
+                    if (timestamp - ts).abs() < PAIR_MAX_AGE {
+                        Some(CprFailure::IncompatiblePair)
+                    } else {
+                        Some(CprFailure::PairTooOld)
+                    }
+                }
+            };
+            if let Some(pos) = pos {
+                coarse.latitude = Some(pos.latitude);
+                coarse.longitude = Some(pos.longitude);
+                latest.pos = Some(pos);
+                latest.timestamp = timestamp;
+            }
+        }
+        TisBMessage::Management(_) => {}
+    }
+}
+
 /**
  * This function is only used  for the decoding of offline messages.
+ *
+ * The state of the decoding is kept for each aircraft: without any
+ * `update_reference`, messages are partitioned by icao24 and partitions are
+ * decoded in parallel (with the `parallel` feature). Otherwise, the reference
+ * position may be updated by any aircraft and messages are decoded
+ * sequentially.
+ *
+ * With `backfill`, a second pass decodes the positions of the messages
+ * received before the pairs of even and odd messages resolved, see
+ * [`backfill_positions`].
  */
 pub fn decode_positions(
     res: &mut [TimedMessage],
     reference: Option<Position>,
     update_reference: &UpdateIf,
+    backfill: bool,
 ) {
-    let mut aircraft: BTreeMap<ICAO, AircraftState> = BTreeMap::new();
-    let mut reference = reference;
-
-    let _: Vec<()> = res
-        .iter_mut()
-        .map(|msg| {
-            if let Some(message) = &mut msg.message {
-                match &mut message.df {
-                    DF::ExtendedSquitterADSB(adsb) => decode_position(
-                        &mut adsb.message,
-                        msg.timestamp,
-                        &adsb.icao24,
-                        &mut aircraft,
-                        &mut reference,
-                        update_reference,
-                    ),
-                    DF::ExtendedSquitterTisB { cf, .. } => decode_position(
-                        &mut cf.me,
-                        msg.timestamp,
-                        &cf.aa,
-                        &mut aircraft,
-                        &mut reference,
-                        update_reference,
-                    ),
-                    _ => {}
-                }
+    if update_reference.is_some() {
+        let mut aircraft: BTreeMap<ICAO, AircraftState> = BTreeMap::new();
+        let mut reference = reference;
+        for msg in res.iter_mut() {
+            decode_timed_position(
+                msg,
+                &mut aircraft,
+                &mut reference,
+                update_reference,
+            );
+        }
+        if backfill {
+            backfill_positions(res);
+        }
+        return;
+    }
+
+    // Messages are modified in place, so they remain in timestamp order
+    let mut partitions: BTreeMap<ICAO, Vec<&mut TimedMessage>> =
+        BTreeMap::new();
+    for msg in res.iter_mut() {
+        if let Some(icao24) = position_icao24(msg) {
+            partitions.entry(icao24).or_default().push(msg);
+        }
+    }
+    #[cfg(feature = "parallel")]
+    let partitions = partitions.into_par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let partitions = partitions.into_iter();
+    partitions.for_each(|(_, mut msgs)| {
+        let mut aircraft: BTreeMap<ICAO, AircraftState> = BTreeMap::new();
+        let mut reference = reference;
+        for msg in msgs.iter_mut() {
+            decode_timed_position(msg, &mut aircraft, &mut reference, &None);
+        }
+        if backfill {
+            backfill_messages(msgs.into_iter());
+        }
+    });
+}
+
+/// The address of the aircraft of a message which may carry a position
+fn position_icao24(msg: &TimedMessage) -> Option<ICAO> {
+    match &msg.message.as_ref()?.df {
+        DF::ExtendedSquitterADSB(adsb) => Some(adsb.icao24),
+        DF::ExtendedSquitterTisB { cf, .. } => Some(cf.aa),
+        _ => None,
+    }
+}
+
+fn decode_timed_position(
+    msg: &mut TimedMessage,
+    aircraft: &mut BTreeMap<ICAO, AircraftState>,
+    reference: &mut Option<Position>,
+    update_reference: &UpdateIf,
+) {
+    let timestamp = msg.timestamp.as_secs_f64();
+    if let Some(message) = &mut msg.message {
+        match &mut message.df {
+            DF::ExtendedSquitterADSB(adsb) => decode_position(
+                &mut adsb.message,
+                timestamp,
+                &adsb.icao24,
+                aircraft,
+                reference,
+                update_reference,
+            ),
+            DF::ExtendedSquitterTisB { cf, .. } => decode_tisb_position(
+                cf,
+                timestamp,
+                aircraft,
+                reference,
+                update_reference,
+            ),
+            _ => {}
+        }
+    }
+}
+
+/**
+ * Decode the positions left undecoded in a first pass, based on the next
+ * decoded position of the same aircraft.
+ *
+ * Globally unambiguous decoding requires both an even and an odd message:
+ * the first message of a pair is left without a position when the messages
+ * are decoded in order. Messages are visited backwards, and positions are
+ * propagated with local decoding as long as they are less than
+ * [`BACKFILL_WINDOW`] seconds apart.
+ *
+ * Messages are expected to be sorted by timestamp.
+ */
+pub fn backfill_positions(res: &mut [TimedMessage]) {
+    backfill_messages(res.iter_mut());
+}
+
+fn backfill_messages<'a>(
+    messages: impl DoubleEndedIterator<Item = &'a mut TimedMessage>,
+) {
+    let mut next: BTreeMap<ICAO, (f64, Position)> = BTreeMap::new();
+    for msg in messages.rev() {
+        let timestamp = msg.timestamp.as_secs_f64();
+        let Some(message) = &mut msg.message else {
+            continue;
+        };
+        let (icao24, entry) = match &mut message.df {
+            DF::ExtendedSquitterADSB(adsb) => (
+                adsb.icao24,
+                backfill_me(
+                    &mut adsb.message,
+                    timestamp,
+                    next.get(&adsb.icao24),
+                ),
+            ),
+            DF::ExtendedSquitterTisB { cf, .. } => {
+                let latest = next.get(&cf.aa);
+                let entry = match &mut cf.me {
+                    TisBMessage::ExtendedSquitter(me) => {
+                        backfill_me(me, timestamp, latest)
+                    }
+                    TisBMessage::Coarse(coarse) => {
+                        let msg = *coarse;
+                        backfill(
+                            &mut coarse.latitude,
+                            &mut coarse.longitude,
+                            timestamp,
+                            latest,
+                            |lat, lon| {
+                                coarse_position_with_reference(&msg, lat, lon)
+                            },
+                        )
+                    }
+                    TisBMessage::Management(_) => None,
+                };
+                (cf.aa, entry)
             }
-        })
-        .collect();
+            _ => continue,
+        };
+        if let Some(entry) = entry {
+            next.insert(icao24, entry);
+        }
+    }
+}
+
+fn backfill_me(
+    me: &mut ME,
+    timestamp: f64,
+    next: Option<&(f64, Position)>,
+) -> Option<(f64, Position)> {
+    match me {
+        ME::BDS05(airborne) => {
+            let msg = *airborne;
+            backfill(
+                &mut airborne.latitude,
+                &mut airborne.longitude,
+                timestamp,
+                next,
+                |lat, lon| airborne_position_with_reference(&msg, lat, lon),
+            )
+        }
+        ME::BDS06(surface) => {
+            let msg = *surface;
+            backfill(
+                &mut surface.latitude,
+                &mut surface.longitude,
+                timestamp,
+                next,
+                |lat, lon| surface_position_with_reference(&msg, lat, lon),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Fill in the position of a message if needed, return the position to use
+/// as a reference for the previous messages
+fn backfill(
+    latitude: &mut Option<f64>,
+    longitude: &mut Option<f64>,
+    timestamp: f64,
+    next: Option<&(f64, Position)>,
+    decode: impl FnOnce(f64, f64) -> Option<Position>,
+) -> Option<(f64, Position)> {
+    if let (Some(latitude), Some(longitude)) = (*latitude, *longitude) {
+        let pos = Position {
+            latitude,
+            longitude,
+        };
+        return Some((timestamp, pos));
+    }
+    let &(next_ts, next_pos) = next?;
+    if next_ts - timestamp > BACKFILL_WINDOW {
+        return None;
+    }
+    let pos = decode(next_pos.latitude, next_pos.longitude)?;
+    // Same sanity check as in decode_position
+    if dist_haversine(&pos, &next_pos) > 50. {
+        return None;
+    }
+    *latitude = Some(pos.latitude);
+    *longitude = Some(pos.longitude);
+    Some((timestamp, pos))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::prelude::*;
+    use crate::testing::timed;
     use approx::assert_relative_eq;
     use hexlit::hex;
 
@@ -725,6 +1192,115 @@ mod tests {
         assert_relative_eq!(longitude, 33.44787, max_relative = 1e-3);
     }
 
+    #[test]
+    fn decode_position_failure() {
+        let mut aircraft = BTreeMap::new();
+        let mut reference = None;
+        let mut decode = |bytes: &[u8], timestamp: f64| {
+            let (_, msg) = Message::from_bytes((bytes, 0)).unwrap();
+            let ExtendedSquitterADSB(mut adsb) = msg.df else {
+                unreachable!()
+            };
+            decode_position(
+                &mut adsb.message,
+                timestamp,
+                &adsb.icao24,
+                &mut aircraft,
+                &mut reference,
+                &None,
+            );
+            aircraft[&adsb.icao24].failure()
+        };
+
+        let even = hex!("8D40058B58C901375147EFD09357");
+        let odd = hex!("8D40058B58C904A87F402D3B8C59");
+        assert_eq!(decode(&even, 0.), Some(CprFailure::SingleParity));
+        assert_eq!(decode(&odd, 20.), Some(CprFailure::PairTooOld));
+        assert_eq!(decode(&even, 25.), None);
+        assert_eq!(decode(&odd, 24.), Some(CprFailure::OutOfOrder));
+
+        // A surface position without any reference
+        let surface = hex!("8c4841753aab238733c8cd4020b1");
+        assert_eq!(decode(&surface, 0.), Some(CprFailure::NoReference));
+        assert_eq!(CprFailure::NoReference.to_string(), "no reference");
+    }
+
+    #[test]
+    fn decode_position_interleaved() {
+        type Decoded = (Option<(f64, f64)>, Option<CprFailure>);
+        // Returns the decoded position and the failure, if any
+        fn decode(
+            aircraft: &mut BTreeMap<ICAO, AircraftState>,
+            bytes: &[u8],
+            timestamp: f64,
+        ) -> Decoded {
+            let (_, msg) = Message::from_bytes((bytes, 0)).unwrap();
+            let ExtendedSquitterADSB(mut adsb) = msg.df else {
+                unreachable!()
+            };
+            let icao24 = adsb.icao24;
+            let me = &mut adsb.message;
+            decode_position(me, timestamp, &icao24, aircraft, &mut None, &None);
+            let ME::BDS05(airborne) = adsb.message else {
+                unreachable!()
+            };
+            let position = airborne.latitude.zip(airborne.longitude);
+            (position, aircraft[&icao24].failure())
+        }
+
+        let mut aircraft = BTreeMap::new();
+        let even = hex!("8D40058B58C901375147EFD09357");
+        let odd = hex!("8D40058B58C904A87F402D3B8C59");
+
+        // Two sensors receive the same frames, with offset timestamps, and
+        // their copies interleave on the way to the decoder
+        let received = [
+            (&even, 10.),
+            (&odd, 10.5),
+            (&odd, 10.45),
+            (&even, 10.05),
+            (&even, 20.),
+            (&odd, 20.4),
+            (&even, 19.95),
+        ];
+
+        // Without deduplication, older copies are rejected
+        let decoded = received
+            .iter()
+            .map(|(frame, ts)| decode(&mut aircraft, frame.as_slice(), *ts))
+            .collect::<Vec<_>>();
+        assert_eq!(decoded[2], (None, Some(CprFailure::OutOfOrder)));
+        assert_eq!(decoded[3], (None, Some(CprFailure::OutOfOrder)));
+        assert_eq!(decoded[6], (None, Some(CprFailure::OutOfOrder)));
+        // ... and never replace the fresher messages to pair with
+        let latest = &aircraft[&ICAO(0x40058b)];
+        assert_eq!((latest.even_ts, latest.odd_ts), (20., 20.4));
+
+        // The deduplicated stream keeps the first copy of each frame, in
+        // timestamp order: all the messages after the first one decode
+        let mut aircraft = BTreeMap::new();
+        let mut stream: Vec<(&[u8; 14], f64)> = vec![];
+        for (frame, ts) in received {
+            let copy = stream
+                .iter_mut()
+                .find(|(f, t)| *f == frame && (t - ts).abs() < 1.);
+            match copy {
+                Some((_, t)) => *t = t.min(ts),
+                None => stream.push((frame, ts)),
+            }
+        }
+        stream.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let decoded = stream
+            .iter()
+            .map(|(frame, ts)| decode(&mut aircraft, frame.as_slice(), *ts))
+            .collect::<Vec<_>>();
+        assert_eq!(decoded[0], (None, Some(CprFailure::SingleParity)));
+        assert!(decoded[1..]
+            .iter()
+            .all(|(pos, fail)| pos.is_some() && fail.is_none()));
+        assert_eq!(decoded[1].0, decoded[3].0);
+    }
+
     #[test]
     fn decode_surface_position_with_reference() {
         let bytes = hex!("8c4841753aab238733c8cd4020b1");
@@ -746,4 +1322,137 @@ mod tests {
         assert_relative_eq!(latitude, 52.32061, max_relative = 1e-3);
         assert_relative_eq!(longitude, 4.73473, max_relative = 1e-3);
     }
+
+    /// Timed messages from the frames, one second apart
+    fn timed_messages(frames: &[[u8; 14]]) -> Vec<TimedMessage> {
+        frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| timed(frame, i as f64))
+            .collect()
+    }
+
+    /// The latitude of an airborne position (BDS 0,5), if decoded
+    fn latitude(msg: &TimedMessage) -> Option<f64> {
+        match &msg.message {
+            Some(Message {
+                df: ExtendedSquitterADSB(adsb),
+                ..
+            }) => match &adsb.message {
+                ME::BDS05(me) => me.latitude,
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn backfill_airborne_position() {
+        let frames = [
+            hex!("8D40058B58C901375147EFD09357"),
+            hex!("8D40058B58C904A87F402D3B8C59"),
+        ];
+
+        let mut res = timed_messages(&frames);
+        decode_positions(&mut res, None, &None, false);
+        assert_eq!(latitude(&res[0]), None);
+        assert!(latitude(&res[1]).is_some());
+
+        let mut res = timed_messages(&frames);
+        decode_positions(&mut res, None, &None, true);
+        assert_relative_eq!(
+            latitude(&res[0]).unwrap(),
+            49.81755,
+            max_relative = 1e-3
+        );
+    }
+
+    #[test]
+    fn decode_positions_in_parallel() {
+        // Two aircraft, with interleaved messages
+        let frames = [
+            hex!("8D40058B58C901375147EFD09357"),
+            hex!("8d4d224f58bf07c2d41a9a353d70"),
+            hex!("8D40058B58C904A87F402D3B8C59"),
+            hex!("8d4d224f58bf003b221b34aa5b8d"),
+            hex!("8D40058B58C901375147EFD09357"),
+        ];
+        let positions = |res: &[TimedMessage]| -> Vec<Option<f64>> {
+            res.iter().map(latitude).collect()
+        };
+
+        for backfill in [false, true] {
+            let mut parallel = timed_messages(&frames);
+            decode_positions(&mut parallel, None, &None, backfill);
+            // A reference never updated, but decoded sequentially
+            let mut sequential = timed_messages(&frames);
+            let update_reference: UpdateIf = Some(Box::new(|_| false));
+            decode_positions(
+                &mut sequential,
+                None,
+                &update_reference,
+                backfill,
+            );
+
+            assert_eq!(positions(&parallel), positions(&sequential));
+            assert_eq!(parallel[0].frame, frames[0]);
+            let decoded = positions(&parallel).iter().flatten().count();
+            assert_eq!(decoded, if backfill { 5 } else { 3 });
+        }
+    }
+
+    /// The closed-form expression of NL, from DO-260B (A.1.7.2)
+    fn nl_formula(lat: f64) -> u64 {
+        use std::f64::consts::PI;
+        let a = 1. - libm::cos(PI / (2. * NZ));
+        let b = libm::cos(PI / 180. * lat).powi(2);
+        libm::floor(2. * PI / libm::acos(1. - a / b)) as u64
+    }
+
+    #[test]
+    fn number_of_longitude_zones() {
+        assert_eq!(nl(0.), 59);
+        assert_eq!(nl(87.), 2);
+        assert_eq!(nl(-87.), 2);
+        assert_eq!(nl(87.000_001), 1);
+        assert_eq!(nl(90.), 1);
+        assert_eq!(nl(-90.), 1);
+        assert_eq!(nl(f64::NAN), 1);
+        assert_eq!(nl(100.), 1);
+        // at transition latitudes, NL decreases
+        assert_eq!(nl(10.470_471_29), 59);
+        assert_eq!(nl(10.470_471_30), 58);
+        assert_eq!(nl(-10.470_471_30), 58);
+
+        // Compare the table with the closed-form expression, every 1e-4
+        // degree, except too close to a transition latitude
+        for i in -869_999..=869_999 {
+            let lat = i as f64 / 1e4;
+            let expected = nl_formula(lat);
+            if nl_formula(lat - 1e-7) != nl_formula(lat + 1e-7) {
+                continue;
+            }
+            assert_eq!(nl(lat), expected, "latitude {lat}");
+            assert_eq!(nl(lat), nl(-lat));
+        }
+    }
+
+    #[test]
+    fn size_of_zones() {
+        use CPRFormat::*;
+        assert_relative_eq!(d_lat(Even, false), 6.);
+        assert_relative_eq!(d_lat(Odd, false), 360. / 59.);
+        assert_relative_eq!(d_lat(Even, true), 1.5);
+        assert_relative_eq!(d_lat(Odd, true), 90. / 59.);
+
+        assert_eq!(lon_zones(43.6, Even), 43);
+        assert_eq!(lon_zones(43.6, Odd), 42);
+        assert_relative_eq!(d_lon(43.6, Even, false), 360. / 43.);
+        assert_relative_eq!(d_lon(43.6, Odd, true), 90. / 42.);
+        // only one zone for both formats close to the poles
+        assert_eq!(lon_zones(87., Odd), 1);
+        assert_eq!(lon_zones(89., Even), 1);
+        assert_eq!(lon_zones(89., Odd), 1);
+        assert_relative_eq!(d_lon(-89., Odd, false), 360.);
+    }
 }
@@ -48,10 +48,97 @@ pub const CRC_TABLE: [u32; 256] = [
     0x0005_ec92, 0x00fa_189b, 0x0005_f089, 0x00fa_0480,
 ];
 
-/// Compute the checksum of the given message
-///
-/// This should be equal to 0 for ADS-B messages.
+/// Tables to process 8 bytes at once: `CRC_SLICES[k][i]` is the contribution
+/// of byte `i` followed by `k` zero bytes
+pub const CRC_SLICES: [[u32; 256]; 8] = crc_slices();
+
+const fn crc_slices() -> [[u32; 256]; 8] {
+    let mut slices = [[0; 256]; 8];
+    slices[0] = CRC_TABLE;
+    let mut k = 1;
+    while k < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let prev = slices[k - 1][i];
+            slices[k][i] =
+                ((prev << 8) ^ CRC_TABLE[(prev >> 16) as usize]) & 0x00ff_ffff;
+            i += 1;
+        }
+        k += 1;
+    }
+    slices
+}
+
+/**
+ * Compute the checksum of the given message.
+ *
+ * This should be equal to 0 for ADS-B messages.
+ *
+ * The CRC is computed with the slice-by-8 method: the data part of long
+ * messages (11 bytes) is processed in one block of 8 bytes and 3 single
+ * bytes; short messages (4 bytes) in one block of 4 bytes.
+ */
 pub fn modes_checksum(message: &[u8], bits: usize) -> Result<u32, DekuError> {
+    let n = bits / 8;
+    if (n < 3) || (message.len() < n) {
+        return Err(DekuError::Incomplete(NeedSize::new(4)));
+    }
+
+    let (data, parity) = message[..n].split_at(n - 3);
+    let mut rem: u32 = 0;
+
+    let mut blocks = data.chunks_exact(8);
+    for block in &mut blocks {
+        rem = slice8(rem, block.try_into().unwrap());
+    }
+    let mut tail = blocks.remainder();
+    if let Some((block, rest)) = tail.split_first_chunk::<4>() {
+        rem = slice4(rem, block);
+        tail = rest;
+    }
+    for &byte in tail {
+        rem = (rem << 8) ^ CRC_TABLE[(u32::from(byte) ^ (rem >> 16)) as usize];
+        rem &= 0x00ff_ffff;
+    }
+
+    Ok(rem ^ parity_bits(parity))
+}
+
+/// Process a block of 8 bytes, the register overlapping the first ones
+#[inline(always)]
+fn slice8(rem: u32, block: &[u8; 8]) -> u32 {
+    let t = &CRC_SLICES;
+    t[7][(block[0] as u32 ^ (rem >> 16)) as usize]
+        ^ t[6][(block[1] as u32 ^ ((rem >> 8) & 0xff)) as usize]
+        ^ t[5][(block[2] as u32 ^ (rem & 0xff)) as usize]
+        ^ t[4][block[3] as usize]
+        ^ t[3][block[4] as usize]
+        ^ t[2][block[5] as usize]
+        ^ t[1][block[6] as usize]
+        ^ t[0][block[7] as usize]
+}
+
+/// Process a block of 4 bytes, the register overlapping the first ones
+#[inline(always)]
+fn slice4(rem: u32, block: &[u8; 4]) -> u32 {
+    let t = &CRC_SLICES;
+    t[3][(block[0] as u32 ^ (rem >> 16)) as usize]
+        ^ t[2][(block[1] as u32 ^ ((rem >> 8) & 0xff)) as usize]
+        ^ t[1][(block[2] as u32 ^ (rem & 0xff)) as usize]
+        ^ t[0][block[3] as usize]
+}
+
+fn parity_bits(parity: &[u8]) -> u32 {
+    u32::from(parity[0]) << 16
+        | u32::from(parity[1]) << 8
+        | u32::from(parity[2])
+}
+
+/// Byte-wise implementation of [`modes_checksum`], with a single table
+pub fn modes_checksum_bytewise(
+    message: &[u8],
+    bits: usize,
+) -> Result<u32, DekuError> {
     let mut rem: u32 = 0;
     let n = bits / 8;
 
@@ -124,4 +211,35 @@ mod tests {
         let crc = modes_checksum(&bytes, 14 * 8).unwrap();
         assert_eq!(crc, 353333);
     }
+
+    #[test]
+    fn test_crc_short() {
+        let bytes = hex!("5d4d20237a55a6");
+        assert_eq!(modes_checksum(&bytes, 7 * 8).unwrap(), 0);
+        let bytes = hex!("a0001910cc300030aa0000eae004");
+        assert_eq!(modes_checksum(&bytes, 14 * 8).unwrap(), 0x020176);
+    }
+
+    #[test]
+    fn test_crc_bytewise() {
+        // Compare both implementations on random messages of all sizes
+        let mut state = 0x2545_f491_u32;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        };
+        for _ in 0..10_000 {
+            let message: Vec<u8> = (0..16).map(|_| next()).collect();
+            for n in 3..=16 {
+                assert_eq!(
+                    modes_checksum(&message, n * 8).unwrap(),
+                    modes_checksum_bytewise(&message, n * 8).unwrap(),
+                );
+            }
+        }
+        assert!(modes_checksum(&[0x8d, 0x40], 2 * 8).is_err());
+        assert!(modes_checksum(&[0x8d, 0x40, 0x6b], 7 * 8).is_err());
+    }
 }
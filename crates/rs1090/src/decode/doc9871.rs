@@ -0,0 +1,536 @@
+/*!
+ * An audit view of decoded messages, with the names of ICAO Doc 9871
+ * (Technical Provisions for Mode S Services and Extended Squitter).
+ *
+ * Decoded messages are usually serialized with short field names (e.g.
+ * `lat_cpr`, `NUCp`) and physical values. For validation against the
+ * standard, [`audit`] lists instead the fields of the 56-bit ME (DF17/18) or
+ * MB (DF20/21) field as in the tables of the annexes of Doc 9871, with their
+ * bit numbering (1 to 56), their raw value and the decoded value when it
+ * matches the field.
+ *
+ * ```
+ * use rs1090::decode::doc9871::audit;
+ * use rs1090::prelude::*;
+ *
+ * let frame = hex::decode("8d40621d58c382d690c8ac2863a7").unwrap();
+ * let msg = Message::try_from(frame.as_slice()).unwrap();
+ * let audit = audit(&frame, &msg);
+ * assert_eq!(audit.registers[0].register, "ES_AIRBORNE_POS");
+ * assert_eq!(audit.registers[0].fields[3].name, "ALTITUDE");
+ * assert_eq!(audit.registers[0].fields[3].bits, "9-20");
+ * ```
+ */
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::Message;
+
+/// A field of a register, as in the tables of Doc 9871
+struct Spec {
+    name: &'static str,
+    first: u8,
+    last: u8,
+    /// The serialized names of the decoded value, if any
+    keys: &'static [&'static str],
+}
+
+const fn spec(
+    name: &'static str,
+    first: u8,
+    last: u8,
+    keys: &'static [&'static str],
+) -> Spec {
+    Spec {
+        name,
+        first,
+        last,
+        keys,
+    }
+}
+
+const ES_AIRBORNE_POS: &[Spec] = &[
+    spec("FORMAT TYPE CODE", 1, 5, &["tc"]),
+    spec("SURVEILLANCE STATUS", 6, 7, &[]),
+    spec("NIC SUPPLEMENT-B", 8, 8, &["NICb"]),
+    spec("ALTITUDE", 9, 20, &["altitude"]),
+    spec("TIME", 21, 21, &[]),
+    spec("CPR FORMAT", 22, 22, &["parity"]),
+    spec("ENCODED LATITUDE", 23, 39, &["lat_cpr"]),
+    spec("ENCODED LONGITUDE", 40, 56, &["lon_cpr"]),
+];
+
+const ES_SURFACE_POS: &[Spec] = &[
+    spec("FORMAT TYPE CODE", 1, 5, &["tc"]),
+    spec("MOVEMENT", 6, 12, &["groundspeed"]),
+    spec("STATUS FOR GROUND TRACK", 13, 13, &[]),
+    spec("GROUND TRACK", 14, 20, &["track"]),
+    spec("TIME", 21, 21, &[]),
+    spec("CPR FORMAT", 22, 22, &["parity"]),
+    spec("ENCODED LATITUDE", 23, 39, &["lat_cpr"]),
+    spec("ENCODED LONGITUDE", 40, 56, &["lon_cpr"]),
+];
+
+const ES_IDENTIFICATION: &[Spec] = &[
+    spec("FORMAT TYPE CODE", 1, 5, &["id"]),
+    spec("AIRCRAFT EMITTER CATEGORY", 6, 8, &["wake_vortex"]),
+    spec("CHARACTERS", 9, 56, &["callsign"]),
+];
+
+const ES_AIRBORNE_VELOCITY_GROUND: &[Spec] = &[
+    spec("FORMAT TYPE CODE", 1, 5, &[]),
+    spec("SUBTYPE", 6, 8, &[]),
+    spec("INTENT CHANGE FLAG", 9, 9, &[]),
+    spec("IFR CAPABILITY FLAG", 10, 10, &[]),
+    spec(
+        "NAVIGATION ACCURACY CATEGORY FOR VELOCITY",
+        11,
+        13,
+        &["NACv"],
+    ),
+    spec("EAST/WEST DIRECTION BIT", 14, 14, &[]),
+    spec("EAST/WEST VELOCITY", 15, 24, &[]),
+    spec("NORTH/SOUTH DIRECTION BIT", 25, 25, &[]),
+    spec("NORTH/SOUTH VELOCITY", 26, 35, &[]),
+    spec("SOURCE BIT FOR VERTICAL RATE", 36, 36, &["vrate_src"]),
+    spec("SIGN BIT FOR VERTICAL RATE", 37, 37, &[]),
+    spec("VERTICAL RATE", 38, 46, &["vertical_rate"]),
+    spec("RESERVED FOR TURN INDICATOR", 47, 48, &[]),
+    spec("DIFFERENCE FROM BARO ALTITUDE SIGN BIT", 49, 49, &[]),
+    spec("DIFFERENCE FROM BARO ALTITUDE", 50, 56, &["geo_minus_baro"]),
+];
+
+const ES_AIRBORNE_VELOCITY_AIR: &[Spec] = &[
+    spec("FORMAT TYPE CODE", 1, 5, &[]),
+    spec("SUBTYPE", 6, 8, &[]),
+    spec("INTENT CHANGE FLAG", 9, 9, &[]),
+    spec("IFR CAPABILITY FLAG", 10, 10, &[]),
+    spec(
+        "NAVIGATION ACCURACY CATEGORY FOR VELOCITY",
+        11,
+        13,
+        &["NACv"],
+    ),
+    spec("STATUS BIT FOR MAGNETIC HEADING", 14, 14, &[]),
+    spec("MAGNETIC HEADING", 15, 24, &["heading"]),
+    spec("AIRSPEED TYPE", 25, 25, &[]),
+    spec("AIRSPEED", 26, 35, &["TAS", "IAS"]),
+    spec("SOURCE BIT FOR VERTICAL RATE", 36, 36, &["vrate_src"]),
+    spec("SIGN BIT FOR VERTICAL RATE", 37, 37, &[]),
+    spec("VERTICAL RATE", 38, 46, &["vertical_rate"]),
+    spec("RESERVED FOR TURN INDICATOR", 47, 48, &[]),
+    spec("DIFFERENCE FROM BARO ALTITUDE SIGN BIT", 49, 49, &[]),
+    spec("DIFFERENCE FROM BARO ALTITUDE", 50, 56, &["geo_minus_baro"]),
+];
+
+const ES_AIRCRAFT_STATUS: &[Spec] = &[
+    spec("FORMAT TYPE CODE", 1, 5, &[]),
+    spec("SUBTYPE CODE", 6, 8, &["subtype"]),
+    spec("EMERGENCY STATE", 9, 11, &["emergency_state"]),
+    spec("MODE A CODE", 12, 24, &["squawk"]),
+    spec("RESERVED", 25, 56, &[]),
+];
+
+const ES_TARGET_STATE: &[Spec] = &[
+    spec("FORMAT TYPE CODE", 1, 5, &[]),
+    spec("SUBTYPE CODE", 6, 7, &[]),
+    spec("SIL SUPPLEMENT", 8, 8, &[]),
+    spec("SELECTED ALTITUDE TYPE", 9, 9, &["source"]),
+    spec("SELECTED ALTITUDE", 10, 20, &["selected_altitude"]),
+    spec(
+        "BAROMETRIC PRESSURE SETTING",
+        21,
+        29,
+        &["barometric_setting"],
+    ),
+    spec("SELECTED HEADING STATUS", 30, 30, &[]),
+    spec("SELECTED HEADING SIGN", 31, 31, &[]),
+    spec("SELECTED HEADING", 32, 39, &["selected_heading"]),
+    spec(
+        "NAVIGATION ACCURACY CATEGORY FOR POSITION",
+        40,
+        43,
+        &["NACp"],
+    ),
+    spec("NAVIGATION INTEGRITY CATEGORY FOR BARO", 44, 44, &[]),
+    spec("SOURCE INTEGRITY LEVEL", 45, 46, &[]),
+    spec("STATUS OF MCP/FCU MODE BITS", 47, 47, &[]),
+    spec("AUTOPILOT ENGAGED", 48, 48, &["autopilot"]),
+    spec("VNAV MODE ENGAGED", 49, 49, &["vnav_mode"]),
+    spec("ALTITUDE HOLD MODE", 50, 50, &["alt_hold"]),
+    spec("RESERVED FOR ADS-R FLAG", 51, 51, &[]),
+    spec("APPROACH MODE", 52, 52, &["approach_mode"]),
+    spec("TCAS/ACAS OPERATIONAL", 53, 53, &["tcas_operational"]),
+    spec("LNAV MODE ENGAGED", 54, 54, &["lnav_mode"]),
+    spec("RESERVED", 55, 56, &[]),
+];
+
+const ES_OPERATIONAL_STATUS_AIRBORNE: &[Spec] = &[
+    spec("FORMAT TYPE CODE", 1, 5, &[]),
+    spec("SUBTYPE CODE", 6, 8, &[]),
+    spec("CAPABILITY CLASS CODES", 9, 24, &[]),
+    spec("OPERATIONAL MODE CODES", 25, 40, &[]),
+    spec("ADS-B VERSION NUMBER", 41, 43, &["version"]),
+    spec("NIC SUPPLEMENT-A", 44, 44, &["NICa"]),
+    spec(
+        "NAVIGATION ACCURACY CATEGORY FOR POSITION",
+        45,
+        48,
+        &["NACp"],
+    ),
+    spec("GEOMETRIC VERTICAL ACCURACY", 49, 50, &["GVA"]),
+    spec("SOURCE INTEGRITY LEVEL", 51, 52, &["SIL"]),
+    spec("NAVIGATION INTEGRITY CATEGORY FOR BARO", 53, 53, &["BAI"]),
+    spec("HORIZONTAL REFERENCE DIRECTION", 54, 54, &["HRD"]),
+    spec("SIL SUPPLEMENT", 55, 55, &["SILs"]),
+    spec("RESERVED", 56, 56, &[]),
+];
+
+const ES_OPERATIONAL_STATUS_SURFACE: &[Spec] = &[
+    spec("FORMAT TYPE CODE", 1, 5, &[]),
+    spec("SUBTYPE CODE", 6, 8, &[]),
+    spec("CAPABILITY CLASS CODES", 9, 20, &[]),
+    spec("LENGTH/WIDTH CODES", 21, 24, &["LW"]),
+    spec("OPERATIONAL MODE CODES", 25, 40, &[]),
+    spec("ADS-B VERSION NUMBER", 41, 43, &["version"]),
+    spec("NIC SUPPLEMENT-A", 44, 44, &["NICa"]),
+    spec(
+        "NAVIGATION ACCURACY CATEGORY FOR POSITION",
+        45,
+        48,
+        &["NACp"],
+    ),
+    spec("RESERVED", 49, 50, &[]),
+    spec("SOURCE INTEGRITY LEVEL", 51, 52, &["SIL"]),
+    spec("TRACK ANGLE/HEADING", 53, 53, &["TAH"]),
+    spec("HORIZONTAL REFERENCE DIRECTION", 54, 54, &["HRD"]),
+    spec("SIL SUPPLEMENT", 55, 55, &["SILs"]),
+    spec("RESERVED", 56, 56, &[]),
+];
+
+/// Registers whose fields are not described (yet)
+const UNDESCRIBED: &[Spec] = &[];
+
+const AIRCRAFT_IDENTIFICATION: &[Spec] = &[
+    spec("BDS", 1, 8, &[]),
+    spec("CHARACTERS", 9, 56, &["callsign"]),
+];
+
+const SELECTED_VERTICAL_INTENTION: &[Spec] = &[
+    spec("STATUS", 1, 1, &[]),
+    spec("MCP/FCU SELECTED ALTITUDE", 2, 13, &["selected_mcp"]),
+    spec("STATUS", 14, 14, &[]),
+    spec("FMS SELECTED ALTITUDE", 15, 26, &["selected_fms"]),
+    spec("STATUS", 27, 27, &[]),
+    spec(
+        "BAROMETRIC PRESSURE SETTING MINUS 800 MB",
+        28,
+        39,
+        &["barometric_setting"],
+    ),
+    spec("RESERVED", 40, 47, &[]),
+    spec("STATUS OF MCP/FCU MODE BITS", 48, 48, &[]),
+    spec("VNAV MODE", 49, 49, &[]),
+    spec("ALT HOLD MODE", 50, 50, &[]),
+    spec("APPROACH MODE", 51, 51, &[]),
+    spec("RESERVED", 52, 53, &[]),
+    spec("STATUS OF TARGET ALT SOURCE BITS", 54, 54, &[]),
+    spec("TARGET ALT SOURCE", 55, 56, &["target_source"]),
+];
+
+const TRACK_AND_TURN: &[Spec] = &[
+    spec("STATUS", 1, 1, &[]),
+    spec("SIGN", 2, 2, &[]),
+    spec("ROLL ANGLE", 3, 11, &["roll"]),
+    spec("STATUS", 12, 12, &[]),
+    spec("SIGN", 13, 13, &[]),
+    spec("TRUE TRACK ANGLE", 14, 23, &["track"]),
+    spec("STATUS", 24, 24, &[]),
+    spec("GROUND SPEED", 25, 34, &["groundspeed"]),
+    spec("STATUS", 35, 35, &[]),
+    spec("SIGN", 36, 36, &[]),
+    spec("TRACK ANGLE RATE", 37, 45, &["track_rate"]),
+    spec("STATUS", 46, 46, &[]),
+    spec("TRUE AIRSPEED", 47, 56, &["TAS"]),
+];
+
+const HEADING_AND_SPEED: &[Spec] = &[
+    spec("STATUS", 1, 1, &[]),
+    spec("SIGN", 2, 2, &[]),
+    spec("MAGNETIC HEADING", 3, 12, &["heading"]),
+    spec("STATUS", 13, 13, &[]),
+    spec("INDICATED AIRSPEED", 14, 23, &["IAS"]),
+    spec("STATUS", 24, 24, &[]),
+    spec("MACH", 25, 34, &["Mach"]),
+    spec("STATUS", 35, 35, &[]),
+    spec("SIGN", 36, 36, &[]),
+    spec("BAROMETRIC ALTITUDE RATE", 37, 45, &["vrate_barometric"]),
+    spec("STATUS", 46, 46, &[]),
+    spec("SIGN", 47, 47, &[]),
+    spec("INERTIAL VERTICAL VELOCITY", 48, 56, &["vrate_inertial"]),
+];
+
+/// A field of the ME or MB field, as in the tables of Doc 9871
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Field {
+    /// The name of the field, e.g. `ENCODED LATITUDE`
+    pub name: &'static str,
+    /// The bits of the field, numbered from 1 to 56, e.g. `23-39`
+    pub bits: String,
+    /// The raw value of the bits
+    pub raw: u64,
+    /// The decoded value, if the decoder reports it for this field
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+/// A register (BDS) carried by the ME or MB field
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Register {
+    /// The name of the register, e.g. `ES_AIRBORNE_POS`
+    pub register: &'static str,
+    /// The BDS code, e.g. `0,5`
+    #[serde(rename = "BDS")]
+    pub bds: String,
+    /// The fields of the register (empty if not described here)
+    pub fields: Vec<Field>,
+    /// Decoded values which do not match a single field, e.g. the ground
+    /// speed computed from both components of the velocity
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub decoded: Map<String, Value>,
+}
+
+/// The audit view of a message, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Audit {
+    /// The downlink format
+    #[serde(rename = "DF")]
+    pub df: u8,
+    /// The aircraft address (from the AP field for DF20/21)
+    #[serde(rename = "AA", skip_serializing_if = "Option::is_none")]
+    pub aa: Option<String>,
+    /// `ME` for extended squitters (DF17/18), `MB` for Comm-B (DF20/21)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<&'static str>,
+    /// The registers carried by the message: Comm-B replies may match
+    /// several registers
+    pub registers: Vec<Register>,
+}
+
+/**
+ * The name of a register and the description of its fields.
+ *
+ * The 56-bit field is needed to tell apart the subtypes of some registers.
+ */
+fn register(bds: &str, data: u64) -> Option<(&'static str, &'static [Spec])> {
+    let tc = bits(data, 1, 5);
+    let subtype = bits(data, 6, 8);
+    let register = match bds {
+        "05" => ("ES_AIRBORNE_POS", ES_AIRBORNE_POS),
+        "06" => ("ES_SURFACE_POS", ES_SURFACE_POS),
+        "08" => ("ES_IDENTIFICATION", ES_IDENTIFICATION),
+        "09" => match subtype {
+            1 | 2 => ("ES_AIRBORNE_VELOCITY", ES_AIRBORNE_VELOCITY_GROUND),
+            _ => ("ES_AIRBORNE_VELOCITY", ES_AIRBORNE_VELOCITY_AIR),
+        },
+        "10" => ("DATA_LINK_CAPABILITY", UNDESCRIBED),
+        "17" => ("GICB_CAPABILITY", UNDESCRIBED),
+        "18" | "19" => ("MSSS_CAPABILITY", UNDESCRIBED),
+        "20" => ("AIRCRAFT_IDENTIFICATION", AIRCRAFT_IDENTIFICATION),
+        "21" => ("AIRCRAFT_REGISTRATION", UNDESCRIBED),
+        "30" => ("ACAS_RA", UNDESCRIBED),
+        "40" => ("SELECTED_VERTICAL_INTENTION", SELECTED_VERTICAL_INTENTION),
+        "44" => ("MET_ROUTINE", UNDESCRIBED),
+        "45" => ("MET_HAZARD", UNDESCRIBED),
+        "50" => ("TRACK_AND_TURN", TRACK_AND_TURN),
+        "60" => ("HEADING_AND_SPEED", HEADING_AND_SPEED),
+        "61" if tc == 28 && subtype == 1 => {
+            ("ES_AIRCRAFT_STATUS", ES_AIRCRAFT_STATUS)
+        }
+        "61" => ("ES_AIRCRAFT_STATUS", UNDESCRIBED),
+        "62" if tc == 29 && subtype >> 1 == 1 => {
+            ("ES_TARGET_STATE", ES_TARGET_STATE)
+        }
+        "62" => ("ES_TARGET_STATE", UNDESCRIBED),
+        "65" => match subtype {
+            0 => ("ES_OPERATIONAL_STATUS", ES_OPERATIONAL_STATUS_AIRBORNE),
+            1 => ("ES_OPERATIONAL_STATUS", ES_OPERATIONAL_STATUS_SURFACE),
+            _ => ("ES_OPERATIONAL_STATUS", UNDESCRIBED),
+        },
+        _ => return None,
+    };
+    Some(register)
+}
+
+/// The bits `first` to `last` (numbered from 1) of a 56-bit field
+fn bits(data: u64, first: u8, last: u8) -> u64 {
+    (data >> (56 - last)) & ((1 << (last - first + 1)) - 1)
+}
+
+/// The BDS code as written in Doc 9871, e.g. `0,5` for `05`
+fn bds_code(bds: &str) -> String {
+    let mut chars = bds.chars();
+    match (chars.next(), chars.next()) {
+        (Some(first), Some(second)) => format!("{first},{second}"),
+        _ => bds.to_string(),
+    }
+}
+
+fn describe(bds: &str, data: u64, mut decoded: Map<String, Value>) -> Register {
+    let Some((name, specs)) = register(bds, data) else {
+        return Register {
+            register: "UNKNOWN",
+            bds: bds_code(bds),
+            fields: vec![],
+            decoded,
+        };
+    };
+    let fields = specs
+        .iter()
+        .map(|spec| Field {
+            name: spec.name,
+            bits: match spec.first == spec.last {
+                true => spec.first.to_string(),
+                false => format!("{}-{}", spec.first, spec.last),
+            },
+            raw: bits(data, spec.first, spec.last),
+            value: spec.keys.iter().find_map(|key| decoded.remove(*key)),
+        })
+        .collect();
+    Register {
+        register: name,
+        bds: bds_code(bds),
+        fields,
+        decoded,
+    }
+}
+
+/**
+ * Describe a message with the names of Doc 9871.
+ *
+ * The raw values are read from the frame; the decoded values from the
+ * decoded message. Messages without any ME or MB field (or without any
+ * identified register) come without any register.
+ */
+pub fn audit(frame: &[u8], msg: &Message) -> Audit {
+    let df = frame.first().map_or(0, |first| (first >> 3).min(24));
+    let mut audit = Audit {
+        df,
+        aa: msg.icao24().map(|icao24| icao24.to_string()),
+        field: None,
+        registers: vec![],
+    };
+    // The ME and MB fields are the bits 33 to 88 of long messages
+    let Some(data) = frame.get(4..11) else {
+        return audit;
+    };
+    let data = data
+        .iter()
+        .fold(0u64, |acc, &byte| (acc << 8) | byte as u64);
+    let Ok(Value::Object(mut fields)) = serde_json::to_value(msg) else {
+        return audit;
+    };
+
+    match df {
+        17 | 18 => {
+            audit.field = Some("ME");
+            if let Some(Value::String(bds)) = fields.remove("bds") {
+                for key in ["df", "icao24"] {
+                    fields.remove(key);
+                }
+                audit.registers.push(describe(&bds, data, fields));
+            }
+        }
+        20 | 21 => {
+            audit.field = Some("MB");
+            // Ambiguous replies are decoded as several registers
+            for (key, value) in fields {
+                let Value::Object(mut decoded) = value else {
+                    continue;
+                };
+                if let Some(Value::String(bds)) = decoded.remove("bds") {
+                    if key.starts_with("bds") {
+                        audit.registers.push(describe(&bds, data, decoded));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    audit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hexlit::hex;
+
+    fn field<'a>(register: &'a Register, name: &str) -> &'a Field {
+        register.fields.iter().find(|f| f.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_airborne_position() {
+        let frame = hex!("8d40621d58c382d690c8ac2863a7");
+        let msg = Message::try_from(frame.as_slice()).unwrap();
+        let audit = audit(&frame, &msg);
+        assert_eq!(audit.df, 17);
+        assert_eq!(audit.aa.as_deref(), Some("40621d"));
+        assert_eq!(audit.field, Some("ME"));
+
+        let register = &audit.registers[0];
+        assert_eq!(register.register, "ES_AIRBORNE_POS");
+        assert_eq!(register.bds, "0,5");
+        let tc = field(register, "FORMAT TYPE CODE");
+        assert_eq!((tc.bits.as_str(), tc.raw), ("1-5", 11));
+        let altitude = field(register, "ALTITUDE");
+        assert_eq!(altitude.bits, "9-20");
+        assert_eq!(altitude.value, Some(Value::from(38000)));
+        let latitude = field(register, "ENCODED LATITUDE");
+        assert_eq!(latitude.raw, 93000);
+        assert_eq!(latitude.value, Some(Value::from(93000)));
+        let cpr = field(register, "CPR FORMAT");
+        assert_eq!((cpr.bits.as_str(), cpr.raw), ("22", 0));
+        // Values derived from several fields
+        assert_eq!(register.decoded["NUCp"], Value::from(7));
+    }
+
+    #[test]
+    fn test_velocity() {
+        let frame = hex!("8d485020994409940838175b284f");
+        let msg = Message::try_from(frame.as_slice()).unwrap();
+        let register = &audit(&frame, &msg).registers[0];
+        assert_eq!(register.register, "ES_AIRBORNE_VELOCITY");
+        assert_eq!(field(register, "SUBTYPE").raw, 1);
+        assert_eq!(field(register, "EAST/WEST VELOCITY").raw, 9);
+        assert_eq!(field(register, "NORTH/SOUTH VELOCITY").raw, 160);
+        assert_eq!(
+            field(register, "VERTICAL RATE").value,
+            Some(Value::from(-832))
+        );
+        assert!(register.decoded.contains_key("groundspeed"));
+    }
+
+    #[test]
+    fn test_commb() {
+        let frame = hex!("a000139381951536e024d4ccf6b5");
+        let msg = Message::try_from(frame.as_slice()).unwrap();
+        let audit = audit(&frame, &msg);
+        assert_eq!(audit.df, 20);
+        assert_eq!(audit.field, Some("MB"));
+        let register = &audit.registers[0];
+        assert_eq!(register.register, "TRACK_AND_TURN");
+        assert_eq!(register.bds, "5,0");
+        let tas = field(register, "TRUE AIRSPEED");
+        assert_eq!(tas.bits, "47-56");
+        assert_eq!(tas.value, Some(Value::from(424)));
+
+        // Short messages carry no register
+        let frame = hex!("02c18c3b323e4f");
+        let msg = Message::try_from(frame.as_slice()).unwrap();
+        let audit = super::audit(&frame, &msg);
+        assert_eq!((audit.df, audit.field), (0, None));
+        assert!(audit.registers.is_empty());
+    }
+}
@@ -127,6 +127,7 @@ fn btea(v: &mut [u32], k: &[u32]) {
  */
 
 #[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Flarm {
     #[deku(endian = "little")]
     /// The timestamp must be passed for the decoding
@@ -266,6 +267,18 @@ impl fmt::Display for Address {
     }
 }
 
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Address {
+    fn schema_name() -> String {
+        "FlarmAddress".to_string()
+    }
+    fn json_schema(
+        _gen: &mut schemars::gen::SchemaGenerator,
+    ) -> schemars::schema::Schema {
+        crate::decode::pattern_schema("^[0-9a-f]{6}$")
+    }
+}
+
 impl serde::ser::Serialize for Address {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -287,6 +300,7 @@ fn magic_value(v: u8) -> Result<bool, DekuError> {
 }
 
 #[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "4", endian = "big")]
 pub enum AircraftType {
     Unknown = 0,
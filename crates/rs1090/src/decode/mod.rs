@@ -3,17 +3,26 @@ pub mod bds;
 pub mod commb;
 pub mod cpr;
 pub mod crc;
+pub mod doc9871;
 pub mod flarm;
+pub mod strict;
 pub mod time;
+pub mod tisb;
+pub mod tracking;
+pub mod units;
 
-use adsb::{ADSB, ME};
+use adsb::ADSB;
+use bds::bds30::ACASResolutionAdvisory;
 use commb::{DF20DataSelector, DF21DataSelector};
 use crc::modes_checksum;
 use deku::prelude::*;
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
+use time::{TimeSource, Timestamp};
+use tisb::TisBMessage;
 use tracing::debug;
+use units::{Precision, Units};
 
 /**
  * DF stands for Downlink Format.
@@ -36,7 +45,8 @@ use tracing::debug;
  * | 24       | [`DF::CommDExtended`]               | 3.1.2.7.3   |
  */
 
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "5", ctx = "crc: u32")]
 #[serde(tag = "df")]
 pub enum DF {
@@ -65,9 +75,8 @@ pub enum DF {
         #[serde(skip)]
         unused1: u8,
         /// Reply information
-        #[deku(bits = "4")]
-        #[serde(skip)]
-        ri: u8,
+        #[serde(rename = "reply_information")]
+        ri: ReplyInformation,
         /// unused
         #[deku(bits = "2")]
         #[serde(skip)]
@@ -78,7 +87,7 @@ pub enum DF {
         /// ICAO address, parity
         #[serde(rename = "icao24")]
         #[deku(ctx = "crc")]
-        ap: IcaoParity,
+        ap: ICAO,
     },
 
     /// DF=4: Surveillance Altitude Reply (3.1.2.6.5)
@@ -100,7 +109,7 @@ pub enum DF {
         /// Address/Parity
         #[serde(rename = "icao24")]
         #[deku(ctx = "crc")]
-        ap: IcaoParity,
+        ap: ICAO,
     },
 
     /// DF=5: Surveillance Identity Reply (3.1.2.6.7)
@@ -122,7 +131,7 @@ pub enum DF {
         /// Address/Parity
         #[serde(rename = "icao24")]
         #[deku(ctx = "crc")]
-        ap: IcaoParity,
+        ap: ICAO,
     },
 
     /// DF=11: (Mode S) All-call reply, Downlink format 11 (2.1.2.5.2.2)
@@ -155,14 +164,9 @@ pub enum DF {
         #[deku(bits = "2")]
         #[serde(skip)]
         reserved2: u8,
-        #[deku(bits = "4")]
         /// Reply information
-        ///
-        /// - 0000: No operating ACAS
-        /// - 0010: ACAS with resolution capability inhibited
-        /// - 0011: ACAS with vertical-only resolution capability
-        /// - 0111: ACAS with vertical and horizontal resolution capability
-        ri: u8,
+        #[serde(rename = "reply_information")]
+        ri: ReplyInformation,
         #[deku(bits = "2")]
         #[serde(skip)]
         reserved3: u8,
@@ -173,10 +177,18 @@ pub enum DF {
         #[deku(count = "7")]
         #[serde(skip)]
         mv: Vec<u8>,
+        /// Active resolution advisory, when the message is a RA report
+        /// (same content as BDS 3,0, sent while a RA is active)
+        #[deku(
+            skip,
+            default = "ACASResolutionAdvisory::try_from(mv.as_slice()).ok()"
+        )]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        acas: Option<ACASResolutionAdvisory>,
         /// Address/Parity
         #[serde(rename = "icao24")]
         #[deku(ctx = "crc")]
-        ap: IcaoParity,
+        ap: ICAO,
     },
 
     #[deku(id = "17")]
@@ -202,9 +214,14 @@ pub enum DF {
     /// DF=19: Extended Squitter Military Application, Downlink Format 19 (3.1.2.8.8)
     #[deku(id = "19")]
     ExtendedSquitterMilitary {
-        /// Reserved
+        /// Application field
         #[deku(bits = "3")]
         af: u8,
+        /// The content of the message (104 bits), in the ADS-B format with
+        /// AF=0, see [`Message::military`]
+        #[deku(count = "13")]
+        #[serde(skip)]
+        content: Vec<u8>,
     },
 
     /// Comm-B Altitude Reply, Downlink Format 20 (3.1.2.6.6)
@@ -225,12 +242,12 @@ pub enum DF {
         ac: AC13Field,
         /// BDS Message, Comm-B
         #[serde(flatten)]
-        #[deku(ctx = "*ac")]
+        #[deku(ctx = "*ac, *dr")]
         bds: DF20DataSelector,
         /// address/parity
         #[serde(rename = "icao24")]
         #[deku(ctx = "crc")]
-        ap: IcaoParity,
+        ap: ICAO,
     },
 
     /// Comm-B Identity Reply, Downlink Format 21 (3.1.2.6.8)
@@ -251,11 +268,12 @@ pub enum DF {
         id: IdentityCode,
         /// BDS Message, Comm-B
         #[serde(flatten)]
+        #[deku(ctx = "*dr")]
         bds: DF21DataSelector,
         /// Address/Parity
         #[serde(rename = "icao24")]
         #[deku(ctx = "crc")]
-        ap: IcaoParity,
+        ap: ICAO,
     },
 
     /// 24: Comm-D Extended, Downlink Format 24 (3.1.2.7.3)
@@ -281,7 +299,8 @@ pub enum DF {
 /// The entry point to Mode S and ADS-B decoding
 ///
 /// Use as `Message::try_from()` in mostly all applications
-#[derive(Debug, PartialEq, Serialize, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Message {
     /// Calculated from all bits, should be 0 for ADS-B (raises a DekuError),
     /// icao24 otherwise
@@ -289,8 +308,13 @@ pub struct Message {
     pub crc: u32,
 
     /// The Downlink Format encoded in 5 bits
-    #[serde(flatten)]
+    #[serde(flatten, serialize_with = "units::serialize_df")]
     pub df: DF,
+
+    /// Physically impossible values removed in strict mode, see
+    /// [`strict`](crate::decode::strict)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<strict::Warning>,
 }
 
 impl DekuContainerRead<'_> for Message {
@@ -323,7 +347,7 @@ impl DekuContainerRead<'_> for Message {
         }
 
         let value = Self::from_reader_with_ctx(reader, ())?;
-        let read_whole_byte = (reader.bits_read % 8) == 0;
+        let read_whole_byte = reader.bits_read.is_multiple_of(8);
         let idx = if read_whole_byte {
             reader.bits_read / 8
         } else {
@@ -364,6 +388,10 @@ impl DekuReader<'_> for Message {
         let res = value.unwrap().into_vec();
         remaining_bytes.extend_from_slice(&res);
 
+        #[cfg(feature = "profile")]
+        let crc = tracing::trace_span!(target: "profile", "crc")
+            .in_scope(|| modes_checksum(&remaining_bytes, bit_len))?;
+        #[cfg(not(feature = "profile"))]
         let crc = modes_checksum(&remaining_bytes, bit_len)?;
         // Also the CRC must be 0 for ADS-B (DF=17) messages
         match (df, crc) {
@@ -374,10 +402,16 @@ impl DekuReader<'_> for Message {
                 // Restart reading by creating a new cursor/reader (with context)
                 let mut input = deku::no_std_io::Cursor::new(&remaining_bytes);
                 let mut reader = Reader::new(&mut input);
-                match DF::from_reader_with_ctx(&mut reader, crc) {
-                    Ok(df) => Ok(Self { crc, df }),
-                    Err(e) => Err(e),
-                }
+                #[cfg(feature = "profile")]
+                let df = tracing::trace_span!(target: "profile", "parse")
+                    .in_scope(|| DF::from_reader_with_ctx(&mut reader, crc))?;
+                #[cfg(not(feature = "profile"))]
+                let df = DF::from_reader_with_ctx(&mut reader, crc)?;
+                Ok(Self {
+                    crc,
+                    df,
+                    warnings: Vec::new(),
+                })
             }
         }
     }
@@ -399,13 +433,71 @@ impl core::convert::TryFrom<&[u8]> for Message {
     }
 }
 
+impl Message {
+    /**
+     * The ICAO address of the aircraft sending the message, together with
+     * how it is known.
+     *
+     * Military extended squitters (DF19) and TIS-B management messages
+     * (DF18) do not carry any aircraft address.
+     */
+    pub fn address(&self) -> Option<(ICAO, AddressProvenance)> {
+        use AddressProvenance::*;
+        match &self.df {
+            DF::ShortAirAirSurveillance { ap, .. }
+            | DF::SurveillanceAltitudeReply { ap, .. }
+            | DF::SurveillanceIdentityReply { ap, .. }
+            | DF::LongAirAirSurveillance { ap, .. }
+            | DF::CommBAltitudeReply { ap, .. }
+            | DF::CommBIdentityReply { ap, .. } => Some((*ap, Parity)),
+            DF::AllCallReply { icao, .. } => Some((*icao, Announced)),
+            DF::ExtendedSquitterADSB(adsb) => Some((adsb.icao24, Announced)),
+            DF::ExtendedSquitterTisB { cf, .. } => match cf.me {
+                TisBMessage::Management(_) => None,
+                _ => Some((cf.aa, Announced)),
+            },
+            DF::ExtendedSquitterMilitary { .. } => None,
+            DF::CommDExtended { .. } => Some((ICAO(self.crc), Parity)),
+        }
+    }
+
+    /// The ICAO address of the aircraft sending the message
+    pub fn icao24(&self) -> Option<ICAO> {
+        self.address().map(|(icao, _)| icao)
+    }
+
+    /**
+     * Decode the content of military extended squitters (DF19) with AF=0,
+     * which follows the ADS-B format on some military transponders.
+     *
+     * The message is then handled as an ADS-B message (DF17), tagged with
+     * `military: true`. Other messages, and messages with an invalid
+     * parity, are left as they are.
+     */
+    pub fn military(&mut self) {
+        let DF::ExtendedSquitterMilitary { af: 0, content } = &self.df else {
+            return;
+        };
+        if self.crc != 0 {
+            return;
+        }
+        // The ADS-B format, after the DF and CA (here, AF) fields
+        let bytes = [&[19 << 3], content.as_slice()].concat();
+        if let Ok((_, mut adsb)) = ADSB::from_bytes((&bytes, 5)) {
+            adsb.military = true;
+            self.df = DF::ExtendedSquitterADSB(adsb);
+        }
+    }
+}
+
 impl fmt::Display for Message {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let crc = self.crc;
         match &self.df {
-            DF::ShortAirAirSurveillance { ac, .. } => {
+            DF::ShortAirAirSurveillance { ac, ri, .. } => {
                 writeln!(f, " DF0. Short Air-Air Surveillance")?;
                 writeln!(f, "  ICAO Address:  {crc:06x} (Mode S / ADS-B)")?;
+                writeln!(f, "  Reply info:    {ri}")?;
                 if ac.0 > 0 {
                     let altitude = ac.0;
                     writeln!(f, "  Air/Ground:    airborne")?;
@@ -436,9 +528,10 @@ impl fmt::Display for Message {
                 writeln!(f, "  ICAO Address:  {icao} (Mode S / ADS-B)")?;
                 writeln!(f, "  Air/Ground:    {capability}")?;
             }
-            DF::LongAirAirSurveillance { ac, .. } => {
+            DF::LongAirAirSurveillance { ac, ri, acas, .. } => {
                 writeln!(f, " DF16. Long Air-Air ACAS")?;
                 writeln!(f, "  ICAO Address:  {crc:06x} (Mode S / ADS-B)")?;
+                writeln!(f, "  Reply info:    {ri}")?;
                 if ac.0 > 0 {
                     let altitude = ac.0;
                     writeln!(f, "  Air/Ground:    airborne")?;
@@ -446,6 +539,14 @@ impl fmt::Display for Message {
                 } else {
                     writeln!(f, "  Air/Ground:    ground")?;
                 }
+                if let Some(acas) = acas {
+                    let status = match (acas.issued_ra, acas.terminated) {
+                        (_, true) => "terminated",
+                        (true, false) => "active",
+                        (false, false) => "none",
+                    };
+                    writeln!(f, "  ACAS RA:       {status}")?;
+                }
             }
             DF::ExtendedSquitterADSB(msg) => {
                 write!(f, "{msg}")?;
@@ -478,12 +579,13 @@ impl fmt::Display for Message {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SensorMetadata {
     /// The timestamp when the message was received by the receptor
-    pub system_timestamp: f64,
+    pub system_timestamp: Timestamp,
     /// The GNSS timestamp of the message
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub gnss_timestamp: Option<f64>,
+    pub gnss_timestamp: Option<Timestamp>,
     /// Number of nanoseconds since beginning of UTC day
     #[serde(skip_serializing_if = "Option::is_none")]
     pub nanoseconds: Option<u64>,
@@ -495,12 +597,27 @@ pub struct SensorMetadata {
     /// A possible name for the receptor
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// The clock used to timestamp the message, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_source: Option<TimeSource>,
+    /// The estimated error (in s) of the system clock of the host computer
+    /// which set the `system_timestamp`, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_error: Option<f64>,
+    /// The estimated error (in s) of the `system_timestamp` when derived from
+    /// the count of samples of a SDR, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_error: Option<f64>,
 }
 
 #[derive(Debug)]
 struct SerializeConfig {
     /// Include the decode time in the serialization process (default: false)
     pub decode_time: bool,
+    /// The units of decoded values (default: implicit), see [`units`]
+    pub units: Units,
+    /// The number of decimal places of decoded values (default: all)
+    pub precision: Precision,
 }
 
 static CONFIG: OnceCell<SerializeConfig> = OnceCell::new();
@@ -510,29 +627,87 @@ fn skip_serialize_decode_time(field: &Option<f64>) -> bool {
     !decode_time | field.is_none()
 }
 
-pub fn serialize_config(decode_time: bool) {
+fn units() -> Units {
+    CONFIG.get().map(|cfg| cfg.units).unwrap_or_default()
+}
+
+fn precision() -> Precision {
+    CONFIG.get().map(|cfg| cfg.precision).unwrap_or_default()
+}
+
+pub fn serialize_config(decode_time: bool, units: Units, precision: Precision) {
     CONFIG
-        .set(SerializeConfig { decode_time })
+        .set(SerializeConfig {
+            decode_time,
+            units,
+            precision,
+        })
         .expect("configuration can only happen once");
 }
 
 #[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TimedMessage {
-    /// The timestamp (in s) of the first time the message was received
-    pub timestamp: f64,
+    /// The timestamp of the first time the message was received
+    pub timestamp: Timestamp,
     /// The message payload
     #[serde(serialize_with = "as_hex", deserialize_with = "from_hex")]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
     pub frame: Vec<u8>,
     /// The decoded message
     #[serde(flatten)]
     pub message: Option<Message>,
-    /// Information about when and where the message was received
+    /// Information about when and where the message was received, with the
+    /// number of sensors which received it (`num_receivers`)
+    #[serde(flatten, serialize_with = "serialize_metadata")]
+    #[cfg_attr(feature = "schema", schemars(with = "Receivers<'static>"))]
     pub metadata: Vec<SensorMetadata>,
     /// Debugging information about decoding time (not serialized)
     #[serde(skip_serializing_if = "skip_serialize_decode_time")]
     pub decode_time: Option<f64>,
 }
 
+/// The sensors receiving a message, as serialized with [`TimedMessage`]
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct Receivers<'a> {
+    metadata: &'a [SensorMetadata],
+    /// The number of sensors which received the message, after
+    /// deduplication (useful to assess the feasibility of multilateration)
+    num_receivers: usize,
+}
+
+fn serialize_metadata<S: Serializer>(
+    metadata: &[SensorMetadata],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let num_receivers = metadata.len();
+    Receivers {
+        metadata,
+        num_receivers,
+    }
+    .serialize(serializer)
+}
+
+/**
+ * The JSON schema of decoded messages, as serialized by [`TimedMessage`].
+ *
+ * Ingestion pipelines can be configured from this schema. Fields with a unit
+ * are named after the units set with [`serialize_config`]; the precision
+ * only rounds values, so it does not change the schema.
+ */
+#[cfg(feature = "schema")]
+pub fn schema() -> schemars::schema::RootSchema {
+    let schema = schemars::schema_for!(TimedMessage);
+    if units() == Units::Implicit {
+        return schema;
+    }
+    let mut value =
+        serde_json::to_value(schema).expect("a schema is a JSON value");
+    units::transform_schema(&mut value, units());
+    serde_json::from_value(value).expect("a schema is a JSON value")
+}
+
 pub fn as_hex<S>(data: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -549,9 +724,25 @@ where
     hex::decode(&hex_string).map_err(serde::de::Error::custom) // Decode and handle errors
 }
 
+impl TimedMessage {
+    /**
+     * A message received at a given time, decoded from its frame, without
+     * any information about the sensors which received it.
+     */
+    pub fn from_frame(timestamp: Timestamp, frame: &[u8]) -> Self {
+        TimedMessage {
+            timestamp,
+            frame: frame.to_vec(),
+            message: Message::try_from(frame).ok(),
+            metadata: vec![],
+            decode_time: None,
+        }
+    }
+}
+
 impl fmt::Display for TimedMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{:.5},{}", &self.timestamp, hex::encode(&self.frame))?;
+        writeln!(f, "{:.5},{}", self.timestamp, hex::encode(&self.frame))?;
         if let Some(msg) = &self.message {
             writeln!(f, "{}", msg)?;
         }
@@ -560,7 +751,7 @@ impl fmt::Display for TimedMessage {
 }
 impl fmt::Debug for TimedMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{:.5},{}", &self.timestamp, hex::encode(&self.frame))?;
+        writeln!(f, "{:.5},{}", self.timestamp, hex::encode(&self.frame))?;
         if let Some(msg) = &self.message {
             writeln!(f, "{:#}", msg)?;
         }
@@ -570,51 +761,9 @@ impl fmt::Debug for TimedMessage {
 
 /// ICAO 24-bit address, commonly use to reference airframes, i.e. tail numbers
 /// of aircraft
-#[derive(PartialEq, Eq, PartialOrd, DekuRead, Hash, Copy, Clone, Ord)]
-#[deku(ctx = "crc: u32")]
-pub struct IcaoParity(
-    // Ok it looks convoluted, actually the final bits are already read when
-    // we compute the crc so we don't need to read this again
-    #[deku(bits = 24, map = "|_v: u32| -> Result<_, DekuError> { Ok(crc) }")]
-    pub u32,
-);
-
-impl fmt::Debug for IcaoParity {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:06x}", self.0)?;
-        Ok(())
-    }
-}
-
-impl fmt::Display for IcaoParity {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:06x}", self.0)?;
-        Ok(())
-    }
-}
-
-impl Serialize for IcaoParity {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::ser::Serializer,
-    {
-        let icao = format!("{:06x}", &self.0);
-        serializer.serialize_str(&icao)
-    }
-}
-
-impl core::str::FromStr for IcaoParity {
-    type Err = core::num::ParseIntError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let num = u32::from_str_radix(s, 16)?;
-        Ok(Self(num))
-    }
-}
-
-/// ICAO 24-bit address, commonly use to reference airframes, i.e. tail numbers
-/// of aircraft
-#[derive(PartialEq, Eq, PartialOrd, DekuRead, Hash, Copy, Clone, Ord)]
+#[derive(
+    PartialEq, Eq, PartialOrd, DekuRead, Hash, Copy, Clone, Ord, Default,
+)]
 pub struct ICAO(#[deku(bits = 24, endian = "big")] pub u32);
 
 impl fmt::Debug for ICAO {
@@ -641,6 +790,18 @@ impl Serialize for ICAO {
     }
 }
 
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for ICAO {
+    fn schema_name() -> String {
+        "ICAO".to_string()
+    }
+    fn json_schema(
+        _gen: &mut schemars::gen::SchemaGenerator,
+    ) -> schemars::schema::Schema {
+        pattern_schema("^[0-9a-f]{6}$")
+    }
+}
+
 impl<'de> Deserialize<'de> for ICAO {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -661,12 +822,43 @@ impl core::str::FromStr for ICAO {
     }
 }
 
-impl From<IcaoParity> for ICAO {
-    fn from(ap: IcaoParity) -> Self {
-        Self(ap.0)
+/**
+ * Read an Address/Parity (AP) field.
+ *
+ * The parity bits are already taken into account when the CRC of the whole
+ * message is computed: the resulting CRC is the address of the aircraft
+ * (provided the message is not corrupted).
+ */
+impl DekuReader<'_, u32> for ICAO {
+    fn from_reader_with_ctx<
+        R: deku::no_std_io::Read + deku::no_std_io::Seek,
+    >(
+        reader: &mut Reader<R>,
+        crc: u32,
+    ) -> Result<Self, DekuError> {
+        u32::from_reader_with_ctx(
+            reader,
+            (deku::ctx::Endian::Big, deku::ctx::BitSize(24)),
+        )?;
+        Ok(Self(crc))
     }
 }
 
+/// Address/Parity fields are now read as [`ICAO`] addresses
+#[deprecated(since = "0.4.5", note = "use ICAO instead")]
+pub type IcaoParity = ICAO;
+
+/// How the ICAO address of a message is known
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressProvenance {
+    /// The address is sent in clear (DF11, DF17, DF18)
+    Announced,
+    /// The address is recovered from the Address/Parity field: it may be
+    /// wrong if the message is corrupted
+    Parity,
+}
+
 /// 13 bit identity code (squawk code), a 4-octal digit identifier
 #[derive(PartialEq, DekuRead, Copy, Clone)]
 pub struct IdentityCode(#[deku(reader = "Self::read(deku::reader)")] pub u16);
@@ -707,8 +899,51 @@ impl Serialize for IdentityCode {
     }
 }
 
+impl<'de> Deserialize<'de> for IdentityCode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        // Squawk codes are stored as their octal digits, i.e. 7700 is 0x7700
+        let s = String::deserialize(deserializer)?;
+        u16::from_str_radix(&s, 16)
+            .map(IdentityCode)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for IdentityCode {
+    fn schema_name() -> String {
+        "IdentityCode".to_string()
+    }
+    fn json_schema(
+        _gen: &mut schemars::gen::SchemaGenerator,
+    ) -> schemars::schema::Schema {
+        pattern_schema("^[0-7]{4}$")
+    }
+}
+
+/// The JSON schema of a string matching a regular expression
+#[cfg(feature = "schema")]
+pub(crate) fn pattern_schema(pattern: &str) -> schemars::schema::Schema {
+    use schemars::schema::{InstanceType, SchemaObject, StringValidation};
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            pattern: Some(pattern.to_string()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
 /// 13 bit encoded altitude
-#[derive(Debug, PartialEq, Eq, Serialize, DekuRead, Copy, Clone)]
+#[derive(
+    Debug, PartialEq, Eq, Serialize, Deserialize, DekuRead, Copy, Clone,
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct AC13Field(#[deku(reader = "Self::read(deku::reader)")] pub u16);
 
 impl AC13Field {
@@ -750,7 +985,10 @@ impl AC13Field {
 }
 
 /// Transponder level and additional information (3.1.2.5.2.2.1)
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(
+    Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone, Default,
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "3")]
 #[allow(non_camel_case_types)]
 pub enum Capability {
@@ -767,6 +1005,7 @@ pub enum Capability {
     AG_AIRBORNE = 0x05,
     /// Level 2 or above transponder, either airborne or on ground
     #[serde(rename = "ground/airborne")]
+    #[default]
     AG_GROUND_AIRBORNE = 0x06,
     /// DR field is not equal to 0,
     /// or fs field equal 2, 3, 4, or 5,
@@ -792,10 +1031,14 @@ impl fmt::Display for Capability {
 }
 
 /// Airborne or Ground and SPI (used in DF=4, 5, 20 or 21)
-#[derive(Debug, PartialEq, Serialize, DekuRead, Copy, Clone)]
+#[derive(
+    Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone, Default,
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "3")]
 #[serde(rename_all = "snake_case")]
 pub enum FlightStatus {
+    #[default]
     NoAlertNoSpiAirborne = 0b000,
     NoAlertNoSpiOnGround = 0b001,
     AlertNoSpiAirborne = 0b010,
@@ -824,10 +1067,88 @@ impl fmt::Display for FlightStatus {
     }
 }
 
+/**
+ * The reply information (used in DF=0 or 16, 3.1.2.8.2.2).
+ *
+ * Values 0 to 7 report the ACAS capability of the aircraft; values 8 to 15
+ * report its maximum cruising true airspeed, in replies to acquisition
+ * interrogations.
+ */
+#[derive(
+    Debug, PartialEq, Eq, Serialize, Deserialize, DekuRead, Copy, Clone,
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[deku(id_type = "u8", bits = "4")]
+#[serde(rename_all = "snake_case")]
+pub enum ReplyInformation {
+    /// No operating ACAS
+    NoAcas = 0,
+    /// ACAS with resolution capability inhibited
+    AcasInhibited = 2,
+    /// ACAS with vertical-only resolution capability
+    AcasVerticalOnly = 3,
+    /// ACAS with vertical and horizontal resolution capability
+    AcasVerticalHorizontal = 7,
+    /// No maximum airspeed data available
+    NoMaxAirspeed = 8,
+    /// Maximum airspeed up to 75 kts
+    MaxAirspeed75 = 9,
+    /// Maximum airspeed between 75 and 150 kts
+    MaxAirspeed150 = 10,
+    /// Maximum airspeed between 150 and 300 kts
+    MaxAirspeed300 = 11,
+    /// Maximum airspeed between 300 and 600 kts
+    MaxAirspeed600 = 12,
+    /// Maximum airspeed between 600 and 1200 kts
+    MaxAirspeed1200 = 13,
+    /// Maximum airspeed above 1200 kts
+    MaxAirspeedAbove1200 = 14,
+    #[deku(id_pat = "_")]
+    NotAssigned,
+}
+
+impl ReplyInformation {
+    /// True if the reply information is about the ACAS capability
+    pub fn is_acas(&self) -> bool {
+        matches!(
+            self,
+            Self::NoAcas
+                | Self::AcasInhibited
+                | Self::AcasVerticalOnly
+                | Self::AcasVerticalHorizontal
+        )
+    }
+}
+
+impl fmt::Display for ReplyInformation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::NoAcas => "no operating ACAS",
+                Self::AcasInhibited => "ACAS, resolution inhibited",
+                Self::AcasVerticalOnly => "ACAS, vertical resolution",
+                Self::AcasVerticalHorizontal =>
+                    "ACAS, vertical and horizontal resolution",
+                Self::NoMaxAirspeed => "no maximum airspeed",
+                Self::MaxAirspeed75 => "max airspeed <= 75 kts",
+                Self::MaxAirspeed150 => "max airspeed 75-150 kts",
+                Self::MaxAirspeed300 => "max airspeed 150-300 kts",
+                Self::MaxAirspeed600 => "max airspeed 300-600 kts",
+                Self::MaxAirspeed1200 => "max airspeed 600-1200 kts",
+                Self::MaxAirspeedAbove1200 => "max airspeed > 1200 kts",
+                Self::NotAssigned => "not assigned",
+            }
+        )
+    }
+}
+
 /// The downlink request (used in DF=4, 5, 20 or 21)
-#[derive(Debug, PartialEq, Eq, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, DekuRead, Copy, Clone, Default)]
 #[deku(id_type = "u8", bits = "5")]
 pub enum DownlinkRequest {
+    #[default]
     None = 0b00000,
     RequestSendCommB = 0b00001,
     CommBBroadcastMsg1 = 0b00100,
@@ -836,8 +1157,19 @@ pub enum DownlinkRequest {
     Unknown,
 }
 
+impl DownlinkRequest {
+    /// True if the reply announces a Comm-B broadcast message
+    pub fn is_broadcast(&self) -> bool {
+        matches!(
+            self,
+            DownlinkRequest::CommBBroadcastMsg1
+                | DownlinkRequest::CommBBroadcastMsg2
+        )
+    }
+}
+
 /// The utility message (used in DF=4, 5, 20 or 21)
-#[derive(Debug, PartialEq, Eq, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, DekuRead, Copy, Clone, Default)]
 pub struct UtilityMessage {
     #[deku(bits = "4")]
     pub iis: u8,
@@ -845,9 +1177,10 @@ pub struct UtilityMessage {
 }
 
 /// The utility message type (used in DF=4, 5, 20 or 21)
-#[derive(Debug, PartialEq, Eq, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, DekuRead, Copy, Clone, Default)]
 #[deku(id_type = "u8", bits = "2")]
 pub enum UtilityMessageType {
+    #[default]
     NoInformation = 0b00,
     CommB = 0b01,
     CommC = 0b10,
@@ -855,26 +1188,34 @@ pub enum UtilityMessageType {
 }
 
 /// The control field in TIS-B messages (DF=18)
-#[derive(Debug, PartialEq, Serialize, DekuRead, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ControlField {
     #[serde(rename = "tisb")]
     pub field_type: ControlFieldType,
     /// AA: Address, Announced
     #[serde(rename = "icao24")]
     pub aa: ICAO,
-    /// ME: message, extended squitter
+    /// ME: message, extended squitter, with a format depending on the
+    /// control field
     #[serde(flatten)]
-    pub me: ME,
+    #[deku(reader = "TisBMessage::read(deku::reader, *field_type)")]
+    pub me: TisBMessage,
 }
 
 impl fmt::Display for ControlField {
-    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        Ok(())
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, " DF18. Extended Squitter {}", self.field_type)?;
+        writeln!(f, "  Address:       {}", self.aa)?;
+        write!(f, "{}", self.me)
     }
 }
 
 /// The control field type in TIS-B messages (DF=18)
-#[derive(Debug, PartialEq, serde::Serialize, DekuRead, Clone)]
+#[derive(
+    Debug, PartialEq, Eq, serde::Serialize, Deserialize, DekuRead, Copy, Clone,
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[deku(id_type = "u8", bits = "3")]
 #[allow(non_camel_case_types)]
 pub enum ControlFieldType {
@@ -894,7 +1235,9 @@ pub enum ControlFieldType {
     #[deku(id = "3")]
     TISB_COARSE,
 
-    /// Code 4, Coarse Format TIS-B Message
+    /// Code 4, TIS-B and ADS-R Management Message
+    ///
+    /// The address field does not identify any aircraft
     #[deku(id = "4")]
     TISB_MANAGE,
 
@@ -928,9 +1271,10 @@ impl fmt::Display for ControlFieldType {
 }
 
 /// Uplink / Downlink (DF=24)
-#[derive(Debug, PartialEq, Eq, DekuRead, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, DekuRead, Copy, Clone, Default)]
 #[deku(id_type = "u8", bits = "1")]
 pub enum KE {
+    #[default]
     DownlinkELMTx = 0,
     UplinkELMAck = 1,
 }
@@ -1044,4 +1388,154 @@ mod tests {
             unreachable!()
         }
     }
+
+    #[test]
+    fn test_address() {
+        use AddressProvenance::*;
+
+        let bytes = hex!("8d406b902015a678d4d220aa4bda");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        assert_eq!(msg.address(), Some((ICAO(0x406b90), Announced)));
+
+        let bytes = hex!("5d4d20237a55a6");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        assert_eq!(msg.address(), Some((ICAO(0x4d2023), Announced)));
+
+        let bytes = hex!("a0001910cc300030aa0000eae004");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        assert_eq!(msg.address(), Some((ICAO(0x020176), Parity)));
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["icao24"], "020176");
+    }
+
+    #[test]
+    fn test_reply_information() {
+        let bytes = hex!("0281983898b4fa");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        match msg.df {
+            DF::ShortAirAirSurveillance { ri, ac, .. } => {
+                assert_eq!(ri, ReplyInformation::AcasVerticalOnly);
+                assert_eq!(ac.0, 38000);
+            }
+            _ => unreachable!(),
+        }
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["reply_information"], "acas_vertical_only");
+        assert_eq!(json["icao24"], "406b90");
+
+        let bytes = hex!("808398383000000000000071bc34");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        let DF::LongAirAirSurveillance { ri, .. } = msg.df else {
+            unreachable!()
+        };
+        assert_eq!(ri, ReplyInformation::AcasVerticalHorizontal);
+        assert!(ri.is_acas());
+        assert!(!ReplyInformation::MaxAirspeed600.is_acas());
+    }
+
+    #[test]
+    fn test_df16_acas() {
+        let bytes = hex!("8083983830c20004f19618254a86");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        let DF::LongAirAirSurveillance { acas, .. } = &msg.df else {
+            unreachable!()
+        };
+        let acas = acas.as_ref().unwrap();
+        assert!(acas.issued_ra);
+        assert_eq!(acas.corrective, Some(true));
+        assert_eq!(acas.downward_sense, Some(false));
+        assert_eq!(acas.positive, Some(true));
+        assert!(!acas.terminated);
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["icao24"], "406b90");
+        assert_eq!(json["acas"]["threat_identity"], "3c6586");
+
+        // No active RA
+        let bytes = hex!("808398383000000000000071bc34");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["acas"]["issued_ra"], false);
+
+        // Not a RA report
+        let bytes = hex!("8083983820000000000000e6e2e7");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        let json = serde_json::to_value(&msg).unwrap();
+        assert!(json.get("acas").is_none());
+    }
+
+    #[test]
+    fn test_military() {
+        // The content of a DF17 message, sent as DF19 with AF=0
+        let bytes = hex!("9840621d58c382d690c8ac96f889");
+        let (_, mut msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        assert!(matches!(msg.df, DF::ExtendedSquitterMilitary { af: 0, .. }));
+        assert_eq!(msg.icao24(), None);
+
+        msg.military();
+        let DF::ExtendedSquitterADSB(adsb) = &msg.df else {
+            unreachable!()
+        };
+        assert!(adsb.military);
+        assert_eq!(adsb.icao24, ICAO(0x40621d));
+        let adsb::ME::BDS05(position) = &adsb.message else {
+            unreachable!()
+        };
+        assert_eq!(position.alt, Some(38000));
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["military"], true);
+        assert_eq!(json["icao24"], "40621d");
+
+        // Regular ADS-B messages are not tagged
+        let bytes = hex!("8d40621d58c382d690c8ac2863a7");
+        let (_, mut msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        msg.military();
+        let json = serde_json::to_value(&msg).unwrap();
+        assert!(json.get("military").is_none());
+
+        // Invalid parity
+        let bytes = hex!("9840621d58c382d690c8ac000000");
+        let (_, mut msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        msg.military();
+        assert!(matches!(msg.df, DF::ExtendedSquitterMilitary { .. }));
+    }
+
+    #[test]
+    fn test_num_receivers() {
+        let sensor = |serial| SensorMetadata {
+            system_timestamp: Timestamp::from_secs(1_700_000_000),
+            gnss_timestamp: None,
+            nanoseconds: None,
+            rssi: None,
+            serial,
+            name: None,
+            time_source: None,
+            clock_error: None,
+            timestamp_error: None,
+        };
+        let frame = hex!("5d4d20237a55a6");
+        let msg = TimedMessage {
+            metadata: vec![sensor(1), sensor(2)],
+            ..TimedMessage::from_frame(
+                Timestamp::from_secs(1_700_000_000),
+                &frame,
+            )
+        };
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["num_receivers"], 2);
+        assert_eq!(json["metadata"][1]["serial"], 2);
+        assert_eq!(json["icao24"], "4d2023");
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn test_schema() {
+        let schema = serde_json::to_value(schema()).unwrap();
+        let properties = &schema["properties"];
+        assert!(properties["timestamp"].is_object());
+        assert_eq!(properties["frame"]["type"], "string");
+        assert_eq!(properties["num_receivers"]["type"], "integer");
+        let definitions = &schema["definitions"];
+        assert_eq!(definitions["ICAO"]["pattern"], "^[0-9a-f]{6}$");
+        assert!(definitions["SensorMetadata"].is_object());
+    }
 }
@@ -0,0 +1,144 @@
+/*!
+ * An opt-in strict validation of decoded values.
+ *
+ * A valid CRC does not guarantee that decoded values make sense: bit errors
+ * may go undetected, and some transponders send garbage. In strict mode,
+ * physically impossible values of ADS-B messages (DF17) are removed from the
+ * decoded message and replaced by typed [`Warning`]s, serialized in a
+ * `warnings` field, so that data-quality pipelines can spot them.
+ *
+ * ```
+ * use rs1090::decode::strict::Warning;
+ * use rs1090::prelude::*;
+ *
+ * let frame = hex::decode("8d40621d58c382d690c8ac2863a7").unwrap();
+ * let mut msg = Message::try_from(frame.as_slice()).unwrap();
+ * msg.strict();
+ * assert!(msg.warnings.is_empty());
+ * ```
+ */
+
+use serde::{Deserialize, Serialize};
+
+use super::adsb::ME;
+use super::bds::bds09::AirborneVelocitySubType;
+use super::{Message, DF};
+
+/// Maximum ground speed or airspeed (in kts) in subsonic velocity messages
+pub const MAX_SPEED: f64 = 1200.;
+/// Maximum absolute vertical rate (in ft/min)
+pub const MAX_VERTICAL_RATE: i16 = 32000;
+/// Maximum barometric altitude (in ft) of civil aircraft
+pub const MAX_ALTITUDE: u16 = 60000;
+
+/// A physically impossible value, rejected in strict mode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "warning", rename_all = "snake_case")]
+pub enum Warning {
+    /// A ground speed or airspeed (in kts) above [`MAX_SPEED`] in a
+    /// subsonic velocity message (BDS 0,9 subtypes 1 and 3)
+    Speed { value: f64 },
+    /// A vertical rate (in ft/min) above [`MAX_VERTICAL_RATE`]
+    VerticalRate { value: i16 },
+    /// An altitude (in ft) above [`MAX_ALTITUDE`] in an airborne position
+    Altitude { value: u16 },
+}
+
+/**
+ * Check the values of a decoded message, and remove the physically
+ * impossible ones.
+ *
+ * The ground speed of velocity messages is not optional, so it is kept,
+ * together with a warning.
+ */
+pub fn check(msg: &mut Message) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let DF::ExtendedSquitterADSB(adsb) = &mut msg.df else {
+        return warnings;
+    };
+    match &mut adsb.message {
+        ME::BDS05(position) => {
+            if let Some(value) = position.alt.filter(|&a| a > MAX_ALTITUDE) {
+                position.alt = None;
+                warnings.push(Warning::Altitude { value });
+            }
+        }
+        ME::BDS09(velocity) => {
+            match &mut velocity.velocity {
+                AirborneVelocitySubType::GroundSpeedDecoding(spd)
+                    if velocity.subtype == 1 && spd.groundspeed > MAX_SPEED =>
+                {
+                    let value = spd.groundspeed;
+                    warnings.push(Warning::Speed { value });
+                }
+                AirborneVelocitySubType::AirspeedSubsonic(spd) => {
+                    if let Some(value) =
+                        spd.airspeed.filter(|&a| a as f64 > MAX_SPEED)
+                    {
+                        spd.airspeed = None;
+                        let value = value as f64;
+                        warnings.push(Warning::Speed { value });
+                    }
+                }
+                _ => {}
+            }
+            if let Some(value) = velocity
+                .vertical_rate
+                .filter(|vr| vr.unsigned_abs() > MAX_VERTICAL_RATE as u16)
+            {
+                velocity.vertical_rate = None;
+                warnings.push(Warning::VerticalRate { value });
+            }
+        }
+        _ => {}
+    }
+    warnings
+}
+
+impl Message {
+    /// Reject the physically impossible values, see [`check`]
+    pub fn strict(&mut self) {
+        self.warnings = check(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use hexlit::hex;
+
+    #[test]
+    fn test_strict() {
+        let bytes = hex!("8d40621d58c382d690c8ac2863a7");
+        let mut msg = Message::try_from(bytes.as_slice()).unwrap();
+        if let ExtendedSquitterADSB(ADSB {
+            message: ME::BDS05(position),
+            ..
+        }) = &mut msg.df
+        {
+            position.alt = Some(65000);
+        }
+        msg.strict();
+        assert_eq!(msg.warnings, vec![Warning::Altitude { value: 65000 }]);
+        let json = serde_json::to_value(&msg).unwrap();
+        assert!(json.get("altitude").unwrap().is_null());
+        assert_eq!(json["warnings"][0]["warning"], "altitude");
+
+        let bytes = hex!("8d485020994409940838175b284f");
+        let mut msg = Message::try_from(bytes.as_slice()).unwrap();
+        msg.strict();
+        assert!(msg.warnings.is_empty());
+        if let ExtendedSquitterADSB(ADSB {
+            message: ME::BDS09(velocity),
+            ..
+        }) = &mut msg.df
+        {
+            velocity.vertical_rate = Some(-32640);
+        }
+        msg.strict();
+        let warning = Warning::VerticalRate { value: -32640 };
+        assert_eq!(msg.warnings, vec![warning]);
+    }
+}
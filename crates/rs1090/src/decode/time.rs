@@ -6,12 +6,196 @@
  * - nanoseconds since midnight UTC (Beast format)
  * - nanoseconds since GPT time of week
  */
-use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 static GPS_TO_UNIX_OFFSET: u64 = 315964800; // GPS epoch to Unix epoch in seconds
 
 static LEAP_SECONDS_SINCE_2017: u64 = 18;
 
+/// The clock used by a receiver to timestamp the messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum TimeSource {
+    /// Timestamps synchronized with a GNSS receiver (suitable for MLAT)
+    Gnss,
+    /// Timestamps from a free-running counter of the receiver
+    Counter,
+    /// Timestamps from the clock of the host computer
+    System,
+}
+
+/**
+ * A Unix timestamp, as an integer number of nanoseconds since epoch.
+ *
+ * Floating point seconds lose precision (about 0.2 µs for current dates)
+ * and do not compare or hash deterministically, which matters when messages
+ * received by several sensors are matched (deduplication, multilateration).
+ *
+ * For compatibility, timestamps are still serialized as floating point
+ * seconds, and any number of seconds is accepted when deserializing.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub i64);
+
+impl Timestamp {
+    pub const fn from_nanos(nanos: i64) -> Self {
+        Timestamp(nanos)
+    }
+
+    pub const fn from_millis(millis: i64) -> Self {
+        Timestamp(millis * 1_000_000)
+    }
+
+    pub const fn from_secs(secs: i64) -> Self {
+        Timestamp(secs * 1_000_000_000)
+    }
+
+    /// Convert from floating point seconds, rounded to the nanosecond
+    pub fn from_secs_f64(secs: f64) -> Self {
+        // Split to keep the precision of the fractional part
+        let whole = secs.floor();
+        let nanos = ((secs - whole) * 1e9).round() as i64;
+        Timestamp(whole as i64 * 1_000_000_000 + nanos)
+    }
+
+    /// The current time of the host computer
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+
+    pub const fn as_nanos(&self) -> i64 {
+        self.0
+    }
+
+    pub const fn as_millis(&self) -> i64 {
+        self.0.div_euclid(1_000_000)
+    }
+
+    /// The number of whole seconds (rounded down)
+    pub const fn as_secs(&self) -> i64 {
+        self.0.div_euclid(1_000_000_000)
+    }
+
+    pub fn as_secs_f64(&self) -> f64 {
+        // Split to keep the precision of the nanoseconds
+        self.as_secs() as f64 + self.0.rem_euclid(1_000_000_000) as f64 * 1e-9
+    }
+
+    /// The date in UTC (year, month, day) and the milliseconds since midnight
+    pub fn civil(&self) -> (i64, i64, i64, i64) {
+        let millis = self.as_millis();
+        let days = millis.div_euclid(86_400_000);
+        let ms = millis.rem_euclid(86_400_000);
+        // Civil date from the number of days since 1970-01-01
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        (year, month, day, ms)
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => Timestamp(d.as_nanos() as i64),
+            Err(e) => Timestamp(-(e.duration().as_nanos() as i64)),
+        }
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+    fn add(self, rhs: Duration) -> Timestamp {
+        Timestamp(self.0 + rhs.as_nanos() as i64)
+    }
+}
+
+impl Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+    fn sub(self, rhs: Duration) -> Timestamp {
+        Timestamp(self.0 - rhs.as_nanos() as i64)
+    }
+}
+
+impl Sub for Timestamp {
+    type Output = Duration;
+    /// The duration between two timestamps (zero if negative)
+    fn sub(self, rhs: Timestamp) -> Duration {
+        Duration::from_nanos(self.0.saturating_sub(rhs.0).max(0) as u64)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_secs_f64(), f)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.as_secs_f64())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        f64::deserialize(deserializer).map(Timestamp::from_secs_f64)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Timestamp {
+    fn schema_name() -> String {
+        f64::schema_name()
+    }
+    fn json_schema(
+        gen: &mut schemars::gen::SchemaGenerator,
+    ) -> schemars::schema::Schema {
+        f64::json_schema(gen)
+    }
+    fn is_referenceable() -> bool {
+        false
+    }
+}
+
+/// The estimated error (in ns) of the system clock, negative if unknown
+static CLOCK_ERROR: AtomicI64 = AtomicI64::new(-1);
+
+/**
+ * Record the estimated error of the system clock of the host computer.
+ *
+ * Sources timestamping messages with the system clock report this error in
+ * the metadata of the messages, so that timing analyses can account for
+ * hosts which are not synchronized (e.g. with NTP).
+ */
+pub fn set_clock_error(error: Option<Duration>) {
+    let nanos = error.map_or(-1, |e| e.as_nanos().min(i64::MAX as u128) as i64);
+    CLOCK_ERROR.store(nanos, Ordering::Relaxed);
+}
+
+/// The estimated error (in s) of the system clock, if known
+pub fn clock_error() -> Option<f64> {
+    let nanos = CLOCK_ERROR.load(Ordering::Relaxed);
+    (nanos >= 0).then_some(nanos as f64 * 1e-9)
+}
+
 pub fn now_in_ns() -> u128 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -45,6 +229,42 @@ pub fn since_gps_week_to_since_today(gps_ns: u64) -> u64 {
     (gps_ns - LEAP_SECONDS_SINCE_2017 * 1_000_000_000) % 86_400_000_000_000
 }
 
-pub fn since_gps_week_to_unix_s(gps_ns: u64) -> f64 {
-    gps_week_in_s(now_in_s()) as f64 + (gps_ns as f64 * 1e-9)
+pub fn since_gps_week_to_unix(gps_ns: u64) -> Timestamp {
+    Timestamp::from_secs(gps_week_in_s(now_in_s()) as i64)
+        + Duration::from_nanos(gps_ns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp() {
+        let ts = Timestamp::from_nanos(1_712_345_678_123_456_789);
+        assert_eq!(ts.as_secs(), 1_712_345_678);
+        assert_eq!(ts.as_millis(), 1_712_345_678_123);
+        assert_eq!(
+            Timestamp::from_secs_f64(ts.as_secs_f64()).as_millis(),
+            ts.as_millis()
+        );
+        assert_eq!(
+            ts + Duration::from_millis(450) - ts,
+            Duration::from_millis(450)
+        );
+        assert_eq!(ts - (ts + Duration::from_secs(1)), Duration::ZERO);
+        assert_eq!(Timestamp::from_nanos(-1).as_secs(), -1);
+    }
+
+    #[test]
+    fn test_timestamp_serde() {
+        // Serialized as seconds, as in previous versions
+        let ts = Timestamp::from_millis(1_712_345_678_125);
+        let json = serde_json::to_string(&ts).unwrap();
+        assert_eq!(json, "1712345678.125");
+        let back: Timestamp = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, ts);
+        // Integer seconds are accepted as well
+        let ts: Timestamp = serde_json::from_str("1712345678").unwrap();
+        assert_eq!(ts, Timestamp::from_secs(1_712_345_678));
+    }
 }
@@ -0,0 +1,289 @@
+use super::adsb::ME;
+use super::bds::bds05::decode_ac12;
+use super::cpr::CPRFormat;
+use super::{as_hex, from_hex, ControlFieldType};
+use deku::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/**
+ * The content of a DF=18 message, following the address field.
+ *
+ * Its format depends on the control field:
+ *
+ * | CF         | Content                                  |
+ * | ---------- | ---------------------------------------- |
+ * | 0, 1, 2    | [`ME`], same as DF=17                    |
+ * | 3          | [`TisBCoarseAirbornePosition`]           |
+ * | 4          | [`ManagementMessage`]                    |
+ * | 5, 6       | [`ME`], same as DF=17                    |
+ * | 7          | [`ManagementMessage`] (reserved, raw)    |
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum TisBMessage {
+    /// ADS-B, fine TIS-B and ADS-R messages, with the same format as DF=17
+    ExtendedSquitter(ME),
+    /// Coarse format TIS-B airborne position
+    Coarse(TisBCoarseAirbornePosition),
+    /// TIS-B and ADS-R management messages, and reserved control fields
+    Management(ManagementMessage),
+}
+
+impl TisBMessage {
+    pub(crate) fn read<R: deku::no_std_io::Read + deku::no_std_io::Seek>(
+        reader: &mut Reader<R>,
+        field_type: ControlFieldType,
+    ) -> Result<Self, DekuError> {
+        use ControlFieldType::*;
+        Ok(match field_type {
+            TISB_COARSE => TisBMessage::Coarse(
+                TisBCoarseAirbornePosition::from_reader_with_ctx(reader, ())?,
+            ),
+            TISB_MANAGE | Reserved => TisBMessage::Management(
+                ManagementMessage::from_reader_with_ctx(reader, ())?,
+            ),
+            _ => TisBMessage::ExtendedSquitter(ME::from_reader_with_ctx(
+                reader,
+                (),
+            )?),
+        })
+    }
+}
+
+impl fmt::Display for TisBMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TisBMessage::ExtendedSquitter(me) => write!(f, "{me}"),
+            TisBMessage::Coarse(me) => write!(f, "{me}"),
+            TisBMessage::Management(me) => write!(f, "{me}"),
+        }
+    }
+}
+
+/**
+ * ## TIS-B Coarse Airborne Position (DF=18, CF=3)
+ *
+ * Coarse positions are broadcast by ground stations for aircraft tracked by
+ * secondary surveillance radars, with the ground speed and track angle in
+ * the same message. CPR coordinates are encoded on 12 bits only.
+ *
+ * | IMF | SVID | ALT | GTS | GTA | GS | F | LAT-CPR | LON-CPR |
+ * | --- | ---- | --- | --- | --- | -- | - | ------- | ------- |
+ * | 1   | 4    | 12  | 1   | 5   | 6  | 1 | 12      | 12      |
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TisBCoarseAirbornePosition {
+    #[deku(bits = "1")]
+    /// ICAO/Mode A Flag: true if the address is not an ICAO 24-bit address
+    pub imf: bool,
+
+    #[deku(bits = "4")]
+    /// Service Volume Identifier of the ground station
+    pub svid: u8,
+
+    #[deku(reader = "decode_ac12(deku::reader)")]
+    #[serde(rename = "altitude")]
+    /// Decode the altitude in feet, encoded on 12 bits.
+    /// None if not available.
+    pub alt: Option<u16>,
+
+    #[deku(bits = "1")]
+    #[serde(skip)]
+    /// Ground track status
+    pub track_status: bool,
+
+    #[deku(
+        bits = "5",
+        map = "|v: u8| -> Result<_, DekuError> {
+            Ok(track_status.then_some(v as f64 * 360. / 32.))
+        }"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Ground track angle in degrees, with a resolution of 11.25°
+    pub track: Option<f64>,
+
+    #[deku(
+        bits = "6",
+        map = "|v: u16| -> Result<_, DekuError> {
+            if v == 0 { Ok(None) } else { Ok(Some((v - 1) * 16)) }
+        }"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Ground speed in knots, with a resolution of 16 kts
+    pub groundspeed: Option<u16>,
+
+    pub parity: CPRFormat,
+
+    #[deku(bits = "12", endian = "big")]
+    pub lat_cpr: u16,
+
+    #[deku(bits = "12", endian = "big", pad_bits_after = "2")]
+    pub lon_cpr: u16,
+
+    #[deku(skip, default = "None")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+
+    #[deku(skip, default = "None")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+}
+
+impl fmt::Display for TisBCoarseAirbornePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  TIS-B Coarse Airborne Position")?;
+        writeln!(f, "  Service volume: {}", self.svid)?;
+        let altitude = self.alt.map_or_else(
+            || "None".to_string(),
+            |altitude| format!("{altitude} ft"),
+        );
+        writeln!(f, "  Altitude:      {}", altitude)?;
+        if let Some(track) = self.track {
+            writeln!(f, "  Track angle:   {}°", track)?;
+        }
+        if let Some(groundspeed) = self.groundspeed {
+            writeln!(f, "  Groundspeed:   {} kts", groundspeed)?;
+        }
+        writeln!(f, "  CPR type:      Airborne (coarse)")?;
+        writeln!(f, "  CPR parity:    {}", self.parity)?;
+        writeln!(f, "  CPR latitude:  ({})", self.lat_cpr)?;
+        writeln!(f, "  CPR longitude: ({})", self.lon_cpr)?;
+        Ok(())
+    }
+}
+
+/**
+ * ## TIS-B and ADS-R Management Message (DF=18, CF=4)
+ *
+ * The content of management messages is specific to the service provider,
+ * and is kept raw. The address field of such messages does not identify any
+ * aircraft.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, DekuRead, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ManagementMessage {
+    #[deku(count = "7")]
+    #[serde(
+        rename = "management",
+        serialize_with = "as_hex",
+        deserialize_with = "from_hex"
+    )]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
+    /// The raw content of the ME field
+    pub data: Vec<u8>,
+}
+
+impl fmt::Display for ManagementMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  Management message")?;
+        writeln!(f, "  Content:       {}", hex::encode(&self.data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::cpr::{decode_tisb_position, CprFailure, Position};
+    use crate::decode::DF::ExtendedSquitterTisB;
+    use crate::prelude::*;
+    use approx::assert_relative_eq;
+    use hexlit::hex;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_tisb_fine() {
+        let bytes = hex!("92406b902015a678d4d22067a5df");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        let ExtendedSquitterTisB { cf, .. } = msg.df else {
+            unreachable!()
+        };
+        assert_eq!(cf.field_type, ControlFieldType::TISB_FINE);
+        assert_eq!(format!("{}", cf.aa), "406b90");
+        let TisBMessage::ExtendedSquitter(ME::BDS08(id)) = cf.me else {
+            unreachable!()
+        };
+        assert_eq!(id.callsign, "EZY85MH");
+    }
+
+    #[test]
+    fn test_tisb_coarse() {
+        let bytes = hex!("93a1b2c32dcc50e9110ab49167fa");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        assert_eq!(msg.icao24(), Some(ICAO(0xa1b2c3)));
+        let ExtendedSquitterTisB { mut cf, .. } = msg.df else {
+            unreachable!()
+        };
+        assert_eq!(cf.field_type, ControlFieldType::TISB_COARSE);
+        let TisBMessage::Coarse(coarse) = cf.me else {
+            unreachable!()
+        };
+        assert!(!coarse.imf);
+        assert_eq!(coarse.svid, 5);
+        assert_eq!(coarse.alt, Some(36000));
+        assert_eq!(coarse.track, Some(90.));
+        assert_eq!(coarse.groundspeed, Some(448));
+        assert_eq!(coarse.parity, CPRFormat::Even);
+
+        let mut aircraft = BTreeMap::new();
+        let mut reference = Some(Position {
+            latitude: 43.,
+            longitude: 1.,
+        });
+        decode_tisb_position(&mut cf, 0., &mut aircraft, &mut reference, &None);
+        let TisBMessage::Coarse(coarse) = cf.me else {
+            unreachable!()
+        };
+        assert_relative_eq!(coarse.latitude.unwrap(), 43.6, epsilon = 1e-2);
+        assert_relative_eq!(coarse.longitude.unwrap(), 1.4, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn test_tisb_coarse_pair() {
+        let bytes = hex!("93a1b2c32dcc50e9110ab49167fa");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        let ExtendedSquitterTisB { mut cf, .. } = msg.df else {
+            unreachable!()
+        };
+        let TisBMessage::Coarse(even) = cf.me else {
+            unreachable!()
+        };
+
+        // Without any reference, one message is not enough
+        let mut aircraft = BTreeMap::new();
+        let mut reference = None;
+        decode_tisb_position(&mut cf, 0., &mut aircraft, &mut reference, &None);
+        let failure = aircraft[&cf.aa].failure();
+        assert_eq!(failure, Some(CprFailure::SingleParity));
+
+        // An odd message (at 43.62, 1.45) two seconds later
+        cf.me = TisBMessage::Coarse(TisBCoarseAirbornePosition {
+            parity: CPRFormat::Odd,
+            lat_cpr: 610,
+            lon_cpr: 693,
+            ..even
+        });
+        decode_tisb_position(&mut cf, 2., &mut aircraft, &mut reference, &None);
+        let TisBMessage::Coarse(odd) = cf.me else {
+            unreachable!()
+        };
+        assert_eq!(aircraft[&cf.aa].failure(), None);
+        assert_relative_eq!(odd.latitude.unwrap(), 43.62, epsilon = 2e-3);
+        assert_relative_eq!(odd.longitude.unwrap(), 1.45, epsilon = 2e-3);
+        assert_eq!(odd.groundspeed, Some(448));
+        assert_eq!(odd.track, Some(90.));
+    }
+
+    #[test]
+    fn test_tisb_management() {
+        let bytes = hex!("9412345601020304050607519c89");
+        let (_, msg) = Message::from_bytes((&bytes, 0)).unwrap();
+        // Management messages do not identify any aircraft
+        assert_eq!(msg.icao24(), None);
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["tisb"], "TISB_MANAGE");
+        assert_eq!(json["management"], "01020304050607");
+    }
+}
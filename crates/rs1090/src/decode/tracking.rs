@@ -0,0 +1,500 @@
+/*!
+ * Smoothing of trajectories with a Kalman filter for each aircraft.
+ *
+ * Decoded positions come with the quantization of the CPR format, and
+ * sometimes with glitches; velocities come in separate messages. The
+ * [`Tracker`] fuses airborne positions (BDS 0,5), airborne velocities
+ * (BDS 0,9), and the speeds of Comm-B replies (groundspeed and track in
+ * BDS 5,0, vertical rates in BDS 6,0) into a smoothed state vector.
+ *
+ * Each aircraft follows a constant velocity model, in a local tangent plane
+ * centred on one of its positions. With independent errors on each axis,
+ * the filter splits into three filters of two states (position and speed)
+ * along the east, north and vertical axes. Observations too far from the
+ * prediction are rejected, and the track restarts after a few rejected
+ * positions in a row, or after a long silence.
+ */
+
+use super::adsb::ME;
+use super::bds::bds05::{AirbornePosition, Source};
+use super::bds::bds09::{AirborneVelocity, AirborneVelocitySubType};
+use super::bds::bds50::TrackAndTurnReport;
+use super::bds::bds60::HeadingAndSpeedReport;
+use super::commb::{DF20DataSelector, DF21DataSelector};
+use super::cpr::Position;
+use super::{TimedMessage, DF, ICAO};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Mean Earth radius, in m
+const RADIUS: f64 = 6_371_000.;
+/// One knot, in m/s
+const KNOT: f64 = 1852. / 3600.;
+/// Distance (in m) from the origin of the tangent plane before moving it
+const RECENTER: f64 = 100_000.;
+/// Squared normalized distance above which a position is rejected (5σ)
+const GATE: f64 = 25.;
+/// Number of rejected positions in a row before restarting the track
+const MAX_REJECTED: u8 = 3;
+/// Initial uncertainty of an unknown speed, in m/s (or ft/s)
+const UNKNOWN_SPEED: f64 = 300.;
+
+/**
+ * The noise parameters of the filters.
+ *
+ * Observation noises are standard deviations; process noises are the
+ * spectral densities of the (random) accelerations.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    /// Noise of decoded positions, in m
+    pub position: f64,
+    /// Noise of ADS-B velocities, in m/s
+    pub velocity: f64,
+    /// Noise of Comm-B speeds (BDS 5,0), in m/s
+    pub commb_velocity: f64,
+    /// Noise of barometric altitudes, in ft
+    pub altitude: f64,
+    /// Noise of vertical rates, in ft/s
+    pub vertical_rate: f64,
+    /// Horizontal process noise, in m²/s³
+    pub horizontal: f64,
+    /// Vertical process noise, in ft²/s³
+    pub vertical: f64,
+    /// Maximum silence (in s) before a track restarts
+    pub max_gap: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            position: 50.,
+            velocity: 2.,
+            commb_velocity: 5.,
+            altitude: 15.,
+            vertical_rate: 2.,
+            horizontal: 1.,
+            vertical: 10.,
+            max_gap: 60.,
+        }
+    }
+}
+
+/// The smoothed state vector of an aircraft
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Smoothed {
+    /// The timestamp of the last observation (in s)
+    pub timestamp: f64,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Barometric altitude (in ft), unknown until the first altitude
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    /// Groundspeed, in kts
+    pub groundspeed: f64,
+    /// True track angle, in degrees
+    pub track: f64,
+    /// Vertical rate, in ft/min
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_rate: Option<f64>,
+}
+
+/// A Kalman filter for a position and a speed along one axis
+#[derive(Debug, Clone, Copy)]
+struct Axis {
+    x: [f64; 2],
+    p: [[f64; 2]; 2],
+}
+
+impl Axis {
+    fn new(position: f64, noise: f64) -> Self {
+        Axis {
+            x: [position, 0.],
+            p: [[noise * noise, 0.], [0., UNKNOWN_SPEED * UNKNOWN_SPEED]],
+        }
+    }
+
+    /// Extrapolate the state with a constant speed for `dt` seconds
+    fn predict(&mut self, dt: f64, q: f64) {
+        let [[p00, p01], [_, p11]] = self.p;
+        self.x[0] += dt * self.x[1];
+        let p00 = p00 + 2. * dt * p01 + dt * dt * p11 + q * dt.powi(3) / 3.;
+        let p01 = p01 + dt * p11 + q * dt * dt / 2.;
+        self.p = [[p00, p01], [p01, p11 + q * dt]];
+    }
+
+    /// The squared normalized innovation of an observation of state `i`
+    fn distance(&self, i: usize, z: f64, noise: f64) -> f64 {
+        (z - self.x[i]).powi(2) / (self.p[i][i] + noise * noise)
+    }
+
+    /// Update the state with an observation of the position (0) or speed (1)
+    fn update(&mut self, i: usize, z: f64, noise: f64) {
+        let s = self.p[i][i] + noise * noise;
+        let k = [self.p[0][i] / s, self.p[1][i] / s];
+        let y = z - self.x[i];
+        let row = self.p[i];
+        for (j, k) in k.iter().enumerate() {
+            self.x[j] += k * y;
+            for (l, value) in row.iter().enumerate() {
+                self.p[j][l] -= k * value;
+            }
+        }
+    }
+}
+
+/// The filters of one aircraft
+#[derive(Debug, Clone)]
+struct Track {
+    origin: Position,
+    timestamp: f64,
+    east: Axis,
+    north: Axis,
+    vertical: Option<Axis>,
+    rejected: u8,
+}
+
+impl Track {
+    fn new(timestamp: f64, origin: Position, settings: &Settings) -> Self {
+        Track {
+            origin,
+            timestamp,
+            east: Axis::new(0., settings.position),
+            north: Axis::new(0., settings.position),
+            vertical: None,
+            rejected: 0,
+        }
+    }
+
+    /// Coordinates of a position in the tangent plane, in m
+    fn project(&self, position: &Position) -> (f64, f64) {
+        let x = (position.longitude - self.origin.longitude).to_radians()
+            * RADIUS
+            * self.origin.latitude.to_radians().cos();
+        let y =
+            (position.latitude - self.origin.latitude).to_radians() * RADIUS;
+        (x, y)
+    }
+
+    /// Position of coordinates in the tangent plane
+    fn unproject(&self, x: f64, y: f64) -> Position {
+        let cos = self.origin.latitude.to_radians().cos();
+        Position {
+            latitude: self.origin.latitude + (y / RADIUS).to_degrees(),
+            longitude: self.origin.longitude + (x / RADIUS / cos).to_degrees(),
+        }
+    }
+
+    fn predict(&mut self, timestamp: f64, settings: &Settings) {
+        let dt = timestamp - self.timestamp;
+        if dt <= 0. {
+            return;
+        }
+        self.east.predict(dt, settings.horizontal);
+        self.north.predict(dt, settings.horizontal);
+        if let Some(vertical) = &mut self.vertical {
+            vertical.predict(dt, settings.vertical);
+        }
+        self.timestamp = timestamp;
+    }
+
+    /// Move the origin of the tangent plane to the current position
+    fn recenter(&mut self) {
+        let (x, y) = (self.east.x[0], self.north.x[0]);
+        if x.hypot(y) > RECENTER {
+            self.origin = self.unproject(x, y);
+            self.east.x[0] = 0.;
+            self.north.x[0] = 0.;
+        }
+    }
+
+    /// Update with a position, false if it is rejected
+    fn position(&mut self, position: &Position, noise: f64) -> bool {
+        let (x, y) = self.project(position);
+        let distance =
+            self.east.distance(0, x, noise) + self.north.distance(0, y, noise);
+        if distance > GATE {
+            self.rejected += 1;
+            return false;
+        }
+        self.rejected = 0;
+        self.east.update(0, x, noise);
+        self.north.update(0, y, noise);
+        self.recenter();
+        true
+    }
+
+    /// Update with a groundspeed (in kts) and a track angle (in degrees)
+    fn velocity(&mut self, groundspeed: f64, track: f64, noise: f64) {
+        let (sin, cos) = track.to_radians().sin_cos();
+        let speed = groundspeed * KNOT;
+        self.east.update(1, speed * sin, noise);
+        self.north.update(1, speed * cos, noise);
+    }
+
+    fn altitude(&mut self, altitude: f64, noise: f64) {
+        match &mut self.vertical {
+            Some(vertical) => vertical.update(0, altitude, noise),
+            None => self.vertical = Some(Axis::new(altitude, noise)),
+        }
+    }
+
+    /// Update with a vertical rate, in ft/min
+    fn vertical_rate(&mut self, vertical_rate: f64, noise: f64) {
+        if let Some(vertical) = &mut self.vertical {
+            vertical.update(1, vertical_rate / 60., noise);
+        }
+    }
+
+    fn state(&self) -> Smoothed {
+        let position = self.unproject(self.east.x[0], self.north.x[0]);
+        let (vx, vy) = (self.east.x[1], self.north.x[1]);
+        Smoothed {
+            timestamp: self.timestamp,
+            latitude: position.latitude,
+            longitude: position.longitude,
+            altitude: self.vertical.map(|v| v.x[0]),
+            groundspeed: vx.hypot(vy) / KNOT,
+            track: vx.atan2(vy).to_degrees().rem_euclid(360.),
+            vertical_rate: self.vertical.map(|v| v.x[1] * 60.),
+        }
+    }
+}
+
+/**
+ * The Kalman filters of all the aircraft.
+ *
+ * Messages are fed in timestamp order with [`Tracker::observe`], after the
+ * decoding of positions (see [`super::cpr::decode_position`]). Tracks start
+ * at the first airborne position of an aircraft: velocities received before
+ * are ignored. Tracks silent for longer than `max_gap` are forgotten.
+ */
+#[derive(Debug, Default)]
+pub struct Tracker {
+    pub settings: Settings,
+    tracks: BTreeMap<ICAO, Track>,
+    /// The timestamp of the last removal of silent tracks
+    pruned: f64,
+}
+
+impl Tracker {
+    pub fn new(settings: Settings) -> Self {
+        Tracker {
+            settings,
+            ..Tracker::default()
+        }
+    }
+
+    /**
+     * Update the track of an aircraft with the content of a message, and
+     * return its smoothed state if the message was used.
+     */
+    pub fn observe(&mut self, msg: &TimedMessage) -> Option<Smoothed> {
+        let timestamp = msg.timestamp.as_secs_f64();
+        self.prune(timestamp);
+        match &msg.message.as_ref()?.df {
+            DF::ExtendedSquitterADSB(adsb) => match &adsb.message {
+                ME::BDS05(position) => {
+                    self.airborne_position(&adsb.icao24, timestamp, position)
+                }
+                ME::BDS09(velocity) => {
+                    self.airborne_velocity(&adsb.icao24, timestamp, velocity)
+                }
+                _ => None,
+            },
+            DF::CommBAltitudeReply { bds, ap, ac, .. } => {
+                let DF20DataSelector { bds50, bds60, .. } = bds;
+                self.commb(ap, timestamp, Some(ac.0), bds50, bds60)
+            }
+            DF::CommBIdentityReply { bds, ap, .. } => {
+                let DF21DataSelector { bds50, bds60, .. } = bds;
+                self.commb(ap, timestamp, None, bds50, bds60)
+            }
+            _ => None,
+        }
+    }
+
+    /// The smoothed state of an aircraft, at its last observation
+    pub fn state(&self, icao24: &ICAO) -> Option<Smoothed> {
+        self.tracks.get(icao24).map(Track::state)
+    }
+
+    fn prune(&mut self, timestamp: f64) {
+        if timestamp - self.pruned < self.settings.max_gap {
+            return;
+        }
+        let max_gap = self.settings.max_gap;
+        self.tracks
+            .retain(|_, track| timestamp - track.timestamp <= max_gap);
+        self.pruned = timestamp;
+    }
+
+    /// The track of an aircraft, predicted at the timestamp (if recent)
+    fn track(&mut self, icao24: &ICAO, timestamp: f64) -> Option<&mut Track> {
+        let settings = self.settings;
+        let last = self.tracks.get(icao24)?.timestamp;
+        if timestamp - last > settings.max_gap {
+            self.tracks.remove(icao24);
+            return None;
+        }
+        let track = self.tracks.get_mut(icao24)?;
+        track.predict(timestamp, &settings);
+        Some(track)
+    }
+
+    fn airborne_position(
+        &mut self,
+        icao24: &ICAO,
+        timestamp: f64,
+        msg: &AirbornePosition,
+    ) -> Option<Smoothed> {
+        let position = Position {
+            latitude: msg.latitude?,
+            longitude: msg.longitude?,
+        };
+        let settings = self.settings;
+        let track = match self.track(icao24, timestamp) {
+            Some(track) => track,
+            None => self
+                .tracks
+                .entry(*icao24)
+                .or_insert(Track::new(timestamp, position, &settings)),
+        };
+        if !track.position(&position, settings.position) {
+            if track.rejected >= MAX_REJECTED {
+                // The track was probably wrong, start again from here
+                *track = Track::new(timestamp, position, &settings);
+            } else {
+                return None;
+            }
+        }
+        // GNSS heights would mix with barometric altitudes
+        if let (Some(altitude), Source::Barometric) = (msg.alt, msg.source) {
+            track.altitude(altitude as f64, settings.altitude);
+        }
+        Some(track.state())
+    }
+
+    fn airborne_velocity(
+        &mut self,
+        icao24: &ICAO,
+        timestamp: f64,
+        msg: &AirborneVelocity,
+    ) -> Option<Smoothed> {
+        let settings = self.settings;
+        let track = self.track(icao24, timestamp)?;
+        let mut updated = false;
+        if let AirborneVelocitySubType::GroundSpeedDecoding(velocity) =
+            &msg.velocity
+        {
+            track.velocity(
+                velocity.groundspeed,
+                velocity.track,
+                settings.velocity,
+            );
+            updated = true;
+        }
+        if let Some(vertical_rate) = msg.vertical_rate {
+            track.vertical_rate(vertical_rate as f64, settings.vertical_rate);
+            updated = true;
+        }
+        updated.then(|| track.state())
+    }
+
+    fn commb(
+        &mut self,
+        icao24: &ICAO,
+        timestamp: f64,
+        altitude: Option<u16>,
+        bds50: &Option<TrackAndTurnReport>,
+        bds60: &Option<HeadingAndSpeedReport>,
+    ) -> Option<Smoothed> {
+        let settings = self.settings;
+        let track = self.track(icao24, timestamp)?;
+        let mut updated = false;
+        if let Some(altitude) = altitude.filter(|&altitude| altitude > 0) {
+            track.altitude(altitude as f64, settings.altitude);
+            updated = true;
+        }
+        if let Some(bds50) = bds50 {
+            if let (Some(groundspeed), Some(angle)) =
+                (bds50.groundspeed, bds50.track_angle)
+            {
+                let noise = settings.commb_velocity;
+                track.velocity(groundspeed as f64, angle, noise);
+                updated = true;
+            }
+        }
+        if let Some(bds60) = bds60 {
+            let vertical_rate = bds60
+                .inertial_vertical_velocity
+                .or(bds60.barometric_altitude_rate);
+            if let Some(vertical_rate) = vertical_rate {
+                track.vertical_rate(
+                    vertical_rate as f64,
+                    settings.vertical_rate,
+                );
+                updated = true;
+            }
+        }
+        updated.then(|| track.state())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_axis() {
+        // A constant speed of 200 m/s, with noisy positions
+        let mut axis = Axis::new(0., 50.);
+        let noise = [30., -40., 10., 45., -25., -5., 35., -50., 20., 0.];
+        for (i, n) in noise.iter().cycle().take(60).enumerate() {
+            let t = (i + 1) as f64;
+            axis.predict(1., 1.);
+            axis.update(0, 200. * t + n, 50.);
+        }
+        assert!((axis.x[0] - 12_000.).abs() < 30.);
+        assert!((axis.x[1] - 200.).abs() < 2.);
+        assert!(axis.p[0][0] < 50. * 50.);
+    }
+
+    #[test]
+    fn test_track() {
+        let settings = Settings::default();
+        let origin = Position {
+            latitude: 43.6,
+            longitude: 1.4,
+        };
+        let mut track = Track::new(0., origin, &settings);
+        // Heading east at 400 kts, climbing at 1500 ft/min
+        for i in 1..=60 {
+            let t = i as f64;
+            track.predict(t, &settings);
+            let position = track.unproject(400. * KNOT * t, 0.);
+            assert!(track.position(&position, settings.position));
+            track.altitude(10_000. + 25. * t, settings.altitude);
+            if i % 2 == 0 {
+                track.velocity(400., 90., settings.velocity);
+                track.vertical_rate(1500., settings.vertical_rate);
+            }
+        }
+        let state = track.state();
+        assert!((state.groundspeed - 400.).abs() < 1.);
+        assert!((state.track - 90.).abs() < 0.5);
+        assert!((state.altitude.unwrap() - 11_500.).abs() < 10.);
+        assert!((state.vertical_rate.unwrap() - 1500.).abs() < 30.);
+        assert!((state.latitude - 43.6).abs() < 1e-3);
+
+        // A position 20 km away is rejected, then the track restarts
+        let glitch = Position {
+            latitude: state.latitude + 0.2,
+            longitude: state.longitude,
+        };
+        track.predict(61., &settings);
+        assert!(!track.position(&glitch, settings.position));
+        assert_eq!(track.rejected, 1);
+    }
+}
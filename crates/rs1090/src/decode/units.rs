@@ -0,0 +1,473 @@
+/*!
+ * Units of the serialized decoded values.
+ *
+ * Decoded values come in the units of the standards: altitudes in ft, speeds
+ * in kts, vertical rates in ft/min, pressures in hPa, temperatures in °C.
+ * These units are implicit in the default serialization, which regularly
+ * causes integration bugs downstream. With [`serialize_config`], fields with
+ * a unit can be serialized instead:
+ *
+ * - with an explicit unit in their name ([`Units::Explicit`]), e.g.
+ *   `"altitude_ft": 37000`;
+ * - converted to SI units ([`Units::Si`]), e.g. `"altitude_m": 11277.6`.
+ *
+ * Angles remain in degrees (with a `_deg` suffix) in both cases.
+ *
+ * Decoded floating point values come with their full precision. A
+ * [`Precision`] sets the number of decimal places of positions, altitudes
+ * and speeds (after the conversion to SI units), making payloads smaller.
+ *
+ * [`serialize_config`]: crate::decode::serialize_config
+ */
+
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+use super::DF;
+
+/// How the units of decoded values appear in the serialization
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// The units of the standards, not mentioned in the field names
+    #[default]
+    Implicit,
+    /// The units of the standards, with a suffix in the field names
+    Explicit,
+    /// Converted to SI units, with a suffix in the field names
+    Si,
+}
+
+impl FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "implicit" => Ok(Units::Implicit),
+            "explicit" => Ok(Units::Explicit),
+            "si" => Ok(Units::Si),
+            _ => Err(format!("unknown units: {s} (implicit, explicit or si)")),
+        }
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Units::Implicit => write!(f, "implicit"),
+            Units::Explicit => write!(f, "explicit"),
+            Units::Si => write!(f, "si"),
+        }
+    }
+}
+
+/**
+ * The number of decimal places of serialized floating point values, for
+ * each kind of value (all the decimals if not set).
+ *
+ * The string representation lists the kinds with their number of decimal
+ * places, e.g. `position=5,altitude=0,speed=1`.
+ */
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub struct Precision {
+    /// Latitudes and longitudes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<u8>,
+    /// Altitudes and heights
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<u8>,
+    /// Speeds and vertical rates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u8>,
+}
+
+impl Precision {
+    /// True if all the decimals of all the values are kept
+    pub fn is_empty(&self) -> bool {
+        *self == Precision::default()
+    }
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut precision = Precision::default();
+        for item in s.split(',').filter(|item| !item.is_empty()) {
+            let (kind, decimals) = item
+                .split_once('=')
+                .ok_or_else(|| format!("expected kind=decimals: {item}"))?;
+            let decimals = decimals
+                .trim()
+                .parse::<u8>()
+                .map_err(|e| format!("{item}: {e}"))?;
+            match kind.trim() {
+                "position" => precision.position = Some(decimals),
+                "altitude" => precision.altitude = Some(decimals),
+                "speed" => precision.speed = Some(decimals),
+                _ => {
+                    return Err(format!(
+                        "unknown kind: {kind} (position, altitude or speed)"
+                    ))
+                }
+            }
+        }
+        Ok(precision)
+    }
+}
+
+/// Fields rounded with the precision of positions
+const POSITION_FIELDS: &[&str] = &["latitude", "longitude"];
+
+/// Round a floating point value (integers are left untouched)
+fn round(value: &mut Value, decimals: Option<u8>) {
+    if let (Some(decimals), Some(v)) = (decimals, value.as_f64()) {
+        if value.is_f64() {
+            let factor = 10f64.powi(decimals as i32);
+            *value = ((v * factor).round() / factor).into();
+        }
+    }
+}
+
+/// The unit of a decoded value
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unit {
+    Feet,
+    Knots,
+    FeetPerMinute,
+    HectoPascal,
+    Celsius,
+    Degrees,
+    DegreesPerSecond,
+}
+
+impl Unit {
+    /// The suffix of the field name, in the standard and in SI units
+    fn suffix(self, units: Units) -> &'static str {
+        match (self, units) {
+            (Unit::Feet, Units::Si) => "m",
+            (Unit::Feet, _) => "ft",
+            (Unit::Knots, Units::Si) => "mps",
+            (Unit::Knots, _) => "kt",
+            (Unit::FeetPerMinute, Units::Si) => "mps",
+            (Unit::FeetPerMinute, _) => "fpm",
+            (Unit::HectoPascal, Units::Si) => "pa",
+            (Unit::HectoPascal, _) => "hpa",
+            (Unit::Celsius, Units::Si) => "k",
+            (Unit::Celsius, _) => "c",
+            (Unit::Degrees, _) => "deg",
+            (Unit::DegreesPerSecond, _) => "deg_s",
+        }
+    }
+
+    /// The number of decimal places of values in this unit
+    fn decimals(self, precision: &Precision) -> Option<u8> {
+        match self {
+            Unit::Feet => precision.altitude,
+            Unit::Knots | Unit::FeetPerMinute => precision.speed,
+            _ => None,
+        }
+    }
+
+    /// Convert a value to SI units (rounded to three decimals)
+    fn to_si(self, value: f64) -> f64 {
+        let value = match self {
+            Unit::Feet => value * 0.3048,
+            Unit::Knots => value * 1852. / 3600.,
+            Unit::FeetPerMinute => value * 0.3048 / 60.,
+            Unit::HectoPascal => value * 100.,
+            Unit::Celsius => value + 273.15,
+            Unit::Degrees | Unit::DegreesPerSecond => return value,
+        };
+        (value * 1000.).round() / 1000.
+    }
+}
+
+/// The serialized fields with a unit, in all the downlink formats and BDS
+const FIELDS: &[(&str, Unit)] = &[
+    ("altitude", Unit::Feet),
+    ("geo_minus_baro", Unit::Feet),
+    ("selected_altitude", Unit::Feet),
+    ("selected_mcp", Unit::Feet),
+    ("selected_fms", Unit::Feet),
+    ("radio_height", Unit::Feet),
+    ("groundspeed", Unit::Knots),
+    ("IAS", Unit::Knots),
+    ("TAS", Unit::Knots),
+    ("wind_speed", Unit::Knots),
+    ("vertical_rate", Unit::FeetPerMinute),
+    ("vrate_barometric", Unit::FeetPerMinute),
+    ("vrate_inertial", Unit::FeetPerMinute),
+    ("barometric_setting", Unit::HectoPascal),
+    ("pressure", Unit::HectoPascal),
+    ("static_pressure", Unit::HectoPascal),
+    ("temperature", Unit::Celsius),
+    ("static_temperature", Unit::Celsius),
+    ("track", Unit::Degrees),
+    ("heading", Unit::Degrees),
+    ("selected_heading", Unit::Degrees),
+    ("wind_direction", Unit::Degrees),
+    ("roll", Unit::Degrees),
+    ("track_rate", Unit::DegreesPerSecond),
+];
+
+/**
+ * Rename (and convert in SI units) the fields with a unit of a serialized
+ * message, in nested objects as well.
+ */
+pub fn convert(value: &mut Value, units: Units) {
+    transform(value, units, &Precision::default())
+}
+
+/**
+ * Rename (and convert in SI units) the fields with a unit of a serialized
+ * message, then round them with the given precision, in nested objects as
+ * well.
+ */
+pub fn transform(value: &mut Value, units: Units, precision: &Precision) {
+    match value {
+        Value::Object(fields) => {
+            let entries = std::mem::take(fields);
+            for (key, mut value) in entries {
+                let unit = FIELDS
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, unit)| *unit);
+                let key = match unit {
+                    Some(unit) => {
+                        if let (Units::Si, Some(v)) = (units, value.as_f64()) {
+                            value = unit.to_si(v).into();
+                        }
+                        round(&mut value, unit.decimals(precision));
+                        match units {
+                            Units::Implicit => key,
+                            _ => format!("{key}_{}", unit.suffix(units)),
+                        }
+                    }
+                    None if POSITION_FIELDS.contains(&key.as_str()) => {
+                        round(&mut value, precision.position);
+                        key
+                    }
+                    None => {
+                        transform(&mut value, units, precision);
+                        key
+                    }
+                };
+                fields.insert(key, value);
+            }
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| transform(value, units, precision)),
+        _ => {}
+    }
+}
+
+/**
+ * Rename the fields with a unit in a JSON schema of serialized messages, as
+ * [`transform`] does in the messages. Converted to SI units, values of these
+ * fields are floating point numbers.
+ */
+pub fn transform_schema(schema: &mut Value, units: Units) {
+    if units == Units::Implicit {
+        return;
+    }
+    let Value::Object(fields) = schema else {
+        if let Value::Array(values) = schema {
+            values
+                .iter_mut()
+                .for_each(|value| transform_schema(value, units));
+        }
+        return;
+    };
+    let unit_of = |key: &str| {
+        FIELDS
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, unit)| *unit)
+    };
+    if let Some(Value::Object(properties)) = fields.get_mut("properties") {
+        let entries = std::mem::take(properties);
+        for (key, mut property) in entries {
+            let key = match unit_of(&key) {
+                Some(unit) => {
+                    if units == Units::Si {
+                        as_number(&mut property);
+                    }
+                    format!("{key}_{}", unit.suffix(units))
+                }
+                None => key,
+            };
+            properties.insert(key, property);
+        }
+    }
+    if let Some(Value::Array(required)) = fields.get_mut("required") {
+        for name in required.iter_mut() {
+            if let Some(key) = name.as_str() {
+                if let Some(unit) = unit_of(key) {
+                    *name = format!("{key}_{}", unit.suffix(units)).into();
+                }
+            }
+        }
+    }
+    fields
+        .values_mut()
+        .for_each(|value| transform_schema(value, units));
+}
+
+/// Change the integer type of a property into a floating point number
+fn as_number(property: &mut Value) {
+    let Value::Object(fields) = property else {
+        return;
+    };
+    let integer = Value::from("integer");
+    match fields.get_mut("type") {
+        Some(kind) if *kind == integer => *kind = "number".into(),
+        Some(Value::Array(kinds)) if kinds.contains(&integer) => {
+            kinds
+                .iter_mut()
+                .filter(|kind| **kind == integer)
+                .for_each(|kind| *kind = "number".into());
+        }
+        _ => return,
+    }
+    fields.remove("format");
+}
+
+/// Serialize the downlink format with the configured units and precision
+pub fn serialize_df<S: Serializer>(
+    df: &DF,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let (units, precision) = (super::units(), super::precision());
+    if units == Units::Implicit && precision.is_empty() {
+        return df.serialize(serializer);
+    }
+    let mut value =
+        serde_json::to_value(df).map_err(serde::ser::Error::custom)?;
+    transform(&mut value, units, &precision);
+    value.serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use hexlit::hex;
+    use serde_json::json;
+
+    #[test]
+    fn test_units() {
+        let bytes = hex!("8d485020994409940838175b284f");
+        let msg = Message::try_from(bytes.as_slice()).unwrap();
+        let implicit = serde_json::to_value(&msg).unwrap();
+
+        let mut value = implicit.clone();
+        convert(&mut value, Units::Implicit);
+        assert_eq!(value, implicit);
+
+        let mut value = implicit.clone();
+        convert(&mut value, Units::Explicit);
+        assert_eq!(value["groundspeed_kt"], implicit["groundspeed"]);
+        assert_eq!(value["track_deg"], implicit["track"]);
+        assert_eq!(value["vertical_rate_fpm"], json!(-832));
+        assert_eq!(value["geo_minus_baro_ft"], json!(550));
+        assert!(value.get("groundspeed").is_none());
+        assert_eq!(value["icao24"], "485020");
+
+        let mut value = implicit.clone();
+        convert(&mut value, Units::Si);
+        assert_eq!(value["vertical_rate_mps"], json!(-4.227));
+        assert_eq!(value["geo_minus_baro_m"], json!(167.64));
+        let groundspeed = implicit["groundspeed"].as_f64().unwrap();
+        let si = value["groundspeed_mps"].as_f64().unwrap();
+        assert!((si - groundspeed * 0.514444).abs() < 1e-2);
+        assert_eq!(value["track_deg"], implicit["track"]);
+
+        let mut value = json!({"temperature": -45.5, "altitude": null});
+        convert(&mut value, Units::Si);
+        assert_eq!(value, json!({"temperature_k": 227.65, "altitude_m": null}));
+
+        assert_eq!("si".parse::<Units>(), Ok(Units::Si));
+        assert!("metric".parse::<Units>().is_err());
+    }
+
+    #[test]
+    fn test_schema() {
+        let schema = json!({
+            "type": "object",
+            "required": ["altitude", "icao24"],
+            "properties": {
+                "altitude": {"type": ["integer", "null"], "format": "uint16"},
+                "track": {"type": "number", "format": "double"},
+                "icao24": {"type": "string"},
+            },
+            "definitions": {
+                "BDS": {"properties": {"wind_speed": {"type": "integer"}}},
+            },
+        });
+
+        let mut value = schema.clone();
+        transform_schema(&mut value, Units::Implicit);
+        assert_eq!(value, schema);
+
+        let mut value = schema.clone();
+        transform_schema(&mut value, Units::Explicit);
+        assert_eq!(value["required"], json!(["altitude_ft", "icao24"]));
+        assert_eq!(
+            value["properties"]["altitude_ft"],
+            schema["properties"]["altitude"]
+        );
+        assert_eq!(value["properties"]["track_deg"]["format"], "double");
+        assert!(value["definitions"]["BDS"]["properties"]["wind_speed_kt"]
+            .is_object());
+
+        let mut value = schema.clone();
+        transform_schema(&mut value, Units::Si);
+        assert_eq!(
+            value["properties"]["altitude_m"],
+            json!({"type": ["number", "null"]})
+        );
+        assert_eq!(
+            value["definitions"]["BDS"]["properties"]["wind_speed_mps"],
+            json!({"type": "number"})
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let precision: Precision =
+            "position=3,altitude=0,speed=1".parse().unwrap();
+        assert_eq!(precision.position, Some(3));
+        assert!("position=3,heading=1".parse::<Precision>().is_err());
+        assert!("".parse::<Precision>().unwrap().is_empty());
+
+        let mut value = json!({
+            "latitude": 43.619241, "longitude": 1.374512,
+            "altitude": 38000, "groundspeed": 452.213, "track": 12.34567,
+            "bds": [{"wind_speed": 11.26}],
+        });
+        transform(&mut value, Units::Implicit, &precision);
+        assert_eq!(value["latitude"], json!(43.619));
+        assert_eq!(value["longitude"], json!(1.375));
+        // Integers are not changed into floats
+        assert_eq!(value["altitude"], json!(38000));
+        assert_eq!(value["groundspeed"], json!(452.2));
+        assert_eq!(value["track"], json!(12.34567));
+        assert_eq!(value["bds"][0]["wind_speed"], json!(11.3));
+
+        // Values are rounded after the conversion to SI units
+        let mut value = json!({"altitude": 1234.5, "groundspeed": 452.213});
+        transform(&mut value, Units::Si, &precision);
+        assert_eq!(
+            value,
+            json!({"altitude_m": 376.0, "groundspeed_mps": 232.6})
+        );
+    }
+}
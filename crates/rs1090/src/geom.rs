@@ -0,0 +1,363 @@
+//! Conversions between coordinate reference systems.
+//!
+//! Positions decoded from ADS-B are expressed as WGS84 latitude and
+//! longitude, with a barometric altitude. Fusion with other sources of
+//! surveillance (e.g. radar plots) is easier in Cartesian frames: Earth
+//! Centered Earth Fixed (ECEF) coordinates, or local East North Up (ENU)
+//! coordinates relative to a receiver.
+//!
+//! Field deployments also often get the positions of receivers in grid
+//! formats: UTM and MGRS references are converted to WGS84 coordinates.
+
+/// WGS84 semi-major axis, in meters
+const WGS84_A: f64 = 6_378_137.0;
+/// WGS84 flattening
+const WGS84_F: f64 = 1. / 298.257_223_563;
+/// WGS84 first eccentricity squared
+const WGS84_E2: f64 = WGS84_F * (2. - WGS84_F);
+
+/// Conversion factor from feet to meters
+pub const FT: f64 = 0.3048;
+
+/// Pressure at the mean sea level in the standard atmosphere (ISA), in hPa
+pub const ISA_PRESSURE: f64 = 1013.25;
+/// Temperature lapse rate over the standard temperature, per foot (ISA)
+const ISA_LAPSE: f64 = 6.875_585_6e-6;
+/// Exponent of the barometric formula in the troposphere (ISA)
+const ISA_EXPONENT: f64 = 5.255_879_7;
+
+/**
+ * The WGS84 geometric height of an aircraft, in meters.
+ *
+ * The GNSS height is computed from the barometric altitude and the
+ * difference between the GNSS height and the barometric altitude (both in
+ * feet), as found in airborne velocity messages (BDS 0,9).
+ *
+ * ADS-B transponders report the GNSS height above the WGS84 ellipsoid
+ * (HAE), so no geoid correction is needed. If the height above the mean sea
+ * level is reported instead, pass the geoid undulation at the position of
+ * the aircraft (in meters) to convert it to an ellipsoidal height.
+ */
+pub fn geometric_height(
+    altitude: i32,
+    geo_minus_baro: i16,
+    undulation: Option<f64>,
+) -> f64 {
+    (altitude + geo_minus_baro as i32) as f64 * FT + undulation.unwrap_or(0.)
+}
+
+/**
+ * Correct a barometric altitude (in feet) with the local QNH (in hPa).
+ *
+ * Transponders report the pressure altitude, i.e. the altitude in the
+ * standard atmosphere with a 1013.25 hPa setting. Near airfields, the
+ * altitude above the mean sea level is closer to the one shown with the
+ * local QNH setting: about 27 ft more per hPa above the standard pressure.
+ * The correction only holds in the troposphere, i.e. at low altitudes.
+ */
+pub fn qnh_altitude(altitude: f64, qnh: f64) -> f64 {
+    let pressure =
+        ISA_PRESSURE * (1. - ISA_LAPSE * altitude).powf(ISA_EXPONENT);
+    (1. - (pressure / qnh).powf(1. / ISA_EXPONENT)) / ISA_LAPSE
+}
+
+/**
+ * Convert WGS84 coordinates (in degrees, height in meters above the
+ * ellipsoid) to ECEF coordinates, in meters.
+ */
+pub fn ecef(latitude: f64, longitude: f64, height: f64) -> [f64; 3] {
+    let (sin_lat, cos_lat) = latitude.to_radians().sin_cos();
+    let (sin_lon, cos_lon) = longitude.to_radians().sin_cos();
+    // Prime vertical radius of curvature
+    let n = WGS84_A / (1. - WGS84_E2 * sin_lat * sin_lat).sqrt();
+    [
+        (n + height) * cos_lat * cos_lon,
+        (n + height) * cos_lat * sin_lon,
+        (n * (1. - WGS84_E2) + height) * sin_lat,
+    ]
+}
+
+/**
+ * Convert ECEF coordinates to local East North Up coordinates (in meters),
+ * relative to a reference given in WGS84 coordinates (in degrees, height in
+ * meters above the ellipsoid), e.g. the position of the receiver.
+ */
+pub fn enu(
+    ecef: [f64; 3],
+    latitude: f64,
+    longitude: f64,
+    height: f64,
+) -> [f64; 3] {
+    let [x0, y0, z0] = self::ecef(latitude, longitude, height);
+    let (dx, dy, dz) = (ecef[0] - x0, ecef[1] - y0, ecef[2] - z0);
+    let (sin_lat, cos_lat) = latitude.to_radians().sin_cos();
+    let (sin_lon, cos_lon) = longitude.to_radians().sin_cos();
+    [
+        -sin_lon * dx + cos_lon * dy,
+        -sin_lat * cos_lon * dx - sin_lat * sin_lon * dy + cos_lat * dz,
+        cos_lat * cos_lon * dx + cos_lat * sin_lon * dy + sin_lat * dz,
+    ]
+}
+
+/// UTM scale factor on the central meridian
+const UTM_K0: f64 = 0.9996;
+/// Latitude bands of UTM and MGRS, 8° wide from 80°S (12° wide for X)
+const BANDS: &str = "CDEFGHJKLMNPQRSTUVWX";
+/// MGRS column letters (eastings), repeating every three zones
+const MGRS_COLUMNS: [&str; 3] = ["ABCDEFGH", "JKLMNPQR", "STUVWXYZ"];
+/// MGRS row letters (northings), shifted by five rows for even zones
+const MGRS_ROWS: &str = "ABCDEFGHJKLMNPQRSTUV";
+
+/**
+ * Convert UTM coordinates (easting and northing, in meters) to WGS84
+ * latitude and longitude (in degrees).
+ *
+ * Northings in the southern hemisphere include the false northing of
+ * 10,000 km. The conversion follows the series of Snyder (Map Projections,
+ * A Working Manual), accurate to the meter within the zone.
+ */
+pub fn utm(zone: u8, north: bool, easting: f64, northing: f64) -> (f64, f64) {
+    let ep2 = WGS84_E2 / (1. - WGS84_E2);
+    let x = easting - 500_000.;
+    let y = if north {
+        northing
+    } else {
+        northing - 10_000_000.
+    };
+
+    // The footprint latitude, from the meridian arc
+    let e2 = WGS84_E2;
+    let mu = y
+        / UTM_K0
+        / (WGS84_A
+            * (1. - e2 / 4. - 3. * e2 * e2 / 64. - 5. * e2.powi(3) / 256.));
+    let e1 = (1. - (1. - e2).sqrt()) / (1. + (1. - e2).sqrt());
+    let phi = mu
+        + (3. * e1 / 2. - 27. * e1.powi(3) / 32.) * (2. * mu).sin()
+        + (21. * e1 * e1 / 16. - 55. * e1.powi(4) / 32.) * (4. * mu).sin()
+        + (151. * e1.powi(3) / 96.) * (6. * mu).sin()
+        + (1097. * e1.powi(4) / 512.) * (8. * mu).sin();
+
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let c = ep2 * cos_phi * cos_phi;
+    let t = phi.tan() * phi.tan();
+    let w = 1. - e2 * sin_phi * sin_phi;
+    let n = WGS84_A / w.sqrt();
+    let r = WGS84_A * (1. - e2) / w.powf(1.5);
+    let d = x / (n * UTM_K0);
+
+    let latitude = phi
+        - (n * phi.tan() / r)
+            * (d.powi(2) / 2.
+                - (5. + 3. * t + 10. * c - 4. * c * c - 9. * ep2) * d.powi(4)
+                    / 24.
+                + (61. + 90. * t + 298. * c + 45. * t * t
+                    - 252. * ep2
+                    - 3. * c * c)
+                    * d.powi(6)
+                    / 720.);
+    let longitude = (d - (1. + 2. * t + c) * d.powi(3) / 6.
+        + (5. - 2. * c + 28. * t - 3. * c * c + 8. * ep2 + 24. * t * t)
+            * d.powi(5)
+            / 120.)
+        / cos_phi;
+    let central_meridian = zone as f64 * 6. - 183.;
+    (
+        latitude.to_degrees(),
+        central_meridian + longitude.to_degrees(),
+    )
+}
+
+/**
+ * Parse a UTM or MGRS grid reference, and convert it to WGS84 latitude and
+ * longitude (in degrees).
+ *
+ * Both start with the zone (1 to 60) and the latitude band (C to X, N and
+ * above for the northern hemisphere):
+ *
+ * - UTM references continue with the easting and the northing in meters,
+ *   e.g. `31U 448251 5411932`;
+ * - MGRS references continue with the two letters of the 100 km square and
+ *   an even number of digits, e.g. `31UDQ4825111932` or `31U DQ 482 119`.
+ *
+ * Returns None if the string does not start like a grid reference.
+ */
+pub fn grid_reference(s: &str) -> Option<Result<(f64, f64), String>> {
+    let s = s.trim();
+    let digits = s.chars().take_while(|c| c.is_ascii_digit()).count();
+    let zone: u8 = s[..digits].parse().ok()?;
+    let band = s[digits..].chars().next()?.to_ascii_uppercase();
+    let band_index = BANDS.find(band)?;
+    if !(1..=60).contains(&zone) {
+        return Some(Err(format!("Invalid UTM zone: {zone}")));
+    }
+    let north = band >= 'N';
+    let rest = s[digits + 1..].trim();
+
+    if rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Some(mgrs(zone, north, band_index, rest));
+    }
+    let values = rest
+        .split_whitespace()
+        .map(|v| v.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>();
+    Some(match values.as_deref() {
+        Ok([easting, northing]) => Ok(utm(zone, north, *easting, *northing)),
+        Ok(_) => Err("UTM references need an easting and a northing".into()),
+        Err(e) => Err(format!("UTM parse error: {e}")),
+    })
+}
+
+fn mgrs(
+    zone: u8,
+    north: bool,
+    band_index: usize,
+    square: &str,
+) -> Result<(f64, f64), String> {
+    let square: String = square
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase();
+    if !square.is_ascii() {
+        return Err(format!("Invalid MGRS reference: {square}"));
+    }
+    let mut letters = square.chars();
+    let (Some(column), Some(row)) = (letters.next(), letters.next()) else {
+        return Err("MGRS references need two letters".into());
+    };
+    let digits = &square[2..];
+    if !digits.len().is_multiple_of(2)
+        || digits.len() > 10
+        || !digits.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(format!("Invalid MGRS digits: {digits}"));
+    }
+
+    let columns = MGRS_COLUMNS[(zone as usize - 1) % 3];
+    let column = columns
+        .find(column)
+        .ok_or_else(|| format!("Invalid MGRS column letter in zone {zone}"))?;
+    let row = MGRS_ROWS
+        .find(row)
+        .ok_or_else(|| "Invalid MGRS row letter".to_string())?;
+    let row = (row + if zone.is_multiple_of(2) { 15 } else { 0 }) % 20;
+
+    // Digits locate the south west corner of a cell within the square
+    let precision = digits.len() / 2;
+    let scale = 10f64.powi(5 - precision as i32);
+    let parse = |d: &str| d.parse::<f64>().unwrap_or(0.) * scale;
+    let easting = (column + 1) as f64 * 100_000. + parse(&digits[..precision]);
+    let northing = row as f64 * 100_000. + parse(&digits[precision..]);
+
+    // Row letters repeat every 2,000 km: keep the northing within the band
+    let band_center = match band_index {
+        19 => 78.,
+        _ => -76. + 8. * band_index as f64,
+    };
+    (0..5)
+        .map(|cycle| {
+            utm(zone, north, easting, northing + cycle as f64 * 2_000_000.)
+        })
+        .min_by(|(lat1, _), (lat2, _)| {
+            let d1 = (lat1 - band_center).abs();
+            let d2 = (lat2 - band_center).abs();
+            d1.total_cmp(&d2)
+        })
+        .ok_or_else(|| "Invalid MGRS reference".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_geometric_height() {
+        assert_relative_eq!(
+            geometric_height(37000, -475, None),
+            11132.82,
+            epsilon = 1e-6
+        );
+        assert_relative_eq!(
+            geometric_height(37000, -475, Some(50.)),
+            11182.82,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn test_qnh_altitude() {
+        assert_relative_eq!(
+            qnh_altitude(2500., ISA_PRESSURE),
+            2500.,
+            epsilon = 1e-6
+        );
+        // A high pressure lifts the altitude, about 27 ft per hPa
+        assert_relative_eq!(qnh_altitude(0., 1023.25), 271.5, epsilon = 0.1);
+        assert_relative_eq!(
+            qnh_altitude(1500., 1003.25),
+            1228.1,
+            epsilon = 0.1
+        );
+    }
+
+    #[test]
+    fn test_ecef() {
+        let [x, y, z] = ecef(0., 0., 0.);
+        assert_relative_eq!(x, WGS84_A);
+        assert_relative_eq!(y, 0.);
+        assert_relative_eq!(z, 0.);
+
+        // Semi-minor axis at the North pole
+        let [x, _, z] = ecef(90., 0., 100.);
+        assert_relative_eq!(x, 0., epsilon = 1e-6);
+        assert_relative_eq!(z, 6_356_752.314 + 100., epsilon = 1e-3);
+
+        // Toulouse airport
+        let [x, y, z] = ecef(43.628101, 1.367263, 150.);
+        assert_relative_eq!(x, 4_622_875.8, epsilon = 0.1);
+        assert_relative_eq!(y, 110_337.7, epsilon = 0.1);
+        assert_relative_eq!(z, 4_378_378.2, epsilon = 0.1);
+    }
+
+    #[test]
+    fn test_enu() {
+        let (lat, lon) = (43.628101, 1.367263);
+        let [e, n, u] = enu(ecef(lat, lon, 1150.), lat, lon, 150.);
+        assert_relative_eq!(e, 0., epsilon = 1e-6);
+        assert_relative_eq!(n, 0., epsilon = 1e-6);
+        assert_relative_eq!(u, 1000., epsilon = 1e-6);
+
+        let [e, n, _] = enu(ecef(lat + 0.1, lon + 0.1, 150.), lat, lon, 150.);
+        assert_relative_eq!(e, 8_057.3, epsilon = 0.1);
+        assert_relative_eq!(n, 11_115.7, epsilon = 0.1);
+    }
+
+    #[test]
+    fn test_grid_reference() {
+        // The Eiffel Tower
+        let (lat, lon) = grid_reference("31U 448251 5411932").unwrap().unwrap();
+        assert_relative_eq!(lat, 48.8582, epsilon = 1e-4);
+        assert_relative_eq!(lon, 2.2945, epsilon = 1e-4);
+        for mgrs in ["31UDQ4825111932", "31u dq 48251 11932"] {
+            let (lat, lon) = grid_reference(mgrs).unwrap().unwrap();
+            assert_relative_eq!(lat, 48.8582, epsilon = 1e-4);
+            assert_relative_eq!(lon, 2.2945, epsilon = 1e-4);
+        }
+        // Less precise references locate the corner of a larger cell
+        let (lat, lon) = grid_reference("31UDQ4811").unwrap().unwrap();
+        assert_relative_eq!(lat, 48.850, epsilon = 1e-3);
+        assert_relative_eq!(lon, 2.291, epsilon = 1e-3);
+
+        // The Sydney Opera House, in the southern hemisphere
+        let (lat, lon) = grid_reference("56HLH3487352266").unwrap().unwrap();
+        assert_relative_eq!(lat, -33.8570, epsilon = 1e-4);
+        assert_relative_eq!(lon, 151.2150, epsilon = 1e-4);
+
+        assert!(grid_reference("43.6,1.4").is_none());
+        assert!(grid_reference("LFBO").is_none());
+        assert!(grid_reference("61U 448251 5411932").unwrap().is_err());
+        assert!(grid_reference("31UDQ481").unwrap().is_err());
+    }
+}
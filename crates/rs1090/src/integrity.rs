@@ -0,0 +1,245 @@
+//! Integrity hashes of decoded messages.
+//!
+//! For evidentiary or archival purposes, each message written to a stream
+//! can be serialized together with a SHA-256 hash of its raw content (frame
+//! and timestamp), so that tampering with archives can be detected later.
+//!
+//! In chained mode, each hash also covers the previous one: removing,
+//! inserting or reordering records breaks the chain as well.
+
+use crate::decode::{from_hex, TimedMessage};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Size of the window read at the end of a file to find the last record
+const TAIL_SIZE: u64 = 64 * 1024;
+
+/// How the hashes of the messages are computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegrityMode {
+    /// Each message is hashed independently (detects altered records)
+    Hash,
+    /// Each hash also covers the previous hash (detects altered, removed,
+    /// inserted and reordered records)
+    Chain,
+}
+
+impl FromStr for IntegrityMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hash" => Ok(IntegrityMode::Hash),
+            "chain" => Ok(IntegrityMode::Chain),
+            _ => Err(format!("unknown integrity mode: {s} (hash or chain)")),
+        }
+    }
+}
+
+/**
+ * A record as written to an output stream with its integrity hash.
+ *
+ * Only the fields covered by the hash are deserialized: the decoded content
+ * is derived from the frame and can always be decoded again.
+ */
+#[derive(Debug, Deserialize)]
+pub struct SignedRecord {
+    pub timestamp: f64,
+    #[serde(deserialize_with = "from_hex")]
+    pub frame: Vec<u8>,
+    pub hash: String,
+}
+
+#[derive(Serialize)]
+struct Signed<'a> {
+    #[serde(flatten)]
+    message: &'a TimedMessage,
+    hash: String,
+}
+
+/**
+ * Compute (or check) the successive hashes of the messages of a stream.
+ *
+ * The hash of a record is the SHA-256 of the previous hash (in chained
+ * mode), the timestamp (as big endian IEEE 754 bits) and the raw frame.
+ * The first hash of a chain is computed from a zero previous hash.
+ */
+#[derive(Debug, Clone)]
+pub struct HashChain {
+    mode: IntegrityMode,
+    previous: [u8; 32],
+}
+
+impl HashChain {
+    pub fn new(mode: IntegrityMode) -> Self {
+        HashChain {
+            mode,
+            previous: [0; 32],
+        }
+    }
+
+    /**
+     * Continue the chain from the last record of an existing file.
+     *
+     * A new chain is started if the file does not exist or if no record
+     * carrying a hash is found at the end of the file. Lines without a hash
+     * (e.g. flight summaries) are not part of the chain.
+     */
+    pub fn resume(mode: IntegrityMode, path: &Path) -> io::Result<Self> {
+        let mut chain = HashChain::new(mode);
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(chain),
+            Err(e) => return Err(e),
+        };
+        let len = file.metadata()?.len();
+        file.seek(SeekFrom::Start(len.saturating_sub(TAIL_SIZE)))?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail)?;
+
+        let previous = String::from_utf8_lossy(&tail)
+            .lines()
+            .rev()
+            .find_map(|line| serde_json::from_str::<SignedRecord>(line).ok())
+            .and_then(|record| decode_hash(&record.hash));
+        if let Some(previous) = previous {
+            chain.previous = previous;
+        }
+        Ok(chain)
+    }
+
+    pub fn mode(&self) -> IntegrityMode {
+        self.mode
+    }
+
+    /// Compute the hash of the next record of the stream
+    pub fn next(&mut self, timestamp: f64, frame: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        if self.mode == IntegrityMode::Chain {
+            hasher.update(self.previous);
+        }
+        hasher.update(timestamp.to_bits().to_be_bytes());
+        hasher.update(frame);
+        let hash = hasher.finalize().into();
+        self.previous = hash;
+        hash
+    }
+
+    /// Serialize a message to JSON, with its hash in a `hash` field
+    pub fn sign(&mut self, msg: &TimedMessage) -> serde_json::Result<String> {
+        // The timestamp is hashed as it is written, in seconds
+        let timestamp = msg.timestamp.as_secs_f64();
+        let hash = hex::encode(self.next(timestamp, &msg.frame));
+        serde_json::to_string(&Signed { message: msg, hash })
+    }
+
+    /**
+     * Check the hash of the next record of the stream.
+     *
+     * The chain then continues from the hash found in the record, so that
+     * an altered record is reported only once and does not invalidate all
+     * the following ones.
+     */
+    pub fn verify(&mut self, record: &SignedRecord) -> bool {
+        let expected = self.next(record.timestamp, &record.frame);
+        let found = decode_hash(&record.hash);
+        if let Some(found) = found {
+            self.previous = found;
+        }
+        found == Some(expected)
+    }
+}
+
+fn decode_hash(hash: &str) -> Option<[u8; 32]> {
+    hex::decode(hash).ok()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::time::Timestamp;
+    use hexlit::hex;
+
+    fn messages() -> Vec<TimedMessage> {
+        let frames = [
+            hex!("8d406b902015a678d4d220aa4bda").to_vec(),
+            hex!("8d40058b58c901375147efd09357").to_vec(),
+            hex!("5d4d20237a55a6").to_vec(),
+        ];
+        frames
+            .into_iter()
+            .enumerate()
+            .map(|(i, frame)| TimedMessage {
+                timestamp: Timestamp::from_nanos(
+                    1_712_345_678_123_456_000 + i as i64 * 100_000_000,
+                ),
+                frame,
+                message: None,
+                metadata: vec![],
+                decode_time: None,
+            })
+            .collect()
+    }
+
+    fn records(mode: IntegrityMode) -> Vec<SignedRecord> {
+        let mut chain = HashChain::new(mode);
+        messages()
+            .iter()
+            .map(|msg| serde_json::from_str(&chain.sign(msg).unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_hash_chain() {
+        let mut records = records(IntegrityMode::Chain);
+        let mut chain = HashChain::new(IntegrityMode::Chain);
+        assert!(records.iter().all(|record| chain.verify(record)));
+
+        // Altered timestamp
+        records[1].timestamp += 1e-6;
+        let mut chain = HashChain::new(IntegrityMode::Chain);
+        let valid: Vec<_> = records.iter().map(|r| chain.verify(r)).collect();
+        assert_eq!(valid, [true, false, true]);
+
+        // Removed record
+        let mut records = self::records(IntegrityMode::Chain);
+        records.remove(1);
+        let mut chain = HashChain::new(IntegrityMode::Chain);
+        let valid: Vec<_> = records.iter().map(|r| chain.verify(r)).collect();
+        assert_eq!(valid, [true, false]);
+
+        // Independent hashes do not detect removals
+        let mut records = self::records(IntegrityMode::Hash);
+        records.remove(1);
+        let mut chain = HashChain::new(IntegrityMode::Hash);
+        assert!(records.iter().all(|record| chain.verify(record)));
+    }
+
+    #[test]
+    fn test_resume() {
+        let path = std::env::temp_dir().join("rs1090_test_integrity.jsonl");
+        let messages = messages();
+        let mut chain = HashChain::new(IntegrityMode::Chain);
+        let mut content = String::new();
+        for msg in &messages[..2] {
+            content.push_str(&chain.sign(msg).unwrap());
+            content.push('\n');
+        }
+        std::fs::write(&path, &content).unwrap();
+
+        let mut resumed =
+            HashChain::resume(IntegrityMode::Chain, &path).unwrap();
+        content.push_str(&resumed.sign(&messages[2]).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let mut chain = HashChain::new(IntegrityMode::Chain);
+        for line in content.lines() {
+            assert!(chain.verify(&serde_json::from_str(line).unwrap()));
+        }
+    }
+}
@@ -1,9 +1,18 @@
 #![allow(rustdoc::broken_intra_doc_links)]
 #![allow(clippy::needless_doctest_main)]
 #![doc = include_str!("../readme.md")]
+pub mod archive;
+pub mod czml;
 pub mod data;
 pub mod decode;
+pub mod geom;
+#[cfg(feature = "integrity")]
+pub mod integrity;
+pub mod sbs;
 pub mod source;
+#[cfg(test)]
+mod testing;
+pub mod tracker;
 
 pub mod prelude {
     /// This re-export is necessary to decode messages
@@ -17,11 +26,14 @@ pub mod prelude {
     pub use crate::decode::bds::bds61::AircraftStatus;
     pub use crate::decode::bds::bds62::TargetStateAndStatusInformation;
     pub use crate::decode::bds::bds65::AircraftOperationStatus;
+    pub use crate::decode::tisb::TisBMessage;
     /// The root structure to decode messages
     pub use crate::decode::Message;
     pub use crate::decode::DF::*;
     pub use crate::decode::{
-        cpr::Position, SensorMetadata, TimedMessage, ICAO,
+        cpr::Position,
+        time::{TimeSource, Timestamp},
+        SensorMetadata, TimedMessage, ICAO,
     };
 
     /// This re-export is necessary for the following export
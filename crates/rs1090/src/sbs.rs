@@ -0,0 +1,271 @@
+/*!
+ * Export of decoded messages to the SBS-1 (BaseStation) format.
+ *
+ * This is the CSV format served by dump1090 on port 30003, understood by
+ * many downstream tools (e.g. Virtual Radar Server). Each message is a line
+ * `MSG,<type>,...` with 22 fields, most of them left empty:
+ *
+ * | Type | Message                              | Fields                  |
+ * | ---- | ------------------------------------ | ----------------------- |
+ * | 1    | Identification (BDS 0,8)             | callsign                |
+ * | 2    | Surface position (BDS 0,6)           | speed, track, position  |
+ * | 3    | Airborne position (BDS 0,5)          | altitude, position      |
+ * | 4    | Airborne velocity (BDS 0,9)          | speed, track, vert. rate|
+ * | 5    | Surveillance altitude (DF 4, 20)     | altitude                |
+ * | 6    | Surveillance identity (DF 5, 21)     | squawk                  |
+ * | 7    | Air-air surveillance (DF 0, 16)      | altitude                |
+ * | 8    | All-call reply (DF 11)               | ground flag             |
+ *
+ * Positions are only written once decoded (see [`crate::decode::cpr`]);
+ * flags are written as `-1` (true) or `0` (false), or left empty when
+ * unknown.
+ *
+ * ```
+ * use rs1090::prelude::*;
+ *
+ * let frame = hex::decode("8d4840d6202cc371c32ce0576098").unwrap();
+ * let msg = TimedMessage {
+ *     timestamp: Timestamp::from_secs(1_700_000_000),
+ *     message: Some(Message::try_from(frame.as_slice()).unwrap()),
+ *     frame,
+ *     metadata: vec![],
+ *     decode_time: None,
+ * };
+ * assert_eq!(
+ *     msg.to_sbs().unwrap(),
+ *     "MSG,1,1,1,4840D6,1,2023/11/14,22:13:20.000,\
+ *     2023/11/14,22:13:20.000,KLM1023,,,,,,,,,,,"
+ * );
+ * ```
+ *
+ * See <http://woodair.net/sbs/article/barebones42_socket_data.htm>
+ */
+
+use crate::decode::adsb::ME;
+use crate::decode::bds::bds05::SurveillanceStatus;
+use crate::decode::bds::bds09::AirborneVelocitySubType;
+use crate::decode::tisb::TisBMessage;
+use crate::decode::{Capability, FlightStatus, IdentityCode};
+use crate::prelude::*;
+
+/// The fields of a SBS line, after the transmission type and timestamps
+#[derive(Debug, Default)]
+struct Fields {
+    callsign: Option<String>,
+    altitude: Option<u16>,
+    groundspeed: Option<f64>,
+    track: Option<f64>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    vertical_rate: Option<i16>,
+    squawk: Option<IdentityCode>,
+    alert: Option<bool>,
+    emergency: Option<bool>,
+    spi: Option<bool>,
+    on_ground: Option<bool>,
+}
+
+impl Fields {
+    /// The alert, SPI and ground flags in the flight status
+    fn status(mut self, fs: &FlightStatus) -> Self {
+        use FlightStatus::*;
+        self.alert = Some(matches!(
+            fs,
+            AlertNoSpiAirborne | AlertNoSpiOnGround | AlertSpiAirborneGround
+        ));
+        self.spi = Some(matches!(
+            fs,
+            AlertSpiAirborneGround | NoAlertSpiAirborneGround
+        ));
+        self.on_ground = match fs {
+            NoAlertNoSpiAirborne | AlertNoSpiAirborne => Some(false),
+            NoAlertNoSpiOnGround | AlertNoSpiOnGround => Some(true),
+            _ => None,
+        };
+        self
+    }
+}
+
+impl TimedMessage {
+    /**
+     * The message as a SBS-1 (BaseStation) line, without a line ending.
+     *
+     * Messages with no equivalent in the format (e.g. Comm-B registers,
+     * operational status) are skipped.
+     */
+    pub fn to_sbs(&self) -> Option<String> {
+        let message = self.message.as_ref()?;
+        let icao24 = message.icao24()?;
+        let (kind, fields) = match &message.df {
+            ExtendedSquitterADSB(adsb) => extended_squitter(&adsb.message)?,
+            ExtendedSquitterTisB { cf, .. } => match &cf.me {
+                TisBMessage::ExtendedSquitter(me) => extended_squitter(me)?,
+                _ => return None,
+            },
+            SurveillanceAltitudeReply { fs, ac, .. }
+            | CommBAltitudeReply { fs, ac, .. } => (
+                5,
+                Fields {
+                    altitude: Some(ac.0),
+                    ..Fields::default()
+                }
+                .status(fs),
+            ),
+            SurveillanceIdentityReply { fs, id, .. }
+            | CommBIdentityReply { fs, id, .. } => (
+                6,
+                Fields {
+                    squawk: Some(*id),
+                    emergency: Some(matches!(id.0, 0x7500 | 0x7600 | 0x7700)),
+                    ..Fields::default()
+                }
+                .status(fs),
+            ),
+            ShortAirAirSurveillance { ac, .. }
+            | LongAirAirSurveillance { ac, .. } => (
+                7,
+                Fields {
+                    altitude: Some(ac.0),
+                    ..Fields::default()
+                },
+            ),
+            AllCallReply { capability, .. } => (
+                8,
+                Fields {
+                    on_ground: match capability {
+                        Capability::AG_GROUND => Some(true),
+                        Capability::AG_AIRBORNE => Some(false),
+                        _ => None,
+                    },
+                    ..Fields::default()
+                },
+            ),
+            _ => return None,
+        };
+
+        let (year, month, day, ms) = self.timestamp.civil();
+        let time = format!(
+            "{year:04}/{month:02}/{day:02},{:02}:{:02}:{:02}.{:03}",
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            ms % 1000
+        );
+        let text = |value: Option<String>| value.unwrap_or_default();
+        let flag = |value: Option<bool>| match value {
+            Some(true) => "-1",
+            Some(false) => "0",
+            None => "",
+        };
+        Some(format!(
+            "MSG,{kind},1,1,{:06X},1,{time},{time},{},{},{},{},{},{},{},{},\
+            {},{},{},{}",
+            icao24.0,
+            text(fields.callsign),
+            text(fields.altitude.map(|v| v.to_string())),
+            text(fields.groundspeed.map(|v| format!("{v:.0}"))),
+            text(fields.track.map(|v| format!("{v:.0}"))),
+            text(fields.latitude.map(|v| format!("{v:.5}"))),
+            text(fields.longitude.map(|v| format!("{v:.5}"))),
+            text(fields.vertical_rate.map(|v| v.to_string())),
+            text(fields.squawk.map(|v| v.to_string())),
+            flag(fields.alert),
+            flag(fields.emergency),
+            flag(fields.spi),
+            flag(fields.on_ground),
+        ))
+    }
+}
+
+/// The transmission type and fields of ADS-B (and TIS-B) messages
+fn extended_squitter(me: &ME) -> Option<(u8, Fields)> {
+    match me {
+        ME::BDS08(id) => Some((
+            1,
+            Fields {
+                callsign: Some(id.callsign.trim().to_string()),
+                ..Fields::default()
+            },
+        )),
+        ME::BDS06(pos) => Some((
+            2,
+            Fields {
+                groundspeed: pos.groundspeed,
+                track: pos.track,
+                latitude: pos.latitude,
+                longitude: pos.longitude,
+                on_ground: Some(true),
+                ..Fields::default()
+            },
+        )),
+        ME::BDS05(pos) => Some((
+            3,
+            Fields {
+                altitude: pos.alt,
+                latitude: pos.latitude,
+                longitude: pos.longitude,
+                alert: Some(matches!(
+                    pos.ss,
+                    SurveillanceStatus::PermanentAlert
+                        | SurveillanceStatus::TemporaryAlert
+                )),
+                spi: Some(pos.ss == SurveillanceStatus::SPICondition),
+                on_ground: Some(false),
+                ..Fields::default()
+            },
+        )),
+        ME::BDS09(velocity) => {
+            let (groundspeed, track) = match &velocity.velocity {
+                AirborneVelocitySubType::GroundSpeedDecoding(gs) => {
+                    (Some(gs.groundspeed), Some(gs.track))
+                }
+                _ => (None, None),
+            };
+            Some((
+                4,
+                Fields {
+                    groundspeed,
+                    track,
+                    vertical_rate: velocity.vertical_rate,
+                    ..Fields::default()
+                },
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::cpr::decode_positions;
+    use crate::testing::timed;
+    use hexlit::hex;
+
+    #[test]
+    fn test_sbs() {
+        let mut messages = vec![
+            timed(&hex!("8d40621d58c382d690c8ac2863a7"), 1_700_000_001.),
+            timed(&hex!("8d40621d58c386435cc412692ad6"), 1_700_000_002.5),
+            timed(&hex!("8d485020994409940838175b284f"), 1_700_000_003.),
+            timed(&hex!("5d4d20237a55a6"), 1_700_000_004.),
+        ];
+        decode_positions(&mut messages, None, &None, true);
+        let lines: Vec<String> =
+            messages.iter().filter_map(|msg| msg.to_sbs()).collect();
+        assert_eq!(lines.len(), 4);
+        for line in &lines {
+            assert_eq!(line.split(',').count(), 22);
+        }
+        assert_eq!(
+            lines[0],
+            "MSG,3,1,1,40621D,1,2023/11/14,22:13:21.000,\
+            2023/11/14,22:13:21.000,,38000,,,52.25720,3.91937,,,0,,0,0"
+        );
+        assert!(lines[1].starts_with(
+            "MSG,3,1,1,40621D,1,2023/11/14,22:13:22.500,\
+            2023/11/14,22:13:22.500,,38000,,,52.2"
+        ));
+        assert!(lines[2].ends_with(",159,183,,,-832,,,,,"));
+        assert!(lines[3].starts_with("MSG,8,1,1,4D2023,"));
+    }
+}
@@ -0,0 +1,93 @@
+/// Lines longer than this are not AVR frames
+const MAX_LINE: usize = 64;
+
+/**
+ * Extract the next AVR frame from the buffer, if it is complete.
+ *
+ * AVR lines are converted to Beast frames (without escaping), so that they
+ * can follow the same processing as Beast feeds:
+ *
+ * - `*<frame>;` has no timestamp nor signal level;
+ * - `@<timestamp><frame>;` comes with a 48-bit timestamp;
+ * - `<<timestamp><signal><frame>;` also comes with a signal level.
+ *
+ * Invalid lines are discarded; incomplete lines are left in the buffer
+ * until more data is received.
+ */
+pub fn next_frame(data: &mut Vec<u8>) -> Option<Vec<u8>> {
+    loop {
+        let Some(end) = data.iter().position(|&x| x == b';') else {
+            if data.len() > MAX_LINE {
+                // Keep the beginning of the next line, if any
+                let start = data.iter().rposition(|&x| x == b'\n');
+                data.drain(..start.map_or(data.len(), |i| i + 1));
+            }
+            return None;
+        };
+        let line: Vec<u8> = data.drain(..=end).collect();
+        let line = String::from_utf8_lossy(&line[..end]);
+        if let Some(frame) = parse_line(line.trim()) {
+            return Some(frame);
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Option<Vec<u8>> {
+    let (timestamp, signal, frame) = match line.get(..1)? {
+        "*" => ("000000000000", "ff", &line[1..]),
+        "@" => (line.get(1..13)?, "ff", line.get(13..)?),
+        "<" => (line.get(1..13)?, line.get(13..15)?, line.get(15..)?),
+        _ => return None,
+    };
+    let kind = match frame.len() {
+        4 => 0x31,
+        14 => 0x32,
+        28 => 0x33,
+        _ => return None,
+    };
+    let mut msg = vec![0x1A, kind];
+    msg.extend(hex::decode(timestamp).ok()?);
+    msg.extend(hex::decode(signal).ok()?);
+    msg.extend(hex::decode(frame).ok()?);
+    Some(msg)
+}
+
+/// Encode a frame as an AVR line, without timestamp: `*<frame>;`
+pub fn encode(frame: &[u8]) -> String {
+    format!("*{};", hex::encode_upper(frame))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hexlit::hex;
+
+    #[test]
+    fn test_next_frame() {
+        let mut data = b"*8D406B902015A678D4D220AA4BDA;\r\n\
+            *garbage;\n\
+            @0A1B2C3D4E5F5D4D20237A55A6;\n\
+            <0A1B2C3D4E5F808D406B902015A678D4D220AA4BDA;\n\
+            *8D40"
+            .to_vec();
+
+        let msg = next_frame(&mut data).unwrap();
+        assert_eq!(msg[..9], hex!("1a33000000000000ff"));
+        assert_eq!(msg[9..], hex!("8d406b902015a678d4d220aa4bda"));
+
+        let msg = next_frame(&mut data).unwrap();
+        assert_eq!(msg[..9], hex!("1a320a1b2c3d4e5fff"));
+        assert_eq!(msg[9..], hex!("5d4d20237a55a6"));
+
+        let msg = next_frame(&mut data).unwrap();
+        assert_eq!(msg[..9], hex!("1a330a1b2c3d4e5f80"));
+
+        assert_eq!(next_frame(&mut data), None);
+        assert_eq!(data, b"\n*8D40");
+
+        let mut data = encode(&hex!("5d4d20237a55a6")).into_bytes();
+        assert_eq!(data, b"*5D4D20237A55A6;");
+        let msg = next_frame(&mut data).unwrap();
+        assert_eq!(msg[9..], hex!("5d4d20237a55a6"));
+    }
+}
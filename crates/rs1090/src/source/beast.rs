@@ -10,12 +10,21 @@ use tokio_tungstenite::{
     tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream,
 };
 use tracing::info;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
-use std::collections::HashSet;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::collections::HashMap;
 use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use crate::decode::time::{now_in_ns, since_today_to_nanos};
+use super::filter::FrameFilter;
+use super::format::Format;
+use super::parser::{BeastParser, Frame};
+pub use super::parser::{Framer, FramingStats};
+use super::timestamps::TimestampCheck;
+use crate::decode::time::{clock_error, since_today_to_nanos};
 use crate::prelude::*;
 
 /// Iterate a Beast binary feed.
@@ -23,7 +32,8 @@ use crate::prelude::*;
 ///  - esc "1" : 6 byte MLAT timestamp, 1 byte signal level, 2 byte Mode-AC
 ///  - esc "2" : 6 byte MLAT timestamp, 1 byte signal level, 7 byte Mode-S short frame
 ///  - esc "3" : 6 byte MLAT timestamp, 1 byte signal level, 14 byte Mode-S long frame
-///  - esc "4" : 6 byte MLAT timestamp, status data, DIP switch configuration settings (not on Mode-S Beast classic)
+///  - esc "4" : 6 byte MLAT timestamp, status data, DIP switch configuration settings (not on Mode-S Beast classic), see [`ReceiverStatus`]
+///  - esc "5" : position of the receiver (Radarcape), see [`receiver_position`]
 ///
 /// esc esc: true 0x1a
 /// esc is 0x1a, and "1", "2" and "3" are 0x31, 0x32 and 0x33
@@ -44,21 +54,123 @@ pub enum BeastSource {
     Websocket(String),
 }
 
-pub async fn next_msg(mut stream: DataSource) -> impl Stream<Item = Vec<u8>> {
-    // Initialize a HashSet to check for valid message types
-    let valid_msg_types: HashSet<u8> =
-        vec![0x31, 0x32, 0x33, 0x34].into_iter().collect();
+/// Size of the read buffer, enough for the largest UDP datagrams
+const BUFFER_SIZE: usize = 65536;
+/// Duration after which the parser of a silent UDP sender is dropped
+const SENDER_TIMEOUT: Duration = Duration::from_secs(60);
+/// Maximum number of UDP senders with their own parser
+const MAX_SENDERS: usize = 1024;
 
-    let mut data = Vec::new();
+/**
+ * The parsers of the senders of UDP datagrams, with their partial frames.
+ *
+ * Senders silent for [`SENDER_TIMEOUT`] are dropped, and the least recently
+ * active sender is dropped to make room for a new one beyond
+ * [`MAX_SENDERS`], so that spoofed or short-lived source addresses do not
+ * accumulate.
+ */
+struct Senders {
+    parsers: HashMap<SocketAddr, (BeastParser, Instant)>,
+    format: Option<Format>,
+    filter: FrameFilter,
+    stats: FramingStats,
+    last_sweep: Instant,
+}
+
+impl Senders {
+    fn new(
+        format: Option<Format>,
+        filter: FrameFilter,
+        stats: FramingStats,
+    ) -> Self {
+        Senders {
+            parsers: HashMap::new(),
+            format,
+            filter,
+            stats,
+            last_sweep: Instant::now(),
+        }
+    }
+
+    /// The parser of a sender, created if necessary
+    fn parser(&mut self, sender: SocketAddr, now: Instant) -> &mut BeastParser {
+        if now.duration_since(self.last_sweep) >= SENDER_TIMEOUT {
+            self.parsers.retain(|_, (_, seen)| {
+                now.duration_since(*seen) < SENDER_TIMEOUT
+            });
+            self.last_sweep = now;
+        }
+        if self.parsers.len() >= MAX_SENDERS
+            && !self.parsers.contains_key(&sender)
+        {
+            let oldest = self
+                .parsers
+                .iter()
+                .min_by_key(|(_, (_, seen))| *seen)
+                .map(|(address, _)| *address);
+            if let Some(oldest) = oldest {
+                self.parsers.remove(&oldest);
+            }
+        }
+        let (parser, seen) = self.parsers.entry(sender).or_insert_with(|| {
+            let parser = BeastParser::new(self.format, self.stats.clone())
+                .with_filter(self.filter.clone());
+            (parser, now)
+        });
+        *seen = now;
+        parser
+    }
+}
+
+/**
+ * Iterate the Beast frames of a stream, see [`BeastParser`].
+ *
+ * If the format is not given, it is detected from the first bytes of the
+ * stream. Frames in the AVR format are converted to Beast frames. The
+ * framing statistics of Beast streams are updated in `stats`.
+ *
+ * UDP datagrams may carry several frames, or split frames between
+ * consecutive datagrams: each sender has its own parser, so that frames are
+ * reassembled across datagram boundaries even when several senders share
+ * the same socket. Silent senders are dropped after a while.
+ *
+ * With an idle timeout, the stream of a TCP source ends when no data is
+ * read for this duration, even if frames keep being dropped by the filter.
+ */
+pub async fn next_msg(
+    mut stream: DataSource,
+    format: Option<Format>,
+    filter: FrameFilter,
+    stats: FramingStats,
+    idle_timeout: Option<Duration>,
+) -> impl Stream<Item = Vec<u8>> {
+    let mut parser =
+        BeastParser::new(format, stats.clone()).with_filter(filter.clone());
+    let mut senders = Senders::new(format, filter, stats.clone());
+    let mut buffer = vec![0u8; BUFFER_SIZE];
     stream! {
     loop {
         // Read from the stream into the buffer
-        let mut buffer = [0u8; 1024];
-        let bytes_read = match &mut stream {
+        let (parser, data) = match &mut stream {
             DataSource::Tcp(tcp_stream) => {
-                match tcp_stream.read(&mut buffer).await {
+                let read = tcp_stream.read(&mut buffer);
+                let read = match idle_timeout {
+                    Some(idle) => match tokio::time::timeout(idle, read).await
+                    {
+                        Ok(read) => read,
+                        Err(_) => {
+                            warn!(
+                                "No data received for {:?}, closing",
+                                idle
+                            );
+                            break;
+                        }
+                    },
+                    None => read.await,
+                };
+                match read {
                     Ok(0) => break, // Connection closed by peer
-                    Ok(n) => n,
+                    Ok(n) => (&mut parser, &buffer[..n]),
                     Err(e) => {
                         error!("Error reading from socket: {}", e);
                         break;
@@ -67,7 +179,10 @@ pub async fn next_msg(mut stream: DataSource) -> impl Stream<Item = Vec<u8>> {
             }
             DataSource::Udp(udp_socket) => {
                 match udp_socket.recv_from(&mut buffer).await {
-                    Ok((n, _)) => n,
+                    Ok((n, sender)) => {
+                        let parser = senders.parser(sender, Instant::now());
+                        (parser, &buffer[..n])
+                    }
                     Err(e) => {
                         error!("Error reading from socket: {}", e);
                         break;
@@ -80,7 +195,7 @@ pub async fn next_msg(mut stream: DataSource) -> impl Stream<Item = Vec<u8>> {
                         debug!("Received {:?}", data);
                         let len = data.len().min(buffer.len());
                         buffer[..len].copy_from_slice(&data[..len]);
-                        len
+                        (&mut parser, &buffer[..len])
                     }
                     _ => {
                         error!("Error reading from websocket");
@@ -90,118 +205,343 @@ pub async fn next_msg(mut stream: DataSource) -> impl Stream<Item = Vec<u8>> {
             }
         };
 
-        // Extend the data vector with the read bytes
-        data.extend_from_slice(&buffer[..bytes_read]);
+        #[cfg(feature = "profile")]
+        let frames: Vec<Frame> =
+            tracing::trace_span!(target: "profile", "framing")
+                .in_scope(|| parser.push(data).collect());
+        #[cfg(not(feature = "profile"))]
+        let frames: Vec<Frame> = parser.push(data).collect();
+        if let DataSource::Udp(_) = stream {
+            stats.record_datagram(data.len(), parser);
+        }
+        if parser.format() == Some(Format::Sbs) {
+            error!("The SBS format only contains decoded data and is not supported, use the Beast or AVR output of the decoder");
+            break;
+        }
+        for frame in frames {
+            yield frame.into_bytes()
+        }
+    }
+    }
+}
 
-        while data.len() >= 23 {
-            if let Some(it) = data.iter().position(|&x| x == 0x1A) {
-                data = data.split_off(it);
+/// Delay before the first attempt to reconnect, doubled each time
+pub(crate) const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Maximum delay between two attempts to reconnect
+pub(crate) const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
 
-                if data.len() < 23 {
-                    break;
-                }
+/// How the stream of a Beast source is read
+#[derive(Debug, Clone, Default)]
+pub struct StreamSettings {
+    /// The format of the stream, detected if not given
+    pub format: Option<Format>,
+    /// Reconnect to TCP sources when no data is received for this duration
+    pub idle_timeout: Option<Duration>,
+    /// Drop unwanted frames as soon as they are extracted from the stream
+    pub filter: FrameFilter,
+}
 
-                let msg_type = data[1];
-                if valid_msg_types.contains(&msg_type) {
-                    // Collapse consecutive 0x1A into a single 0x1A
-                    let mut ref_idx = 1;
-                    let mut idx;
-                    let msg_size = match msg_type {
-                        0x31 => 11,
-                        0x32 => 16,
-                        0x33 => 23,
-                        0x34 => 23, // Adjust the message size accordingly
-                        _ => 0,
-                    };
-
-                    loop {
-                        idx = data[ref_idx..msg_size.min(data.len())]
-                            .iter()
-                            .position(|&x| x == 0x1A);
-                        if let Some(start) = idx.map(|idx| ref_idx + idx) {
-                            ref_idx = start + 1;
-                            if data.get(ref_idx) == Some(&0x1A) {
-                                data.splice(start..=start, std::iter::empty());
-                            }
-                        } else {
-                            break;
-                        }
-                    }
+impl BeastSource {
+    fn address(&self) -> &str {
+        match self {
+            BeastSource::Tcp(address)
+            | BeastSource::Udp(address)
+            | BeastSource::Websocket(address) => address,
+        }
+    }
 
-                    if idx.is_some() || data.len() < msg_size {
-                        // Move to the next buffer
-                        break;
+    async fn connect(&self) -> io::Result<DataSource> {
+        match self {
+            BeastSource::Tcp(address) => {
+                match TcpStream::connect(&address).await {
+                    Ok(stream) => {
+                        info!("Connected to TCP stream: {}", address);
+                        Ok(DataSource::Tcp(stream))
                     }
-
-                    let msg = data.drain(..msg_size).collect::<Vec<u8>>();
-                    if msg_type != 0x34 {
-                        yield msg
+                    Err(error) => {
+                        info!(
+                            "Failed to connect to TCP {} ({}), trying in UDP",
+                            address,
+                            error.to_string()
+                        );
+                        Ok(DataSource::Udp(UdpSocket::bind(&address).await?))
                     }
-                } else {
-                    // Probably corrupted message
-                    data = data.split_off(1);
                 }
-            } else {
-                break;
+            }
+            BeastSource::Udp(address) => {
+                Ok(DataSource::Udp(UdpSocket::bind(&address).await?))
+            }
+            BeastSource::Websocket(address) => {
+                info!("Connecting to websocket: {}", address);
+                let (stream, _) =
+                    connect_async(address).await.map_err(io::Error::other)?;
+                info!("Connected to websocket: {}", address);
+                let (_, rx) = stream.split();
+                Ok(DataSource::Websocket(rx))
             }
         }
     }
+
+    /// Reconnect to a TCP source, until it accepts the connection
+    async fn reconnect(&self) -> DataSource {
+        let address = self.address();
+        let mut delay = RECONNECT_DELAY;
+        loop {
+            match TcpStream::connect(address).await {
+                Ok(stream) => {
+                    info!("Reconnected to TCP stream: {}", address);
+                    return DataSource::Tcp(stream);
+                }
+                Err(error) => {
+                    warn!(
+                        "Failed to reconnect to TCP {} ({}), retrying in {:?}",
+                        address, error, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            }
+        }
     }
 }
 
+/**
+ * Receive the frames of a Beast source and send them to a queue.
+ *
+ * Some servers keep TCP connections open but stop sending frames after an
+ * internal error: with an idle timeout, the receiver reconnects to TCP
+ * sources when no data is received for this duration (frames dropped by the
+ * filter count as data), or when the connection is closed (reconnections
+ * are counted in `stats`). Otherwise, the receiver stops when the
+ * connection is closed.
+ *
+ * Status and position frames update the `report` of the receiver, and
+ * status frames set the clock of the following messages.
+ */
+#[allow(clippy::too_many_arguments)]
 pub async fn receiver(
     address: BeastSource,
     tx: mpsc::Sender<TimedMessage>,
     serial: u64,
     name: Option<String>,
+    settings: StreamSettings,
+    stats: FramingStats,
+    mut timestamps: TimestampCheck,
+    report: ReceiverReport,
 ) -> io::Result<()> {
-    let msg_stream = match address {
-        BeastSource::Tcp(address) => match TcpStream::connect(&address).await {
-            Ok(stream) => {
-                info!("Connected to TCP stream: {}", address);
-                DataSource::Tcp(stream)
+    let mut stream = address.connect().await?;
+    loop {
+        // UDP sockets are not connected, even when falling back from TCP
+        let idle_timeout = match stream {
+            DataSource::Tcp(_) => settings.idle_timeout,
+            _ => None,
+        };
+        let msg_stream = next_msg(
+            stream,
+            settings.format,
+            settings.filter.clone(),
+            stats.clone(),
+            idle_timeout,
+        )
+        .await;
+        pin_mut!(msg_stream); // needed for iteration
+        loop {
+            let Some(msg) = msg_stream.next().await else {
+                if idle_timeout.is_none() {
+                    info!("Connection closed: {}", address.address());
+                    return Ok(());
+                }
+                warn!("Connection closed: {}, reconnecting", address.address());
+                stats.record_reconnection();
+                break;
+            };
+            if let Some(status) = ReceiverStatus::from_frame(&msg) {
+                if report.status() != Some(status) {
+                    info!("Receiver status: {:?}", status);
+                }
+                report.set_status(status);
+                continue;
             }
-            Err(error) => {
-                info!(
-                    "Failed to connect to TCP {} ({}), trying in UDP",
-                    address,
-                    error.to_string()
-                );
-                DataSource::Udp(UdpSocket::bind(&address).await?)
+            if let Some(position) = receiver_position(&msg) {
+                if report.position() != Some(position) {
+                    info!(
+                        "Receiver position: {:.4},{:.4}",
+                        position.latitude, position.longitude
+                    );
+                }
+                report.set_position(position);
+                continue;
+            }
+            let time_source =
+                report.status().map(|status| status.time_source());
+            let mut tmsg =
+                process_radarcape(&msg, serial, name.clone(), time_source);
+            for metadata in tmsg.metadata.iter_mut() {
+                timestamps.apply(metadata);
             }
-        },
-        BeastSource::Udp(address) => {
-            DataSource::Udp(UdpSocket::bind(&address).await?)
-        }
-        BeastSource::Websocket(address) => {
-            info!("Connecting to websocket: {}", address);
-            let (stream, _) = connect_async(&address)
-                .await
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            info!("Connected to websocket: {}", address);
-            let (_, rx) = stream.split();
-            DataSource::Websocket(rx)
-        }
-    };
-
-    let msg_stream = beast::next_msg(msg_stream).await;
-    pin_mut!(msg_stream); // needed for iteration
-    'receive: loop {
-        while let Some(msg) = msg_stream.next().await {
-            let tmsg = process_radarcape(&msg, serial, name.clone());
             info!("Received {}", tmsg);
             if tx.send(tmsg).await.is_err() {
-                break 'receive;
+                return Ok(());
             }
         }
+        stream = address.reconnect().await;
+    }
+}
+
+/// The GPS timestamps setting (DIP switch g) in the settings byte
+const SETTINGS_GPS_TIMESTAMPS: u8 = 0x10;
+/// The GPS fix flag in the GPS status byte
+const GPS_STATUS_FIX: u8 = 0x20;
+
+/**
+ * The status of a Radarcape receiver, sent every second in 0x34 frames.
+ *
+ * | Byte | Content                                               |
+ * | ---- | ----------------------------------------------------- |
+ * | 0    | Settings: DIP switches c to j as bits 0 to 7          |
+ * | 1    | Timestamp offset between the FPGA and the GPS clock   |
+ * | 2    | GPS status                                            |
+ * | 3-13 | Reserved                                              |
+ *
+ * Neither status frames nor position frames carry the serial number of the
+ * receiver: they apply to the sensor of the source they are received from,
+ * identified by the serial given to the [`receiver`].
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiverStatus {
+    /// The DIP switch configuration settings
+    pub settings: u8,
+    /// The timestamp offset reported by the receiver
+    pub offset: u8,
+    /// The GPS status flags
+    pub gps: u8,
+}
+
+impl ReceiverStatus {
+    /// Parse a status frame, as returned by [`next_msg`]
+    pub fn from_frame(msg: &[u8]) -> Option<Self> {
+        match msg {
+            [0x1A, 0x34, _, _, _, _, _, _, _, settings, offset, gps, ..] => {
+                Some(ReceiverStatus {
+                    settings: *settings,
+                    offset: *offset,
+                    gps: *gps,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// True if the receiver is configured to output GPS timestamps
+    pub fn gps_timestamps(&self) -> bool {
+        self.settings & SETTINGS_GPS_TIMESTAMPS != 0
+    }
+
+    /// True if the GPS receiver has a valid fix
+    pub fn gps_fix(&self) -> bool {
+        self.gps & GPS_STATUS_FIX != 0
+    }
+
+    /// The clock timestamping the following messages
+    pub fn time_source(&self) -> TimeSource {
+        if self.gps_timestamps() && self.gps_fix() {
+            TimeSource::Gnss
+        } else {
+            TimeSource::Counter
+        }
+    }
+}
+
+/**
+ * The position of a Radarcape receiver, sent in 0x35 frames.
+ *
+ * The latitude and longitude (in degrees) are little-endian 32-bit floats,
+ * at bytes 4 and 8 after the type byte. Frames without a valid position
+ * (e.g. before the first GPS fix) are ignored.
+ */
+pub fn receiver_position(msg: &[u8]) -> Option<Position> {
+    let [0x1A, 0x35, payload @ ..] = msg else {
+        return None;
+    };
+    let float = |offset: usize| -> Option<f64> {
+        let bytes = payload.get(offset..offset + 4)?;
+        Some(f32::from_le_bytes(bytes.try_into().ok()?) as f64)
+    };
+    let (latitude, longitude) = (float(4)?, float(8)?);
+    let valid = (-90. ..=90.).contains(&latitude)
+        && (-180. ..=180.).contains(&longitude)
+        && (latitude, longitude) != (0., 0.);
+    valid.then_some(Position {
+        latitude,
+        longitude,
+    })
+}
+
+/**
+ * The last status and position reported by the receiver of a source.
+ *
+ * The [`receiver`] of a Beast source updates the report with the status and
+ * position frames of Radarcape receivers. Clones share the same report.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ReceiverReport(Arc<Mutex<Report>>);
+
+#[derive(Debug, Default)]
+struct Report {
+    status: Option<ReceiverStatus>,
+    position: Option<Position>,
+}
+
+impl ReceiverReport {
+    /// The last status reported by the receiver, if any
+    pub fn status(&self) -> Option<ReceiverStatus> {
+        self.0.lock().unwrap().status
+    }
+
+    /// The last position reported by the receiver, if any
+    pub fn position(&self) -> Option<Position> {
+        self.0.lock().unwrap().position
+    }
+
+    fn set_status(&self, status: ReceiverStatus) {
+        self.0.lock().unwrap().status = Some(status);
+    }
+
+    fn set_position(&self, position: Position) {
+        self.0.lock().unwrap().position = Some(position);
+    }
+
+    /// True if the receiver has not reported anything
+    pub fn is_empty(&self) -> bool {
+        let report = self.0.lock().unwrap();
+        report.status.is_none() && report.position.is_none()
+    }
+}
+
+impl Serialize for ReceiverReport {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ReceiverReport", 4)?;
+        if let Some(status) = self.status() {
+            state.serialize_field("gps_fix", &status.gps_fix())?;
+            state
+                .serialize_field("gps_timestamps", &status.gps_timestamps())?;
+            state.serialize_field("time_source", &status.time_source())?;
+        }
+        if let Some(position) = self.position() {
+            state.serialize_field("position", &position)?;
+        }
+        state.end()
     }
-    Ok(())
 }
 
 fn process_radarcape(
     msg: &[u8],
     serial: u64,
     name: Option<String>,
+    time_source: Option<TimeSource>,
 ) -> TimedMessage {
     // Copy the bytes from the slice into the array starting from index 2
     let mut array = [0u8; 8];
@@ -210,29 +550,40 @@ fn process_radarcape(
     let ts_u64 = u64::from_be_bytes(array);
     let seconds = ts_u64 as u128 >> 30;
     let nanos = ts_u64 & 0x00003FFFFFFF;
-    let timestamp_in_s =
-        since_today_to_nanos(seconds * 1_000_000_000 + nanos as u128) as f64
-            * 1e-9;
+    let timestamp = Timestamp::from_nanos(since_today_to_nanos(
+        seconds * 1_000_000_000 + nanos as u128,
+    ) as i64);
 
-    let system_timestamp = now_in_ns() as f64 * 1e-9;
+    let system_timestamp = Timestamp::now();
 
-    let gnss_timestamp = match (system_timestamp - timestamp_in_s).abs() {
-        value if value < 3600. => Some(timestamp_in_s),
-        _ => None,
+    let gnss_timestamp = match time_source {
+        // Frames without a timestamp (e.g. from the AVR format)
+        _ if ts_u64 == 0 => None,
+        Some(TimeSource::Gnss) => Some(timestamp),
+        Some(_) => None,
+        // In some cases, the timestamp is just the one of dump1090
+        None => {
+            match system_timestamp.as_nanos().abs_diff(timestamp.as_nanos()) {
+                value if value < 3_600_000_000_000 => Some(timestamp),
+                _ => None,
+            }
+        }
     };
 
     let rssi = if msg[8] == 0xff { None } else { Some(msg[8]) };
     let rssi = rssi.map(|v| v as f64 / 255.);
     let rssi = rssi.map(|v| 10. * (v * v).log10() as f32);
 
-    // In some cases, the timestamp is just the one of dump1090, so forget it!
     let metadata = SensorMetadata {
         system_timestamp,
         gnss_timestamp,
-        nanoseconds: Some(ts_u64),
+        nanoseconds: (ts_u64 != 0).then_some(ts_u64),
         rssi,
         serial,
         name,
+        time_source,
+        clock_error: clock_error(),
+        timestamp_error: None,
     };
 
     TimedMessage {
@@ -243,3 +594,284 @@ fn process_radarcape(
         decode_time: None,
     }
 }
+
+/**
+ * Encode a message as a Beast frame, e.g. to feed other decoders.
+ *
+ * The timestamp (the raw 48-bit value, zero if unknown) and the signal
+ * level are the ones of the first copy of the message, as received; `0x1a`
+ * bytes are escaped. Only Mode S frames (7 or 14 bytes) are encoded.
+ */
+pub fn encode(msg: &TimedMessage) -> Option<Vec<u8>> {
+    let kind = match msg.frame.len() {
+        7 => 0x32,
+        14 => 0x33,
+        _ => return None,
+    };
+    let metadata = msg.metadata.first();
+    let timestamp = metadata.and_then(|m| m.nanoseconds).unwrap_or(0);
+    // 0xff is for an unknown signal level
+    let signal = metadata.and_then(|m| m.rssi).map_or(0xff, |rssi| {
+        (255. * 10f64.powf(rssi as f64 / 20.))
+            .round()
+            .clamp(0., 254.) as u8
+    });
+    let mut data = vec![0x1a, kind];
+    let mut body = timestamp.to_be_bytes()[2..].to_vec();
+    body.push(signal);
+    body.extend(&msg.frame);
+    for byte in body {
+        data.push(byte);
+        if byte == 0x1a {
+            data.push(byte);
+        }
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hexlit::hex;
+
+    #[test]
+    fn test_receiver_status() {
+        let mut data =
+            hex!("1a3400000000000000131a1a200000000000000000000000").to_vec();
+        let mut framer = Framer::new(FramingStats::default());
+        let msg = framer.next_frame(&mut data).unwrap();
+        assert_eq!(msg.len(), 23);
+        let status = ReceiverStatus::from_frame(&msg).unwrap();
+        assert_eq!(status.offset, 0x1a);
+        assert!(status.gps_timestamps());
+        assert!(status.gps_fix());
+        assert_eq!(status.time_source(), TimeSource::Gnss);
+
+        // GPS timestamps without a fix
+        let status = ReceiverStatus { gps: 0, ..status };
+        assert_eq!(status.time_source(), TimeSource::Counter);
+
+        // Mode S frames are not status frames
+        let msg = hex!("1a33001a3f9a1c42c08d406b902015a678d4d220aa4bda");
+        assert_eq!(ReceiverStatus::from_frame(&msg), None);
+
+        let report = ReceiverReport::default();
+        report.set_status(status);
+        assert!(!report.is_empty());
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "gps_fix": false,
+                "gps_timestamps": true,
+                "time_source": "counter"
+            })
+        );
+    }
+
+    #[test]
+    fn test_receiver_position() {
+        let mut data = vec![0x1A, 0x35, 0, 0, 0, 0];
+        data.extend(43.6f32.to_le_bytes());
+        data.extend(1.4f32.to_le_bytes());
+        data.extend(150f32.to_le_bytes());
+        data.extend([0; 5]);
+        let mut framer = Framer::new(FramingStats::default());
+        let msg = framer.next_frame(&mut data).unwrap();
+        assert_eq!(msg.len(), 23);
+        let position = receiver_position(&msg).unwrap();
+        assert!((position.latitude - 43.6).abs() < 1e-5);
+        assert!((position.longitude - 1.4).abs() < 1e-5);
+
+        // No position before the first GPS fix
+        let mut msg = msg.clone();
+        msg[6..14].fill(0);
+        assert_eq!(receiver_position(&msg), None);
+
+        let report = ReceiverReport::default();
+        assert!(report.is_empty());
+        report.clone().set_position(position);
+        assert_eq!(report.position(), Some(position));
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut data =
+            hex!("1a33001a1a3f9a1c42c08d406b902015a678d4d220aa4bda").to_vec();
+        let mut framer = Framer::new(FramingStats::default());
+        let frame = framer.next_frame(&mut data).unwrap();
+        let msg = process_radarcape(&frame, 0, None, None);
+        assert_eq!(
+            encode(&msg).unwrap(),
+            hex!("1a33001a1a3f9a1c42c08d406b902015a678d4d220aa4bda")
+        );
+
+        // Without any metadata
+        let msg = TimedMessage {
+            metadata: vec![],
+            frame: hex!("5d4d20237a55a6").to_vec(),
+            ..msg
+        };
+        assert_eq!(
+            encode(&msg).unwrap(),
+            hex!("1a32000000000000ff5d4d20237a55a6")
+        );
+        let msg = TimedMessage {
+            frame: vec![0x20, 0x00],
+            ..msg
+        };
+        assert_eq!(encode(&msg), None);
+    }
+
+    #[tokio::test]
+    async fn test_udp_reassembly() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+        let stats = FramingStats::default();
+        let stream = next_msg(
+            DataSource::Udp(socket),
+            None,
+            Default::default(),
+            stats.clone(),
+            None,
+        )
+        .await;
+        pin_mut!(stream);
+
+        let long = hex!("1a33000000000000ff8d406b902015a678d4d220aa4bda");
+        let short = hex!("1a32000000000000ff5d4d20237a55a6");
+        let first = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let second = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        // Two frames in one datagram, the second one split in two
+        // datagrams, interleaved with the datagram of another sender
+        let datagram = [&long[..], &long[..10]].concat();
+        first.send_to(&datagram, address).await.unwrap();
+        second.send_to(&short, address).await.unwrap();
+        first.send_to(&long[10..], address).await.unwrap();
+
+        let frames = stream.take(3).collect::<Vec<Vec<u8>>>();
+        let frames = tokio::time::timeout(Duration::from_secs(5), frames)
+            .await
+            .unwrap();
+        assert_eq!(frames, vec![long.to_vec(), short.to_vec(), long.to_vec()]);
+        assert_eq!(stats.datagrams(), 3);
+        assert_eq!(stats.truncated(), 1);
+        assert_eq!(stats.oversized(), 0);
+        assert_eq!(stats.resyncs(), 0);
+    }
+
+    #[test]
+    fn test_senders_eviction() {
+        let mut senders =
+            Senders::new(None, Default::default(), FramingStats::default());
+        let start = senders.last_sweep;
+        let address = |port: u16| SocketAddr::from(([127, 0, 0, 1], port));
+        let long = hex!("1a33000000000000ff8d406b902015a678d4d220aa4bda");
+
+        // A partial frame is kept for an active sender
+        let _ = senders.parser(address(1), start).push(&long[..10]).count();
+        let parser = senders.parser(address(1), start);
+        assert_eq!(parser.pending(), 10);
+
+        // The least recently active sender makes room for new ones
+        for port in 2..=MAX_SENDERS as u16 + 1 {
+            let now = start + Duration::from_millis(port.into());
+            senders.parser(address(port), now);
+        }
+        assert_eq!(senders.parsers.len(), MAX_SENDERS);
+        assert!(!senders.parsers.contains_key(&address(1)));
+
+        // Silent senders are dropped
+        let later = start + SENDER_TIMEOUT + Duration::from_secs(2);
+        assert_eq!(senders.parser(address(1), later).pending(), 0);
+        assert_eq!(senders.parsers.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::TcpListener;
+        use tokio::time::timeout;
+
+        let patience = Duration::from_secs(5);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, mut rx) = mpsc::channel(10);
+        let stats = FramingStats::default();
+        let settings = StreamSettings {
+            idle_timeout: Some(Duration::from_millis(100)),
+            ..StreamSettings::default()
+        };
+        tokio::spawn(receiver(
+            BeastSource::Tcp(address),
+            tx,
+            0,
+            None,
+            settings,
+            stats.clone(),
+            TimestampCheck::default(),
+            ReceiverReport::default(),
+        ));
+
+        // The server keeps the connection open after the first frame, but
+        // stops sending: the receiver reconnects
+        let frame = hex!("1a33000000000000ff8d406b902015a678d4d220aa4bda");
+        let (mut first, _) = listener.accept().await.unwrap();
+        first.write_all(&frame).await.unwrap();
+        let (mut second, _) =
+            timeout(patience, listener.accept()).await.unwrap().unwrap();
+        second.write_all(&frame).await.unwrap();
+        for _ in 0..2 {
+            let msg = timeout(patience, rx.recv()).await.unwrap().unwrap();
+            assert_eq!(msg.frame, frame[9..]);
+        }
+        assert_eq!(stats.reconnections(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_with_filter() {
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::TcpListener;
+        use tokio::time::timeout;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, mut rx) = mpsc::channel(10);
+        let stats = FramingStats::default();
+        let settings = StreamSettings {
+            idle_timeout: Some(Duration::from_millis(100)),
+            filter: FrameFilter {
+                df_filter: Some(vec![17]),
+                ..Default::default()
+            },
+            ..StreamSettings::default()
+        };
+        tokio::spawn(receiver(
+            BeastSource::Tcp(address),
+            tx,
+            0,
+            None,
+            settings,
+            stats.clone(),
+            TimestampCheck::default(),
+            ReceiverReport::default(),
+        ));
+
+        // Frames keep arriving for several idle timeouts, but all of them
+        // are dropped by the filter: the connection is not idle
+        let filtered = hex!("1a32000000000000ff5d4d20237a55a6");
+        let frame = hex!("1a33000000000000ff8d406b902015a678d4d220aa4bda");
+        let (mut server, _) = listener.accept().await.unwrap();
+        for _ in 0..20 {
+            server.write_all(&filtered).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        server.write_all(&frame).await.unwrap();
+        let msg = timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.frame, frame[9..]);
+        assert_eq!(stats.reconnections(), 0);
+    }
+}
@@ -0,0 +1,280 @@
+//! Signal processing kernels for the demodulator.
+//!
+//! The IQ to magnitude conversion and the preamble detection run over every
+//! single sample produced by the SDR (2.4 million per second), which makes
+//! them the most CPU intensive part of the decoding chain on small boards.
+//!
+//! Each kernel comes in a portable scalar version and in vectorised versions
+//! (AVX2 on x86_64, NEON on aarch64), selected at runtime. All versions
+//! produce bit-identical results.
+
+use num_complex::Complex;
+
+/// Scale factor applied to the IQ samples (i16) to get values in [-1, 1]
+const IQ_SCALE: f32 = 1. / (1 << 15) as f32;
+
+/**
+ * Compute the magnitude of IQ samples, scaled to the u16 range.
+ *
+ * The output slice must be at least as long as the input slice.
+ * The fastest implementation available on the current CPU is used.
+ */
+pub fn magnitude(data: &[Complex<i16>], out: &mut [u16]) {
+    assert!(out.len() >= data.len());
+
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+        // SAFETY: the required CPU features have just been checked
+        unsafe { magnitude_avx2(data, out) };
+        return;
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        // SAFETY: the required CPU features have just been checked
+        unsafe { magnitude_neon(data, out) };
+        return;
+    }
+
+    magnitude_scalar(data, out)
+}
+
+/// Portable implementation of [`magnitude`]
+pub fn magnitude_scalar(data: &[Complex<i16>], out: &mut [u16]) {
+    for (b, o) in data.iter().zip(out.iter_mut()) {
+        let fi = f32::from(b.im) * IQ_SCALE;
+        let fq = f32::from(b.re) * IQ_SCALE;
+
+        let mag_sqr = fi.mul_add(fi, fq * fq);
+        let mag = f32::sqrt(mag_sqr);
+        *o = mag.mul_add(f32::from(u16::MAX), 0.5) as u16;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn magnitude_avx2(data: &[Complex<i16>], out: &mut [u16]) {
+    use std::arch::x86_64::*;
+
+    let chunks = data.len() / 8;
+    let scale = _mm256_set1_ps(IQ_SCALE);
+    let max = _mm256_set1_ps(f32::from(u16::MAX));
+    let half = _mm256_set1_ps(0.5);
+    let clamp = _mm256_set1_epi32(i32::from(u16::MAX));
+
+    for k in 0..chunks {
+        // 8 complex samples, interleaved as (re, im) i16 pairs
+        let v = _mm256_loadu_si256(data.as_ptr().add(8 * k) as *const __m256i);
+        // sign-extend the low (re) and high (im) halves of each 32-bit lane
+        let re = _mm256_srai_epi32::<16>(_mm256_slli_epi32::<16>(v));
+        let im = _mm256_srai_epi32::<16>(v);
+
+        let fq = _mm256_mul_ps(_mm256_cvtepi32_ps(re), scale);
+        let fi = _mm256_mul_ps(_mm256_cvtepi32_ps(im), scale);
+
+        let mag_sqr = _mm256_fmadd_ps(fi, fi, _mm256_mul_ps(fq, fq));
+        let mag = _mm256_sqrt_ps(mag_sqr);
+        let scaled = _mm256_fmadd_ps(mag, max, half);
+
+        // truncate like `as u16`, saturating to u16::MAX
+        let int = _mm256_min_epi32(_mm256_cvttps_epi32(scaled), clamp);
+        let packed = _mm_packus_epi32(
+            _mm256_castsi256_si128(int),
+            _mm256_extracti128_si256::<1>(int),
+        );
+        _mm_storeu_si128(out.as_mut_ptr().add(8 * k) as *mut __m128i, packed);
+    }
+
+    magnitude_scalar(&data[8 * chunks..], &mut out[8 * chunks..]);
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn magnitude_neon(data: &[Complex<i16>], out: &mut [u16]) {
+    use std::arch::aarch64::*;
+
+    let chunks = data.len() / 8;
+    let scale = vdupq_n_f32(IQ_SCALE);
+    let max = vdupq_n_f32(f32::from(u16::MAX));
+    let half = vdupq_n_f32(0.5);
+
+    let kernel = |re: int16x4_t, im: int16x4_t| -> uint16x4_t {
+        let fq = vmulq_f32(vcvtq_f32_s32(vmovl_s16(re)), scale);
+        let fi = vmulq_f32(vcvtq_f32_s32(vmovl_s16(im)), scale);
+
+        let mag_sqr = vfmaq_f32(vmulq_f32(fq, fq), fi, fi);
+        let mag = vsqrtq_f32(mag_sqr);
+        let scaled = vfmaq_f32(half, mag, max);
+        // truncate like `as u16`, saturating to u16::MAX
+        vqmovn_u32(vcvtq_u32_f32(scaled))
+    };
+
+    for k in 0..chunks {
+        // 8 complex samples, deinterleaved into re and im lanes
+        let v = vld2q_s16(data.as_ptr().add(8 * k) as *const i16);
+        let lo = kernel(vget_low_s16(v.0), vget_low_s16(v.1));
+        let hi = kernel(vget_high_s16(v.0), vget_high_s16(v.1));
+        vst1q_u16(out.as_mut_ptr().add(8 * k), vcombine_u16(lo, hi));
+    }
+
+    magnitude_scalar(&data[8 * chunks..], &mut out[8 * chunks..]);
+}
+
+/**
+ * Find the positions where a Mode S preamble may start.
+ *
+ * A preamble candidate at position `j` must show a rising edge between
+ * samples `j` and `j + 1` and a falling edge between samples `j + 12` and
+ * `j + 13`. This cheap test discards the vast majority of positions before
+ * the full preamble correlation is performed.
+ *
+ * Positions are looked up in `0..length` and appended to `candidates`;
+ * `data` must contain at least `length + 13` samples.
+ */
+pub fn preamble_candidates(
+    data: &[u16],
+    length: usize,
+    candidates: &mut Vec<usize>,
+) {
+    assert!(data.len() >= length + 13);
+
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: the required CPU features have just been checked
+        unsafe { preamble_candidates_avx2(data, length, candidates) };
+        return;
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        // SAFETY: the required CPU features have just been checked
+        unsafe { preamble_candidates_neon(data, length, candidates) };
+        return;
+    }
+
+    preamble_candidates_scalar(data, 0, length, candidates)
+}
+
+/// Portable implementation of [`preamble_candidates`]
+pub fn preamble_candidates_scalar(
+    data: &[u16],
+    start: usize,
+    length: usize,
+    candidates: &mut Vec<usize>,
+) {
+    for j in start..length {
+        if data[j] < data[j + 1] && data[j + 12] > data[j + 13] {
+            candidates.push(j);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn preamble_candidates_avx2(
+    data: &[u16],
+    length: usize,
+    candidates: &mut Vec<usize>,
+) {
+    use std::arch::x86_64::*;
+
+    let load = |j: usize| {
+        // AVX2 only has signed comparisons: flip the sign bit
+        let v = _mm256_loadu_si256(data.as_ptr().add(j) as *const __m256i);
+        _mm256_xor_si256(v, _mm256_set1_epi16(i16::MIN))
+    };
+
+    let chunks = length / 16;
+    for k in 0..chunks {
+        let j = 16 * k;
+        let rising = _mm256_cmpgt_epi16(load(j + 1), load(j));
+        let falling = _mm256_cmpgt_epi16(load(j + 12), load(j + 13));
+        // two bits per 16-bit lane, keep one
+        let mut mask = _mm256_movemask_epi8(_mm256_and_si256(rising, falling))
+            as u32
+            & 0x5555_5555;
+        while mask != 0 {
+            candidates.push(j + mask.trailing_zeros() as usize / 2);
+            mask &= mask - 1;
+        }
+    }
+
+    preamble_candidates_scalar(data, 16 * chunks, length, candidates);
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn preamble_candidates_neon(
+    data: &[u16],
+    length: usize,
+    candidates: &mut Vec<usize>,
+) {
+    use std::arch::aarch64::*;
+
+    let load = |j: usize| vld1q_u16(data.as_ptr().add(j));
+
+    let chunks = length / 8;
+    for k in 0..chunks {
+        let j = 8 * k;
+        let rising = vcltq_u16(load(j), load(j + 1));
+        let falling = vcgtq_u16(load(j + 12), load(j + 13));
+        // four bits per 16-bit lane, keep one
+        let narrowed = vshrn_n_u16::<4>(vandq_u16(rising, falling));
+        let mut mask = vget_lane_u64::<0>(vreinterpret_u64_u8(narrowed))
+            & 0x1111_1111_1111_1111;
+        while mask != 0 {
+            candidates.push(j + mask.trailing_zeros() as usize / 4);
+            mask &= mask - 1;
+        }
+    }
+
+    preamble_candidates_scalar(data, 8 * chunks, length, candidates);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random samples (xorshift)
+    fn samples(n: usize) -> Vec<Complex<i16>> {
+        let mut state = 0x2545_f491_u32;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as i16
+        };
+        let mut data: Vec<_> =
+            (0..n).map(|_| Complex::new(next(), next())).collect();
+        // extreme values, saturating the output
+        data[0] = Complex::new(i16::MIN, i16::MIN);
+        data[1] = Complex::new(i16::MAX, i16::MIN);
+        data[2] = Complex::new(0, 0);
+        data
+    }
+
+    #[test]
+    fn test_magnitude() {
+        let data = samples(1003);
+        let mut expected = vec![0; data.len()];
+        let mut actual = vec![0; data.len()];
+        magnitude_scalar(&data, &mut expected);
+        magnitude(&data, &mut actual);
+        assert_eq!(expected[0], u16::MAX);
+        assert_eq!(expected[2], 0);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_preamble_candidates() {
+        let data = samples(1003);
+        let mut mag = vec![0; data.len()];
+        magnitude(&data, &mut mag);
+        let length = mag.len() - 13;
+
+        let mut expected = vec![];
+        let mut actual = vec![];
+        preamble_candidates_scalar(&mag, 0, length, &mut expected);
+        preamble_candidates(&mag, length, &mut actual);
+        assert!(!expected.is_empty());
+        assert_eq!(expected, actual);
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+
+/**
+ * Allow and deny lists on downlink formats and typecodes of raw frames.
+ *
+ * Filtering only looks at the first bits of the frames, so it is applied
+ * before any CRC computation or decoding: dropping high-volume traffic
+ * (e.g. DF11 all-call replies) at this stage saves a significant amount of
+ * CPU on busy sites when only ADS-B is wanted.
+ *
+ * Typecode filters only apply to extended squitters (DF17 and DF18).
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrameFilter {
+    /// Downlink formats to keep (default: all)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub df_filter: Option<Vec<u8>>,
+    /// Downlink formats to drop (default: none)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub df_exclude: Option<Vec<u8>>,
+    /// Typecodes of extended squitters to keep (default: all)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tc_filter: Option<Vec<u8>>,
+    /// Typecodes of extended squitters to drop (default: none)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tc_exclude: Option<Vec<u8>>,
+}
+
+impl FrameFilter {
+    /// True if no filter is set, i.e. all frames are accepted
+    pub fn is_empty(&self) -> bool {
+        self.df_filter.is_none()
+            && self.df_exclude.is_none()
+            && self.tc_filter.is_none()
+            && self.tc_exclude.is_none()
+    }
+
+    /// True if the frame passes all the filters
+    pub fn accept(&self, frame: &[u8]) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Some(first) = frame.first() else {
+            return false;
+        };
+        // All downlink formats starting with 11 are DF24 (Comm-D)
+        let df = (first >> 3).min(24);
+        if !allowed(df, &self.df_filter, &self.df_exclude) {
+            return false;
+        }
+        if df == 17 || df == 18 {
+            let Some(me) = frame.get(4) else {
+                return false;
+            };
+            return allowed(me >> 3, &self.tc_filter, &self.tc_exclude);
+        }
+        true
+    }
+}
+
+fn allowed(value: u8, allow: &Option<Vec<u8>>, deny: &Option<Vec<u8>>) -> bool {
+    allow.as_ref().is_none_or(|list| list.contains(&value))
+        && !deny.as_ref().is_some_and(|list| list.contains(&value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hexlit::hex;
+
+    #[test]
+    fn test_frame_filter() {
+        let df11 = hex!("5d4d20237a55a6");
+        let df17_tc4 = hex!("8d406b902015a678d4d220aa4bda");
+        let df17_tc11 = hex!("8d40058b58c901375147efd09357");
+
+        let filter = FrameFilter::default();
+        assert!(filter.accept(&df11));
+        assert!(filter.accept(&[]));
+
+        let filter = FrameFilter {
+            df_filter: Some(vec![17, 18]),
+            ..Default::default()
+        };
+        assert!(!filter.accept(&df11));
+        assert!(filter.accept(&df17_tc4));
+
+        let filter = FrameFilter {
+            df_exclude: Some(vec![11]),
+            tc_filter: Some(vec![9, 10, 11, 12, 13, 14, 15, 16, 17, 18]),
+            ..Default::default()
+        };
+        assert!(!filter.accept(&df11));
+        assert!(!filter.accept(&df17_tc4));
+        assert!(filter.accept(&df17_tc11));
+
+        let filter = FrameFilter {
+            tc_exclude: Some(vec![4]),
+            ..Default::default()
+        };
+        assert!(filter.accept(&df11));
+        assert!(!filter.accept(&df17_tc4));
+        assert!(!filter.accept(&[]));
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of bytes needed to detect the format of a stream
+const DETECT_SIZE: usize = 64;
+
+/**
+ * The format of a stream of Mode S data.
+ *
+ * - Beast binary: the default output of most decoders (port 30005 for
+ *   dump1090, port 10003 for Radarcape devices);
+ * - AVR: hexadecimal frames as ASCII lines, e.g. `*8D406B902015A678D4D220AA4BDA;`
+ *   (port 30002 for dump1090), optionally with a timestamp (`@` prefix) or a
+ *   timestamp and a signal level (`<` prefix);
+ * - SBS: the BaseStation CSV format (port 30003 for dump1090), detected but
+ *   not supported as it only contains decoded data.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Beast,
+    Avr,
+    Sbs,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Beast => write!(f, "Beast binary"),
+            Format::Avr => write!(f, "AVR"),
+            Format::Sbs => write!(f, "SBS"),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "beast" => Ok(Format::Beast),
+            "avr" => Ok(Format::Avr),
+            "sbs" => Ok(Format::Sbs),
+            _ => Err(format!("unknown format: {s} (beast, avr or sbs)")),
+        }
+    }
+}
+
+impl Format {
+    /**
+     * Detect the format of a stream from its first bytes.
+     *
+     * Returns None if more data is needed to decide. Beast frames are
+     * recognized from their escape sequence (0x1A, then a frame type);
+     * text formats from the first characters of a complete line.
+     */
+    pub fn detect(data: &[u8]) -> Option<Format> {
+        if data
+            .windows(2)
+            .any(|w| w[0] == 0x1A && (0x31..=0x34).contains(&w[1]))
+        {
+            return Some(Format::Beast);
+        }
+        let text = String::from_utf8_lossy(data);
+        let mut lines = text.split('\n').map(str::trim);
+        // The last line may be incomplete
+        lines.next_back();
+        for line in lines {
+            if line.starts_with(['*', '@', '<']) && line.ends_with(';') {
+                return Some(Format::Avr);
+            }
+            let kind = line.split(',').next().unwrap_or_default();
+            if ["MSG", "SEL", "ID", "AIR", "STA", "CLK"].contains(&kind) {
+                return Some(Format::Sbs);
+            }
+        }
+        None
+    }
+
+    /// True if enough data was received to give up detection
+    pub fn detection_failed(data: &[u8]) -> bool {
+        (data.len() >= DETECT_SIZE && data.contains(&b'\n'))
+            || data.len() >= 16 * DETECT_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hexlit::hex;
+
+    #[test]
+    fn test_detect() {
+        let beast = hex!("001a33001a1a3f9a1c42c08d406b902015a678d4d220aa4bda");
+        assert_eq!(Format::detect(&beast), Some(Format::Beast));
+        // Beast frames without an escape sequence yet
+        assert_eq!(Format::detect(&beast[..1]), None);
+
+        let avr = b"*8D406B902015A678D4D220AA4BDA;\r\n*5D4D2023";
+        assert_eq!(Format::detect(avr), Some(Format::Avr));
+        let avr = b"@0A1B2C3D4E5F8D406B902015A678D4D220AA4BDA;\n";
+        assert_eq!(Format::detect(avr), Some(Format::Avr));
+        // Incomplete line
+        assert_eq!(Format::detect(b"*8D406B902015A678"), None);
+
+        let sbs = b"MSG,3,1,1,4CA2D6,1,2024/01/01,12:00:00.000,\
+            2024/01/01,12:00:00.000,,37000,,,43.6,1.4,,,0,0,0,0\n";
+        assert_eq!(Format::detect(sbs), Some(Format::Sbs));
+
+        assert_eq!("avr".parse(), Ok(Format::Avr));
+        assert!("raw".parse::<Format>().is_err());
+    }
+}
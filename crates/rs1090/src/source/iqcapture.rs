@@ -0,0 +1,363 @@
+/*!
+ * Capture of raw IQ samples around weakly demodulated frames.
+ *
+ * When a frame is demodulated with a low signal to noise ratio, the IQ
+ * samples around it can be saved to a capture file, so that problematic
+ * frames can be replayed through the demodulator later on.
+ *
+ * The capture file is a ring buffer with a fixed number of slots: when all
+ * the slots are used, the oldest snippets are overwritten. Snippets are
+ * indexed by the hash of their frame (see [`frame_hash`]), so that a frame
+ * spotted in the decoded output can be found in the capture file.
+ *
+ * The file (little endian) starts with a 20 bytes header: a `R1IQ` magic
+ * number, the number of slots (u32), the number of samples per slot (u32),
+ * the sample rate (u32) and the next slot to be written (u32). Then comes
+ * the index, with 36 bytes per slot: frame hash (u64), timestamp in
+ * nanoseconds (i64), SNR in dB (f32), frame length (u8, 0 for an empty
+ * slot) and frame (14 bytes, zero padded). The samples come last, with 4
+ * bytes per sample (I and Q as i16).
+ */
+
+use num_complex::Complex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::decode::time::Timestamp;
+
+/// The header of the capture file, with the version of the format
+const MAGIC: &[u8; 4] = b"R1IQ";
+/// Size of the header of the capture file
+const HEADER: u64 = 20;
+/// Size of each entry of the index
+const RECORD: usize = 36;
+/// The longest frame (in bytes)
+const MAX_FRAME: usize = 14;
+
+/// Number of samples per snippet, enough for a long frame and its preamble
+pub const SNIPPET_SAMPLES: usize = 512;
+/// Number of samples kept before the preamble of the frame
+pub const SNIPPET_LEADING: usize = 64;
+
+fn default_snr() -> f32 {
+    6.
+}
+
+fn default_slots() -> u32 {
+    4096
+}
+
+/// Configuration of the capture of IQ samples
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureSettings {
+    /// The path to the capture file (overwritten when the capture starts)
+    pub path: PathBuf,
+    /// Frames demodulated below this SNR (in dB) are captured
+    #[serde(default = "default_snr")]
+    pub snr: f32,
+    /// The number of snippets kept in the ring buffer
+    #[serde(default = "default_slots")]
+    pub slots: u32,
+}
+
+/**
+ * A hash of a frame, to look up its samples in a capture file.
+ *
+ * This is a 64-bit FNV-1a hash, simple enough to be computed again from the
+ * hexadecimal representation of a frame.
+ */
+pub fn frame_hash(frame: &[u8]) -> u64 {
+    frame.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// An entry of the index of a capture file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub hash: u64,
+    pub timestamp: Timestamp,
+    /// The signal to noise ratio (in dB) estimated on the preamble
+    pub snr: f32,
+    pub frame: Vec<u8>,
+}
+
+impl Entry {
+    fn to_bytes(&self) -> [u8; RECORD] {
+        let mut bytes = [0; RECORD];
+        let len = self.frame.len().min(MAX_FRAME);
+        bytes[0..8].copy_from_slice(&self.hash.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.timestamp.0.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.snr.to_le_bytes());
+        bytes[20] = len as u8;
+        bytes[21..21 + len].copy_from_slice(&self.frame[..len]);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let len = (bytes[20] as usize).min(MAX_FRAME);
+        if len == 0 {
+            return None;
+        }
+        Some(Entry {
+            hash: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            timestamp: Timestamp(i64::from_le_bytes(
+                bytes[8..16].try_into().unwrap(),
+            )),
+            snr: f32::from_le_bytes(bytes[16..20].try_into().unwrap()),
+            frame: bytes[21..21 + len].to_vec(),
+        })
+    }
+}
+
+/// A frame with the IQ samples around it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    pub entry: Entry,
+    /// [`SNIPPET_LEADING`] samples before the preamble, then the frame
+    pub samples: Vec<Complex<i16>>,
+}
+
+impl Snippet {
+    /**
+     * Take the samples around a frame, starting [`SNIPPET_LEADING`] samples
+     * before its preamble, at index `start` of the IQ buffer. Samples out of
+     * the buffer are set to zero.
+     */
+    pub fn new(
+        timestamp: Timestamp,
+        frame: &[u8],
+        snr: f32,
+        iq: &[Complex<i16>],
+        start: usize,
+    ) -> Self {
+        let samples = (0..SNIPPET_SAMPLES)
+            .map(|i| {
+                (start + i)
+                    .checked_sub(SNIPPET_LEADING)
+                    .and_then(|index| iq.get(index).copied())
+                    .unwrap_or_default()
+            })
+            .collect();
+        Snippet {
+            entry: Entry {
+                hash: frame_hash(frame),
+                timestamp,
+                snr,
+                frame: frame.to_vec(),
+            },
+            samples,
+        }
+    }
+}
+
+/// A capture file, written as a ring buffer
+#[derive(Debug)]
+pub struct CaptureWriter {
+    file: File,
+    /// The frames demodulated below this SNR (in dB) are captured
+    pub snr: f32,
+    slots: u32,
+    next: u32,
+}
+
+impl CaptureWriter {
+    pub fn create(
+        settings: &CaptureSettings,
+        sample_rate: u32,
+    ) -> io::Result<Self> {
+        let slots = settings.slots.max(1);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&settings.path)?;
+        let size =
+            HEADER + slots as u64 * (RECORD + SNIPPET_SAMPLES * 4) as u64;
+        file.set_len(size)?;
+        file.write_all(MAGIC)?;
+        for value in [slots, SNIPPET_SAMPLES as u32, sample_rate, 0] {
+            file.write_all(&value.to_le_bytes())?;
+        }
+        Ok(CaptureWriter {
+            file,
+            snr: settings.snr,
+            slots,
+            next: 0,
+        })
+    }
+
+    /// Write a snippet, in place of the oldest one if the file is full
+    pub fn write(&mut self, snippet: &Snippet) -> io::Result<()> {
+        let slot = self.next as u64;
+        let samples: Vec<u8> = (0..SNIPPET_SAMPLES)
+            .flat_map(|i| {
+                let sample =
+                    snippet.samples.get(i).copied().unwrap_or_default();
+                let [i0, i1] = sample.re.to_le_bytes();
+                let [q0, q1] = sample.im.to_le_bytes();
+                [i0, i1, q0, q1]
+            })
+            .collect();
+        let record = HEADER + slot * RECORD as u64;
+        let samples_offset = HEADER
+            + self.slots as u64 * RECORD as u64
+            + slot * samples.len() as u64;
+        // The slot is emptied while its samples are written: it is never
+        // indexed with the samples of another frame
+        self.file.seek(SeekFrom::Start(record))?;
+        self.file.write_all(&[0; RECORD])?;
+        self.file.seek(SeekFrom::Start(samples_offset))?;
+        self.file.write_all(&samples)?;
+        self.file.seek(SeekFrom::Start(record))?;
+        self.file.write_all(&snippet.entry.to_bytes())?;
+        self.next = (self.next + 1) % self.slots;
+        self.file.seek(SeekFrom::Start(HEADER - 4))?;
+        self.file.write_all(&self.next.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+/// A capture file, opened to replay the captured frames
+#[derive(Debug)]
+pub struct CaptureReader {
+    file: File,
+    /// The sample rate (in Hz) of the captured samples
+    pub sample_rate: u32,
+    slots: u32,
+    samples: u32,
+    /// The captured frames, from the oldest to the most recent one
+    entries: Vec<(u32, Entry)>,
+    index: HashMap<u64, u32>,
+}
+
+impl CaptureReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut header = [0; HEADER as usize];
+        file.read_exact(&mut header)?;
+        if &header[0..4] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an IQ capture file",
+            ));
+        }
+        let u32_at =
+            |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        let (slots, samples, sample_rate, next) =
+            (u32_at(4), u32_at(8), u32_at(12), u32_at(16));
+
+        let mut records = vec![0; slots as usize * RECORD];
+        file.read_exact(&mut records)?;
+        let mut entries: Vec<(u32, Entry)> = records
+            .chunks(RECORD)
+            .enumerate()
+            .filter_map(|(slot, bytes)| {
+                Some((slot as u32, Entry::from_bytes(bytes)?))
+            })
+            .collect();
+        // The oldest snippet is in the next slot to be written
+        entries.sort_by_key(|(slot, _)| (slot + slots - next) % slots);
+        // For frames captured several times, the most recent snippet wins
+        let index = entries
+            .iter()
+            .map(|(slot, entry)| (entry.hash, *slot))
+            .collect();
+        Ok(CaptureReader {
+            file,
+            sample_rate,
+            slots,
+            samples,
+            entries,
+            index,
+        })
+    }
+
+    /// The captured frames, from the oldest to the most recent one
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().map(|(_, entry)| entry)
+    }
+
+    /// The most recent snippet of a frame, from its hash
+    pub fn get(&mut self, hash: u64) -> io::Result<Option<Snippet>> {
+        let Some(&slot) = self.index.get(&hash) else {
+            return Ok(None);
+        };
+        let entry = self
+            .entries
+            .iter()
+            .find(|(s, _)| *s == slot)
+            .map(|(_, entry)| entry.clone())
+            .unwrap();
+        let size = self.samples as u64 * 4;
+        let offset =
+            HEADER + self.slots as u64 * RECORD as u64 + slot as u64 * size;
+        self.file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![0; size as usize];
+        self.file.read_exact(&mut bytes)?;
+        let samples = bytes
+            .chunks(4)
+            .map(|b| {
+                Complex::new(
+                    i16::from_le_bytes([b[0], b[1]]),
+                    i16::from_le_bytes([b[2], b[3]]),
+                )
+            })
+            .collect();
+        Ok(Some(Snippet { entry, samples }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hexlit::hex;
+
+    #[test]
+    fn test_capture() {
+        assert_eq!(frame_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(frame_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+
+        let path = std::env::temp_dir().join("rs1090_test_iqcapture.bin");
+        let settings = CaptureSettings {
+            path: path.clone(),
+            snr: 6.,
+            slots: 2,
+        };
+        let iq: Vec<Complex<i16>> =
+            (0..1000).map(|i| Complex::new(i, -i)).collect();
+        let frames = [
+            hex!("8d4840d6202cc371c32ce0576098"),
+            hex!("8d40621d58c382d690c8ac2863a7"),
+            hex!("8d40621d58c386435cc412692ad6"),
+        ];
+        let mut writer = CaptureWriter::create(&settings, 2_400_000).unwrap();
+        for (i, frame) in frames.iter().enumerate() {
+            let timestamp = Timestamp::from_secs(i as i64);
+            let snippet = Snippet::new(timestamp, frame, 4.5, &iq, 10 + i);
+            writer.write(&snippet).unwrap();
+        }
+
+        let mut reader = CaptureReader::open(&path).unwrap();
+        assert_eq!(reader.sample_rate, 2_400_000);
+        // The first snippet was overwritten
+        let entries: Vec<&Entry> = reader.entries().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].frame, frames[1]);
+        assert_eq!(entries[1].timestamp, Timestamp::from_secs(2));
+        assert!(reader.get(frame_hash(&frames[0])).unwrap().is_none());
+
+        let snippet = reader.get(frame_hash(&frames[2])).unwrap().unwrap();
+        assert_eq!(snippet.entry.frame, frames[2]);
+        assert_eq!(snippet.entry.snr, 4.5);
+        assert_eq!(snippet.samples.len(), SNIPPET_SAMPLES);
+        // Zero padded before the start of the IQ buffer
+        assert_eq!(snippet.samples[0], Complex::new(0, 0));
+        assert_eq!(snippet.samples[SNIPPET_LEADING], Complex::new(12, -12));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,287 @@
+use futures_util::stream::StreamExt;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::io;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tracing::{info, warn};
+
+use crate::decode::crc::modes_checksum;
+use crate::decode::strict::Warning;
+use crate::decode::{as_hex, from_hex, DF};
+use crate::prelude::*;
+use crate::source::beast::{MAX_RECONNECT_DELAY, RECONNECT_DELAY};
+use crate::source::filter::FrameFilter;
+
+/// The websocket endpoint of jet1090 streaming decoded messages
+pub const ENDPOINT: &str = "stream";
+
+/// A decoded message, as sent on the stream
+///
+/// Decoded fields are always serialized in implicit units and with full
+/// precision, whatever the configuration of the sender, so that they can be
+/// read back.
+#[derive(Serialize)]
+struct Outgoing<'a> {
+    timestamp: Timestamp,
+    #[serde(serialize_with = "as_hex")]
+    frame: &'a Vec<u8>,
+    #[serde(flatten)]
+    df: Option<&'a DF>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    warnings: &'a [Warning],
+    metadata: &'a [SensorMetadata],
+    num_receivers: usize,
+}
+
+/// A decoded message, as received from the stream
+#[derive(Deserialize)]
+struct Incoming {
+    timestamp: Timestamp,
+    #[serde(deserialize_with = "from_hex")]
+    frame: Vec<u8>,
+    /// Left empty if the decoded fields can not be read
+    #[serde(flatten)]
+    df: Option<DF>,
+    #[serde(default)]
+    warnings: Vec<Warning>,
+    #[serde(default)]
+    metadata: Vec<SensorMetadata>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Line {
+    /// Other events (e.g. heartbeats of sensors) are tagged with their type
+    #[allow(dead_code)]
+    Record {
+        record: IgnoredAny,
+    },
+    Message(Box<Incoming>),
+}
+
+impl From<Incoming> for TimedMessage {
+    fn from(incoming: Incoming) -> Self {
+        let bits = incoming.frame.len() * 8;
+        let message = match (incoming.df, modes_checksum(&incoming.frame, bits))
+        {
+            (Some(df), Ok(crc)) => Some(Message {
+                crc,
+                df,
+                warnings: incoming.warnings,
+            }),
+            _ => None,
+        };
+        TimedMessage {
+            timestamp: incoming.timestamp,
+            frame: incoming.frame,
+            message,
+            metadata: incoming.metadata,
+            decode_time: None,
+        }
+    }
+}
+
+/**
+ * Serialize a message for the websocket stream of jet1090.
+ *
+ * Unlike the serialization of [`TimedMessage`], decoded fields are written
+ * in implicit units and with full precision, so that [`parse_line`] reads
+ * them back as they were decoded.
+ */
+pub fn to_json(tmsg: &TimedMessage) -> serde_json::Result<String> {
+    serde_json::to_string(&Outgoing {
+        timestamp: tmsg.timestamp,
+        frame: &tmsg.frame,
+        df: tmsg.message.as_ref().map(|msg| &msg.df),
+        warnings: tmsg
+            .message
+            .as_ref()
+            .map_or(&[], |msg| msg.warnings.as_slice()),
+        metadata: &tmsg.metadata,
+        num_receivers: tmsg.metadata.len(),
+    })
+}
+
+/**
+ * Read a message from the websocket stream of jet1090, with its decoded
+ * fields.
+ *
+ * Returns None if the line is not a message. Fields which are not
+ * serialized (e.g. the surveillance status of DF4 and DF5 messages) take
+ * their default value. If the decoded fields can not be read, the `message`
+ * field is left empty, so that the frame is decoded again.
+ */
+pub fn parse_line(line: &str) -> serde_json::Result<Option<TimedMessage>> {
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    match serde_json::from_str(line)? {
+        Line::Record { .. } => Ok(None),
+        Line::Message(incoming) => Ok(Some((*incoming).into())),
+    }
+}
+
+/**
+ * Receive the messages decoded by another instance of jet1090, on the
+ * websocket stream of its REST API (`ws://host:port/stream`).
+ *
+ * Messages come with the metadata of the remote sensors and with their
+ * decoded fields, so that they are not decoded again. They are merged by
+ * frame with messages received from other sources. Frames rejected by the
+ * filter are dropped.
+ *
+ * When the connection fails or is closed, the receiver reconnects, waiting
+ * longer and longer between attempts. It stops when the queue is closed.
+ */
+pub async fn receiver(
+    address: &str,
+    tx: mpsc::Sender<TimedMessage>,
+    filter: FrameFilter,
+) -> io::Result<()> {
+    let url = format!("ws://{}/{}", address.trim_end_matches('/'), ENDPOINT);
+    let mut delay = RECONNECT_DELAY;
+    loop {
+        info!("Connecting to jet1090: {}", url);
+        match connect_async(&url).await {
+            Ok((stream, _)) => {
+                info!("Connected to jet1090: {}", url);
+                delay = RECONNECT_DELAY;
+                match forward(&url, stream, &tx, &filter).await {
+                    Ok(()) => info!("Disconnected from jet1090: {}", url),
+                    Err(e) => {
+                        warn!("Disconnected from jet1090: {} ({})", url, e)
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to connect to jet1090: {} ({})", url, e),
+        }
+        if tx.is_closed() {
+            return Ok(());
+        }
+        warn!("Reconnecting to jet1090 {} in {:?}", url, delay);
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+/// Forward the messages of a connection, until it is closed
+async fn forward(
+    url: &str,
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    tx: &mpsc::Sender<TimedMessage>,
+    filter: &FrameFilter,
+) -> io::Result<()> {
+    let (_, mut rx) = stream.split();
+    while let Some(msg) = rx.next().await {
+        let text = match msg.map_err(io::Error::other)? {
+            WsMessage::Text(text) => text,
+            WsMessage::Close(_) => break,
+            _ => continue,
+        };
+        match parse_line(&text) {
+            Ok(Some(tmsg)) if !filter.accept(&tmsg.frame) => {}
+            Ok(Some(tmsg)) => {
+                if tx.send(tmsg).await.is_err() {
+                    break;
+                }
+            }
+            // Other events (heartbeats of sensors)
+            Ok(None) => {}
+            Err(e) => warn!("Invalid message from {}: {}", url, e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::timed;
+    use futures_util::SinkExt;
+    use hexlit::hex;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio::time::timeout;
+    use tokio_tungstenite::accept_async;
+
+    fn received(frame: &[u8]) -> TimedMessage {
+        let mut tmsg = timed(frame, 1_700_000_000.5);
+        tmsg.metadata.push(SensorMetadata {
+            system_timestamp: tmsg.timestamp,
+            gnss_timestamp: None,
+            nanoseconds: None,
+            rssi: Some(-12.5),
+            serial: 42,
+            name: Some("remote".to_string()),
+            time_source: None,
+            clock_error: None,
+            timestamp_error: None,
+        });
+        tmsg
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for frame in [
+            hex!("8d406b902015a678d4d220aa4bda").as_slice(),
+            &hex!("8d485020994409940838175b284f"),
+            &hex!("5d4d20237a55a6"),
+            &hex!("a0001838ca3e51f0a8000047a9f5"),
+        ] {
+            let tmsg = received(frame);
+            let line = to_json(&tmsg).unwrap();
+            let parsed = parse_line(&line).unwrap().unwrap();
+            assert_eq!(parsed.timestamp, tmsg.timestamp);
+            assert_eq!(parsed.frame, tmsg.frame);
+            // Fields which are not serialized take their default value
+            assert_eq!(
+                serde_json::to_value(&parsed.message).unwrap(),
+                serde_json::to_value(&tmsg.message).unwrap()
+            );
+            assert_eq!(parsed.message.unwrap().crc, tmsg.message.unwrap().crc);
+            assert_eq!(parsed.metadata[0].serial, 42);
+        }
+
+        // Undecoded messages are decoded again
+        let mut tmsg = received(&hex!("8d406b902015a678d4d220aa4bda"));
+        tmsg.message = None;
+        let line = to_json(&tmsg).unwrap();
+        assert!(parse_line(&line).unwrap().unwrap().message.is_none());
+
+        let heartbeat = r#"{"record":"heartbeat","serial":42}"#;
+        assert!(parse_line(heartbeat).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, mut rx) = mpsc::channel(10);
+        tokio::spawn(async move {
+            receiver(&address, tx, FrameFilter::default()).await
+        });
+
+        // The server closes the connection after each message
+        let frames = [
+            hex!("8d406b902015a678d4d220aa4bda"),
+            hex!("8d485020994409940838175b284f"),
+        ];
+        for frame in frames {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(socket).await.unwrap();
+            let line = to_json(&received(&frame)).unwrap();
+            ws.send(WsMessage::text(line)).await.unwrap();
+            ws.close(None).await.unwrap();
+
+            let msg = timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(msg.frame, frame);
+            assert!(msg.message.is_some());
+        }
+    }
+}
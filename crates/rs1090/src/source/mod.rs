@@ -1,5 +1,23 @@
 #[cfg(not(target_arch = "wasm32"))]
 pub mod beast;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod iqcapture;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod jet1090;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod processing;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sampleclock;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sensor;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod timestamps;
+
+pub mod avr;
+pub mod dsp;
+pub mod filter;
+pub mod format;
+pub mod parser;
 
 #[cfg(feature = "rtlsdr")]
 pub mod rtlsdr;
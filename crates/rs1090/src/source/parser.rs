@@ -0,0 +1,603 @@
+/*!
+ * A synchronous, incremental parser of Beast (and AVR) streams.
+ *
+ * The parser does not perform any IO: bytes are pushed as they are read
+ * (from a socket, a file, or a JavaScript buffer), and complete frames are
+ * returned. The async [`receiver`] is a thin wrapper around it.
+ *
+ * ```
+ * use rs1090::source::parser::BeastParser;
+ *
+ * let data = hex::decode("1a33001a1a3f9a1c42c08d406b902015a678d4d220aa4bda")
+ *     .unwrap();
+ * let mut parser = BeastParser::default();
+ * // Frames may be split across several chunks
+ * assert_eq!(parser.push(&data[..10]).count(), 0);
+ * let frames: Vec<_> = parser.push(&data[10..]).collect();
+ * assert_eq!(frames.len(), 1);
+ * assert_eq!(frames[0].timestamp(), 0x001a3f9a1c42);
+ * assert_eq!(hex::encode(frames[0].payload()), "8d406b902015a678d4d220aa4bda");
+ * ```
+ *
+ * [`receiver`]: crate::source::beast::receiver
+ */
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use super::avr;
+use super::filter::FrameFilter;
+use super::format::Format;
+use crate::decode::crc::modes_checksum;
+
+/// The size of a frame (with the escape and type bytes), from its type byte
+fn frame_size(kind: u8) -> Option<usize> {
+    match kind {
+        0x31 => Some(11),
+        0x32 => Some(16),
+        0x33..=0x35 => Some(23),
+        _ => None,
+    }
+}
+
+/// The result of reading one frame at the start of a buffer
+#[derive(Debug, PartialEq)]
+enum Unescaped {
+    /// More data is necessary
+    Incomplete,
+    /// Another frame starts at this index (in the buffer), before the end of
+    /// the current one
+    Truncated(usize),
+    /// The frame (with escaped 0x1A bytes collapsed), and the number of bytes
+    /// it takes in the buffer
+    Frame(Vec<u8>, usize),
+}
+
+/// Read the frame starting at the beginning of the buffer (with a valid type)
+fn unescape(data: &[u8]) -> Unescaped {
+    let Some(size) = data.get(1).copied().and_then(frame_size) else {
+        return Unescaped::Incomplete;
+    };
+    let mut frame = Vec::with_capacity(size);
+    frame.extend_from_slice(&data[..2]);
+    let mut idx = 2;
+    while frame.len() < size {
+        match data.get(idx..idx + 2) {
+            Some([0x1A, 0x1A]) => {
+                frame.push(0x1A);
+                idx += 2;
+            }
+            Some([0x1A, kind]) if frame_size(*kind).is_some() => {
+                return Unescaped::Truncated(idx);
+            }
+            // A single 0x1A is kept, as in the previous versions
+            Some([byte, _]) => {
+                frame.push(*byte);
+                idx += 1;
+            }
+            _ => match data.get(idx) {
+                Some(&byte) if byte != 0x1A => {
+                    frame.push(byte);
+                    idx += 1;
+                }
+                _ => return Unescaped::Incomplete,
+            },
+        }
+    }
+    Unescaped::Frame(frame, idx)
+}
+
+/**
+ * True if the CRC of a Mode S frame (DF11, DF17 or DF18) is valid.
+ *
+ * The parity of other downlink formats is overlaid with the address of the
+ * aircraft, so they cannot be checked.
+ */
+fn valid_crc(frame: &[u8]) -> Option<bool> {
+    let payload = frame.get(9..)?;
+    let df = payload.first()? >> 3;
+    let bits = payload.len() * 8;
+    match df {
+        // The interrogator identifier may be in the lower bits
+        11 => Some(modes_checksum(payload, bits).is_ok_and(|crc| crc < 0x80)),
+        17 | 18 => {
+            Some(modes_checksum(payload, bits).is_ok_and(|crc| crc == 0))
+        }
+        _ => None,
+    }
+}
+
+/// The largest UDP payload sent in a single Ethernet frame: larger
+/// datagrams are fragmented at the IP level, and more likely to be lost
+pub const MAX_DATAGRAM: usize = 1472;
+
+/**
+ * Statistics about the framing of a Beast stream.
+ *
+ * A high number of resynchronizations or discarded bytes hints at a wrong
+ * format or a corrupted link; truncated or oversized datagrams at a UDP
+ * sender splitting frames badly. Clones share the same counters.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct FramingStats(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    resyncs: AtomicU64,
+    discarded: AtomicU64,
+    datagrams: AtomicU64,
+    truncated: AtomicU64,
+    oversized: AtomicU64,
+    reconnections: AtomicU64,
+}
+
+impl FramingStats {
+    /// Number of times the synchronization on frame boundaries was lost
+    pub fn resyncs(&self) -> u64 {
+        self.0.resyncs.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes discarded while looking for a frame boundary
+    pub fn discarded(&self) -> u64 {
+        self.0.discarded.load(Ordering::Relaxed)
+    }
+
+    /// Number of datagrams received (for UDP sources)
+    pub fn datagrams(&self) -> u64 {
+        self.0.datagrams.load(Ordering::Relaxed)
+    }
+
+    /// Number of datagrams ending in the middle of a frame, completed by the
+    /// next datagram of the same sender
+    pub fn truncated(&self) -> u64 {
+        self.0.truncated.load(Ordering::Relaxed)
+    }
+
+    /// Number of datagrams larger than [`MAX_DATAGRAM`]
+    pub fn oversized(&self) -> u64 {
+        self.0.oversized.load(Ordering::Relaxed)
+    }
+
+    /// Number of reconnections of TCP sources with an idle timeout
+    pub fn reconnections(&self) -> u64 {
+        self.0.reconnections.load(Ordering::Relaxed)
+    }
+
+    /// Count a reconnection of a TCP source
+    pub fn record_reconnection(&self) {
+        self.0.reconnections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /**
+     * Count a datagram of a given size, after its bytes were pushed to the
+     * parser of its sender.
+     */
+    pub fn record_datagram(&self, size: usize, parser: &BeastParser) {
+        self.0.datagrams.fetch_add(1, Ordering::Relaxed);
+        if parser.pending() > 0 {
+            self.0.truncated.fetch_add(1, Ordering::Relaxed);
+        }
+        if size > MAX_DATAGRAM {
+            self.0.oversized.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Serialize for FramingStats {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("FramingStats", 6)?;
+        state.serialize_field("resyncs", &self.resyncs())?;
+        state.serialize_field("discarded", &self.discarded())?;
+        // Only for UDP sources
+        if self.datagrams() > 0 {
+            state.serialize_field("datagrams", &self.datagrams())?;
+            state.serialize_field("truncated", &self.truncated())?;
+            state.serialize_field("oversized", &self.oversized())?;
+        }
+        // Only for TCP sources with an idle timeout
+        if self.reconnections() > 0 {
+            state.serialize_field("reconnections", &self.reconnections())?;
+        }
+        state.end()
+    }
+}
+
+/**
+ * Split a Beast stream into frames, keeping track of the synchronization on
+ * frame boundaries.
+ *
+ * When synchronized, each frame must start with 0x1A and a valid type byte,
+ * and be followed by the start of the next frame. Otherwise (e.g. a TCP
+ * stream starting mid-frame, or dropped bytes), the synchronization is lost:
+ * frames are then only accepted again after a 0x1A and a valid type byte,
+ * when their CRC is valid (DF11, DF17, DF18) or when they are immediately
+ * followed by the start of another frame. Bytes skipped meanwhile are
+ * discarded and counted.
+ */
+#[derive(Debug)]
+pub struct Framer {
+    synced: bool,
+    stats: FramingStats,
+}
+
+impl Framer {
+    pub fn new(stats: FramingStats) -> Self {
+        Framer {
+            synced: true,
+            stats,
+        }
+    }
+
+    fn lose_sync(&mut self) {
+        self.synced = false;
+        self.stats.0.resyncs.fetch_add(1, Ordering::Relaxed);
+        debug!("Lost synchronization in Beast stream");
+    }
+
+    fn discard(&mut self, data: &mut Vec<u8>, count: usize) {
+        data.drain(..count);
+        self.stats
+            .0
+            .discarded
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// True if the buffer starts with a frame, None if more data is necessary
+    fn frame_start(data: &[u8]) -> Option<bool> {
+        match data {
+            [] => None,
+            [0x1A] => None,
+            [0x1A, kind, ..] => Some(frame_size(*kind).is_some()),
+            _ => Some(false),
+        }
+    }
+
+    /**
+     * Extract the next Beast frame from the buffer, if it is complete.
+     *
+     * Escaped 0x1A bytes are collapsed; incomplete frames are left in the
+     * buffer until more data is received.
+     */
+    pub fn next_frame(&mut self, data: &mut Vec<u8>) -> Option<Vec<u8>> {
+        loop {
+            if self.synced {
+                match Self::frame_start(data)? {
+                    true => {}
+                    false => {
+                        self.lose_sync();
+                        continue;
+                    }
+                }
+                match unescape(data) {
+                    Unescaped::Incomplete => return None,
+                    Unescaped::Truncated(idx) => {
+                        self.lose_sync();
+                        self.discard(data, idx);
+                    }
+                    Unescaped::Frame(frame, size) => {
+                        if Self::frame_start(&data[size..]) == Some(false) {
+                            self.lose_sync();
+                            continue;
+                        }
+                        data.drain(..size);
+                        return Some(frame);
+                    }
+                }
+                continue;
+            }
+
+            // Look for the start of a frame
+            let Some(start) = data
+                .windows(2)
+                .position(|w| w[0] == 0x1A && frame_size(w[1]).is_some())
+            else {
+                // Keep a trailing 0x1A, which may start a frame
+                let keep = usize::from(data.last() == Some(&0x1A));
+                self.discard(data, data.len() - keep);
+                return None;
+            };
+            self.discard(data, start);
+            match unescape(data) {
+                Unescaped::Incomplete => return None,
+                Unescaped::Truncated(idx) => self.discard(data, idx),
+                Unescaped::Frame(frame, size) => {
+                    let valid = match valid_crc(&frame) {
+                        Some(valid) => Some(valid),
+                        None => Self::frame_start(&data[size..]),
+                    };
+                    match valid {
+                        None => return None,
+                        Some(false) => self.discard(data, 1),
+                        Some(true) => {
+                            self.synced = true;
+                            data.drain(..size);
+                            return Some(frame);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The type of a Beast frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// A Mode A/C reply (2 bytes)
+    ModeAc,
+    /// A short Mode S frame (7 bytes)
+    ModeSShort,
+    /// A long Mode S frame (14 bytes)
+    ModeSLong,
+    /// A receiver status or position (Radarcape), see [`ReceiverStatus`]
+    /// and [`receiver_position`]
+    ///
+    /// [`ReceiverStatus`]: crate::source::beast::ReceiverStatus
+    /// [`receiver_position`]: crate::source::beast::receiver_position
+    Status,
+}
+
+/**
+ * A Beast frame, with escaped 0x1A bytes collapsed.
+ *
+ * The bytes are the escape byte (0x1A), the type byte, a 6-byte timestamp,
+ * a 1-byte signal level and the payload. Frames in the AVR format are
+ * converted to this layout.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame(Vec<u8>);
+
+impl Frame {
+    pub fn kind(&self) -> FrameKind {
+        match self.0[1] {
+            0x31 => FrameKind::ModeAc,
+            0x32 => FrameKind::ModeSShort,
+            0x33 => FrameKind::ModeSLong,
+            _ => FrameKind::Status,
+        }
+    }
+
+    /// The 48-bit timestamp of the receiver, 0 if not available
+    pub fn timestamp(&self) -> u64 {
+        let mut array = [0u8; 8];
+        array[2..8].copy_from_slice(&self.0[2..8]);
+        u64::from_be_bytes(array)
+    }
+
+    /// The signal level, None if not available
+    pub fn signal(&self) -> Option<u8> {
+        Some(self.0[8]).filter(|&signal| signal != 0xff)
+    }
+
+    /// The Mode S (or Mode A/C) frame, or the status data
+    pub fn payload(&self) -> &[u8] {
+        &self.0[9..]
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/**
+ * An incremental parser of Beast frames, without any IO.
+ *
+ * If the format is not given, it is detected from the first bytes pushed.
+ * Frames in the AVR format are converted to Beast frames. The SBS format is
+ * detected but not supported: no frame is returned. Incomplete frames are
+ * kept until more bytes are pushed.
+ *
+ * Mode S frames rejected by the [`FrameFilter`] (if any) are dropped as soon
+ * as their length is known, before any CRC computation.
+ */
+#[derive(Debug)]
+pub struct BeastParser {
+    buffer: Vec<u8>,
+    format: Option<Format>,
+    framer: Framer,
+    filter: FrameFilter,
+}
+
+impl Default for BeastParser {
+    fn default() -> Self {
+        BeastParser::new(None, FramingStats::default())
+    }
+}
+
+impl BeastParser {
+    /// A parser of a given format (if known), updating the framing statistics
+    pub fn new(format: Option<Format>, stats: FramingStats) -> Self {
+        BeastParser {
+            buffer: Vec::new(),
+            format,
+            framer: Framer::new(stats),
+            filter: FrameFilter::default(),
+        }
+    }
+
+    /// Drop the Mode S frames rejected by the filter
+    pub fn with_filter(mut self, filter: FrameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// The format of the stream, once detected
+    pub fn format(&self) -> Option<Format> {
+        self.format
+    }
+
+    /// Number of bytes kept until more bytes are pushed, e.g. the beginning
+    /// of a frame
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Push bytes, and iterate the frames completed by these bytes
+    pub fn push(&mut self, data: &[u8]) -> impl Iterator<Item = Frame> + '_ {
+        self.buffer.extend_from_slice(data);
+        std::iter::from_fn(move || self.next_frame())
+    }
+
+    fn next_frame(&mut self) -> Option<Frame> {
+        loop {
+            let frame = self.next_any_frame()?;
+            let mode_s = matches!(
+                frame.kind(),
+                FrameKind::ModeSShort | FrameKind::ModeSLong
+            );
+            if !mode_s || self.filter.accept(frame.payload()) {
+                return Some(frame);
+            }
+        }
+    }
+
+    fn next_any_frame(&mut self) -> Option<Frame> {
+        if self.format.is_none() {
+            self.format = Format::detect(&self.buffer);
+            match self.format {
+                Some(format) => info!("Detected {} format", format),
+                None if Format::detection_failed(&self.buffer) => {
+                    warn!("Unknown format, trying Beast binary");
+                    self.format = Some(Format::Beast);
+                }
+                None => return None,
+            }
+        }
+        let frame = match self.format? {
+            Format::Avr => avr::next_frame(&mut self.buffer),
+            Format::Beast => self.framer.next_frame(&mut self.buffer),
+            Format::Sbs => {
+                self.buffer.clear();
+                None
+            }
+        };
+        frame.map(Frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hexlit::hex;
+
+    #[test]
+    fn test_next_frame() {
+        // garbage, then a Mode S long frame with an escaped 0x1A in the
+        // timestamp, then the beginning of another frame
+        let frame = hex!("1a33001a1a3f9a1c42c08d406b902015a678d4d220aa4bda");
+        let mut data = vec![0x00, 0x42];
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(&hex!("1a3300"));
+
+        let mut framer = Framer::new(FramingStats::default());
+        let msg = framer.next_frame(&mut data).unwrap();
+        assert_eq!(msg.len(), 23);
+        assert_eq!(msg[..4], hex!("1a33001a"));
+        assert_eq!(msg[9..], hex!("8d406b902015a678d4d220aa4bda"));
+        assert_eq!(data, hex!("1a3300"));
+        assert_eq!(framer.next_frame(&mut data), None);
+    }
+
+    #[test]
+    fn test_resync() {
+        let long = hex!("1a33001a1a3f9a1c42c08d406b902015a678d4d220aa4bda");
+        let short = hex!("1a32001a1a3f9a1c42c05d4840d6d6b9e5");
+        let stats = FramingStats::default();
+        let mut framer = Framer::new(stats.clone());
+
+        // The stream starts mid-frame, then a short frame is cut by a long one
+        let mut data = long[12..].to_vec();
+        data.extend_from_slice(&long);
+        data.extend_from_slice(&short);
+        data.extend_from_slice(&short[..12]);
+        data.extend_from_slice(&long);
+        data.extend_from_slice(&hex!("1a33"));
+
+        let mut frames = Vec::new();
+        while let Some(msg) = framer.next_frame(&mut data) {
+            frames.push(msg[9..].to_vec());
+        }
+        assert_eq!(
+            frames,
+            vec![
+                hex!("8d406b902015a678d4d220aa4bda").to_vec(),
+                hex!("5d4840d6d6b9e5").to_vec(),
+                hex!("8d406b902015a678d4d220aa4bda").to_vec(),
+            ]
+        );
+        assert_eq!(data, hex!("1a33"));
+        assert_eq!(stats.resyncs(), 2);
+        assert_eq!(stats.discarded(), (long.len() - 12 + 12) as u64);
+
+        // A frame which is not followed by another one is dropped, and the
+        // synchronization is only recovered with a valid CRC
+        let mut data = short.to_vec();
+        data.extend_from_slice(&hex!("00421a33001a1a3f9a1c42c08d406b902015"));
+        assert_eq!(framer.next_frame(&mut data), None);
+        assert_eq!(stats.resyncs(), 3);
+        let mut corrupted = long.to_vec();
+        corrupted[20] ^= 0x01;
+        data.extend_from_slice(&corrupted);
+        data.extend_from_slice(&long);
+        let msg = framer.next_frame(&mut data).unwrap();
+        assert_eq!(msg, hex!("1a33001a3f9a1c42c08d406b902015a678d4d220aa4bda"));
+        assert!(data.is_empty());
+        assert_eq!(stats.resyncs(), 3);
+    }
+
+    #[test]
+    fn test_parser() {
+        let long = hex!("1a33001a1a3f9a1c42c08d406b902015a678d4d220aa4bda");
+        let status = hex!("1a3400000000000000131a1a200000000000000000000000");
+        let mut data = long.to_vec();
+        data.extend_from_slice(&status);
+        data.extend_from_slice(&long);
+
+        // Byte by byte
+        let mut parser = BeastParser::default();
+        let frames: Vec<Frame> = data
+            .iter()
+            .flat_map(|b| parser.push(&[*b]).collect::<Vec<_>>())
+            .collect();
+        assert_eq!(parser.format(), Some(Format::Beast));
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].kind(), FrameKind::ModeSLong);
+        assert_eq!(frames[0].signal(), Some(0xc0));
+        assert_eq!(frames[0].payload(), hex!("8d406b902015a678d4d220aa4bda"));
+        assert_eq!(frames[1].kind(), FrameKind::Status);
+        assert_eq!(frames[2], frames[0]);
+        assert_eq!(parser.push(&hex!("1a32")).count(), 0);
+
+        let mut parser = BeastParser::default();
+        let frames: Vec<Frame> = parser
+            .push(b"*8D406B902015A678D4D220AA4BDA;\n*5D4D20237A55A6;\n")
+            .collect();
+        assert_eq!(parser.format(), Some(Format::Avr));
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].kind(), FrameKind::ModeSShort);
+        assert_eq!((frames[1].timestamp(), frames[1].signal()), (0, None));
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = FrameFilter {
+            df_exclude: Some(vec![11]),
+            ..FrameFilter::default()
+        };
+        let mut parser = BeastParser::default().with_filter(filter);
+        let frames: Vec<Frame> = parser
+            .push(b"*5D4D20237A55A6;\n*8D406B902015A678D4D220AA4BDA;\n")
+            .collect();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload(), hex!("8d406b902015a678d4d220aa4bda"));
+    }
+}
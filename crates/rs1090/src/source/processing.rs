@@ -0,0 +1,240 @@
+/*!
+ * Processing of IQ samples on a dedicated thread.
+ *
+ * On small single board computers, the demodulation of IQ samples competes
+ * for the CPU with the tasks of the async runtime (decoding, outputs): when
+ * samples are not read fast enough, the driver drops them (overflows). The
+ * processing can then be moved to a dedicated OS thread, optionally pinned
+ * to a CPU core and with a real-time scheduling priority (when permitted,
+ * e.g. with the `CAP_SYS_NICE` capability).
+ *
+ * The [`SampleStats`] counters help checking the effect of these settings.
+ */
+
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use tracing::{info, warn};
+
+/// The priority of the processing thread with the real-time policy (1-99)
+#[cfg(target_os = "linux")]
+const REALTIME_PRIORITY: i32 = 50;
+
+/// Configuration of the dedicated processing thread
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThreadSettings {
+    /// The CPU core the thread is pinned to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<usize>,
+    /// Run the thread with a real-time scheduling policy (SCHED_FIFO)
+    #[serde(default)]
+    pub realtime: bool,
+}
+
+impl ThreadSettings {
+    /**
+     * Start a named thread with these settings.
+     *
+     * Settings which cannot be applied (e.g. for lack of permissions) are
+     * reported in the logs, and the thread runs anyway.
+     */
+    pub fn spawn<F, T>(&self, name: &str, f: F) -> io::Result<JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let settings = self.clone();
+        let name = name.to_string();
+        thread::Builder::new().name(name.clone()).spawn(move || {
+            if let Some(cpu) = settings.cpu {
+                match pin(cpu) {
+                    Ok(()) => info!("{} thread pinned to CPU {}", name, cpu),
+                    Err(e) => {
+                        warn!("Cannot pin {} to CPU {}: {}", name, cpu, e)
+                    }
+                }
+            }
+            if settings.realtime {
+                match realtime() {
+                    Ok(()) => info!("{} thread with real-time priority", name),
+                    Err(e) => {
+                        warn!("No real-time priority for {}: {}", name, e)
+                    }
+                }
+            }
+            f()
+        })
+    }
+}
+
+/// Pin the current thread to a CPU core
+#[cfg(target_os = "linux")]
+pub fn pin(cpu: usize) -> io::Result<()> {
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if cpu >= 8 * size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid CPU core",
+        ));
+    }
+    // SAFETY: the set is a plain bitmask, large enough for the core index
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, size, &set)
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Pin the current thread to a CPU core
+#[cfg(not(target_os = "linux"))]
+pub fn pin(_cpu: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "CPU affinity is only supported on Linux",
+    ))
+}
+
+/// Run the current thread with a real-time scheduling policy
+#[cfg(target_os = "linux")]
+pub fn realtime() -> io::Result<()> {
+    let param = libc::sched_param {
+        sched_priority: REALTIME_PRIORITY,
+    };
+    // SAFETY: the parameter outlives the call, on the current thread
+    let result = unsafe {
+        libc::pthread_setschedparam(
+            libc::pthread_self(),
+            libc::SCHED_FIFO,
+            &param,
+        )
+    };
+    match result {
+        0 => Ok(()),
+        code => Err(io::Error::from_raw_os_error(code)),
+    }
+}
+
+/// Run the current thread with a real-time scheduling policy
+#[cfg(not(target_os = "linux"))]
+pub fn realtime() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "real-time priority is only supported on Linux",
+    ))
+}
+
+/**
+ * Statistics about the IQ samples read from a SDR device.
+ *
+ * Buffers and samples are counted as they are read; overflows mean that
+ * samples were dropped because the demodulation did not keep up with the
+ * sample rate, e.g. on a busy CPU.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct SampleStats(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    buffers: AtomicU64,
+    samples: AtomicU64,
+    overflows: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl SampleStats {
+    /// Number of buffers of samples read from the device
+    pub fn buffers(&self) -> u64 {
+        self.0.buffers.load(Ordering::Relaxed)
+    }
+
+    /// Number of samples read from the device
+    pub fn samples(&self) -> u64 {
+        self.0.samples.load(Ordering::Relaxed)
+    }
+
+    /// Number of times the device dropped samples not read fast enough
+    pub fn overflows(&self) -> u64 {
+        self.0.overflows.load(Ordering::Relaxed)
+    }
+
+    /// Number of other read errors (timeouts, corrupted data)
+    pub fn errors(&self) -> u64 {
+        self.0.errors.load(Ordering::Relaxed)
+    }
+
+    /// True if nothing was read, e.g. for sources other than SDR devices
+    pub fn is_empty(&self) -> bool {
+        self.buffers() == 0 && self.overflows() == 0 && self.errors() == 0
+    }
+
+    /// Count a buffer of samples read from the device
+    pub fn record_buffer(&self, len: usize) {
+        self.0.buffers.fetch_add(1, Ordering::Relaxed);
+        self.0.samples.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Count a read error, or an overflow
+    pub fn record_error(&self, overflow: bool) {
+        let counter = match overflow {
+            true => &self.0.overflows,
+            false => &self.0.errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Serialize for SampleStats {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("SampleStats", 4)?;
+        state.serialize_field("buffers", &self.buffers())?;
+        state.serialize_field("samples", &self.samples())?;
+        state.serialize_field("overflows", &self.overflows())?;
+        state.serialize_field("errors", &self.errors())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_processing() {
+        let stats = SampleStats::default();
+        assert!(stats.is_empty());
+
+        let settings = ThreadSettings {
+            cpu: Some(0),
+            realtime: false,
+        };
+        let shared = stats.clone();
+        let handle = settings
+            .spawn("test", move || {
+                shared.record_buffer(16384);
+                shared.record_buffer(16384);
+                shared.record_error(true);
+                thread::current().name().map(str::to_string)
+            })
+            .unwrap();
+        assert_eq!(handle.join().unwrap().as_deref(), Some("test"));
+        assert_eq!(stats.samples(), 32768);
+        assert_eq!(stats.overflows(), 1);
+        assert_eq!(stats.errors(), 0);
+        assert_eq!(
+            serde_json::to_string(&stats).unwrap(),
+            r#"{"buffers":2,"samples":32768,"overflows":1,"errors":0}"#
+        );
+
+        assert!(pin(1 << 20).is_err());
+    }
+}
@@ -1,14 +1,21 @@
 use std::sync::Mutex;
 
 use num_complex::Complex;
-use soapysdr::{configure_logging, Args, Device, Direction};
+use soapysdr::{
+    configure_logging, Args, Device, Direction, ErrorCode, RxStream,
+};
 use tokio::sync::mpsc;
 
+use super::dsp;
+use super::filter::FrameFilter;
+use super::iqcapture::{CaptureSettings, CaptureWriter, Snippet};
+use super::processing::{SampleStats, ThreadSettings};
+use super::sampleclock::SampleClock;
 use crate::decode::crc::modes_checksum;
-use crate::decode::time::now_in_ns;
+use crate::decode::time::clock_error;
 use crate::prelude::*;
 use std::fmt::{self, Display, Formatter};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 const DIRECTION: Direction = Direction::Rx;
 const MODES_FREQ: f64 = 1.09e9;
@@ -20,11 +27,16 @@ const MODES_SHORT_MSG_BYTES: usize = 7;
 const MODES_MAG_BUF_SAMPLES: usize = 131_072;
 const TRAILING_SAMPLES: usize = 326;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn receiver<A: Into<Args> + fmt::Display + std::marker::Copy>(
     tx: mpsc::Sender<TimedMessage>,
     args: Option<A>,
     serial: u64,
     name: Option<String>,
+    capture: Option<CaptureSettings>,
+    thread: Option<ThreadSettings>,
+    filter: FrameFilter,
+    stats: SampleStats,
 ) {
     match args {
         Some(args) => {
@@ -63,60 +75,144 @@ pub async fn receiver<A: Into<Args> + fmt::Display + std::marker::Copy>(
         .set_gain_element(DIRECTION, channel, "TUNER", RTLSDR_GAIN)
         .unwrap();
 
-    let mut stream = device.rx_stream::<Complex<i16>>(&[channel]).unwrap();
+    // Raw IQ samples around weak frames, for debugging the demodulator
+    let capture = capture.and_then(|settings| {
+        match CaptureWriter::create(&settings, RTLSDR_RATE as u32) {
+            Ok(writer) => {
+                info!("Capturing IQ samples to {}", settings.path.display());
+                Some(writer)
+            }
+            Err(e) => {
+                error!("{}: {}", settings.path.display(), e);
+                None
+            }
+        }
+    });
 
-    let mut buf = vec![Complex::new(0, 0); stream.mtu().unwrap()];
+    let mut stream = device.rx_stream::<Complex<i16>>(&[channel]).unwrap();
+    let buf = vec![Complex::new(0, 0); stream.mtu().unwrap()];
     stream.activate(None).unwrap();
 
-    // Spawn a thread to send messages
-    'receive: loop {
-        match stream.read(&mut [&mut buf], 5_000_000) {
-            Ok(len) => {
-                let buf = &buf[..len];
-                let outbuf = magnitude(buf);
-                let resulting_data = demodulate2400(&outbuf).unwrap();
-                for data in resulting_data {
-                    let system_timestamp = now_in_ns() as f64 * 1e-9;
-                    let metadata = SensorMetadata {
-                        system_timestamp,
-                        gnss_timestamp: None,
-                        nanoseconds: None,
-                        rssi: Some(10. * data.signal_level.log10() as f32),
-                        serial,
-                        name: name.clone(),
-                    };
-                    let tmsg = TimedMessage {
-                        timestamp: system_timestamp,
-                        frame: data.msg.to_vec(),
-                        message: None,
-                        metadata: vec![metadata],
-                        decode_time: None,
-                    };
-                    if tx.send(tmsg).await.is_err() {
-                        break 'receive;
+    let mut samples = Samples {
+        stream,
+        buf,
+        capture,
+        clock: SampleClock::new(RTLSDR_RATE),
+        serial,
+        name,
+        filter,
+        stats,
+    };
+    match thread {
+        // Blocking reads and demodulation away from the async runtime
+        Some(settings) => {
+            let handle = settings.spawn("rtlsdr", move || loop {
+                for tmsg in samples.read() {
+                    if tx.blocking_send(tmsg).is_err() {
+                        return;
                     }
                 }
+            });
+            match handle {
+                Ok(handle) => {
+                    let _ = tokio::task::spawn_blocking(move || handle.join())
+                        .await;
+                }
+                Err(e) => error!("Cannot start the rtlsdr thread: {}", e),
+            }
+        }
+        None => loop {
+            for tmsg in samples.read() {
+                if tx.send(tmsg).await.is_err() {
+                    return;
+                }
             }
+        },
+    }
+}
+
+/// The stream of IQ samples of a device, demodulated buffer by buffer
+struct Samples {
+    stream: RxStream<Complex<i16>>,
+    buf: Vec<Complex<i16>>,
+    capture: Option<CaptureWriter>,
+    clock: SampleClock,
+    serial: u64,
+    name: Option<String>,
+    filter: FrameFilter,
+    stats: SampleStats,
+}
+
+impl Samples {
+    /// Read and demodulate the next buffer of samples (blocking)
+    fn read(&mut self) -> Vec<TimedMessage> {
+        let len = match self.stream.read(&mut [&mut self.buf], 5_000_000) {
+            Ok(len) => len,
             Err(e) => {
+                self.stats.record_error(e.code == ErrorCode::Overflow);
                 error!("SoapySDR read error: {}", e);
+                // Samples were dropped
+                self.clock.resync();
+                return vec![];
+            }
+        };
+        let arrival = Timestamp::now();
+        self.clock.buffer(len, arrival);
+        self.stats.record_buffer(len);
+        let buf = &self.buf[..len];
+        let outbuf = magnitude(buf);
+        let resulting_data = demodulate2400(&outbuf, &self.filter).unwrap();
+        let mut messages = Vec::with_capacity(resulting_data.len());
+        for data in resulting_data {
+            // Timestamps from the count of samples, rather than the arrival
+            // time of the whole buffer
+            let offset = data.offset.saturating_sub(TRAILING_SAMPLES);
+            let sample = self.clock.sample(offset);
+            let system_timestamp =
+                self.clock.timestamp(sample).unwrap_or(arrival);
+            if let Some(writer) = self.capture.as_mut() {
+                if data.snr < writer.snr as f64 {
+                    let snippet = Snippet::new(
+                        system_timestamp,
+                        &data.msg,
+                        data.snr as f32,
+                        buf,
+                        data.offset.saturating_sub(TRAILING_SAMPLES),
+                    );
+                    if let Err(e) = writer.write(&snippet) {
+                        warn!("IQ capture stopped: {}", e);
+                        self.capture = None;
+                    }
+                }
             }
+            let metadata = SensorMetadata {
+                system_timestamp,
+                gnss_timestamp: None,
+                nanoseconds: Some(self.clock.counter(sample)),
+                rssi: Some(10. * data.signal_level.log10() as f32),
+                serial: self.serial,
+                name: self.name.clone(),
+                time_source: Some(TimeSource::Counter),
+                clock_error: clock_error(),
+                timestamp_error: self.clock.error(),
+            };
+            messages.push(TimedMessage {
+                timestamp: system_timestamp,
+                frame: data.msg.to_vec(),
+                message: None,
+                metadata: vec![metadata],
+                decode_time: None,
+            });
         }
+        messages
     }
 }
 
 pub fn magnitude(data: &[Complex<i16>]) -> MagnitudeBuffer {
     let mut outbuf = MagnitudeBuffer::default();
-    for b in data {
-        let i = b.im;
-        let q = b.re;
-
-        let fi = f32::from(i) / (1 << 15) as f32;
-        let fq = f32::from(q) / (1 << 15) as f32;
-
-        let mag_sqr = fi.mul_add(fi, fq * fq);
-        let mag = f32::sqrt(mag_sqr);
-        outbuf.push(mag.mul_add(f32::from(u16::MAX), 0.5) as u16);
-    }
+    let start = TRAILING_SAMPLES;
+    dsp::magnitude(data, &mut outbuf.data[start..start + data.len()]);
+    outbuf.length = data.len();
     outbuf
 }
 
@@ -449,22 +545,30 @@ pub struct ModeSMessage {
     signal_level: f64,
     /// Scoring from scoreModesMessage, if used
     score: i32,
+    /// Index of the preamble in the magnitude buffer
+    offset: usize,
+    /// Signal to noise ratio (in dB) estimated on the preamble
+    snr: f64,
 }
 
+/**
+ * Demodulate the Mode S frames of a buffer of magnitudes.
+ *
+ * Frames rejected by the filter are dropped as soon as their downlink format
+ * (and typecode) are sliced, before their CRC is checked.
+ */
 pub fn demodulate2400(
     mag: &MagnitudeBuffer,
+    filter: &FrameFilter,
 ) -> Result<Vec<ModeSMessage>, &'static str> {
     let mut results = vec![];
 
     let data = &mag.data;
 
-    let mut skip_count: usize = 0;
-    'jloop: for j in 0..mag.length {
-        if skip_count > 0 {
-            skip_count -= 1;
-            continue 'jloop;
-        }
+    let mut candidates = Vec::new();
+    dsp::preamble_candidates(data, mag.length, &mut candidates);
 
+    'jloop: for j in candidates {
         if let Some((high, base_signal, base_noise)) =
             check_preamble(&data[j..j + 14])
         {
@@ -493,6 +597,8 @@ pub fn demodulate2400(
                 msg: [0_u8; MODES_LONG_MSG_BYTES],
                 signal_level: 0.,
                 score: -2,
+                offset: j,
+                snr: 20. * (base_signal as f64 / base_noise as f64).log10(),
             };
 
             let mut msg: [u8; MODES_LONG_MSG_BYTES] =
@@ -527,6 +633,9 @@ pub fn demodulate2400(
                     phase = starting_phase.next_start();
                 }
 
+                if !filter.accept(&msg) {
+                    continue;
+                }
                 let score = score_modes_message(&msg);
 
                 if score > bestmsg.score {
@@ -0,0 +1,188 @@
+/*!
+ * Timestamps of SDR samples, from the number of samples read since the
+ * beginning of the stream.
+ *
+ * The arrival time of a buffer of samples is only known to a few
+ * milliseconds (USB transfers, scheduling), and is the same for all the
+ * frames of the buffer. Counting samples instead gives the relative timing of
+ * frames to the sample period (about 400 ns at 2.4 MHz), as a 12 MHz counter
+ * (the one of dump1090 and Beast receivers), suitable for multilateration.
+ *
+ * The absolute time of a sample follows the counter, disciplined against the
+ * arrival time of the buffers: a second order loop corrects both the offset
+ * and the frequency error of the crystal of the dongle. The estimated error
+ * of these timestamps comes with them.
+ */
+
+use super::super::decode::time::Timestamp;
+
+/// The frequency of the counter, in Hz (as in the Beast format)
+const COUNTER_FREQ: f64 = 12e6;
+/// Counters are on 48 bits in the Beast format
+const COUNTER_MASK: u64 = (1 << 48) - 1;
+/// Correction of the offset at each buffer (proportional gain)
+const OFFSET_GAIN: f64 = 0.01;
+/// Correction of the sample period at each buffer (integral gain)
+const PERIOD_GAIN: f64 = 1e-4;
+/// The sample period may differ from the nominal one by this (relative)
+/// amount at most, i.e. 200 ppm
+const MAX_DRIFT: f64 = 2e-4;
+
+/**
+ * A clock counting the samples of a stream.
+ *
+ * Call [`SampleClock::buffer`] for each buffer of samples read, then
+ * [`SampleClock::timestamp`] and [`SampleClock::counter`] for the samples of
+ * this buffer.
+ */
+#[derive(Debug)]
+pub struct SampleClock {
+    /// The nominal sample rate, in Hz
+    rate: f64,
+    /// The index of the first sample of the current buffer
+    start: u64,
+    /// The index of the first sample after the current buffer
+    end: u64,
+    /// The index of a sample and its estimated time (in ns since epoch)
+    anchor: Option<(u64, f64)>,
+    /// The estimated sample period, in ns
+    period: f64,
+    /// The estimated error of timestamps, in ns
+    error: f64,
+    /// The arrival time of the previous buffer, to count dropped samples
+    previous: Option<Timestamp>,
+}
+
+impl SampleClock {
+    pub fn new(rate: f64) -> Self {
+        SampleClock {
+            rate,
+            start: 0,
+            end: 0,
+            anchor: None,
+            period: 1e9 / rate,
+            error: 0.,
+            previous: None,
+        }
+    }
+
+    /**
+     * Count a buffer of `len` samples, the last of them received at
+     * `arrival` (system time), and discipline the clock.
+     */
+    pub fn buffer(&mut self, len: usize, arrival: Timestamp) {
+        let nanos = arrival.as_nanos() as f64;
+        let len = len as u64;
+        let duration = len as f64 * self.period;
+        if let (None, Some(previous)) = (self.anchor, self.previous) {
+            // Samples dropped since the previous buffer (e.g. overflows)
+            let elapsed = (arrival - previous).as_nanos() as f64;
+            let dropped = (elapsed - duration) / self.period;
+            self.end += dropped.max(0.).round() as u64;
+        }
+        self.start = self.end;
+        self.end += len;
+        self.previous = Some(arrival);
+
+        let Some((sample, time)) = self.anchor else {
+            // The buffer may have waited for up to its own duration
+            self.anchor = Some((self.end, nanos));
+            self.error = duration;
+            return;
+        };
+        let elapsed = (self.end - sample) as f64;
+        let predicted = time + elapsed * self.period;
+        let residual = nanos - predicted;
+        self.anchor = Some((self.end, predicted + OFFSET_GAIN * residual));
+        let nominal = 1e9 / self.rate;
+        self.period = (self.period + PERIOD_GAIN * residual / elapsed)
+            .clamp(nominal * (1. - MAX_DRIFT), nominal * (1. + MAX_DRIFT));
+        // A slowly decreasing peak of the residuals
+        self.error = residual.abs().max(self.error * (1. - OFFSET_GAIN));
+    }
+
+    /**
+     * Restart the discipline, e.g. after samples were dropped: the counter
+     * goes on, with the number of samples estimated from the arrival time of
+     * the next buffer.
+     */
+    pub fn resync(&mut self) {
+        self.anchor = None;
+    }
+
+    /// The index of a sample of the current buffer (from the first one)
+    pub fn sample(&self, offset: usize) -> u64 {
+        self.start + offset as u64
+    }
+
+    /// The estimated time of a sample
+    pub fn timestamp(&self, sample: u64) -> Option<Timestamp> {
+        let (anchor, time) = self.anchor?;
+        let nanos = time + (sample as f64 - anchor as f64) * self.period;
+        Some(Timestamp::from_nanos(nanos.round() as i64))
+    }
+
+    /// The value of the 12 MHz (48-bit) counter for a sample
+    pub fn counter(&self, sample: u64) -> u64 {
+        let ticks = sample as u128 * COUNTER_FREQ as u128 / self.rate as u128;
+        ticks as u64 & COUNTER_MASK
+    }
+
+    /// The estimated error of timestamps, in seconds
+    pub fn error(&self) -> Option<f64> {
+        self.anchor.map(|_| self.error * 1e-9)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f64 = 2.4e6;
+    const LEN: usize = 131_072;
+
+    #[test]
+    fn test_sample_clock() {
+        let mut clock = SampleClock::new(RATE);
+        let origin = Timestamp::from_secs(1_700_000_000);
+        assert_eq!(clock.timestamp(0), None);
+        assert_eq!(clock.error(), None);
+
+        // A dongle running 50 ppm fast, buffers delayed by 1 to 3 ms
+        let period = 1e9 / RATE / (1. + 50e-6);
+        let mut end = 0;
+        for i in 0..2000_u64 {
+            end += LEN as u64;
+            let jitter = i.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 43;
+            let latency = 1_000_000 + jitter as i64;
+            let nanos = (end as f64 * period).round() as i64 + latency;
+            clock.buffer(LEN, Timestamp::from_nanos(origin.as_nanos() + nanos));
+        }
+        assert_eq!(clock.sample(0), end - LEN as u64);
+
+        // The frequency error is corrected
+        let drift = clock.period * RATE / 1e9 - 1.;
+        assert!((drift + 50e-6).abs() < 5e-6, "drift: {drift}");
+        // Timestamps are within a few milliseconds (the latency)
+        let sample = clock.sample(1000);
+        let time = clock.timestamp(sample).unwrap();
+        let expected = origin.as_nanos() + (sample as f64 * period) as i64;
+        let offset = (time.as_nanos() - expected) as f64 * 1e-9;
+        assert!((0. ..4e-3).contains(&offset), "offset: {offset}");
+        let error = clock.error().unwrap();
+        assert!(error > 5e-4 && error < 4e-3, "error: {error}");
+
+        // 12 MHz counter
+        assert_eq!(clock.counter(2_400_000), 12_000_000);
+        assert_eq!(clock.counter(0), 0);
+
+        // Samples dropped: the counter keeps track of time
+        clock.resync();
+        let start = clock.sample(LEN);
+        end += 10 * LEN as u64;
+        let nanos = (end as f64 * period).round() as i64;
+        clock.buffer(LEN, Timestamp::from_nanos(origin.as_nanos() + nanos));
+        let skipped = clock.sample(0) - start;
+        assert!(skipped.abs_diff(9 * LEN as u64) < 10_000, "{skipped}");
+    }
+}
@@ -0,0 +1,175 @@
+/*!
+ * Settings of sensors, kept between runs.
+ *
+ * Sensors are identified by a serial number, computed from the address of
+ * their source (see [`serial`]) unless they come with their own (e.g. SeRo
+ * Systems sensors). Settings which cannot be found in the data (reference
+ * position, altitude, alias and RSSI calibration) may be stored in a small
+ * JSON file keyed by serial number, so that they need not be configured
+ * again at each run.
+ */
+
+use super::super::decode::cpr::Position;
+use serde::{Deserialize, Serialize};
+use siphasher::sip::SipHasher13;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::Hasher;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/**
+ * The serial number of a sensor, from the address of its source.
+ *
+ * The hash is SipHash-1-3 with zero keys over the bytes of the address
+ * followed by 0xff. This is what the standard library computed when jet1090
+ * first used it, but the standard library does not guarantee its hash across
+ * Rust releases: it is specified here so that serial numbers in archived
+ * data and in settings files remain valid.
+ */
+pub fn serial(address: &str) -> u64 {
+    let mut hasher = SipHasher13::new_with_keys(0, 0);
+    hasher.write(address.as_bytes());
+    hasher.write_u8(0xff);
+    hasher.finish()
+}
+
+/// The settings of a sensor, all optional
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SensorSettings {
+    /// An alias to label the sensor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The position of the sensor, to decode surface positions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<Position>,
+    /// The altitude of the sensor (in m, WGS84 height)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    /// An offset (in dB) added to the RSSI of the messages received by the
+    /// sensor, to compare the signal strength between sensors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rssi_offset: Option<f32>,
+}
+
+impl SensorSettings {
+    /// The calibrated RSSI of a message received by the sensor
+    pub fn calibrate(&self, rssi: Option<f32>) -> Option<f32> {
+        match self.rssi_offset {
+            Some(offset) => rssi.map(|rssi| rssi + offset),
+            None => rssi,
+        }
+    }
+}
+
+/**
+ * The settings of all the sensors, stored in a JSON file (if any).
+ *
+ * The file is written again at each change, to a temporary file first so
+ * that it is never truncated.
+ */
+#[derive(Debug, Default)]
+pub struct SensorStore {
+    path: Option<PathBuf>,
+    settings: BTreeMap<u64, SensorSettings>,
+}
+
+impl SensorStore {
+    /// Read the settings saved in a previous run, if any
+    pub fn open(path: Option<PathBuf>) -> Self {
+        let settings = match &path {
+            Some(path) if path.exists() => load(path).unwrap_or_else(|e| {
+                warn!("Failed to read {}: {}", path.display(), e);
+                BTreeMap::new()
+            }),
+            _ => BTreeMap::new(),
+        };
+        SensorStore { path, settings }
+    }
+
+    pub fn get(&self, serial: u64) -> Option<&SensorSettings> {
+        self.settings.get(&serial)
+    }
+
+    /// The settings of all the sensors, by serial number
+    pub fn all(&self) -> &BTreeMap<u64, SensorSettings> {
+        &self.settings
+    }
+
+    /// Set (or replace) the settings of a sensor, and save them
+    pub fn set(
+        &mut self,
+        serial: u64,
+        settings: SensorSettings,
+    ) -> io::Result<()> {
+        self.settings.insert(serial, settings);
+        self.save()
+    }
+
+    /// Forget the settings of a sensor, and save the others
+    pub fn remove(
+        &mut self,
+        serial: u64,
+    ) -> io::Result<Option<SensorSettings>> {
+        let settings = self.settings.remove(&serial);
+        self.save()?;
+        Ok(settings)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        fs::write(&partial, serde_json::to_string_pretty(&self.settings)?)?;
+        fs::rename(&partial, path)
+    }
+}
+
+fn load(path: &Path) -> io::Result<BTreeMap<u64, SensorSettings>> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serial() {
+        assert_eq!(serial("localhost:30005"), 16038381294071733369);
+    }
+
+    #[test]
+    fn test_store() {
+        let path = std::env::temp_dir().join("rs1090_test_sensors.json");
+        let _ = fs::remove_file(&path);
+
+        let serial = serial("localhost:30005");
+        let mut store = SensorStore::open(Some(path.clone()));
+        assert!(store.get(serial).is_none());
+        let settings = SensorSettings {
+            name: Some("rooftop".to_string()),
+            reference: Some(Position {
+                latitude: 43.6,
+                longitude: 1.4,
+            }),
+            altitude: None,
+            rssi_offset: Some(-1.5),
+        };
+        store.set(serial, settings.clone()).unwrap();
+        store.set(1, SensorSettings::default()).unwrap();
+        assert_eq!(store.remove(1).unwrap(), Some(SensorSettings::default()));
+
+        let store = SensorStore::open(Some(path.clone()));
+        assert_eq!(store.all().len(), 1);
+        assert_eq!(store.get(serial), Some(&settings));
+        assert_eq!(settings.calibrate(Some(-20.)), Some(-21.5));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -3,10 +3,15 @@ mod api {
     tonic::include_proto!("serosystems.proto.v3.backend.api");
 }
 
+pub use api::{ModeSDownlinkFrame, SensorInfoResponse, SensorInformation};
+
 use api::{
-    se_ro_api_client::SeRoApiClient, ModeSDownlinkFrame,
-    ModeSDownlinkFramesRequest, SensorInfoRequest, SensorInfoResponse,
+    se_ro_api_client::SeRoApiClient, ModeSDownlinkFramesRequest,
+    SensorInfoRequest,
 };
+use async_stream::stream;
+use futures_util::pin_mut;
+use futures_util::stream::{Stream, StreamExt};
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashMap;
@@ -20,10 +25,11 @@ use tonic::{
 };
 use tracing::{error, info};
 
-use crate::decode::time::now_in_ns;
+use crate::decode::time::clock_error;
 use crate::decode::time::since_gps_week_to_since_today;
-use crate::decode::time::since_gps_week_to_unix_s;
+use crate::decode::time::since_gps_week_to_unix;
 use crate::prelude::*;
+use crate::source::filter::FrameFilter;
 
 type Result<T> =
     std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -33,6 +39,8 @@ pub struct SeroClient {
     pub token: String,
     pub df_filter: Vec<u32>,
     pub aircraft_filter: Vec<u32>,
+    #[serde(default)]
+    pub sensor_filter: Vec<u64>,
 }
 
 async fn download_file(url: &str, destination: &PathBuf) -> Result<()> {
@@ -42,51 +50,119 @@ async fn download_file(url: &str, destination: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-pub async fn receiver(sero: SeroClient, tx: mpsc::Sender<TimedMessage>) {
-    let mut stream = sero.rawstream().await.unwrap();
-    let tx_copy = tx.clone();
-    let info = &sero.info().await.unwrap();
-    let sensor_map: HashMap<u64, String> = info
-        .sensor_info
-        .iter()
-        .map(|elt| (elt.sensor.unwrap().serial, elt.alias.to_string()))
-        .collect();
-    tokio::spawn(async move {
-        while let Some(response) = stream.next().await {
-            if let Ok(msg) = response {
-                let bytes = msg.reply.as_slice();
-                let system_timestamp = now_in_ns() as f64 * 1e-9;
-                let metadata = msg
-                    .receptions
-                    .into_iter()
-                    .map(|rm| SensorMetadata {
-                        system_timestamp,
-                        gnss_timestamp: Some(since_gps_week_to_unix_s(
-                            rm.gnss_timestamp,
-                        )),
-                        nanoseconds: Some(since_gps_week_to_since_today(
-                            rm.gnss_timestamp,
-                        )),
-                        rssi: Some(rm.signal_level),
-                        serial: rm.sensor.unwrap().serial,
-                        name: sensor_map
-                            .get(&rm.sensor.unwrap().serial)
-                            .cloned(),
-                    })
-                    .collect();
-
-                let tmsg = TimedMessage {
-                    timestamp: system_timestamp,
-                    frame: bytes.to_vec(),
-                    message: None,
-                    metadata,
-                    decode_time: None,
-                };
-                if let Err(e) = tx_copy.send(tmsg).await {
+/**
+ * Stream the frames received by the Sero Systems sensors.
+ *
+ * Messages are not decoded yet, only timestamps and sensor metadata are
+ * filled. The stream ends when the connection is closed by the server.
+ *
+ * ```no_run
+ * # use futures_util::{pin_mut, StreamExt};
+ * # use rs1090::source::sero::{self, SeroClient};
+ * # async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+ * let client = SeroClient {
+ *     token: "...".to_string(),
+ *     df_filter: vec![17, 18],
+ *     aircraft_filter: vec![],
+ *     sensor_filter: vec![],
+ * };
+ * let stream = sero::stream(client).await?;
+ * pin_mut!(stream);
+ * while let Some(msg) = stream.next().await {
+ *     println!("{}", msg);
+ * }
+ * # Ok(())
+ * # }
+ * ```
+ */
+pub async fn stream(
+    client: SeroClient,
+) -> Result<impl Stream<Item = TimedMessage>> {
+    let mut sensor_map = HashMap::<u64, String>::new();
+    let info = client.sensor_info(SENSOR_INFO_PAGE_SIZE);
+    pin_mut!(info);
+    while let Some(page) = info.next().await {
+        for elt in page? {
+            if let Some(sensor) = elt.sensor {
+                sensor_map.insert(sensor.serial, elt.alias);
+            }
+        }
+    }
+    let mut frames = client.rawstream().await?;
+
+    Ok(stream! {
+        while let Some(response) = frames.next().await {
+            match response {
+                Ok(msg) => yield timed_message(msg, &sensor_map),
+                Err(e) => {
                     error!("{}", e.to_string());
+                    break;
                 }
             }
         }
+    })
+}
+
+/// Number of sensors per request when collecting sensor information
+const SENSOR_INFO_PAGE_SIZE: usize = 50;
+
+fn timed_message(
+    msg: ModeSDownlinkFrame,
+    sensor_map: &HashMap<u64, String>,
+) -> TimedMessage {
+    let system_timestamp = Timestamp::now();
+    let metadata = msg
+        .receptions
+        .into_iter()
+        .map(|rm| {
+            let serial = rm.sensor.map(|s| s.serial).unwrap_or_default();
+            SensorMetadata {
+                system_timestamp,
+                gnss_timestamp: Some(since_gps_week_to_unix(rm.gnss_timestamp)),
+                nanoseconds: Some(since_gps_week_to_since_today(
+                    rm.gnss_timestamp,
+                )),
+                rssi: Some(rm.signal_level),
+                serial,
+                name: sensor_map.get(&serial).cloned(),
+                time_source: Some(TimeSource::Gnss),
+                clock_error: clock_error(),
+                timestamp_error: None,
+            }
+        })
+        .collect();
+
+    TimedMessage {
+        timestamp: system_timestamp,
+        frame: msg.reply,
+        message: None,
+        metadata,
+        decode_time: None,
+    }
+}
+
+pub async fn receiver(
+    sero: SeroClient,
+    tx: mpsc::Sender<TimedMessage>,
+    filter: FrameFilter,
+) {
+    let stream = match stream(sero).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("Failed to connect to the Sero API: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        pin_mut!(stream);
+        while let Some(tmsg) = stream.next().await {
+            if !filter.accept(&tmsg.frame) {
+                continue;
+            }
+            if let Err(e) = tx.send(tmsg).await {
+                error!("{}", e.to_string());
+            }
+        }
     });
 }
 
@@ -129,7 +205,7 @@ impl SeroClient {
     pub async fn info(&self) -> Result<SensorInfoResponse> {
         let request = tonic::Request::new(SensorInfoRequest {
             token: self.token.clone(),
-            sensors: vec![],
+            sensors: sensors(&self.sensor_filter),
         });
         Ok(self
             .client()
@@ -139,11 +215,59 @@ impl SeroClient {
             .into_inner())
     }
 
+    /**
+     * Information about the sensors, by pages of at most `page_size`
+     * sensors.
+     *
+     * With a sensor filter, each page is a separate request. The API has no
+     * cursor for the full listing (without any sensor filter): it comes in
+     * one response, then split into pages.
+     */
+    pub fn sensor_info(
+        &self,
+        page_size: usize,
+    ) -> impl Stream<Item = Result<Vec<SensorInformation>>> + '_ {
+        let page_size = page_size.max(1);
+        stream! {
+            let mut client = match self.client().await {
+                Ok(client) => client,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let requests: Vec<&[u64]> = match self.sensor_filter.is_empty() {
+                true => vec![self.sensor_filter.as_slice()],
+                false => self.sensor_filter.chunks(page_size).collect(),
+            };
+            for serials in requests {
+                let request = tonic::Request::new(SensorInfoRequest {
+                    token: self.token.clone(),
+                    sensors: sensors(serials),
+                });
+                match client.get_sensor_info(request).await {
+                    Ok(response) => {
+                        let mut info = response.into_inner().sensor_info;
+                        while !info.is_empty() {
+                            let split = page_size.min(info.len());
+                            let rest = info.split_off(split);
+                            yield Ok(std::mem::replace(&mut info, rest));
+                        }
+                    }
+                    Err(e) => {
+                        yield Err(e.into());
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
     pub async fn rawstream(&self) -> Result<Streaming<ModeSDownlinkFrame>> {
         let request = tonic::Request::new(ModeSDownlinkFramesRequest {
             token: self.token.clone(),
             df_filter: self.df_filter.clone(),
-            sensor_filter: vec![],
+            sensor_filter: sensors(&self.sensor_filter),
             aircraft_filter: self.aircraft_filter.clone(),
         });
         Ok(self
@@ -154,3 +278,13 @@ impl SeroClient {
             .into_inner())
     }
 }
+
+fn sensors(serials: &[u64]) -> Vec<api::Sensor> {
+    serials
+        .iter()
+        .map(|&serial| api::Sensor {
+            serial,
+            ..Default::default()
+        })
+        .collect()
+}
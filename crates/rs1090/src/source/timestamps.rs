@@ -0,0 +1,271 @@
+/*!
+ * Checks on the timestamps of Beast frames.
+ *
+ * Receivers timestamp frames with a GNSS synchronized clock, or with a free
+ * running counter: in both cases, timestamps of consecutive frames from a
+ * receiver must be increasing. Misconfigured receivers (e.g. without a valid
+ * GNSS fix, or several receivers merged in a single stream) produce
+ * timestamps going backwards, or jumping away from the arrival time, which
+ * then spoil multilateration and the estimation of the time of applicability
+ * of messages.
+ *
+ * A source is flagged when violations exceed a threshold, and its timestamps
+ * may then be replaced by the system arrival time.
+ */
+
+use super::super::decode::time::{TimeSource, Timestamp};
+use super::super::decode::SensorMetadata;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Violations are counted over windows of this number of timestamped frames
+pub const WINDOW: u64 = 1000;
+/// GNSS timestamps may drift from the arrival time by this much (in ns)
+const MAX_DRIFT: i64 = 1_000_000_000;
+/// GNSS timestamps are counted in nanoseconds since midnight
+const DAY: i64 = 86_400_000_000_000;
+
+/// Configuration of the checks on the timestamps of a source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimestampSettings {
+    /// The number of violations within a window of [`WINDOW`] timestamped
+    /// frames above which the source is flagged as unreliable
+    #[serde(default = "default_threshold")]
+    pub threshold: u64,
+    /// Replace the timestamps of an unreliable source by the arrival time
+    #[serde(default)]
+    pub fallback: bool,
+}
+
+fn default_threshold() -> u64 {
+    10
+}
+
+impl Default for TimestampSettings {
+    fn default() -> Self {
+        TimestampSettings {
+            threshold: default_threshold(),
+            fallback: false,
+        }
+    }
+}
+
+/**
+ * Statistics about the timestamps of the frames received from a source.
+ *
+ * Frames with a timestamp are counted, with those going backwards or
+ * jumping away from the arrival time: the source is flagged unreliable
+ * when these exceed the threshold of the [`TimestampSettings`].
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TimestampStats(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    timestamped: AtomicU64,
+    backwards: AtomicU64,
+    jumps: AtomicU64,
+    unreliable: AtomicBool,
+}
+
+impl TimestampStats {
+    /// Number of frames received with a timestamp
+    pub fn timestamped(&self) -> u64 {
+        self.0.timestamped.load(Ordering::Relaxed)
+    }
+
+    /// Number of timestamps older than the one of the previous frame
+    pub fn backwards(&self) -> u64 {
+        self.0.backwards.load(Ordering::Relaxed)
+    }
+
+    /// Number of GNSS timestamps drifting from the arrival time by more
+    /// than a second since the previous frame
+    pub fn jumps(&self) -> u64 {
+        self.0.jumps.load(Ordering::Relaxed)
+    }
+
+    /// True if violations currently exceed the threshold
+    pub fn unreliable(&self) -> bool {
+        self.0.unreliable.load(Ordering::Relaxed)
+    }
+
+    /// True if no frame came with a timestamp (e.g. AVR or SDR sources)
+    pub fn is_empty(&self) -> bool {
+        self.timestamped() == 0
+    }
+}
+
+impl Serialize for TimestampStats {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("TimestampStats", 4)?;
+        state.serialize_field("timestamped", &self.timestamped())?;
+        state.serialize_field("backwards", &self.backwards())?;
+        state.serialize_field("jumps", &self.jumps())?;
+        state.serialize_field("unreliable", &self.unreliable())?;
+        state.end()
+    }
+}
+
+/// The checks on the timestamps of consecutive frames from a source
+#[derive(Debug, Default)]
+pub struct TimestampCheck {
+    settings: TimestampSettings,
+    stats: TimestampStats,
+    /// The raw timestamp and the arrival time of the previous frame
+    previous: Option<(u64, Timestamp)>,
+    /// Timestamped frames and violations in the current window
+    frames: u64,
+    violations: u64,
+}
+
+impl TimestampCheck {
+    pub fn new(settings: TimestampSettings, stats: TimestampStats) -> Self {
+        TimestampCheck {
+            settings,
+            stats,
+            ..Default::default()
+        }
+    }
+
+    /**
+     * Check the raw (48 bit) timestamp of a frame received at `arrival`.
+     *
+     * GNSS timestamps are in nanoseconds since midnight (seconds in the
+     * upper 18 bits, nanoseconds in the lower 30 bits): they may also be
+     * compared with the arrival time. Counter timestamps can only be checked
+     * for monotonicity.
+     *
+     * Returns false if the timestamp should be replaced by the arrival time.
+     */
+    pub fn check(&mut self, raw: u64, gnss: bool, arrival: Timestamp) -> bool {
+        let counters = &self.stats.0;
+        counters.timestamped.fetch_add(1, Ordering::Relaxed);
+        let mut violation = false;
+        if let Some((previous, previous_arrival)) = self.previous {
+            if gnss {
+                let mut elapsed = day_nanos(raw) - day_nanos(previous);
+                // Timestamps are reset at midnight
+                if elapsed < -DAY / 2 {
+                    elapsed += DAY;
+                } else if elapsed > DAY / 2 {
+                    elapsed -= DAY;
+                }
+                let expected = arrival.as_nanos() - previous_arrival.as_nanos();
+                if elapsed < 0 {
+                    counters.backwards.fetch_add(1, Ordering::Relaxed);
+                    violation = true;
+                } else if (elapsed - expected).abs() > MAX_DRIFT {
+                    counters.jumps.fetch_add(1, Ordering::Relaxed);
+                    violation = true;
+                }
+            } else if raw < previous {
+                counters.backwards.fetch_add(1, Ordering::Relaxed);
+                violation = true;
+            }
+        }
+        self.previous = Some((raw, arrival));
+
+        self.frames += 1;
+        self.violations += violation as u64;
+        let unreliable = self.stats.unreliable();
+        if self.violations > self.settings.threshold && !unreliable {
+            warn!(
+                "Unreliable timestamps: {} violations in {} frames",
+                self.violations, self.frames
+            );
+            counters.unreliable.store(true, Ordering::Relaxed);
+        }
+        if self.frames >= WINDOW {
+            if self.violations <= self.settings.threshold && unreliable {
+                info!("Timestamps are reliable again");
+                counters.unreliable.store(false, Ordering::Relaxed);
+            }
+            self.frames = 0;
+            self.violations = 0;
+        }
+
+        !(self.settings.fallback && self.stats.unreliable())
+    }
+
+    /**
+     * Check the timestamp of a received frame (if any), and fall back to the
+     * arrival time if the timestamps of the source are not reliable.
+     */
+    pub fn apply(&mut self, metadata: &mut SensorMetadata) {
+        let Some(raw) = metadata.nanoseconds else {
+            return;
+        };
+        let gnss = metadata.gnss_timestamp.is_some();
+        if !self.check(raw, gnss, metadata.system_timestamp) {
+            metadata.gnss_timestamp = None;
+            metadata.nanoseconds = None;
+            metadata.time_source = Some(TimeSource::System);
+        }
+    }
+}
+
+/// The number of nanoseconds since midnight of a raw GNSS timestamp
+fn day_nanos(raw: u64) -> i64 {
+    ((raw >> 30) * 1_000_000_000 + (raw & 0x3FFF_FFFF)) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The raw GNSS timestamp for a number of ms since midnight
+    fn raw(ms: u64) -> u64 {
+        ((ms / 1000) << 30) | ((ms % 1000) * 1_000_000)
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let stats = TimestampStats::default();
+        let settings = TimestampSettings {
+            threshold: 2,
+            fallback: true,
+        };
+        let mut check = TimestampCheck::new(settings, stats.clone());
+        let start = Timestamp::from_secs(1_700_000_000);
+        let at = |ms: i64| start + std::time::Duration::from_millis(ms as u64);
+
+        // Increasing timestamps, across midnight
+        assert!(check.check(raw(86_399_900), true, at(0)));
+        assert!(check.check(raw(86_399_950), true, at(50)));
+        assert!(check.check(raw(20), true, at(120)));
+        assert_eq!(stats.backwards() + stats.jumps(), 0);
+
+        // A step back, then a jump of 5 s
+        assert!(check.check(raw(10), true, at(130)));
+        assert!(check.check(raw(5_010), true, at(140)));
+        assert_eq!((stats.backwards(), stats.jumps()), (1, 1));
+        assert!(!stats.unreliable());
+
+        // Counter timestamps are only checked for monotonicity
+        assert!(!check.check(100, false, at(150)));
+        assert!(stats.unreliable());
+        assert!(!check.check(1_000_000_000, false, at(160)));
+
+        // A clean window makes the source reliable again
+        for i in 0..2 * WINDOW {
+            check.check(1_000_000_001 + i, false, at(170));
+        }
+        assert!(!stats.unreliable());
+        assert!(check.check(2_000_000_000, false, at(180)));
+        assert_eq!(stats.timestamped(), 2 * WINDOW + 8);
+        assert_eq!(
+            serde_json::to_string(&stats).unwrap(),
+            format!(
+                r#"{{"timestamped":{},"backwards":2,"jumps":1,"unreliable":false}}"#,
+                2 * WINDOW + 8
+            )
+        );
+    }
+}
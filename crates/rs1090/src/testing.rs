@@ -0,0 +1,8 @@
+//! Helpers shared by the tests of several modules
+
+use crate::prelude::*;
+
+/// A message received at a time (in s), decoded from its frame
+pub fn timed(frame: &[u8], secs: f64) -> TimedMessage {
+    TimedMessage::from_frame(Timestamp::from_secs_f64(secs), frame)
+}
@@ -0,0 +1,453 @@
+/*!
+ * Aggregation of decoded messages into the state vectors of aircraft.
+ *
+ * Each message only carries part of the state of an aircraft: its callsign,
+ * a position, a velocity, etc. The [`StateVectorTracker`] merges the
+ * messages of each aircraft (by ICAO 24-bit address) into a
+ * [`StateVector`] with the last value of each field, and forgets aircraft
+ * silent for longer than a configurable delay. Applications keeping their
+ * own records of aircraft may merge messages into a [`State`] only.
+ *
+ * Positions are taken as they are decoded in the messages: CPR positions
+ * must be decoded first (e.g. with [`decode_positions`]).
+ *
+ * [`decode_positions`]: crate::decode::cpr::decode_positions
+ */
+
+use crate::decode::adsb::ME;
+use crate::decode::bds::bds08::WakeVortex;
+use crate::decode::bds::bds09::AirspeedType;
+use crate::decode::bds::bds09::{AirborneVelocity, AirborneVelocitySubType};
+use crate::decode::bds::bds40::SelectedVerticalIntention;
+use crate::decode::bds::bds44::MeteorologicalRoutineAirReport;
+use crate::decode::bds::bds45::MeteorologicalHazardReport;
+use crate::decode::bds::bds50::TrackAndTurnReport;
+use crate::decode::bds::bds60::HeadingAndSpeedReport;
+use crate::decode::bds::bds65::{
+    ADSBVersionAirborne, ADSBVersionSurface, AircraftOperationStatus,
+};
+use crate::decode::commb::DF20DataSelector;
+use crate::decode::time::Timestamp;
+use crate::decode::tisb::TisBMessage;
+use crate::decode::{IdentityCode, Message, TimedMessage, DF, ICAO};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Silent aircraft are looked for at most once per this interval
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The last value of each field decoded from the messages of an aircraft
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct State {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callsign: Option<String>,
+    /// The emitter category (BDS 0,8)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<WakeVortex>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub squawk: Option<IdentityCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// True with surface positions, false with airborne messages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_ground: Option<bool>,
+    /// Barometric altitude (in ft)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<u16>,
+    /// Selected altitude, from BDS 6,2 or BDS 4,0 (in ft)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selected_altitude: Option<u16>,
+    /// Ground speed (in kts)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groundspeed: Option<f64>,
+    /// Track angle (in degrees)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track: Option<f64>,
+    /// Vertical rate (in ft/min)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_rate: Option<i16>,
+    /// Indicated airspeed (in kts)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ias: Option<u16>,
+    /// True airspeed (in kts)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tas: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mach: Option<f64>,
+    /// Magnetic heading (in degrees)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading: Option<f64>,
+    /// Roll angle (in degrees)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roll: Option<f64>,
+    /// Navigation accuracy category for position
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nacp: Option<u8>,
+    /// The last selected vertical intention (BDS 4,0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bds40: Option<SelectedVerticalIntention>,
+    /// The last meteorological routine air report (BDS 4,4)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bds44: Option<MeteorologicalRoutineAirReport>,
+    /// The last meteorological hazard report (BDS 4,5)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bds45: Option<MeteorologicalHazardReport>,
+    /// The last track and turn report (BDS 5,0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bds50: Option<TrackAndTurnReport>,
+    /// The last heading and speed report (BDS 6,0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bds60: Option<HeadingAndSpeedReport>,
+}
+
+/// The merged state of an aircraft, with the last value of each field
+#[derive(Debug, Clone, Serialize)]
+pub struct StateVector {
+    pub icao24: ICAO,
+    /// The timestamp of the first message
+    pub firstseen: Timestamp,
+    /// The timestamp of the last message
+    pub lastseen: Timestamp,
+    /// The number of messages
+    pub count: u64,
+    #[serde(flatten)]
+    pub state: State,
+}
+
+impl StateVector {
+    pub fn new(icao24: ICAO, timestamp: Timestamp) -> Self {
+        StateVector {
+            icao24,
+            firstseen: timestamp,
+            lastseen: timestamp,
+            count: 0,
+            state: State::default(),
+        }
+    }
+
+    /// Merge the content of a message (of this aircraft)
+    pub fn update(&mut self, msg: &TimedMessage) {
+        let Some(message) = &msg.message else {
+            return;
+        };
+        self.lastseen = self.lastseen.max(msg.timestamp);
+        self.count += 1;
+        self.state.update(message);
+    }
+}
+
+impl State {
+    /**
+     * Merge the content of a message (of this aircraft).
+     *
+     * Comm-B replies matching both BDS 5,0 and BDS 6,0 are ambiguous: their
+     * content is ignored, except for the other registers.
+     */
+    pub fn update(&mut self, message: &Message) {
+        match &message.df {
+            DF::ShortAirAirSurveillance { ac, .. }
+            | DF::SurveillanceAltitudeReply { ac, .. }
+            | DF::LongAirAirSurveillance { ac, .. } => {
+                self.altitude = Some(ac.0);
+            }
+            DF::SurveillanceIdentityReply { id, .. } => {
+                self.squawk = Some(*id);
+            }
+            DF::ExtendedSquitterADSB(adsb) => self.extended(&adsb.message),
+            DF::ExtendedSquitterTisB { cf, .. } => match &cf.me {
+                TisBMessage::ExtendedSquitter(me) => self.extended(me),
+                TisBMessage::Coarse(coarse) => {
+                    self.on_ground = Some(false);
+                    self.latitude = coarse.latitude.or(self.latitude);
+                    self.longitude = coarse.longitude.or(self.longitude);
+                    self.altitude = coarse.alt;
+                    self.track = coarse.track;
+                    self.groundspeed = coarse.groundspeed.map(f64::from);
+                }
+                _ => {}
+            },
+            DF::CommBAltitudeReply { ac, bds, .. } => {
+                self.altitude = Some(ac.0);
+                self.commb(bds);
+            }
+            DF::CommBIdentityReply { id, bds, .. } => {
+                self.squawk = Some(*id);
+                self.commb(&bds.clone().into());
+            }
+            _ => {}
+        }
+    }
+
+    /// Extended squitters (ADS-B, or TIS-B with the same format)
+    fn extended(&mut self, me: &ME) {
+        match me {
+            ME::BDS05(bds05) => {
+                self.on_ground = Some(false);
+                if bds05.latitude.is_some() {
+                    self.latitude = bds05.latitude;
+                    self.longitude = bds05.longitude;
+                }
+                self.altitude = bds05.alt;
+            }
+            ME::BDS06(bds06) => {
+                self.on_ground = Some(true);
+                if bds06.latitude.is_some() {
+                    self.latitude = bds06.latitude;
+                    self.longitude = bds06.longitude;
+                }
+                self.groundspeed = bds06.groundspeed.or(self.groundspeed);
+                self.track = bds06.track.or(self.track);
+                self.altitude = None;
+            }
+            ME::BDS08(bds08) => {
+                self.category = Some(bds08.wake_vortex);
+                self.callsign(&bds08.callsign);
+            }
+            ME::BDS09(bds09) => self.velocity(bds09),
+            ME::BDS61(bds61) => self.squawk = Some(bds61.squawk),
+            ME::BDS62(bds62) => {
+                self.selected_altitude = bds62.selected_altitude;
+                self.nacp = Some(bds62.nac_p);
+            }
+            ME::BDS65(AircraftOperationStatus::Airborne(status)) => {
+                match &status.version {
+                    ADSBVersionAirborne::DOC9871AppendixB(v) => {
+                        self.nacp = Some(v.nac_p)
+                    }
+                    ADSBVersionAirborne::DOC9871AppendixC(v) => {
+                        self.nacp = Some(v.nac_p)
+                    }
+                    _ => {}
+                }
+            }
+            ME::BDS65(AircraftOperationStatus::Surface(status)) => {
+                match &status.version {
+                    ADSBVersionSurface::DOC9871AppendixB(v) => {
+                        self.nacp = Some(v.nac_p)
+                    }
+                    ADSBVersionSurface::DOC9871AppendixC(v) => {
+                        self.nacp = Some(v.nac_p)
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn velocity(&mut self, bds09: &AirborneVelocity) {
+        self.on_ground = Some(false);
+        self.vertical_rate = bds09.vertical_rate;
+        match &bds09.velocity {
+            AirborneVelocitySubType::GroundSpeedDecoding(spd) => {
+                self.groundspeed = Some(spd.groundspeed);
+                self.track = Some(spd.track);
+            }
+            AirborneVelocitySubType::AirspeedSubsonic(spd) => {
+                match spd.airspeed_type {
+                    AirspeedType::IAS => self.ias = spd.airspeed,
+                    AirspeedType::TAS => self.tas = spd.airspeed,
+                }
+                self.heading = spd.heading;
+            }
+            AirborneVelocitySubType::AirspeedSupersonic(spd) => {
+                match spd.airspeed_type {
+                    AirspeedType::IAS => self.ias = spd.airspeed,
+                    AirspeedType::TAS => self.tas = spd.airspeed,
+                }
+                self.heading = spd.heading.map(f64::from);
+            }
+            _ => {}
+        }
+    }
+
+    fn commb(&mut self, bds: &DF20DataSelector) {
+        if let Some(bds20) = &bds.bds20 {
+            self.callsign(&bds20.callsign);
+        }
+        if let Some(bds40) = &bds.bds40 {
+            self.selected_altitude = bds40.selected_altitude_mcp;
+            self.bds40 = Some(bds40.clone());
+        }
+        if let Some(bds44) = &bds.bds44 {
+            self.bds44 = Some(bds44.clone());
+        }
+        if let Some(bds45) = &bds.bds45 {
+            self.bds45 = Some(bds45.clone());
+        }
+        // Ambiguous replies are ignored
+        match (&bds.bds50, &bds.bds60) {
+            (Some(bds50), None) => {
+                self.roll = bds50.roll_angle;
+                self.track = bds50.track_angle;
+                self.groundspeed = bds50.groundspeed.map(f64::from);
+                self.tas = bds50.true_airspeed;
+                self.bds50 = Some(bds50.clone());
+            }
+            (None, Some(bds60)) => {
+                self.ias = bds60.indicated_airspeed;
+                self.mach = bds60.mach_number;
+                self.heading = bds60.magnetic_heading;
+                if bds60.inertial_vertical_velocity.is_some() {
+                    self.vertical_rate = bds60.inertial_vertical_velocity;
+                }
+                self.bds60 = Some(bds60.clone());
+            }
+            _ => {}
+        }
+    }
+
+    fn callsign(&mut self, callsign: &str) {
+        if !callsign.contains('#') {
+            self.callsign = Some(callsign.to_string());
+        }
+    }
+}
+
+/**
+ * The state vectors of all the aircraft, updated message by message.
+ *
+ * Aircraft silent for longer than the expiry delay (if any) are forgotten,
+ * as messages are received: the timestamps of the messages are used, so
+ * that recorded data may be replayed as well.
+ */
+#[derive(Debug, Default)]
+pub struct StateVectorTracker {
+    /// Forget aircraft after this delay without any message
+    pub expire: Option<Duration>,
+    states: BTreeMap<ICAO, StateVector>,
+    /// The timestamp of the last removal of silent aircraft
+    pruned: Timestamp,
+}
+
+impl StateVectorTracker {
+    pub fn new(expire: Option<Duration>) -> Self {
+        StateVectorTracker {
+            expire,
+            ..StateVectorTracker::default()
+        }
+    }
+
+    /**
+     * Merge a message into the state vector of its aircraft, and return the
+     * updated state vector (messages without an address are ignored).
+     */
+    pub fn update(&mut self, msg: &TimedMessage) -> Option<&StateVector> {
+        if msg.timestamp - self.pruned >= PRUNE_INTERVAL {
+            self.expire(msg.timestamp);
+        }
+        let icao24 = msg.message.as_ref()?.icao24()?;
+        let state = self
+            .states
+            .entry(icao24)
+            .or_insert_with(|| StateVector::new(icao24, msg.timestamp));
+        state.update(msg);
+        Some(state)
+    }
+
+    /// Remove and return the aircraft silent for longer than the expiry delay
+    pub fn expire(&mut self, now: Timestamp) -> Vec<StateVector> {
+        self.pruned = now;
+        let Some(expire) = self.expire else {
+            return vec![];
+        };
+        let (expired, states) = std::mem::take(&mut self.states)
+            .into_iter()
+            .partition(|(_, state)| now - state.lastseen > expire);
+        self.states = states;
+        expired.into_values().collect()
+    }
+
+    /// The state vector of an aircraft
+    pub fn get(&self, icao24: &ICAO) -> Option<&StateVector> {
+        self.states.get(icao24)
+    }
+
+    /// The state vectors of all the aircraft, by address
+    pub fn iter(&self) -> impl Iterator<Item = &StateVector> {
+        self.states.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::cpr::{decode_positions, Position};
+    use crate::testing::timed;
+    use hexlit::hex;
+
+    #[test]
+    fn test_tracker() {
+        let mut messages = vec![
+            // Identification
+            timed(&hex!("8d406b902015a678d4d220aa4bda"), 1.0),
+            // Airborne positions (even and odd)
+            timed(&hex!("8d40058b58c901375147efd09357"), 2.0),
+            timed(&hex!("8d40058b58c904a87f402d3b8c59"), 3.0),
+            // Airborne velocity
+            timed(&hex!("8d485020994409940838175b284f"), 4.0),
+            // Comm-B reply (BDS 5,0) and surveillance reply (squawk)
+            timed(&hex!("a000139381951536e024d4ccf6b5"), 5.0),
+            timed(&hex!("2a00516d492b80"), 6.0),
+        ];
+        let reference = Position {
+            latitude: 49.,
+            longitude: 6.,
+        };
+        decode_positions(&mut messages, Some(reference), &None, false);
+
+        let mut tracker =
+            StateVectorTracker::new(Some(Duration::from_secs(60)));
+        for msg in &messages {
+            tracker.update(msg);
+        }
+        assert_eq!(tracker.len(), 5);
+
+        let sv = tracker.get(&"406b90".parse().unwrap()).unwrap();
+        assert_eq!(sv.state.callsign.as_deref(), Some("EZY85MH"));
+        assert!(sv.state.category.is_some());
+        assert_eq!(sv.count, 1);
+
+        let sv = tracker.get(&"40058b".parse().unwrap()).unwrap();
+        assert_eq!(sv.state.on_ground, Some(false));
+        assert_eq!(sv.state.altitude, Some(39000));
+        assert!(sv.state.latitude.is_some() && sv.state.longitude.is_some());
+        assert_eq!(sv.firstseen, Timestamp::from_secs(2));
+        assert_eq!(sv.lastseen, Timestamp::from_secs(3));
+
+        let sv = tracker.get(&"485020".parse().unwrap()).unwrap();
+        assert_eq!(sv.state.vertical_rate, Some(-832));
+        assert!(sv.state.groundspeed.is_some() && sv.state.track.is_some());
+
+        let sv = tracker.get(&"3c4dd2".parse().unwrap()).unwrap();
+        assert_eq!(sv.state.groundspeed, Some(438.));
+        assert!(sv.state.bds50.is_some());
+        let json = serde_json::to_value(sv).unwrap();
+        assert_eq!(json["icao24"], "3c4dd2");
+        assert_eq!(json["groundspeed"], 438.);
+        assert_eq!(json["bds50"]["groundspeed"], 438);
+
+        let sv = tracker.get(&"510af9".parse().unwrap()).unwrap();
+        assert_eq!(sv.state.squawk.unwrap().to_string(), "0356");
+
+        // Silent aircraft are forgotten
+        let expired = tracker.expire(Timestamp::from_secs(64));
+        assert_eq!(expired.len(), 2);
+        assert!(tracker
+            .iter()
+            .all(|sv| sv.lastseen >= Timestamp::from_secs(4)));
+        tracker.expire(Timestamp::from_secs(1000));
+        assert!(tracker.is_empty());
+    }
+}
@@ -1,11 +1,13 @@
 from __future__ import annotations
 
+import asyncio
 import pickle
-from typing import Iterable, Sequence, TypeVar, overload
+from typing import Any, AsyncIterator, Iterable, Sequence, TypeVar, overload
 
 import pandas as pd  # type: ignore
 
 from ._rust import (
+    BeastStream,
     aircraft_information,
     decode_1090,
     decode_1090_vec,
@@ -20,6 +22,8 @@ from ._rust import (
     decode_bds21,
     decode_bds30,
     decode_bds40,
+    decode_bds41,
+    decode_bds42,
     decode_bds44,
     decode_bds45,
     decode_bds50,
@@ -40,6 +44,8 @@ from .stubs import (
     is_bds20,
     is_bds30,
     is_bds40,
+    is_bds41,
+    is_bds42,
     is_bds44,
     is_bds50,
     is_bds60,
@@ -91,6 +97,8 @@ decode_bds20 = unpickle_fun(decode_bds20)
 decode_bds21 = unpickle_fun(decode_bds21)
 decode_bds30 = unpickle_fun(decode_bds30)
 decode_bds40 = unpickle_fun(decode_bds40)
+decode_bds41 = unpickle_fun(decode_bds41)
+decode_bds42 = unpickle_fun(decode_bds42)
 decode_bds44 = unpickle_fun(decode_bds44)
 decode_bds45 = unpickle_fun(decode_bds45)
 decode_bds50 = unpickle_fun(decode_bds50)
@@ -99,6 +107,7 @@ decode_bds65 = unpickle_fun(decode_bds65)
 
 
 __all__ = [
+    "BeastStream",
     "Flarm",
     "Message",
     "batched",
@@ -110,6 +119,8 @@ __all__ = [
     "decode_bds21",
     "decode_bds30",
     "decode_bds40",
+    "decode_bds41",
+    "decode_bds42",
     "decode_bds44",
     "decode_bds45",
     "decode_bds50",
@@ -124,6 +135,8 @@ __all__ = [
     "is_bds20",
     "is_bds30",
     "is_bds40",
+    "is_bds41",
+    "is_bds42",
     "is_bds44",
     "is_bds50",
     "is_bds60",
@@ -140,6 +153,8 @@ __all__ = [
     "is_df4",
     "is_df5",
     "aircraft_information",
+    "state_vectors",
+    "stream",
 ]
 
 
@@ -250,3 +265,48 @@ def flarm(
         payload = decode_flarm_vec(batches, t, reflat, reflon)
 
     return pickle.loads(bytes(payload))  # type: ignore
+
+
+async def stream(
+    address: str,
+    *,
+    reference: None | tuple[float, float] = None,
+) -> AsyncIterator[Message]:
+    """Decode messages live from a Beast source.
+
+    The address is formatted as tcp://host:port, udp://host:port or
+    ws://host:port/path. Positions are decoded on the fly; a reference
+    position is useful for surface messages.
+
+    >>> async for msg in rs1090.stream("tcp://localhost:10003"):
+    ...     print(msg)
+    """
+    source = BeastStream(address, reference)
+    # Messages are pushed into the queue from the Rust runtime
+    queue: asyncio.Queue[None | bytes | Exception] = asyncio.Queue()
+    source.subscribe(asyncio.get_running_loop(), queue)
+    try:
+        while True:
+            payload = await queue.get()
+            if payload is None:
+                break
+            if isinstance(payload, Exception):
+                raise payload
+            yield pickle.loads(payload)
+    finally:
+        source.close()
+
+
+def state_vectors(source: BeastStream) -> list[dict[str, Any]]:
+    """The state vectors of the aircraft received by a Beast source.
+
+    Messages are merged into one state vector per aircraft (callsign,
+    squawk code, position, altitude, speeds, etc.), as in the /all endpoint
+    of jet1090. Aircraft silent for ten minutes are forgotten.
+
+    >>> source = BeastStream("tcp://localhost:10003")
+    >>> for _ in range(1000):
+    ...     source.next()
+    >>> rs1090.state_vectors(source)
+    """
+    return pickle.loads(bytes(source.state_vectors()))  # type: ignore
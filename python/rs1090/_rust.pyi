@@ -1,3 +1,4 @@
+import asyncio
 from typing import Sequence
 
 from .stubs import DF17_BDS05, DF17_BDS65, DF20, DF21
@@ -33,8 +34,23 @@ def decode_bds20(mgs: str) -> DF20 | DF21: ...
 def decode_bds21(mgs: str) -> DF20 | DF21: ...
 def decode_bds30(mgs: str) -> DF20 | DF21: ...
 def decode_bds40(mgs: str) -> DF20 | DF21: ...
+def decode_bds41(mgs: str) -> DF20 | DF21: ...
+def decode_bds42(mgs: str) -> DF20 | DF21: ...
 def decode_bds44(mgs: str) -> DF20 | DF21: ...
 def decode_bds45(mgs: str) -> DF20 | DF21: ...
 def decode_bds50(mgs: str) -> DF20 | DF21: ...
 def decode_bds60(mgs: str) -> DF20 | DF21: ...
 def decode_bds65(mgs: str) -> DF17_BDS65: ...
+
+class BeastStream:
+    def __init__(
+        self, address: str, reference: None | tuple[float, float] = None
+    ) -> None: ...
+    def next(self) -> None | list[int]: ...
+    def subscribe(
+        self,
+        event_loop: asyncio.AbstractEventLoop,
+        queue: asyncio.Queue[None | bytes | Exception],
+    ) -> None: ...
+    def state_vectors(self) -> list[int]: ...
+    def close(self) -> None: ...
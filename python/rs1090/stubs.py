@@ -108,6 +108,18 @@ class BDS40(TypedDict):
     ]
 
 
+class BDS41(TypedDict):
+    bds: Literal["41"]
+    waypoint: str
+
+
+class BDS42(TypedDict):
+    bds: Literal["42"]
+    latitude: float
+    longitude: float
+    altitude: NotRequired[int]
+
+
 class BDS44(TypedDict):
     bds: Literal["44"]
     issued_ra: bool
@@ -185,6 +197,7 @@ class DF16(TypedDict):
     sl: int
     ri: int
     altitude: int
+    acas: NotRequired[BDS30]
     icao24: str
 
 
@@ -324,6 +337,28 @@ class DF17_BDS65(TypedDict):
     # Surveillance Integrity Level (SIL)
     SIL: int
     SILs: int
+    # Capability classes (airborne)
+    ACAS: NotRequired[bool]
+    CDTI: NotRequired[bool]
+    ARV: NotRequired[bool]
+    TS: NotRequired[bool]
+    TC: NotRequired[int]
+    # Capability classes (surface)
+    POA: NotRequired[bool]
+    GRND: NotRequired[bool]
+    NACv: NotRequired[int]
+    NICc: NotRequired[int]
+    # Length/width codes and GPS antenna offset (surface)
+    LW: NotRequired[int]
+    GPSA: NotRequired[int]
+    UATin: bool
+    # Operational modes
+    RA: bool
+    IDENT: bool
+    ATC: bool
+    SAF: bool
+    # System Design Assurance (SDA)
+    SDA: int
 
 
 class DF17_Unknown(TypedDict):
@@ -430,6 +465,28 @@ class DF18_BDS65(TypedDict):
     # Surveillance Integrity Level (SIL)
     SIL: int
     SILs: int
+    # Capability classes (airborne)
+    ACAS: NotRequired[bool]
+    CDTI: NotRequired[bool]
+    ARV: NotRequired[bool]
+    TS: NotRequired[bool]
+    TC: NotRequired[int]
+    # Capability classes (surface)
+    POA: NotRequired[bool]
+    GRND: NotRequired[bool]
+    NACv: NotRequired[int]
+    NICc: NotRequired[int]
+    # Length/width codes and GPS antenna offset (surface)
+    LW: NotRequired[int]
+    GPSA: NotRequired[int]
+    UATin: bool
+    # Operational modes
+    RA: bool
+    IDENT: bool
+    ATC: bool
+    SAF: bool
+    # System Design Assurance (SDA)
+    SDA: int
 
 
 class DF18_Unknown(TypedDict):
@@ -449,6 +506,34 @@ class DF18_Unknown(TypedDict):
     bds: Literal["?"]
 
 
+class DF18_Coarse(TypedDict):
+    timestamp: float
+    df: Literal["18"]
+    tisb: Literal["TISB_COARSE"]
+    icao24: str
+    # ICAO/Mode A Flag: true if the address is not an ICAO 24-bit address
+    imf: bool
+    # Service Volume Identifier of the ground station
+    svid: int
+    altitude: None | int
+    track: NotRequired[float]
+    groundspeed: NotRequired[int]
+    parity: Literal["odd", "even"]
+    lat_cpr: int
+    lon_cpr: int
+    latitude: NotRequired[float]
+    longitude: NotRequired[float]
+
+
+class DF18_Management(TypedDict):
+    timestamp: float
+    df: Literal["18"]
+    tisb: Literal["TISB_MANAGE", "Reserved"]
+    icao24: str
+    # raw content of the ME field, as an hexadecimal string
+    management: str
+
+
 class DF20(TypedDict):
     timestamp: float
     df: Literal["20"]
@@ -463,6 +548,8 @@ class DF20(TypedDict):
     bds21: None | BDS21
     bds30: None | BDS30
     bds40: None | BDS40
+    bds41: None | BDS41
+    bds42: None | BDS42
     bds44: None | BDS44
     bds45: None | BDS45
     bds50: None | BDS50
@@ -482,6 +569,8 @@ class DF21(TypedDict):
     bds21: None | BDS21
     bds30: None | BDS30
     bds40: None | BDS40
+    bds41: None | BDS41
+    bds42: None | BDS42
     bds44: None | BDS44
     bds45: None | BDS45
     bds50: None | BDS50
@@ -504,6 +593,8 @@ DF18 = Union[
     DF18_BDS06,
     DF18_BDS08,
     DF18_BDS65,
+    DF18_Coarse,
+    DF18_Management,
 ]
 
 
@@ -590,6 +681,14 @@ def is_bds40(message: DF20 | DF21) -> bool:
     return message.get("bds", None) == "40" or "bds40" in message
 
 
+def is_bds41(message: DF20 | DF21) -> bool:
+    return message.get("bds", None) == "41" or "bds41" in message
+
+
+def is_bds42(message: DF20 | DF21) -> bool:
+    return message.get("bds", None) == "42" or "bds42" in message
+
+
 def is_bds44(message: DF20 | DF21) -> bool:
     return message.get("bds", None) == "44" or "bds44" in message
 
@@ -1,13 +1,14 @@
 #![allow(rustdoc::missing_crate_level_docs)]
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use pyo3::exceptions::{PyAssertionError, PyValueError};
+use pyo3::exceptions::{PyAssertionError, PyConnectionError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
 use rayon::prelude::*;
-use regex::Regex;
-use rs1090::data::patterns::PATTERNS;
-use rs1090::data::tail::tail;
+use rs1090::data::patterns;
 use rs1090::decode::bds::bds05::AirbornePosition;
 use rs1090::decode::bds::bds10::DataLinkCapability;
 use rs1090::decode::bds::bds17::CommonUsageGICBCapabilityReport;
@@ -17,17 +18,24 @@ use rs1090::decode::bds::bds20::AircraftIdentification;
 use rs1090::decode::bds::bds21::AircraftAndAirlineRegistrationMarkings;
 use rs1090::decode::bds::bds30::ACASResolutionAdvisory;
 use rs1090::decode::bds::bds40::SelectedVerticalIntention;
+use rs1090::decode::bds::bds41::NextWaypointIdentifier;
+use rs1090::decode::bds::bds42::NextWaypointPosition;
 use rs1090::decode::bds::bds44::MeteorologicalRoutineAirReport;
 use rs1090::decode::bds::bds45::MeteorologicalHazardReport;
 use rs1090::decode::bds::bds50::TrackAndTurnReport;
 use rs1090::decode::bds::bds60::HeadingAndSpeedReport;
 use rs1090::decode::bds::bds65::AircraftOperationStatus;
 use rs1090::decode::cpr::{
-    airborne_position_with_reference, decode_positions,
-    surface_position_with_reference, Position,
+    airborne_position_with_reference, coarse_position_with_reference,
+    decode_position, decode_positions, decode_tisb_position,
+    surface_position_with_reference, AircraftState, Position,
 };
 use rs1090::decode::flarm::Flarm;
 use rs1090::prelude::*;
+use rs1090::source::beast::{self, BeastSource};
+use rs1090::tracker::StateVectorTracker;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
 
 #[pyfunction]
 fn decode_1090(msg: String) -> PyResult<Vec<u8>> {
@@ -67,6 +75,26 @@ fn decode_message_with_reference(me: &mut ME, reference: [f64; 2]) {
     }
 }
 
+fn decode_tisb_with_reference(me: &mut TisBMessage, reference: [f64; 2]) {
+    let [latitude_ref, longitude_ref] = reference;
+    match me {
+        TisBMessage::ExtendedSquitter(me) => {
+            decode_message_with_reference(me, reference)
+        }
+        TisBMessage::Coarse(coarse) => {
+            if let Some(pos) = coarse_position_with_reference(
+                coarse,
+                latitude_ref,
+                longitude_ref,
+            ) {
+                coarse.latitude = Some(pos.latitude);
+                coarse.longitude = Some(pos.longitude);
+            }
+        }
+        TisBMessage::Management(_) => (),
+    }
+}
+
 #[pyfunction]
 fn decode_1090_with_reference(
     msg: String,
@@ -76,7 +104,7 @@ fn decode_1090_with_reference(
     if let Ok((_, mut msg)) = Message::from_bytes((&bytes, 0)) {
         match &mut msg.df {
             ExtendedSquitterTisB { cf, .. } => {
-                decode_message_with_reference(&mut cf.me, reference)
+                decode_tisb_with_reference(&mut cf.me, reference)
             }
             ExtendedSquitterADSB(adsb) => {
                 decode_message_with_reference(&mut adsb.message, reference)
@@ -219,6 +247,30 @@ fn decode_bds40(msg: String) -> PyResult<Vec<u8>> {
     }
 }
 
+#[pyfunction]
+fn decode_bds41(msg: String) -> PyResult<Vec<u8>> {
+    let bytes = hex::decode(msg).unwrap();
+    match NextWaypointIdentifier::from_bytes((&bytes[4..], 0)) {
+        Ok((_, msg)) => {
+            let pkl = serde_pickle::to_vec(&msg, Default::default()).unwrap();
+            Ok(pkl)
+        }
+        Err(e) => Err(DecodeError(e).into()),
+    }
+}
+
+#[pyfunction]
+fn decode_bds42(msg: String) -> PyResult<Vec<u8>> {
+    let bytes = hex::decode(msg).unwrap();
+    match NextWaypointPosition::from_bytes((&bytes[4..], 0)) {
+        Ok((_, msg)) => {
+            let pkl = serde_pickle::to_vec(&msg, Default::default()).unwrap();
+            Ok(pkl)
+        }
+        Err(e) => Err(DecodeError(e).into()),
+    }
+}
+
 #[pyfunction]
 fn decode_bds44(msg: String) -> PyResult<Vec<u8>> {
     let bytes = hex::decode(msg).unwrap();
@@ -328,7 +380,7 @@ fn decode_1090t_vec(
                     let bytes = hex::decode(msg).unwrap();
                     if let Ok((_, message)) = Message::from_bytes((&bytes, 0)) {
                         Some(TimedMessage {
-                            timestamp,
+                            timestamp: Timestamp::from_secs_f64(timestamp),
                             frame: bytes,
                             message: Some(message),
                             metadata: vec![],
@@ -347,7 +399,7 @@ fn decode_1090t_vec(
         latitude,
         longitude,
     });
-    decode_positions(&mut res, position, &None);
+    decode_positions(&mut res, position, &None, false);
 
     let pkl = serde_pickle::to_vec(&res, Default::default()).unwrap();
     Ok(pkl)
@@ -397,13 +449,7 @@ fn decode_flarm_vec(
                 .zip(reference)
                 .filter_map(|((msg, timestamp), reference)| {
                     let bytes = hex::decode(msg).unwrap();
-                    if let Ok(flarm) =
-                        Flarm::from_record(timestamp, &reference, &bytes)
-                    {
-                        Some(flarm)
-                    } else {
-                        None
-                    }
+                    Flarm::from_record(timestamp, &reference, &bytes).ok()
                 })
                 .collect()
         })
@@ -420,65 +466,228 @@ fn aircraft_information(
     icao24: &str,
     registration: Option<&str>,
 ) -> PyResult<HashMap<String, String>> {
-    let mut reg = HashMap::<String, String>::new();
     let hexid = u32::from_str_radix(icao24, 16)?;
+    let info = patterns::aircraft_information(hexid, registration);
 
+    let mut reg = HashMap::<String, String>::new();
     reg.insert("icao24".to_string(), icao24.to_lowercase());
+    reg.insert("country".to_string(), info.country);
+    reg.insert("flag".to_string(), info.flag);
+    reg.insert("allocation".to_string(), info.allocation.to_string());
+    for (key, value) in [
+        ("registration", info.registration),
+        ("pattern", info.pattern),
+        ("category", info.category),
+        ("comment", info.comment),
+    ] {
+        if let Some(value) = value {
+            reg.insert(key.to_string(), value);
+        }
+    }
+    Ok(reg)
+}
+
+/// The positions and state vectors decoded along a live feed
+struct LiveDecoder {
+    aircraft: BTreeMap<ICAO, AircraftState>,
+    reference: Option<Position>,
+    /// The state vectors of the aircraft heard in the last ten minutes
+    tracker: StateVectorTracker,
+}
 
-    if let Some(tail) = tail(hexid) {
-        reg.insert("registration".to_string(), tail);
+impl LiveDecoder {
+    /// Decode a message (with its position), None if it cannot be decoded
+    fn decode(&mut self, mut msg: TimedMessage) -> Option<Vec<u8>> {
+        let (_, mut message) = Message::from_bytes((&msg.frame, 0)).ok()?;
+        let timestamp = msg.timestamp.as_secs_f64();
+        match &mut message.df {
+            ExtendedSquitterADSB(adsb) => match adsb.message {
+                ME::BDS05(_) | ME::BDS06(_) => decode_position(
+                    &mut adsb.message,
+                    timestamp,
+                    &adsb.icao24,
+                    &mut self.aircraft,
+                    &mut self.reference,
+                    &None,
+                ),
+                _ => {}
+            },
+            ExtendedSquitterTisB { cf, .. } => match cf.me {
+                TisBMessage::ExtendedSquitter(ME::BDS05(_) | ME::BDS06(_))
+                | TisBMessage::Coarse(_) => decode_tisb_position(
+                    cf,
+                    timestamp,
+                    &mut self.aircraft,
+                    &mut self.reference,
+                    &None,
+                ),
+                _ => {}
+            },
+            _ => {}
+        }
+        msg.message = Some(message);
+        self.tracker.update(&msg);
+        Some(serde_pickle::to_vec(&msg, Default::default()).unwrap())
     }
-    if let Some(tail) = registration {
-        reg.insert("registration".to_string(), tail.to_string());
+}
+
+/**
+ * A live feed of decoded messages from a Beast source
+ *
+ * Messages are either read one by one with `next()`, or pushed by the
+ * runtime of the feed into an asyncio queue with `subscribe()`, so that
+ * no thread of the event loop waits for them.
+ */
+#[pyclass]
+struct BeastStream {
+    runtime: Mutex<Option<Runtime>>,
+    rx: Mutex<Option<mpsc::Receiver<TimedMessage>>>,
+    error: Arc<Mutex<Option<String>>>,
+    decoder: Arc<Mutex<LiveDecoder>>,
+}
+
+#[pymethods]
+impl BeastStream {
+    #[new]
+    #[pyo3(signature = (address, reference=None))]
+    fn new(address: String, reference: Option<[f64; 2]>) -> PyResult<Self> {
+        let source = match address.split_once("://") {
+            Some(("tcp", addr)) => BeastSource::Tcp(addr.to_string()),
+            Some(("udp", addr)) => BeastSource::Udp(addr.to_string()),
+            Some(("ws" | "wss", _)) => BeastSource::Websocket(address),
+            _ => {
+                let msg = format!(
+                    "Invalid address {}, expected tcp://, udp:// or ws://",
+                    address
+                );
+                return Err(PyValueError::new_err(msg));
+            }
+        };
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let (tx, rx) = mpsc::channel(100);
+        let error = Arc::new(Mutex::new(None));
+        let error_copy = error.clone();
+        runtime.spawn(async move {
+            if let Err(e) = beast::receiver(
+                source,
+                tx,
+                0,
+                None,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Default::default(),
+            )
+            .await
+            {
+                *error_copy.lock().unwrap() = Some(e.to_string());
+            }
+        });
+
+        let decoder = LiveDecoder {
+            aircraft: BTreeMap::new(),
+            reference: reference.map(|[latitude, longitude]| Position {
+                latitude,
+                longitude,
+            }),
+            tracker: StateVectorTracker::new(Some(Duration::from_secs(600))),
+        };
+        Ok(BeastStream {
+            runtime: Mutex::new(Some(runtime)),
+            rx: Mutex::new(Some(rx)),
+            error,
+            decoder: Arc::new(Mutex::new(decoder)),
+        })
     }
 
-    if let Some(pattern) = &PATTERNS.registers.iter().find(|elt| {
-        if let Some(start) = &elt.start {
-            if let Some(end) = &elt.end {
-                let start = u32::from_str_radix(&start[2..], 16).unwrap();
-                let end = u32::from_str_radix(&end[2..], 16).unwrap();
-                return (hexid >= start) & (hexid <= end);
+    /// Wait for the next decoded message, None when the stream is closed
+    fn next(&self, py: Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        loop {
+            let msg =
+                py.allow_threads(|| match &mut *self.rx.lock().unwrap() {
+                    Some(rx) => rx.blocking_recv(),
+                    None => None,
+                });
+            let Some(msg) = msg else {
+                return match self.error.lock().unwrap().take() {
+                    Some(e) => Err(PyConnectionError::new_err(e)),
+                    None => Ok(None),
+                };
+            };
+            if let Some(pkl) = self.decoder.lock().unwrap().decode(msg) {
+                return Ok(Some(pkl));
             }
         }
-        false
-    }) {
-        reg.insert("country".to_string(), pattern.country.to_string());
-        reg.insert("flag".to_string(), pattern.flag.to_string());
-        if let Some(p) = &pattern.pattern {
-            reg.insert("pattern".to_string(), p.to_string());
-        }
-        if let Some(comment) = &pattern.comment {
-            reg.insert("comment".to_string(), comment.to_string());
-        }
+    }
 
-        if let Some(tail) = reg.get("registration") {
-            if let Some(categories) = &pattern.categories {
-                if let Some(cat) = categories.iter().find(|elt| {
-                    let re = Regex::new(&elt.pattern).unwrap();
-                    re.is_match(tail)
-                }) {
-                    reg.insert("pattern".to_string(), cat.pattern.to_string());
-                    if let Some(category) = &cat.category {
-                        reg.insert(
-                            "category".to_string(),
-                            category.to_string(),
-                        );
-                    }
-                    if let Some(country) = &cat.country {
-                        reg.insert("country".to_string(), country.to_string());
-                    }
-                    if let Some(flag) = &cat.flag {
-                        reg.insert("flag".to_string(), flag.to_string());
-                    }
+    /**
+     * Push the decoded messages (pickled) into an asyncio queue, from the
+     * runtime of the feed, through the `call_soon_threadsafe` method of the
+     * event loop. None is pushed when the stream is closed, or the
+     * connection error.
+     */
+    fn subscribe(&self, event_loop: PyObject, queue: PyObject) -> PyResult<()> {
+        let Some(mut rx) = self.rx.lock().unwrap().take() else {
+            return Err(PyValueError::new_err("Stream already consumed"));
+        };
+        let runtime = self.runtime.lock().unwrap();
+        let Some(runtime) = runtime.as_ref() else {
+            return Err(PyValueError::new_err("Stream closed"));
+        };
+        let decoder = self.decoder.clone();
+        let error = self.error.clone();
+        // Push an item into the queue, false if the event loop is closed
+        let push = move |item: Option<PyObject>| {
+            Python::with_gil(|py| {
+                let put = queue.getattr(py, "put_nowait")?;
+                event_loop.call_method1(
+                    py,
+                    "call_soon_threadsafe",
+                    (put, item.unwrap_or_else(|| py.None())),
+                )
+            })
+            .is_ok()
+        };
+        runtime.spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                let Some(pkl) = decoder.lock().unwrap().decode(msg) else {
+                    continue;
+                };
+                let item = Python::with_gil(|py| {
+                    PyBytes::new(py, &pkl).into_any().unbind()
+                });
+                if !push(Some(item)) {
+                    return;
                 }
             }
-        }
-    } else {
-        reg.insert("country".to_string(), "Unknown".to_string());
-        reg.insert("flag".to_string(), "🏳".to_string());
+            let item = error.lock().unwrap().take().map(|e| {
+                Python::with_gil(|py| {
+                    PyConnectionError::new_err(e).into_value(py).into_any()
+                })
+            });
+            push(item);
+        });
+        Ok(())
     }
 
-    Ok(reg)
+    /// The state vectors of the aircraft, merged from the messages received
+    fn state_vectors(&self) -> PyResult<Vec<u8>> {
+        let decoder = self.decoder.lock().unwrap();
+        let states: Vec<_> = decoder.tracker.iter().collect();
+        let pkl = serde_pickle::to_vec(&states, Default::default()).unwrap();
+        Ok(pkl)
+    }
+
+    /// Stop receiving messages
+    fn close(&self) {
+        if let Some(runtime) = self.runtime.lock().unwrap().take() {
+            runtime.shutdown_background();
+        }
+    }
 }
 
 /// A Python module implemented in Rust.
@@ -502,6 +711,8 @@ fn _rust(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(decode_bds21, m)?)?;
     m.add_function(wrap_pyfunction!(decode_bds30, m)?)?;
     m.add_function(wrap_pyfunction!(decode_bds40, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_bds41, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_bds42, m)?)?;
     m.add_function(wrap_pyfunction!(decode_bds44, m)?)?;
     m.add_function(wrap_pyfunction!(decode_bds45, m)?)?;
     m.add_function(wrap_pyfunction!(decode_bds50, m)?)?;
@@ -511,5 +722,8 @@ fn _rust(m: &Bound<'_, PyModule>) -> PyResult<()> {
     // icao24 functions
     m.add_function(wrap_pyfunction!(aircraft_information, m)?)?;
 
+    // Live sources
+    m.add_class::<BeastStream>()?;
+
     Ok(())
 }
@@ -9,4 +9,5 @@ def test_info() -> None:
         "pattern": "^F-",
         "icao24": "39b415",
         "flag": "🇫🇷",
+        "allocation": "civil",
     }
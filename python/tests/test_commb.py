@@ -26,6 +26,24 @@ def test_bds40() -> None:
     assert bds40["barometric_setting"] == 1020
 
 
+def test_bds41() -> None:
+    msg = rs1090.decode("A00018389C0A4097041040000000")
+    assert rs1090.is_df20(msg)
+    bds41 = msg["bds41"]
+    assert bds41 is not None
+    assert bds41["waypoint"] == "NARAK"
+
+
+def test_bds42() -> None:
+    msg = rs1090.decode("A00018388F833807C88271000000")
+    assert rs1090.is_df20(msg)
+    bds42 = msg["bds42"]
+    assert bds42 is not None
+    assert bds42["latitude"] == approx(43.6289, rel=1e-4)
+    assert bds42["longitude"] == approx(1.3678, rel=1e-3)
+    assert bds42["altitude"] == 5000
+
+
 def test_bds50() -> None:
     msg = rs1090.decode("A000139381951536E024D4CCF6B5")
     assert rs1090.is_df20(msg)
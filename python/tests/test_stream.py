@@ -0,0 +1,51 @@
+import asyncio
+
+import rs1090
+
+# A DF17 identification message, as sent by the Beast server below
+FRAME = bytes.fromhex("8d406b902015a678d4d220aa4bda")
+
+
+def beast(frame: bytes) -> bytes:
+    # Mode S long frame: 6 bytes of timestamp, 1 byte of signal level
+    payload = bytes(6) + b"\x80" + frame
+    return b"\x1a3" + payload.replace(b"\x1a", b"\x1a\x1a")
+
+
+async def collect(count: int) -> list[rs1090.Message]:
+    async def serve(
+        reader: asyncio.StreamReader, writer: asyncio.StreamWriter
+    ) -> None:
+        writer.write(beast(FRAME) * count)
+        await writer.drain()
+        writer.close()
+
+    server = await asyncio.start_server(serve, "127.0.0.1", 0)
+    port = server.sockets[0].getsockname()[1]
+    messages = []
+    async with server:
+        async for msg in rs1090.stream(f"tcp://127.0.0.1:{port}"):
+            messages.append(msg)
+            if len(messages) == count:
+                break
+    return messages
+
+
+def test_stream() -> None:
+    messages = asyncio.run(asyncio.wait_for(collect(3), timeout=10))
+    assert len(messages) == 3
+    assert all(msg["icao24"] == "406b90" for msg in messages)
+    assert messages[0]["callsign"] == "EZY85MH"
+
+
+def test_stream_invalid_address() -> None:
+    async def run() -> None:
+        async for _ in rs1090.stream("localhost:10003"):
+            pass
+
+    try:
+        asyncio.run(run())
+    except ValueError:
+        pass
+    else:
+        raise AssertionError("the address should be rejected")